@@ -1,4 +1,3 @@
-use tokio;
 use criterion::{criterion_group, criterion_main, Criterion};
 use credit_card::CreditCard;
 use data_vault::{RedisDataVault, DataVault, PostgresDataVault};
@@ -19,7 +18,7 @@ async fn store_retrieve_credit_card_redis() {
     };
 
     let token = vault.store_credit_card(&cc).await.unwrap();
-    let credit_card = vault.retrieve_credit_card(&token.to_string()).await.unwrap();
+    let credit_card = vault.retrieve_credit_card(&token).await.unwrap();
     assert_eq!(credit_card.number, cc.number)
 }
 
@@ -45,9 +44,9 @@ async fn retrieve_credit_card_redis() {
     let vault = RedisDataVault::<AesGcmSivEncryption,Blake3Tokenizer>::new().unwrap();
 
     let token = "token";
-    let mut credit_card = vault.retrieve_credit_card(&token.to_string()).await.unwrap();
+    let mut credit_card = vault.try_retrieve_credit_card(token).await.unwrap().unwrap_or_default();
 
-    if credit_card.number.len() == 0 {
+    if credit_card.number.is_empty() {
         let cc = CreditCard {
             number: "4111111111111111".to_string(),
             cardholder_name: "Graydon Hoare".to_string(),
@@ -58,8 +57,8 @@ async fn retrieve_credit_card_redis() {
         };
 
         let credit_card_json = serde_json::to_string(&cc).unwrap();
-        let _ = vault.store(&token.to_string(), &credit_card_json).await.unwrap();
-        credit_card = vault.retrieve_credit_card(&token.to_string()).await.unwrap();
+        vault.store(token, &credit_card_json).await.unwrap();
+        credit_card = vault.retrieve_credit_card(token).await.unwrap();
     }
 
     assert_eq!(credit_card.number, "4111111111111111".to_string())
@@ -79,7 +78,7 @@ async fn store_retrieve_credit_card_postgres() {
     };
 
     let token = vault.store_credit_card(&cc).await.unwrap();
-    let credit_card = vault.retrieve_credit_card(&token.to_string()).await.unwrap();
+    let credit_card = vault.retrieve_credit_card(&token).await.unwrap();
     assert_eq!(credit_card.number, cc.number)
 }
 
@@ -105,9 +104,9 @@ async fn retrieve_credit_card_postgres() {
     let vault = PostgresDataVault::<AesGcmSivEncryption,Blake3Tokenizer>::new().unwrap();
 
     let token = "token";
-    let mut credit_card = vault.retrieve_credit_card(&token.to_string()).await.unwrap();
+    let mut credit_card = vault.try_retrieve_credit_card(token).await.unwrap().unwrap_or_default();
 
-    if credit_card.number.len() == 0 {
+    if credit_card.number.is_empty() {
         let cc = CreditCard {
             number: "4111111111111111".to_string(),
             cardholder_name: "Graydon Hoare".to_string(),
@@ -118,8 +117,8 @@ async fn retrieve_credit_card_postgres() {
         };
 
         let credit_card_json = serde_json::to_string(&cc).unwrap();
-        let _ = vault.store(&token.to_string(), &credit_card_json).await.unwrap();
-        credit_card = vault.retrieve_credit_card(&token.to_string()).await.unwrap();
+        vault.store(token, &credit_card_json).await.unwrap();
+        credit_card = vault.retrieve_credit_card(token).await.unwrap();
     }
 
     assert_eq!(credit_card.number, "4111111111111111".to_string())
@@ -127,28 +126,28 @@ async fn retrieve_credit_card_postgres() {
 
 // redis
 fn criterion_store_credit_card_redis(c: &mut Criterion) {
-    c.bench_function("store_redis", |b| b.iter(|| store_credit_card_redis()));
+    c.bench_function("store_redis", |b| b.iter(store_credit_card_redis));
 }
 
 fn criterion_retrieve_credit_card_redis(c: &mut Criterion) {
-    c.bench_function("retrieve_redis", |b| b.iter(|| retrieve_credit_card_redis()));
+    c.bench_function("retrieve_redis", |b| b.iter(retrieve_credit_card_redis));
 }
 
 fn criterion_store_retrieve_credit_card_redis(c: &mut Criterion) {
-    c.bench_function("store_retrieve_redis", |b| b.iter(|| store_retrieve_credit_card_redis()));
+    c.bench_function("store_retrieve_redis", |b| b.iter(store_retrieve_credit_card_redis));
 }
 
 // postgres
 fn criterion_store_credit_card_postgres(c: &mut Criterion) {
-    c.bench_function("store_postgres", |b| b.iter(|| store_credit_card_postgres()));
+    c.bench_function("store_postgres", |b| b.iter(store_credit_card_postgres));
 }
 
 fn criterion_retrieve_credit_card_postgres(c: &mut Criterion) {
-    c.bench_function("retrieve_postgres", |b| b.iter(|| retrieve_credit_card_postgres()));
+    c.bench_function("retrieve_postgres", |b| b.iter(retrieve_credit_card_postgres));
 }
 
 fn criterion_store_retrieve_credit_card_postgres(c: &mut Criterion) {
-    c.bench_function("store_retrieve_postgres", |b| b.iter(|| store_retrieve_credit_card_postgres()));
+    c.bench_function("store_retrieve_postgres", |b| b.iter(store_retrieve_credit_card_postgres));
 }
 
 
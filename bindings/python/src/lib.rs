@@ -0,0 +1,176 @@
+//! Python bindings of data_vault, built with maturin
+//!
+//! `Vault` opens the same vault as the library, from a configuration
+//! file read by `Config::from_file` or from the environment variables
+//! `RedisDataVault::new` and `PostgresDataVault::new` read, with
+//! `AesGcmSivEncryption`.  Calls block the calling thread without
+//! holding the GIL.
+//!
+//! ```python
+//! from data_vault_py import Vault, NotFoundError
+//!
+//! vault = Vault(config_file="data_vault.toml")
+//! token = vault.store({
+//!     "number": "4111111111111111",
+//!     "cardholder_name": "Graydon Hoare",
+//!     "expiration_month": "01",
+//!     "expiration_year": "2023",
+//! })
+//! card = vault.retrieve(token)
+//! vault.delete(token)
+//! ```
+//!
+//! Errors are `VaultError`, or `NotFoundError` for unknown tokens,
+//! with card numbers and tokens masked, see `redact`.
+
+use credit_card::CreditCard;
+use data_vault::{redact, Backend, Config, DataVaultDyn, DataVault, DataVaultError, PostgresDataVault, RedisDataVault, TokenizerKind};
+use data_vault::encryption::AesGcmSivEncryption;
+use data_vault::tokenizer::{Blake3DeterministicTokenizer, Blake3Tokenizer};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tokio::runtime::Runtime;
+use std::collections::HashMap;
+use std::error;
+use std::sync::Arc;
+
+create_exception!(data_vault_py, VaultError, PyException, "A vault operation failed");
+create_exception!(data_vault_py, NotFoundError, VaultError, "Nothing is stored under the token");
+
+fn vault_error(e: DataVaultError) -> PyErr {
+    let message = redact(&e.to_string()).into_owned();
+    match e {
+        DataVaultError::NotFound => NotFoundError::new_err(message),
+        _ => VaultError::new_err(message),
+    }
+}
+
+fn open_error(e: Box<dyn error::Error>) -> PyErr {
+    VaultError::new_err(redact(&e.to_string()).into_owned())
+}
+
+fn backend(name: &str) -> PyResult<Backend> {
+    match name {
+        "redis" => Ok(Backend::Redis),
+        "postgres" => Ok(Backend::Postgres),
+        _ => Err(VaultError::new_err(format!("backend {:?} is not redis or postgres", name))),
+    }
+}
+
+fn tokenizer(name: &str) -> PyResult<TokenizerKind> {
+    match name {
+        "random" => Ok(TokenizerKind::Random),
+        "deterministic" => Ok(TokenizerKind::Deterministic),
+        _ => Err(VaultError::new_err(format!("tokenizer {:?} is not random or deterministic", name))),
+    }
+}
+
+/// The vault of `config`, or of the environment without one
+fn open(config: Option<Config>, backend: Backend, tokenizer: TokenizerKind) -> Result<Arc<dyn DataVaultDyn>, Box<dyn error::Error>> {
+    let (redis, postgres) = match config {
+        Some(config) => (config.redis, config.postgres),
+        None => (None, None),
+    };
+    Ok(match (backend, tokenizer) {
+        (Backend::Redis, TokenizerKind::Random) => Arc::new(match redis {
+            Some(redis) => RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(redis)?,
+            None => RedisDataVault::new()?,
+        }),
+        (Backend::Redis, TokenizerKind::Deterministic) => Arc::new(match redis {
+            Some(redis) => RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::from_config(redis)?,
+            None => RedisDataVault::new()?,
+        }),
+        (Backend::Postgres, TokenizerKind::Random) => Arc::new(match postgres {
+            Some(postgres) => PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(postgres)?,
+            None => PostgresDataVault::new()?,
+        }),
+        (Backend::Postgres, TokenizerKind::Deterministic) => Arc::new(match postgres {
+            Some(postgres) => PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::from_config(postgres)?,
+            None => PostgresDataVault::new()?,
+        }),
+    })
+}
+
+/// A credit card vault
+///
+/// `config_file` is a TOML or YAML vault configuration, which names
+/// the backend and tokenizer.  Without one `backend` is `"redis"` or
+/// `"postgres"` and `tokenizer` `"random"` or `"deterministic"`.
+#[pyclass(module = "data_vault_py", frozen)]
+struct Vault {
+    vault: Arc<dyn DataVaultDyn>,
+    runtime: Runtime,
+}
+
+#[pymethods]
+impl Vault {
+    #[new]
+    #[pyo3(signature = (config_file = None, backend = "redis", tokenizer = "random"))]
+    fn new(py: Python<'_>, config_file: Option<&str>, backend: &str, tokenizer: &str) -> PyResult<Self> {
+        let (config, backend, tokenizer) = match config_file {
+            Some(path) => {
+                let config = Config::from_file(path).map_err(open_error)?;
+                let (backend, tokenizer) = (config.backend, config.tokenizer);
+                (Some(config), backend, tokenizer)
+            },
+            None => (None, self::backend(backend)?, self::tokenizer(tokenizer)?),
+        };
+        let runtime = Runtime::new().map_err(|e| VaultError::new_err(e.to_string()))?;
+        // the pools are bound to the runtime they are created in
+        let vault = py.detach(|| {
+            let _entered = runtime.enter();
+            open(config, backend, tokenizer).map_err(|e| e.to_string())
+        }).map_err(|e| VaultError::new_err(redact(&e).into_owned()))?;
+        Ok(Vault { vault, runtime })
+    }
+
+    /// Store a credit card, a dict of `number`, `cardholder_name`,
+    /// `expiration_month`, `expiration_year` and optionally `brand`
+    /// and `security_code`, and return its token
+    fn store(&self, py: Python<'_>, credit_card: HashMap<String, Option<String>>) -> PyResult<String> {
+        // serde names only the field in its errors, never a value
+        let credit_card: CreditCard = serde_json::to_value(credit_card)
+            .and_then(serde_json::from_value)
+            .map_err(|e| VaultError::new_err(format!("invalid credit card: {}", e)))?;
+        py.detach(|| self.runtime.block_on(self.vault.store_credit_card(&credit_card)))
+            .map_err(vault_error)
+    }
+
+    /// The credit card stored under `token`, as the dict `store` takes
+    fn retrieve<'py>(&self, py: Python<'py>, token: &str) -> PyResult<Bound<'py, PyDict>> {
+        let credit_card = py.detach(|| self.runtime.block_on(self.vault.retrieve_credit_card(token)))
+            .map_err(vault_error)?;
+        let dict = PyDict::new(py);
+        dict.set_item("number", credit_card.number)?;
+        dict.set_item("cardholder_name", credit_card.cardholder_name)?;
+        dict.set_item("expiration_month", credit_card.expiration_month)?;
+        dict.set_item("expiration_year", credit_card.expiration_year)?;
+        dict.set_item("brand", credit_card.brand)?;
+        dict.set_item("security_code", credit_card.security_code)?;
+        Ok(dict)
+    }
+
+    /// Delete the credit card stored under `token`, whether there was one
+    fn delete(&self, py: Python<'_>, token: &str) -> PyResult<bool> {
+        let tokens = [token.to_string()];
+        py.detach(|| self.runtime.block_on(self.vault.delete_many(&tokens)))
+            .map(|deleted| deleted > 0)
+            .map_err(vault_error)
+    }
+
+    /// `redis` or `postgres`
+    #[getter]
+    fn backend(&self) -> &'static str {
+        self.vault.backend()
+    }
+}
+
+#[pymodule]
+fn data_vault_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Vault>()?;
+    m.add("VaultError", m.py().get_type::<VaultError>())?;
+    m.add("NotFoundError", m.py().get_type::<NotFoundError>())?;
+    Ok(())
+}
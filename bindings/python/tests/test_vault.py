@@ -0,0 +1,39 @@
+import unittest
+
+from data_vault_py import NotFoundError, Vault, VaultError
+
+CREDIT_CARD = {
+    "number": "4111111111111111",
+    "cardholder_name": "Graydon Hoare",
+    "expiration_month": "01",
+    "expiration_year": "2023",
+}
+
+
+class VaultTest(unittest.TestCase):
+    # redis is configured by REDIS_URL and the encryption key
+    # variables, like for the tests of the crate
+    def test_store_retrieve_delete(self):
+        vault = Vault(backend="redis")
+        self.assertEqual(vault.backend, "redis")
+
+        token = vault.store(CREDIT_CARD)
+        card = vault.retrieve(token)
+        self.assertEqual(card["number"], CREDIT_CARD["number"])
+        self.assertIsNone(card["security_code"])
+
+        self.assertTrue(vault.delete(token))
+        self.assertFalse(vault.delete(token))
+        with self.assertRaises(NotFoundError):
+            vault.retrieve(token)
+
+    def test_errors(self):
+        with self.assertRaises(VaultError):
+            Vault(backend="dynamodb")
+        with self.assertRaises(VaultError) as raised:
+            Vault().store({"number": CREDIT_CARD["number"]})
+        self.assertNotIn(CREDIT_CARD["number"], str(raised.exception))
+
+
+if __name__ == "__main__":
+    unittest.main()
\ No newline at end of file
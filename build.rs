@@ -0,0 +1,44 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc::compile();
+}
+
+/// the service of `proto/data_vault.proto`, generated without protoc
+/// from the messages in `src/grpc.rs`
+#[cfg(feature = "grpc")]
+mod grpc {
+    use tonic_build::manual::{Builder, Method, Service};
+    use std::{env, fs, path::Path};
+
+    const CODEC: &str = "tonic_prost::ProstCodec";
+    const METHODS: [(&str, &str, &str); 4] = [
+        ("tokenize", "Tokenize", "Store a credit card and return its token"),
+        ("detokenize", "Detokenize", "The credit card stored under a token"),
+        ("delete", "Delete", "Remove the record of a token right away"),
+        ("exists", "Exists", "Whether a record is stored under a token"),
+    ];
+
+    pub fn compile() {
+        println!("cargo:rerun-if-changed=build.rs");
+        let mut service = Service::builder()
+            .name("DataVault")
+            .package("data_vault.v1")
+            .comment("see proto/data_vault.proto");
+        for (name, route_name, comment) in METHODS {
+            service = service.method(Method::builder()
+                .name(name)
+                .route_name(route_name)
+                .comment(comment)
+                .input_type(format!("crate::grpc::{}Request", route_name))
+                .output_type(format!("crate::grpc::{}Response", route_name))
+                .codec_path(CODEC)
+                .build());
+        }
+        Builder::new().compile(&[service.build()]);
+
+        // the client is generated for the 2021 prelude
+        let path = Path::new(&env::var("OUT_DIR").unwrap()).join("data_vault.v1.DataVault.rs");
+        let code = fs::read_to_string(&path).unwrap();
+        fs::write(&path, code.replace(" TryInto<", " std::convert::TryInto<")).unwrap();
+    }
+}
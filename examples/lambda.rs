@@ -0,0 +1,34 @@
+//! A Lambda function tokenizing and detokenizing credit cards
+//!
+//! ```text
+//! {"action": "tokenize", "credit_card": {"number": "4111111111111111", ...}}
+//! {"action": "detokenize", "token": "..."}
+//! ```
+use credit_card::CreditCard;
+use data_vault::lambda::{self, LambdaError};
+use lambda_runtime::{service_fn, LambdaEvent};
+use serde_json::{json, Value};
+
+async fn handler(event: LambdaEvent<Value>) -> Result<Value, LambdaError> {
+    // the same vault and connections for every invocation of this environment
+    let vault = lambda::vault()?;
+    match event.payload["action"].as_str() {
+        Some("tokenize") => {
+            let credit_card: CreditCard = serde_json::from_value(event.payload["credit_card"].clone())
+                .map_err(|_| "credit_card is not a credit card")?;
+            let token = vault.store_credit_card(&credit_card).await?;
+            Ok(json!({ "token": token }))
+        },
+        Some("detokenize") => {
+            let token = event.payload["token"].as_str().ok_or("token is missing")?;
+            Ok(serde_json::to_value(vault.retrieve_credit_card(token).await?)?)
+        },
+        _ => Err("action is not tokenize or detokenize".into()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), LambdaError> {
+    tokio::spawn(lambda::shutdown_on_sigterm());
+    lambda_runtime::run(service_fn(handler)).await
+}
\ No newline at end of file
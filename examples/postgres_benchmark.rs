@@ -1,5 +1,3 @@
-use log::{info};
-use tokio;
 use credit_card::CreditCard;
 use data_vault::{PostgresDataVault, DataVault};
 use std::vec;
@@ -1,5 +1,3 @@
-use log::{info};
-use tokio;
 use credit_card::CreditCard;
 use data_vault::{RedisDataVault, DataVault};
 use std::vec;
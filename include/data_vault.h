@@ -0,0 +1,65 @@
+/*
+ * C interface of data_vault, build the library with
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * Every function returns DATA_VAULT_OK or an error code, whose message
+ * data_vault_last_error copies.  A vault may be used from several
+ * threads at once.  Credit cards are JSON objects of "number",
+ * "cardholder_name", "expiration_month", "expiration_year" and
+ * optionally "brand" and "security_code".
+ */
+#ifndef DATA_VAULT_H
+#define DATA_VAULT_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DATA_VAULT_OK 0
+#define DATA_VAULT_ERROR 1
+#define DATA_VAULT_NOT_FOUND 2
+#define DATA_VAULT_INVALID_ARGUMENT 3
+#define DATA_VAULT_BUFFER_TOO_SMALL 4
+
+/* bytes a token buffer needs, the longest token and its NUL */
+#define DATA_VAULT_TOKEN_LEN 65
+
+typedef struct FfiDataVault DataVault;
+
+/*
+ * A vault from the TOML or YAML file config_file, or when it is NULL
+ * from the environment with backend "redis" or "postgres" and
+ * tokenizer "random" or "deterministic", "redis" and "random" when
+ * NULL.  NULL on failure.
+ */
+DataVault *data_vault_new(const char *config_file, const char *backend, const char *tokenizer);
+
+/* store a credit card, token_len is at least DATA_VAULT_TOKEN_LEN */
+int data_vault_store(const DataVault *vault, const char *credit_card_json, char *token, size_t token_len);
+
+/*
+ * copy the credit card stored under token as JSON, *written is its
+ * length without the NUL, also when the buffer is too small
+ */
+int data_vault_retrieve(const DataVault *vault, const char *token, char *credit_card_json, size_t credit_card_json_len, size_t *written);
+
+/* DATA_VAULT_NOT_FOUND when nothing is stored under token */
+int data_vault_delete(const DataVault *vault, const char *token);
+
+/* close the connections and free the vault, NULL is ignored */
+void data_vault_free(DataVault *vault);
+
+/*
+ * copy the message of this thread's last error, cut short to fit,
+ * and return its whole length without the NUL
+ */
+size_t data_vault_last_error(char *message, size_t message_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
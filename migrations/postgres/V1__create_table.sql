@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS {table} (
+    id bigserial NOT NULL PRIMARY KEY,
+    "token" varchar(64) NOT NULL,
+    credit_card bytea NOT NULL
+);
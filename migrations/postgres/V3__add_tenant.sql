@@ -0,0 +1,4 @@
+-- tokens are unique per tenant instead of per table
+ALTER TABLE {table} ADD COLUMN IF NOT EXISTS tenant varchar(64) NOT NULL DEFAULT '';
+CREATE UNIQUE INDEX IF NOT EXISTS "{name}_tenant_token_idx" ON {table} USING btree (tenant, token);
+DROP INDEX IF EXISTS {schema}"{name}_token_idx";
@@ -0,0 +1 @@
+ALTER TABLE {table} ADD COLUMN IF NOT EXISTS deleted_at timestamptz NULL, ADD COLUMN IF NOT EXISTS expires_at timestamptz NULL;
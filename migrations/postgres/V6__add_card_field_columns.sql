@@ -0,0 +1,7 @@
+-- NULL unless a `CardFieldLayout` keeps the field in plaintext or as a blind index
+ALTER TABLE {table}
+    ADD COLUMN IF NOT EXISTS "number" text NULL,
+    ADD COLUMN IF NOT EXISTS cardholder_name text NULL,
+    ADD COLUMN IF NOT EXISTS expiration_month text NULL,
+    ADD COLUMN IF NOT EXISTS expiration_year text NULL,
+    ADD COLUMN IF NOT EXISTS brand text NULL;
@@ -0,0 +1,311 @@
+//! Applying account updater responses, the new numbers and expiry
+//! dates card networks report for cards on file (Visa Account Updater,
+//! Mastercard Automatic Billing Updater)
+//!
+//! Cards are found by the fingerprint of their old number in the
+//! fingerprint index of the vault's namespace, so only vaults with a
+//! deterministic tokenizer can apply updates, see
+//! `DataVault::store_credit_card_deduplicated`.  Updated cards keep
+//! their token, the record is changed in place with
+//! `update_credit_card_if_version`.  Closed accounts are reported,
+//! their cards are left for the caller to delete.
+//!
+//! Responses are read from CSV with the header
+//! `fingerprint,change,number,expiration_month,expiration_year`, where
+//! `change` is `new_account`, `new_expiry` or `closed`:
+//!
+//! ```text
+//! fingerprint,change,number,expiration_month,expiration_year
+//! 5c1e…,new_account,4012888888881881,09,2030
+//! 9a07…,new_expiry,,09,2030
+//! e44b…,closed,,,
+//! ```
+//!
+//! # example
+//! ```rust,ignore
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::account_updater::{apply_account_updates, read_account_updates};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3DeterministicTokenizer;
+//! use std::fs::File;
+//!
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+//! let updates = read_account_updates(File::open("vau-response.csv")?)?;
+//! let report = apply_account_updates(&vault, &updates).await?;
+//! println!("{} updated, {} closed", report.updated.len(), report.closed.len());
+//! ```
+
+use crate::error::DataVaultError;
+use crate::fingerprint::indexed_token;
+use crate::masked::mask_number;
+use crate::traits::DataVault;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+// attempts to update a card changed by someone else meanwhile
+const MAX_ATTEMPTS: usize = 3;
+
+/// What the card network reported for a card
+#[derive(Clone, PartialEq, Eq)]
+pub enum AccountChange {
+    /// the card was replaced by one with another number
+    NewAccount {
+        number: String,
+        expiration_month: String,
+        expiration_year: String,
+    },
+    /// the card was reissued with another expiry
+    NewExpiry {
+        expiration_month: String,
+        expiration_year: String,
+    },
+    /// the account was closed, the card can not be charged anymore
+    Closed,
+}
+
+impl fmt::Debug for AccountChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountChange::NewAccount { number, expiration_month, expiration_year } => f.debug_struct("NewAccount")
+                .field("number", &mask_number(number))
+                .field("expiration_month", expiration_month)
+                .field("expiration_year", expiration_year)
+                .finish(),
+            AccountChange::NewExpiry { expiration_month, expiration_year } => f.debug_struct("NewExpiry")
+                .field("expiration_month", expiration_month)
+                .field("expiration_year", expiration_year)
+                .finish(),
+            AccountChange::Closed => f.write_str("Closed"),
+        }
+    }
+}
+
+/// One row of an account updater response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountUpdate {
+    /// the fingerprint of the old card number, see
+    /// `Tokenizer::fingerprint`
+    pub fingerprint: String,
+    pub change: AccountChange,
+}
+
+/// The outcome of `apply_account_updates`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountUpdateReport {
+    /// tokens of the cards given a new number or expiry
+    pub updated: Vec<String>,
+    /// tokens of the cards of closed accounts
+    pub closed: Vec<String>,
+    /// fingerprints of no card stored in the namespace
+    pub not_found: Vec<String>,
+    /// tokens of the cards the vault refused the new number or expiry
+    /// of, with the reason
+    pub rejected: Vec<(String, String)>,
+}
+
+/// The updates of an account updater response in CSV, see the module
+/// documentation
+///
+/// Fields are not quoted.  Empty lines are skipped.
+/// returns:
+///     * an `io::ErrorKind::InvalidData` error for a row that is not
+///       an update
+pub fn read_account_updates<R: Read>(reader: R) -> io::Result<Vec<AccountUpdate>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason);
+    let mut updates = Vec::new();
+    for line in BufReader::new(reader).lines().skip(1) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != 5 {
+            return Err(invalid("account updates have 5 columns"))
+        }
+        if fields[0].is_empty() {
+            return Err(invalid("account update without a fingerprint"))
+        }
+        let change = match fields[1] {
+            "new_account" if !fields[2].is_empty() => AccountChange::NewAccount {
+                number: fields[2].to_string(),
+                expiration_month: fields[3].to_string(),
+                expiration_year: fields[4].to_string(),
+            },
+            "new_account" => return Err(invalid("new_account without a number")),
+            "new_expiry" => AccountChange::NewExpiry {
+                expiration_month: fields[3].to_string(),
+                expiration_year: fields[4].to_string(),
+            },
+            "closed" => AccountChange::Closed,
+            _ => return Err(invalid("change is not new_account, new_expiry or closed")),
+        };
+        updates.push(AccountUpdate {
+            fingerprint: fields[0].to_string(),
+            change,
+        });
+    }
+    Ok(updates)
+}
+
+/// Apply `updates` to the cards of `vault`'s namespace
+///
+/// New numbers and expiry dates are validated like any stored card,
+/// a refused one is reported as rejected and leaves the card as it
+/// was.  A new number drops the card's security code and brand, the
+/// brand of the new number is filled in.  Other errors of the vault,
+/// e.g. a lost connection, end the run, updates applied before stay.
+pub async fn apply_account_updates<V: DataVault>(vault: &V, updates: &[AccountUpdate]) -> Result<AccountUpdateReport, DataVaultError> {
+    let mut report = AccountUpdateReport::default();
+    for update in updates {
+        let token = match indexed_token(vault, &update.fingerprint).await? {
+            Some(token) => token,
+            None => {
+                report.not_found.push(update.fingerprint.clone());
+                continue
+            },
+        };
+        if update.change == AccountChange::Closed {
+            report.closed.push(token);
+            continue
+        }
+
+        match apply_change(vault, &token, &update.change).await {
+            Ok(()) => report.updated.push(token),
+            Err(e @ DataVaultError::Validation(_)) => report.rejected.push((token, e.to_string())),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(report)
+}
+
+async fn apply_change<V: DataVault>(vault: &V, token: &str, change: &AccountChange) -> Result<(), DataVaultError> {
+    let mut attempt = 1;
+    loop {
+        let (mut credit_card, version) = vault.retrieve_credit_card_with_version(token).await?;
+        match change {
+            AccountChange::NewAccount { number, expiration_month, expiration_year } => {
+                credit_card.number = number.clone();
+                credit_card.expiration_month = expiration_month.clone();
+                credit_card.expiration_year = expiration_year.clone();
+                credit_card.brand = None;
+                credit_card.security_code = None;
+            },
+            AccountChange::NewExpiry { expiration_month, expiration_year } => {
+                credit_card.expiration_month = expiration_month.clone();
+                credit_card.expiration_year = expiration_year.clone();
+            },
+            AccountChange::Closed => return Ok(()),
+        }
+
+        match vault.update_credit_card_if_version(token, &credit_card, version).await {
+            Err(DataVaultError::Conflict) if attempt < MAX_ATTEMPTS => attempt += 1,
+            result => return result.map(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::account_updater::{read_account_updates, AccountChange, AccountUpdate};
+
+    #[test]
+    fn test_read_account_updates() {
+        let csv = "fingerprint,change,number,expiration_month,expiration_year\n\
+            aa,new_account,4012888888881881,09,2030\n\
+            \n\
+            bb,new_expiry,,10,2031\n\
+            cc,closed,,,\n";
+        let updates = read_account_updates(csv.as_bytes()).unwrap();
+        assert_eq!(updates, vec![
+            AccountUpdate {
+                fingerprint: "aa".to_string(),
+                change: AccountChange::NewAccount {
+                    number: "4012888888881881".to_string(),
+                    expiration_month: "09".to_string(),
+                    expiration_year: "2030".to_string(),
+                },
+            },
+            AccountUpdate {
+                fingerprint: "bb".to_string(),
+                change: AccountChange::NewExpiry {
+                    expiration_month: "10".to_string(),
+                    expiration_year: "2031".to_string(),
+                },
+            },
+            AccountUpdate { fingerprint: "cc".to_string(), change: AccountChange::Closed },
+        ]);
+        assert!(!format!("{:?}", updates).contains("4012888888881881"));
+
+        let header = "fingerprint,change,number,expiration_month,expiration_year\n";
+        for row in ["aa,new_account,,09,2030", "aa,reissued,,09,2030", ",closed,,,", "aa,closed"] {
+            let e = read_account_updates(format!("{}{}", header, row).as_bytes()).unwrap_err();
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidData, "{}", row);
+        }
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_apply_account_updates_postgres() {
+        use credit_card::CreditCard;
+        use crate::account_updater::{apply_account_updates, AccountUpdateReport};
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::{Blake3DeterministicTokenizer, Tokenizer};
+        use crate::traits::DataVault;
+        use crate::utils::Salt;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace(&Salt::generate(16)).unwrap();
+        let tokenizer = Blake3DeterministicTokenizer::new();
+        let card = |number: &str| CreditCard {
+            number: number.to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: Some("visa".to_string()),
+            security_code: None
+        };
+        let replaced = vault.store_credit_card(&card("4111111111111111")).await.unwrap();
+        let reissued = vault.store_credit_card(&card("4242424242424242")).await.unwrap();
+        let closed = vault.store_credit_card(&card("5555555555554444")).await.unwrap();
+        let fingerprint = |number: &str| tokenizer.fingerprint(&card(number)).unwrap();
+
+        let updates = vec![
+            AccountUpdate {
+                fingerprint: fingerprint("4111111111111111"),
+                change: AccountChange::NewAccount {
+                    number: "5105105105105100".to_string(),
+                    expiration_month: "9".to_string(),
+                    expiration_year: "2030".to_string(),
+                },
+            },
+            AccountUpdate {
+                fingerprint: fingerprint("4242424242424242"),
+                change: AccountChange::NewExpiry { expiration_month: "10".to_string(), expiration_year: "2031".to_string() },
+            },
+            AccountUpdate { fingerprint: fingerprint("5555555555554444"), change: AccountChange::Closed },
+            AccountUpdate { fingerprint: fingerprint("378282246310005"), change: AccountChange::Closed },
+            AccountUpdate {
+                fingerprint: fingerprint("4242424242424242"),
+                change: AccountChange::NewExpiry { expiration_month: "13".to_string(), expiration_year: "2031".to_string() },
+            },
+        ];
+        let report = apply_account_updates(&vault, &updates).await.unwrap();
+        assert_eq!(report, AccountUpdateReport {
+            updated: vec![replaced.clone(), reissued.clone()],
+            closed: vec![closed.clone()],
+            not_found: vec![fingerprint("378282246310005")],
+            rejected: vec![(reissued.clone(), "invalid card: expiration_month is not 1 to 12".to_string())],
+        });
+
+        let updated = vault.retrieve_credit_card(&replaced).await.unwrap();
+        assert_eq!(updated.number, "5105105105105100");
+        assert_eq!(updated.expiration_month, "09");
+        assert_eq!(updated.brand.as_deref(), Some("mastercard"));
+        assert_eq!(vault.retrieve_credit_card(&reissued).await.unwrap().expiration_year, "2031");
+        // the new number is indexed for the next update
+        assert_eq!(vault.store_credit_card(&card("5105105105105100")).await.unwrap(), replaced);
+
+        vault.delete_many(&[replaced, reissued, closed]).await.unwrap();
+    }
+}
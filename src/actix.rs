@@ -0,0 +1,183 @@
+//! actix-web integration: the vault as app data handlers extract as
+//! `web::Data<dyn DataVaultDyn>`, and the tokenization routes as a
+//! `Scope` to mount into a service of your own
+//!
+//! ```text
+//! POST   /tokens           a credit card as JSON   201 {"token": "..."}, 200 if stored already
+//! GET    /tokens/{token}                           200 the credit card as JSON
+//! DELETE /tokens/{token}                           204
+//! ```
+//!
+//! The routes do not authenticate, wrap the scope in the middleware
+//! guarding the rest of your service.  Errors are `{"error": "..."}`
+//! with card numbers and tokens masked, see `redact`, return a
+//! `VaultError` from your own handlers for the same responses.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use actix_web::{web, App, HttpServer};
+//! use data_vault::{actix, DataVault, RedisDataVault};
+//! use data_vault::actix::{VaultData, VaultError};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! async fn has_card(vault: VaultData, token: web::Path<String>) -> Result<String, VaultError> {
+//!     Ok(vault.exists(&token).await?.to_string())
+//! }
+//!
+//! #[actix_web::main]
+//! async fn main() -> std::io::Result<()> {
+//!     let vault = actix::vault_data(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+//!     HttpServer::new(move || App::new()
+//!             .app_data(vault.clone())
+//!             .service(actix::scope("/vault"))
+//!             .route("/cards/{token}", web::get().to(has_card)))
+//!         .bind("127.0.0.1:8080")?
+//!         .run()
+//!         .await
+//! }
+//! ```
+
+use actix_web::{web, HttpResponse, ResponseError, Scope};
+use actix_web::http::StatusCode;
+use credit_card::CreditCard;
+use serde_json::json;
+use crate::traits::{DataVault, DataVaultDyn};
+use crate::error::DataVaultError;
+use crate::redact::redact;
+use std::fmt;
+use std::sync::Arc;
+
+/// The vault as handlers extract it, registered by `App::app_data`
+pub type VaultData = web::Data<dyn DataVaultDyn>;
+
+/// `vault` to register with `App::app_data`, clone it into each worker
+pub fn vault_data<V>(vault: V) -> VaultData
+    where
+        V: DataVault + 'static,
+{
+    web::Data::from(Arc::new(vault) as Arc<dyn DataVaultDyn>)
+}
+
+/// An error response with the status of a `DataVaultError`, the
+/// message is redacted
+#[derive(Debug)]
+pub struct VaultError {
+    status: StatusCode,
+    message: String,
+}
+
+impl From<DataVaultError> for VaultError {
+    fn from(e: DataVaultError) -> Self {
+        let status = match &e {
+            DataVaultError::NotFound => StatusCode::NOT_FOUND,
+            DataVaultError::InvalidToken => StatusCode::BAD_REQUEST,
+            DataVaultError::AlreadyExists | DataVaultError::Conflict => StatusCode::CONFLICT,
+            DataVaultError::SecurityCodeNotAllowed | DataVaultError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DataVaultError::AccessDenied => StatusCode::FORBIDDEN,
+            DataVaultError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            DataVaultError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            e if e.is_transient() => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let message = match e {
+            DataVaultError::RateLimited(retry_after) => format!("rate limited, retry after {} seconds", retry_after.as_secs().max(1)),
+            e => redact(&e.to_string()).into_owned(),
+        };
+        VaultError { status, message }
+    }
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ResponseError for VaultError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(json!({ "error": self.message }))
+    }
+}
+
+async fn tokenize(vault: VaultData, body: web::Bytes) -> Result<HttpResponse, VaultError> {
+    // parsed here rather than by `web::Json`, whose errors may quote the card
+    let credit_card: CreditCard = serde_json::from_slice(&body)
+        .map_err(|e| VaultError { status: StatusCode::BAD_REQUEST, message: redact(&format!("invalid credit card: {}", e)).into_owned() })?;
+    Ok(match vault.tokenize(&credit_card).await? {
+        (token, true) => HttpResponse::Created().json(json!({ "token": token })),
+        (token, false) => HttpResponse::Ok().json(json!({ "token": token })),
+    })
+}
+
+async fn detokenize(vault: VaultData, token: web::Path<String>) -> Result<HttpResponse, VaultError> {
+    Ok(HttpResponse::Ok().json(vault.retrieve_credit_card(&token).await?))
+}
+
+async fn delete(vault: VaultData, token: web::Path<String>) -> Result<HttpResponse, VaultError> {
+    match vault.delete_many(&[token.into_inner()]).await? {
+        0 => Err(DataVaultError::NotFound.into()),
+        _ => Ok(HttpResponse::NoContent().finish()),
+    }
+}
+
+/// The tokenization routes under `path`, over the `VaultData` of
+/// the app
+pub fn scope(path: &str) -> Scope {
+    web::scope(path)
+        .route("/tokens", web::post().to(tokenize))
+        .route("/tokens/{token}", web::get().to(detokenize))
+        .route("/tokens/{token}", web::delete().to(delete))
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use actix_web::{test, web, App};
+    use actix_web::http::StatusCode;
+    use serde_json::{json, Value};
+    use crate::actix::{self, VaultData, VaultError};
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+    use crate::traits::DataVault;
+
+    async fn backend(vault: VaultData) -> Result<String, VaultError> {
+        Ok(format!("{} {}", vault.backend(), vault.exists("no-such-token").await?))
+    }
+
+    #[actix_web::test]
+    async fn test_scope() {
+        let vault = actix::vault_data(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+        let app = test::init_service(App::new()
+            .app_data(vault)
+            .service(actix::scope("/vault"))
+            .route("/backend", web::get().to(backend))).await;
+
+        let card = json!({"number": "4111111111111111", "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023"});
+        let response = test::call_service(&app, test::TestRequest::post().uri("/vault/tokens").set_json(&card).to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = test::read_body_json::<Value, _>(response).await["token"].as_str().unwrap().to_string();
+
+        let response = test::call_service(&app, test::TestRequest::get().uri(&format!("/vault/tokens/{}", token)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body_json::<Value, _>(response).await["number"], "4111111111111111");
+
+        let response = test::call_service(&app, test::TestRequest::post().uri("/vault/tokens").set_payload("4111111111111111").to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(!String::from_utf8(test::read_body(response).await.to_vec()).unwrap().contains("4111111111111111"));
+
+        let response = test::call_service(&app, test::TestRequest::delete().uri(&format!("/vault/tokens/{}", token)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = test::call_service(&app, test::TestRequest::get().uri(&format!("/vault/tokens/{}", token)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = test::call_service(&app, test::TestRequest::delete().uri(&format!("/vault/tokens/{}", token)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/backend").to_request()).await;
+        assert_eq!(test::read_body(response).await, "redis false");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,161 @@
+//! Aggregates for compliance dashboards and operators
+//!
+//! Nothing here exposes a token, a record or key material, only
+//! counts, times and a one-way id of the key, see `key_info`,
+//! `record_histogram_by_age` and `tenant_summary` of the vaults.
+
+use crate::config::EncryptionSettings;
+use crate::encryption::traits::Encryption;
+use std::time::{Duration, SystemTime};
+
+// blake3 context of key ids, changing it changes every id
+const KEY_ID_CONTEXT: &str = "data_vault 2021-06-01 key id";
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The key a vault encrypts with, without the key itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    /// 16 hexadecimal characters derived from the key and IV, the
+    /// same key material has the same id in every vault and process,
+    /// the key can not be recovered from it
+    pub key_id: String,
+    /// see `Encryption::algorithm`
+    pub algorithm: &'static str,
+    /// when the vault started using the key, on creation or on a
+    /// `reload` that switched keys, a restart of the process resets it
+    pub active_since: SystemTime,
+}
+
+impl KeyInfo {
+    pub(crate) fn new<E: Encryption>(encryption: &E, settings: &EncryptionSettings) -> Self {
+        let mut id = [0; 8];
+        blake3::derive_key(KEY_ID_CONTEXT, format!("{}:{}", settings.key, settings.iv).as_bytes(), &mut id);
+        KeyInfo {
+            key_id: hex::encode(id),
+            algorithm: encryption.algorithm(),
+            active_since: SystemTime::now(),
+        }
+    }
+
+    /// The key after a `reload` to `next`, still active since it
+    /// was first used unless `next` is another key
+    pub(crate) fn reloaded(&self, next: KeyInfo) -> KeyInfo {
+        match next.key_id == self.key_id && next.algorithm == self.algorithm {
+            true => self.clone(),
+            false => next,
+        }
+    }
+
+    /// How long the key has been active, compare it with the
+    /// rotation period of your key management policy
+    pub fn rotation_age(&self) -> Duration {
+        SystemTime::now().duration_since(self.active_since).unwrap_or_default()
+    }
+}
+
+/// Records stored within a range of ages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgeBucket {
+    /// the youngest age in the bucket
+    pub min_age: Duration,
+    /// the age the next bucket starts at, `None` for the oldest bucket
+    pub max_age: Option<Duration>,
+    pub count: u64,
+}
+
+/// Records by the time since they were first stored, see
+/// `record_histogram_by_age`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgeHistogram {
+    /// from the youngest to the oldest records
+    pub buckets: Vec<AgeBucket>,
+}
+
+impl AgeHistogram {
+    /// a day, 30 days, 90 days and a year
+    pub const DEFAULT_BOUNDS: [Duration; 4] = [DAY, Duration::from_secs(30 * DAY.as_secs()), Duration::from_secs(90 * DAY.as_secs()), Duration::from_secs(365 * DAY.as_secs())];
+
+    /// The records of all buckets
+    pub fn total(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.count).sum()
+    }
+
+    /// `bounds` in ascending order without duplicates or zero, the
+    /// ages the buckets after the first start at
+    pub(crate) fn bounds(bounds: &[Duration]) -> Vec<Duration> {
+        let mut bounds: Vec<Duration> = bounds.iter().copied().filter(|bound| !bound.is_zero()).collect();
+        bounds.sort();
+        bounds.dedup();
+        bounds
+    }
+
+    /// The histogram of `counts`, one more than `bounds` returned
+    /// by `AgeHistogram::bounds`
+    pub(crate) fn from_counts(bounds: &[Duration], counts: &[u64]) -> Self {
+        let min_ages = std::iter::once(Duration::ZERO).chain(bounds.iter().copied());
+        let max_ages = bounds.iter().copied().map(Some).chain(std::iter::once(None));
+        AgeHistogram {
+            buckets: min_ages.zip(max_ages).zip(counts)
+                .map(|((min_age, max_age), count)| AgeBucket { min_age, max_age, count: *count })
+                .collect(),
+        }
+    }
+}
+
+/// The records of one namespace, see `tenant_summary`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantSummary {
+    pub namespace: String,
+    /// live records
+    pub count: u64,
+    /// soft deleted records waiting to be purged
+    pub soft_deleted: u64,
+    /// when the oldest live record was first stored
+    pub oldest: Option<SystemTime>,
+    /// when the newest live record was first stored
+    pub newest: Option<SystemTime>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::admin::{AgeBucket, AgeHistogram, KeyInfo};
+    use crate::config::EncryptionSettings;
+    use crate::encryption::{Aes128CbcEncryption, AesGcmSivEncryption};
+    use crate::encryption::traits::Encryption;
+    use std::time::Duration;
+
+    #[test]
+    fn test_key_info() {
+        let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let key = KeyInfo::new(&AesGcmSivEncryption::from_settings(&settings), &settings);
+        assert_eq!(key.key_id.len(), 16);
+        assert_eq!(key.algorithm, "AES-256-GCM-SIV");
+        assert!(!key.key_id.contains("0001") && !key.key_id.contains("f0f1"));
+        assert_eq!(KeyInfo::new(&Aes128CbcEncryption::from_settings(&settings), &settings).algorithm, "AES-128-CBC");
+
+        let same = KeyInfo::new(&AesGcmSivEncryption::from_settings(&settings), &settings);
+        assert_eq!(same.key_id, key.key_id);
+        assert_eq!(key.reloaded(same).active_since, key.active_since);
+
+        let rotated = EncryptionSettings::new("101112131415161718191a1b1c1d1e1f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let next = KeyInfo::new(&AesGcmSivEncryption::from_settings(&rotated), &rotated);
+        assert_ne!(next.key_id, key.key_id);
+        assert_eq!(key.reloaded(next.clone()), next);
+    }
+
+    #[test]
+    fn test_age_histogram() {
+        let day = Duration::from_secs(86400);
+        let bounds = AgeHistogram::bounds(&[day * 30, Duration::ZERO, day, day * 30]);
+        assert_eq!(bounds, vec![day, day * 30]);
+
+        let histogram = AgeHistogram::from_counts(&bounds, &[3, 0, 2]);
+        assert_eq!(histogram.total(), 5);
+        assert_eq!(histogram.buckets, vec![
+            AgeBucket { min_age: Duration::ZERO, max_age: Some(day), count: 3 },
+            AgeBucket { min_age: day, max_age: Some(day * 30), count: 0 },
+            AgeBucket { min_age: day * 30, max_age: None, count: 2 },
+        ]);
+    }
+}
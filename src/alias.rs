@@ -0,0 +1,83 @@
+use crate::error::DataVaultError;
+use crate::traits::DataVault;
+
+// aliases of aliases followed before giving up, a chain only grows
+// when a survivor is merged into another card
+const MAX_ALIAS_HOPS: usize = 8;
+
+/// The vault keeping the aliases of `vault`'s namespace, the token
+/// of a merged card mapped to the token of the card it was merged
+/// into
+pub(crate) fn aliases<V: DataVault>(vault: &V) -> Result<V, DataVaultError> {
+    vault.with_side_namespace("alias")
+}
+
+/// The token of the live card `token` was merged into, following
+/// aliases of merged survivors, `None` if `token` is no alias or its
+/// card was deleted since
+pub(crate) async fn resolve_alias<V: DataVault>(vault: &V, token: &str) -> Result<Option<String>, DataVaultError> {
+    let aliases = aliases(vault)?;
+    let mut token = token.to_string();
+    for _ in 0..MAX_ALIAS_HOPS {
+        match aliases.try_retrieve(&token).await? {
+            Some(survivor) if vault.exists(&survivor).await? => return Ok(Some(survivor)),
+            Some(survivor) => token = survivor,
+            None => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod test {
+    use credit_card::CreditCard;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::error::DataVaultError;
+    use crate::metadata::CustomMetadata;
+    use crate::postgres_data_vault::PostgresDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+    use crate::traits::DataVault;
+    use crate::validation::ValidationError;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_merge() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("merge-test").unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let metadata = |key: &str, value: &str| -> CustomMetadata {
+            vec![(key.to_string(), value.to_string())].into_iter().collect()
+        };
+        let survivor = vault.store_credit_card_with_custom_metadata(&cc, &metadata("source", "crm")).await.unwrap();
+        let mut billing = metadata("source", "billing");
+        billing.extend(metadata("customer_id", "cus_42"));
+        let duplicate = vault.store_credit_card_with_custom_metadata(&cc, &billing).await.unwrap();
+        cc.number = "5555555555554444".to_string();
+        let other = vault.store_credit_card(&cc).await.unwrap();
+
+        let merge = vault.merge(&[survivor.clone(), other.clone()]).await;
+        assert!(matches!(merge, Err(DataVaultError::Validation(ValidationError::NotSameCard))));
+        assert!(vault.exists(&other).await.unwrap());
+
+        let merged = vault.merge(&[survivor.clone(), duplicate.clone(), survivor.clone()]).await.unwrap();
+        assert_eq!(merged, survivor);
+        assert!(!vault.exists(&duplicate).await.unwrap());
+        assert_eq!(vault.resolve_token(&duplicate).await.unwrap(), survivor);
+        assert_eq!(vault.retrieve_credit_card(&duplicate).await.unwrap().number, "4111111111111111");
+        let merged_metadata = vault.retrieve_custom_metadata(&survivor).await.unwrap();
+        assert_eq!(merged_metadata["source"], "crm");
+        assert_eq!(merged_metadata["customer_id"], "cus_42");
+
+        // aliases of a deleted card resolve to nothing
+        vault.delete_many(&[survivor, other]).await.unwrap();
+        assert!(matches!(vault.retrieve_credit_card(&duplicate).await, Err(DataVaultError::NotFound)));
+        assert!(matches!(vault.resolve_token(&duplicate).await, Err(DataVaultError::NotFound)));
+        vault.with_side_namespace("alias").unwrap().delete_many(&[duplicate]).await.unwrap();
+    }
+}
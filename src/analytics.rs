@@ -0,0 +1,283 @@
+//! Tokenized exports for analytics
+//!
+//! `export_analytics` writes one row per credit card of a vault to a
+//! Parquet or Arrow IPC file, so analytics teams can join on tokens
+//! in the warehouse without any way to decrypt.  Rows hold the token
+//! and what can be shown without PCI scope:
+//!
+//! ```text
+//! token             utf8
+//! tenant            utf8
+//! brand             utf8, nullable
+//! last4             utf8
+//! expiration_month  utf8
+//! expiration_year   utf8
+//! created_at        timestamp[ms, UTC], nullable
+//! expires_at        timestamp[ms, UTC], nullable
+//! version           uint64
+//! ```
+//!
+//! Card numbers, cardholder names and security codes are never
+//! written.  Records that are not credit cards, e.g. strings stored
+//! with `DataVault::store`, are skipped.
+//!
+//! # example
+//! ```rust,ignore
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::analytics::{export_analytics, AnalyticsFormat};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//! use std::fs::File;
+//!
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+//! let report = export_analytics(&vault, File::create("cards.parquet")?, AnalyticsFormat::Parquet).await?;
+//! println!("{} cards exported, {} records skipped", report.exported, report.skipped);
+//! ```
+
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_array::builder::{StringBuilder, TimestampMillisecondBuilder, UInt64Builder};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use credit_card::CreditCard;
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::metadata::RecordMetadata;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// rows per record batch, and per parquet row group at most
+const BATCH_SIZE: usize = 8192;
+
+/// The file `export_analytics` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyticsFormat {
+    /// Parquet with snappy compressed columns
+    Parquet,
+    /// the Arrow IPC file format, also known as Feather v2
+    ArrowIpc,
+}
+
+/// The outcome of `export_analytics`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnalyticsReport {
+    /// rows written
+    pub exported: u64,
+    /// records that are not credit cards or were deleted while exporting
+    pub skipped: u64,
+}
+
+/// The columns of an analytics export
+pub fn analytics_schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("token", DataType::Utf8, false),
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("brand", DataType::Utf8, true),
+        Field::new("last4", DataType::Utf8, false),
+        Field::new("expiration_month", DataType::Utf8, false),
+        Field::new("expiration_year", DataType::Utf8, false),
+        Field::new("created_at", timestamp.clone(), true),
+        Field::new("expires_at", timestamp, true),
+        Field::new("version", DataType::UInt64, false),
+    ]))
+}
+
+/// Write the non-sensitive fields of every credit card in this
+/// namespace of `vault` to `writer`, see the module documentation
+///
+/// Every record is read and decrypted in memory to find its brand,
+/// last four digits and expiry, only those leave the process.
+pub async fn export_analytics<V, W>(vault: &V, writer: W, format: AnalyticsFormat) -> Result<AnalyticsReport, DataVaultError>
+    where
+        V: DataVault,
+        W: Write + Send,
+{
+    let schema = analytics_schema();
+    let mut writer = BatchWriter::new(writer, format, &schema).map_err(encoding_error)?;
+    let mut report = AnalyticsReport::default();
+    let mut rows = Rows::default();
+
+    let mut records = vault.iter_records();
+    while let Some((token, _)) = records.try_next().await? {
+        match vault.retrieve_with_metadata(&token).await {
+            Ok((credit_card, metadata)) => rows.push(&token, &credit_card, &metadata),
+            // not a credit card, or gone since it was listed
+            Err(DataVaultError::Serialization(_)) | Err(DataVaultError::NotFound) => {
+                report.skipped += 1;
+                continue
+            },
+            Err(e) => return Err(e),
+        }
+        report.exported += 1;
+        if rows.len == BATCH_SIZE {
+            writer.write(&rows.finish(&schema).map_err(encoding_error)?).map_err(encoding_error)?;
+        }
+    }
+    if rows.len > 0 {
+        writer.write(&rows.finish(&schema).map_err(encoding_error)?).map_err(encoding_error)?;
+    }
+    writer.close().map_err(encoding_error)?;
+
+    Ok(report)
+}
+
+#[derive(Default)]
+struct Rows {
+    token: StringBuilder,
+    tenant: StringBuilder,
+    brand: StringBuilder,
+    last4: StringBuilder,
+    expiration_month: StringBuilder,
+    expiration_year: StringBuilder,
+    created_at: TimestampMillisecondBuilder,
+    expires_at: TimestampMillisecondBuilder,
+    version: UInt64Builder,
+    len: usize,
+}
+
+impl Rows {
+    fn push(&mut self, token: &str, credit_card: &CreditCard, metadata: &RecordMetadata) {
+        let number = &credit_card.number;
+        self.token.append_value(token);
+        self.tenant.append_value(&metadata.tenant);
+        self.brand.append_option(credit_card.brand.as_deref());
+        self.last4.append_value(&number[number.len().saturating_sub(4)..]);
+        self.expiration_month.append_value(&credit_card.expiration_month);
+        self.expiration_year.append_value(&credit_card.expiration_year);
+        self.created_at.append_option(metadata.created_at.map(unix_millis));
+        self.expires_at.append_option(metadata.ttl.map(|ttl| unix_millis(SystemTime::now() + ttl)));
+        self.version.append_value(metadata.version);
+        self.len += 1;
+    }
+
+    /// The rows pushed so far as a batch, the builders start over
+    fn finish(&mut self, schema: &SchemaRef) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.token.finish()),
+            Arc::new(self.tenant.finish()),
+            Arc::new(self.brand.finish()),
+            Arc::new(self.last4.finish()),
+            Arc::new(self.expiration_month.finish()),
+            Arc::new(self.expiration_year.finish()),
+            Arc::new(self.created_at.finish().with_timezone("UTC")),
+            Arc::new(self.expires_at.finish().with_timezone("UTC")),
+            Arc::new(self.version.finish()),
+        ];
+        self.len = 0;
+        RecordBatch::try_new(schema.clone(), columns)
+    }
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_millis() as i64)
+}
+
+enum BatchWriter<W: Write + Send> {
+    Parquet(ArrowWriter<W>),
+    ArrowIpc(arrow_ipc::writer::FileWriter<W>),
+}
+
+impl<W: Write + Send> BatchWriter<W> {
+    fn new(writer: W, format: AnalyticsFormat, schema: &SchemaRef) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match format {
+            AnalyticsFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .set_max_row_group_size(BATCH_SIZE)
+                    .build();
+                BatchWriter::Parquet(ArrowWriter::try_new(writer, schema.clone(), Some(properties))?)
+            },
+            AnalyticsFormat::ArrowIpc => BatchWriter::ArrowIpc(arrow_ipc::writer::FileWriter::try_new(writer, schema)?),
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            BatchWriter::Parquet(writer) => writer.write(batch)?,
+            BatchWriter::ArrowIpc(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+
+    fn close(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            BatchWriter::Parquet(writer) => {
+                writer.close()?;
+            },
+            BatchWriter::ArrowIpc(mut writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+fn encoding_error<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> DataVaultError {
+    let e = e.into();
+    match e.downcast::<io::Error>() {
+        Ok(e) => DataVaultError::Io(*e),
+        Err(e) => DataVaultError::Encoding(e),
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use arrow_array::{Array, StringArray, TimestampMillisecondArray};
+    use credit_card::CreditCard;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use crate::analytics::{export_analytics, AnalyticsFormat};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_analytics() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace("analytics-test").unwrap();
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: Some("visa".to_string()),
+            security_code: None,
+        };
+        let (token, _) = vault.tokenize(&credit_card).await.unwrap();
+        vault.touch(&token, Some(Duration::from_secs(3600))).await.unwrap();
+        vault.store("not-a-card", "plain text").await.unwrap();
+
+        let path = std::env::temp_dir().join(format!("data_vault_analytics_{}.parquet", std::process::id()));
+        let report = export_analytics(&vault, File::create(&path).unwrap(), AnalyticsFormat::Parquet).await.unwrap();
+        assert_eq!((report.exported, report.skipped), (1, 1));
+
+        let file = fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&file).contains("Graydon"));
+        let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap()
+            .build().unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        let batch = &batches[0];
+        let column = |name: &str| batch.column_by_name(name).unwrap().as_any().downcast_ref::<StringArray>().unwrap().value(0).to_string();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(column("token"), token);
+        assert_eq!(column("tenant"), "analytics-test");
+        assert_eq!(column("brand"), "visa");
+        assert_eq!(column("last4"), "1111");
+        assert_eq!(column("expiration_year"), "2023");
+        assert!(batch.column_by_name("number").is_none());
+        let expires_at = batch.column_by_name("expires_at").unwrap().as_any().downcast_ref::<TimestampMillisecondArray>().unwrap();
+        assert!(!expires_at.is_null(0));
+
+        let mut ipc = Vec::new();
+        let report = export_analytics(&vault, &mut ipc, AnalyticsFormat::ArrowIpc).await.unwrap();
+        assert_eq!(report.exported, 1);
+        assert!(ipc.starts_with(b"ARROW1"));
+
+        fs::remove_file(&path).unwrap();
+        vault.delete_many(&[token, "not-a-card".to_string()]).await.unwrap();
+    }
+}
\ No newline at end of file
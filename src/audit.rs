@@ -0,0 +1,462 @@
+//! Tamper evident audit log of vault operations
+//!
+//! Every operation of an `AuditedDataVault` is appended to a file
+//! as one JSON line holding the caller, timestamp, operation, outcome,
+//! namespace and token, never the stored data.  Each line carries
+//! the blake3 hash of the previous line so editing, reordering or
+//! removing lines breaks the chain, which `AuditLog::verify` detects.
+//! Truncating the end of the log can only be detected by comparing
+//! with a sequence number kept elsewhere.
+//!
+//! # example
+//! ```rust
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::audit::AuditLog;
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//! use std::sync::Arc;
+//!
+//! let path = std::env::temp_dir().join("data_vault_audit_example.log");
+//! let log = Arc::new(AuditLog::open(&path).unwrap());
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+//! let vault = log.audited(vault, "checkout-service");
+//!
+//! assert!(AuditLog::verify(&path).is_ok());
+//! ```
+
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::hooks::{Operation, Outcome};
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::config::AuditConfig;
+use crate::redact::redact_token;
+use futures::stream;
+use std::error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+// previous hash of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One audited operation, `Debug` only prints the ends of the token
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// position in the log, starting at 0
+    pub sequence: u64,
+    pub timestamp: SystemTime,
+    /// who used the vault, see `AuditLog::audited`
+    pub caller: String,
+    pub operation: Operation,
+    pub outcome: Outcome,
+    /// the namespace of the vault
+    pub tenant: String,
+    /// the token operated on, for `store_credit_card` and `tokenize`
+    /// the created token, for `rotate_token` the old token
+    pub token: Option<String>,
+    /// hash of the previous entry
+    pub previous_hash: String,
+}
+
+impl fmt::Debug for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditEntry")
+            .field("sequence", &self.sequence)
+            .field("timestamp", &self.timestamp)
+            .field("caller", &self.caller)
+            .field("operation", &self.operation)
+            .field("outcome", &self.outcome)
+            .field("tenant", &self.tenant)
+            .field("token", &self.token.as_deref().map(redact_token))
+            .field("previous_hash", &self.previous_hash)
+            .finish()
+    }
+}
+
+// an entry as written to the log
+#[derive(Serialize, Deserialize)]
+struct AuditLine {
+    #[serde(flatten)]
+    entry: AuditEntry,
+    hash: String,
+}
+
+impl AuditEntry {
+    fn hash(&self) -> Result<String, DataVaultError> {
+        let json = serde_json::to_vec(self)?;
+        Ok(blake3::hash(&json).to_hex().to_string())
+    }
+}
+
+struct AuditState {
+    file: File,
+    sequence: u64,
+    previous_hash: String,
+}
+
+/// An append only, hash chained audit log file
+///
+/// One `AuditLog` must be the only writer of its file, share it
+/// between vaults with an `Arc` instead of opening the file twice.
+pub struct AuditLog {
+    state: Mutex<AuditState>,
+}
+
+impl AuditLog {
+    /// Open or create the log at `path` and continue its chain
+    /// returns:
+    ///     * `DataVaultError::TamperedAuditLog` when the last line
+    ///       of an existing log can not be read
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DataVaultError> {
+        let file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+
+        let mut sequence = 0;
+        let mut previous_hash = GENESIS_HASH.to_string();
+        if let Some(line) = BufReader::new(&file).lines().last() {
+            let line: AuditLine = serde_json::from_str(&line?)
+                .map_err(|_| DataVaultError::TamperedAuditLog(sequence))?;
+            sequence = line.entry.sequence + 1;
+            previous_hash = line.hash;
+        }
+
+        Ok(AuditLog {
+            state: Mutex::new(AuditState {
+                file,
+                sequence,
+                previous_hash,
+            }),
+        })
+    }
+
+    /// `vault` writing every operation to this log as `caller`
+    pub fn audited<V: DataVault>(self: &Arc<Self>, vault: V, caller: &str) -> AuditedDataVault<V> {
+        AuditedDataVault {
+            inner: vault,
+            log: self.clone(),
+            caller: caller.to_string(),
+            side: false,
+        }
+    }
+
+    /// Check the hash chain of the log at `path`
+    /// returns:
+    ///     * the number of entries
+    ///     * `DataVaultError::TamperedAuditLog` with the sequence
+    ///       number of the first entry that does not match the chain
+    pub fn verify<P: AsRef<Path>>(path: P) -> Result<u64, DataVaultError> {
+        let file = File::open(path)?;
+        let mut previous_hash = GENESIS_HASH.to_string();
+        let mut sequence = 0;
+
+        for line in BufReader::new(file).lines() {
+            let line: AuditLine = serde_json::from_str(&line?)
+                .map_err(|_| DataVaultError::TamperedAuditLog(sequence))?;
+            let valid = line.entry.sequence == sequence
+                && line.entry.previous_hash == previous_hash
+                && line.entry.hash()? == line.hash;
+            if !valid {
+                return Err(DataVaultError::TamperedAuditLog(sequence))
+            }
+
+            previous_hash = line.hash;
+            sequence += 1;
+        }
+
+        Ok(sequence)
+    }
+
+    fn append(&self, caller: &str, operation: Operation, outcome: Outcome, tenant: &str, token: Option<String>) -> Result<(), DataVaultError> {
+        let mut state = self.state.lock().unwrap();
+        let entry = AuditEntry {
+            sequence: state.sequence,
+            timestamp: SystemTime::now(),
+            caller: caller.to_string(),
+            operation,
+            outcome,
+            tenant: tenant.to_string(),
+            token,
+            previous_hash: state.previous_hash.clone(),
+        };
+        let hash = entry.hash()?;
+
+        let mut json = serde_json::to_vec(&AuditLine { entry, hash: hash.clone() })?;
+        json.push(b'\n');
+        state.file.write_all(&json)?;
+        state.file.flush()?;
+
+        state.sequence += 1;
+        state.previous_hash = hash;
+        Ok(())
+    }
+}
+
+/// A vault appending each operation to an `AuditLog`
+///
+/// An operation whose entry can not be written fails, so data is
+/// never returned without an audit trail.  The streams of
+/// `iter_records` and `iter_decrypted_records` are audited once
+/// when they are created.
+pub struct AuditedDataVault<V> {
+    inner: V,
+    log: Arc<AuditLog>,
+    caller: String,
+    // a side vault, see `with_side_namespace`
+    side: bool,
+}
+
+impl<V> AuditedDataVault<V>
+    where
+        V: DataVault,
+{
+    /// The wrapped vault, operations on it are not audited
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// The caller identity written to the log
+    pub fn caller(&self) -> &str {
+        &self.caller
+    }
+
+    async fn audited<F, R>(&self, operation: Operation, token: Option<&str>, f: F) -> Result<R, DataVaultError>
+        where
+            F: Future<Output = Result<R, DataVaultError>>,
+    {
+        let result = f.await;
+        self.append(operation, token.map(str::to_string), &result)?;
+        result
+    }
+
+    fn append<R>(&self, operation: Operation, token: Option<String>, result: &Result<R, DataVaultError>) -> Result<(), DataVaultError> {
+        if self.side {
+            return Ok(())
+        }
+        self.log.append(&self.caller, operation, result.into(), self.inner.namespace(), token)
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for AuditedDataVault<V>
+    where
+        V: DataVault,
+{
+    /// Opens the log at `DATA_VAULT_AUDIT_LOG` and audits as
+    /// `DATA_VAULT_AUDIT_CALLER`.  Each call opens the file again,
+    /// use `AuditLog::audited` for several vaults in one process.
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        let cfg = AuditConfig::from_env()?;
+        let log = Arc::new(AuditLog::open(&cfg.log)?);
+        Ok(log.audited(V::new()?, &cfg.caller))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.audited(Operation::Store, Some(token), self.inner.store(token, string)).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.audited(Operation::Store, Some(token), self.inner.store_if_absent(token, string)).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.audited(Operation::Store, Some(token), self.inner.store_expiring(token, string, ttl)).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        let result = self.inner.store_credit_card(credit_card).await;
+        self.append(Operation::StoreCreditCard, result.as_ref().ok().cloned(), &result)?;
+        result
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        let result = self.inner.tokenize(credit_card).await;
+        let token = result.as_ref().ok().map(|(token, _)| token.clone());
+        self.append(Operation::Tokenize, token, &result)?;
+        result
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        let store = self.inner.store_credit_card_with_token(token, credit_card, overwrite);
+        self.audited(Operation::StoreCreditCard, Some(token), store).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.audited(Operation::Retrieve, Some(token), self.inner.retrieve(token)).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.audited(Operation::RetrieveCreditCard, Some(token), self.inner.retrieve_credit_card(token)).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.audited(Operation::Exists, Some(token), self.inner.exists(token)).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.audited(Operation::RetrieveCreditCard, Some(token), self.inner.retrieve_credit_card_with_version(token)).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.audited(Operation::RetrieveCreditCard, Some(token), self.inner.retrieve_with_metadata(token)).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let update = self.inner.update_credit_card_if_version(token, credit_card, expected_version);
+        self.audited(Operation::UpdateCreditCard, Some(token), update).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.audited(Operation::RotateToken, Some(token), self.inner.rotate_token(token)).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.audited(Operation::SoftDelete, Some(token), self.inner.soft_delete(token)).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.audited(Operation::Touch, Some(token), self.inner.touch(token, ttl)).await
+    }
+
+    /// One entry per token
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let result = self.inner.delete_many(tokens).await;
+        for token in tokens {
+            self.append(Operation::DeleteMany, Some(token.clone()), &result)?;
+        }
+        result
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.audited(Operation::PurgeExpired, None, self.inner.purge_expired()).await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        match self.append(Operation::ReadRecords, None, &Ok(())) {
+            Ok(()) => self.inner.iter_records(),
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+        }
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        match self.append(Operation::ReadRecords, None, &Ok(())) {
+            Ok(()) => self.inner.iter_decrypted_records(),
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+        }
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.audited(Operation::ReadRecords, None, self.inner.decrypted_records_page(cursor, limit)).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.audited(Operation::Count, None, self.inner.count()).await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.audited(Operation::Stats, None, self.inner.stats()).await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault writes to the same log as the same caller
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(AuditedDataVault {
+            inner: self.inner.with_namespace(namespace)?,
+            log: self.log.clone(),
+            caller: self.caller.clone(),
+            side: false,
+        })
+    }
+
+    /// Operations of side vaults are not audited, the log only names
+    /// the operations callers ran
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(AuditedDataVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            log: self.log.clone(),
+            caller: self.caller.clone(),
+            side: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::audit::AuditLog;
+    use crate::error::DataVaultError;
+    use crate::hooks::{Operation, Outcome};
+    use std::fs;
+
+    #[test]
+    fn test_audit_log_chain() {
+        let path = std::env::temp_dir().join(format!("data_vault_audit_test_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let log = AuditLog::open(&path).unwrap();
+        log.append("svc", Operation::Store, Outcome::Success, "", Some("abc".to_string())).unwrap();
+        log.append("svc", Operation::Retrieve, Outcome::NotFound, "", Some("abc".to_string())).unwrap();
+        drop(log);
+
+        // reopening continues the chain
+        let log = AuditLog::open(&path).unwrap();
+        log.append("svc", Operation::Count, Outcome::Success, "", None).unwrap();
+        assert_eq!(AuditLog::verify(&path).unwrap(), 3);
+
+        let tampered = fs::read_to_string(&path).unwrap().replacen("\"svc\"", "\"evil\"", 1);
+        fs::write(&path, tampered).unwrap();
+        assert!(matches!(AuditLog::verify(&path), Err(DataVaultError::TamperedAuditLog(0))));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_side_vaults_not_audited() {
+        use credit_card::CreditCard;
+        use crate::mock::MockDataVault;
+        use crate::traits::DataVault;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let path = std::env::temp_dir().join(format!("data_vault_audit_side_test_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+
+        let log = Arc::new(AuditLog::open(&path).unwrap());
+        let vault = log.audited(MockDataVault::new().unwrap().with_credit_card("tok_visa", &credit_card), "svc");
+        futures::executor::block_on(vault.store_cvv("tok_visa", "123", Duration::from_secs(60))).unwrap();
+        drop(vault);
+        drop(log);
+
+        // only the exists check, not the write of the code
+        assert_eq!(AuditLog::verify(&path).unwrap(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
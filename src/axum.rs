@@ -0,0 +1,227 @@
+//! axum integration: the tokenization routes as a `Router` over any
+//! state holding a `VaultState`, and the vault as a `State` for
+//! handlers of your own
+//!
+//! ```text
+//! POST   /tokens           a credit card as JSON   201 {"token": "..."}, 200 if stored already
+//! GET    /tokens/{token}                           200 the credit card as JSON
+//! DELETE /tokens/{token}                           204
+//! ```
+//!
+//! The routes do not authenticate, add the layers guarding the rest
+//! of your service, `server` adds API keys.  Errors are
+//! `{"error": "..."}` with card numbers and tokens masked, see
+//! `redact`, return a `VaultError` from your own handlers for the
+//! same responses.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use axum::Router;
+//! use axum::extract::{FromRef, Path, State};
+//! use axum::routing::get;
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::axum::{VaultError, VaultState};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! #[derive(Clone, FromRef)]
+//! struct AppState {
+//!     vault: VaultState,
+//!     greeting: String,
+//! }
+//!
+//! async fn has_card(State(vault): State<VaultState>, Path(token): Path<String>) -> Result<String, VaultError> {
+//!     Ok(vault.exists(&token).await?.to_string())
+//! }
+//!
+//! # async fn serve() {
+//! let state = AppState {
+//!     vault: VaultState::new(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()),
+//!     greeting: "hello".to_string(),
+//! };
+//! let app = Router::new()
+//!     .nest("/vault", data_vault::axum::router())
+//!     .route("/cards/{token}", get(has_card))
+//!     .with_state(state);
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await.unwrap();
+//! axum::serve(listener, app).await.unwrap();
+//! # }
+//! ```
+
+use ::axum::Router;
+use ::axum::body::Bytes;
+use ::axum::extract::{FromRef, Path, State};
+use ::axum::http::StatusCode;
+use ::axum::response::{IntoResponse, Response};
+use ::axum::routing::{get, post};
+use credit_card::CreditCard;
+use serde_json::json;
+use crate::traits::{DataVault, DataVaultDyn};
+use crate::error::DataVaultError;
+use crate::redact::redact;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A vault shared by the handlers of a router, as its state or a
+/// field of it, see `FromRef`
+#[derive(Clone)]
+pub struct VaultState(Arc<dyn DataVaultDyn>);
+
+impl VaultState {
+    pub fn new<V>(vault: V) -> Self
+        where
+            V: DataVault + 'static,
+    {
+        VaultState(Arc::new(vault))
+    }
+}
+
+impl Deref for VaultState {
+    type Target = dyn DataVaultDyn;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// An error response with the status of a `DataVaultError`, the
+/// message is redacted
+#[derive(Debug)]
+pub struct VaultError {
+    status: StatusCode,
+    message: String,
+}
+
+impl VaultError {
+    pub(crate) fn new(status: StatusCode, message: &str) -> Self {
+        VaultError { status, message: redact(message).into_owned() }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl From<DataVaultError> for VaultError {
+    fn from(e: DataVaultError) -> Self {
+        let status = match &e {
+            DataVaultError::NotFound => StatusCode::NOT_FOUND,
+            DataVaultError::InvalidToken => StatusCode::BAD_REQUEST,
+            DataVaultError::AlreadyExists | DataVaultError::Conflict => StatusCode::CONFLICT,
+            DataVaultError::SecurityCodeNotAllowed | DataVaultError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DataVaultError::AccessDenied => StatusCode::FORBIDDEN,
+            DataVaultError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            DataVaultError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            e if e.is_transient() => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        match e {
+            DataVaultError::RateLimited(retry_after) => VaultError::new(status, &format!("rate limited, retry after {} seconds", retry_after.as_secs().max(1))),
+            e => VaultError::new(status, &e.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for VaultError {
+    fn into_response(self) -> Response {
+        (self.status, ::axum::Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+async fn tokenize(State(vault): State<VaultState>, body: Bytes) -> Result<Response, VaultError> {
+    // parsed here rather than by `Json`, whose errors may quote the card
+    let credit_card: CreditCard = serde_json::from_slice(&body)
+        .map_err(|e| VaultError::new(StatusCode::BAD_REQUEST, &format!("invalid credit card: {}", e)))?;
+    let (token, created) = vault.tokenize(&credit_card).await?;
+    let status = match created {
+        true => StatusCode::CREATED,
+        false => StatusCode::OK,
+    };
+    Ok((status, ::axum::Json(json!({ "token": token }))).into_response())
+}
+
+async fn detokenize(State(vault): State<VaultState>, Path(token): Path<String>) -> Result<Response, VaultError> {
+    Ok(::axum::Json(vault.retrieve_credit_card(&token).await?).into_response())
+}
+
+async fn delete(State(vault): State<VaultState>, Path(token): Path<String>) -> Result<Response, VaultError> {
+    match vault.delete_many(&[token]).await? {
+        0 => Err(DataVaultError::NotFound.into()),
+        _ => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+/// The tokenization routes, over the `VaultState` of the state `S`
+/// the router is finished with
+pub fn router<S>() -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+        VaultState: FromRef<S>,
+{
+    Router::new()
+        .route("/tokens", post(tokenize))
+        .route("/tokens/{token}", get(detokenize).delete(delete))
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use ::axum::Router;
+    use ::axum::body::{self, Body};
+    use ::axum::extract::{FromRef, Path, State};
+    use ::axum::http::{Method, Request, StatusCode};
+    use ::axum::routing::get;
+    use tower::ServiceExt;
+    use crate::axum::{VaultError, VaultState};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+
+    #[derive(Clone, FromRef)]
+    struct AppState {
+        vault: VaultState,
+        greeting: &'static str,
+    }
+
+    async fn greet(State(greeting): State<&'static str>, State(vault): State<VaultState>, Path(token): Path<String>) -> Result<String, VaultError> {
+        Ok(format!("{} {}", greeting, vault.exists(&token).await?))
+    }
+
+    async fn send(app: &Router, method: Method, uri: &str, body: &str) -> (StatusCode, String) {
+        let request = Request::builder().method(method).uri(uri).body(Body::from(body.to_string())).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_router() {
+        let state = AppState {
+            vault: VaultState::new(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()),
+            greeting: "hello",
+        };
+        let app = Router::new()
+            .nest("/vault", crate::axum::router())
+            .route("/greet/{token}", get(greet))
+            .with_state(state);
+        let card = r#"{"number": "4111111111111111", "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023"}"#;
+
+        let (status, created) = send(&app, Method::POST, "/vault/tokens", card).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let token = serde_json::from_str::<serde_json::Value>(&created).unwrap()["token"].as_str().unwrap().to_string();
+        let uri = format!("/vault/tokens/{}", token);
+        let (status, credit_card) = send(&app, Method::GET, &uri, "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(credit_card.contains("4111111111111111"));
+        assert_eq!(send(&app, Method::GET, &format!("/greet/{}", token), "").await.1, "hello true");
+
+        let (status, error) = send(&app, Method::POST, "/vault/tokens", r#"{"number": 4111111111111111}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!error.contains("4111111111111111"));
+
+        assert_eq!(send(&app, Method::DELETE, &uri, "").await.0, StatusCode::NO_CONTENT);
+        assert_eq!(send(&app, Method::GET, &uri, "").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&app, Method::GET, &format!("/greet/{}", token), "").await.1, "hello false");
+    }
+}
\ No newline at end of file
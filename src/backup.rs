@@ -0,0 +1,185 @@
+//! The backup layout
+//!
+//! ```text
+//! <destination>/records.dvexport   the records in the export format, see `export`
+//! <destination>/manifest.json      the `BackupManifest`, written last
+//! ```
+
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::export::{ExportReader, ExportWriter};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::time::SystemTime;
+
+const RECORDS_FILE: &str = "records.dvexport";
+const MANIFEST_FILE: &str = "manifest.json";
+const MANIFEST_VERSION: u32 = 1;
+const KEY_CONTEXT: &str = "data_vault 2021-05-01 backup checksum key";
+
+/// What a backup holds, written next to its records by
+/// `DataVault::backup` and checked by `DataVault::restore`
+///
+/// The manifest is not encrypted, the records checksum is keyed with
+/// the backup key so it tells nothing about the records.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    /// seconds since the unix epoch the backup was started at
+    pub created_at: u64,
+    /// `DataVault::backend` of the vault backed up
+    pub backend: String,
+    /// `DataVault::namespace` of the vault backed up
+    pub namespace: String,
+    pub records: u64,
+    /// blake3 of the records file, hex
+    pub file_checksum: String,
+    /// keyed blake3 of every token and record, in the order of the
+    /// records file, hex
+    pub records_checksum: String,
+}
+
+/// The outcome of `DataVault::restore`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RestoreReport {
+    /// records stored in the vault
+    pub restored: u64,
+    /// records read back from the vault unchanged
+    pub verified: u64,
+    /// tokens missing or different when read back
+    pub mismatched: Vec<String>,
+}
+
+/// hashes tokens and records as they go into or come out of a backup
+struct RecordsChecksum(blake3::Hasher);
+
+impl RecordsChecksum {
+    fn new(backup_key: &[u8]) -> Self {
+        let mut key = [0u8; 32];
+        blake3::derive_key(KEY_CONTEXT, backup_key, &mut key);
+        RecordsChecksum(blake3::Hasher::new_keyed(&key))
+    }
+
+    fn update(&mut self, token: &str, data: &str) {
+        // lengths first, so the boundaries can not shift
+        for field in &[token, data] {
+            self.0.update(&(field.len() as u64).to_be_bytes());
+            self.0.update(field.as_bytes());
+        }
+    }
+
+    fn finalize(&self) -> String {
+        self.0.finalize().to_hex().to_string()
+    }
+}
+
+fn file_checksum(path: &Path) -> Result<String, DataVaultError> {
+    let mut hasher = blake3::Hasher::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// see `DataVault::backup`
+pub(crate) async fn backup<V>(vault: &V, destination: &Path, backup_key: &[u8]) -> Result<BackupManifest, DataVaultError>
+    where
+        V: DataVault,
+{
+    fs::create_dir_all(destination)?;
+    let manifest_path = destination.join(MANIFEST_FILE);
+    if manifest_path.exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} holds a backup already", destination.display())).into())
+    }
+    let created_at = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+
+    let records_path = destination.join(RECORDS_FILE);
+    let mut export = ExportWriter::new(BufWriter::new(File::create(&records_path)?), backup_key)?;
+    let mut checksum = RecordsChecksum::new(backup_key);
+    let mut records = vault.iter_decrypted_records();
+    while let Some((token, data)) = records.try_next().await? {
+        checksum.update(&token, &data);
+        export.write_record(token, data)?;
+    }
+    let records = export.finish()?;
+
+    let manifest = BackupManifest {
+        version: MANIFEST_VERSION,
+        created_at,
+        backend: vault.backend().to_string(),
+        namespace: vault.namespace().to_string(),
+        records,
+        file_checksum: file_checksum(&records_path)?,
+        records_checksum: checksum.finalize(),
+    };
+    fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+/// the manifest of the backup in `source`
+fn read_manifest(source: &Path) -> Result<BackupManifest, DataVaultError> {
+    let manifest: BackupManifest = serde_json::from_slice(&fs::read(source.join(MANIFEST_FILE))?)?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(DataVaultError::InvalidExport("unsupported backup version"))
+    }
+    Ok(manifest)
+}
+
+/// see `DataVault::restore`
+pub(crate) async fn restore<V>(vault: &V, source: &Path, backup_key: &[u8]) -> Result<RestoreReport, DataVaultError>
+    where
+        V: DataVault,
+{
+    let manifest = read_manifest(source)?;
+    let records_path = source.join(RECORDS_FILE);
+    // nothing is stored from a damaged file
+    if file_checksum(&records_path)? != manifest.file_checksum {
+        return Err(DataVaultError::InvalidExport("backup file checksum mismatch"))
+    }
+
+    let mut export = ExportReader::new(BufReader::new(File::open(&records_path)?), backup_key)?;
+    let mut checksum = RecordsChecksum::new(backup_key);
+    while let Some((token, data)) = export.next_record()? {
+        checksum.update(&token, &data);
+        vault.store(&token, &data).await?;
+    }
+    if export.records() != manifest.records || checksum.finalize() != manifest.records_checksum {
+        return Err(DataVaultError::InvalidExport("backup records do not match the manifest"))
+    }
+
+    // read every record back
+    let mut report = RestoreReport {
+        restored: export.records(),
+        ..RestoreReport::default()
+    };
+    let mut export = ExportReader::new(BufReader::new(File::open(&records_path)?), backup_key)?;
+    while let Some((token, data)) = export.next_record()? {
+        match vault.try_retrieve(&token).await? {
+            Some(restored) if restored == data => report.verified += 1,
+            _ => report.mismatched.push(token),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::backup::RecordsChecksum;
+
+    #[test]
+    fn test_records_checksum() {
+        let checksum = |key: &[u8], records: &[(&str, &str)]| {
+            let mut checksum = RecordsChecksum::new(key);
+            for (token, data) in records {
+                checksum.update(token, data);
+            }
+            checksum.finalize()
+        };
+        let records = [("abc", "123"), ("def", "456")];
+        assert_eq!(checksum(b"backup key", &records), checksum(b"backup key", &records));
+        assert_ne!(checksum(b"backup key", &records), checksum(b"other key", &records));
+        assert_ne!(checksum(b"backup key", &records), checksum(b"backup key", &[("def", "456"), ("abc", "123")]));
+        assert_ne!(checksum(b"backup key", &[("ab", "c123")]), checksum(b"backup key", &[("abc", "123")]));
+    }
+}
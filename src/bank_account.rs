@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use crate::masked::mask_number;
+use crate::record::VaultRecord;
+use crate::utils::Salt;
+use crate::validation::{validate_account_number, validate_routing_number, ValidationError};
+use std::fmt;
+
+/// The kind of account an ACH entry debits or credits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountType {
+    #[default]
+    Checking,
+    Savings,
+}
+
+/// A US bank account as an ACH entry names it, vaulted alongside
+/// credit cards
+///
+/// Stored with `DataVault::store_record`, encrypted like any other
+/// record.  The routing number must pass the ABA checksum and the
+/// account number be 4 to 17 digits or `store_record` returns
+/// `DataVaultError::Validation`.  `Debug` masks the account number.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{AccountType, BankAccount, DataVault, RedisDataVault};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let bank_account = BankAccount {
+///     routing_number: "011000015".to_string(),
+///     account_number: "000123456789".to_string(),
+///     account_type: AccountType::Checking,
+///     holder_name: "Graydon Hoare".to_string(),
+/// };
+/// let token = data_vault.store_record(&bank_account).await.unwrap();
+/// let bank_account: BankAccount = data_vault.retrieve_record(&token).await.unwrap();
+/// ```
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BankAccount {
+    /// the 9 digit ABA routing transit number of the bank
+    pub routing_number: String,
+    pub account_number: String,
+    #[serde(default)]
+    pub account_type: AccountType,
+    pub holder_name: String,
+}
+
+impl VaultRecord for BankAccount {
+    /// A salted blake3 hash of the routing number, account number
+    /// and account type, like `Blake3Tokenizer` makes of a card, the
+    /// holder name is not part of it
+    fn generate_token(&self) -> String {
+        let account_type = match self.account_type {
+            AccountType::Checking => "checking",
+            AccountType::Savings => "savings",
+        };
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.routing_number.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(self.account_number.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(account_type.as_bytes());
+        hasher.update(Salt::generate(32).as_bytes());
+        hasher.finalize().to_hex().to_string()
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        validate_routing_number(&self.routing_number)?;
+        validate_account_number(&self.account_number)
+    }
+}
+
+impl fmt::Debug for BankAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BankAccount")
+            .field("routing_number", &self.routing_number)
+            .field("account_number", &mask_number(&self.account_number))
+            .field("account_type", &self.account_type)
+            .field("holder_name", &self.holder_name)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bank_account::{AccountType, BankAccount};
+    use crate::record::VaultRecord;
+    use crate::validation::ValidationError;
+
+    fn bank_account() -> BankAccount {
+        BankAccount {
+            routing_number: "011000015".to_string(),
+            account_number: "000123456789".to_string(),
+            account_type: AccountType::Savings,
+            holder_name: "Graydon Hoare".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bank_account() {
+        let mut bank_account = bank_account();
+        assert_eq!(bank_account.validate(), Ok(()));
+        assert!(!format!("{:?}", bank_account).contains("000123456789"));
+        assert_eq!(bank_account.generate_token().len(), 64);
+        assert_ne!(bank_account.generate_token(), bank_account.generate_token());
+
+        let json = serde_json::to_value(&bank_account).unwrap();
+        assert_eq!(json["account_type"], "savings");
+        assert_eq!(serde_json::from_value::<BankAccount>(json).unwrap(), bank_account);
+
+        bank_account.routing_number = "011000016".to_string();
+        assert_eq!(bank_account.validate(), Err(ValidationError::InvalidRoutingNumber));
+        bank_account.routing_number = "011000015".to_string();
+        bank_account.account_number = "12".to_string();
+        assert_eq!(bank_account.validate(), Err(ValidationError::InvalidAccountNumber));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bank_account_postgres() {
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::error::DataVaultError;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use crate::traits::DataVault;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("bank-account-test").unwrap();
+        let bank_account = bank_account();
+        let token = vault.store_record(&bank_account).await.unwrap();
+        assert_eq!(vault.retrieve_record::<BankAccount>(&token).await.unwrap(), bank_account);
+
+        let invalid = BankAccount { routing_number: "123456789".to_string(), ..bank_account };
+        let stored = vault.store_record(&invalid).await;
+        assert!(matches!(stored, Err(DataVaultError::Validation(ValidationError::InvalidRoutingNumber))));
+
+        vault.delete_many(&[token]).await.unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+//! Issuer country, card type and scheme of stored cards by their BIN
+//! (the leading digits of the card number), readable without
+//! detokenizing
+//!
+//! A `BinLookupVault` looks up the BIN of every card it stores in a
+//! `BinSource` and keeps the `BinInfo` next to the card, in the
+//! namespace of the vault followed by `~bin`, e.g. `tenant-a~bin`.
+//! `BinLookupVault::bin_info` reads it back for routing, fraud rules
+//! or reporting without reading the card.
+//!
+//! `BinTable::embedded` knows the BINs of common test cards only,
+//! load a licensed BIN table with `BinTable::from_csv`, or implement
+//! `BinSource` over another data source.
+//!
+//! # example
+//! ```rust,ignore
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::bin::{BinTable, CardType};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//! use std::sync::Arc;
+//!
+//! let table = BinTable::from_csv(std::fs::File::open("bins.csv").unwrap()).unwrap();
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+//!     .with_bin_lookup(Arc::new(table));
+//! let token = vault.store_credit_card(&credit_card).await.unwrap();
+//! let info = vault.bin_info(&token).await.unwrap().unwrap();
+//! assert_eq!(info.card_type, Some(CardType::Debit));
+//! ```
+
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::brand::{detect_brand, CardBrand};
+use std::error;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The leading digits kept in `BinInfo::bin`
+const BIN_LENGTH: usize = 6;
+
+/// The BINs `BinTable::embedded` knows
+const EMBEDDED_TABLE: &str = include_str!("../data/bins.csv");
+
+/// How a card is funded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardType {
+    Credit,
+    Debit,
+    Prepaid,
+}
+
+/// What the BIN of a card tells about it, nothing that identifies
+/// the card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinInfo {
+    /// the first 6 digits of the card number
+    pub bin: String,
+    pub scheme: Option<CardBrand>,
+    pub card_type: Option<CardType>,
+    /// the ISO 3166-1 alpha-2 code of the issuer's country, e.g. `US`
+    pub country: Option<String>,
+    /// the name of the issuing bank
+    pub issuer: Option<String>,
+}
+
+/// Where `BinLookupVault` looks up card numbers, e.g. a `BinTable`
+/// or a client of a BIN lookup service with a local cache
+pub trait BinSource: Send + Sync {
+    /// The `BinInfo` of the card number `pan`, `None` if nothing is
+    /// known about it
+    fn lookup(&self, pan: &str) -> Option<BinInfo>;
+}
+
+/// One row of a `BinTable`
+#[derive(Debug, Clone)]
+struct BinRange {
+    prefix: String,
+    scheme: Option<CardBrand>,
+    card_type: Option<CardType>,
+    country: Option<String>,
+    issuer: Option<String>,
+}
+
+/// BIN ranges by their leading digits, the longest matching prefix
+/// wins
+///
+/// Card numbers matching no range get the scheme `detect_brand`
+/// tells from their number and nothing else.
+#[derive(Debug, Clone, Default)]
+pub struct BinTable {
+    // longest prefixes first
+    ranges: Vec<BinRange>,
+}
+
+impl BinTable {
+    /// The BINs of the test cards of the major schemes and processors,
+    /// e.g. `424242` and `378282`, enough for tests and development
+    pub fn embedded() -> Self {
+        BinTable::from_csv(EMBEDDED_TABLE.as_bytes()).expect("the embedded BIN table is valid")
+    }
+
+    /// A table read from CSV with the header
+    /// `prefix,scheme,card_type,country,issuer`
+    ///
+    /// `prefix` is 1 to 11 digits, `scheme` a `CardBrand` name such
+    /// as `visa`, `card_type` one of `credit`, `debit` and `prepaid`.
+    /// Every column but `prefix` may be empty.  Fields are not
+    /// quoted, issuer names can not contain commas.
+    /// returns:
+    ///     * an `io::ErrorKind::InvalidData` error for a row that is
+    ///       not a BIN range
+    pub fn from_csv<R: Read>(reader: R) -> io::Result<Self> {
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason);
+        let mut ranges = Vec::new();
+        for line in BufReader::new(reader).lines().skip(1) {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() != 5 {
+                return Err(invalid("BIN table rows have 5 columns"))
+            }
+            let prefix = fields[0];
+            if !(1..=11).contains(&prefix.len()) || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid("BIN prefix is not 1 to 11 digits"))
+            }
+            let optional = |field: &str| Some(field.to_string()).filter(|field| !field.is_empty());
+            ranges.push(BinRange {
+                prefix: prefix.to_string(),
+                scheme: optional(fields[1])
+                    .map(|scheme| serde_json::from_value(scheme.to_ascii_lowercase().into()))
+                    .transpose()
+                    .map_err(|_| invalid("unknown card scheme"))?,
+                card_type: optional(fields[2])
+                    .map(|card_type| serde_json::from_value(card_type.to_ascii_lowercase().into()))
+                    .transpose()
+                    .map_err(|_| invalid("card type is not credit, debit or prepaid"))?,
+                country: optional(fields[3]).map(|country| country.to_ascii_uppercase()),
+                issuer: optional(fields[4]),
+            });
+        }
+        ranges.sort_by_key(|range| std::cmp::Reverse(range.prefix.len()));
+        Ok(BinTable { ranges })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl BinSource for BinTable {
+    fn lookup(&self, pan: &str) -> Option<BinInfo> {
+        if pan.len() < BIN_LENGTH || !pan.bytes().all(|b| b.is_ascii_digit()) {
+            return None
+        }
+        let bin = pan[..BIN_LENGTH].to_string();
+        let detected = detect_brand(pan);
+
+        match self.ranges.iter().find(|range| pan.starts_with(&range.prefix)) {
+            Some(range) => Some(BinInfo {
+                bin,
+                scheme: range.scheme.or(detected),
+                card_type: range.card_type,
+                country: range.country.clone(),
+                issuer: range.issuer.clone(),
+            }),
+            None => detected.map(|scheme| BinInfo {
+                bin,
+                scheme: Some(scheme),
+                card_type: None,
+                country: None,
+                issuer: None,
+            }),
+        }
+    }
+}
+
+/// A vault keeping the `BinInfo` of each card it stores, see the
+/// module documentation and `DataVault::with_bin_lookup`
+///
+/// `store_credit_card`, `tokenize`, `store_credit_card_with_token`
+/// and `update_credit_card_if_version` store the card, then its
+/// `BinInfo`, failing if that fails.  `rotate_token` moves it to the
+/// new token, `soft_delete` and `delete_many` delete it.  Records
+/// that expire, see `CvvPolicy::Expire`, leave theirs behind until
+/// they are deleted.
+pub struct BinLookupVault<V> {
+    inner: V,
+    source: Arc<dyn BinSource>,
+}
+
+impl<V> BinLookupVault<V>
+    where
+        V: DataVault,
+{
+    pub fn new(inner: V, source: Arc<dyn BinSource>) -> Self {
+        BinLookupVault { inner, source }
+    }
+
+    /// The wrapped vault, cards stored through it get no `BinInfo`
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// The `BinInfo` of the card stored under `token`, read without
+    /// reading the card
+    /// returns:
+    ///     * `None` for tokens stored without a `BinInfo`, e.g. of
+    ///       cards of an unknown scheme or by another vault
+    pub async fn bin_info(&self, token: &str) -> Result<Option<BinInfo>, DataVaultError> {
+        match self.bin_vault()?.try_retrieve(token).await? {
+            Some(info) => Ok(Some(serde_json::from_str(&info)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The vault keeping the `BinInfo`s
+    fn bin_vault(&self) -> Result<V, DataVaultError> {
+        self.inner.with_side_namespace("bin")
+    }
+
+    async fn store_bin_info(&self, token: &str, credit_card: &CreditCard) -> Result<(), DataVaultError> {
+        match self.source.lookup(&credit_card.number) {
+            Some(info) => self.bin_vault()?.store(token, &serde_json::to_string(&info)?).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for BinLookupVault<V>
+    where
+        V: DataVault,
+{
+    /// Looks up cards in `BinTable::embedded`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(BinLookupVault::new(V::new()?, Arc::new(BinTable::embedded())))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.inner.store(token, string).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.inner.store_if_absent(token, string).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.inner.store_expiring(token, string, ttl).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        let token = self.inner.store_credit_card(credit_card).await?;
+        self.store_bin_info(&token, credit_card).await?;
+        Ok(token)
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        let (token, created) = self.inner.tokenize(credit_card).await?;
+        self.store_bin_info(&token, credit_card).await?;
+        Ok((token, created))
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.inner.store_credit_card_with_token(token, credit_card, overwrite).await?;
+        self.store_bin_info(token, credit_card).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inner.retrieve(token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.inner.retrieve_credit_card(token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.inner.exists(token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.inner.retrieve_credit_card_with_version(token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.inner.retrieve_with_metadata(token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let version = self.inner.update_credit_card_if_version(token, credit_card, expected_version).await?;
+        self.store_bin_info(token, credit_card).await?;
+        Ok(version)
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        let new_token = self.inner.rotate_token(token).await?;
+        let bin_vault = self.bin_vault()?;
+        if let Some(info) = bin_vault.try_retrieve(token).await? {
+            bin_vault.store(&new_token, &info).await?;
+            bin_vault.delete_many(&[token.to_string()]).await?;
+        }
+        Ok(new_token)
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.inner.soft_delete(token).await?;
+        self.bin_vault()?.delete_many(&[token.to_string()]).await?;
+        Ok(())
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.inner.touch(token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let deleted = self.inner.delete_many(tokens).await?;
+        self.bin_vault()?.delete_many(tokens).await?;
+        Ok(deleted)
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.inner.purge_expired().await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.inner.decrypted_records_page(cursor, limit).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.inner.count().await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.inner.stats().await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault looks up cards in the same source
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(BinLookupVault::new(self.inner.with_namespace(namespace)?, self.source.clone()))
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(BinLookupVault::new(self.inner.with_side_namespace(kind)?, self.source.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bin::{BinSource, BinTable, CardType};
+    use crate::brand::CardBrand;
+
+    #[test]
+    fn test_bin_table() {
+        let table = BinTable::embedded();
+        assert!(!table.is_empty());
+
+        let info = table.lookup("5200828282828210").unwrap();
+        assert_eq!(info.bin, "520082");
+        assert_eq!(info.scheme, Some(CardBrand::Mastercard));
+        assert_eq!(info.card_type, Some(CardType::Debit));
+        assert_eq!(info.country.as_deref(), Some("US"));
+
+        // unknown BIN of a known scheme
+        let info = table.lookup("4000123412341234").unwrap();
+        assert_eq!(info.scheme, Some(CardBrand::Visa));
+        assert_eq!(info.card_type, None);
+        assert!(table.lookup("9999999999999999").is_none());
+        assert!(table.lookup("4111").is_none());
+
+        let csv = "prefix,scheme,card_type,country,issuer\n4,visa,,,\n41111111,,prepaid,gb,Test Bank\n";
+        let table = BinTable::from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(table.len(), 2);
+        let info = table.lookup("4111111111111111").unwrap();
+        assert_eq!(info.card_type, Some(CardType::Prepaid));
+        assert_eq!(info.country.as_deref(), Some("GB"));
+        assert_eq!(info.issuer.as_deref(), Some("Test Bank"));
+        // the scheme of the number when the row has none
+        assert_eq!(info.scheme, Some(CardBrand::Visa));
+        assert_eq!(table.lookup("4222222222222").unwrap().card_type, None);
+
+        assert!(BinTable::from_csv("prefix,scheme,card_type,country,issuer\n4x,visa,,,\n".as_bytes()).is_err());
+        assert!(BinTable::from_csv("prefix,scheme,card_type,country,issuer\n4,bitcoin,,,\n".as_bytes()).is_err());
+        assert!(BinTable::from_csv("prefix,scheme,card_type,country,issuer\n4,visa,charge,,\n".as_bytes()).is_err());
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bin_lookup_vault() {
+        use credit_card::CreditCard;
+        use crate::traits::DataVault;
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use std::sync::Arc;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("bin-test").unwrap()
+            .with_bin_lookup(Arc::new(BinTable::embedded()));
+        let cc = CreditCard {
+            number: "5105105105105100".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let info = vault.bin_info(&token).await.unwrap().unwrap();
+        assert_eq!(info.card_type, Some(CardType::Prepaid));
+        // kept apart from the cards
+        assert!(vault.inner().with_side_namespace("bin").unwrap().exists(&token).await.unwrap());
+
+        let token = vault.rotate_token(&token).await.unwrap();
+        assert_eq!(vault.bin_info(&token).await.unwrap().unwrap().bin, "510510");
+        vault.delete_many(std::slice::from_ref(&token)).await.unwrap();
+        assert_eq!(vault.bin_info(&token).await.unwrap(), None);
+    }
+}
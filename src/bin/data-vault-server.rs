@@ -0,0 +1,10 @@
+//! A REST tokenization service, configured from the environment,
+//! see `data_vault::server` and `ServerConfig`
+
+use data_vault::ServerConfig;
+use std::error;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<(), Box<dyn error::Error>> {
+    data_vault::server::run(ServerConfig::from_env()?).await
+}
\ No newline at end of file
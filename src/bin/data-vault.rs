@@ -0,0 +1,14 @@
+//! The `data-vault` command line tool, see `data_vault::cli`
+
+use clap::Parser;
+use data_vault::cli::{self, Cli};
+use std::io;
+use std::process;
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() {
+    if let Err(e) = cli::run(Cli::parse(), io::stdin().lock(), io::stdout().lock()).await {
+        eprintln!("error: {}", data_vault::redact(&e.to_string()));
+        process::exit(1);
+    }
+}
\ No newline at end of file
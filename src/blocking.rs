@@ -0,0 +1,423 @@
+use credit_card::CreditCard;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::hooks::{HookedDataVault, VaultHooks};
+use crate::record::VaultRecord;
+use crate::backup::{BackupManifest, RestoreReport};
+use futures::StreamExt;
+#[cfg(feature = "redis")]
+use crate::redis_data_vault::RedisDataVault;
+#[cfg(feature = "postgres")]
+use crate::postgres_data_vault::PostgresDataVault;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+use crate::serializer::JsonSerializer;
+use tokio::runtime::{Builder, Runtime};
+use std::error;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A synchronous data vault for applications that are not async
+///
+/// Wraps any `DataVault` together with its own single threaded
+/// tokio runtime and blocks the calling thread until each
+/// operation completes.  Pools are created inside that runtime
+/// so no tokio setup is needed by the caller.
+///
+/// These methods must not be called from within an async
+/// runtime, doing so will panic.
+///
+/// # Examples
+/// ```rust
+/// use data_vault::blocking::BlockingRedisDataVault;
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// let data_vault = BlockingRedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// ```
+pub struct BlockingDataVault<V> {
+    inner: V,
+    runtime: Arc<Runtime>,
+}
+
+/// `RedisDataVault` with a blocking API
+#[cfg(feature = "redis")]
+pub type BlockingRedisDataVault<E, T, S = JsonSerializer> = BlockingDataVault<RedisDataVault<E, T, S>>;
+
+/// `PostgresDataVault` with a blocking API
+#[cfg(feature = "postgres")]
+pub type BlockingPostgresDataVault<E, T, S = JsonSerializer> = BlockingDataVault<PostgresDataVault<E, T, S>>;
+
+impl<V> BlockingDataVault<V>
+    where
+        V: DataVault,
+{
+    /// Create a new blocking vault and the runtime that drives it
+    /// # examples
+    /// ```rust
+    /// use data_vault::blocking::BlockingPostgresDataVault;
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// let data_vault = BlockingPostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// ```
+    pub fn new() -> Result<Self, Box<dyn error::Error>> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let inner = {
+            let _guard = runtime.enter();
+            V::new()?
+        };
+
+        Ok(BlockingDataVault {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Encrypt and Store a string with the given token
+    /// see `DataVault::store`
+    pub fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.runtime.block_on(self.inner.store(token, string))
+    }
+
+    /// Store a string unless the token is taken
+    /// see `DataVault::store_if_absent`
+    pub fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.runtime.block_on(self.inner.store_if_absent(token, string))
+    }
+
+    /// Store a string that expires after `ttl`
+    /// see `DataVault::store_expiring`
+    pub fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.runtime.block_on(self.inner.store_expiring(token, string, ttl))
+    }
+
+    /// Store the credit card in the data vault
+    /// see `DataVault::store_credit_card`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::blocking::BlockingRedisDataVault;
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use credit_card::CreditCard;
+    ///
+    /// let cc = CreditCard {
+    ///    number: "4111111111111111".to_string(),
+    ///    cardholder_name: "Graydon Hoare".to_string(),
+    ///    expiration_month: "01".to_string(),
+    ///    expiration_year: "2023".to_string(),
+    ///    brand: None,
+    ///    security_code: None
+    /// };
+    ///
+    /// let data_vault = BlockingRedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let token = data_vault.store_credit_card(&cc).unwrap();
+    /// let credit_card = data_vault.retrieve_credit_card(&token).unwrap();
+    /// ```
+    pub fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.runtime.block_on(self.inner.store_credit_card(credit_card))
+    }
+
+    /// Get or create the token for a credit card
+    /// see `DataVault::tokenize`
+    pub fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.runtime.block_on(self.inner.tokenize(credit_card))
+    }
+
+    /// Store the credit card under a token chosen by the caller
+    /// see `DataVault::store_credit_card_with_token`
+    pub fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.runtime.block_on(self.inner.store_credit_card_with_token(token, credit_card, overwrite))
+    }
+
+    /// Get decrypted arbitrary data from the vault by token
+    /// see `DataVault::retrieve`
+    pub fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve(token))
+    }
+
+    /// Get the credit card from the data vault given a token
+    /// see `DataVault::retrieve_credit_card`
+    pub fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve_credit_card(token))
+    }
+
+    /// Whether a record is stored under `token`
+    /// see `DataVault::exists`
+    pub fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.runtime.block_on(self.inner.exists(token))
+    }
+
+    /// Get decrypted data or `None` if the token is not stored
+    /// see `DataVault::try_retrieve`
+    pub fn try_retrieve(&self, token: &str) -> Result<Option<String>, DataVaultError> {
+        self.runtime.block_on(self.inner.try_retrieve(token))
+    }
+
+    /// Get the credit card or `None` if the token is not stored
+    /// see `DataVault::try_retrieve_credit_card`
+    pub fn try_retrieve_credit_card(&self, token: &str) -> Result<Option<CreditCard>, DataVaultError> {
+        self.runtime.block_on(self.inner.try_retrieve_credit_card(token))
+    }
+
+    /// Store any record
+    /// see `DataVault::store_record`
+    pub fn store_record<R: VaultRecord>(&self, record: &R) -> Result<String, DataVaultError> {
+        self.runtime.block_on(self.inner.store_record(record))
+    }
+
+    /// Get a record stored with `store_record`
+    /// see `DataVault::retrieve_record`
+    pub fn retrieve_record<R: VaultRecord>(&self, token: &str) -> Result<R, DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve_record(token))
+    }
+
+    /// Get a record or `None` if the token is not stored
+    /// see `DataVault::try_retrieve_record`
+    pub fn try_retrieve_record<R: VaultRecord>(&self, token: &str) -> Result<Option<R>, DataVaultError> {
+        self.runtime.block_on(self.inner.try_retrieve_record(token))
+    }
+
+    /// Get the credit card and the version it is at
+    /// see `DataVault::retrieve_credit_card_with_version`
+    pub fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve_credit_card_with_version(token))
+    }
+
+    /// Get the credit card with its metadata
+    /// see `DataVault::retrieve_with_metadata`
+    pub fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve_with_metadata(token))
+    }
+
+    /// Replace the credit card only if it is still at `expected_version`
+    /// see `DataVault::update_credit_card_if_version`
+    pub fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.runtime.block_on(self.inner.update_credit_card_if_version(token, credit_card, expected_version))
+    }
+
+    /// Move a record to a new random token
+    /// see `DataVault::rotate_token`
+    pub fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.runtime.block_on(self.inner.rotate_token(token))
+    }
+
+    /// Hide a record until the retention period is over
+    /// see `DataVault::soft_delete`
+    pub fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.runtime.block_on(self.inner.soft_delete(token))
+    }
+
+    /// Restart the time to live of a record
+    /// see `DataVault::touch`
+    pub fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.runtime.block_on(self.inner.touch(token, ttl))
+    }
+
+    /// Remove the records of `tokens` right away
+    /// see `DataVault::delete_many`
+    pub fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.runtime.block_on(self.inner.delete_many(tokens))
+    }
+
+    /// Remove records whose retention period is over
+    /// see `DataVault::purge_expired`
+    pub fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.runtime.block_on(self.inner.purge_expired())
+    }
+
+    /// Every record as `(token, ciphertext)`
+    /// see `DataVault::iter_records`
+    pub fn iter_records(&self) -> BlockingRecordIter<'_, Vec<u8>> {
+        BlockingRecordIter {
+            stream: self.inner.iter_records(),
+            runtime: &self.runtime,
+        }
+    }
+
+    /// Every record as `(token, decrypted string)`
+    /// see `DataVault::iter_decrypted_records`
+    pub fn iter_decrypted_records(&self) -> BlockingRecordIter<'_, String> {
+        BlockingRecordIter {
+            stream: self.inner.iter_decrypted_records(),
+            runtime: &self.runtime,
+        }
+    }
+
+    /// About `limit` decrypted records starting at `cursor`
+    /// see `DataVault::decrypted_records_page`
+    pub fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.runtime.block_on(self.inner.decrypted_records_page(cursor, limit))
+    }
+
+    /// Write an encrypted export of every record to `writer`
+    /// see `DataVault::export`
+    pub fn export<W: Write + Send>(&self, writer: W, export_key: &[u8]) -> Result<u64, DataVaultError> {
+        self.runtime.block_on(self.inner.export(writer, export_key))
+    }
+
+    /// Store every record of an export
+    /// see `DataVault::import`
+    pub fn import<R: Read + Send>(&self, reader: R, export_key: &[u8]) -> Result<u64, DataVaultError> {
+        self.runtime.block_on(self.inner.import(reader, export_key))
+    }
+
+    /// Back up every record to a directory
+    /// see `DataVault::backup`
+    pub fn backup<P: AsRef<Path> + Send>(&self, destination: P, backup_key: &[u8]) -> Result<BackupManifest, DataVaultError> {
+        self.runtime.block_on(self.inner.backup(destination, backup_key))
+    }
+
+    /// Store and verify every record of a backup
+    /// see `DataVault::restore`
+    pub fn restore<P: AsRef<Path> + Send>(&self, source: P, backup_key: &[u8]) -> Result<RestoreReport, DataVaultError> {
+        self.runtime.block_on(self.inner.restore(source, backup_key))
+    }
+
+    /// Number of records in the vault
+    /// see `DataVault::count`
+    pub fn count(&self) -> Result<u64, DataVaultError> {
+        self.runtime.block_on(self.inner.count())
+    }
+
+    /// Storage statistics
+    /// see `DataVault::stats`
+    pub fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.runtime.block_on(self.inner.stats())
+    }
+
+    /// see `DataVault::health_check`
+    pub fn health_check(&self) -> HealthReport {
+        self.runtime.block_on(self.inner.health_check())
+    }
+
+    /// The namespace every operation of this vault is scoped to
+    /// see `DataVault::namespace`
+    pub fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    /// see `DataVault::backend`
+    pub fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// A vault scoped to `namespace` sharing this one's runtime
+    /// see `DataVault::with_namespace`
+    pub fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(BlockingDataVault {
+            inner: self.inner.with_namespace(namespace)?,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// This vault calling `hooks` after each operation, sharing
+    /// this one's runtime
+    /// see `DataVault::with_hooks`
+    pub fn with_hooks(self, hooks: Arc<dyn VaultHooks>) -> BlockingDataVault<HookedDataVault<V>> {
+        BlockingDataVault {
+            inner: self.inner.with_hooks(hooks),
+            runtime: self.runtime,
+        }
+    }
+
+    /// `store` in `namespace`
+    /// see `DataVault::store_in`
+    pub fn store_in(&self, namespace: &str, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.runtime.block_on(self.inner.store_in(namespace, token, string))
+    }
+
+    /// `store_credit_card` in `namespace`
+    /// see `DataVault::store_credit_card_in`
+    pub fn store_credit_card_in(&self, namespace: &str, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.runtime.block_on(self.inner.store_credit_card_in(namespace, credit_card))
+    }
+
+    /// `retrieve` from `namespace`
+    /// see `DataVault::retrieve_from`
+    pub fn retrieve_from(&self, namespace: &str, token: &str) -> Result<String, DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve_from(namespace, token))
+    }
+
+    /// `retrieve_credit_card` from `namespace`
+    /// see `DataVault::retrieve_credit_card_from`
+    pub fn retrieve_credit_card_from(&self, namespace: &str, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.runtime.block_on(self.inner.retrieve_credit_card_from(namespace, token))
+    }
+
+    /// The wrapped async vault
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+}
+
+/// Iterator over the records of a `BlockingDataVault`, each batch
+/// is fetched when the previous one is used up
+pub struct BlockingRecordIter<'a, T> {
+    stream: RecordStream<'a, T>,
+    runtime: &'a Runtime,
+}
+
+impl<'a, T> Iterator for BlockingRecordIter<'a, T> {
+    type Item = Result<(String, T), DataVaultError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    #[cfg(feature = "redis")]
+    use crate::blocking::BlockingRedisDataVault;
+    #[cfg(feature = "postgres")]
+    use crate::blocking::BlockingPostgresDataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::tokenizer::Blake3Tokenizer;
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn store_retrieve_blocking_redis() {
+        let vault = BlockingRedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).unwrap();
+        let credit_card = vault.retrieve_credit_card(&token).unwrap();
+        assert_eq!(credit_card.number, cc.number)
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn store_retrieve_blocking_postgres() {
+        let vault = BlockingPostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).unwrap();
+        let credit_card = vault.retrieve_credit_card(&token).unwrap();
+        assert_eq!(credit_card.number, cc.number)
+    }
+}
@@ -0,0 +1,173 @@
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+/// The card network a card number belongs to, told by its leading
+/// digits (IIN), see `detect_brand`
+///
+/// Displayed and serialized with the names the `credit_card` crate
+/// gives them, e.g. `visa` or `amex`, which is what `store_credit_card`
+/// writes to a card's `brand`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardBrand {
+    Visa,
+    Mastercard,
+    Amex,
+    Discover,
+    DinersClub,
+    Jcb,
+    UnionPay,
+    Maestro,
+    Mir,
+}
+
+impl CardBrand {
+    /// The lowercase name of the brand, e.g. `mastercard`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CardBrand::Visa => "visa",
+            CardBrand::Mastercard => "mastercard",
+            CardBrand::Amex => "amex",
+            CardBrand::Discover => "discover",
+            CardBrand::DinersClub => "dinersclub",
+            CardBrand::Jcb => "jcb",
+            CardBrand::UnionPay => "unionpay",
+            CardBrand::Maestro => "maestro",
+            CardBrand::Mir => "mir",
+        }
+    }
+
+    /// Whether the brand issues card numbers of `length` digits,
+    /// e.g. 15 for Amex or 13, 16 and 19 for Visa
+    pub fn is_valid_length(&self, length: usize) -> bool {
+        match self {
+            CardBrand::Visa => matches!(length, 13 | 16 | 19),
+            CardBrand::Mastercard => length == 16,
+            CardBrand::Amex => length == 15,
+            CardBrand::DinersClub => (14..=19).contains(&length),
+            CardBrand::Maestro => (12..=19).contains(&length),
+            CardBrand::Discover | CardBrand::Jcb | CardBrand::UnionPay | CardBrand::Mir => (16..=19).contains(&length),
+        }
+    }
+}
+
+impl fmt::Display for CardBrand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The brand of `pan` by its IIN ranges, `None` for numbers of an
+/// unknown network or that are not all digits
+///
+/// Only the leading digits are looked at, the number is not
+/// validated, see `validate_number`.  Ranges shared by two networks
+/// go to the one issuing most cards in them, e.g. `622126` to
+/// `622925` to Discover.
+/// # example
+/// ```rust
+/// use data_vault::{detect_brand, CardBrand};
+///
+/// assert_eq!(detect_brand("4111111111111111"), Some(CardBrand::Visa));
+/// assert_eq!(detect_brand("378282246310005"), Some(CardBrand::Amex));
+/// assert_eq!(detect_brand("9999999999999999"), None);
+/// ```
+pub fn detect_brand(pan: &str) -> Option<CardBrand> {
+    if pan.len() < 6 || !pan.bytes().all(|b| b.is_ascii_digit()) {
+        return None
+    }
+    // 6 digits are enough for every range below
+    let prefix = |digits: usize| pan[..digits].parse::<u32>().unwrap_or_default();
+    let in_range = |low: u32, high: u32| {
+        let digits = low.to_string().len();
+        (low..=high).contains(&prefix(digits))
+    };
+
+    let brand = match pan.as_bytes()[0] {
+        b'4' => CardBrand::Visa,
+        b'2' if in_range(2200, 2204) => CardBrand::Mir,
+        b'2' if in_range(2221, 2720) => CardBrand::Mastercard,
+        b'3' if in_range(34, 34) || in_range(37, 37) => CardBrand::Amex,
+        b'3' if in_range(3528, 3589) => CardBrand::Jcb,
+        b'3' if in_range(300, 305) || in_range(3095, 3095) || in_range(36, 36) || in_range(38, 39) => CardBrand::DinersClub,
+        b'5' if in_range(51, 55) => CardBrand::Mastercard,
+        b'5' if in_range(50, 50) || in_range(56, 58) => CardBrand::Maestro,
+        b'6' if in_range(6011, 6011) || in_range(622126, 622925) || in_range(644, 649) || in_range(65, 65) => CardBrand::Discover,
+        b'6' if in_range(62, 62) => CardBrand::UnionPay,
+        b'6' if in_range(6304, 6304) || in_range(6759, 6759) || in_range(6761, 6763) => CardBrand::Maestro,
+        b'8' if in_range(8100, 8171) => CardBrand::UnionPay,
+        _ => return None,
+    };
+    Some(brand)
+}
+
+/// `credit_card` with the `brand` of its number when it has none,
+/// borrowed when there is nothing to fill in
+#[cfg_attr(not(any(feature = "redis", feature = "postgres")), allow(dead_code))]
+pub(crate) fn with_brand(credit_card: &CreditCard) -> Cow<'_, CreditCard> {
+    match (&credit_card.brand, detect_brand(&credit_card.number)) {
+        (None, Some(brand)) => {
+            let mut credit_card = credit_card.clone();
+            credit_card.brand = Some(brand.name().to_string());
+            Cow::Owned(credit_card)
+        },
+        _ => Cow::Borrowed(credit_card),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::brand::{detect_brand, with_brand, CardBrand};
+
+    #[test]
+    fn test_detect_brand() {
+        let cases = [
+            ("4111111111111111", Some(CardBrand::Visa)),
+            ("4222222222222", Some(CardBrand::Visa)),
+            ("5555555555554444", Some(CardBrand::Mastercard)),
+            ("2223003122003222", Some(CardBrand::Mastercard)),
+            ("378282246310005", Some(CardBrand::Amex)),
+            ("341111111111111", Some(CardBrand::Amex)),
+            ("6011111111111117", Some(CardBrand::Discover)),
+            ("6445644564456445", Some(CardBrand::Discover)),
+            ("6221261111111111", Some(CardBrand::Discover)),
+            ("30569309025904", Some(CardBrand::DinersClub)),
+            ("36227206271667", Some(CardBrand::DinersClub)),
+            ("3530111333300000", Some(CardBrand::Jcb)),
+            ("6200000000000005", Some(CardBrand::UnionPay)),
+            ("8171999927660000", Some(CardBrand::UnionPay)),
+            ("6304000000000000", Some(CardBrand::Maestro)),
+            ("5018000000000009", Some(CardBrand::Maestro)),
+            ("2200000000000004", Some(CardBrand::Mir)),
+            ("9999999999999999", None),
+            ("1234567890123", None),
+            ("4111", None),
+            ("4111-1111-1111-1111", None),
+        ];
+        for (pan, brand) in cases {
+            assert_eq!(detect_brand(pan), brand, "{}", pan);
+        }
+        assert_eq!(CardBrand::DinersClub.to_string(), "dinersclub");
+        assert_eq!(serde_json::to_string(&CardBrand::Jcb).unwrap(), "\"jcb\"");
+    }
+
+    #[test]
+    fn test_with_brand() {
+        let mut cc = CreditCard {
+            number: "5555555555554444".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        assert_eq!(with_brand(&cc).brand.as_deref(), Some("mastercard"));
+
+        // a brand given by the caller is kept
+        cc.brand = Some("maestro".to_string());
+        assert_eq!(with_brand(&cc).brand.as_deref(), Some("maestro"));
+    }
+}
@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use crate::masked::mask_number;
+use crate::record::VaultRecord;
+use crate::validation::{validate_number, ValidationError};
+use std::fmt;
+use std::time::Duration;
+
+/// The longest track data or an EMV payload is kept, long enough to
+/// forward it to the switch
+pub const MAX_CARD_PRESENT_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long track data and EMV payloads are kept unless given a
+/// `with_ttl`
+const DEFAULT_CARD_PRESENT_TTL_SECS: u64 = 60;
+
+fn default_ttl_secs() -> u64 {
+    DEFAULT_CARD_PRESENT_TTL_SECS
+}
+
+/// A time to live of at least a second and at most
+/// `MAX_CARD_PRESENT_TTL`
+fn validate_ttl(ttl_secs: u64) -> Result<(), ValidationError> {
+    match ttl_secs {
+        0 => Err(ValidationError::InvalidCardPresentTtl),
+        secs if secs > MAX_CARD_PRESENT_TTL.as_secs() => Err(ValidationError::InvalidCardPresentTtl),
+        _ => Ok(()),
+    }
+}
+
+/// The track 2 equivalent data of a magstripe swipe or of EMV tag
+/// `57`, staged for the moment between the terminal and the switch
+///
+/// Stored with `DataVault::store_record` under a random token and
+/// expiring after at most `MAX_CARD_PRESENT_TTL`, one minute unless
+/// set `with_ttl`.  Forward it with `DataVault::take_record`, which
+/// deletes it as it is read.  `store_record` returns
+/// `DataVaultError::Validation` for data that is not track 2.  `Debug`
+/// masks the card number and leaves out everything else.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{DataVault, RedisDataVault, Track2Data};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let token = data_vault.store_record(&Track2Data::new(";4111111111111111=25121010000012300000?")).await.unwrap();
+/// let track2: Track2Data = data_vault.take_record(&token).await.unwrap();
+/// ```
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Track2Data {
+    /// e.g. `;4111111111111111=25121010000012300000?`, the sentinels
+    /// are optional and `D` may separate the card number like in EMV
+    /// tag `57`
+    pub data: String,
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Track2Data {
+    /// Track data kept for one minute
+    pub fn new(data: &str) -> Self {
+        Track2Data {
+            data: data.to_string(),
+            ttl_secs: DEFAULT_CARD_PRESENT_TTL_SECS,
+        }
+    }
+
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        Track2Data { ttl_secs: ttl.as_secs(), ..self }
+    }
+
+    /// The card number, if the data is track 2
+    pub fn pan(&self) -> Option<&str> {
+        self.fields().map(|(pan, _, _)| pan)
+    }
+
+    /// The expiration month and 4 digit year, if the data is track 2
+    pub fn expiry(&self) -> Option<(String, String)> {
+        self.fields().map(|(_, expiry, _)| (expiry[2..].to_string(), format!("20{}", &expiry[..2])))
+    }
+
+    /// The 3 digit service code, e.g. `201` for a chip card
+    pub fn service_code(&self) -> Option<&str> {
+        self.fields().map(|(_, _, service_code)| service_code)
+    }
+
+    /// The card number, `YYMM` expiry and service code
+    fn fields(&self) -> Option<(&str, &str, &str)> {
+        let data = self.data.strip_prefix(';').unwrap_or(&self.data);
+        let data = data.strip_suffix('?').unwrap_or(data);
+        // EMV tag 57 pads odd lengths with an `F`
+        let data = data.strip_suffix('F').unwrap_or(data);
+        let (pan, rest) = data.split_once(['=', 'D'])?;
+        if rest.len() < 7 || !rest.bytes().all(|b| b.is_ascii_digit()) {
+            return None
+        }
+        let (expiry, service_code) = (&rest[..4], &rest[4..7]);
+        if !(1..=12).contains(&expiry[2..].parse::<u32>().ok()?) {
+            return None
+        }
+        Some((pan, expiry, service_code))
+    }
+}
+
+impl VaultRecord for Track2Data {
+    fn ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.ttl_secs))
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        let pan = self.pan().ok_or(ValidationError::InvalidTrackData)?;
+        validate_number(pan)?;
+        validate_ttl(self.ttl_secs)
+    }
+}
+
+impl fmt::Debug for Track2Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Track2Data")
+            .field("pan", &self.pan().map(mask_number))
+            .field("ttl_secs", &self.ttl_secs)
+            .finish()
+    }
+}
+
+/// The EMV tags a chip card and terminal produce for an
+/// authorization, e.g. the ARQC cryptogram in `9F26`, staged for the
+/// moment between the terminal and the switch
+///
+/// Stored with `DataVault::store_record` like `Track2Data`, with the
+/// same time to live and `DataVault::take_record` to forward it.
+/// `store_record` returns `DataVaultError::Validation` unless `tlv`
+/// is hex encoded BER-TLV.  `Debug` shows the tags without their
+/// values.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmvPayload {
+    /// hex encoded BER-TLV, e.g. `9F2608A1B2C3D4E5F60718`
+    pub tlv: String,
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl EmvPayload {
+    /// A payload kept for one minute
+    pub fn new(tlv: &str) -> Self {
+        EmvPayload {
+            tlv: tlv.to_string(),
+            ttl_secs: DEFAULT_CARD_PRESENT_TTL_SECS,
+        }
+    }
+
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        EmvPayload { ttl_secs: ttl.as_secs(), ..self }
+    }
+
+    /// The top level tags with their values, both upper case hex, in
+    /// the order of the payload
+    pub fn tags(&self) -> Result<Vec<(String, String)>, ValidationError> {
+        let bytes = hex::decode(&self.tlv).map_err(|_| ValidationError::InvalidEmvPayload)?;
+        let mut tags = Vec::new();
+        let mut rest = bytes.as_slice();
+        while !rest.is_empty() {
+            let (tag, value, next) = split_tlv(rest).ok_or(ValidationError::InvalidEmvPayload)?;
+            tags.push((hex::encode_upper(tag), hex::encode_upper(value)));
+            rest = next;
+        }
+        Ok(tags)
+    }
+
+    /// The value of `tag`, e.g. `9F26`, as upper case hex
+    pub fn tag(&self, tag: &str) -> Option<String> {
+        self.tags().ok()?.into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(tag))
+            .map(|(_, value)| value)
+    }
+}
+
+/// The tag, value and what follows of the BER-TLV at the start of
+/// `bytes`
+fn split_tlv(bytes: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    // the low 5 bits all set announce more tag bytes, each but the
+    // last with its high bit set
+    let mut tag_len = 1;
+    if bytes.first()? & 0x1F == 0x1F {
+        tag_len += bytes[1..].iter().position(|b| b & 0x80 == 0)? + 1;
+    }
+    let (tag, rest) = bytes.split_at(tag_len);
+
+    let (length, rest) = match *rest.first()? {
+        short if short < 0x80 => (usize::from(short), &rest[1..]),
+        long => {
+            let octets = usize::from(long & 0x7F);
+            if octets == 0 || octets > 2 || rest.len() <= octets {
+                return None
+            }
+            let length = rest[1..=octets].iter().fold(0, |length, b| length << 8 | usize::from(*b));
+            (length, &rest[1 + octets..])
+        },
+    };
+    if rest.len() < length {
+        return None
+    }
+    let (value, rest) = rest.split_at(length);
+    Some((tag, value, rest))
+}
+
+impl VaultRecord for EmvPayload {
+    fn ttl(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.ttl_secs))
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.tags()?.is_empty() {
+            return Err(ValidationError::InvalidEmvPayload)
+        }
+        validate_ttl(self.ttl_secs)
+    }
+}
+
+impl fmt::Debug for EmvPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags = self.tags().map(|tags| tags.into_iter().map(|(tag, _)| tag).collect::<Vec<String>>());
+        f.debug_struct("EmvPayload")
+            .field("tags", &tags.unwrap_or_default())
+            .field("ttl_secs", &self.ttl_secs)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::card_present::{EmvPayload, Track2Data, MAX_CARD_PRESENT_TTL};
+    use crate::record::VaultRecord;
+    use crate::validation::ValidationError;
+    use std::time::Duration;
+
+    #[test]
+    fn test_track2_data() {
+        let track2 = Track2Data::new(";4111111111111111=25121010000012300000?");
+        assert_eq!(track2.validate(), Ok(()));
+        assert_eq!(track2.pan(), Some("4111111111111111"));
+        assert_eq!(track2.expiry(), Some(("12".to_string(), "2025".to_string())));
+        assert_eq!(track2.service_code(), Some("101"));
+        assert_eq!(track2.ttl(), Some(Duration::from_secs(60)));
+        assert!(!format!("{:?}", track2).contains("4111111111111111"));
+
+        // EMV tag 57
+        let tag57 = Track2Data::new("4111111111111111D2512201000001230000F");
+        assert_eq!(tag57.pan(), Some("4111111111111111"));
+        assert_eq!(tag57.service_code(), Some("201"));
+
+        assert_eq!(Track2Data::new("4111111111111111").validate(), Err(ValidationError::InvalidTrackData));
+        assert_eq!(Track2Data::new("4111111111111111=2513101").validate(), Err(ValidationError::InvalidTrackData));
+        assert_eq!(Track2Data::new("4111111111111112=2512101").validate(), Err(ValidationError::LuhnCheckFailed));
+        let long = track2.with_ttl(MAX_CARD_PRESENT_TTL + Duration::from_secs(1));
+        assert_eq!(long.validate(), Err(ValidationError::InvalidCardPresentTtl));
+    }
+
+    #[test]
+    fn test_emv_payload() {
+        // 9F26 cryptogram, 9F02 amount, 5F2A currency and a 130 byte
+        // tag with a long form length
+        let tlv = format!("9F2608A1B2C3D4E5F607189F02060000000012345F2A0208409F7C8182{}", "00".repeat(130));
+        let payload = EmvPayload::new(&tlv);
+        assert_eq!(payload.validate(), Ok(()));
+        let tags = payload.tags().unwrap();
+        assert_eq!(tags.iter().map(|(tag, _)| tag.as_str()).collect::<Vec<&str>>(), ["9F26", "9F02", "5F2A", "9F7C"]);
+        assert_eq!(payload.tag("9f26").as_deref(), Some("A1B2C3D4E5F60718"));
+        assert_eq!(tags[3].1.len(), 260);
+        assert_eq!(format!("{:?}", payload), "EmvPayload { tags: [\"9F26\", \"9F02\", \"5F2A\", \"9F7C\"], ttl_secs: 60 }");
+
+        for tlv in ["", "9F2608A1B2", "9F", "zz", "9F2681"].iter() {
+            assert_eq!(EmvPayload::new(tlv).validate(), Err(ValidationError::InvalidEmvPayload), "{}", tlv);
+        }
+        let zero = payload.with_ttl(Duration::ZERO);
+        assert_eq!(zero.validate(), Err(ValidationError::InvalidCardPresentTtl));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_card_present_postgres() {
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::error::DataVaultError;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use crate::traits::DataVault;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("card-present-test").unwrap();
+        let track2 = Track2Data::new(";4111111111111111=25121010000012300000?");
+        let token = vault.store_record(&track2).await.unwrap();
+        assert_eq!(vault.take_record::<Track2Data>(&token).await.unwrap(), track2);
+        assert!(matches!(vault.take_record::<Track2Data>(&token).await, Err(DataVaultError::NotFound)));
+
+        let payload = EmvPayload::new("9F2608A1B2C3D4E5F60718").with_ttl(Duration::from_secs(1));
+        let token = vault.store_record(&payload).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(matches!(vault.take_record::<EmvPayload>(&token).await, Err(DataVaultError::NotFound)));
+    }
+}
@@ -0,0 +1,362 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::retry::sleep;
+use std::error;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Latency, timeouts and errors a `ChaosDataVault` injects into the
+/// operations of the vault it wraps
+///
+/// Every operation is delayed by `latency` and a random time up to
+/// `jitter`.  Then a share of `timeout_rate` of the operations waits
+/// `timeout` more and fails with an `io::ErrorKind::TimedOut` error,
+/// a share of `error_rate` fails with `io::ErrorKind::ConnectionReset`.
+/// Both are transient, see `DataVaultError::is_transient`, so they
+/// are retried like a dropped connection would be.
+///
+/// Configured with `DATA_VAULT_CHAOS_LATENCY_MS`,
+/// `DATA_VAULT_CHAOS_JITTER_MS`, `DATA_VAULT_CHAOS_TIMEOUT_RATE`,
+/// `DATA_VAULT_CHAOS_TIMEOUT_MS`, `DATA_VAULT_CHAOS_ERROR_RATE` and
+/// `DATA_VAULT_CHAOS_SEED`, by default nothing is injected.
+/// # example
+/// ```rust
+/// use data_vault::{ChaosPolicy, DataVault, RedisDataVault, RetryPolicy};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::time::Duration;
+///
+/// let chaos = ChaosPolicy::new()
+///     .with_latency(Duration::from_millis(5), Duration::from_millis(20))
+///     .with_error_rate(0.1)
+///     .with_seed(42);
+/// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let vault = RetryPolicy::new(4).retrying(chaos.injecting(vault));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChaosPolicy {
+    /// the delay of every operation
+    pub latency: Duration,
+    /// the most added to `latency` at random
+    pub jitter: Duration,
+    /// the share of operations timing out, from 0 to 1
+    pub timeout_rate: f64,
+    /// how long an operation hangs before it times out
+    pub timeout: Duration,
+    /// the share of operations failing, from 0 to 1
+    pub error_rate: f64,
+    /// the seed of the random faults, the same seed injects the same
+    /// faults into the same sequence of operations
+    pub seed: Option<u64>,
+}
+
+impl Default for ChaosPolicy {
+    fn default() -> Self {
+        ChaosPolicy {
+            latency: Duration::from_millis(0),
+            jitter: Duration::from_millis(0),
+            timeout_rate: 0.0,
+            timeout: Duration::from_secs(1),
+            error_rate: 0.0,
+            seed: None,
+        }
+    }
+}
+
+/// what `ChaosPolicy::draw` picked for one operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fault {
+    None,
+    Timeout,
+    Error,
+}
+
+impl ChaosPolicy {
+    /// Inject nothing, until configured otherwise
+    pub fn new() -> Self {
+        ChaosPolicy::default()
+    }
+
+    /// see `ChaosConfig::from_env`
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        Ok(crate::config::ChaosConfig::from_env(None)?.into())
+    }
+
+    pub fn with_latency(mut self, latency: Duration, jitter: Duration) -> Self {
+        self.latency = latency;
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_timeout_rate(mut self, timeout_rate: f64, timeout: Duration) -> Self {
+        self.timeout_rate = timeout_rate;
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_error_rate(mut self, error_rate: f64) -> Self {
+        self.error_rate = error_rate;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// `vault` with faults injected into its operations, see
+    /// `ChaosDataVault`
+    pub fn injecting<V: DataVault>(self, vault: V) -> ChaosDataVault<V> {
+        let rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        ChaosDataVault {
+            inner: vault,
+            policy: self,
+            rng: Arc::new(Mutex::new(rng)),
+        }
+    }
+
+    /// the delay and fault of the next operation
+    fn draw<R: Rng>(&self, rng: &mut R) -> (Duration, Fault) {
+        let delay = self.latency + self.jitter.mul_f64(rng.gen::<f64>());
+        let roll = rng.gen::<f64>();
+        let fault = if roll < self.timeout_rate {
+            Fault::Timeout
+        } else if roll < self.timeout_rate + self.error_rate {
+            Fault::Error
+        } else {
+            Fault::None
+        };
+        (delay, fault)
+    }
+}
+
+/// A vault injecting latency, timeouts and errors into the
+/// operations of another, as its `ChaosPolicy` says, to test how an
+/// application copes with a slow or failing vault
+///
+/// Faults are injected before the operation, a failed operation was
+/// not passed on.  Streams, `health_check`, `namespace` and `backend`
+/// are passed on untouched.  Vaults of other namespaces made by
+/// `with_namespace` share the random faults, so a seeded policy
+/// stays reproducible.
+pub struct ChaosDataVault<V> {
+    inner: V,
+    policy: ChaosPolicy,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl<V> ChaosDataVault<V>
+    where
+        V: DataVault,
+{
+    /// The wrapped vault, nothing is injected into operations on it
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    pub fn policy(&self) -> &ChaosPolicy {
+        &self.policy
+    }
+
+    /// the faults of the next operation, waited for
+    async fn inject(&self) -> Result<(), DataVaultError> {
+        let (delay, fault) = self.policy.draw(&mut *self.rng.lock().unwrap());
+        sleep(delay).await;
+        match fault {
+            Fault::None => Ok(()),
+            Fault::Timeout => {
+                sleep(self.policy.timeout).await;
+                Err(io::Error::new(io::ErrorKind::TimedOut, "timeout injected by ChaosDataVault").into())
+            },
+            Fault::Error => Err(io::Error::new(io::ErrorKind::ConnectionReset, "error injected by ChaosDataVault").into()),
+        }
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for ChaosDataVault<V>
+    where
+        V: DataVault,
+{
+    /// A vault from the environment injecting faults as
+    /// `ChaosPolicy::from_env` says
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(ChaosPolicy::from_env()?.injecting(V::new()?))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.inject().await?;
+        self.inner.store(token, string).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.inject().await?;
+        self.inner.store_if_absent(token, string).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.inject().await?;
+        self.inner.store_expiring(token, string, ttl).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.inject().await?;
+        self.inner.store_credit_card(credit_card).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.inject().await?;
+        self.inner.tokenize(credit_card).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.inject().await?;
+        self.inner.store_credit_card_with_token(token, credit_card, overwrite).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inject().await?;
+        self.inner.retrieve(token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.inject().await?;
+        self.inner.retrieve_credit_card(token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.inject().await?;
+        self.inner.exists(token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.inject().await?;
+        self.inner.retrieve_credit_card_with_version(token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.inject().await?;
+        self.inner.retrieve_with_metadata(token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.inject().await?;
+        self.inner.update_credit_card_if_version(token, credit_card, expected_version).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inject().await?;
+        self.inner.rotate_token(token).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.inject().await?;
+        self.inner.soft_delete(token).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.inject().await?;
+        self.inner.touch(token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.inject().await?;
+        self.inner.delete_many(tokens).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.inject().await?;
+        self.inner.purge_expired().await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.inject().await?;
+        self.inner.decrypted_records_page(cursor, limit).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.inject().await?;
+        self.inner.count().await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.inject().await?;
+        self.inner.stats().await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(ChaosDataVault {
+            inner: self.inner.with_namespace(namespace)?,
+            policy: self.policy,
+            rng: self.rng.clone(),
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(ChaosDataVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            policy: self.policy,
+            rng: self.rng.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::chaos::{ChaosPolicy, Fault};
+    use std::time::Duration;
+
+    #[test]
+    fn test_chaos_draw() {
+        let policy = ChaosPolicy::new()
+            .with_latency(Duration::from_millis(10), Duration::from_millis(5))
+            .with_timeout_rate(0.2, Duration::from_millis(100))
+            .with_error_rate(0.3);
+        let mut rng = StdRng::seed_from_u64(7);
+        let draws: Vec<(Duration, Fault)> = (0..10_000).map(|_| policy.draw(&mut rng)).collect();
+        assert!(draws.iter().all(|(delay, _)| *delay >= Duration::from_millis(10) && *delay <= Duration::from_millis(15)));
+
+        let share = |fault| draws.iter().filter(|(_, drawn)| *drawn == fault).count() as f64 / draws.len() as f64;
+        assert!((share(Fault::Timeout) - 0.2).abs() < 0.02);
+        assert!((share(Fault::Error) - 0.3).abs() < 0.02);
+
+        let mut again = StdRng::seed_from_u64(7);
+        assert!(draws.iter().take(100).all(|draw| *draw == policy.draw(&mut again)));
+        assert!((0..1000).all(|_| ChaosPolicy::new().draw(&mut rng) == (Duration::from_millis(0), Fault::None)));
+    }
+}
@@ -0,0 +1,419 @@
+//! The `data-vault` command line tool, for maintenance and one-off
+//! lookups without writing Rust
+//!
+//! The vault is configured like the library: by the file `--config`,
+//! see `Config::from_file`, or else by the environment variables
+//! `RedisDataVault::new` and `PostgresDataVault::new` read, with
+//! `--backend` and `--tokenizer` picking the vault.  Records are
+//! sealed with `AesGcmSivEncryption`.  Card numbers and key material
+//! are never taken as arguments, cards are read from stdin and keys
+//! from files.
+//!
+//! ```text
+//! $ data-vault store < card.json
+//! $ data-vault retrieve <token>
+//! $ data-vault --config data_vault.toml export --output vault.dvexport --key-file export.key
+//! $ data-vault migrate --to postgres.toml
+//! $ data-vault import --input portfolio.csv --mapping portfolio.tokens.csv
+//! $ data-vault rekey --new-key-file new.key
+//! $ data-vault verify
+//! ```
+
+use clap::{Parser, Subcommand};
+use credit_card::CreditCard;
+use dotenv::dotenv;
+use rand::RngCore;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::config::EncryptionSettings;
+use crate::config_file::{Backend, Config, TokenizerKind};
+use crate::encryption::AesGcmSivEncryption;
+use crate::import::{import_cards, resume_point, ImportFormat, ImportOptions, ImportProgress};
+use crate::migrate::{migrate, MigrateOptions, MigrationProgress, MigrationReport};
+use crate::postgres_data_vault::PostgresDataVault;
+use crate::redact::redact_token;
+use crate::redis_data_vault::RedisDataVault;
+use crate::tokenizer::{Blake3DeterministicTokenizer, Blake3Tokenizer, Tokenizer};
+use crate::verify::VerifyReport;
+use std::error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+type RedisVault<T> = RedisDataVault<AesGcmSivEncryption, T>;
+type PostgresVault<T> = PostgresDataVault<AesGcmSivEncryption, T>;
+
+/// Maintenance and one-off lookups of a data vault
+#[derive(Debug, Parser)]
+#[command(name = "data-vault", version)]
+pub struct Cli {
+    /// configuration file, see `Config::from_file`, instead of the
+    /// environment
+    #[arg(long, env = "DATA_VAULT_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// the backend configured by the environment
+    #[arg(long, value_enum, default_value = "redis")]
+    pub backend: Backend,
+    /// the tokenizer when configured by the environment
+    #[arg(long, value_enum, default_value = "random")]
+    pub tokenizer: TokenizerKind,
+    /// work in this namespace instead of the default one
+    #[arg(long)]
+    pub namespace: Option<String>,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Store the credit card read as JSON from stdin, print its token
+    Store,
+    /// Print the credit card stored under a token as JSON
+    Retrieve {
+        token: String,
+    },
+    /// Delete the records of tokens, print how many there were
+    Delete {
+        #[arg(required = true)]
+        tokens: Vec<String>,
+    },
+    /// Write every record to an encrypted export, see `DataVault::export`
+    Export {
+        /// the export file, must not exist
+        #[arg(long)]
+        output: PathBuf,
+        /// file holding the export key
+        #[arg(long)]
+        key_file: PathBuf,
+    },
+    /// Copy every record to the vault of another configuration file,
+    /// see `migrate`
+    Migrate {
+        /// configuration file of the destination
+        #[arg(long)]
+        to: PathBuf,
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+        /// the cursor a previous run printed last
+        #[arg(long)]
+        resume_from: Option<String>,
+        /// skip reading every record back from the destination
+        #[arg(long)]
+        no_verify: bool,
+    },
+    /// Tokenize the cards of a CSV or NDJSON file and write which row
+    /// got which token, see `import`
+    Import {
+        /// the file of cards
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ImportFormat,
+        /// the mapping of rows to tokens, an existing one is continued
+        /// after its last row
+        #[arg(long)]
+        mapping: PathBuf,
+        #[arg(long, default_value_t = 500)]
+        batch_size: usize,
+    },
+    /// Re-encrypt every record with a new key, stop writers first and
+    /// switch the configuration to the new key afterwards
+    Rekey {
+        /// file holding the new key
+        #[arg(long)]
+        new_key_file: PathBuf,
+        /// file holding the new IV, the current one is kept otherwise
+        #[arg(long)]
+        new_iv_file: Option<PathBuf>,
+        /// where the records are spooled to while they are re-encrypted,
+        /// sealed with a throw-away key, the temporary directory by default
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+    },
+    /// Check that every record decrypts and reads, exits with an
+    /// error when one does not
+    Verify,
+}
+
+/// A vault the tool can open from a `Config`
+#[async_trait::async_trait]
+trait ConfiguredVault: DataVault + Sized + 'static {
+    fn open(cfg: &Config) -> Result<Self, Box<dyn error::Error>>;
+    async fn verify_records(&self) -> Result<VerifyReport, DataVaultError>;
+}
+
+#[async_trait::async_trait]
+impl<T> ConfiguredVault for RedisVault<T>
+    where
+        T: Tokenizer + std::marker::Sync + std::marker::Send + 'static,
+{
+    fn open(cfg: &Config) -> Result<Self, Box<dyn error::Error>> {
+        RedisDataVault::from_config(cfg.redis.clone().ok_or("the configuration has no redis section")?)
+    }
+
+    async fn verify_records(&self) -> Result<VerifyReport, DataVaultError> {
+        self.verify().await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> ConfiguredVault for PostgresVault<T>
+    where
+        T: Tokenizer + std::marker::Sync + std::marker::Send + 'static,
+{
+    fn open(cfg: &Config) -> Result<Self, Box<dyn error::Error>> {
+        PostgresDataVault::from_config(cfg.postgres.clone().ok_or("the configuration has no postgres section")?)
+    }
+
+    async fn verify_records(&self) -> Result<VerifyReport, DataVaultError> {
+        self.verify().await
+    }
+}
+
+/// Run `cli`, reading cards from `input` and printing to `out`
+pub async fn run<R, W>(cli: Cli, input: R, mut out: W) -> Result<(), Box<dyn error::Error>>
+    where
+        R: Read,
+        W: Write,
+{
+    // for the `env` secrets of configuration files too
+    dotenv().ok();
+    let cfg = match &cli.config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::from_env(cli.backend, cli.tokenizer)?,
+    };
+    match (cfg.backend, cfg.tokenizer) {
+        (Backend::Redis, TokenizerKind::Random) => execute::<RedisVault<Blake3Tokenizer>, _, _>(&cli, &cfg, input, &mut out).await,
+        (Backend::Redis, TokenizerKind::Deterministic) => execute::<RedisVault<Blake3DeterministicTokenizer>, _, _>(&cli, &cfg, input, &mut out).await,
+        (Backend::Postgres, TokenizerKind::Random) => execute::<PostgresVault<Blake3Tokenizer>, _, _>(&cli, &cfg, input, &mut out).await,
+        (Backend::Postgres, TokenizerKind::Deterministic) => execute::<PostgresVault<Blake3DeterministicTokenizer>, _, _>(&cli, &cfg, input, &mut out).await,
+    }
+}
+
+async fn execute<V, R, W>(cli: &Cli, cfg: &Config, input: R, out: &mut W) -> Result<(), Box<dyn error::Error>>
+    where
+        V: ConfiguredVault,
+        R: Read,
+        W: Write,
+{
+    let namespace = cli.namespace.as_deref();
+    let vault: V = open_in(cfg, namespace)?;
+    match &cli.command {
+        Command::Store => {
+            let credit_card: CreditCard = serde_json::from_reader(input)
+                .map_err(|e| format!("stdin is not a credit card in JSON: {}", e))?;
+            writeln!(out, "{}", vault.store_credit_card(&credit_card).await?)?;
+        },
+        Command::Retrieve { token } => {
+            serde_json::to_writer(&mut *out, &vault.retrieve_credit_card(token).await?)?;
+            writeln!(out)?;
+        },
+        Command::Delete { tokens } => {
+            writeln!(out, "{}", vault.delete_many(tokens).await?)?;
+        },
+        Command::Export { output, key_file } => {
+            let export_key = read_secret(key_file)?;
+            let mut writer = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(output)?);
+            let exported = vault.export(&mut writer, export_key.as_bytes()).await?;
+            writer.get_ref().sync_all()?;
+            writeln!(out, "{} records exported", exported)?;
+        },
+        Command::Migrate { to, batch_size, resume_from, no_verify } => {
+            let destination = Config::from_file(to)?;
+            let options = MigrateOptions {
+                batch_size: *batch_size,
+                resume_from: resume_from.clone(),
+                verify: !no_verify,
+                on_progress: Some(Box::new(|progress: &MigrationProgress| match &progress.cursor {
+                    Some(cursor) => eprintln!("{} records migrated, resume with --resume-from {}", progress.migrated, cursor),
+                    None => eprintln!("{} records migrated", progress.migrated),
+                })),
+            };
+            let report = match (destination.backend, destination.tokenizer) {
+                (Backend::Redis, TokenizerKind::Random) => migrate_to::<_, RedisVault<Blake3Tokenizer>>(&vault, &destination, namespace, options).await?,
+                (Backend::Redis, TokenizerKind::Deterministic) => migrate_to::<_, RedisVault<Blake3DeterministicTokenizer>>(&vault, &destination, namespace, options).await?,
+                (Backend::Postgres, TokenizerKind::Random) => migrate_to::<_, PostgresVault<Blake3Tokenizer>>(&vault, &destination, namespace, options).await?,
+                (Backend::Postgres, TokenizerKind::Deterministic) => migrate_to::<_, PostgresVault<Blake3DeterministicTokenizer>>(&vault, &destination, namespace, options).await?,
+            };
+            writeln!(out, "{} records migrated, {} verified", report.migrated, report.verified)?;
+            if !report.mismatched.is_empty() {
+                let tokens: Vec<String> = report.mismatched.iter().map(|token| redact_token(token)).collect();
+                return Err(format!("{} records differ in the destination: {}", tokens.len(), tokens.join(", ")).into())
+            }
+        },
+        Command::Import { input, format, mapping, batch_size } => {
+            let skip_rows = match File::open(mapping) {
+                Ok(file) => resume_point(BufReader::new(file))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+                Err(e) => return Err(format!("{}: {}", mapping.display(), e).into()),
+            };
+            // a mapping without rows is started again, header included
+            let writer = match skip_rows {
+                0 => File::create(mapping)?,
+                _ => {
+                    eprintln!("continuing {} after row {}", mapping.display(), skip_rows);
+                    OpenOptions::new().append(true).open(mapping)?
+                },
+            };
+            let options = ImportOptions {
+                batch_size: *batch_size,
+                skip_rows,
+                on_progress: Some(Box::new(|progress: &ImportProgress| {
+                    eprintln!("row {}: {} imported, {} rejected", progress.row, progress.imported, progress.rejected)
+                })),
+                ..ImportOptions::new(*format)
+            };
+            let input = File::open(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+            let report = import_cards(&vault, BufReader::new(input), writer, options).await?;
+            writeln!(out, "{} imported, {} stored before, {} rejected", report.imported, report.existing, report.rejected)?;
+        },
+        Command::Rekey { new_key_file, new_iv_file, work_dir } => {
+            // imported records keep their tokens, a deterministic
+            // tokenizer keyed with the new key would give their cards others
+            if cfg.tokenizer == TokenizerKind::Deterministic {
+                return Err("the deterministic tokenizer derives tokens from the key, migrate to a vault with the new key instead".into())
+            }
+            let encryption = EncryptionSettings {
+                key: read_secret(new_key_file)?,
+                iv: match new_iv_file {
+                    Some(new_iv_file) => read_secret(new_iv_file)?,
+                    None => cfg.encryption.iv.clone(),
+                },
+            };
+            let rekeyed: V = open_in(&cfg.clone().with_encryption(encryption), namespace)?;
+            let spool = work_dir.clone().unwrap_or_else(std::env::temp_dir)
+                .join(format!("data-vault-rekey-{}.dvexport", std::process::id()));
+            let result = rekey(&vault, &rekeyed, &spool).await;
+            let _ = fs::remove_file(&spool);
+            writeln!(out, "{} records re-encrypted", result?)?;
+        },
+        Command::Verify => {
+            let report = vault.verify_records().await?;
+            writeln!(out, "{} checked, {} issues", report.checked, report.issues.len())?;
+            for issue in &report.issues {
+                writeln!(out, "{} {:?}", redact_token(&issue.token), issue.problem)?;
+            }
+            if !report.is_ok() {
+                return Err("the vault has records that do not verify".into())
+            }
+        },
+    }
+    Ok(())
+}
+
+/// The vault of `cfg` in `namespace`
+fn open_in<V: ConfiguredVault>(cfg: &Config, namespace: Option<&str>) -> Result<V, Box<dyn error::Error>> {
+    let vault = V::open(cfg)?;
+    Ok(match namespace {
+        Some(namespace) => vault.with_namespace(namespace)?,
+        None => vault,
+    })
+}
+
+async fn migrate_to<S, D>(from: &S, destination: &Config, namespace: Option<&str>, options: MigrateOptions<'_>) -> Result<MigrationReport, Box<dyn error::Error>>
+    where
+        S: DataVault,
+        D: ConfiguredVault,
+{
+    let to: D = open_in(destination, namespace)?;
+    Ok(migrate(from, &to, options).await?)
+}
+
+/// Export every record of `vault` to `spool` under a throw-away key
+/// and import them into `rekeyed`, the same records under another key
+async fn rekey<V: DataVault>(vault: &V, rekeyed: &V, spool: &Path) -> Result<u64, Box<dyn error::Error>> {
+    let mut spool_key = [0; 32];
+    rand::rngs::OsRng.fill_bytes(&mut spool_key);
+
+    let mut writer = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(spool)?);
+    let exported = vault.export(&mut writer, &spool_key).await?;
+    drop(writer);
+    let imported = rekeyed.import(BufReader::new(File::open(spool)?), &spool_key).await?;
+    if imported != exported {
+        return Err(format!("{} records exported but {} re-encrypted", exported, imported).into())
+    }
+    Ok(imported)
+}
+
+/// The trimmed contents of the secret file `path`
+fn read_secret(path: &Path) -> Result<String, Box<dyn error::Error>> {
+    let secret = fs::read_to_string(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .trim()
+        .to_string();
+    if secret.is_empty() {
+        return Err(format!("{} is empty", path.display()).into())
+    }
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+    use crate::cli::{self, Cli};
+    use std::error;
+
+    async fn data_vault(options: &[&str], command: &[&str], input: &str) -> Result<String, Box<dyn error::Error>> {
+        let cli = Cli::try_parse_from(std::iter::once(&"data-vault").chain(options).chain(command))?;
+        let mut out = Vec::new();
+        cli::run(cli, input.as_bytes(), &mut out).await?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cli() {
+        let dir = std::env::temp_dir().join(format!("data_vault_cli_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let namespace = format!("cli-{}", std::process::id());
+        let file = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let export_key = file("export.key", "f3e1c1b0a9d8e7f6f3e1c1b0a9d8e7f6\n");
+        let new_key = file("new.key", "0f0e0d0c0b0a09080706050403020100\n");
+        let export = dir.join("vault.dvexport");
+        let _ = std::fs::remove_file(&export);
+        let options = ["--namespace", namespace.as_str()];
+
+        let card = r#"{"number": "4111111111111111", "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023"}"#;
+        let token = data_vault(&options, &["store"], card).await.unwrap().trim().to_string();
+        let retrieved = data_vault(&options, &["retrieve", &token], "").await.unwrap();
+        assert!(retrieved.contains("4111111111111111"));
+        assert!(data_vault(&options, &["store"], "4111111111111111").await.is_err());
+
+        assert_eq!(data_vault(&options, &["export", "--output", export.to_str().unwrap(), "--key-file", &export_key], "").await.unwrap(), "1 records exported\n");
+        assert!(data_vault(&options, &["export", "--output", export.to_str().unwrap(), "--key-file", &export_key], "").await.is_err());
+        assert_eq!(data_vault(&options, &["verify"], "").await.unwrap(), "1 checked, 0 issues\n");
+
+        let portfolio = file("portfolio.ndjson", &format!("{}\n{{}}\n", card));
+        let mapping = dir.join("portfolio.tokens.csv");
+        let _ = std::fs::remove_file(&mapping);
+        let import = ["import", "--input", portfolio.as_str(), "--format", "ndjson", "--mapping", mapping.to_str().unwrap()];
+        assert_eq!(data_vault(&options, &import, "").await.unwrap(), "1 imported, 0 stored before, 1 rejected\n");
+        // continued after the last row, nothing is left
+        assert_eq!(data_vault(&options, &import, "").await.unwrap(), "0 imported, 0 stored before, 0 rejected\n");
+        let imported = std::fs::read_to_string(&mapping).unwrap().lines().nth(1).unwrap().split(',').nth(1).unwrap().to_string();
+        assert_eq!(data_vault(&options, &["delete", &imported], "").await.unwrap(), "1\n");
+
+        assert_eq!(data_vault(&options, &["rekey", "--new-key-file", &new_key], "").await.unwrap(), "1 records re-encrypted\n");
+        assert!(data_vault(&options, &["verify"], "").await.is_err());
+
+        // the same vault configured by a file with the new key
+        let config = file("data_vault.toml", &format!(r#"
+            backend = "redis"
+
+            [encryption]
+            key = {{ file = "{}" }}
+            iv = {{ env = "ENCRYPTED_DATA_VAULT_IV" }}
+
+            [redis]
+            url = {{ env = "REDIS_URL" }}
+        "#, new_key));
+        let options = ["--config", config.as_str(), "--namespace", namespace.as_str()];
+        assert_eq!(data_vault(&options, &["retrieve", &token], "").await.unwrap(), retrieved);
+        assert_eq!(data_vault(&options, &["delete", &token], "").await.unwrap(), "1\n");
+        assert_eq!(data_vault(&options, &["delete", &token], "").await.unwrap(), "0\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
\ No newline at end of file
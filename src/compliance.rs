@@ -0,0 +1,212 @@
+//! Evidence of the deployed configuration for PCI DSS assessments
+//!
+//! `compliance_report` of the vaults describes how cardholder data is
+//! protected: the encryption algorithm and the id and age of its key,
+//! whether tokens are deterministic or salted, the security code
+//! policy, the retention period of deleted records and, added with
+//! `ComplianceReport::with_audit_log`, the state of the audit log.
+//! `ComplianceReport::to_json` writes it for the assessor.  The report
+//! never holds key material, tokens or records.
+//!
+//! # example
+//! ```rust,ignore
+//! use data_vault::RedisDataVault;
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+//! let report = vault.compliance_report().with_audit_log("/var/log/data_vault/audit.log");
+//! std::fs::write("compliance.json", report.to_json()?)?;
+//! ```
+
+use serde::Serialize;
+use crate::admin::KeyInfo;
+use crate::audit::AuditLog;
+use crate::compression::CompressionAlgo;
+use crate::cvv::CvvPolicy;
+use crate::error::DataVaultError;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// The configuration of a vault as evidence for an assessment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComplianceReport {
+    /// the version of data_vault
+    pub version: &'static str,
+    /// seconds since the unix epoch when the report was made
+    pub generated_at: u64,
+    /// see `DataVault::backend`
+    pub backend: &'static str,
+    /// the namespace of the vault
+    pub tenant: String,
+    pub encryption: EncryptionEvidence,
+    /// how tokens are derived from cards
+    pub tokens: TokenKind,
+    pub security_code: SecurityCodeEvidence,
+    /// seconds a deleted record is kept before it is purged
+    pub deleted_retention_secs: u64,
+    pub audit_log: AuditLogStatus,
+}
+
+/// The encryption of stored records
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncryptionEvidence {
+    /// see `Encryption::algorithm`
+    pub algorithm: &'static str,
+    /// see `KeyInfo::key_id`
+    pub key_id: String,
+    /// seconds since the unix epoch when the vault started using the key
+    pub key_active_since: u64,
+    /// seconds the key has been in use, see `KeyInfo::rotation_age`
+    pub key_age_secs: u64,
+    /// compression applied before encryption
+    pub compression: &'static str,
+}
+
+/// Whether the same card always has the same token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenKind {
+    /// keyed with the vault's key, the same card always has the same token
+    Deterministic,
+    /// a new random token for every card stored
+    Salted,
+}
+
+/// What happens to a card's security code, see `CvvPolicy`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecurityCodeEvidence {
+    /// `strip`, `reject` or `expire`
+    pub policy: &'static str,
+    /// seconds a record with a security code lives, for `expire`
+    pub ttl_secs: Option<u64>,
+}
+
+/// The state of the audit log of a vault
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AuditLogStatus {
+    /// no audit log was added to the report
+    NotConfigured,
+    /// the hash chain of the log is intact
+    Verified { path: String, entries: u64 },
+    /// the entry at `sequence` does not match the hash chain
+    Tampered { path: String, sequence: u64 },
+    /// the log could not be read
+    Unreadable { path: String, error: String },
+}
+
+impl ComplianceReport {
+    pub(crate) fn new(backend: &'static str, tenant: &str, key: &KeyInfo, deterministic: bool, cvv_policy: CvvPolicy, compression: CompressionAlgo, retention: Duration) -> Self {
+        let (policy, ttl) = match cvv_policy {
+            CvvPolicy::Strip => ("strip", None),
+            CvvPolicy::Reject => ("reject", None),
+            CvvPolicy::Expire(ttl) => ("expire", Some(ttl.as_secs())),
+        };
+        ComplianceReport {
+            version: env!("CARGO_PKG_VERSION"),
+            generated_at: unix_secs(SystemTime::now()),
+            backend,
+            tenant: tenant.to_string(),
+            encryption: EncryptionEvidence {
+                algorithm: key.algorithm,
+                key_id: key.key_id.clone(),
+                key_active_since: unix_secs(key.active_since),
+                key_age_secs: key.rotation_age().as_secs(),
+                compression: compression_name(compression),
+            },
+            tokens: match deterministic {
+                true => TokenKind::Deterministic,
+                false => TokenKind::Salted,
+            },
+            security_code: SecurityCodeEvidence { policy, ttl_secs: ttl },
+            deleted_retention_secs: retention.as_secs(),
+            audit_log: AuditLogStatus::NotConfigured,
+        }
+    }
+
+    /// The report with the state of the audit log at `path`, its hash
+    /// chain is verified with `AuditLog::verify`
+    pub fn with_audit_log<P: AsRef<Path>>(self, path: P) -> Self {
+        let path = path.as_ref();
+        let audit_log = match AuditLog::verify(path) {
+            Ok(entries) => AuditLogStatus::Verified { path: path.display().to_string(), entries },
+            Err(DataVaultError::TamperedAuditLog(sequence)) => AuditLogStatus::Tampered { path: path.display().to_string(), sequence },
+            Err(e) => AuditLogStatus::Unreadable { path: path.display().to_string(), error: e.to_string() },
+        };
+        ComplianceReport { audit_log, ..self }
+    }
+
+    /// The report as pretty printed JSON
+    pub fn to_json(&self) -> Result<String, DataVaultError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn compression_name(compression: CompressionAlgo) -> &'static str {
+    match compression {
+        CompressionAlgo::None => "none",
+        #[cfg(feature = "lz4")]
+        CompressionAlgo::Lz4 => "lz4",
+        #[cfg(feature = "deflate")]
+        CompressionAlgo::Deflate => "deflate",
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::admin::KeyInfo;
+    use crate::audit::AuditLog;
+    use crate::compliance::{AuditLogStatus, ComplianceReport, TokenKind};
+    use crate::compression::CompressionAlgo;
+    use crate::config::EncryptionSettings;
+    use crate::cvv::CvvPolicy;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::encryption::traits::Encryption;
+    use std::fs;
+    use std::time::Duration;
+
+    fn report(cvv_policy: CvvPolicy) -> ComplianceReport {
+        let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let key = KeyInfo::new(&AesGcmSivEncryption::from_settings(&settings), &settings);
+        ComplianceReport::new("redis", "default", &key, true, cvv_policy, CompressionAlgo::None, Duration::from_secs(86400))
+    }
+
+    #[test]
+    fn test_compliance_report() {
+        let report = report(CvvPolicy::Expire(Duration::from_secs(300)));
+        assert_eq!(report.encryption.algorithm, "AES-256-GCM-SIV");
+        assert_eq!(report.tokens, TokenKind::Deterministic);
+        assert_eq!(report.audit_log, AuditLogStatus::NotConfigured);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["tokens"], "deterministic");
+        assert_eq!(json["security_code"]["policy"], "expire");
+        assert_eq!(json["security_code"]["ttl_secs"], 300);
+        assert_eq!(json["deleted_retention_secs"], 86400);
+        assert_eq!(json["encryption"]["key_id"].as_str().unwrap().len(), 16);
+        assert_eq!(json["audit_log"]["status"], "not_configured");
+        assert!(!report.to_json().unwrap().contains("0001"));
+    }
+
+    #[test]
+    fn test_compliance_report_audit_log() {
+        let path = std::env::temp_dir().join(format!("data_vault_compliance_{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        AuditLog::open(&path).unwrap();
+
+        let report = report(CvvPolicy::Strip).with_audit_log(&path);
+        assert_eq!(report.audit_log, AuditLogStatus::Verified { path: path.display().to_string(), entries: 0 });
+        fs::write(&path, "not an audit entry\n").unwrap();
+        let report = report.with_audit_log(&path);
+        assert_eq!(report.audit_log, AuditLogStatus::Tampered { path: path.display().to_string(), sequence: 0 });
+        fs::remove_file(&path).unwrap();
+
+        let report = report.with_audit_log(&path);
+        assert!(matches!(report.audit_log, AuditLogStatus::Unreadable { .. }));
+    }
+}
\ No newline at end of file
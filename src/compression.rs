@@ -0,0 +1,125 @@
+use crate::error::DataVaultError;
+use std::borrow::Cow;
+
+// precedes every compressed record, neither JSON nor the other
+// record formats can start with it
+const MARKER: &[u8] = b"\0dvz";
+
+/// How a vault compresses records before they are encrypted, see
+/// `with_compression` of the back ends
+///
+/// Compressed records start with a marker naming the algorithm, so
+/// records are read whatever the vault is configured with now.  A
+/// record is stored uncompressed when compressing does not make it
+/// smaller, as most single credit cards are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionAlgo {
+    /// records are stored as they are
+    #[default]
+    None,
+    /// fast, needs the `lz4` feature
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// smaller, needs the `deflate` feature
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+#[cfg(feature = "lz4")]
+const LZ4: u8 = 1;
+#[cfg(feature = "deflate")]
+const DEFLATE: u8 = 2;
+
+impl CompressionAlgo {
+    /// `record` as it is written before encryption
+    pub(crate) fn compress<'a>(&self, record: &'a [u8]) -> Cow<'a, [u8]> {
+        let compressed: Option<(u8, Vec<u8>)> = match self {
+            CompressionAlgo::None => None,
+            #[cfg(feature = "lz4")]
+            CompressionAlgo::Lz4 => Some((LZ4, lz4_flex::compress_prepend_size(record))),
+            #[cfg(feature = "deflate")]
+            CompressionAlgo::Deflate => Some((DEFLATE, deflate(record))),
+        };
+
+        match compressed {
+            Some((algo, compressed)) if MARKER.len() + 1 + compressed.len() < record.len() => {
+                Cow::Owned([MARKER, &[algo], compressed.as_slice()].concat())
+            },
+            _ => Cow::Borrowed(record),
+        }
+    }
+
+    /// The record written by `compress` with any algorithm
+    /// returns:
+    ///     * `DataVaultError::Encoding` for a damaged record or one
+    ///       compressed by an algorithm whose feature is not enabled
+    pub(crate) fn decompress(record: &[u8]) -> Result<Cow<'_, [u8]>, DataVaultError> {
+        let compressed = match record.strip_prefix(MARKER) {
+            Some(compressed) => compressed,
+            None => return Ok(Cow::Borrowed(record)),
+        };
+
+        match compressed.split_first() {
+            #[cfg(feature = "lz4")]
+            Some((&LZ4, compressed)) => lz4_flex::decompress_size_prepended(compressed)
+                .map(Cow::Owned)
+                .map_err(|e| DataVaultError::Encoding(Box::new(e))),
+            #[cfg(feature = "deflate")]
+            Some((&DEFLATE, compressed)) => inflate(compressed)
+                .map(Cow::Owned)
+                .map_err(|e| DataVaultError::Encoding(Box::new(e))),
+            _ => Err(DataVaultError::Encoding("record compressed with an unsupported algorithm".into())),
+        }
+    }
+}
+
+#[cfg(feature = "deflate")]
+fn deflate(record: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    // writing to a Vec can not fail
+    encoder.write_all(record).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[cfg(feature = "deflate")]
+fn inflate(compressed: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    use std::io::Read;
+
+    let mut record = Vec::new();
+    flate2::read::DeflateDecoder::new(compressed).read_to_end(&mut record)?;
+    Ok(record)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::compression::CompressionAlgo;
+
+    #[test]
+    fn test_compression() {
+        let record = r#"{"number":"4111111111111111","cardholder_name":"Graydon Hoare"}"#.repeat(20);
+
+        assert_eq!(CompressionAlgo::None.compress(record.as_bytes()).as_ref(), record.as_bytes());
+        assert_eq!(CompressionAlgo::decompress(record.as_bytes()).unwrap().as_ref(), record.as_bytes());
+
+        #[cfg(feature = "lz4")]
+        {
+            let compressed = CompressionAlgo::Lz4.compress(record.as_bytes());
+            assert!(compressed.len() < record.len());
+            assert_eq!(CompressionAlgo::decompress(&compressed).unwrap().as_ref(), record.as_bytes());
+        }
+
+        #[cfg(feature = "deflate")]
+        {
+            let compressed = CompressionAlgo::Deflate.compress(record.as_bytes());
+            assert!(compressed.len() < record.len());
+            assert_eq!(CompressionAlgo::decompress(&compressed).unwrap().as_ref(), record.as_bytes());
+        }
+
+        // small records are not worth it
+        #[cfg(feature = "lz4")]
+        assert_eq!(CompressionAlgo::Lz4.compress(b"{}").as_ref(), b"{}");
+        assert!(CompressionAlgo::decompress(b"\0dvz\x7f").is_err());
+    }
+}
@@ -1,19 +1,1038 @@
 use serde::Deserialize;
 use dotenv::dotenv;
+use std::fmt;
+use std::time::Duration;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+use crate::cvv::{CvvPolicy, CvvPolicyKind};
+#[cfg(feature = "postgres")]
+use crate::fields::CardField;
+#[cfg(feature = "redis")]
+use deadpool_redis::Runtime;
+#[cfg(all(feature = "postgres", not(feature = "redis")))]
+use deadpool_postgres::Runtime;
+#[cfg(any(feature = "redis-tls", feature = "server"))]
+use std::path::PathBuf;
+#[cfg(feature = "server")]
+use crate::config_file::{Backend, TokenizerKind};
+use crate::retry::RetryPolicy;
+use crate::chaos::ChaosPolicy;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+use crate::recycle::RecycleMethod;
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Deserialize, Default)]
 pub struct EncryptionConfig {
     pub key: String,
     pub iv: String,
     // cipher: Aes128Cbc,
 }
 
+/// The key material the vault encrypts, tokenizes and blind
+/// indexes with
+///
+/// `from_env` reads `ENCRYPTED_DATA_VAULT_KEY` and
+/// `ENCRYPTED_DATA_VAULT_IV`, or the files `*_FILE` names, `new`
+/// takes them from code, e.g. from a secrets manager.  `Debug`
+/// does not print either.
+/// # example
+/// ```rust
+/// use data_vault::EncryptionSettings;
+/// use data_vault::encryption::traits::Encryption;
+/// use data_vault::encryption::AesGcmSivEncryption;
+///
+/// let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+/// let enc = AesGcmSivEncryption::from_settings(&settings);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct EncryptionSettings {
+    /// `ENCRYPTED_DATA_VAULT_KEY`
+    pub key: String,
+    /// `ENCRYPTED_DATA_VAULT_IV`, only used by `Aes128CbcEncryption`
+    pub iv: String,
+}
+
+impl EncryptionSettings {
+    pub fn new(key: &str, iv: &str) -> Self {
+        EncryptionSettings {
+            key: key.to_string(),
+            iv: iv.to_string(),
+        }
+    }
+
+    /// see `EncryptionConfig::from_env`
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        Ok(EncryptionConfig::from_env(None)?.into())
+    }
+
+    /// `from_env` reading `<prefix>_ENCRYPTED_DATA_VAULT_KEY` and
+    /// `<prefix>_ENCRYPTED_DATA_VAULT_IV`
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self, ::config::ConfigError> {
+        Ok(EncryptionConfig::from_env(Some(prefix))?.into())
+    }
+
+    /// An error naming the variable unless the key and the IV are
+    /// both 32 hexadecimal characters, the ciphers panic on anything
+    /// else
+    pub fn validate(&self) -> Result<(), ::config::ConfigError> {
+        self.validate_prefixed(None)
+    }
+
+    /// `validate` naming the variables of the instance `prefix`
+    pub(crate) fn validate_prefixed(&self, prefix: Option<&str>) -> Result<(), ::config::ConfigError> {
+        validate_hex(&env_name(prefix, "ENCRYPTED_DATA_VAULT_KEY"), &self.key)?;
+        validate_hex(&env_name(prefix, "ENCRYPTED_DATA_VAULT_IV"), &self.iv)
+    }
+}
+
+/// The variable `name` of the vault instance `prefix`, e.g.
+/// `VAULT_A_REDIS_URL`, `name` itself without a prefix
+pub(crate) fn env_name(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}_{}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+/// The environment of the vault instance `prefix`, all of it
+/// without a prefix
+#[cfg(any(feature = "redis", feature = "postgres"))]
+fn instance_environment(prefix: Option<&str>) -> ::config::Environment {
+    match prefix {
+        Some(prefix) => ::config::Environment::with_prefix(prefix),
+        None => ::config::Environment::new(),
+    }
+}
+
+/// `value` is 32 hexadecimal characters, 128 bits, the error
+/// names the variable but never shows the secret
+fn validate_hex(name: &str, value: &str) -> Result<(), ::config::ConfigError> {
+    if value.len() != 32 {
+        return Err(::config::ConfigError::Message(format!(
+            "{} must be 32 hexadecimal characters, it has {}", name, value.chars().count()
+        )));
+    }
+    match value.chars().position(|c| !c.is_ascii_hexdigit()) {
+        Some(position) => Err(::config::ConfigError::Message(format!(
+            "{} must be 32 hexadecimal characters, character {} is not 0-9 or a-f", name, position + 1
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// An error naming `name` unless a configured pool holds at least
+/// one connection
+#[cfg(any(feature = "redis", feature = "postgres"))]
+fn validate_pool_size(name: &str, max_size: Option<usize>) -> Result<(), ::config::ConfigError> {
+    match max_size {
+        Some(0) => Err(::config::ConfigError::Message(format!("{} must be at least 1", name))),
+        _ => Ok(()),
+    }
+}
+
+impl From<EncryptionConfig> for EncryptionSettings {
+    fn from(cfg: EncryptionConfig) -> Self {
+        EncryptionSettings {
+            key: cfg.key,
+            iv: cfg.iv,
+        }
+    }
+}
+
+impl fmt::Debug for EncryptionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionConfig")
+            .field("key", &"<redacted>")
+            .field("iv", &"<redacted>")
+            .finish()
+    }
+}
+
+impl fmt::Debug for EncryptionSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionSettings")
+            .field("key", &"<redacted>")
+            .field("iv", &"<redacted>")
+            .finish()
+    }
+}
+
+// 180 days, long enough for most chargeback windows
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub(crate) const DEFAULT_RETENTION_SECS: u64 = 180 * 24 * 60 * 60;
+
+// 10 minutes, enough to authorize a payment
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub(crate) const DEFAULT_CVV_TTL_SECS: u64 = 10 * 60;
+
+#[cfg(any(feature = "redis", feature = "postgres"))]
+#[derive(Debug, Deserialize)]
+pub struct DataVaultConfig {
+    #[serde(default = "default_retention_secs")]
+    pub retention_secs: u64,
+    #[serde(default)]
+    pub(crate) cvv_policy: CvvPolicyKind,
+    #[serde(default = "default_cvv_ttl_secs")]
+    pub cvv_ttl_secs: u64,
+    #[serde(default = "default_validate_cards")]
+    pub validate_cards: bool,
+    #[serde(default)]
+    pub reject_expired: bool,
+    pub slow_op_ms: Option<u64>,
+    #[cfg(feature = "postgres")]
+    #[serde(default)]
+    pub plaintext_fields: String,
+    #[cfg(feature = "postgres")]
+    #[serde(default)]
+    pub blind_index_fields: String,
+    #[cfg(feature = "postgres")]
+    #[serde(default)]
+    pub auto_create_schema: bool,
+}
+
+#[cfg(any(feature = "redis", feature = "postgres"))]
+fn default_retention_secs() -> u64 {
+    DEFAULT_RETENTION_SECS
+}
+
+#[cfg(any(feature = "redis", feature = "postgres"))]
+fn default_cvv_ttl_secs() -> u64 {
+    DEFAULT_CVV_TTL_SECS
+}
+
+#[cfg(any(feature = "redis", feature = "postgres"))]
+fn default_validate_cards() -> bool {
+    true
+}
+
+/// Everything `RedisDataVault::from_config` needs, assembled in
+/// code or read with `from_env`
+/// # example
+/// ```rust
+/// use data_vault::{CvvPolicy, EncryptionSettings, RedisDataVault, RedisVaultConfig};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::time::Duration;
+///
+/// let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+/// let cfg = RedisVaultConfig::new("redis://127.0.0.1/", settings)
+///     .with_pool_max_size(8)
+///     .with_retention(Duration::from_secs(90 * 24 * 60 * 60))
+///     .with_cvv_policy(CvvPolicy::Reject);
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+/// ```
+#[cfg(feature = "redis")]
+#[derive(Debug, Clone)]
+pub struct RedisVaultConfig {
+    pub redis: deadpool_redis::Config,
+    /// prepended to every key the vault writes, see `with_key_prefix`
+    pub key_prefix: String,
+    pub encryption: EncryptionSettings,
+    /// how long soft deleted records are kept
+    pub retention: Duration,
+    /// store and retrieve operations taking longer are logged, see
+    /// `with_slow_op`
+    pub slow_op: Option<Duration>,
+    pub cvv_policy: CvvPolicy,
+    /// reject card numbers of the wrong length or failing the Luhn
+    /// check and invalid expiry dates, see `with_card_validation`
+    pub validate_cards: bool,
+    /// reject cards that expired before the current month, see
+    /// `with_reject_expired`
+    pub reject_expired: bool,
+    /// used to take connections from the pool
+    pub retry: RetryPolicy,
+    /// how pooled connections are checked on checkout
+    pub recycle: RecycleMethod,
+    /// used for `rediss://` urls, see `with_tls`
+    #[cfg(feature = "redis-tls")]
+    pub tls: RedisTlsConfig,
+    /// the prefix of the variables the configuration was read from,
+    /// see `from_env_with_prefix`, `reload` reads them again
+    pub env_prefix: Option<String>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisVaultConfig {
+    /// Connect to `url`, everything else at its default
+    pub fn new(url: &str, encryption: EncryptionSettings) -> Self {
+        RedisVaultConfig {
+            redis: deadpool_redis::Config {
+                url: Some(url.to_string()),
+                connection: None,
+                pool: None,
+            },
+            key_prefix: String::new(),
+            encryption,
+            retention: Duration::from_secs(DEFAULT_RETENTION_SECS),
+            slow_op: None,
+            cvv_policy: CvvPolicy::default(),
+            validate_cards: true,
+            reject_expired: false,
+            retry: RetryPolicy::default(),
+            recycle: RecycleMethod::default(),
+            #[cfg(feature = "redis-tls")]
+            tls: RedisTlsConfig::default(),
+            env_prefix: None,
+        }
+    }
+
+    /// The configuration `RedisDataVault::new` uses, see
+    /// `DeadpoolRedisConfig::from_env`, `RedisKeyConfig::from_env`,
+    /// `PoolRecycleConfig::from_env`, `RedisTlsConfig::from_env`,
+    /// `DataVaultConfig::from_env`, `RetryConfig::from_env` and
+    /// `EncryptionSettings::from_env`
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        RedisVaultConfig::from_env_prefixed(None)
+    }
+
+    /// `from_env` reading the variables of one of several vaults in
+    /// a process, each starting with `prefix`, e.g. `VAULT_A_REDIS_URL`
+    /// and `VAULT_A_ENCRYPTED_DATA_VAULT_KEY` for `VAULT_A`
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self, ::config::ConfigError> {
+        RedisVaultConfig::from_env_prefixed(Some(prefix))
+    }
+
+    fn from_env_prefixed(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        let vault_cfg = DataVaultConfig::from_env(prefix)?;
+        Ok(RedisVaultConfig {
+            redis: DeadpoolRedisConfig::from_env(prefix)?.redis,
+            key_prefix: RedisKeyConfig::from_env(prefix)?.key_prefix,
+            encryption: EncryptionConfig::from_env(prefix)?.into(),
+            retention: Duration::from_secs(vault_cfg.retention_secs),
+            slow_op: vault_cfg.slow_op_ms.map(Duration::from_millis),
+            cvv_policy: CvvPolicy::from_config(vault_cfg.cvv_policy, vault_cfg.cvv_ttl_secs),
+            validate_cards: vault_cfg.validate_cards,
+            reject_expired: vault_cfg.reject_expired,
+            retry: RetryConfig::from_env(prefix)?.into(),
+            recycle: PoolRecycleConfig::from_env(&env_name(prefix, "REDIS"))?.pool_recycle.unwrap_or_default(),
+            #[cfg(feature = "redis-tls")]
+            tls: RedisTlsConfig::from_env_prefixed(prefix)?,
+            env_prefix: prefix.map(str::to_string),
+        })
+    }
+
+    /// An error naming the variable that is wrong, see
+    /// `EncryptionSettings::validate`, called by
+    /// `RedisDataVault::from_config`
+    pub fn validate(&self) -> Result<(), ::config::ConfigError> {
+        let prefix = self.env_prefix.as_deref();
+        self.encryption.validate_prefixed(prefix)?;
+        validate_pool_size(&env_name(prefix, "REDIS_POOL_MAX_SIZE"), self.redis.pool.as_ref().map(|pool| pool.max_size))?;
+        match (&self.redis.url, &self.redis.connection) {
+            (Some(url), None) => match deadpool_redis::redis::IntoConnectionInfo::into_connection_info(url.as_str()) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(::config::ConfigError::Message(format!("{} is not a redis url: {}", env_name(prefix, "REDIS_URL"), e))),
+            },
+            (Some(_), Some(_)) => Err(::config::ConfigError::Message(format!(
+                "{} and {} must not be specified at the same time", env_name(prefix, "REDIS_URL"), env_name(prefix, "REDIS_CONNECTION")
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn with_pool_max_size(mut self, max_size: usize) -> Self {
+        self.redis.pool.get_or_insert_with(Default::default).max_size = max_size;
+        self
+    }
+
+    /// Prepend `key_prefix`, e.g. `dv:prod:`, to every key so the
+    /// vault can share a redis instance with other applications
+    pub fn with_key_prefix(mut self, key_prefix: &str) -> Self {
+        self.key_prefix = key_prefix.to_string();
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Log store and retrieve operations taking longer than
+    /// `threshold` at warn level, with the `data_vault::slow` target,
+    /// with the time spent in each stage, see `LatencyStage`, needs
+    /// the `log` feature
+    pub fn with_slow_op(mut self, threshold: Duration) -> Self {
+        self.slow_op = Some(threshold);
+        self
+    }
+
+    pub fn with_cvv_policy(mut self, cvv_policy: CvvPolicy) -> Self {
+        self.cvv_policy = cvv_policy;
+        self
+    }
+
+    /// Store cards without checking the length and Luhn check digit
+    /// of their numbers and their expiry with `false`, e.g. for test
+    /// cards of a processor that are not valid card numbers
+    pub fn with_card_validation(mut self, validate_cards: bool) -> Self {
+        self.validate_cards = validate_cards;
+        self
+    }
+
+    /// Refuse cards that expired before the current month with
+    /// `ValidationError::Expired`
+    pub fn with_reject_expired(mut self, reject_expired: bool) -> Self {
+        self.reject_expired = reject_expired;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_recycle(mut self, recycle: RecycleMethod) -> Self {
+        self.recycle = recycle;
+        self
+    }
+
+    /// Connect to a `rediss://` url with `tls`, e.g. the CA bundle
+    /// of a managed redis, plain `redis://` urls ignore it
+    #[cfg(feature = "redis-tls")]
+    pub fn with_tls(mut self, tls: RedisTlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+}
+
+/// How `RedisDataVault` connects to `rediss://` urls
+///
+/// The server certificate is checked against the system roots and
+/// the certificates in `ca_file`.  With `cert_file` and `key_file`
+/// the vault presents a client certificate, for redis configured
+/// with `tls-auth-clients`.  A url ending in `#insecure` accepts any
+/// certificate.
+/// # example
+/// ```rust
+/// use data_vault::{EncryptionSettings, RedisTlsConfig, RedisVaultConfig};
+///
+/// let tls = RedisTlsConfig {
+///     ca_file: Some("/etc/ssl/certs/redis-ca.pem".into()),
+///     ..RedisTlsConfig::default()
+/// };
+/// let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+/// let cfg = RedisVaultConfig::new("rediss://:foobared@redis.internal:6380/", settings)
+///     .with_tls(tls);
+/// ```
+#[cfg(feature = "redis-tls")]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RedisTlsConfig {
+    /// PEM certificates of the authorities to trust besides the
+    /// system roots
+    #[serde(default)]
+    pub ca_file: Option<PathBuf>,
+    /// PEM client certificate
+    #[serde(default)]
+    pub cert_file: Option<PathBuf>,
+    /// PEM PKCS #8 key of the client certificate
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    /// check the certificate is issued to the host of the url, only
+    /// turn off for servers reached by an address not on it
+    #[serde(default = "default_verify_hostname")]
+    pub verify_hostname: bool,
+}
+
+#[cfg(feature = "redis-tls")]
+impl Default for RedisTlsConfig {
+    fn default() -> Self {
+        RedisTlsConfig {
+            ca_file: None,
+            cert_file: None,
+            key_file: None,
+            verify_hostname: true,
+        }
+    }
+}
+
+#[cfg(feature = "redis-tls")]
+fn default_verify_hostname() -> bool {
+    true
+}
+
+/// Everything `PostgresDataVault::from_config` needs, assembled in
+/// code or read with `from_env`
+/// # example
+/// ```rust
+/// use data_vault::{CardField, EncryptionSettings, PostgresDataVault, PostgresVaultConfig};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let mut postgres = deadpool_postgres::Config::new();
+/// postgres.host = Some("127.0.0.1".to_string());
+/// postgres.dbname = Some("data_vault".to_string());
+/// let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+/// let cfg = PostgresVaultConfig::new(postgres, settings)
+///     .with_pool_max_size(8)
+///     .with_plaintext_fields(&[CardField::ExpirationMonth, CardField::ExpirationYear]);
+/// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+/// ```
+#[cfg(feature = "postgres")]
+#[derive(Debug, Clone)]
+pub struct PostgresVaultConfig {
+    pub postgres: deadpool_postgres::Config,
+    pub encryption: EncryptionSettings,
+    /// how long soft deleted records are kept
+    pub retention: Duration,
+    /// store and retrieve operations taking longer are logged, see
+    /// `with_slow_op`
+    pub slow_op: Option<Duration>,
+    pub cvv_policy: CvvPolicy,
+    /// reject card numbers of the wrong length or failing the Luhn
+    /// check and invalid expiry dates, see `with_card_validation`
+    pub validate_cards: bool,
+    /// reject cards that expired before the current month, see
+    /// `with_reject_expired`
+    pub reject_expired: bool,
+    /// used to take connections from the pool
+    pub retry: RetryPolicy,
+    /// see `CardFieldLayout`
+    pub plaintext_fields: Vec<CardField>,
+    /// see `CardFieldLayout`
+    pub blind_index_fields: Vec<CardField>,
+    /// the table holding the records, see `with_table`
+    pub table: String,
+    /// the schema of `table`, the search path when `None`
+    pub schema: Option<String>,
+    /// create or upgrade the table before it is first used, see
+    /// `with_auto_create_schema`
+    pub auto_create_schema: bool,
+    /// the prefix of the variables the configuration was read from,
+    /// see `from_env_with_prefix`, `reload` reads them again
+    pub env_prefix: Option<String>,
+    /// read replicas answering `retrieve`, `retrieve_credit_card`
+    /// and `exists`, see `with_replica`
+    pub replicas: Vec<deadpool_postgres::Config>,
+    /// how far a replica may be behind the primary and still be
+    /// read from, any lag when `None`
+    pub max_replica_lag: Option<Duration>,
+}
+
+#[cfg(feature = "postgres")]
+impl PostgresVaultConfig {
+    /// Connect with `postgres`, everything else at its default
+    pub fn new(postgres: deadpool_postgres::Config, encryption: EncryptionSettings) -> Self {
+        PostgresVaultConfig {
+            postgres,
+            encryption,
+            retention: Duration::from_secs(DEFAULT_RETENTION_SECS),
+            slow_op: None,
+            cvv_policy: CvvPolicy::default(),
+            validate_cards: true,
+            reject_expired: false,
+            retry: RetryPolicy::default(),
+            plaintext_fields: Vec::new(),
+            blind_index_fields: Vec::new(),
+            table: DEFAULT_TABLE_NAME.to_string(),
+            schema: None,
+            auto_create_schema: false,
+            env_prefix: None,
+            replicas: Vec::new(),
+            max_replica_lag: None,
+        }
+    }
+
+    /// The configuration `PostgresDataVault::new` uses, see
+    /// `DeadpoolPostgresConfig::from_env`, `PostgresTableConfig::from_env`,
+    /// `PostgresReplicaConfig::from_env`, `PoolRecycleConfig::from_env`, `DataVaultConfig::from_env`,
+    /// `RetryConfig::from_env` and `EncryptionSettings::from_env`
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        PostgresVaultConfig::from_env_prefixed(None)
+    }
+
+    /// `from_env` reading the variables of one of several vaults in
+    /// a process, each starting with `prefix`, e.g.
+    /// `VAULT_A_POSTGRES.HOST` and `VAULT_A_ENCRYPTED_DATA_VAULT_KEY`
+    /// for `VAULT_A`
+    pub fn from_env_with_prefix(prefix: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        PostgresVaultConfig::from_env_prefixed(Some(prefix))
+    }
+
+    fn from_env_prefixed(prefix: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let vault_cfg = DataVaultConfig::from_env(prefix)?;
+        let table_cfg = PostgresTableConfig::from_env(prefix)?;
+        let replica_cfg = PostgresReplicaConfig::from_env(prefix)?;
+        let cfg = PostgresVaultConfig {
+            postgres: DeadpoolPostgresConfig::from_env(prefix)?.postgres,
+            encryption: EncryptionConfig::from_env(prefix)?.into(),
+            retention: Duration::from_secs(vault_cfg.retention_secs),
+            slow_op: vault_cfg.slow_op_ms.map(Duration::from_millis),
+            cvv_policy: CvvPolicy::from_config(vault_cfg.cvv_policy, vault_cfg.cvv_ttl_secs),
+            validate_cards: vault_cfg.validate_cards,
+            reject_expired: vault_cfg.reject_expired,
+            retry: RetryConfig::from_env(prefix)?.into(),
+            plaintext_fields: parse_fields(&vault_cfg.plaintext_fields)?,
+            blind_index_fields: parse_fields(&vault_cfg.blind_index_fields)?,
+            table: table_cfg.table_name,
+            schema: table_cfg.schema,
+            auto_create_schema: vault_cfg.auto_create_schema,
+            env_prefix: prefix.map(str::to_string),
+            replicas: Vec::new(),
+            max_replica_lag: replica_cfg.max_replica_lag_ms.map(Duration::from_millis),
+        };
+        let cfg = match PoolRecycleConfig::from_env(&env_name(prefix, "POSTGRES"))?.pool_recycle {
+            Some(recycle) => cfg.with_recycle(recycle),
+            None => cfg,
+        };
+        Ok(cfg.with_replica_hosts(&replica_cfg.hosts()))
+    }
+
+    /// An error naming the variable that is wrong, see
+    /// `EncryptionSettings::validate`, called by
+    /// `PostgresDataVault::from_config`
+    pub fn validate(&self) -> Result<(), ::config::ConfigError> {
+        let prefix = self.env_prefix.as_deref();
+        self.encryption.validate_prefixed(prefix)?;
+        validate_pool_size(&env_name(prefix, "POSTGRES.POOL.MAX_SIZE"), self.postgres.pool.as_ref().map(|pool| pool.max_size))?;
+        if let Err(e) = self.postgres.get_pg_config() {
+            return Err(::config::ConfigError::Message(format!("{} configuration is invalid: {}", env_name(prefix, "POSTGRES"), e)))
+        }
+        for replica in &self.replicas {
+            if let Err(e) = replica.get_pg_config() {
+                return Err(::config::ConfigError::Message(format!("{} configuration is invalid: {}", env_name(prefix, "PG_REPLICA_HOSTS"), e)))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn with_pool_max_size(mut self, max_size: usize) -> Self {
+        self.postgres.pool.get_or_insert_with(Default::default).max_size = max_size;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Log store and retrieve operations taking longer than
+    /// `threshold` at warn level, with the `data_vault::slow` target,
+    /// with the time spent in each stage, see `LatencyStage`, needs
+    /// the `log` feature
+    pub fn with_slow_op(mut self, threshold: Duration) -> Self {
+        self.slow_op = Some(threshold);
+        self
+    }
+
+    pub fn with_cvv_policy(mut self, cvv_policy: CvvPolicy) -> Self {
+        self.cvv_policy = cvv_policy;
+        self
+    }
+
+    /// Store cards without checking the length and Luhn check digit
+    /// of their numbers and their expiry with `false`, e.g. for test
+    /// cards of a processor that are not valid card numbers
+    pub fn with_card_validation(mut self, validate_cards: bool) -> Self {
+        self.validate_cards = validate_cards;
+        self
+    }
+
+    /// Refuse cards that expired before the current month with
+    /// `ValidationError::Expired`
+    pub fn with_reject_expired(mut self, reject_expired: bool) -> Self {
+        self.reject_expired = reject_expired;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Check pooled connections on checkout with `recycle`, the
+    /// same as setting `postgres.manager`
+    pub fn with_recycle(mut self, recycle: RecycleMethod) -> Self {
+        self.postgres.manager = Some(deadpool_postgres::ManagerConfig { recycling_method: recycle.into() });
+        self
+    }
+
+    pub fn with_plaintext_fields(mut self, fields: &[CardField]) -> Self {
+        self.plaintext_fields = fields.to_vec();
+        self
+    }
+
+    pub fn with_blind_index_fields(mut self, fields: &[CardField]) -> Self {
+        self.blind_index_fields = fields.to_vec();
+        self
+    }
+
+    /// Connect over the unix socket in `dir`, e.g. `/run/postgresql`,
+    /// instead of TCP, the same as setting `postgres.host` to `dir`
+    pub fn with_socket_dir(mut self, dir: &str) -> Self {
+        self.postgres.host = Some(dir.to_string());
+        self.postgres.hosts = None;
+        self
+    }
+
+    /// Keep the records in `schema`.`table` instead of `data_vault`,
+    /// the table needs the columns and unique index of the default
+    /// one, see `PostgresDataVault::run_migrations`
+    pub fn with_table(mut self, schema: Option<&str>, table: &str) -> Self {
+        self.schema = schema.map(str::to_string);
+        self.table = table.to_string();
+        self
+    }
+
+    /// Create the table, or upgrade it, with
+    /// `PostgresDataVault::run_migrations` on the first connection
+    /// the vault takes, e.g. for development and CI databases
+    pub fn with_auto_create_schema(mut self, auto_create: bool) -> Self {
+        self.auto_create_schema = auto_create;
+        self
+    }
+
+    /// Answer `retrieve`, `retrieve_credit_card` and `exists` from the
+    /// read replica `postgres` as well, see `PostgresDataVault`
+    pub fn with_replica(mut self, postgres: deadpool_postgres::Config) -> Self {
+        self.replicas.push(postgres);
+        self
+    }
+
+    /// `with_replica` for each of `hosts`, `host` or `host:port`,
+    /// connected with the user, database and pool settings of
+    /// `postgres`, so these are set first
+    pub fn with_replica_hosts(mut self, hosts: &[&str]) -> Self {
+        for host in hosts {
+            let mut replica = self.postgres.clone();
+            // an IPv6 address has several colons and no port
+            match host.split_once(':').filter(|(_, port)| !port.contains(':')).map(|(host, port)| (host, port.parse())) {
+                Some((host, Ok(port))) => {
+                    replica.host = Some(host.to_string());
+                    replica.port = Some(port);
+                },
+                _ => replica.host = Some(host.to_string()),
+            }
+            replica.hosts = None;
+            replica.ports = None;
+            self.replicas.push(replica);
+        }
+        self
+    }
+
+    /// Skip replicas more than `lag` behind the primary
+    pub fn with_max_replica_lag(mut self, lag: Duration) -> Self {
+        self.max_replica_lag = Some(lag);
+        self
+    }
+}
+
+/// comma separated column names, e.g. `expiration_month, brand`
+#[cfg(feature = "postgres")]
+fn parse_fields(fields: &str) -> Result<Vec<CardField>, crate::error::DataVaultError> {
+    fields.split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        RetryConfig {
+            max_attempts: policy.max_attempts,
+            base_delay_ms: policy.base_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+            jitter: policy.jitter,
+        }
+    }
+}
+
+impl From<RetryConfig> for RetryPolicy {
+    fn from(cfg: RetryConfig) -> Self {
+        RetryPolicy {
+            max_attempts: cfg.max_attempts,
+            base_delay: Duration::from_millis(cfg.base_delay_ms),
+            max_delay: Duration::from_millis(cfg.max_delay_ms),
+            jitter: cfg.jitter,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ChaosConfig {
+    pub latency_ms: u64,
+    pub jitter_ms: u64,
+    pub timeout_rate: f64,
+    pub timeout_ms: u64,
+    pub error_rate: f64,
+    pub seed: Option<u64>,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        let policy = ChaosPolicy::default();
+        ChaosConfig {
+            latency_ms: policy.latency.as_millis() as u64,
+            jitter_ms: policy.jitter.as_millis() as u64,
+            timeout_rate: policy.timeout_rate,
+            timeout_ms: policy.timeout.as_millis() as u64,
+            error_rate: policy.error_rate,
+            seed: policy.seed,
+        }
+    }
+}
+
+impl From<ChaosConfig> for ChaosPolicy {
+    fn from(cfg: ChaosConfig) -> Self {
+        ChaosPolicy {
+            latency: Duration::from_millis(cfg.latency_ms),
+            jitter: Duration::from_millis(cfg.jitter_ms),
+            timeout_rate: cfg.timeout_rate,
+            timeout: Duration::from_millis(cfg.timeout_ms),
+            error_rate: cfg.error_rate,
+            seed: cfg.seed,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditConfig {
+    pub log: String,
+    pub caller: String,
+}
+
+/// How `lambda::vault` builds the vault of a Lambda execution
+/// environment
+#[cfg(feature = "lambda")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LambdaConfig {
+    /// `redis` or `postgres`, by default redis when `REDIS_URL` is
+    /// set and postgres otherwise
+    pub backend: Option<String>,
+    /// `random` or `deterministic`, random by default
+    pub tokenizer: Option<String>,
+    /// the pool size unless the backend's own variable sets one
+    pub pool_max_size: Option<usize>,
+}
+
+/// How `data-vault-server` listens and which vault it serves, see
+/// `server::run`
+///
+/// The vault is read from the configuration file `vault_config`
+/// names, see `Config::from_file`, or else from the environment with
+/// `backend` and `tokenizer`.  `Debug` does not print the API keys.
+#[cfg(feature = "server")]
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// the address to listen on
+    pub addr: String,
+    /// the API keys clients send as `Authorization: Bearer <key>`,
+    /// separated by commas so keys can be rotated one at a time
+    pub api_keys: String,
+    /// PEM certificate chain served to clients
+    pub tls_cert_file: Option<PathBuf>,
+    /// PEM key of the certificate
+    pub tls_key_file: Option<PathBuf>,
+    /// serve plain HTTP without a certificate, e.g. behind a sidecar
+    /// terminating TLS, refused otherwise
+    pub allow_plaintext: bool,
+    pub vault_config: Option<PathBuf>,
+    pub backend: Backend,
+    pub tokenizer: TokenizerKind,
+}
+
+#[cfg(feature = "server")]
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            addr: "0.0.0.0:8443".to_string(),
+            api_keys: String::new(),
+            tls_cert_file: None,
+            tls_key_file: None,
+            allow_plaintext: false,
+            vault_config: None,
+            backend: Backend::Redis,
+            tokenizer: TokenizerKind::Random,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("addr", &self.addr)
+            .field("api_keys", &format!("<{} redacted>", self.api_keys().len()))
+            .field("tls_cert_file", &self.tls_cert_file)
+            .field("tls_key_file", &self.tls_key_file)
+            .field("allow_plaintext", &self.allow_plaintext)
+            .field("vault_config", &self.vault_config)
+            .field("backend", &self.backend)
+            .field("tokenizer", &self.tokenizer)
+            .finish()
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerConfig {
+    /// The API keys, without blanks
+    pub fn api_keys(&self) -> Vec<&str> {
+        self.api_keys.split(',').map(str::trim).filter(|key| !key.is_empty()).collect()
+    }
+
+    /// An error naming the variable unless there is an API key and
+    /// either a certificate with its key or `allow_plaintext`
+    pub fn validate(&self) -> Result<(), ::config::ConfigError> {
+        if self.api_keys().is_empty() {
+            return Err(::config::ConfigError::Message("DATA_VAULT_SERVER_API_KEYS must name at least one key".to_string()))
+        }
+        match (&self.tls_cert_file, &self.tls_key_file) {
+            (Some(_), Some(_)) => Ok(()),
+            (None, None) if self.allow_plaintext => Ok(()),
+            (None, None) => Err(::config::ConfigError::Message(
+                "set DATA_VAULT_SERVER_TLS_CERT_FILE and DATA_VAULT_SERVER_TLS_KEY_FILE, or DATA_VAULT_SERVER_ALLOW_PLAINTEXT=true".to_string()
+            )),
+            _ => Err(::config::ConfigError::Message(
+                "set both DATA_VAULT_SERVER_TLS_CERT_FILE and DATA_VAULT_SERVER_TLS_KEY_FILE".to_string()
+            )),
+        }
+    }
+}
+
+/// Where `RemoteDataVault` finds the vault service, see `server`.
+/// `Debug` does not print the API key.
+#[cfg(feature = "remote")]
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct RemoteVaultConfig {
+    /// the service's base URL, e.g. `https://vault.internal:8443`
+    pub url: String,
+    /// sent as `Authorization: Bearer <key>`
+    pub api_key: String,
+    /// how long a request may take, ignored by runtimes without timers
+    pub timeout_ms: u64,
+}
+
+#[cfg(feature = "remote")]
+impl Default for RemoteVaultConfig {
+    fn default() -> Self {
+        RemoteVaultConfig {
+            url: String::new(),
+            api_key: String::new(),
+            timeout_ms: 10_000,
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl fmt::Debug for RemoteVaultConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteVaultConfig")
+            .field("url", &self.url)
+            .field("api_key", &"<redacted>")
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+/// Where `events::EventPublisher::from_env` publishes change events.
+/// `Debug` does not print the webhook secret.
+#[cfg(any(feature = "kafka", feature = "nats", feature = "webhooks"))]
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct EventsConfig {
+    /// comma separated Kafka bootstrap servers
+    pub kafka_brokers: Option<String>,
+    /// the NATS server, used when no Kafka brokers are set
+    pub nats_url: Option<String>,
+    /// the endpoint events are posted to, used when neither Kafka
+    /// brokers nor a NATS server are set
+    pub webhook_url: Option<String>,
+    /// the key webhook payloads are signed with
+    pub webhook_secret: String,
+    /// the Kafka topic or NATS subject
+    pub topic: String,
+    /// events waiting to be published before new ones are dropped
+    pub buffer: usize,
+}
+
+#[cfg(any(feature = "kafka", feature = "nats", feature = "webhooks"))]
+impl Default for EventsConfig {
+    fn default() -> Self {
+        EventsConfig {
+            kafka_brokers: None,
+            nats_url: None,
+            webhook_url: None,
+            webhook_secret: String::new(),
+            topic: "data_vault.changes".to_string(),
+            buffer: 1024,
+        }
+    }
+}
+
+#[cfg(any(feature = "kafka", feature = "nats", feature = "webhooks"))]
+impl fmt::Debug for EventsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventsConfig")
+            .field("kafka_brokers", &self.kafka_brokers)
+            .field("nats_url", &self.nats_url)
+            .field("webhook_url", &self.webhook_url)
+            .field("webhook_secret", &"<redacted>")
+            .field("topic", &self.topic)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+#[cfg(feature = "redis")]
 #[derive(Debug, Deserialize, Default)]
 pub struct DeadpoolRedisConfig {
     #[serde(default)]
     pub redis: deadpool_redis::Config,
 }
 
+#[cfg(feature = "redis")]
+#[derive(Debug, Deserialize, Default)]
+pub struct RedisKeyConfig {
+    #[serde(default)]
+    pub key_prefix: String,
+}
+
+#[cfg(any(feature = "redis", feature = "postgres"))]
+#[derive(Debug, Deserialize, Default)]
+pub struct PoolRecycleConfig {
+    #[serde(default)]
+    pub pool_recycle: Option<RecycleMethod>,
+}
+
+#[cfg(feature = "postgres")]
+pub(crate) const DEFAULT_TABLE_NAME: &str = "data_vault";
+
+#[cfg(feature = "postgres")]
+#[derive(Debug, Deserialize)]
+pub struct PostgresTableConfig {
+    #[serde(default = "default_table_name")]
+    pub table_name: String,
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+#[cfg(feature = "postgres")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PostgresReplicaConfig {
+    pub replica_hosts: String,
+    pub max_replica_lag_ms: Option<u64>,
+}
+
+#[cfg(feature = "postgres")]
+fn default_table_name() -> String {
+    DEFAULT_TABLE_NAME.to_string()
+}
+
+#[cfg(feature = "postgres")]
 #[derive(Debug, Deserialize, Default)]
 pub struct DeadpoolPostgresConfig {
     #[serde(default)]
@@ -25,12 +1044,205 @@ pub struct DeadpoolPostgresConfig {
 /// Possible Values:
 /// ENCRYPTED_DATA_VAULT_KEY=000102030405060708090a0b0c0d0e0f
 /// ENCRYPTED_DATA_VAULT_IV=f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff
+/// ENCRYPTED_DATA_VAULT_KEY_FILE=/run/secrets/data_vault_key
+/// ENCRYPTED_DATA_VAULT_IV_FILE=/run/secrets/data_vault_iv
 impl EncryptionConfig {
-    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
         dotenv().ok();
         let mut cfg = ::config::Config::new();
-        let environment = ::config::Environment::new().separator("_").prefix("ENCRYPTED_DATA_VAULT");
+        let environment = ::config::Environment::new().separator("_").prefix(&env_name(prefix, "ENCRYPTED_DATA_VAULT"));
         cfg.merge(environment).unwrap();
+        if let Some(key) = read_secret_file(&env_name(prefix, "ENCRYPTED_DATA_VAULT_KEY_FILE"), &env_name(prefix, "ENCRYPTED_DATA_VAULT_KEY"))? {
+            cfg.set("key", key)?;
+        }
+        if let Some(iv) = read_secret_file(&env_name(prefix, "ENCRYPTED_DATA_VAULT_IV_FILE"), &env_name(prefix, "ENCRYPTED_DATA_VAULT_IV"))? {
+            cfg.set("iv", iv)?;
+        }
+        cfg.try_into()
+    }
+}
+
+/// The contents of the file the variable `file_var` names, without
+/// the trailing newline, the Docker and Kubernetes convention for
+/// secrets mounted as files
+///
+/// `None` if `file_var` is not set, an error if `var`, the variable
+/// holding the secret itself, is set as well.
+pub(crate) fn read_secret_file(file_var: &str, var: &str) -> Result<Option<String>, ::config::ConfigError> {
+    let path = match std::env::var_os(file_var) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    if std::env::var_os(var).is_some() {
+        return Err(::config::ConfigError::Message(format!("set either {} or {}, not both", var, file_var)));
+    }
+    match std::fs::read_to_string(&path) {
+        Ok(secret) => Ok(Some(secret.trim_end_matches(&['\r', '\n'][..]).to_string())),
+        Err(e) => Err(::config::ConfigError::Message(format!(
+            "{} names {}, which cannot be read: {}", file_var, std::path::Path::new(&path).display(), e
+        ))),
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for behaviour shared by every back end.
+/// Possible Values:
+/// DATA_VAULT_RETENTION_SECS=15552000
+/// DATA_VAULT_CVV_POLICY=strip
+/// DATA_VAULT_CVV_TTL_SECS=600
+/// DATA_VAULT_VALIDATE_CARDS=true
+/// DATA_VAULT_REJECT_EXPIRED=false
+/// DATA_VAULT_SLOW_OP_MS=50
+/// DATA_VAULT_PLAINTEXT_FIELDS=expiration_month,expiration_year,brand
+/// DATA_VAULT_BLIND_INDEX_FIELDS=number
+/// DATA_VAULT_AUTO_CREATE_SCHEMA=true
+#[cfg(any(feature = "redis", feature = "postgres"))]
+impl DataVaultConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "DATA_VAULT"));
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `retry::RetryPolicy`.
+/// Possible Values:
+/// DATA_VAULT_RETRY_MAX_ATTEMPTS=3
+/// DATA_VAULT_RETRY_BASE_DELAY_MS=50
+/// DATA_VAULT_RETRY_MAX_DELAY_MS=2000
+/// DATA_VAULT_RETRY_JITTER=true
+impl RetryConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "DATA_VAULT_RETRY"));
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `server::run`.
+/// Possible Values:
+/// DATA_VAULT_SERVER_ADDR=0.0.0.0:8443
+/// DATA_VAULT_SERVER_API_KEYS=key-1,key-2
+/// DATA_VAULT_SERVER_API_KEYS_FILE=/run/secrets/data_vault_api_keys
+/// DATA_VAULT_SERVER_TLS_CERT_FILE=/etc/data_vault/server.pem
+/// DATA_VAULT_SERVER_TLS_KEY_FILE=/etc/data_vault/server.key
+/// DATA_VAULT_SERVER_ALLOW_PLAINTEXT=false
+/// DATA_VAULT_SERVER_VAULT_CONFIG=/etc/data_vault/data_vault.toml
+/// DATA_VAULT_SERVER_BACKEND=postgres
+/// DATA_VAULT_SERVER_TOKENIZER=deterministic
+#[cfg(feature = "server")]
+impl ServerConfig {
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix("DATA_VAULT_SERVER");
+        cfg.merge(environment)?;
+        if let Some(api_keys) = read_secret_file("DATA_VAULT_SERVER_API_KEYS_FILE", "DATA_VAULT_SERVER_API_KEYS")? {
+            cfg.set("api_keys", api_keys)?;
+        }
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `remote::RemoteDataVault`, edge runtimes without environment
+/// variables pass a `RemoteVaultConfig` to `from_config` instead.
+/// Possible Values:
+/// DATA_VAULT_REMOTE_URL=https://vault.internal:8443
+/// DATA_VAULT_REMOTE_API_KEY=key-1
+/// DATA_VAULT_REMOTE_API_KEY_FILE=/run/secrets/vault_api_key
+/// DATA_VAULT_REMOTE_TIMEOUT_MS=10000
+#[cfg(feature = "remote")]
+impl RemoteVaultConfig {
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix("DATA_VAULT_REMOTE");
+        cfg.merge(environment)?;
+        if let Some(api_key) = read_secret_file("DATA_VAULT_REMOTE_API_KEY_FILE", "DATA_VAULT_REMOTE_API_KEY")? {
+            cfg.set("api_key", api_key)?;
+        }
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `events::EventPublisher`.
+/// Possible Values:
+/// DATA_VAULT_EVENTS_KAFKA_BROKERS=kafka-1:9092,kafka-2:9092
+/// DATA_VAULT_EVENTS_NATS_URL=nats://127.0.0.1:4222
+/// DATA_VAULT_EVENTS_WEBHOOK_URL=https://hooks.example.com/data-vault
+/// DATA_VAULT_EVENTS_WEBHOOK_SECRET=whsec-1
+/// DATA_VAULT_EVENTS_WEBHOOK_SECRET_FILE=/run/secrets/webhook_secret
+/// DATA_VAULT_EVENTS_TOPIC=data_vault.changes
+/// DATA_VAULT_EVENTS_BUFFER=1024
+#[cfg(any(feature = "kafka", feature = "nats", feature = "webhooks"))]
+impl EventsConfig {
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix("DATA_VAULT_EVENTS");
+        cfg.merge(environment)?;
+        if let Some(secret) = read_secret_file("DATA_VAULT_EVENTS_WEBHOOK_SECRET_FILE", "DATA_VAULT_EVENTS_WEBHOOK_SECRET")? {
+            cfg.set("webhook_secret", secret)?;
+        }
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `chaos::ChaosPolicy`.
+/// Possible Values:
+/// DATA_VAULT_CHAOS_LATENCY_MS=5
+/// DATA_VAULT_CHAOS_JITTER_MS=20
+/// DATA_VAULT_CHAOS_TIMEOUT_RATE=0.01
+/// DATA_VAULT_CHAOS_TIMEOUT_MS=1000
+/// DATA_VAULT_CHAOS_ERROR_RATE=0.05
+/// DATA_VAULT_CHAOS_SEED=42
+impl ChaosConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "DATA_VAULT_CHAOS"));
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `lambda::vault`.
+/// Possible Values:
+/// DATA_VAULT_LAMBDA_BACKEND=postgres
+/// DATA_VAULT_LAMBDA_TOKENIZER=deterministic
+/// DATA_VAULT_LAMBDA_POOL_MAX_SIZE=2
+#[cfg(feature = "lambda")]
+impl LambdaConfig {
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix("DATA_VAULT_LAMBDA");
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for `audit::AuditedDataVault`.
+/// Possible Values:
+/// DATA_VAULT_AUDIT_LOG=/var/log/data_vault/audit.log
+/// DATA_VAULT_AUDIT_CALLER=checkout-service
+impl AuditConfig {
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix("DATA_VAULT_AUDIT");
+        cfg.merge(environment)?;
         cfg.try_into()
     }
 }
@@ -40,11 +1252,109 @@ impl EncryptionConfig {
 /// Possible Values:
 /// REDIS_URL=redis://:foobared@127.0.0.1/
 /// REDIS_POOL_MAX_SIZE=16
+/// REDIS_URL_FILE=/run/secrets/redis_url
+#[cfg(feature = "redis")]
 impl DeadpoolRedisConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = instance_environment(prefix).separator("_");
+        cfg.merge(environment)?;
+        if let Some(url) = read_secret_file(&env_name(prefix, "REDIS_URL_FILE"), &env_name(prefix, "REDIS_URL"))? {
+            cfg.set("redis.url", url)?;
+        }
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for the keys `redis_data_vault::RedisDataVault` writes.
+/// Possible Values:
+/// REDIS_KEY_PREFIX=dv:prod:
+#[cfg(feature = "redis")]
+impl RedisKeyConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "REDIS"));
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for the read replicas of `postgres_data_vault::PostgresDataVault`.
+/// Possible Values:
+/// PG_REPLICA_HOSTS=replica-1,replica-2:5433
+/// PG_MAX_REPLICA_LAG_MS=500
+#[cfg(feature = "postgres")]
+impl PostgresReplicaConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "PG"));
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+
+    /// the comma separated `replica_hosts`
+    pub fn hosts(&self) -> Vec<&str> {
+        self.replica_hosts.split(',')
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .collect()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for the table of `postgres_data_vault::PostgresDataVault`.
+/// Possible Values:
+/// PG_SCHEMA=vault
+/// PG_TABLE_NAME=data_vault
+#[cfg(feature = "postgres")]
+impl PostgresTableConfig {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "PG"));
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for the pool of the back end whose variables start with `prefix`.
+/// Possible Values:
+/// REDIS_POOL_RECYCLE=verified
+/// POSTGRES_POOL_RECYCLE=fast
+#[cfg(any(feature = "redis", feature = "postgres"))]
+impl PoolRecycleConfig {
+    pub fn from_env(prefix: &str) -> Result<Self, ::config::ConfigError> {
+        dotenv().ok();
+        let mut cfg = ::config::Config::new();
+        let environment = ::config::Environment::with_prefix(prefix);
+        cfg.merge(environment)?;
+        cfg.try_into()
+    }
+}
+
+/// Populates a configuration from .env file or Environment Variables
+/// for the `rediss://` connections of `redis_data_vault::RedisDataVault`.
+/// Possible Values:
+/// REDIS_TLS_CA_FILE=/etc/ssl/certs/redis-ca.pem
+/// REDIS_TLS_CERT_FILE=/etc/data_vault/redis-client.pem
+/// REDIS_TLS_KEY_FILE=/etc/data_vault/redis-client.key
+/// REDIS_TLS_VERIFY_HOSTNAME=true
+#[cfg(feature = "redis-tls")]
+impl RedisTlsConfig {
     pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        RedisTlsConfig::from_env_prefixed(None)
+    }
+
+    pub(crate) fn from_env_prefixed(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
         dotenv().ok();
         let mut cfg = ::config::Config::new();
-        let environment = ::config::Environment::new().separator("_");
+        let environment = ::config::Environment::with_prefix(&env_name(prefix, "REDIS_TLS"));
         cfg.merge(environment)?;
         cfg.try_into()
     }
@@ -53,15 +1363,217 @@ impl DeadpoolRedisConfig {
 /// Populates a configuration from .env file or Environment Variables
 /// for `postgres_data_vault::DeadpoolPostgresConfig`.
 /// Possible Values:
-/// REDIS_URL=redis://:foobared@127.0.0.1/
-/// REDIS_POOL_MAX_SIZE=16
+/// POSTGRES.HOST=127.0.0.1
+/// POSTGRES.PASSWORD=foobared
+/// POSTGRES.POOL.MAX_SIZE=16
+/// PG_PASSWORD_FILE=/run/secrets/postgres_password
+#[cfg(feature = "postgres")]
 impl DeadpoolPostgresConfig {
-    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+    pub fn from_env(prefix: Option<&str>) -> Result<Self, ::config::ConfigError> {
         dotenv().ok();
         let mut cfg = ::config::Config::new();
-        let environment = ::config::Environment::new().separator(".");
+        let environment = instance_environment(prefix).separator(".");
         cfg.merge(environment)?;
+        if let Some(password) = read_secret_file(&env_name(prefix, "PG_PASSWORD_FILE"), &env_name(prefix, "POSTGRES.PASSWORD"))? {
+            cfg.set("postgres.password", password)?;
+        }
         cfg.try_into()
     }
 }
 
+/// The deadpool runtime for the enabled `rt-*` feature.
+/// deadpool skips this field when deserializing, so without it
+/// any configured pool timeout fails with `NoRuntimeSpecified`.
+/// tokio wins if both runtime features are enabled.
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub(crate) fn pool_runtime() -> Runtime {
+    #[cfg(feature = "rt-tokio")]
+    return Runtime::Tokio1;
+    #[cfg(all(feature = "rt-async-std", not(feature = "rt-tokio")))]
+    return Runtime::AsyncStd1;
+    #[cfg(not(any(feature = "rt-tokio", feature = "rt-async-std")))]
+    return Runtime::None;
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::config::EncryptionSettings;
+
+    #[test]
+    fn test_encryption_settings() {
+        let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let debug = format!("{:?}", settings);
+        assert!(!debug.contains("000102030405060708090a0b0c0d0e0f"));
+        assert!(!debug.contains("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"));
+
+        assert_eq!(EncryptionSettings::from_env().unwrap().key.len(), 32);
+    }
+
+    #[test]
+    fn test_read_secret_file() {
+        use crate::config::read_secret_file;
+
+        let path = std::env::temp_dir().join("data_vault_test_secret");
+        std::fs::write(&path, "s3cr3t\n").unwrap();
+        assert_eq!(read_secret_file("DATA_VAULT_TEST_SECRET_FILE", "DATA_VAULT_TEST_SECRET").unwrap(), None);
+
+        std::env::set_var("DATA_VAULT_TEST_SECRET_FILE", &path);
+        assert_eq!(read_secret_file("DATA_VAULT_TEST_SECRET_FILE", "DATA_VAULT_TEST_SECRET").unwrap().as_deref(), Some("s3cr3t"));
+
+        std::env::set_var("DATA_VAULT_TEST_SECRET", "s3cr3t");
+        let e = read_secret_file("DATA_VAULT_TEST_SECRET_FILE", "DATA_VAULT_TEST_SECRET").unwrap_err();
+        assert_eq!(e.to_string(), "set either DATA_VAULT_TEST_SECRET or DATA_VAULT_TEST_SECRET_FILE, not both");
+
+        std::env::remove_var("DATA_VAULT_TEST_SECRET");
+        std::env::set_var("DATA_VAULT_TEST_SECRET_FILE", "/nonexistent/secret");
+        assert!(read_secret_file("DATA_VAULT_TEST_SECRET_FILE", "DATA_VAULT_TEST_SECRET").is_err());
+        std::env::remove_var("DATA_VAULT_TEST_SECRET_FILE");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_encryption_settings() {
+        let iv = "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff";
+        assert!(EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", iv).validate().is_ok());
+
+        let short = EncryptionSettings::new("0001020304050607", iv).validate().unwrap_err();
+        assert_eq!(short.to_string(), "ENCRYPTED_DATA_VAULT_KEY must be 32 hexadecimal characters, it has 16");
+
+        let not_hex = EncryptionSettings::new("000102030405060708090a0b0c0d0e0g", iv).validate().unwrap_err();
+        assert_eq!(not_hex.to_string(), "ENCRYPTED_DATA_VAULT_KEY must be 32 hexadecimal characters, character 32 is not 0-9 or a-f");
+        assert!(!not_hex.to_string().contains("0001020304"));
+
+        let no_iv = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "").validate().unwrap_err();
+        assert!(no_iv.to_string().starts_with("ENCRYPTED_DATA_VAULT_IV"));
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_redis_vault_config() {
+        use crate::config::RedisVaultConfig;
+        use crate::cvv::CvvPolicy;
+
+        let cfg = RedisVaultConfig::new("redis://127.0.0.1/", EncryptionSettings::default())
+            .with_pool_max_size(4);
+        assert_eq!(cfg.redis.url.as_deref(), Some("redis://127.0.0.1/"));
+        assert_eq!(cfg.redis.pool.unwrap().max_size, 4);
+        assert_eq!(cfg.cvv_policy, CvvPolicy::Strip);
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_redis_vault_config_with_prefix() {
+        use crate::config::RedisVaultConfig;
+
+        std::env::set_var("TEST_VAULT_A_REDIS_URL", "redis://127.0.0.1:6390/");
+        std::env::set_var("TEST_VAULT_A_REDIS_KEY_PREFIX", "a:");
+        std::env::set_var("TEST_VAULT_A_ENCRYPTED_DATA_VAULT_KEY", "a0a1a2a3a4a5a6a7a8a9aaabacadaeaf");
+        std::env::set_var("TEST_VAULT_A_ENCRYPTED_DATA_VAULT_IV", "b0b1b2b3b4b5b6b7b8b9babbbcbdbebf");
+        std::env::set_var("TEST_VAULT_A_DATA_VAULT_RETRY_MAX_ATTEMPTS", "3");
+        std::env::set_var("TEST_VAULT_A_DATA_VAULT_SLOW_OP_MS", "50");
+        let cfg = RedisVaultConfig::from_env_with_prefix("TEST_VAULT_A").unwrap();
+        assert_eq!(cfg.redis.url.as_deref(), Some("redis://127.0.0.1:6390/"));
+        assert_eq!(cfg.key_prefix, "a:");
+        assert_eq!(cfg.encryption.key, "a0a1a2a3a4a5a6a7a8a9aaabacadaeaf");
+        assert_eq!(cfg.retry.max_attempts, 3);
+        assert_eq!(cfg.slow_op, Some(std::time::Duration::from_millis(50)));
+        assert_eq!(cfg.env_prefix.as_deref(), Some("TEST_VAULT_A"));
+        assert!(cfg.validate().is_ok());
+
+        // the unprefixed variables belong to another vault
+        assert_ne!(RedisVaultConfig::from_env().unwrap().encryption.key, cfg.encryption.key);
+
+        std::env::set_var("TEST_VAULT_B_ENCRYPTED_DATA_VAULT_KEY", "short");
+        std::env::set_var("TEST_VAULT_B_ENCRYPTED_DATA_VAULT_IV", "b0b1b2b3b4b5b6b7b8b9babbbcbdbebf");
+        let e = RedisVaultConfig::from_env_with_prefix("TEST_VAULT_B").unwrap().validate().unwrap_err();
+        assert!(e.to_string().starts_with("TEST_VAULT_B_ENCRYPTED_DATA_VAULT_KEY must be"));
+        assert!(RedisVaultConfig::from_env_with_prefix("TEST_VAULT_MISSING").is_err());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres_vault_config_with_prefix() {
+        use crate::config::PostgresVaultConfig;
+
+        std::env::set_var("TEST_VAULT_C_POSTGRES.HOST", "/run/postgresql");
+        std::env::set_var("TEST_VAULT_C_POSTGRES.DBNAME", "vault_c");
+        std::env::set_var("TEST_VAULT_C_PG_TABLE_NAME", "cards_c");
+        std::env::set_var("TEST_VAULT_C_DATA_VAULT_AUTO_CREATE_SCHEMA", "true");
+        std::env::set_var("TEST_VAULT_C_ENCRYPTED_DATA_VAULT_KEY", "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf");
+        std::env::set_var("TEST_VAULT_C_ENCRYPTED_DATA_VAULT_IV", "b0b1b2b3b4b5b6b7b8b9babbbcbdbebf");
+        let cfg = PostgresVaultConfig::from_env_with_prefix("TEST_VAULT_C").unwrap();
+        assert_eq!(cfg.postgres.host.as_deref(), Some("/run/postgresql"));
+        assert_eq!(cfg.postgres.dbname.as_deref(), Some("vault_c"));
+        assert_eq!(cfg.table, "cards_c");
+        assert!(cfg.auto_create_schema);
+        assert_eq!(cfg.encryption.key, "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf");
+        assert!(cfg.validate().is_ok());
+
+        let e = cfg.with_pool_max_size(0).validate().unwrap_err();
+        assert_eq!(e.to_string(), "TEST_VAULT_C_POSTGRES.POOL.MAX_SIZE must be at least 1");
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres_replicas() {
+        use crate::config::PostgresVaultConfig;
+        use std::time::Duration;
+
+        std::env::set_var("TEST_VAULT_D_POSTGRES.HOST", "primary");
+        std::env::set_var("TEST_VAULT_D_POSTGRES.DBNAME", "vault_d");
+        std::env::set_var("TEST_VAULT_D_PG_REPLICA_HOSTS", "replica-1, replica-2:5433,");
+        std::env::set_var("TEST_VAULT_D_PG_MAX_REPLICA_LAG_MS", "500");
+        std::env::set_var("TEST_VAULT_D_ENCRYPTED_DATA_VAULT_KEY", "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf");
+        std::env::set_var("TEST_VAULT_D_ENCRYPTED_DATA_VAULT_IV", "b0b1b2b3b4b5b6b7b8b9babbbcbdbebf");
+        let cfg = PostgresVaultConfig::from_env_with_prefix("TEST_VAULT_D").unwrap();
+        assert_eq!(cfg.max_replica_lag, Some(Duration::from_millis(500)));
+        assert_eq!(cfg.replicas.len(), 2);
+        assert_eq!(cfg.replicas[0].host.as_deref(), Some("replica-1"));
+        assert_eq!(cfg.replicas[0].port, None);
+        assert_eq!(cfg.replicas[1].host.as_deref(), Some("replica-2"));
+        assert_eq!(cfg.replicas[1].port, Some(5433));
+        assert!(cfg.replicas.iter().all(|replica| replica.dbname.as_deref() == Some("vault_d")));
+        assert!(cfg.validate().is_ok());
+
+        let cfg = cfg.with_replica_hosts(&["::1"]);
+        assert_eq!(cfg.replicas[2].host.as_deref(), Some("::1"));
+        assert_eq!(cfg.replicas[2].port, None);
+
+        let e = cfg.with_replica(deadpool_postgres::Config::new()).validate().unwrap_err();
+        assert!(e.to_string().starts_with("TEST_VAULT_D_PG_REPLICA_HOSTS configuration is invalid"));
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_validate_redis_vault_config() {
+        use crate::config::RedisVaultConfig;
+
+        let settings = EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        assert!(RedisVaultConfig::new("redis://127.0.0.1/", settings.clone()).validate().is_ok());
+
+        let e = RedisVaultConfig::new("redis://127.0.0.1/", settings.clone()).with_pool_max_size(0).validate().unwrap_err();
+        assert_eq!(e.to_string(), "REDIS_POOL_MAX_SIZE must be at least 1");
+
+        let e = RedisVaultConfig::new("http://127.0.0.1/", settings).validate().unwrap_err();
+        assert!(e.to_string().starts_with("REDIS_URL is not a redis url"));
+
+        let e = RedisVaultConfig::new("redis://127.0.0.1/", EncryptionSettings::default()).validate().unwrap_err();
+        assert!(e.to_string().starts_with("ENCRYPTED_DATA_VAULT_KEY"));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_validate_postgres_vault_config() {
+        use crate::config::PostgresVaultConfig;
+
+        assert!(PostgresVaultConfig::from_env().unwrap().validate().is_ok());
+
+        let e = PostgresVaultConfig::from_env().unwrap().with_pool_max_size(0).validate().unwrap_err();
+        assert_eq!(e.to_string(), "POSTGRES.POOL.MAX_SIZE must be at least 1");
+
+        let mut cfg = PostgresVaultConfig::from_env().unwrap();
+        cfg.postgres.dbname = None;
+        assert!(cfg.validate().unwrap_err().to_string().starts_with("POSTGRES configuration is invalid"));
+    }
+}
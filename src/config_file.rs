@@ -0,0 +1,441 @@
+use serde::Deserialize;
+use crate::config::{EncryptionSettings, RetryConfig};
+use crate::cvv::{CvvPolicy, CvvPolicyKind};
+use crate::recycle::RecycleMethod;
+#[cfg(feature = "redis")]
+use crate::config::RedisVaultConfig;
+#[cfg(feature = "redis-tls")]
+use crate::config::RedisTlsConfig;
+#[cfg(feature = "postgres")]
+use crate::config::PostgresVaultConfig;
+#[cfg(any(feature = "server", feature = "ffi"))]
+use crate::traits::DataVault;
+#[cfg(any(feature = "server", feature = "ffi"))]
+use crate::encryption::AesGcmSivEncryption;
+#[cfg(any(feature = "server", feature = "ffi"))]
+use crate::tokenizer::Tokenizer;
+#[cfg(any(feature = "server", feature = "ffi"))]
+use crate::redis_data_vault::RedisDataVault;
+#[cfg(any(feature = "server", feature = "ffi"))]
+use crate::postgres_data_vault::PostgresDataVault;
+use std::error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The back end a configuration file selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Redis,
+    Postgres,
+}
+
+/// The tokenizer a configuration file selects, the vault type
+/// picks it with its `Tokenizer` parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum TokenizerKind {
+    /// `Blake3Tokenizer`
+    #[default]
+    Random,
+    /// `Blake3DeterministicTokenizer`
+    Deterministic,
+}
+
+/// A secret in a configuration file: inline, from an environment
+/// variable or from a file, e.g. a mounted secret
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum SecretRef {
+    Env { env: String },
+    File { file: PathBuf },
+    Value(String),
+}
+
+impl fmt::Debug for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+            SecretRef::File { file } => f.debug_struct("File").field("file", file).finish(),
+            SecretRef::Value(_) => f.debug_tuple("Value").field(&"<redacted>").finish(),
+        }
+    }
+}
+
+impl SecretRef {
+    fn resolve(&self) -> Result<String, Box<dyn error::Error>> {
+        match self {
+            SecretRef::Env { env } => std::env::var(env)
+                .map_err(|e| format!("{}: {}", env, e).into()),
+            SecretRef::File { file } => Ok(std::fs::read_to_string(file)?.trim_end().to_string()),
+            SecretRef::Value(value) => Ok(value.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EncryptionSection {
+    key: SecretRef,
+    iv: Option<SecretRef>,
+}
+
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+#[derive(Debug, Deserialize)]
+struct RedisSection {
+    url: SecretRef,
+    #[serde(default)]
+    key_prefix: String,
+    pool_max_size: Option<usize>,
+    pool_recycle: Option<RecycleMethod>,
+    #[cfg(feature = "redis-tls")]
+    #[serde(default)]
+    tls: RedisTlsConfig,
+}
+
+#[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+#[derive(Debug, Deserialize)]
+struct PostgresSection {
+    host: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    password: Option<SecretRef>,
+    dbname: Option<String>,
+    schema: Option<String>,
+    table_name: Option<String>,
+    pool_max_size: Option<usize>,
+    pool_recycle: Option<RecycleMethod>,
+    #[serde(default)]
+    replica_hosts: Vec<String>,
+    max_replica_lag_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct VaultSection {
+    retention_secs: Option<u64>,
+    #[serde(default)]
+    cvv_policy: CvvPolicyKind,
+    cvv_ttl_secs: Option<u64>,
+    validate_cards: Option<bool>,
+    #[serde(default)]
+    reject_expired: bool,
+    slow_op_ms: Option<u64>,
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    #[serde(default)]
+    plaintext_fields: Vec<String>,
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    #[serde(default)]
+    blind_index_fields: Vec<String>,
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    #[serde(default)]
+    auto_create_schema: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigFile {
+    backend: Backend,
+    #[serde(default)]
+    tokenizer: TokenizerKind,
+    encryption: EncryptionSection,
+    redis: Option<RedisSection>,
+    postgres: Option<PostgresSection>,
+    #[serde(default)]
+    vault: VaultSection,
+    #[serde(default)]
+    retry: RetryConfig,
+}
+
+/// A vault configuration read from a TOML or YAML file, for
+/// deployments that template files rather than environment variables
+///
+/// The format follows the file extension, `.toml` needs the `toml`
+/// feature and `.yaml` the `yaml` feature.  Secrets are given
+/// inline, as `{ env = "NAME" }` or as `{ file = "/path" }`.  The
+/// section of the selected backend is required, the other is
+/// optional.  A `[retry]` table holds the `RetryPolicy` both back
+/// ends take connections with, a `[redis.tls]` table holds the `RedisTlsConfig` of
+/// `rediss://` urls.
+/// ```toml
+/// backend = "postgres"
+/// tokenizer = "deterministic"
+///
+/// [encryption]
+/// key = { file = "/run/secrets/data_vault_key" }
+///
+/// [postgres]
+/// host = "db.internal"
+/// user = "data_vault"
+/// password = { env = "DATA_VAULT_DB_PASSWORD" }
+/// dbname = "data_vault"
+/// schema = "vault"
+/// pool_max_size = 32
+/// pool_recycle = "fast"
+/// replica_hosts = ["db-replica-1.internal", "db-replica-2.internal:5433"]
+/// max_replica_lag_ms = 500
+///
+/// [vault]
+/// retention_secs = 15552000
+/// cvv_policy = "reject"
+/// validate_cards = true
+/// reject_expired = true
+/// slow_op_ms = 50
+/// plaintext_fields = ["expiration_month", "expiration_year"]
+///
+/// [retry]
+/// max_attempts = 3
+/// base_delay_ms = 100
+/// ```
+/// # example
+/// ```rust,ignore
+/// use data_vault::{Backend, Config, PostgresDataVault, RedisDataVault};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3DeterministicTokenizer;
+///
+/// let cfg = Config::from_file("data_vault.toml").unwrap();
+/// assert_eq!(cfg.backend, Backend::Postgres);
+/// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::from_config(cfg.postgres.unwrap()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub backend: Backend,
+    pub tokenizer: TokenizerKind,
+    pub encryption: EncryptionSettings,
+    #[cfg(feature = "redis")]
+    pub redis: Option<RedisVaultConfig>,
+    #[cfg(feature = "postgres")]
+    pub postgres: Option<PostgresVaultConfig>,
+}
+
+impl Config {
+    /// Read and resolve the configuration at `path`
+    /// returns:
+    ///     * an error for an unknown format, a missing backend
+    ///       section or a secret that can not be read
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn error::Error>> {
+        let mut cfg = ::config::Config::new();
+        cfg.merge(::config::File::from(path.as_ref()))?;
+        let file: ConfigFile = cfg.try_into()?;
+
+        let encryption = EncryptionSettings {
+            key: file.encryption.key.resolve()?,
+            iv: match &file.encryption.iv {
+                Some(iv) => iv.resolve()?,
+                None => String::new(),
+            },
+        };
+        let retention = Duration::from_secs(file.vault.retention_secs.unwrap_or(crate::config::DEFAULT_RETENTION_SECS));
+        let cvv_policy = CvvPolicy::from_config(file.vault.cvv_policy, file.vault.cvv_ttl_secs.unwrap_or(crate::config::DEFAULT_CVV_TTL_SECS));
+        let validate_cards = file.vault.validate_cards.unwrap_or(true);
+        let retry = file.retry.into();
+        let slow_op = file.vault.slow_op_ms.map(Duration::from_millis);
+
+        let selected = match file.backend {
+            Backend::Redis => file.redis.is_some(),
+            Backend::Postgres => file.postgres.is_some(),
+        };
+        if !selected {
+            return Err(format!("the {:?} backend is selected but not configured", file.backend).into())
+        }
+
+        #[cfg(feature = "redis")]
+        let redis = match &file.redis {
+            Some(section) => {
+                let mut redis = RedisVaultConfig::new(&section.url.resolve()?, encryption.clone())
+                    .with_key_prefix(&section.key_prefix)
+                    .with_retention(retention)
+                    .with_cvv_policy(cvv_policy)
+                    .with_card_validation(validate_cards)
+                    .with_reject_expired(file.vault.reject_expired)
+                    .with_retry(retry);
+                if let Some(max_size) = section.pool_max_size {
+                    redis = redis.with_pool_max_size(max_size);
+                }
+                if let Some(slow_op) = slow_op {
+                    redis = redis.with_slow_op(slow_op);
+                }
+                if let Some(recycle) = section.pool_recycle {
+                    redis = redis.with_recycle(recycle);
+                }
+                #[cfg(feature = "redis-tls")]
+                {
+                    redis = redis.with_tls(section.tls.clone());
+                }
+                Some(redis)
+            },
+            None => None,
+        };
+
+        #[cfg(feature = "postgres")]
+        let postgres = match &file.postgres {
+            Some(section) => {
+                let mut postgres_cfg = deadpool_postgres::Config::new();
+                postgres_cfg.host = section.host.clone();
+                postgres_cfg.port = section.port;
+                postgres_cfg.user = section.user.clone();
+                postgres_cfg.password = match &section.password {
+                    Some(password) => Some(password.resolve()?),
+                    None => None,
+                };
+                postgres_cfg.dbname = section.dbname.clone();
+
+                let parse = |fields: &[String]| fields.iter()
+                    .map(|field| field.parse())
+                    .collect::<Result<Vec<_>, _>>();
+                let mut postgres = PostgresVaultConfig::new(postgres_cfg, encryption.clone())
+                    .with_retention(retention)
+                    .with_cvv_policy(cvv_policy)
+                    .with_card_validation(validate_cards)
+                    .with_reject_expired(file.vault.reject_expired)
+                    .with_retry(retry)
+                    .with_plaintext_fields(&parse(&file.vault.plaintext_fields)?)
+                    .with_blind_index_fields(&parse(&file.vault.blind_index_fields)?)
+                    .with_auto_create_schema(file.vault.auto_create_schema);
+                if let Some(max_size) = section.pool_max_size {
+                    postgres = postgres.with_pool_max_size(max_size);
+                }
+                if let Some(slow_op) = slow_op {
+                    postgres = postgres.with_slow_op(slow_op);
+                }
+                if let Some(recycle) = section.pool_recycle {
+                    postgres = postgres.with_recycle(recycle);
+                }
+                let table = section.table_name.as_deref().unwrap_or(crate::config::DEFAULT_TABLE_NAME);
+                postgres = postgres.with_table(section.schema.as_deref(), table);
+                let replica_hosts: Vec<&str> = section.replica_hosts.iter().map(String::as_str).collect();
+                postgres = postgres.with_replica_hosts(&replica_hosts);
+                if let Some(lag) = section.max_replica_lag_ms {
+                    postgres = postgres.with_max_replica_lag(Duration::from_millis(lag));
+                }
+                Some(postgres)
+            },
+            None => None,
+        };
+
+        Ok(Config {
+            backend: file.backend,
+            tokenizer: file.tokenizer,
+            encryption,
+            #[cfg(feature = "redis")]
+            redis,
+            #[cfg(feature = "postgres")]
+            postgres,
+        })
+    }
+}
+
+/// The Redis vault of the `redis` section of `vault_config`, else
+/// the one `RedisDataVault::new` reads from the environment
+#[cfg(any(feature = "server", feature = "ffi"))]
+pub(crate) fn redis_vault<T>(vault_config: Option<Config>) -> Result<RedisDataVault<AesGcmSivEncryption, T>, Box<dyn error::Error>>
+    where
+        T: Tokenizer + std::marker::Sync + std::marker::Send,
+{
+    match vault_config.and_then(|vault_config| vault_config.redis) {
+        Some(redis) => RedisDataVault::from_config(redis),
+        None => RedisDataVault::new(),
+    }
+}
+
+/// The Postgres vault of the `postgres` section of `vault_config`,
+/// else the one `PostgresDataVault::new` reads from the environment
+#[cfg(any(feature = "server", feature = "ffi"))]
+pub(crate) fn postgres_vault<T>(vault_config: Option<Config>) -> Result<PostgresDataVault<AesGcmSivEncryption, T>, Box<dyn error::Error>>
+    where
+        T: Tokenizer + std::marker::Sync + std::marker::Send,
+{
+    match vault_config.and_then(|vault_config| vault_config.postgres) {
+        Some(postgres) => PostgresDataVault::from_config(postgres),
+        None => PostgresDataVault::new(),
+    }
+}
+
+#[cfg(feature = "cli")]
+impl Config {
+    /// The `backend` section read from the environment like
+    /// `RedisDataVault::new` and `PostgresDataVault::new` do
+    pub(crate) fn from_env(backend: Backend, tokenizer: TokenizerKind) -> Result<Self, Box<dyn error::Error>> {
+        let (redis, postgres) = match backend {
+            Backend::Redis => (Some(RedisVaultConfig::from_env()?), None),
+            Backend::Postgres => (None, Some(PostgresVaultConfig::from_env()?)),
+        };
+        let encryption = match (&redis, &postgres) {
+            (Some(redis), _) => redis.encryption.clone(),
+            (_, Some(postgres)) => postgres.encryption.clone(),
+            _ => unreachable!(),
+        };
+        Ok(Config { backend, tokenizer, encryption, redis, postgres })
+    }
+
+    /// This configuration sealing records with `encryption`
+    pub(crate) fn with_encryption(mut self, encryption: EncryptionSettings) -> Self {
+        if let Some(redis) = &mut self.redis {
+            redis.encryption = encryption.clone();
+        }
+        if let Some(postgres) = &mut self.postgres {
+            postgres.encryption = encryption.clone();
+        }
+        self.encryption = encryption;
+        self
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use crate::config_file::{Backend, Config, TokenizerKind};
+    use std::io::Write;
+
+    #[test]
+    fn test_config_from_file() {
+        let dir = std::env::temp_dir().join(format!("data_vault_config_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("key");
+        std::fs::write(&key_file, "000102030405060708090a0b0c0d0e0f\n").unwrap();
+        std::env::set_var("DATA_VAULT_CONFIG_TEST_URL", "redis://127.0.0.1/");
+
+        let path = dir.join("data_vault.toml");
+        let mut file = std::fs::File::create(&path).unwrap();
+        write!(file, r#"
+            backend = "redis"
+            tokenizer = "deterministic"
+
+            [encryption]
+            key = {{ file = "{}" }}
+            iv = "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"
+
+            [redis]
+            url = {{ env = "DATA_VAULT_CONFIG_TEST_URL" }}
+            pool_max_size = 4
+            pool_recycle = "fast"
+
+            [vault]
+            cvv_policy = "reject"
+            validate_cards = false
+
+            [retry]
+            max_attempts = 3
+        "#, key_file.display()).unwrap();
+
+        let cfg = Config::from_file(&path).unwrap();
+        assert_eq!(cfg.backend, Backend::Redis);
+        assert_eq!(cfg.tokenizer, TokenizerKind::Deterministic);
+        assert_eq!(cfg.encryption.key, "000102030405060708090a0b0c0d0e0f");
+        #[cfg(feature = "redis")]
+        {
+            use crate::cvv::CvvPolicy;
+
+            let redis = cfg.redis.unwrap();
+            assert_eq!(redis.redis.url.as_deref(), Some("redis://127.0.0.1/"));
+            assert_eq!(redis.redis.pool.unwrap().max_size, 4);
+            assert_eq!(redis.cvv_policy, CvvPolicy::Reject);
+            assert!(!redis.validate_cards);
+            assert_eq!(redis.retry.max_attempts, 3);
+            assert_eq!(redis.recycle, crate::RecycleMethod::Fast);
+        }
+
+        std::fs::write(&path, "backend = \"postgres\"\n[encryption]\nkey = \"k\"\n").unwrap();
+        assert!(Config::from_file(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+//! Redis and Postgres in docker containers for integration tests
+//!
+//! Each container is started with `start`, listens on a free port of
+//! the docker host and is removed when it is dropped, so keep it
+//! alive as long as the vaults talking to it.  The vaults encrypt
+//! with a random key per container.  A docker daemon has to be
+//! reachable, see the `testcontainers` crate for `DOCKER_HOST`.
+//!
+//! # Examples
+//! ```rust,ignore
+//! use data_vault::DataVault;
+//! use data_vault::containers::RedisContainer;
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! let redis = RedisContainer::start().await.unwrap();
+//! let vault = redis.vault::<AesGcmSivEncryption, Blake3Tokenizer>().unwrap();
+//! vault.store("abc1", "{number: 123}").await.unwrap();
+//! ```
+
+use testcontainers::{ContainerAsync, GenericImage};
+#[cfg(feature = "postgres")]
+use testcontainers::ImageExt;
+use testcontainers::core::{IntoContainerPort, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use crate::config::EncryptionSettings;
+use crate::encryption::traits::Encryption;
+use crate::tokenizer::Tokenizer;
+#[cfg(feature = "redis")]
+use crate::config::RedisVaultConfig;
+#[cfg(feature = "redis")]
+use crate::redis_data_vault::RedisDataVault;
+#[cfg(feature = "postgres")]
+use crate::config::PostgresVaultConfig;
+#[cfg(feature = "postgres")]
+use crate::postgres_data_vault::PostgresDataVault;
+use std::env;
+use std::error;
+
+#[cfg(feature = "redis")]
+const REDIS_IMAGE: (&str, &str) = ("redis", "7-alpine");
+#[cfg(feature = "postgres")]
+const POSTGRES_IMAGE: (&str, &str) = ("postgres", "16-alpine");
+#[cfg(feature = "postgres")]
+const POSTGRES_USER: &str = "data_vault";
+#[cfg(feature = "postgres")]
+const POSTGRES_PASSWORD: &str = "foobared";
+
+/// a random key and IV for the vaults of one container
+fn random_encryption() -> EncryptionSettings {
+    EncryptionSettings::new(&hex::encode(rand::random::<[u8; 16]>()), &hex::encode(rand::random::<[u8; 16]>()))
+}
+
+/// export the key material of `encryption` for `DataVault::new`
+fn set_encryption_env(encryption: &EncryptionSettings) {
+    env::set_var("ENCRYPTED_DATA_VAULT_KEY", &encryption.key);
+    env::set_var("ENCRYPTED_DATA_VAULT_IV", &encryption.iv);
+}
+
+/// A redis server in a container
+#[cfg(feature = "redis")]
+pub struct RedisContainer {
+    container: ContainerAsync<GenericImage>,
+    url: String,
+    encryption: EncryptionSettings,
+}
+
+#[cfg(feature = "redis")]
+impl RedisContainer {
+    /// Start `redis:7-alpine` and wait until it accepts connections
+    pub async fn start() -> Result<Self, Box<dyn error::Error>> {
+        RedisContainer::start_image(REDIS_IMAGE.0, REDIS_IMAGE.1).await
+    }
+
+    /// `start` with another redis image, e.g. a version you run in
+    /// production
+    pub async fn start_image(name: &str, tag: &str) -> Result<Self, Box<dyn error::Error>> {
+        let container = GenericImage::new(name, tag)
+            .with_exposed_port(6379.tcp())
+            .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+            .start()
+            .await?;
+        let url = format!("redis://{}:{}/", container.get_host().await?, container.get_host_port_ipv4(6379).await?);
+        Ok(RedisContainer {
+            container,
+            url,
+            encryption: random_encryption(),
+        })
+    }
+
+    /// `redis://` url of the server
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The configuration of a vault on this server
+    pub fn config(&self) -> RedisVaultConfig {
+        RedisVaultConfig::new(&self.url, self.encryption.clone())
+    }
+
+    /// A vault on this server
+    pub fn vault<E, T>(&self) -> Result<RedisDataVault<E, T>, Box<dyn error::Error>>
+        where
+            E: Encryption + std::marker::Sync + std::marker::Send,
+            T: Tokenizer + std::marker::Sync + std::marker::Send,
+    {
+        RedisDataVault::from_config(self.config())
+    }
+
+    /// Point `REDIS_URL` and the key material at this container, so
+    /// `RedisDataVault::new` in the code under test uses it
+    ///
+    /// The environment is shared by the whole process, tests running
+    /// in parallel should use `vault` or `config` instead.
+    pub fn set_env(&self) {
+        env::set_var("REDIS_URL", &self.url);
+        set_encryption_env(&self.encryption);
+    }
+
+    /// The running container
+    pub fn container(&self) -> &ContainerAsync<GenericImage> {
+        &self.container
+    }
+}
+
+/// A postgres server in a container, with a `data_vault` user and
+/// database
+#[cfg(feature = "postgres")]
+pub struct PostgresContainer {
+    container: ContainerAsync<GenericImage>,
+    host: String,
+    port: u16,
+    encryption: EncryptionSettings,
+}
+
+#[cfg(feature = "postgres")]
+impl PostgresContainer {
+    /// Start `postgres:16-alpine` and wait until it accepts connections
+    pub async fn start() -> Result<Self, Box<dyn error::Error>> {
+        PostgresContainer::start_image(POSTGRES_IMAGE.0, POSTGRES_IMAGE.1).await
+    }
+
+    /// `start` with another postgres image, e.g. a version you run in
+    /// production
+    pub async fn start_image(name: &str, tag: &str) -> Result<Self, Box<dyn error::Error>> {
+        // the message is logged once by the initdb server and once by the real one
+        let container = GenericImage::new(name, tag)
+            .with_exposed_port(5432.tcp())
+            .with_wait_for(WaitFor::message_on_stderr("database system is ready to accept connections"))
+            .with_wait_for(WaitFor::message_on_stdout("database system is ready to accept connections"))
+            .with_env_var("POSTGRES_USER", POSTGRES_USER)
+            .with_env_var("POSTGRES_PASSWORD", POSTGRES_PASSWORD)
+            .with_env_var("POSTGRES_DB", POSTGRES_USER)
+            .start()
+            .await?;
+        Ok(PostgresContainer {
+            host: container.get_host().await?.to_string(),
+            port: container.get_host_port_ipv4(5432).await?,
+            container,
+            encryption: random_encryption(),
+        })
+    }
+
+    /// Host and port of the server
+    pub fn address(&self) -> (&str, u16) {
+        (&self.host, self.port)
+    }
+
+    /// The configuration of a vault on this server, creating its
+    /// table on first use
+    pub fn config(&self) -> PostgresVaultConfig {
+        let postgres = deadpool_postgres::Config {
+            host: Some(self.host.clone()),
+            port: Some(self.port),
+            user: Some(POSTGRES_USER.to_string()),
+            password: Some(POSTGRES_PASSWORD.to_string()),
+            dbname: Some(POSTGRES_USER.to_string()),
+            ..deadpool_postgres::Config::default()
+        };
+        PostgresVaultConfig::new(postgres, self.encryption.clone())
+            .with_auto_create_schema(true)
+    }
+
+    /// A vault on this server
+    pub fn vault<E, T>(&self) -> Result<PostgresDataVault<E, T>, Box<dyn error::Error>>
+        where
+            E: Encryption + std::marker::Sync + std::marker::Send,
+            T: Tokenizer + std::marker::Sync + std::marker::Send,
+    {
+        PostgresDataVault::from_config(self.config())
+    }
+
+    /// Point the `POSTGRES.*` variables and the key material at this
+    /// container, so `PostgresDataVault::new` in the code under test
+    /// uses it, with `DATA_VAULT_AUTO_CREATE_SCHEMA`
+    ///
+    /// The environment is shared by the whole process, tests running
+    /// in parallel should use `vault` or `config` instead.
+    pub fn set_env(&self) {
+        env::set_var("POSTGRES.HOST", &self.host);
+        env::set_var("POSTGRES.PORT", self.port.to_string());
+        env::set_var("POSTGRES.USER", POSTGRES_USER);
+        env::set_var("POSTGRES.PASSWORD", POSTGRES_PASSWORD);
+        env::set_var("POSTGRES.DBNAME", POSTGRES_USER);
+        env::set_var("DATA_VAULT_AUTO_CREATE_SCHEMA", "true");
+        set_encryption_env(&self.encryption);
+    }
+
+    /// The running container
+    pub fn container(&self) -> &ContainerAsync<GenericImage> {
+        &self.container
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::tokenizer::Blake3Tokenizer;
+
+    #[cfg(feature = "redis")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn redis_container() {
+        let redis = crate::containers::RedisContainer::start().await.unwrap();
+        let vault = redis.vault::<AesGcmSivEncryption, Blake3Tokenizer>().unwrap();
+        vault.store("abc1", "{number: 123}").await.unwrap();
+        assert_eq!(vault.retrieve("abc1").await.unwrap(), "{number: 123}");
+        assert!(vault.health_check().await.is_healthy())
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn postgres_container() {
+        let postgres = crate::containers::PostgresContainer::start().await.unwrap();
+        let vault = postgres.vault::<AesGcmSivEncryption, Blake3Tokenizer>().unwrap();
+        vault.store("abc1", "{number: 123}").await.unwrap();
+        assert_eq!(vault.retrieve("abc1").await.unwrap(), "{number: 123}");
+        assert!(vault.health_check().await.is_healthy())
+    }
+}
@@ -0,0 +1,101 @@
+use credit_card::CreditCard;
+use serde::Deserialize;
+use crate::error::DataVaultError;
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// What `store_credit_card`, `tokenize` and
+/// `update_credit_card_if_version` do with a card's `security_code`
+///
+/// PCI DSS forbids keeping the security code after authorization,
+/// so by default it is removed before the card is stored.
+/// Configured with `DATA_VAULT_CVV_POLICY` (`strip`, `reject` or
+/// `expire`) and `DATA_VAULT_CVV_TTL_SECS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CvvPolicy {
+    /// store the card without its security code
+    #[default]
+    Strip,
+    /// fail with `DataVaultError::SecurityCodeNotAllowed`
+    Reject,
+    /// store the card with its security code, the whole record
+    /// expires after the given time, e.g. once authorization is done
+    Expire(Duration),
+}
+
+/// `DATA_VAULT_CVV_POLICY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CvvPolicyKind {
+    #[default]
+    Strip,
+    Reject,
+    Expire,
+}
+
+impl CvvPolicy {
+    pub(crate) fn from_config(kind: CvvPolicyKind, ttl_secs: u64) -> Self {
+        match kind {
+            CvvPolicyKind::Strip => CvvPolicy::Strip,
+            CvvPolicyKind::Reject => CvvPolicy::Reject,
+            CvvPolicyKind::Expire => CvvPolicy::Expire(Duration::from_secs(ttl_secs)),
+        }
+    }
+
+    /// The card as it may be stored and the time to live the record
+    /// must be stored with, if any
+    pub(crate) fn apply<'a>(&self, credit_card: &'a CreditCard) -> Result<(Cow<'a, CreditCard>, Option<Duration>), DataVaultError> {
+        let has_security_code = credit_card.security_code.as_deref()
+            .map(|security_code| !security_code.is_empty())
+            .unwrap_or(false);
+
+        if !has_security_code {
+            return Ok((Cow::Borrowed(credit_card), None))
+        }
+
+        match self {
+            CvvPolicy::Strip => {
+                let mut credit_card = credit_card.clone();
+                credit_card.security_code = None;
+                Ok((Cow::Owned(credit_card), None))
+            },
+            CvvPolicy::Reject => Err(DataVaultError::SecurityCodeNotAllowed),
+            CvvPolicy::Expire(ttl) => Ok((Cow::Borrowed(credit_card), Some(*ttl))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::cvv::CvvPolicy;
+    use crate::error::DataVaultError;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cvv_policy() {
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let (stored, ttl) = CvvPolicy::Reject.apply(&cc).unwrap();
+        assert_eq!(stored.number, cc.number);
+        assert_eq!(ttl, None);
+
+        cc.security_code = Some("123".to_string());
+        let (stored, ttl) = CvvPolicy::Strip.apply(&cc).unwrap();
+        assert_eq!(stored.security_code, None);
+        assert_eq!(ttl, None);
+
+        assert!(matches!(CvvPolicy::Reject.apply(&cc), Err(DataVaultError::SecurityCodeNotAllowed)));
+
+        let (stored, ttl) = CvvPolicy::Expire(Duration::from_secs(60)).apply(&cc).unwrap();
+        assert_eq!(stored.security_code.as_deref(), Some("123"));
+        assert_eq!(ttl, Some(Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,544 @@
+//! The spool layout
+//!
+//! ```text
+//! <directory>/<id>.dvletter   one letter in the export format, see `export`
+//! <directory>/<id>.tmp        a letter being written, renamed when complete
+//! ```
+//!
+//! Ids start with the time of the failure, so the file names sort
+//! from the oldest letter to the newest.
+
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::export::{ExportReader, ExportWriter};
+use crate::redact::redact_token;
+use futures::TryStreamExt;
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const LETTER_EXTENSION: &str = "dvletter";
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// What a failed write was to store
+#[derive(Clone, Serialize, Deserialize)]
+pub enum DeadLetterRecord {
+    /// the string of `store`
+    String(String),
+    /// the card of `store_credit_card_with_token`, without its
+    /// security code, which must not be kept after authorization
+    CreditCard(CreditCard),
+}
+
+/// A write that failed with a transient error, kept by a
+/// `DeadLetterSink` until `DeadLetterVault::replay` stores it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// unique and sorting from the oldest letter to the newest, a
+    /// valid token of every back end
+    pub id: String,
+    /// `DataVault::namespace` of the vault the write failed in
+    pub namespace: String,
+    pub token: String,
+    pub record: DeadLetterRecord,
+    /// seconds since the unix epoch the write failed at
+    pub failed_at: u64,
+}
+
+impl DeadLetter {
+    fn new(namespace: &str, token: &str, record: DeadLetterRecord) -> Self {
+        let since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        DeadLetter {
+            id: format!("{:020}-{}", since_epoch.as_millis(), hex::encode(rand::random::<[u8; 8]>())),
+            namespace: namespace.to_string(),
+            token: token.to_string(),
+            record,
+            failed_at: since_epoch.as_secs(),
+        }
+    }
+}
+
+impl fmt::Debug for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let record = match self.record {
+            DeadLetterRecord::String(_) => "String",
+            DeadLetterRecord::CreditCard(_) => "CreditCard",
+        };
+        f.debug_struct("DeadLetter")
+            .field("id", &self.id)
+            .field("namespace", &self.namespace)
+            .field("token", &redact_token(&self.token))
+            .field("record", &record)
+            .field("failed_at", &self.failed_at)
+            .finish()
+    }
+}
+
+/// Where a `DeadLetterVault` keeps the writes its vault failed,
+/// `SpoolSink` and `VaultSink` or a sink of your own
+///
+/// Letters are never kept in plaintext, a sink encrypts them or
+/// hands them to something that does.
+#[async_trait]
+pub trait DeadLetterSink: Send + Sync {
+    /// Keep `letter` until it is removed
+    async fn capture(&self, letter: &DeadLetter) -> Result<(), DataVaultError>;
+    /// Every letter kept, from the oldest to the newest
+    async fn pending(&self) -> Result<Vec<DeadLetter>, DataVaultError>;
+    /// Forget the letter with `id`, a letter that is gone already
+    /// is not an error
+    async fn remove(&self, id: &str) -> Result<(), DataVaultError>;
+}
+
+/// Letters in a local directory, one file each, encrypted in the
+/// export format with the spool key
+///
+/// A letter is written to a temporary file that is synced and
+/// renamed, so a crash never leaves half a letter behind.  Several
+/// processes may share the directory, a letter replayed by two of
+/// them is stored twice.
+/// # example
+/// ```rust
+/// use data_vault::SpoolSink;
+///
+/// let directory = std::env::temp_dir().join("data_vault_spool_example");
+/// let sink = SpoolSink::new(&directory, b"a high entropy spool key").unwrap();
+/// ```
+pub struct SpoolSink {
+    directory: PathBuf,
+    spool_key: Vec<u8>,
+}
+
+impl SpoolSink {
+    /// Letters in `directory`, created if need be
+    ///
+    /// `spool_key` is a high entropy secret, not a password, the
+    /// same key is needed to read the letters again.
+    pub fn new<P: AsRef<Path>>(directory: P, spool_key: &[u8]) -> Result<Self, DataVaultError> {
+        fs::create_dir_all(directory.as_ref())?;
+        Ok(SpoolSink {
+            directory: directory.as_ref().to_path_buf(),
+            spool_key: spool_key.to_vec(),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// the file of the letter with `id`
+    fn path(&self, id: &str, extension: &str) -> Result<PathBuf, DataVaultError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(DataVaultError::InvalidToken)
+        }
+        Ok(self.directory.join(id).with_extension(extension))
+    }
+
+    fn read(&self, path: &Path) -> Result<DeadLetter, DataVaultError> {
+        let mut export = ExportReader::new(BufReader::new(File::open(path)?), &self.spool_key)?;
+        let (id, data) = export.next_record()?
+            .ok_or(DataVaultError::InvalidExport("dead letter without a record"))?;
+        let letter: DeadLetter = serde_json::from_str(&data)?;
+        match letter.id == id {
+            true => Ok(letter),
+            false => Err(DataVaultError::InvalidExport("dead letter of another file")),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterSink for SpoolSink {
+    async fn capture(&self, letter: &DeadLetter) -> Result<(), DataVaultError> {
+        let temporary = self.path(&letter.id, TEMPORARY_EXTENSION)?;
+        let file = File::create(&temporary)?;
+        let mut export = ExportWriter::new(BufWriter::new(&file), &self.spool_key)?;
+        export.write_record(letter.id.clone(), serde_json::to_string(letter)?)?;
+        export.finish()?;
+        file.sync_all()?;
+        fs::rename(&temporary, self.path(&letter.id, LETTER_EXTENSION)?)?;
+        Ok(())
+    }
+
+    async fn pending(&self) -> Result<Vec<DeadLetter>, DataVaultError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == LETTER_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut letters = Vec::with_capacity(paths.len());
+        for path in paths {
+            match self.read(&path) {
+                Ok(letter) => letters.push(letter),
+                // removed by another process since the directory was read
+                Err(DataVaultError::Io(e)) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(letters)
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), DataVaultError> {
+        match fs::remove_file(self.path(id, LETTER_EXTENSION)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Letters in a secondary vault, e.g. a postgres vault for a redis
+/// one, each stored under its id and encrypted by that vault
+///
+/// Every record of the vault's namespace is taken for a letter, give
+/// it a namespace of its own.
+/// # example
+/// ```rust
+/// use data_vault::{DataVault, PostgresDataVault, VaultSink};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let secondary = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let sink = VaultSink::new(secondary.with_namespace("dead-letters").unwrap());
+/// ```
+pub struct VaultSink<V> {
+    vault: V,
+}
+
+impl<V> VaultSink<V>
+    where
+        V: DataVault,
+{
+    pub fn new(vault: V) -> Self {
+        VaultSink { vault }
+    }
+
+    pub fn vault(&self) -> &V {
+        &self.vault
+    }
+}
+
+#[async_trait]
+impl<V> DeadLetterSink for VaultSink<V>
+    where
+        V: DataVault,
+{
+    async fn capture(&self, letter: &DeadLetter) -> Result<(), DataVaultError> {
+        self.vault.store(&letter.id, &serde_json::to_string(letter)?).await
+    }
+
+    async fn pending(&self) -> Result<Vec<DeadLetter>, DataVaultError> {
+        let mut letters = Vec::new();
+        let mut records = self.vault.iter_decrypted_records();
+        while let Some((_, data)) = records.try_next().await? {
+            letters.push(serde_json::from_str::<DeadLetter>(&data)?);
+        }
+        letters.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(letters)
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), DataVaultError> {
+        self.vault.delete_many(&[id.to_string()]).await.map(|_| ())
+    }
+}
+
+/// The outcome of `DeadLetterVault::replay`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    /// letters stored and removed from the sink
+    pub replayed: u64,
+    /// letters left for a later replay after the vault failed
+    /// with a transient error again
+    pub remaining: u64,
+    /// ids of the letters the vault rejected, e.g. with
+    /// `DataVaultError::InvalidToken`, they stay in the sink
+    pub rejected: Vec<String>,
+}
+
+/// A vault keeping the writes of another that fail with a transient
+/// error in a `DeadLetterSink`, to `replay` them once the back end
+/// is back, so an outage does not lose tokenization requests
+///
+/// Wrap a `RetryingVault` to capture writes only after their
+/// retries.  Writes with a token known up front are captured,
+/// `store` and `store_credit_card_with_token` with `overwrite`, and
+/// succeed once the sink kept them.  Their records can not be
+/// retrieved until they are replayed.  Other operations, and writes
+/// whose sink fails as well, return the vault's error.  Expiring
+/// writes, `store_expiring` and so `store_record` of records with a
+/// `VaultRecord::ttl`, are never captured, a replay would keep
+/// wallet cryptograms or track data past their expiry.
+/// # example
+/// ```rust
+/// use data_vault::{DataVault, DeadLetterVault, RedisDataVault, RetryPolicy, SpoolSink};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::sync::Arc;
+///
+/// let directory = std::env::temp_dir().join("data_vault_dead_letter_example");
+/// let sink = Arc::new(SpoolSink::new(&directory, b"a high entropy spool key").unwrap());
+/// let vault = RetryPolicy::new(4)
+///     .retrying(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+/// let vault = DeadLetterVault::with_sink(vault, sink);
+/// ```
+pub struct DeadLetterVault<V> {
+    inner: V,
+    sink: Arc<dyn DeadLetterSink>,
+    // a side vault, see `with_side_namespace`
+    side: bool,
+}
+
+impl<V> DeadLetterVault<V>
+    where
+        V: DataVault,
+{
+    /// `vault` keeping its failed writes in `sink`
+    pub fn with_sink(vault: V, sink: Arc<dyn DeadLetterSink>) -> Self {
+        DeadLetterVault {
+            inner: vault,
+            sink,
+            side: false,
+        }
+    }
+
+    /// The wrapped vault, its failed writes are not captured
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    pub fn sink(&self) -> &Arc<dyn DeadLetterSink> {
+        &self.sink
+    }
+
+    /// Store the letters of this namespace in the wrapped vault, from
+    /// the oldest to the newest, removing each once it is stored
+    ///
+    /// A replayed letter overwrites its token, writes made to it
+    /// since the failure are lost, replay before taking new writes.
+    /// The first transient error stops the replay, so later letters
+    /// of a token never overtake earlier ones.
+    pub async fn replay(&self) -> Result<ReplayReport, DataVaultError> {
+        let letters: Vec<DeadLetter> = self.sink.pending().await?.into_iter()
+            .filter(|letter| letter.namespace == self.inner.namespace())
+            .collect();
+
+        let mut report = ReplayReport::default();
+        for (replayed, letter) in letters.iter().enumerate() {
+            let stored = match &letter.record {
+                DeadLetterRecord::String(string) => self.inner.store(&letter.token, string).await,
+                DeadLetterRecord::CreditCard(credit_card) => self.inner.store_credit_card_with_token(&letter.token, credit_card, true).await,
+            };
+            match stored {
+                Ok(()) => {
+                    self.sink.remove(&letter.id).await?;
+                    report.replayed += 1;
+                },
+                Err(e) if e.is_transient() => {
+                    report.remaining = (letters.len() - replayed) as u64;
+                    break
+                },
+                Err(_) => report.rejected.push(letter.id.clone()),
+            }
+        }
+        Ok(report)
+    }
+
+    /// `result` of writing `record` under `token`, or `Ok` once a
+    /// transient error is captured
+    async fn capture(&self, token: &str, record: DeadLetterRecord, result: Result<(), DataVaultError>) -> Result<(), DataVaultError> {
+        match result {
+            Err(e) if e.is_transient() && !self.side => {
+                let letter = DeadLetter::new(self.inner.namespace(), token, record);
+                self.sink.capture(&letter).await.map_err(|_| e)
+            },
+            result => result,
+        }
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for DeadLetterVault<V>
+    where
+        V: DataVault,
+{
+    /// Always fails, a sink can not come from the environment, use
+    /// `DeadLetterVault::with_sink`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Err("DeadLetterVault needs a sink, use DeadLetterVault::with_sink".into())
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        let result = self.inner.store(token, string).await;
+        self.capture(token, DeadLetterRecord::String(string.to_string()), result).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.inner.store_if_absent(token, string).await
+    }
+
+    /// Not captured, a replayed record would outlive its expiry
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.inner.store_expiring(token, string, ttl).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.inner.store_credit_card(credit_card).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.inner.tokenize(credit_card).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        let result = self.inner.store_credit_card_with_token(token, credit_card, overwrite).await;
+        match overwrite {
+            true => {
+                let credit_card = CreditCard { security_code: None, ..credit_card.clone() };
+                self.capture(token, DeadLetterRecord::CreditCard(credit_card), result).await
+            },
+            false => result,
+        }
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inner.retrieve(token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.inner.retrieve_credit_card(token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.inner.exists(token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.inner.retrieve_credit_card_with_version(token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.inner.retrieve_with_metadata(token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.inner.update_credit_card_if_version(token, credit_card, expected_version).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inner.rotate_token(token).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.inner.soft_delete(token).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.inner.touch(token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.inner.delete_many(tokens).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.inner.purge_expired().await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.inner.decrypted_records_page(cursor, limit).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.inner.count().await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.inner.stats().await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(DeadLetterVault {
+            inner: self.inner.with_namespace(namespace)?,
+            sink: self.sink.clone(),
+            side: false,
+        })
+    }
+
+    /// Writes of side vaults are never captured, `replay` only
+    /// replays the namespaces of callers and security codes must not
+    /// be kept after authorization
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(DeadLetterVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            sink: self.sink.clone(),
+            side: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_spool_sink() {
+        use crate::dead_letter::{DeadLetter, DeadLetterRecord, DeadLetterSink, SpoolSink};
+
+        let directory = std::env::temp_dir().join(format!("data_vault_spool_test_{}", std::process::id()));
+        let sink = SpoolSink::new(&directory, b"spool key").unwrap();
+        let first = DeadLetter::new("data_vault", "abc1", DeadLetterRecord::String("{number: 123}".to_string()));
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = DeadLetter::new("tenant-a", "abc2", DeadLetterRecord::String("{number: 456}".to_string()));
+        sink.capture(&second).await.unwrap();
+        sink.capture(&first).await.unwrap();
+
+        let pending = sink.pending().await.unwrap();
+        assert_eq!(pending.iter().map(|letter| letter.token.as_str()).collect::<Vec<&str>>(), vec!["abc1", "abc2"]);
+        assert_eq!(pending[1].namespace, "tenant-a");
+        assert!(matches!(&pending[0].record, DeadLetterRecord::String(string) if string == "{number: 123}"));
+
+        // letters are encrypted with the spool key
+        let file = std::fs::read(directory.join(&first.id).with_extension("dvletter")).unwrap();
+        assert!(!String::from_utf8_lossy(&file).contains("number"));
+        assert!(SpoolSink::new(&directory, b"other key").unwrap().pending().await.is_err());
+        assert!(sink.remove("../abc1").await.is_err());
+
+        sink.remove(&first.id).await.unwrap();
+        sink.remove(&first.id).await.unwrap();
+        assert_eq!(sink.pending().await.unwrap().len(), 1);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
\ No newline at end of file
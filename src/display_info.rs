@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use crate::validation::ValidationError;
+use std::fmt;
+
+/// Longest nickname or card art id of a card, in characters
+pub const MAX_DISPLAY_INFO_LENGTH: usize = 64;
+
+/// How a wallet shows a stored card without detokenizing it, see
+/// `DataVault::get_display_info`
+///
+/// `Display` is the label of the card, e.g. `Work Visa •••• 1111`,
+/// or the brand instead of the nickname for cards without one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayInfo {
+    pub nickname: Option<String>,
+    /// the card art the wallet shows, an id of the integrator's
+    pub art_id: Option<String>,
+    pub brand: Option<String>,
+    pub last_four: String,
+}
+
+impl fmt::Display for DisplayInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = self.nickname.as_ref().or(self.brand.as_ref()) {
+            write!(f, "{} ", name)?;
+        }
+        write!(f, "\u{2022}\u{2022}\u{2022}\u{2022} {}", self.last_four)
+    }
+}
+
+/// The nickname and card art id of a card, as kept in the side
+/// namespace `<namespace>~display`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct StoredDisplayInfo {
+    pub nickname: Option<String>,
+    pub art_id: Option<String>,
+}
+
+impl StoredDisplayInfo {
+    pub(crate) fn new(nickname: Option<&str>, art_id: Option<&str>) -> Result<Self, ValidationError> {
+        let valid = |value: Option<&str>| value.map(|value| value.chars().count() <= MAX_DISPLAY_INFO_LENGTH).unwrap_or(true);
+        if !valid(nickname) || !valid(art_id) {
+            return Err(ValidationError::InvalidDisplayInfo)
+        }
+        Ok(StoredDisplayInfo {
+            nickname: nickname.map(str::to_string),
+            art_id: art_id.map(str::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::display_info::{DisplayInfo, StoredDisplayInfo};
+    use crate::validation::ValidationError;
+
+    #[test]
+    fn test_display_info() {
+        let mut info = DisplayInfo {
+            nickname: Some("Work Visa".to_string()),
+            art_id: Some("art-42".to_string()),
+            brand: Some("visa".to_string()),
+            last_four: "1111".to_string(),
+        };
+        assert_eq!(info.to_string(), "Work Visa \u{2022}\u{2022}\u{2022}\u{2022} 1111");
+        info.nickname = None;
+        assert_eq!(info.to_string(), "visa \u{2022}\u{2022}\u{2022}\u{2022} 1111");
+        info.brand = None;
+        assert_eq!(info.to_string(), "\u{2022}\u{2022}\u{2022}\u{2022} 1111");
+
+        assert_eq!(StoredDisplayInfo::new(Some("Work Visa"), None).unwrap().nickname.as_deref(), Some("Work Visa"));
+        assert_eq!(StoredDisplayInfo::new(None, Some(&"a".repeat(65))), Err(ValidationError::InvalidDisplayInfo));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_display_info_postgres() {
+        use credit_card::CreditCard;
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::error::DataVaultError;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use crate::traits::DataVault;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("display-info-test").unwrap();
+        let token = vault.store_credit_card(&CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        }).await.unwrap();
+        assert_eq!(vault.get_display_info(&token).await.unwrap().to_string(), "visa \u{2022}\u{2022}\u{2022}\u{2022} 1111");
+
+        vault.set_display_info(&token, Some("Work Visa"), Some("art-42")).await.unwrap();
+        let token = vault.rotate_token(&token).await.unwrap();
+        let info = vault.get_display_info(&token).await.unwrap();
+        assert_eq!(info.to_string(), "Work Visa \u{2022}\u{2022}\u{2022}\u{2022} 1111");
+        assert_eq!(info.art_id.as_deref(), Some("art-42"));
+
+        vault.set_display_info(&token, None, None).await.unwrap();
+        assert_eq!(vault.get_display_info(&token).await.unwrap().nickname, None);
+        assert!(matches!(vault.set_display_info("unknown", Some("Work Visa"), None).await, Err(DataVaultError::NotFound)));
+
+        vault.delete_many(&[token]).await.unwrap();
+    }
+}
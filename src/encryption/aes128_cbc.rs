@@ -1,4 +1,4 @@
-use crate::config::EncryptionConfig;
+use crate::config::EncryptionSettings;
 use aes::Aes128;
 use block_modes::{BlockMode, Cbc};
 use block_modes::block_padding::Pkcs7;
@@ -26,10 +26,13 @@ impl Encryption for Aes128CbcEncryption {
     /// let enc = Aes128CbcEncryption::new();
     /// ```
     fn new() -> Self {
-        let cfg = EncryptionConfig::from_env().unwrap();
+        Self::from_settings(&EncryptionSettings::from_env().unwrap())
+    }
 
-        let key = hex::decode(cfg.key).unwrap();
-        let iv = hex::decode(cfg.iv).unwrap();
+    /// uses `settings.key` and `settings.iv`, 16 hex encoded bytes each
+    fn from_settings(settings: &EncryptionSettings) -> Self {
+        let key = hex::decode(&settings.key).unwrap();
+        let iv = hex::decode(&settings.iv).unwrap();
         // let mut cipher = Aes128Cbc::new_var(
         //     key.clone().as_slice(),
         //     iv.clone().as_slice()
@@ -70,7 +73,7 @@ impl Encryption for Aes128CbcEncryption {
     /// let encrypted_data = enc.encrypt_string(&test_data);
     /// ```
     #[allow(dead_code)]
-    fn encrypt_string(&self, text: &String) -> Vec<u8> {
+    fn encrypt_string(&self, text: &str) -> Vec<u8> {
         self.encrypt(text.as_bytes())
     }
 
@@ -86,8 +89,22 @@ impl Encryption for Aes128CbcEncryption {
     /// let encrypted_data = enc.decrypt(test_data.as_slice());
     /// ```
     fn decrypt(&self, cipher_bytes: &[u8]) -> String {
-        let decrypt_vec = self.new_cipher().decrypt_vec(cipher_bytes).unwrap();
-        String::from_utf8(decrypt_vec).unwrap_or_default()
+        String::from_utf8(self.decrypt_bytes(cipher_bytes)).unwrap_or_default()
+    }
+
+    /// `decrypt` without the conversion to `String`
+    fn decrypt_bytes(&self, cipher_bytes: &[u8]) -> Vec<u8> {
+        self.new_cipher().decrypt_vec(cipher_bytes).unwrap()
+    }
+
+    /// `decrypt_bytes` failing on ciphertexts with invalid padding,
+    /// CBC is not authenticated, other damage goes unnoticed
+    fn try_decrypt_bytes(&self, cipher_bytes: &[u8]) -> Option<Vec<u8>> {
+        self.new_cipher().decrypt_vec(cipher_bytes).ok()
+    }
+
+    fn algorithm(&self) -> &'static str {
+        "AES-128-CBC"
     }
 
     /// decrypts a `Vec<u8>`
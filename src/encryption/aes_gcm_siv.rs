@@ -1,4 +1,8 @@
-use crate::config::EncryptionConfig;
+// generic-array 0.14.9 deprecates itself in favour of 1.x, which the
+// aes-gcm-siv 0.10 API still hands out
+#![allow(deprecated)]
+
+use crate::config::EncryptionSettings;
 use crate::encryption::traits::{Encryption};
 use aes_gcm_siv::Aes256GcmSiv;
 use aes_gcm_siv::aead::{Aead, NewAead, generic_array::GenericArray};
@@ -21,9 +25,13 @@ impl Encryption for AesGcmSivEncryption {
     /// let enc = AesGcmSivEncryption::new();
     /// ```
     fn new() -> Self {
-        let cfg = EncryptionConfig::from_env().unwrap();
+        Self::from_settings(&EncryptionSettings::from_env().unwrap())
+    }
+
+    /// uses `settings.key`, 32 bytes
+    fn from_settings(settings: &EncryptionSettings) -> Self {
         let key = GenericArray::from_slice(
-            cfg.key.as_bytes()
+            settings.key.as_bytes()
         );
 
         let cipher = Aes256GcmSiv::new(key);
@@ -73,7 +81,7 @@ impl Encryption for AesGcmSivEncryption {
     /// let encrypted_data = enc.encrypt_string(&test_data);
     /// ```
     #[allow(dead_code)]
-    fn encrypt_string(&self, text: &String) -> Vec<u8> {
+    fn encrypt_string(&self, text: &str) -> Vec<u8> {
         self.encrypt(text.as_bytes())
     }
 
@@ -94,14 +102,31 @@ impl Encryption for AesGcmSivEncryption {
     /// let encrypted_data = enc.decrypt_vec(test_data);
     /// ```
     fn decrypt(&self, bytes: &[u8]) -> String {
+        String::from_utf8(self.decrypt_bytes(bytes)).unwrap_or_default()
+    }
+
+    /// `decrypt` without the conversion to `String`
+    fn decrypt_bytes(&self, bytes: &[u8]) -> Vec<u8> {
         if bytes.get(12).is_none() {
-            return String::from("")
+            return Vec::new()
         }
 
         let (nonce_bytes, cipher_bytes) = bytes.split_at(12);
         let nonce = GenericArray::from_slice(nonce_bytes);
-        let decrypt_vec = self.cipher.decrypt(nonce, cipher_bytes).unwrap();
-        String::from_utf8(decrypt_vec).unwrap_or_default()
+        self.cipher.decrypt(nonce, cipher_bytes).unwrap()
+    }
+
+    /// `decrypt_bytes` failing on damaged or foreign ciphertexts,
+    /// which do not authenticate
+    fn try_decrypt_bytes(&self, bytes: &[u8]) -> Option<Vec<u8>> {
+        // the nonce and at least one byte
+        bytes.get(12)?;
+        let (nonce_bytes, cipher_bytes) = bytes.split_at(12);
+        self.cipher.decrypt(GenericArray::from_slice(nonce_bytes), cipher_bytes).ok()
+    }
+
+    fn algorithm(&self) -> &'static str {
+        "AES-256-GCM-SIV"
     }
 
     /// decrypts a `Vec<u8>`
@@ -139,4 +164,16 @@ mod test {
         let decrypted_data = enc.decrypt_vec(encrypted_data);
         assert_eq!(test_data, decrypted_data)
     }
+
+    #[test]
+    fn test_aes_gcm_siv_try_decrypt() {
+        let enc = AesGcmSivEncryption::new();
+        let mut encrypted_data = enc.encrypt_string("Hello world!");
+        assert_eq!(enc.try_decrypt_bytes(&encrypted_data).as_deref(), Some(&b"Hello world!"[..]));
+
+        let last = encrypted_data.len() - 1;
+        encrypted_data[last] ^= 1;
+        assert_eq!(enc.try_decrypt_bytes(&encrypted_data), None);
+        assert_eq!(enc.try_decrypt_bytes(&encrypted_data[..12]), None);
+    }
 }
\ No newline at end of file
@@ -1,13 +1,31 @@
 use block_modes::Cbc;
 use aes::Aes128;
 use block_modes::block_padding::Pkcs7;
+use crate::config::EncryptionSettings;
 
 pub trait Encryption {
+    /// reads the key from the environment, see `EncryptionSettings::from_env`
     fn new() -> Self;
+    fn from_settings(settings: &EncryptionSettings) -> Self;
     fn encrypt(&self, bytes: &[u8]) -> Vec<u8>;
-    fn encrypt_string(&self, text: &String) -> Vec<u8>;
+    fn encrypt_string(&self, text: &str) -> Vec<u8>;
     fn decrypt(&self, cipher_bytes: &[u8]) -> String;
+    /// decrypts data that need not be text, e.g. a record written
+    /// by a binary `Serializer`
+    fn decrypt_bytes(&self, cipher_bytes: &[u8]) -> Vec<u8> {
+        self.decrypt(cipher_bytes).into_bytes()
+    }
     fn decrypt_vec(&self, cipher_vector: Vec<u8>) -> String;
+    /// `decrypt_bytes`, `None` instead of a panic when `cipher_bytes`
+    /// are damaged or were encrypted with another key
+    fn try_decrypt_bytes(&self, cipher_bytes: &[u8]) -> Option<Vec<u8>> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.decrypt_bytes(cipher_bytes))).ok()
+    }
+    /// the cipher and mode, e.g. `AES-256-GCM-SIV`, as reported by
+    /// `key_info`
+    fn algorithm(&self) -> &'static str {
+        "unknown"
+    }
 }
 
 pub trait Aes128CbcCipher {
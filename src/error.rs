@@ -0,0 +1,259 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use crate::validation::ValidationError;
+#[cfg(feature = "redis")]
+use deadpool_redis::PoolError as RedisPoolError;
+#[cfg(feature = "redis")]
+use deadpool_redis::redis::{ErrorKind as RedisErrorKind, RedisError};
+#[cfg(feature = "postgres")]
+use deadpool_postgres::PoolError as PostgresPoolError;
+#[cfg(feature = "postgres")]
+use deadpool_postgres::tokio_postgres::Error as PostgresError;
+#[cfg(feature = "remote")]
+use reqwest::Error as RemoteError;
+
+/// Everything that can go wrong in a `DataVault` operation
+#[derive(Debug)]
+pub enum DataVaultError {
+    /// nothing is stored under the requested token
+    NotFound,
+    /// the record was changed by another operation in the meantime
+    Conflict,
+    /// the namespace contains characters other than `[A-Za-z0-9_.-]`,
+    /// is longer than 64 characters or is reserved
+    InvalidNamespace(String),
+    /// the token can not be used as a key by this backend
+    InvalidToken,
+    /// a record is already stored under the token
+    AlreadyExists,
+    /// a stored record could not be serialized or deserialized
+    Serialization(serde_json::Error),
+    /// a record could not be written or read by a `Serializer` other
+    /// than `JsonSerializer`
+    Encoding(Box<dyn error::Error + Send + Sync>),
+    /// reading or writing an export failed
+    Io(io::Error),
+    /// an export is damaged, truncated, of an unknown format version
+    /// or was made with another export key
+    InvalidExport(&'static str),
+    /// the cursor was not returned by this kind of vault
+    InvalidCursor,
+    /// the card has a security code and the vault's `CvvPolicy`
+    /// rejects those
+    SecurityCodeNotAllowed,
+    /// the card number is not one, see `validate_number`
+    Validation(ValidationError),
+    /// the caller is not allowed this operation by the vault's
+    /// `AccessPolicy`
+    AccessDenied,
+    /// the caller detokenized too many records, retry after the
+    /// given time
+    RateLimited(Duration),
+    /// the audit log entry with this sequence number was changed,
+    /// removed or reordered
+    TamperedAuditLog(u64),
+    /// a card field layout stores the card number in plaintext, names
+    /// an unknown field or a lookup uses a field that is only encrypted
+    InvalidFieldLayout(&'static str),
+    /// the vault can not do this operation, e.g. a `RemoteDataVault`
+    /// asked for one the vault service does not offer
+    Unsupported(&'static str),
+    /// no connection could be taken from the redis pool
+    #[cfg(feature = "redis")]
+    RedisPool(RedisPoolError),
+    /// redis returned an error
+    #[cfg(feature = "redis")]
+    Redis(RedisError),
+    /// no connection could be taken from the postgres pool
+    #[cfg(feature = "postgres")]
+    PostgresPool(PostgresPoolError),
+    /// postgres returned an error
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresError),
+    /// the vault service could not be reached or its answer not read
+    #[cfg(feature = "remote")]
+    Remote(RemoteError),
+    /// the vault service answered with this status and message
+    #[cfg(feature = "remote")]
+    RemoteStatus(u16, String),
+}
+
+impl DataVaultError {
+    /// Whether the error may go away by itself, e.g. a dropped
+    /// connection, a pool timeout or a redis failover, and the
+    /// operation is worth retrying, see `RetryPolicy`
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[cfg(feature = "redis")]
+            DataVaultError::RedisPool(e) => match e {
+                RedisPoolError::Timeout(_) => true,
+                RedisPoolError::Backend(e) => is_transient_redis(e),
+                _ => false,
+            },
+            #[cfg(feature = "redis")]
+            DataVaultError::Redis(e) => is_transient_redis(e),
+            #[cfg(feature = "postgres")]
+            DataVaultError::PostgresPool(e) => match e {
+                PostgresPoolError::Timeout(_) => true,
+                PostgresPoolError::Backend(e) => is_transient_postgres(e),
+                _ => false,
+            },
+            #[cfg(feature = "postgres")]
+            DataVaultError::Postgres(e) => is_transient_postgres(e),
+            #[cfg(feature = "remote")]
+            DataVaultError::Remote(e) => e.is_timeout() || e.is_request(),
+            // a gateway or the service itself is unavailable
+            #[cfg(feature = "remote")]
+            DataVaultError::RemoteStatus(status, _) => matches!(status, 502..=504),
+            // e.g. injected by `ChaosDataVault`
+            DataVaultError::Io(e) => matches!(e.kind(), io::ErrorKind::TimedOut
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::Interrupted),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+fn is_transient_redis(e: &RedisError) -> bool {
+    e.is_io_error()
+        || e.is_connection_refusal()
+        || e.is_connection_dropped()
+        || e.is_timeout()
+        || matches!(e.kind(), RedisErrorKind::BusyLoadingError
+            | RedisErrorKind::TryAgain
+            | RedisErrorKind::ClusterDown
+            | RedisErrorKind::MasterDown
+            | RedisErrorKind::ReadOnly)
+}
+
+#[cfg(feature = "postgres")]
+fn is_transient_postgres(e: &PostgresError) -> bool {
+    use deadpool_postgres::tokio_postgres::error::SqlState;
+
+    match e.code() {
+        // class 08 are connection exceptions
+        Some(code) => code.code().starts_with("08") || [
+            SqlState::ADMIN_SHUTDOWN,
+            SqlState::CRASH_SHUTDOWN,
+            SqlState::CANNOT_CONNECT_NOW,
+            SqlState::T_R_SERIALIZATION_FAILURE,
+            SqlState::T_R_DEADLOCK_DETECTED,
+        ].contains(code),
+        None => e.is_closed() || error::Error::source(e).is_some_and(|source| source.is::<io::Error>()),
+    }
+}
+
+impl fmt::Display for DataVaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataVaultError::NotFound => write!(f, "token not found"),
+            DataVaultError::Conflict => write!(f, "record was modified concurrently"),
+            DataVaultError::InvalidNamespace(namespace) => write!(f, "invalid namespace: {:?}", namespace),
+            DataVaultError::InvalidToken => write!(f, "invalid token"),
+            DataVaultError::AlreadyExists => write!(f, "token already exists"),
+            DataVaultError::Serialization(e) => write!(f, "serialization error: {}", e),
+            DataVaultError::Encoding(e) => write!(f, "encoding error: {}", e),
+            DataVaultError::Io(e) => write!(f, "io error: {}", e),
+            DataVaultError::InvalidExport(reason) => write!(f, "invalid export: {}", reason),
+            DataVaultError::InvalidCursor => write!(f, "invalid cursor"),
+            DataVaultError::SecurityCodeNotAllowed => write!(f, "storing the security code is not allowed"),
+            DataVaultError::Validation(e) => write!(f, "invalid card: {}", e),
+            DataVaultError::AccessDenied => write!(f, "access denied"),
+            DataVaultError::RateLimited(retry_after) => write!(f, "rate limited, retry after {:?}", retry_after),
+            DataVaultError::TamperedAuditLog(sequence) => write!(f, "audit log tampered at entry {}", sequence),
+            DataVaultError::InvalidFieldLayout(reason) => write!(f, "invalid field layout: {}", reason),
+            DataVaultError::Unsupported(operation) => write!(f, "unsupported operation: {}", operation),
+            #[cfg(feature = "redis")]
+            DataVaultError::RedisPool(e) => write!(f, "redis pool error: {}", e),
+            #[cfg(feature = "redis")]
+            DataVaultError::Redis(e) => write!(f, "redis error: {}", e),
+            #[cfg(feature = "postgres")]
+            DataVaultError::PostgresPool(e) => write!(f, "postgres pool error: {}", e),
+            #[cfg(feature = "postgres")]
+            DataVaultError::Postgres(e) => write!(f, "postgres error: {}", e),
+            #[cfg(feature = "remote")]
+            DataVaultError::Remote(e) => write!(f, "vault service error: {}", e),
+            #[cfg(feature = "remote")]
+            DataVaultError::RemoteStatus(status, message) => write!(f, "vault service answered {}: {}", status, message),
+        }
+    }
+}
+
+impl error::Error for DataVaultError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DataVaultError::NotFound => None,
+            DataVaultError::Conflict => None,
+            DataVaultError::InvalidNamespace(_) => None,
+            DataVaultError::InvalidToken => None,
+            DataVaultError::AlreadyExists => None,
+            DataVaultError::Serialization(e) => Some(e),
+            DataVaultError::Encoding(e) => Some(&**e),
+            DataVaultError::Io(e) => Some(e),
+            DataVaultError::InvalidExport(_) => None,
+            DataVaultError::InvalidCursor => None,
+            DataVaultError::SecurityCodeNotAllowed => None,
+            DataVaultError::Validation(e) => Some(e),
+            DataVaultError::AccessDenied => None,
+            DataVaultError::RateLimited(_) => None,
+            DataVaultError::TamperedAuditLog(_) => None,
+            DataVaultError::InvalidFieldLayout(_) => None,
+            DataVaultError::Unsupported(_) => None,
+            #[cfg(feature = "redis")]
+            DataVaultError::RedisPool(e) => Some(e),
+            #[cfg(feature = "redis")]
+            DataVaultError::Redis(e) => Some(e),
+            #[cfg(feature = "postgres")]
+            DataVaultError::PostgresPool(e) => Some(e),
+            #[cfg(feature = "postgres")]
+            DataVaultError::Postgres(e) => Some(e),
+            #[cfg(feature = "remote")]
+            DataVaultError::Remote(e) => Some(e),
+            #[cfg(feature = "remote")]
+            DataVaultError::RemoteStatus(..) => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for DataVaultError {
+    fn from(e: serde_json::Error) -> Self {DataVaultError::Serialization(e)}
+}
+
+impl From<io::Error> for DataVaultError {
+    fn from(e: io::Error) -> Self {DataVaultError::Io(e)}
+}
+
+impl From<ValidationError> for DataVaultError {
+    fn from(e: ValidationError) -> Self {DataVaultError::Validation(e)}
+}
+
+#[cfg(feature = "redis")]
+impl From<RedisPoolError> for DataVaultError {
+    fn from(e: RedisPoolError) -> Self {DataVaultError::RedisPool(e)}
+}
+
+#[cfg(feature = "redis")]
+impl From<RedisError> for DataVaultError {
+    fn from(e: RedisError) -> Self {DataVaultError::Redis(e)}
+}
+
+#[cfg(feature = "postgres")]
+impl From<PostgresPoolError> for DataVaultError {
+    fn from(e: PostgresPoolError) -> Self {DataVaultError::PostgresPool(e)}
+}
+
+#[cfg(feature = "postgres")]
+impl From<PostgresError> for DataVaultError {
+    fn from(e: PostgresError) -> Self {DataVaultError::Postgres(e)}
+}
+
+// the URL names the token, it is left out
+#[cfg(feature = "remote")]
+impl From<RemoteError> for DataVaultError {
+    fn from(e: RemoteError) -> Self {DataVaultError::Remote(e.without_url())}
+}
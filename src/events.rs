@@ -0,0 +1,794 @@
+//! Change events of a vault published to Kafka, NATS or a webhook
+//!
+//! A `PublishingDataVault` hands a `ChangeEvent` to its
+//! `EventPublisher` after each successful change, so downstream
+//! systems can keep card on file counts and lifecycle views without
+//! reading the vault.  Events name the kind of change, the token,
+//! the tenant and the time, never card data.
+//!
+//! Events are published as JSON by a background task, a slow or
+//! unreachable broker does not slow down the vault.  Events that do
+//! not fit the publisher's buffer are dropped and counted, see
+//! `EventPublisher::dropped`, delivery is at most once.  Kafka
+//! messages are keyed by token so the events of one token stay in
+//! order within their partition.  Webhook requests are signed with
+//! HMAC-SHA256 and retried, see `WebhookSink`.
+//!
+//! `EventPublisher::publish_expiring` announces the tokens whose
+//! records expire soon, call it periodically like
+//! `purge_expired_every`.
+//!
+//! # example
+//! ```rust,ignore
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::events::{EventPublisher, KafkaSink};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! let sink = KafkaSink::new("kafka-1:9092,kafka-2:9092", "data_vault.changes").unwrap();
+//! let publisher = EventPublisher::new(sink, 1024).unwrap();
+//! let vault = publisher.published(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+//! let token = vault.store_credit_card(&credit_card).await.unwrap();
+//! ```
+
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "webhooks")]
+use hmac::Mac;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::config::EventsConfig;
+#[cfg(feature = "webhooks")]
+use crate::retry::RetryPolicy;
+use crate::redact::redact_token;
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// The error type of `EventSink::publish`
+pub type SinkError = Box<dyn error::Error + Send + Sync>;
+
+/// What changed in the vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// a record was stored under a new token
+    Created,
+    /// the record of a token was replaced
+    Updated,
+    /// a record moved to a new token, the old one is gone
+    Rotated,
+    /// a record was soft deleted or removed
+    Deleted,
+    /// a purge of records past their retention period completed
+    Purged,
+    /// the record of a token expires soon, see `publish_expiring`
+    ExpiringSoon,
+}
+
+/// One change of a vault, `Debug` only prints the ends of the tokens
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    /// the token changed, for `Rotated` the new token, none for `Purged`
+    pub token: Option<String>,
+    /// the token before a rotation
+    pub previous_token: Option<String>,
+    /// how many records were removed, for `Purged`
+    pub count: Option<u64>,
+    /// when the record expires, for `ExpiringSoon`
+    pub expires_at: Option<SystemTime>,
+    /// the namespace of the vault
+    pub tenant: String,
+    pub timestamp: SystemTime,
+}
+
+impl ChangeEvent {
+    fn new(kind: ChangeKind, token: Option<&str>, tenant: &str) -> Self {
+        ChangeEvent {
+            kind,
+            token: token.map(str::to_string),
+            previous_token: None,
+            count: None,
+            expires_at: None,
+            tenant: tenant.to_string(),
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+impl fmt::Debug for ChangeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChangeEvent")
+            .field("kind", &self.kind)
+            .field("token", &self.token.as_deref().map(redact_token))
+            .field("previous_token", &self.previous_token.as_deref().map(redact_token))
+            .field("count", &self.count)
+            .field("expires_at", &self.expires_at)
+            .field("tenant", &self.tenant)
+            .field("timestamp", &self.timestamp)
+            .finish()
+    }
+}
+
+/// Where an `EventPublisher` sends events, `KafkaSink`, `NatsSink`,
+/// `WebhookSink` or a broker of your own
+#[async_trait]
+pub trait EventSink: Send + Sync + 'static {
+    async fn publish(&self, event: &ChangeEvent) -> Result<(), SinkError>;
+}
+
+/// Publishes to a Kafka topic, keyed by token
+#[cfg(feature = "kafka")]
+pub struct KafkaSink {
+    producer: rdkafka::producer::FutureProducer,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaSink {
+    /// A producer of `topic` on the comma separated `brokers`
+    pub fn new(brokers: &str, topic: &str) -> Result<Self, rdkafka::error::KafkaError> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", "30000")
+            .create()?;
+        Ok(KafkaSink {
+            producer,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl EventSink for KafkaSink {
+    async fn publish(&self, event: &ChangeEvent) -> Result<(), SinkError> {
+        use rdkafka::producer::FutureRecord;
+        use rdkafka::util::Timeout;
+
+        let payload = serde_json::to_vec(event)?;
+        let key = event.token.as_deref().unwrap_or(&event.tenant);
+        let record = FutureRecord::to(&self.topic).key(key).payload(&payload);
+        self.producer.send(record, Timeout::Never).await
+            .map(|_| ())
+            .map_err(|(e, _)| e.into())
+    }
+}
+
+/// Publishes to a NATS subject, connecting on the first event
+#[cfg(feature = "nats")]
+pub struct NatsSink {
+    url: String,
+    subject: String,
+    client: tokio::sync::OnceCell<async_nats::Client>,
+}
+
+#[cfg(feature = "nats")]
+impl NatsSink {
+    /// A publisher of `subject` on the server at `url`
+    pub fn new(url: &str, subject: &str) -> Self {
+        NatsSink {
+            url: url.to_string(),
+            subject: subject.to_string(),
+            client: tokio::sync::OnceCell::new(),
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EventSink for NatsSink {
+    async fn publish(&self, event: &ChangeEvent) -> Result<(), SinkError> {
+        let client = self.client.get_or_try_init(|| async_nats::connect(self.url.as_str())).await?;
+        let payload = serde_json::to_vec(event)?;
+        client.publish(self.subject.clone(), payload.into()).await?;
+        Ok(())
+    }
+}
+
+/// The header carrying `sha256=` and the hex HMAC-SHA256 of
+/// `<timestamp>.<body>`, see `verify_signature`
+#[cfg(feature = "webhooks")]
+pub const SIGNATURE_HEADER: &str = "x-data-vault-signature";
+/// The header carrying the Unix time in seconds the request was signed at
+#[cfg(feature = "webhooks")]
+pub const TIMESTAMP_HEADER: &str = "x-data-vault-timestamp";
+/// The header carrying the `ChangeKind` of the event, e.g. `created`
+#[cfg(feature = "webhooks")]
+pub const EVENT_HEADER: &str = "x-data-vault-event";
+
+/// The value of `SIGNATURE_HEADER` for `body` sent at `timestamp`
+#[cfg(feature = "webhooks")]
+pub fn sign(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    format!("sha256={}", hex::encode(signature_mac(secret, timestamp, body).finalize().into_bytes()))
+}
+
+/// Whether `signature` is the `SIGNATURE_HEADER` of `body` sent at
+/// `timestamp` with `secret`, compared in constant time
+///
+/// Receivers should also reject timestamps more than a few minutes
+/// old so a captured request can not be replayed later.
+#[cfg(feature = "webhooks")]
+pub fn verify_signature(secret: &[u8], timestamp: u64, body: &[u8], signature: &str) -> bool {
+    match signature.strip_prefix("sha256=").map(hex::decode) {
+        Some(Ok(signature)) => signature_mac(secret, timestamp, body).verify_slice(&signature).is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "webhooks")]
+fn signature_mac(secret: &[u8], timestamp: u64, body: &[u8]) -> hmac::Hmac<sha2::Sha256> {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Posts each event as JSON to a URL, signed with HMAC-SHA256
+///
+/// Every attempt carries `TIMESTAMP_HEADER`, `SIGNATURE_HEADER` and
+/// `EVENT_HEADER`.  Any 2xx answer delivers the event, connection
+/// errors, timeouts, 429 and 5xx answers are retried as the
+/// `RetryPolicy` allows, by default 5 attempts over about 30 seconds.
+/// Receivers may see an event twice when an answer is lost.
+#[cfg(feature = "webhooks")]
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    secret: Vec<u8>,
+    retry: RetryPolicy,
+}
+
+#[cfg(feature = "webhooks")]
+impl WebhookSink {
+    /// A sink posting to `url`, signing with `secret`
+    pub fn new(url: &str, secret: &[u8]) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        Ok(WebhookSink {
+            client,
+            url: url.to_string(),
+            secret: secret.to_vec(),
+            retry: RetryPolicy::new(5)
+                .with_base_delay(Duration::from_secs(1))
+                .with_max_delay(Duration::from_secs(16)),
+        })
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    async fn post(&self, kind: &str, body: &[u8]) -> Result<(), WebhookError> {
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let response = self.client.post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, sign(&self.secret, timestamp, body))
+            .header(EVENT_HEADER, kind)
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| WebhookError {
+                transient: e.is_connect() || e.is_timeout() || e.is_request(),
+                error: e.without_url().into(),
+            })?;
+
+        let status = response.status();
+        match status.is_success() {
+            true => Ok(()),
+            false => Err(WebhookError {
+                transient: status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+                error: format!("webhook answered {}", status).into(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+struct WebhookError {
+    error: SinkError,
+    transient: bool,
+}
+
+#[cfg(feature = "webhooks")]
+#[async_trait]
+impl EventSink for WebhookSink {
+    async fn publish(&self, event: &ChangeEvent) -> Result<(), SinkError> {
+        let body = serde_json::to_vec(event)?;
+        let kind = serde_json::to_value(event.kind)?;
+        let kind = kind.as_str().unwrap_or_default();
+        self.retry.run(|e: &WebhookError| e.transient, || self.post(kind, &body)).await
+            .map_err(|e| e.error)
+    }
+}
+
+/// Hands events to an `EventSink` on a background task
+///
+/// Share one publisher between vaults with the `Arc` `new` returns.
+pub struct EventPublisher {
+    sender: mpsc::Sender<ChangeEvent>,
+    dropped: AtomicU64,
+    failed: Arc<AtomicU64>,
+}
+
+impl EventPublisher {
+    /// A publisher buffering up to `buffer` events for `sink`
+    /// returns:
+    ///     * `DataVaultError::Unsupported` outside of a tokio runtime
+    pub fn new<S: EventSink>(sink: S, buffer: usize) -> Result<Arc<Self>, DataVaultError> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|_| DataVaultError::Unsupported("publishing events outside of a tokio runtime"))?;
+        let (sender, mut receiver) = mpsc::channel::<ChangeEvent>(buffer.max(1));
+        let failed = Arc::new(AtomicU64::new(0));
+
+        let failures = failed.clone();
+        runtime.spawn(async move {
+            // ends once every publisher and its vaults are dropped
+            while let Some(event) = receiver.recv().await {
+                if sink.publish(&event).await.is_err() {
+                    failures.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+
+        Ok(Arc::new(EventPublisher {
+            sender,
+            dropped: AtomicU64::new(0),
+            failed,
+        }))
+    }
+
+    /// The publisher `EventsConfig::from_env` describes, Kafka when
+    /// brokers are set, else NATS when a server is, else a webhook
+    pub fn from_env() -> Result<Arc<Self>, Box<dyn error::Error>> {
+        let cfg = EventsConfig::from_env()?;
+        #[cfg(feature = "kafka")]
+        {
+            if let Some(brokers) = &cfg.kafka_brokers {
+                return Ok(EventPublisher::new(KafkaSink::new(brokers, &cfg.topic)?, cfg.buffer)?)
+            }
+        }
+        #[cfg(feature = "nats")]
+        {
+            if let Some(url) = &cfg.nats_url {
+                return Ok(EventPublisher::new(NatsSink::new(url, &cfg.topic), cfg.buffer)?)
+            }
+        }
+        #[cfg(feature = "webhooks")]
+        {
+            if let Some(url) = &cfg.webhook_url {
+                return Ok(EventPublisher::new(WebhookSink::new(url, cfg.webhook_secret.as_bytes())?, cfg.buffer)?)
+            }
+        }
+        Err("none of DATA_VAULT_EVENTS_KAFKA_BROKERS, DATA_VAULT_EVENTS_NATS_URL and DATA_VAULT_EVENTS_WEBHOOK_URL is set for this build".into())
+    }
+
+    /// `vault` publishing its changes through this publisher
+    pub fn published<V: DataVault>(self: &Arc<Self>, vault: V) -> PublishingDataVault<V> {
+        PublishingDataVault {
+            inner: vault,
+            publisher: self.clone(),
+            side: false,
+        }
+    }
+
+    /// Publish `ExpiringSoon` for each record of `vault` that expires
+    /// within `within`, records without a time to live never do
+    ///
+    /// Every record is read, so call it rarely, e.g. daily.  Unlike
+    /// changes these events wait for room in the buffer instead of
+    /// being dropped.
+    /// returns:
+    ///     * how many events were published
+    pub async fn publish_expiring<V: DataVault>(&self, vault: &V, within: Duration) -> Result<u64, DataVaultError> {
+        let mut published = 0;
+        let mut records = vault.iter_records();
+        while let Some((token, _)) = records.try_next().await? {
+            let ttl = match vault.retrieve_with_metadata(&token).await {
+                Ok((_, metadata)) => metadata.ttl,
+                // expired or deleted since it was listed
+                Err(DataVaultError::NotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            if let Some(ttl) = ttl.filter(|ttl| *ttl <= within) {
+                let mut event = ChangeEvent::new(ChangeKind::ExpiringSoon, Some(&token), vault.namespace());
+                event.expires_at = Some(event.timestamp + ttl);
+                if self.sender.send(event).await.is_err() {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    continue
+                }
+                published += 1;
+            }
+        }
+        Ok(published)
+    }
+
+    /// Events dropped because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Events the sink failed to publish
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, event: ChangeEvent) {
+        match self.sender.try_send(event) {
+            Ok(()) => {},
+            Err(TrySendError::Full(_)) | Err(TrySendError::Closed(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            },
+        }
+    }
+}
+
+/// A vault publishing a `ChangeEvent` after each successful change
+/// of the wrapped vault, see `EventPublisher::published`
+///
+/// `tokenize` only publishes when it created the record, `store` and
+/// `store_credit_card_with_token` publish `Created` even when they
+/// replaced a record.
+/// `delete_many` publishes one event per token when it deleted
+/// anything, tokens that were not stored included.  `purge_expired`
+/// publishes `Purged` after every completed purge.  `touch` and
+/// reads publish nothing.
+pub struct PublishingDataVault<V> {
+    inner: V,
+    publisher: Arc<EventPublisher>,
+    // a side vault, see `with_side_namespace`
+    side: bool,
+}
+
+impl<V> PublishingDataVault<V>
+    where
+        V: DataVault,
+{
+    /// The wrapped vault, changes made through it are not published
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    pub fn publisher(&self) -> &Arc<EventPublisher> {
+        &self.publisher
+    }
+
+    fn publish(&self, kind: ChangeKind, token: Option<&str>) {
+        self.send(ChangeEvent::new(kind, token, self.inner.namespace()));
+    }
+
+    fn send(&self, event: ChangeEvent) {
+        if !self.side {
+            self.publisher.send(event);
+        }
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for PublishingDataVault<V>
+    where
+        V: DataVault,
+{
+    /// Publishes through `EventPublisher::from_env`, each call starts
+    /// another publisher, use `EventPublisher::published` for several
+    /// vaults in one process.
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(EventPublisher::from_env()?.published(V::new()?))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.inner.store(token, string).await?;
+        self.publish(ChangeKind::Created, Some(token));
+        Ok(())
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        let stored = self.inner.store_if_absent(token, string).await?;
+        if stored {
+            self.publish(ChangeKind::Created, Some(token));
+        }
+        Ok(stored)
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.inner.store_expiring(token, string, ttl).await?;
+        self.publish(ChangeKind::Created, Some(token));
+        Ok(())
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        let token = self.inner.store_credit_card(credit_card).await?;
+        self.publish(ChangeKind::Created, Some(&token));
+        Ok(token)
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        let (token, created) = self.inner.tokenize(credit_card).await?;
+        if created {
+            self.publish(ChangeKind::Created, Some(&token));
+        }
+        Ok((token, created))
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.inner.store_credit_card_with_token(token, credit_card, overwrite).await?;
+        self.publish(ChangeKind::Created, Some(token));
+        Ok(())
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inner.retrieve(token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.inner.retrieve_credit_card(token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.inner.exists(token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.inner.retrieve_credit_card_with_version(token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.inner.retrieve_with_metadata(token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let version = self.inner.update_credit_card_if_version(token, credit_card, expected_version).await?;
+        self.publish(ChangeKind::Updated, Some(token));
+        Ok(version)
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        let new_token = self.inner.rotate_token(token).await?;
+        let mut event = ChangeEvent::new(ChangeKind::Rotated, Some(&new_token), self.inner.namespace());
+        event.previous_token = Some(token.to_string());
+        self.send(event);
+        Ok(new_token)
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.inner.soft_delete(token).await?;
+        self.publish(ChangeKind::Deleted, Some(token));
+        Ok(())
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.inner.touch(token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let deleted = self.inner.delete_many(tokens).await?;
+        if deleted > 0 {
+            for token in tokens {
+                self.publish(ChangeKind::Deleted, Some(token));
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        let report = self.inner.purge_expired().await?;
+        let mut event = ChangeEvent::new(ChangeKind::Purged, None, self.inner.namespace());
+        event.count = Some(report.purged);
+        self.send(event);
+        Ok(report)
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.inner.decrypted_records_page(cursor, limit).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.inner.count().await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.inner.stats().await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault publishes through the same publisher
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(self.publisher.published(self.inner.with_namespace(namespace)?))
+    }
+
+    /// Changes of side vaults are not published, they are no records
+    /// of the vault but data kept about them, e.g. security codes
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(PublishingDataVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            publisher: self.publisher.clone(),
+            side: true,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use async_trait::async_trait;
+    use credit_card::CreditCard;
+    use crate::events::{ChangeEvent, ChangeKind, EventPublisher, EventSink, SinkError};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        payloads: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl EventSink for RecordingSink {
+        async fn publish(&self, event: &ChangeEvent) -> Result<(), SinkError> {
+            self.payloads.lock().unwrap().push(serde_json::to_string(event)?);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_change_events() {
+        let sink = RecordingSink::default();
+        let publisher = EventPublisher::new(sink.clone(), 16).unwrap();
+        let vault = publisher.published(RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap())
+            .with_namespace("events-test").unwrap();
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+
+        let (token, _) = vault.tokenize(&credit_card).await.unwrap();
+        // stored already, nothing is published
+        vault.tokenize(&credit_card).await.unwrap();
+        let rotated = vault.rotate_token(&token).await.unwrap();
+        // data kept about a record is no change of the vault
+        vault.store_cvv(&rotated, "123", Duration::from_secs(60)).await.unwrap();
+        vault.delete_many(std::slice::from_ref(&rotated)).await.unwrap();
+
+        let mut payloads = Vec::new();
+        for _ in 0..100 {
+            payloads = sink.payloads.lock().unwrap().clone();
+            if payloads.len() >= 3 {
+                break
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let events: Vec<ChangeEvent> = payloads.iter().map(|payload| serde_json::from_str(payload).unwrap()).collect();
+        let kinds: Vec<ChangeKind> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, vec![ChangeKind::Created, ChangeKind::Rotated, ChangeKind::Deleted]);
+        assert_eq!(events[1].previous_token.as_deref(), Some(token.as_str()));
+        assert_eq!(events[2].token.as_deref(), Some(rotated.as_str()));
+        assert!(events.iter().all(|event| event.tenant == "events-test"));
+        assert!(payloads.iter().all(|payload| !payload.contains("4111111111111111") && !payload.contains("Graydon")));
+        assert_eq!(publisher.dropped(), 0);
+    }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_publish_expiring() {
+        let sink = RecordingSink::default();
+        let publisher = EventPublisher::new(sink.clone(), 16).unwrap();
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace("events-expiring-test").unwrap();
+        let credit_card = CreditCard {
+            number: "5555555555554444".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+        let (token, _) = vault.tokenize(&credit_card).await.unwrap();
+
+        vault.touch(&token, None).await.unwrap();
+        assert_eq!(publisher.publish_expiring(&vault, Duration::from_secs(3600)).await.unwrap(), 0);
+        vault.touch(&token, Some(Duration::from_secs(600))).await.unwrap();
+        assert_eq!(publisher.publish_expiring(&vault, Duration::from_secs(60)).await.unwrap(), 0);
+        assert_eq!(publisher.publish_expiring(&vault, Duration::from_secs(3600)).await.unwrap(), 1);
+
+        vault.delete_many(std::slice::from_ref(&token)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let event: ChangeEvent = serde_json::from_str(&sink.payloads.lock().unwrap()[0]).unwrap();
+        assert_eq!(event.kind, ChangeKind::ExpiringSoon);
+        assert_eq!(event.token, Some(token));
+        assert!(event.expires_at.unwrap() > event.timestamp);
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_webhook_sink() {
+        use crate::events::{verify_signature, WebhookSink, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+        use crate::retry::RetryPolicy;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        // answers 503 first and 200 then, keeping the requests
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length: usize = text.lines()
+                            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ").map(str::to_string))
+                            .map_or(0, |length| length.parse().unwrap());
+                        if request.len() >= end + 4 + length {
+                            break
+                        }
+                    }
+                }
+                let answer = format!("HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", status);
+                socket.write_all(answer.as_bytes()).await.unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+
+        let sink = WebhookSink::new(&url, b"whsec-test").unwrap()
+            .with_retry(RetryPolicy::new(3).with_base_delay(Duration::from_millis(10)));
+        let mut event = ChangeEvent::new(ChangeKind::Purged, None, "webhook-test");
+        event.count = Some(3);
+        sink.publish(&event).await.unwrap();
+
+        let requests = server.await.unwrap();
+        let (head, body) = requests[1].split_once("\r\n\r\n").unwrap();
+        let header = |name: &str| head.lines()
+            .find_map(|line| line.to_ascii_lowercase().starts_with(name).then(|| line[name.len() + 2..].to_string()))
+            .unwrap();
+        let timestamp: u64 = header(TIMESTAMP_HEADER).parse().unwrap();
+        assert!(verify_signature(b"whsec-test", timestamp, body.as_bytes(), &header(SIGNATURE_HEADER)));
+        assert!(!verify_signature(b"another-secret", timestamp, body.as_bytes(), &header(SIGNATURE_HEADER)));
+        assert!(!verify_signature(b"whsec-test", timestamp + 1, body.as_bytes(), &header(SIGNATURE_HEADER)));
+        assert_eq!(header("x-data-vault-event"), "purged");
+        assert_eq!(serde_json::from_str::<ChangeEvent>(body).unwrap(), event);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,250 @@
+// generic-array 0.14.9 deprecates itself in favour of 1.x, which the
+// aes-gcm-siv 0.10 API still hands out
+#![allow(deprecated)]
+
+//! The export format
+//!
+//! ```text
+//! header:  "DVEXPORT" | format version (u8) | salt (16 bytes)
+//! frames:  length (u32, big endian) | nonce (12 bytes) | ciphertext
+//! ```
+//!
+//! Every frame is AES-256-GCM-SIV encrypted with a key derived from
+//! the export key and the salt.  The header and the position of the
+//! frame are authenticated along with it, so frames can not be
+//! altered, reordered or moved between exports.  The last frame holds
+//! the number of records, a truncated export is detected on import.
+
+use aes_gcm_siv::Aes256GcmSiv;
+use aes_gcm_siv::aead::{Aead, NewAead, Payload, generic_array::GenericArray};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 8] = b"DVEXPORT";
+const FORMAT_VERSION: u8 = 1;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const HEADER_SIZE: usize = MAGIC.len() + 1 + SALT_SIZE;
+const KEY_CONTEXT: &str = "data_vault 2021-05-01 export key";
+// larger frames are rejected before allocating them
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+enum Frame {
+    Record { token: String, data: String },
+    End { records: u64 },
+}
+
+struct ExportCipher {
+    cipher: Aes256GcmSiv,
+    header: Vec<u8>,
+}
+
+impl ExportCipher {
+    fn new(export_key: &[u8], header: Vec<u8>) -> Self {
+        let salt = &header[HEADER_SIZE - SALT_SIZE..];
+        let key_material = [export_key, salt].concat();
+        let mut key = [0u8; 32];
+        blake3::derive_key(KEY_CONTEXT, &key_material, &mut key);
+
+        Self {
+            cipher: Aes256GcmSiv::new(GenericArray::from_slice(&key)),
+            header,
+        }
+    }
+
+    /// the header and the frame position are authenticated with every frame
+    fn associated_data(&self, index: u64) -> Vec<u8> {
+        [self.header.as_slice(), &index.to_be_bytes()].concat()
+    }
+
+    fn seal(&self, index: u64, frame: &Frame) -> Result<Vec<u8>, DataVaultError> {
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let payload = Payload {
+            msg: &serde_json::to_vec(frame)?,
+            aad: &self.associated_data(index),
+        };
+        let cipher_text = self.cipher.encrypt(GenericArray::from_slice(&nonce), payload)
+            .map_err(|_| DataVaultError::InvalidExport("encryption failed"))?;
+        Ok([&nonce[..], &cipher_text].concat())
+    }
+
+    fn open(&self, index: u64, sealed: &[u8]) -> Result<Frame, DataVaultError> {
+        if sealed.len() < NONCE_SIZE {
+            return Err(DataVaultError::InvalidExport("frame too short"))
+        }
+
+        let (nonce, cipher_text) = sealed.split_at(NONCE_SIZE);
+        let payload = Payload {
+            msg: cipher_text,
+            aad: &self.associated_data(index),
+        };
+        let plain_text = self.cipher.decrypt(GenericArray::from_slice(nonce), payload)
+            .map_err(|_| DataVaultError::InvalidExport("authentication failed, wrong key or damaged export"))?;
+        Ok(serde_json::from_slice(&plain_text)?)
+    }
+}
+
+fn write_frame<W: Write>(writer: &mut W, sealed: &[u8]) -> Result<(), DataVaultError> {
+    let length: u32 = sealed.len().try_into()
+        .map_err(|_| DataVaultError::InvalidExport("record too large"))?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(sealed)?;
+    Ok(())
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>, DataVaultError> {
+    let mut length = [0u8; 4];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(DataVaultError::InvalidExport("frame too large"))
+    }
+
+    let mut sealed = vec![0u8; length];
+    reader.read_exact(&mut sealed)?;
+    Ok(sealed)
+}
+
+/// Writes the frames of an export
+pub(crate) struct ExportWriter<W> {
+    writer: W,
+    cipher: ExportCipher,
+    index: u64,
+}
+
+impl<W: Write> ExportWriter<W> {
+    /// writes the header
+    pub(crate) fn new(mut writer: W, export_key: &[u8]) -> Result<Self, DataVaultError> {
+        let salt: [u8; SALT_SIZE] = rand::random();
+        let header = [&MAGIC[..], &[FORMAT_VERSION], &salt].concat();
+        writer.write_all(&header)?;
+
+        Ok(ExportWriter {
+            writer,
+            cipher: ExportCipher::new(export_key, header),
+            index: 0,
+        })
+    }
+
+    pub(crate) fn write_record(&mut self, token: String, data: String) -> Result<(), DataVaultError> {
+        write_frame(&mut self.writer, &self.cipher.seal(self.index, &Frame::Record { token, data })?)?;
+        self.index += 1;
+        Ok(())
+    }
+
+    /// writes the end frame
+    /// returns the number of records written
+    pub(crate) fn finish(mut self) -> Result<u64, DataVaultError> {
+        write_frame(&mut self.writer, &self.cipher.seal(self.index, &Frame::End { records: self.index })?)?;
+        self.writer.flush()?;
+        Ok(self.index)
+    }
+}
+
+/// Reads the records of an export
+pub(crate) struct ExportReader<R> {
+    reader: R,
+    cipher: ExportCipher,
+    index: u64,
+    done: bool,
+}
+
+impl<R: Read> ExportReader<R> {
+    /// reads and checks the header
+    pub(crate) fn new(mut reader: R, export_key: &[u8]) -> Result<Self, DataVaultError> {
+        let mut header = vec![0u8; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(DataVaultError::InvalidExport("not a data vault export"))
+        }
+        if header[MAGIC.len()] != FORMAT_VERSION {
+            return Err(DataVaultError::InvalidExport("unsupported format version"))
+        }
+
+        Ok(ExportReader {
+            reader,
+            cipher: ExportCipher::new(export_key, header),
+            index: 0,
+            done: false,
+        })
+    }
+
+    /// the next token and record, `None` after the end frame
+    pub(crate) fn next_record(&mut self) -> Result<Option<(String, String)>, DataVaultError> {
+        if self.done {
+            return Ok(None)
+        }
+        let sealed = read_frame(&mut self.reader)?;
+        match self.cipher.open(self.index, &sealed)? {
+            Frame::Record { token, data } => {
+                self.index += 1;
+                Ok(Some((token, data)))
+            },
+            Frame::End { records } if records == self.index => {
+                self.done = true;
+                Ok(None)
+            },
+            Frame::End { .. } => Err(DataVaultError::InvalidExport("record count mismatch")),
+        }
+    }
+
+    /// the number of records read so far
+    pub(crate) fn records(&self) -> u64 {
+        self.index
+    }
+}
+
+/// see `DataVault::export`
+pub(crate) async fn export<V, W>(vault: &V, writer: W, export_key: &[u8]) -> Result<u64, DataVaultError>
+    where
+        V: DataVault,
+        W: Write,
+{
+    let mut export = ExportWriter::new(writer, export_key)?;
+    let mut records = vault.iter_decrypted_records();
+    while let Some((token, data)) = records.try_next().await? {
+        export.write_record(token, data)?;
+    }
+    export.finish()
+}
+
+/// see `DataVault::import`
+pub(crate) async fn import<V, R>(vault: &V, reader: R, export_key: &[u8]) -> Result<u64, DataVaultError>
+    where
+        V: DataVault,
+        R: Read,
+{
+    let mut export = ExportReader::new(reader, export_key)?;
+    while let Some((token, data)) = export.next_record()? {
+        vault.store(&token, &data).await?;
+    }
+    Ok(export.records())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::export::{ExportCipher, Frame, HEADER_SIZE};
+
+    #[test]
+    fn test_export_frames_are_authenticated() {
+        let cipher = ExportCipher::new(b"export key", vec![1u8; HEADER_SIZE]);
+        let frame = Frame::Record { token: "abc123".to_string(), data: "{number: 123}".to_string() };
+        let sealed = cipher.seal(7, &frame).unwrap();
+
+        assert!(matches!(cipher.open(7, &sealed), Ok(Frame::Record { .. })));
+        assert!(cipher.open(8, &sealed).is_err());
+
+        let other_key = ExportCipher::new(b"other key", vec![1u8; HEADER_SIZE]);
+        assert!(other_key.open(7, &sealed).is_err());
+
+        let mut damaged = sealed.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 1;
+        assert!(cipher.open(7, &damaged).is_err());
+    }
+}
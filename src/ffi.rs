@@ -0,0 +1,369 @@
+//! A C interface to the vault for software that can not link Rust
+//! directly, declared by `include/data_vault.h`
+//!
+//! Build the library with the functions exported:
+//! ```sh
+//! cargo rustc --lib --release --features ffi --crate-type cdylib     # libdata_vault.so
+//! cargo rustc --lib --release --features ffi --crate-type staticlib  # libdata_vault.a
+//! ```
+//!
+//! A vault is an opaque `DataVault *` from `data_vault_new`, released
+//! by `data_vault_free`, and may be used from several threads at once.
+//! Credit cards cross the interface as JSON, tokens as C strings.
+//! Functions return `DATA_VAULT_OK` or an error code and never unwind
+//! into the caller, `data_vault_last_error` copies the message of the
+//! calling thread's last error, with card numbers and tokens masked.
+//!
+//! ```c
+//! DataVault *vault = data_vault_new("data_vault.toml", NULL, NULL);
+//! char token[DATA_VAULT_TOKEN_LEN];
+//! if (data_vault_store(vault, card_json, token, sizeof token) != DATA_VAULT_OK) {
+//!     char message[256];
+//!     data_vault_last_error(message, sizeof message);
+//! }
+//! data_vault_free(vault);
+//! ```
+
+use credit_card::CreditCard;
+use crate::traits::DataVaultDyn;
+use crate::error::DataVaultError;
+use crate::redact::redact;
+use crate::config_file::{postgres_vault, redis_vault, Backend, Config, TokenizerKind};
+use crate::tokenizer::{Blake3DeterministicTokenizer, Blake3Tokenizer};
+use tokio::runtime::Runtime;
+use std::cell::RefCell;
+use std::error;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+
+/// The call succeeded
+pub const DATA_VAULT_OK: c_int = 0;
+/// The call failed, see `data_vault_last_error`
+pub const DATA_VAULT_ERROR: c_int = 1;
+/// Nothing is stored under the token
+pub const DATA_VAULT_NOT_FOUND: c_int = 2;
+/// A pointer is null, a string not UTF-8 or a card not valid JSON
+pub const DATA_VAULT_INVALID_ARGUMENT: c_int = 3;
+/// The output buffer is too small, nothing was written to it
+pub const DATA_VAULT_BUFFER_TOO_SMALL: c_int = 4;
+/// Bytes a token buffer needs, the longest token and its NUL
+pub const DATA_VAULT_TOKEN_LEN: usize = 65;
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// A vault and the runtime driving it, opaque to C
+pub struct FfiDataVault {
+    vault: Arc<dyn DataVaultDyn>,
+    runtime: Runtime,
+}
+
+fn set_last_error(message: &str) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = redact(message).into_owned());
+}
+
+fn fail(code: c_int, message: &str) -> c_int {
+    set_last_error(message);
+    code
+}
+
+fn vault_error(e: DataVaultError) -> c_int {
+    let code = match e {
+        DataVaultError::NotFound => DATA_VAULT_NOT_FOUND,
+        _ => DATA_VAULT_ERROR,
+    };
+    fail(code, &e.to_string())
+}
+
+/// `f`, with a panic turned into `DATA_VAULT_ERROR`
+fn guard<F>(f: F) -> c_int
+    where
+        F: FnOnce() -> c_int,
+{
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| fail(DATA_VAULT_ERROR, "the vault panicked"))
+}
+
+/// The UTF-8 string at `s`, `None` for null
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<Option<&'a str>, c_int> {
+    if s.is_null() {
+        return Ok(None)
+    }
+    CStr::from_ptr(s).to_str()
+        .map(Some)
+        .map_err(|_| fail(DATA_VAULT_INVALID_ARGUMENT, "a string argument is not UTF-8"))
+}
+
+/// Copy `s` and a NUL into `buf` of `len` bytes, if it fits
+unsafe fn write_str(s: &str, buf: *mut c_char, len: usize) -> bool {
+    if buf.is_null() || s.len() >= len {
+        return false
+    }
+    ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buf, s.len());
+    *buf.add(s.len()) = 0;
+    true
+}
+
+fn open(config: Option<Config>, backend: Backend, tokenizer: TokenizerKind) -> Result<Arc<dyn DataVaultDyn>, Box<dyn error::Error>> {
+    Ok(match (backend, tokenizer) {
+        (Backend::Redis, TokenizerKind::Random) => Arc::new(redis_vault::<Blake3Tokenizer>(config)?),
+        (Backend::Redis, TokenizerKind::Deterministic) => Arc::new(redis_vault::<Blake3DeterministicTokenizer>(config)?),
+        (Backend::Postgres, TokenizerKind::Random) => Arc::new(postgres_vault::<Blake3Tokenizer>(config)?),
+        (Backend::Postgres, TokenizerKind::Deterministic) => Arc::new(postgres_vault::<Blake3DeterministicTokenizer>(config)?),
+    })
+}
+
+fn new_vault(config_file: Option<&str>, backend: Option<&str>, tokenizer: Option<&str>) -> Result<FfiDataVault, String> {
+    let (config, backend, tokenizer) = match config_file {
+        Some(path) => {
+            let config = Config::from_file(path).map_err(|e| e.to_string())?;
+            let (backend, tokenizer) = (config.backend, config.tokenizer);
+            (Some(config), backend, tokenizer)
+        },
+        None => {
+            let backend = match backend.unwrap_or("redis") {
+                "redis" => Backend::Redis,
+                "postgres" => Backend::Postgres,
+                other => return Err(format!("backend {:?} is not redis or postgres", other)),
+            };
+            let tokenizer = match tokenizer.unwrap_or("random") {
+                "random" => TokenizerKind::Random,
+                "deterministic" => TokenizerKind::Deterministic,
+                other => return Err(format!("tokenizer {:?} is not random or deterministic", other)),
+            };
+            (None, backend, tokenizer)
+        },
+    };
+    let runtime = Runtime::new().map_err(|e| e.to_string())?;
+    // the pools are bound to the runtime they are created in
+    let vault = {
+        let _entered = runtime.enter();
+        open(config, backend, tokenizer).map_err(|e| e.to_string())?
+    };
+    Ok(FfiDataVault { vault, runtime })
+}
+
+/// Open a vault from the TOML or YAML file `config_file`, see
+/// `Config::from_file`, or when it is null from the environment with
+/// `backend`, "redis" or "postgres", and `tokenizer`, "random" or
+/// "deterministic", which default to "redis" and "random" when null
+///
+/// Returns null on failure, see `data_vault_last_error`.
+///
+/// # Safety
+/// Each argument is null or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn data_vault_new(config_file: *const c_char, backend: *const c_char, tokenizer: *const c_char) -> *mut FfiDataVault {
+    let mut vault = ptr::null_mut();
+    guard(|| {
+        let args = str_arg(config_file).and_then(|config_file| Ok((config_file, str_arg(backend)?, str_arg(tokenizer)?)));
+        let (config_file, backend, tokenizer) = match args {
+            Ok(args) => args,
+            Err(code) => return code,
+        };
+        match new_vault(config_file, backend, tokenizer) {
+            Ok(opened) => {
+                vault = Box::into_raw(Box::new(opened));
+                DATA_VAULT_OK
+            },
+            Err(e) => fail(DATA_VAULT_ERROR, &e),
+        }
+    });
+    vault
+}
+
+/// Store the credit card `credit_card_json` and copy its token into
+/// `token`, a buffer of `token_len` bytes, at least
+/// `DATA_VAULT_TOKEN_LEN`
+///
+/// # Safety
+/// `vault` comes from `data_vault_new` and is not freed,
+/// `credit_card_json` is a NUL terminated string and `token` points
+/// to `token_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn data_vault_store(vault: *const FfiDataVault, credit_card_json: *const c_char, token: *mut c_char, token_len: usize) -> c_int {
+    guard(|| {
+        let vault = match vault.as_ref() {
+            Some(vault) => vault,
+            None => return fail(DATA_VAULT_INVALID_ARGUMENT, "vault is null"),
+        };
+        // checked first, a stored card's token must not be lost
+        if token.is_null() || token_len < DATA_VAULT_TOKEN_LEN {
+            return fail(DATA_VAULT_BUFFER_TOO_SMALL, "the token buffer is shorter than DATA_VAULT_TOKEN_LEN")
+        }
+        let credit_card_json = match str_arg(credit_card_json) {
+            Ok(Some(json)) => json,
+            Ok(None) => return fail(DATA_VAULT_INVALID_ARGUMENT, "credit_card_json is null"),
+            Err(code) => return code,
+        };
+        let credit_card: CreditCard = match serde_json::from_str(credit_card_json) {
+            Ok(credit_card) => credit_card,
+            Err(e) => return fail(DATA_VAULT_INVALID_ARGUMENT, &format!("invalid credit card: {}", e)),
+        };
+        match vault.runtime.block_on(vault.vault.store_credit_card(&credit_card)) {
+            Ok(stored) if write_str(&stored, token, token_len) => DATA_VAULT_OK,
+            Ok(_) => fail(DATA_VAULT_ERROR, "the token is longer than DATA_VAULT_TOKEN_LEN"),
+            Err(e) => vault_error(e),
+        }
+    })
+}
+
+/// Copy the credit card stored under `token` as JSON into
+/// `credit_card_json`, a buffer of `credit_card_json_len` bytes, and
+/// its length without the NUL into `written` unless it is null
+///
+/// When the buffer is too small `written` is set to the length the
+/// buffer needs, without the NUL.
+///
+/// # Safety
+/// `vault` comes from `data_vault_new` and is not freed, `token` is a
+/// NUL terminated string, `credit_card_json` points to
+/// `credit_card_json_len` writable bytes and `written` is null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn data_vault_retrieve(vault: *const FfiDataVault, token: *const c_char, credit_card_json: *mut c_char, credit_card_json_len: usize, written: *mut usize) -> c_int {
+    guard(|| {
+        let vault = match vault.as_ref() {
+            Some(vault) => vault,
+            None => return fail(DATA_VAULT_INVALID_ARGUMENT, "vault is null"),
+        };
+        let token = match str_arg(token) {
+            Ok(Some(token)) => token,
+            Ok(None) => return fail(DATA_VAULT_INVALID_ARGUMENT, "token is null"),
+            Err(code) => return code,
+        };
+        let credit_card = match vault.runtime.block_on(vault.vault.retrieve_credit_card(token)) {
+            Ok(credit_card) => credit_card,
+            Err(e) => return vault_error(e),
+        };
+        let json = match serde_json::to_string(&credit_card) {
+            Ok(json) => json,
+            Err(e) => return vault_error(e.into()),
+        };
+        if !written.is_null() {
+            *written = json.len();
+        }
+        match write_str(&json, credit_card_json, credit_card_json_len) {
+            true => DATA_VAULT_OK,
+            false => fail(DATA_VAULT_BUFFER_TOO_SMALL, "the credit card buffer is too small"),
+        }
+    })
+}
+
+/// Delete the credit card stored under `token`
+///
+/// Returns `DATA_VAULT_NOT_FOUND` when nothing is stored under it.
+///
+/// # Safety
+/// `vault` comes from `data_vault_new` and is not freed, `token` is a
+/// NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn data_vault_delete(vault: *const FfiDataVault, token: *const c_char) -> c_int {
+    guard(|| {
+        let vault = match vault.as_ref() {
+            Some(vault) => vault,
+            None => return fail(DATA_VAULT_INVALID_ARGUMENT, "vault is null"),
+        };
+        let token = match str_arg(token) {
+            Ok(Some(token)) => token.to_string(),
+            Ok(None) => return fail(DATA_VAULT_INVALID_ARGUMENT, "token is null"),
+            Err(code) => return code,
+        };
+        match vault.runtime.block_on(vault.vault.delete_many(&[token])) {
+            Ok(0) => vault_error(DataVaultError::NotFound),
+            Ok(_) => DATA_VAULT_OK,
+            Err(e) => vault_error(e),
+        }
+    })
+}
+
+/// Close the vault's connections and free it, null is ignored
+///
+/// # Safety
+/// `vault` is null or comes from `data_vault_new` and is not used
+/// again.
+#[no_mangle]
+pub unsafe extern "C" fn data_vault_free(vault: *mut FfiDataVault) {
+    if !vault.is_null() {
+        guard(|| {
+            drop(Box::from_raw(vault));
+            DATA_VAULT_OK
+        });
+    }
+}
+
+/// Copy the message of the calling thread's last error into
+/// `message`, a buffer of `message_len` bytes, cut short to fit
+///
+/// Returns the length of the whole message without the NUL.
+///
+/// # Safety
+/// `message` is null or points to `message_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn data_vault_last_error(message: *mut c_char, message_len: usize) -> usize {
+    LAST_ERROR.with(|last| {
+        let last = last.borrow();
+        if !message.is_null() && message_len > 0 {
+            let mut end = last.len().min(message_len - 1);
+            while !last.is_char_boundary(end) {
+                end -= 1;
+            }
+            write_str(&last[..end], message, message_len);
+        }
+        last.len()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ffi::*;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    fn last_error() -> String {
+        let mut message = [0 as c_char; 256];
+        unsafe {
+            data_vault_last_error(message.as_mut_ptr(), message.len());
+            CStr::from_ptr(message.as_ptr()).to_str().unwrap().to_string()
+        }
+    }
+
+    #[test]
+    fn test_ffi() {
+        let backend = CString::new("redis").unwrap();
+        let card = CString::new(r#"{"number": "4111111111111111", "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023"}"#).unwrap();
+        unsafe {
+            let vault = data_vault_new(ptr::null(), backend.as_ptr(), ptr::null());
+            assert!(!vault.is_null(), "{}", last_error());
+
+            let mut short = [0 as c_char; 8];
+            assert_eq!(data_vault_store(vault, card.as_ptr(), short.as_mut_ptr(), short.len()), DATA_VAULT_BUFFER_TOO_SMALL);
+            let invalid = CString::new(r#"{"number": "4111111111111111"}"#).unwrap();
+            let mut token = [0 as c_char; DATA_VAULT_TOKEN_LEN];
+            assert_eq!(data_vault_store(vault, invalid.as_ptr(), token.as_mut_ptr(), token.len()), DATA_VAULT_INVALID_ARGUMENT);
+            assert!(!last_error().contains("4111111111111111"));
+            assert_eq!(data_vault_store(vault, card.as_ptr(), token.as_mut_ptr(), token.len()), DATA_VAULT_OK);
+
+            let mut written = 0;
+            assert_eq!(data_vault_retrieve(vault, token.as_ptr(), short.as_mut_ptr(), short.len(), &mut written), DATA_VAULT_BUFFER_TOO_SMALL);
+            let mut json = vec![0 as c_char; written + 1];
+            assert_eq!(data_vault_retrieve(vault, token.as_ptr(), json.as_mut_ptr(), json.len(), &mut written), DATA_VAULT_OK);
+            assert!(CStr::from_ptr(json.as_ptr()).to_str().unwrap().contains("4111111111111111"));
+
+            assert_eq!(data_vault_delete(vault, token.as_ptr()), DATA_VAULT_OK);
+            assert_eq!(data_vault_delete(vault, token.as_ptr()), DATA_VAULT_NOT_FOUND);
+            assert_eq!(data_vault_retrieve(vault, token.as_ptr(), json.as_mut_ptr(), json.len(), ptr::null_mut()), DATA_VAULT_NOT_FOUND);
+            assert!(!last_error().contains(CStr::from_ptr(token.as_ptr()).to_str().unwrap()));
+            data_vault_free(vault);
+
+            let unknown = CString::new("dynamodb").unwrap();
+            assert!(data_vault_new(ptr::null(), unknown.as_ptr(), ptr::null()).is_null());
+            assert!(last_error().contains("dynamodb"));
+        }
+    }
+}
\ No newline at end of file
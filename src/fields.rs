@@ -0,0 +1,210 @@
+use credit_card::CreditCard;
+use crate::config::{EncryptionConfig, EncryptionSettings};
+use crate::error::DataVaultError;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+const KEY_CONTEXT: &str = "data_vault 2021-05-01 blind index";
+
+/// A `CreditCard` field that can get a column of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CardField {
+    Number,
+    CardholderName,
+    ExpirationMonth,
+    ExpirationYear,
+    Brand,
+}
+
+const CARD_FIELDS: [CardField; 5] = [
+    CardField::Number,
+    CardField::CardholderName,
+    CardField::ExpirationMonth,
+    CardField::ExpirationYear,
+    CardField::Brand,
+];
+
+impl CardField {
+    /// The postgres column holding the field
+    pub fn column(&self) -> &'static str {
+        match self {
+            CardField::Number => "number",
+            CardField::CardholderName => "cardholder_name",
+            CardField::ExpirationMonth => "expiration_month",
+            CardField::ExpirationYear => "expiration_year",
+            CardField::Brand => "brand",
+        }
+    }
+
+    fn value<'a>(&self, credit_card: &'a CreditCard) -> Option<&'a str> {
+        match self {
+            CardField::Number => Some(&credit_card.number),
+            CardField::CardholderName => Some(&credit_card.cardholder_name),
+            CardField::ExpirationMonth => Some(&credit_card.expiration_month),
+            CardField::ExpirationYear => Some(&credit_card.expiration_year),
+            CardField::Brand => credit_card.brand.as_deref(),
+        }
+    }
+}
+
+/// Parses the column name, e.g. `expiration_year`
+impl FromStr for CardField {
+    type Err = DataVaultError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CARD_FIELDS.iter()
+            .find(|field| field.column() == s)
+            .copied()
+            .ok_or(DataVaultError::InvalidFieldLayout("unknown card field"))
+    }
+}
+
+/// How a field is kept in its column, the encrypted record always
+/// holds the whole card
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldStorage {
+    /// only in the encrypted record, the column stays NULL
+    #[default]
+    Encrypted,
+    /// in cleartext, e.g. expiry and brand for reporting
+    Plaintext,
+    /// as a keyed blake3 hash, found by equality but not readable
+    BlindIndex,
+}
+
+/// Which `CreditCard` fields `PostgresDataVault` writes to columns
+/// of their own next to the encrypted record
+///
+/// By default every field is only encrypted.  Blind indexes are
+/// keyed with a key derived from `ENCRYPTED_DATA_VAULT_KEY`.  The
+/// card number can not be stored in plaintext.  Configured with
+/// `DATA_VAULT_PLAINTEXT_FIELDS` and `DATA_VAULT_BLIND_INDEX_FIELDS`,
+/// comma separated column names.
+/// # example
+/// ```rust
+/// use data_vault::{CardField, CardFieldLayout, FieldStorage};
+///
+/// let layout = CardFieldLayout::new().unwrap()
+///     .with(CardField::ExpirationYear, FieldStorage::Plaintext).unwrap()
+///     .with(CardField::Brand, FieldStorage::Plaintext).unwrap()
+///     .with(CardField::Number, FieldStorage::BlindIndex).unwrap();
+/// assert!(layout.clone().with(CardField::Number, FieldStorage::Plaintext).is_err());
+/// ```
+#[derive(Clone)]
+pub struct CardFieldLayout {
+    storage: [FieldStorage; 5],
+    key: [u8; 32],
+}
+
+impl fmt::Debug for CardFieldLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CardFieldLayout")
+            .field("storage", &self.storage)
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+impl CardFieldLayout {
+    /// Every field encrypted only
+    pub fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(CardFieldLayout::from_settings(&EncryptionConfig::from_env(None)?.into()))
+    }
+
+    /// Every field encrypted only, blind indexes keyed with a key
+    /// derived from `settings.key`
+    pub fn from_settings(settings: &EncryptionSettings) -> Self {
+        let mut key = [0u8; 32];
+        blake3::derive_key(KEY_CONTEXT, settings.key.as_bytes(), &mut key);
+
+        CardFieldLayout {
+            storage: Default::default(),
+            key,
+        }
+    }
+
+    pub(crate) fn from_config(settings: &EncryptionSettings, plaintext_fields: &[CardField], blind_index_fields: &[CardField]) -> Result<Self, DataVaultError> {
+        let mut layout = CardFieldLayout::from_settings(settings);
+        let configured = [(plaintext_fields, FieldStorage::Plaintext), (blind_index_fields, FieldStorage::BlindIndex)];
+        for (fields, storage) in configured.iter() {
+            for field in fields.iter() {
+                layout = layout.with(*field, *storage)?;
+            }
+        }
+        Ok(layout)
+    }
+
+    /// This layout keeping `field` as `storage`
+    /// returns:
+    ///     * `DataVaultError::InvalidFieldLayout` for a plaintext card number
+    pub fn with(mut self, field: CardField, storage: FieldStorage) -> Result<Self, DataVaultError> {
+        if field == CardField::Number && storage == FieldStorage::Plaintext {
+            return Err(DataVaultError::InvalidFieldLayout("the card number can not be stored in plaintext"))
+        }
+
+        self.storage[field as usize] = storage;
+        Ok(self)
+    }
+
+    /// How `field` is kept
+    pub fn storage(&self, field: CardField) -> FieldStorage {
+        self.storage[field as usize]
+    }
+
+    /// The column values of a card in the order of `CardField`,
+    /// `None` for encrypted fields
+    pub(crate) fn columns(&self, credit_card: &CreditCard) -> [Option<String>; 5] {
+        let mut columns: [Option<String>; 5] = Default::default();
+        for (field, column) in CARD_FIELDS.iter().zip(columns.iter_mut()) {
+            *column = field.value(credit_card)
+                .and_then(|value| self.column_value(*field, value).ok());
+        }
+        columns
+    }
+
+    /// What the column of `field` holds for `value`
+    /// returns:
+    ///     * `DataVaultError::InvalidFieldLayout` for an encrypted field
+    pub(crate) fn column_value(&self, field: CardField, value: &str) -> Result<String, DataVaultError> {
+        match self.storage(field) {
+            FieldStorage::Encrypted => Err(DataVaultError::InvalidFieldLayout("the field is only stored encrypted")),
+            FieldStorage::Plaintext => Ok(value.to_string()),
+            FieldStorage::BlindIndex => Ok(blake3::keyed_hash(&self.key, value.as_bytes()).to_hex().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::config::EncryptionSettings;
+    use crate::fields::{CardField, CardFieldLayout, FieldStorage};
+
+    #[test]
+    fn test_card_field_layout() {
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        assert_eq!(CardFieldLayout::new().unwrap().columns(&cc), [None, None, None, None, None]);
+
+        let settings = EncryptionSettings::from_env().unwrap();
+        let layout = CardFieldLayout::from_config(&settings, &[CardField::ExpirationMonth, CardField::ExpirationYear, CardField::Brand], &[CardField::Number]).unwrap();
+        assert_eq!(layout.storage(CardField::CardholderName), FieldStorage::Encrypted);
+        let [number, cardholder_name, month, year, brand] = layout.columns(&cc);
+        assert_eq!(number.unwrap().len(), 64);
+        assert_eq!(cardholder_name, None);
+        assert_eq!(month.as_deref(), Some("01"));
+        assert_eq!(year.as_deref(), Some("2023"));
+        assert_eq!(brand, None);
+
+        assert!(CardFieldLayout::from_config(&settings, &[CardField::Number], &[]).is_err());
+        assert!("cvv".parse::<CardField>().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+#[cfg(any(feature = "redis", feature = "postgres"))]
+use crate::error::DataVaultError;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+use crate::traits::DataVault;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+use std::future::Future;
+
+/// What `DataVault::store_credit_card_deduplicated` did with a card
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreOutcome {
+    /// the card was stored under the new token
+    Created(String),
+    /// a card with the same number was stored before, under this token,
+    /// nothing was written
+    Duplicate(String),
+}
+
+impl StoreOutcome {
+    /// The token of the card, new or existing
+    pub fn token(&self) -> &str {
+        match self {
+            StoreOutcome::Created(token) | StoreOutcome::Duplicate(token) => token,
+        }
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self, StoreOutcome::Duplicate(_))
+    }
+}
+
+/// The token and whether it was created of `store`, or the token of
+/// the live card indexed under `fingerprint` without storing anything
+///
+/// The index maps fingerprints to tokens in the side namespace
+/// `<namespace>~fingerprint`, so cards are only found within the
+/// namespace, e.g. of one customer.  An entry naming a card that was
+/// deleted or rotated away is replaced.  When two stores of one card
+/// race the first to index it wins and the other card is deleted.
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub(crate) async fn deduplicate<V, F>(vault: &V, fingerprint: Option<String>, store: F) -> Result<(String, bool), DataVaultError>
+    where
+        V: DataVault,
+        F: Future<Output = Result<(String, bool), DataVaultError>> + Send,
+{
+    let fingerprint = match fingerprint {
+        Some(fingerprint) => fingerprint,
+        None => return store.await,
+    };
+    let index = fingerprint_index(vault)?;
+    if let Some(existing) = live_token(vault, &index, &fingerprint).await? {
+        return Ok((existing, false))
+    }
+
+    let (token, created) = store.await?;
+    if index.store_if_absent(&fingerprint, &token).await? {
+        return Ok((token, created))
+    }
+    match live_token(vault, &index, &fingerprint).await? {
+        Some(existing) if existing != token => {
+            if created {
+                vault.delete_many(std::slice::from_ref(&token)).await?;
+            }
+            Ok((existing, false))
+        },
+        _ => {
+            index.store(&fingerprint, &token).await?;
+            Ok((token, created))
+        },
+    }
+}
+
+/// The token indexed under `fingerprint` in `vault`'s namespace if a
+/// record is stored under it
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub(crate) async fn indexed_token<V: DataVault>(vault: &V, fingerprint: &str) -> Result<Option<String>, DataVaultError> {
+    live_token(vault, &fingerprint_index(vault)?, fingerprint).await
+}
+
+/// Index the card stored under `token` by `fingerprint`, replacing
+/// what is indexed under it, e.g. after the card got a new number
+#[cfg(feature = "redis")]
+pub(crate) async fn index<V: DataVault>(vault: &V, fingerprint: Option<String>, token: &str) -> Result<(), DataVaultError> {
+    match fingerprint {
+        Some(fingerprint) => fingerprint_index(vault)?.store(&fingerprint, token).await,
+        None => Ok(()),
+    }
+}
+
+/// The vault keeping the fingerprint index of `vault`'s namespace
+#[cfg(any(feature = "redis", feature = "postgres"))]
+fn fingerprint_index<V: DataVault>(vault: &V) -> Result<V, DataVaultError> {
+    vault.with_side_namespace("fingerprint")
+}
+
+#[cfg(any(feature = "redis", feature = "postgres"))]
+async fn live_token<V: DataVault>(vault: &V, index: &V, fingerprint: &str) -> Result<Option<String>, DataVaultError> {
+    match index.try_retrieve(fingerprint).await? {
+        Some(token) if vault.exists(&token).await? => Ok(Some(token)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod test {
+    use credit_card::CreditCard;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::fingerprint::StoreOutcome;
+    use crate::postgres_data_vault::PostgresDataVault;
+    use crate::tokenizer::{Blake3DeterministicTokenizer, Blake3Tokenizer};
+    use crate::traits::DataVault;
+    use crate::utils::Salt;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_store_credit_card_deduplicated() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace("fingerprint-test").unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = match vault.store_credit_card_deduplicated(&cc).await.unwrap() {
+            StoreOutcome::Created(token) => token,
+            outcome => panic!("{:?}", outcome),
+        };
+
+        // the same number under another name and expiry
+        cc.cardholder_name = "G. Hoare".to_string();
+        cc.expiration_year = "2027".to_string();
+        let outcome = vault.store_credit_card_deduplicated(&cc).await.unwrap();
+        assert_eq!(outcome, StoreOutcome::Duplicate(token.clone()));
+        assert_eq!(vault.store_credit_card(&cc).await.unwrap(), token);
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().expiration_year, "2023");
+
+        // other namespaces keep their own cards
+        let other = vault.with_namespace("fingerprint-test-other").unwrap();
+        let other_token = other.store_credit_card(&cc).await.unwrap();
+        assert_eq!(other.retrieve_credit_card(&other_token).await.unwrap().expiration_year, "2027");
+
+        // a deleted card is stored again
+        vault.delete_many(std::slice::from_ref(&token)).await.unwrap();
+        let outcome = vault.store_credit_card_deduplicated(&cc).await.unwrap();
+        assert!(!outcome.is_duplicate());
+        assert_ne!(outcome.token(), token);
+
+        // random tokenizers do not fingerprint cards
+        let random = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("fingerprint-test").unwrap();
+        let random_outcome = random.store_credit_card_deduplicated(&cc).await.unwrap();
+        assert!(!random_outcome.is_duplicate());
+
+        vault.delete_many(&[outcome.token().to_string(), random_outcome.token().to_string()]).await.unwrap();
+        other.delete_many(&[other_token]).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_update_indexes_normalized_number() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace(&format!("fingerprint-update-{}", Salt::generate(16))).unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = vault.store_credit_card(&cc).await.unwrap();
+
+        // a new number as an account updater may send it
+        cc.number = "5555 5555-5555 4444".to_string();
+        let (_, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        vault.update_credit_card_if_version(&token, &cc, version).await.unwrap();
+
+        cc.number = "5555555555554444".to_string();
+        let outcome = vault.store_credit_card_deduplicated(&cc).await.unwrap();
+        assert_eq!(outcome, StoreOutcome::Duplicate(token.clone()));
+
+        vault.delete_many(&[token]).await.unwrap();
+    }
+}
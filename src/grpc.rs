@@ -0,0 +1,289 @@
+//! A gRPC service over any `DataVault`, the contract is
+//! `proto/data_vault.proto`
+//!
+//! `service` wraps a vault for a `tonic` server, `mtls_config` makes
+//! the server ask every client for a certificate of your CA.  Errors
+//! carry the gRPC code of the `DataVaultError`, e.g. `NOT_FOUND`, and
+//! a message with card numbers and tokens masked, see `redact`.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use data_vault::{grpc, DataVault, RedisDataVault};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! # async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new()?;
+//! let tls = grpc::mtls_config(&std::fs::read("server.pem")?, &std::fs::read("server.key")?, &std::fs::read("clients-ca.pem")?);
+//! tonic::transport::Server::builder()
+//!     .tls_config(tls)?
+//!     .add_service(grpc::service(vault))
+//!     .serve("0.0.0.0:50051".parse()?)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#![allow(clippy::derive_partial_eq_without_eq)]
+
+use tonic::{Request, Response, Status};
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::redact::{redact, redact_token};
+use std::fmt;
+
+include!(concat!(env!("OUT_DIR"), "/data_vault.v1.DataVault.rs"));
+
+pub use data_vault_client::DataVaultClient;
+pub use data_vault_server::DataVaultServer;
+
+/// `credit_card::CreditCard` on the wire, `Debug` masks the number
+/// and leaves out the security code
+#[derive(Clone, PartialEq, prost::Message)]
+#[prost(skip_debug)]
+pub struct CreditCard {
+    #[prost(string, tag = "1")]
+    pub number: String,
+    #[prost(string, tag = "2")]
+    pub cardholder_name: String,
+    #[prost(string, tag = "3")]
+    pub expiration_month: String,
+    #[prost(string, tag = "4")]
+    pub expiration_year: String,
+    #[prost(string, optional, tag = "5")]
+    pub brand: Option<String>,
+    #[prost(string, optional, tag = "6")]
+    pub security_code: Option<String>,
+}
+
+impl fmt::Debug for CreditCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreditCard")
+            .field("number", &redact(&self.number))
+            .field("expiration_month", &self.expiration_month)
+            .field("expiration_year", &self.expiration_year)
+            .field("brand", &self.brand)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<credit_card::CreditCard> for CreditCard {
+    fn from(credit_card: credit_card::CreditCard) -> Self {
+        CreditCard {
+            number: credit_card.number,
+            cardholder_name: credit_card.cardholder_name,
+            expiration_month: credit_card.expiration_month,
+            expiration_year: credit_card.expiration_year,
+            brand: credit_card.brand,
+            security_code: credit_card.security_code,
+        }
+    }
+}
+
+impl From<CreditCard> for credit_card::CreditCard {
+    fn from(credit_card: CreditCard) -> Self {
+        credit_card::CreditCard {
+            number: credit_card.number,
+            cardholder_name: credit_card.cardholder_name,
+            expiration_month: credit_card.expiration_month,
+            expiration_year: credit_card.expiration_year,
+            brand: credit_card.brand,
+            security_code: credit_card.security_code,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TokenizeRequest {
+    #[prost(message, optional, tag = "1")]
+    pub credit_card: Option<CreditCard>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+#[prost(skip_debug)]
+pub struct TokenizeResponse {
+    #[prost(string, tag = "1")]
+    pub token: String,
+    /// false when the card was stored under this token already
+    #[prost(bool, tag = "2")]
+    pub created: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+#[prost(skip_debug)]
+pub struct DetokenizeRequest {
+    #[prost(string, tag = "1")]
+    pub token: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DetokenizeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub credit_card: Option<CreditCard>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+#[prost(skip_debug)]
+pub struct DeleteRequest {
+    #[prost(string, tag = "1")]
+    pub token: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DeleteResponse {
+    /// false when nothing was stored under the token
+    #[prost(bool, tag = "1")]
+    pub deleted: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+#[prost(skip_debug)]
+pub struct ExistsRequest {
+    #[prost(string, tag = "1")]
+    pub token: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ExistsResponse {
+    #[prost(bool, tag = "1")]
+    pub exists: bool,
+}
+
+// the messages holding a token show it redacted
+macro_rules! redacted_token_debug {
+    ($($message:ident),*) => {
+        $(impl fmt::Debug for $message {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($message))
+                    .field("token", &redact_token(&self.token))
+                    .finish_non_exhaustive()
+            }
+        })*
+    };
+}
+
+redacted_token_debug!(TokenizeResponse, DetokenizeRequest, DeleteRequest, ExistsRequest);
+
+/// The gRPC status of `e`, its message redacted
+fn status(e: DataVaultError) -> Status {
+    let message = redact(&e.to_string()).into_owned();
+    match e {
+        DataVaultError::NotFound => Status::not_found(message),
+        DataVaultError::InvalidToken | DataVaultError::Validation(_) => Status::invalid_argument(message),
+        DataVaultError::AlreadyExists => Status::already_exists(message),
+        DataVaultError::Conflict => Status::aborted(message),
+        DataVaultError::SecurityCodeNotAllowed => Status::failed_precondition(message),
+        DataVaultError::AccessDenied => Status::permission_denied(message),
+        DataVaultError::RateLimited(_) => Status::resource_exhausted(message),
+        DataVaultError::Unsupported(_) => Status::unimplemented(message),
+        e if e.is_transient() => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// The implementation of the generated `DataVault` service over a
+/// vault, see `service`
+pub struct DataVaultService<V> {
+    vault: V,
+}
+
+impl<V> DataVaultService<V>
+    where
+        V: DataVault,
+{
+    pub fn new(vault: V) -> Self {
+        DataVaultService { vault }
+    }
+
+    pub fn vault(&self) -> &V {
+        &self.vault
+    }
+}
+
+#[tonic::async_trait]
+impl<V> data_vault_server::DataVault for DataVaultService<V>
+    where
+        V: DataVault + 'static,
+{
+    async fn tokenize(&self, request: Request<TokenizeRequest>) -> Result<Response<TokenizeResponse>, Status> {
+        let credit_card = request.into_inner().credit_card
+            .ok_or_else(|| Status::invalid_argument("credit_card is required"))?;
+        let (token, created) = self.vault.tokenize(&credit_card.into()).await.map_err(status)?;
+        Ok(Response::new(TokenizeResponse { token, created }))
+    }
+
+    async fn detokenize(&self, request: Request<DetokenizeRequest>) -> Result<Response<DetokenizeResponse>, Status> {
+        let credit_card = self.vault.retrieve_credit_card(&request.into_inner().token).await.map_err(status)?;
+        Ok(Response::new(DetokenizeResponse { credit_card: Some(credit_card.into()) }))
+    }
+
+    async fn delete(&self, request: Request<DeleteRequest>) -> Result<Response<DeleteResponse>, Status> {
+        let deleted = self.vault.delete_many(&[request.into_inner().token]).await.map_err(status)?;
+        Ok(Response::new(DeleteResponse { deleted: deleted > 0 }))
+    }
+
+    async fn exists(&self, request: Request<ExistsRequest>) -> Result<Response<ExistsResponse>, Status> {
+        let exists = self.vault.exists(&request.into_inner().token).await.map_err(status)?;
+        Ok(Response::new(ExistsResponse { exists }))
+    }
+}
+
+/// `vault` as a service to add to a `tonic::transport::Server`
+pub fn service<V>(vault: V) -> DataVaultServer<DataVaultService<V>>
+    where
+        V: DataVault + 'static,
+{
+    DataVaultServer::new(DataVaultService::new(vault))
+}
+
+/// TLS with the PEM certificate chain `cert` and its key `key`,
+/// accepting only clients with a certificate issued by the PEM
+/// certificates `client_ca`
+pub fn mtls_config(cert: &[u8], key: &[u8], client_ca: &[u8]) -> ServerTlsConfig {
+    ServerTlsConfig::new()
+        .identity(Identity::from_pem(cert, key))
+        .client_ca_root(Certificate::from_pem(client_ca))
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use tonic::Code;
+    use crate::grpc::{self, CreditCard, DataVaultClient, DeleteRequest, DetokenizeRequest, ExistsRequest, TokenizeRequest};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_grpc_service() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        tokio::spawn(tonic::transport::Server::builder().add_service(grpc::service(vault)).serve(addr));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let mut client = DataVaultClient::connect(format!("http://{}", addr)).await.unwrap();
+
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+        assert!(!format!("{:?}", credit_card).contains("4111111111111111"));
+        let tokenized = client.tokenize(TokenizeRequest { credit_card: Some(credit_card.clone()) }).await.unwrap().into_inner();
+        let token = tokenized.token;
+        assert_eq!(client.tokenize(TokenizeRequest { credit_card: Some(credit_card.clone()) }).await.unwrap().into_inner().token, token);
+
+        let detokenized = client.detokenize(DetokenizeRequest { token: token.clone() }).await.unwrap().into_inner();
+        assert_eq!(detokenized.credit_card, Some(credit_card));
+        assert!(client.exists(ExistsRequest { token: token.clone() }).await.unwrap().into_inner().exists);
+
+        assert!(client.delete(DeleteRequest { token: token.clone() }).await.unwrap().into_inner().deleted);
+        assert!(!client.delete(DeleteRequest { token: token.clone() }).await.unwrap().into_inner().deleted);
+        let e = client.detokenize(DetokenizeRequest { token }).await.unwrap_err();
+        assert_eq!(e.code(), Code::NotFound);
+        assert_eq!(client.tokenize(TokenizeRequest { credit_card: None }).await.unwrap_err().code(), Code::InvalidArgument);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,92 @@
+#[cfg(any(test, feature = "redis", feature = "postgres"))]
+use crate::error::DataVaultError;
+#[cfg(any(test, feature = "redis", feature = "postgres"))]
+use std::time::Instant;
+use std::time::{Duration, SystemTime};
+
+/// The outcome of one check of `DataVault::health_check`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub healthy: bool,
+    /// how long the check took
+    pub latency: Duration,
+    /// why the check failed, never record data
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    /// `check` timed, failed if it returns an error
+    #[cfg(any(feature = "redis", feature = "postgres"))]
+    pub(crate) async fn of<F>(check: F) -> Self
+        where
+            F: std::future::Future<Output = Result<(), DataVaultError>>,
+    {
+        let started = Instant::now();
+        let result = check.await;
+        CheckResult {
+            healthy: result.is_ok(),
+            latency: started.elapsed(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+
+    /// a check that seals a probe and opens it again with the vault's
+    /// key material, failed unless the probe comes back unchanged
+    #[cfg(any(test, feature = "redis", feature = "postgres"))]
+    pub(crate) fn roundtrip<F>(roundtrip: F) -> Self
+        where
+            F: FnOnce(&[u8]) -> Result<Vec<u8>, DataVaultError>,
+    {
+        let started = Instant::now();
+        let probe = b"data_vault health check";
+        let error = match roundtrip(probe) {
+            Ok(opened) if opened == probe => None,
+            Ok(_) => Some("decrypted probe does not match".to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        CheckResult {
+            healthy: error.is_none(),
+            latency: started.elapsed(),
+            error,
+        }
+    }
+}
+
+/// What `DataVault::health_check` found, for readiness and liveness
+/// probes
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    /// see `DataVault::backend`
+    pub backend: &'static str,
+    /// a connection from the pool answered `PING` on redis or
+    /// `SELECT 1` on postgres
+    pub connection: CheckResult,
+    /// a probe was encrypted and decrypted with the vault's keys
+    pub encryption: CheckResult,
+    /// when the checks started
+    pub checked_at: SystemTime,
+}
+
+impl HealthReport {
+    /// whether every check passed
+    pub fn is_healthy(&self) -> bool {
+        self.connection.healthy && self.encryption.healthy
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::DataVaultError;
+    use crate::health::CheckResult;
+
+    #[test]
+    fn test_roundtrip() {
+        assert!(CheckResult::roundtrip(|probe| Ok(probe.to_vec())).healthy);
+
+        let garbled = CheckResult::roundtrip(|_| Ok(b"garbled".to_vec()));
+        assert!(!garbled.healthy);
+        assert_eq!(garbled.error.as_deref(), Some("decrypted probe does not match"));
+
+        assert!(!CheckResult::roundtrip(|_| Err(DataVaultError::NotFound)).healthy);
+    }
+}
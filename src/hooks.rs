@@ -0,0 +1,320 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::redact::redact_token;
+use std::error;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// The `DataVault` operation an event is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Operation {
+    Store,
+    StoreCreditCard,
+    Tokenize,
+    Retrieve,
+    RetrieveCreditCard,
+    Exists,
+    UpdateCreditCard,
+    RotateToken,
+    SoftDelete,
+    Touch,
+    DeleteMany,
+    PurgeExpired,
+    ReadRecords,
+    Count,
+    Stats,
+}
+
+/// How an operation ended, errors are reduced to their kind so
+/// no record data can leak into a hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Outcome {
+    Success,
+    NotFound,
+    Conflict,
+    Failure,
+}
+
+impl<R> From<&Result<R, DataVaultError>> for Outcome {
+    fn from(result: &Result<R, DataVaultError>) -> Self {
+        match result {
+            Ok(_) => Outcome::Success,
+            Err(DataVaultError::NotFound) => Outcome::NotFound,
+            Err(DataVaultError::Conflict)
+            | Err(DataVaultError::AlreadyExists) => Outcome::Conflict,
+            Err(_) => Outcome::Failure,
+        }
+    }
+}
+
+/// What `VaultHooks` are told about an operation, never the
+/// stored data itself, `Debug` only prints the ends of the token
+#[derive(Clone, PartialEq)]
+pub struct VaultEvent {
+    pub operation: Operation,
+    pub outcome: Outcome,
+    /// the token operated on, for `store_credit_card` and `tokenize`
+    /// the created token, for `rotate_token` the old token
+    pub token: Option<String>,
+    /// the namespace of the vault
+    pub tenant: String,
+    /// when the operation started
+    pub timestamp: SystemTime,
+    /// how long the operation took
+    pub duration: Duration,
+}
+
+impl fmt::Debug for VaultEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultEvent")
+            .field("operation", &self.operation)
+            .field("outcome", &self.outcome)
+            .field("token", &self.token.as_deref().map(redact_token))
+            .field("tenant", &self.tenant)
+            .field("timestamp", &self.timestamp)
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
+
+/// Receives an event after each operation of a vault registered
+/// with `DataVault::with_hooks`, for metrics, alerting or an audit
+/// sink of your own
+///
+/// Hooks are called on the task running the operation, slow work
+/// should be handed off to a channel.  Every method defaults to
+/// `on_event` so one method is enough to see everything.
+pub trait VaultHooks: Send + Sync {
+    /// every operation not covered by another method
+    fn on_event(&self, _event: &VaultEvent) {}
+
+    /// `store`, `store_credit_card`, `tokenize`,
+    /// `update_credit_card_if_version` and `rotate_token`
+    fn on_store(&self, event: &VaultEvent) {
+        self.on_event(event)
+    }
+
+    /// `retrieve`, `retrieve_credit_card`, their variants and `exists`
+    fn on_retrieve(&self, event: &VaultEvent) {
+        self.on_event(event)
+    }
+
+    /// `soft_delete`, `delete_many` and `purge_expired`
+    fn on_delete(&self, event: &VaultEvent) {
+        self.on_event(event)
+    }
+}
+
+struct NoHooks;
+
+impl VaultHooks for NoHooks {}
+
+/// A vault calling `VaultHooks` around each operation of the
+/// wrapped vault, see `DataVault::with_hooks`
+///
+/// `iter_records` and `iter_decrypted_records` are not reported
+/// because they are lazy, `decrypted_records_page` is.
+pub struct HookedDataVault<V> {
+    inner: V,
+    hooks: Arc<dyn VaultHooks>,
+}
+
+impl<V> HookedDataVault<V>
+    where
+        V: DataVault,
+{
+    pub(crate) fn new(inner: V, hooks: Arc<dyn VaultHooks>) -> Self {
+        HookedDataVault {
+            inner,
+            hooks,
+        }
+    }
+
+    /// The wrapped vault, operations on it are not reported
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    async fn hooked<F, R>(&self, operation: Operation, token: Option<&str>, f: F) -> Result<R, DataVaultError>
+        where
+            F: Future<Output = Result<R, DataVaultError>>,
+    {
+        let timestamp = SystemTime::now();
+        let started = Instant::now();
+        let result = f.await;
+        self.report(operation, token.map(str::to_string), timestamp, started, &result);
+        result
+    }
+
+    fn report<R>(&self, operation: Operation, token: Option<String>, timestamp: SystemTime, started: Instant, result: &Result<R, DataVaultError>) {
+        let event = VaultEvent {
+            operation,
+            outcome: result.into(),
+            token,
+            tenant: self.inner.namespace().to_string(),
+            timestamp,
+            duration: started.elapsed(),
+        };
+
+        match operation {
+            Operation::Store
+            | Operation::StoreCreditCard
+            | Operation::Tokenize
+            | Operation::UpdateCreditCard
+            | Operation::RotateToken
+            | Operation::Touch => self.hooks.on_store(&event),
+            Operation::Retrieve
+            | Operation::RetrieveCreditCard
+            | Operation::Exists => self.hooks.on_retrieve(&event),
+            Operation::SoftDelete
+            | Operation::DeleteMany
+            | Operation::PurgeExpired => self.hooks.on_delete(&event),
+            Operation::ReadRecords
+            | Operation::Count
+            | Operation::Stats => self.hooks.on_event(&event),
+        }
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for HookedDataVault<V>
+    where
+        V: DataVault,
+{
+    /// A vault without hooks, register them with `with_hooks`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(HookedDataVault::new(V::new()?, Arc::new(NoHooks)))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.hooked(Operation::Store, Some(token), self.inner.store(token, string)).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.hooked(Operation::Store, Some(token), self.inner.store_if_absent(token, string)).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.hooked(Operation::Store, Some(token), self.inner.store_expiring(token, string, ttl)).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        let timestamp = SystemTime::now();
+        let started = Instant::now();
+        let result = self.inner.store_credit_card(credit_card).await;
+        let token = result.as_ref().ok().cloned();
+        self.report(Operation::StoreCreditCard, token, timestamp, started, &result);
+        result
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        let timestamp = SystemTime::now();
+        let started = Instant::now();
+        let result = self.inner.tokenize(credit_card).await;
+        let token = result.as_ref().ok().map(|(token, _)| token.clone());
+        self.report(Operation::Tokenize, token, timestamp, started, &result);
+        result
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        let store = self.inner.store_credit_card_with_token(token, credit_card, overwrite);
+        self.hooked(Operation::StoreCreditCard, Some(token), store).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.hooked(Operation::Retrieve, Some(token), self.inner.retrieve(token)).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.hooked(Operation::RetrieveCreditCard, Some(token), self.inner.retrieve_credit_card(token)).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.hooked(Operation::Exists, Some(token), self.inner.exists(token)).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.hooked(Operation::RetrieveCreditCard, Some(token), self.inner.retrieve_credit_card_with_version(token)).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.hooked(Operation::RetrieveCreditCard, Some(token), self.inner.retrieve_with_metadata(token)).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let update = self.inner.update_credit_card_if_version(token, credit_card, expected_version);
+        self.hooked(Operation::UpdateCreditCard, Some(token), update).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.hooked(Operation::RotateToken, Some(token), self.inner.rotate_token(token)).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.hooked(Operation::SoftDelete, Some(token), self.inner.soft_delete(token)).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.hooked(Operation::Touch, Some(token), self.inner.touch(token, ttl)).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.hooked(Operation::DeleteMany, None, self.inner.delete_many(tokens)).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.hooked(Operation::PurgeExpired, None, self.inner.purge_expired()).await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.hooked(Operation::ReadRecords, None, self.inner.decrypted_records_page(cursor, limit)).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.hooked(Operation::Count, None, self.inner.count()).await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.hooked(Operation::Stats, None, self.inner.stats()).await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault reports to the same hooks
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(HookedDataVault::new(self.inner.with_namespace(namespace)?, self.hooks.clone()))
+    }
+
+    /// The side vault reports to no hooks, its operations are internal
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(HookedDataVault::new(self.inner.with_side_namespace(kind)?, Arc::new(NoHooks)))
+    }
+}
@@ -0,0 +1,405 @@
+//! Bulk import of credit cards from CSV or NDJSON files, e.g. a
+//! portfolio handed over by a legacy processor
+//!
+//! `import_cards` streams the rows of a file, validates each card,
+//! tokenizes the valid ones in concurrent batches and writes a
+//! mapping of row number to token, or to the reason a row was
+//! rejected, as CSV:
+//!
+//! ```text
+//! row,token,error
+//! 1,0f3a…,
+//! 2,,number is not 12 to 19 digits
+//! ```
+//!
+//! CSV files have a header naming the `CreditCard` fields, `brand`
+//! and `security_code` may be left out.  NDJSON files hold one card
+//! as a JSON object per line, empty lines are skipped.  Rows are
+//! counted from 1 without the CSV header, for NDJSON they are line
+//! numbers.  Reasons never contain card data.
+//!
+//! The mapping is flushed after every batch.  After a failure pass
+//! `resume_point` of the mapping written so far as
+//! `ImportOptions::skip_rows` and append to it, rows of the batch
+//! that failed are imported again.  With a deterministic tokenizer
+//! that stores nothing twice, with a random one those cards get a
+//! second token.
+//!
+//! # example
+//! ```rust,ignore
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::import::{import_cards, ImportFormat, ImportOptions};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3DeterministicTokenizer;
+//! use std::fs::File;
+//!
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+//! let options = ImportOptions::new(ImportFormat::Csv);
+//! let report = import_cards(&vault, File::open("portfolio.csv")?, File::create("portfolio.tokens.csv")?, options).await?;
+//! println!("{} imported, {} rejected", report.imported, report.rejected);
+//! ```
+
+use credit_card::CreditCard;
+use futures::future::join_all;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Called with the progress of an import after each batch
+pub type ImportProgressCallback<'a> = Box<dyn FnMut(&ImportProgress) + Send + 'a>;
+
+/// How the file `import_cards` reads is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ImportFormat {
+    /// comma separated values with a header
+    Csv,
+    /// one JSON object per line
+    Ndjson,
+}
+
+/// How `import_cards` runs
+pub struct ImportOptions<'a> {
+    pub format: ImportFormat,
+    /// cards tokenized concurrently, and rows per mapping flush
+    pub batch_size: usize,
+    /// rows up to this one were imported by an earlier run, see
+    /// `resume_point`, no mapping header is written then
+    pub skip_rows: u64,
+    /// called after each batch is written to the mapping
+    pub on_progress: Option<ImportProgressCallback<'a>>,
+}
+
+impl ImportOptions<'_> {
+    pub fn new(format: ImportFormat) -> Self {
+        ImportOptions {
+            format,
+            batch_size: DEFAULT_BATCH_SIZE,
+            skip_rows: 0,
+            on_progress: None,
+        }
+    }
+}
+
+/// Handed to `ImportOptions::on_progress` after each batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportProgress {
+    /// the last row in the mapping
+    pub row: u64,
+    /// cards stored by this run so far
+    pub imported: u64,
+    /// rows rejected by this run so far
+    pub rejected: u64,
+}
+
+/// The outcome of `import_cards`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// cards tokenized by this run, including cards stored before
+    pub imported: u64,
+    /// of those, cards the vault held already, see `DataVault::tokenize`
+    pub existing: u64,
+    /// rows written to the mapping with a reason instead of a token
+    pub rejected: u64,
+    /// rows skipped for `ImportOptions::skip_rows`
+    pub skipped: u64,
+}
+
+/// Tokenize every card of `input` into `vault`, writing the mapping
+/// of rows to tokens to `mapping`
+///
+/// Invalid rows and cards the vault refuses, e.g. a security code
+/// under a policy that forbids storing it, are rejected in the
+/// mapping.  Other errors of the vault, e.g. a lost connection, end
+/// the import after flushing the batches before.
+pub async fn import_cards<V, R, W>(vault: &V, input: R, mapping: W, mut options: ImportOptions<'_>) -> Result<ImportReport, DataVaultError>
+    where
+        V: DataVault,
+        R: Read,
+        W: Write,
+{
+    let mut rows = Rows::new(input, options.format)?;
+    let mut writer = csv::Writer::from_writer(mapping);
+    if options.skip_rows == 0 {
+        writer.write_record(["row", "token", "error"]).map_err(csv_error)?;
+    }
+    let batch_size = options.batch_size.max(1);
+    let mut report = ImportReport::default();
+    let mut last_row = options.skip_rows;
+
+    loop {
+        let mut batch = Vec::with_capacity(batch_size);
+        while batch.len() < batch_size {
+            match rows.next()? {
+                Some((row, _)) if row <= options.skip_rows => report.skipped += 1,
+                Some(parsed) => batch.push(parsed),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            break
+        }
+
+        let results = join_all(batch.iter().map(|(_, card)| async move {
+            match card {
+                Ok(card) => Some(vault.tokenize(card).await),
+                Err(_) => None,
+            }
+        })).await;
+
+        for ((row, card), result) in batch.iter().zip(results) {
+            let row = row.to_string();
+            match (card, result) {
+                (Ok(_), Some(Ok((token, created)))) => {
+                    report.imported += 1;
+                    report.existing += u64::from(!created);
+                    writer.write_record([row.as_str(), token.as_str(), ""])
+                },
+                (Err(reason), _) => {
+                    report.rejected += 1;
+                    writer.write_record([row.as_str(), "", reason.as_str()])
+                },
+                (Ok(_), Some(Err(e))) if is_rejection(&e) => {
+                    report.rejected += 1;
+                    writer.write_record([row.as_str(), "", e.to_string().as_str()])
+                },
+                (Ok(_), Some(Err(e))) => {
+                    // the rows before stay resumable
+                    writer.flush()?;
+                    return Err(e)
+                },
+                (Ok(_), None) => unreachable!("valid cards are tokenized"),
+            }.map_err(csv_error)?;
+        }
+        writer.flush()?;
+
+        last_row = batch.last().map_or(last_row, |(row, _)| *row);
+        if let Some(on_progress) = options.on_progress.as_mut() {
+            on_progress(&ImportProgress {
+                row: last_row,
+                imported: report.imported,
+                rejected: report.rejected,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// The last row of a mapping `import_cards` wrote, 0 for a mapping
+/// without rows, pass it as `ImportOptions::skip_rows` to continue
+pub fn resume_point<R: Read>(mapping: R) -> Result<u64, DataVaultError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(mapping);
+    let mut last_row = 0;
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        last_row = record.get(0)
+            .and_then(|row| row.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "the mapping has a row that is not numbered"))?;
+    }
+    Ok(last_row)
+}
+
+/// Whether `e` refuses this card rather than failing the vault
+pub(crate) fn is_rejection(e: &DataVaultError) -> bool {
+    matches!(e, DataVaultError::SecurityCodeNotAllowed | DataVaultError::Validation(_) | DataVaultError::InvalidToken | DataVaultError::AccessDenied)
+}
+
+/// The reason `credit_card` can not be imported
+fn validate(credit_card: &CreditCard) -> Result<(), &'static str> {
+    validate_number(credit_card)?;
+    if credit_card.cardholder_name.trim().is_empty() {
+        return Err("cardholder_name is empty")
+    }
+    validate_expiry(credit_card)
+}
+
+/// `validate` without the cardholder name, which processor exports
+/// often leave empty
+#[cfg(feature = "interop")]
+pub(crate) fn validate_number_and_expiry(credit_card: &CreditCard) -> Result<(), &'static str> {
+    validate_number(credit_card)?;
+    validate_expiry(credit_card)
+}
+
+fn validate_number(credit_card: &CreditCard) -> Result<(), &'static str> {
+    let number = &credit_card.number;
+    if !(12..=19).contains(&number.len()) || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("number is not 12 to 19 digits")
+    }
+    Ok(())
+}
+
+fn validate_expiry(credit_card: &CreditCard) -> Result<(), &'static str> {
+    match credit_card.expiration_month.parse::<u8>() {
+        Ok(1..=12) => {},
+        _ => return Err("expiration_month is not 1 to 12"),
+    }
+    let year = &credit_card.expiration_year;
+    if !(year.len() == 2 || year.len() == 4) || !year.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("expiration_year is not 2 or 4 digits")
+    }
+    Ok(())
+}
+
+/// A card, or why a row is not one, without any of its values
+type ParsedRow = (u64, Result<CreditCard, String>);
+
+enum Rows<R: Read> {
+    Csv {
+        reader: csv::Reader<R>,
+        headers: csv::ByteRecord,
+        record: csv::ByteRecord,
+        row: u64,
+    },
+    Ndjson {
+        lines: io::Lines<BufReader<R>>,
+        row: u64,
+    },
+}
+
+impl<R: Read> Rows<R> {
+    fn new(input: R, format: ImportFormat) -> Result<Self, DataVaultError> {
+        Ok(match format {
+            ImportFormat::Csv => {
+                let mut reader = csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(input);
+                let headers = reader.byte_headers().map_err(csv_error)?.clone();
+                Rows::Csv { reader, headers, record: csv::ByteRecord::new(), row: 0 }
+            },
+            ImportFormat::Ndjson => Rows::Ndjson { lines: BufReader::new(input).lines(), row: 0 },
+        })
+    }
+
+    fn next(&mut self) -> Result<Option<ParsedRow>, DataVaultError> {
+        let (row, card) = match self {
+            Rows::Csv { reader, headers, record, row } => {
+                match reader.read_byte_record(record) {
+                    Ok(true) => {},
+                    Ok(false) => return Ok(None),
+                    // a row that is not UTF-8 or not CSV at all
+                    Err(e) if !matches!(e.kind(), csv::ErrorKind::Io(_)) => {
+                        *row += 1;
+                        return Ok(Some((*row, Err("row is not valid CSV".to_string()))))
+                    },
+                    Err(e) => return Err(csv_error(e)),
+                }
+                *row += 1;
+                // every field is a string, only missing ones fail
+                let card = record.deserialize::<CreditCard>(Some(headers))
+                    .map_err(|e| match e.kind() {
+                        csv::ErrorKind::Deserialize { err, .. } => match err.kind() {
+                            csv::DeserializeErrorKind::Message(message) if message.starts_with("missing field ") => message.clone(),
+                            csv::DeserializeErrorKind::InvalidUtf8(_) => "row is not UTF-8".to_string(),
+                            // a short row, named by the header of its first missing field
+                            csv::DeserializeErrorKind::UnexpectedEndOfRow => match headers.get(record.len()) {
+                                Some(field) => format!("missing field `{}`", String::from_utf8_lossy(field)),
+                                None => "row is not a credit card".to_string(),
+                            },
+                            _ => "row is not a credit card".to_string(),
+                        },
+                        _ => "row is not a credit card".to_string(),
+                    });
+                (*row, card)
+            },
+            Rows::Ndjson { lines, row } => loop {
+                let line = match lines.next() {
+                    Some(line) => line?,
+                    None => return Ok(None),
+                };
+                *row += 1;
+                if line.trim().is_empty() {
+                    continue
+                }
+                let card = match serde_json::from_str(&line) {
+                    Ok(object @ serde_json::Value::Object(_)) => serde_json::from_value::<CreditCard>(object).map_err(|e| json_reason(&e)),
+                    _ => Err("line is not a JSON object".to_string()),
+                };
+                break (*row, card)
+            },
+        };
+        Ok(Some((row, card.and_then(|card| validate(&card).map(|_| card).map_err(str::to_string)))))
+    }
+}
+
+/// The reason of an object that is not a card, serde_json quotes
+/// mistyped values in its messages so only missing fields are named
+fn json_reason(e: &serde_json::Error) -> String {
+    match e.to_string().strip_prefix("missing field ") {
+        Some(field) => format!("missing field {}", field.split_whitespace().next().unwrap_or_default()),
+        None => "a field is not a string".to_string(),
+    }
+}
+
+pub(crate) fn csv_error(e: csv::Error) -> DataVaultError {
+    DataVaultError::Io(e.into())
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use crate::import::{import_cards, resume_point, ImportFormat, ImportOptions};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_import_csv() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace("import-csv-test").unwrap();
+        let csv = "number,cardholder_name,expiration_month,expiration_year\n\
+            4111111111111111,Graydon Hoare,01,2023\n\
+            4111-1111-1111-1112,Graydon Hoare,01,2023\n\
+            5555555555554444,Graydon Hoare,13,2023\n\
+            378282246310005,Graydon Hoare\n\
+            5105105105105100,Graydon Hoare,12,25\n";
+
+        let mut mapping = Vec::new();
+        let mut options = ImportOptions::new(ImportFormat::Csv);
+        options.batch_size = 2;
+        let report = import_cards(&vault, csv.as_bytes(), &mut mapping, options).await.unwrap();
+        assert_eq!((report.imported, report.rejected), (2, 3));
+        let mapping = String::from_utf8(mapping).unwrap();
+        let lines: Vec<&str> = mapping.lines().collect();
+        assert_eq!(lines[0], "row,token,error");
+        assert!(lines[1].starts_with("1,") && lines[1].ends_with(','));
+        assert_eq!(lines[2], "2,,number is not 12 to 19 digits");
+        assert_eq!(lines[3], "3,,expiration_month is not 1 to 12");
+        assert_eq!(lines[4], "4,,missing field `expiration_month`");
+        assert!(!mapping.contains("4111111111111111") && !mapping.contains("4111-1111"));
+        assert_eq!(resume_point(mapping.as_bytes()).unwrap(), 5);
+
+        // a run resumed after row 3 appends rows 4 and 5
+        let mut resumed = Vec::new();
+        let mut options = ImportOptions::new(ImportFormat::Csv);
+        options.skip_rows = 3;
+        let report = import_cards(&vault, csv.as_bytes(), &mut resumed, options).await.unwrap();
+        assert_eq!((report.imported, report.existing, report.rejected, report.skipped), (1, 1, 1, 3));
+        assert_eq!(String::from_utf8(resumed).unwrap(), lines[4..].join("\n") + "\n");
+
+        let tokens: Vec<String> = lines.iter().filter_map(|line| line.split(',').nth(1)).filter(|token| token.len() > 5).map(str::to_string).collect();
+        assert_eq!(vault.delete_many(&tokens).await.unwrap(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_import_ndjson() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace("import-ndjson-test").unwrap();
+        let ndjson = r#"{"number": "4111111111111111", "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023"}
+
+{"number": 4111111111111111, "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023"}
+4111111111111111
+"#;
+        let mut mapping = Vec::new();
+        let report = import_cards(&vault, ndjson.as_bytes(), &mut mapping, ImportOptions::new(ImportFormat::Ndjson)).await.unwrap();
+        assert_eq!((report.imported, report.rejected), (1, 2));
+        let mapping = String::from_utf8(mapping).unwrap();
+        assert!(mapping.contains("\n3,,a field is not a string\n4,,line is not a JSON object\n"));
+        assert!(!mapping.contains("4111111111111111"));
+
+        let token = mapping.lines().nth(1).unwrap().split(',').nth(1).unwrap().to_string();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, "4111111111111111");
+        vault.delete_many(&[token]).await.unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,84 @@
+use crate::redact::redact_token;
+use std::fmt;
+
+/// How an index and the records it points to disagree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityProblem {
+    /// the index lists the token but its record is gone
+    Dangling,
+    /// the record is missing from the index, lookups and counts
+    /// going through the index do not see it
+    Unindexed,
+    /// the index entry does not match the record, postgres only
+    Stale,
+}
+
+/// An index entry or record `integrity_report` found out of step
+#[derive(Clone, PartialEq)]
+pub struct IntegrityIssue {
+    pub token: String,
+    /// the index, e.g. `data_vault:expiring` in redis or the
+    /// `number` column in postgres
+    pub index: &'static str,
+    pub problem: IntegrityProblem,
+}
+
+impl fmt::Debug for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntegrityIssue")
+            .field("token", &redact_token(&self.token))
+            .field("index", &self.index)
+            .field("problem", &self.problem)
+            .finish()
+    }
+}
+
+/// The outcome of `integrity_report`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// records and index entries checked
+    pub checked: u64,
+    /// the entries out of step, in the order they were checked
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// Whether the indexes and the records agree
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// The issues of `problem`
+    pub fn issues_of(&self, problem: IntegrityProblem) -> impl Iterator<Item = &IntegrityIssue> {
+        self.issues.iter().filter(move |issue| issue.problem == problem)
+    }
+
+    pub(crate) fn check(&mut self, token: &str, index: &'static str, problem: Option<IntegrityProblem>) {
+        self.checked += 1;
+        if let Some(problem) = problem {
+            self.issues.push(IntegrityIssue {
+                token: token.to_string(),
+                index,
+                problem,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::integrity::{IntegrityProblem, IntegrityReport};
+
+    #[test]
+    fn test_integrity_report() {
+        let mut report = IntegrityReport::default();
+        report.check("abc1", "data_vault:index", None);
+        assert!(report.is_ok());
+        report.check("5f4dcc3b5aa765d61d8327deb882cf99", "data_vault:index", Some(IntegrityProblem::Dangling));
+        report.check("abc2", "number", Some(IntegrityProblem::Stale));
+        assert_eq!(report.checked, 3);
+        assert!(!report.is_ok());
+        assert_eq!(report.issues_of(IntegrityProblem::Stale).map(|issue| issue.token.as_str()).collect::<Vec<&str>>(), vec!["abc2"]);
+        assert!(format!("{:?}", report).contains("5f4d****cf99"));
+    }
+}
@@ -0,0 +1,7 @@
+//! Interoperability with other vaults and payment processors, e.g.
+//! the card exports handed over when moving a portfolio to this vault
+//!
+//! `import` loads the PGP encrypted PAN exports of Stripe, Braintree
+//! and Adyen, see `import_provider_export`.
+
+pub mod import;
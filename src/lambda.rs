@@ -0,0 +1,139 @@
+//! Running the vault in AWS Lambda
+//!
+//! `vault` builds the vault on the first invocation of an execution
+//! environment and hands the same one to every later invocation, so
+//! connections survive between invocations instead of being opened
+//! on each.  Pools default to `LAMBDA_POOL_MAX_SIZE` connections, an
+//! environment runs one invocation at a time.  `shutdown_on_sigterm`
+//! closes the connections when Lambda shuts the environment down.
+//!
+//! The backend is `DATA_VAULT_LAMBDA_BACKEND`, or redis when
+//! `REDIS_URL` is set and postgres otherwise, configured by the same
+//! variables as `RedisDataVault::new` and `PostgresDataVault::new`,
+//! see `LambdaConfig`.  Records are sealed with `AesGcmSivEncryption`.
+//!
+//! See `examples/lambda.rs` for a function using `lambda_runtime`.
+
+use crate::traits::DataVaultDyn;
+use crate::config::LambdaConfig;
+use crate::encryption::AesGcmSivEncryption;
+use crate::tokenizer::{Blake3DeterministicTokenizer, Blake3Tokenizer};
+#[cfg(feature = "redis")]
+use crate::config::RedisVaultConfig;
+#[cfg(feature = "redis")]
+use crate::redis_data_vault::RedisDataVault;
+#[cfg(feature = "postgres")]
+use crate::config::PostgresVaultConfig;
+#[cfg(feature = "postgres")]
+use crate::postgres_data_vault::PostgresDataVault;
+use std::env;
+use std::error;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Connections per pool unless configured otherwise
+pub const LAMBDA_POOL_MAX_SIZE: usize = 2;
+
+/// The error type of `lambda_runtime` handlers
+pub type LambdaError = Box<dyn error::Error + Send + Sync>;
+
+// shared by the invocations of this execution environment
+static VAULT: Mutex<Option<Arc<dyn DataVaultDyn>>> = Mutex::new(None);
+
+/// The vault of this execution environment, built from the
+/// environment by the first call
+///
+/// A failed build is not kept, the next invocation tries again.
+/// # example
+/// ```rust,ignore
+/// async fn handler(event: LambdaEvent<Value>) -> Result<Value, LambdaError> {
+///     let vault = data_vault::lambda::vault()?;
+///     let credit_card = vault.retrieve_credit_card(event.payload["token"].as_str().unwrap_or_default()).await?;
+///     Ok(json!({ "last4": &credit_card.number[credit_card.number.len() - 4..] }))
+/// }
+/// ```
+pub fn vault() -> Result<Arc<dyn DataVaultDyn>, LambdaError> {
+    let mut shared = VAULT.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(vault) = &*shared {
+        return Ok(vault.clone())
+    }
+    let vault = from_env().map_err(|e| e.to_string())?;
+    *shared = Some(vault.clone());
+    Ok(vault)
+}
+
+/// Wait for SIGTERM, drop the shared vault and exit
+///
+/// Lambda sends SIGTERM before shutting down an execution
+/// environment with extensions, spawn this at startup so the pool's
+/// connections are closed instead of left to time out on the server.
+pub async fn shutdown_on_sigterm() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler");
+        terminate.recv().await;
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    // the last `Arc` closes the pool, none is left after an invocation
+    let vault = VAULT.lock().unwrap_or_else(PoisonError::into_inner).take();
+    drop(vault);
+    std::process::exit(0);
+}
+
+fn from_env() -> Result<Arc<dyn DataVaultDyn>, Box<dyn error::Error>> {
+    let cfg = LambdaConfig::from_env()?;
+    let pool_max_size = cfg.pool_max_size.unwrap_or(LAMBDA_POOL_MAX_SIZE);
+    let deterministic = match cfg.tokenizer.as_deref() {
+        None | Some("random") => false,
+        Some("deterministic") => true,
+        Some(other) => return Err(format!("DATA_VAULT_LAMBDA_TOKENIZER {:?} is not random or deterministic", other).into()),
+    };
+    let backend = match cfg.backend.as_deref() {
+        Some(backend) => backend,
+        None if cfg!(feature = "redis") && (env::var_os("REDIS_URL").is_some() || env::var_os("REDIS_URL_FILE").is_some()) => "redis",
+        None => "postgres",
+    };
+
+    match backend {
+        #[cfg(feature = "redis")]
+        "redis" => {
+            let mut redis = RedisVaultConfig::from_env()?;
+            if redis.redis.pool.is_none() {
+                redis = redis.with_pool_max_size(pool_max_size);
+            }
+            Ok(match deterministic {
+                true => Arc::new(RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::from_config(redis)?),
+                false => Arc::new(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(redis)?),
+            })
+        },
+        #[cfg(feature = "postgres")]
+        "postgres" => {
+            let mut postgres = PostgresVaultConfig::from_env()?;
+            if postgres.postgres.pool.is_none() {
+                postgres = postgres.with_pool_max_size(pool_max_size);
+            }
+            Ok(match deterministic {
+                true => Arc::new(PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::from_config(postgres)?),
+                false => Arc::new(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(postgres)?),
+            })
+        },
+        other => Err(format!("DATA_VAULT_LAMBDA_BACKEND {:?} is not a backend of this build", other).into()),
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use crate::lambda;
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shared_vault() {
+        let vault = lambda::vault().unwrap();
+        assert_eq!(vault.backend(), "redis");
+        assert!(Arc::ptr_eq(&vault, &lambda::vault().unwrap()));
+        assert!(vault.health_check().await.is_healthy());
+    }
+}
\ No newline at end of file
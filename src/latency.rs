@@ -0,0 +1,370 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[cfg(feature = "log")]
+use std::cell::RefCell;
+#[cfg(feature = "log")]
+use std::pin::Pin;
+#[cfg(feature = "log")]
+use std::task::{Context, Poll};
+#[cfg(feature = "log")]
+use crate::redact::redact_token;
+
+/// A part of an operation whose latency is recorded, see
+/// `latency_report`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LatencyStage {
+    /// the round trip writing a record to the back end
+    Store,
+    /// the round trip reading a record from the back end
+    Retrieve,
+    /// compressing and encrypting a record
+    Encrypt,
+    /// decrypting and decompressing a record
+    Decrypt,
+    /// turning a credit card into a record, see `Serializer`
+    Serialize,
+    /// turning a record back into a credit card
+    Deserialize,
+}
+
+impl LatencyStage {
+    #[cfg(feature = "log")]
+    fn name(&self) -> &'static str {
+        match self {
+            LatencyStage::Store => "store",
+            LatencyStage::Retrieve => "retrieve",
+            LatencyStage::Encrypt => "encrypt",
+            LatencyStage::Decrypt => "decrypt",
+            LatencyStage::Serialize => "serialize",
+            LatencyStage::Deserialize => "deserialize",
+        }
+    }
+
+    const ALL: [LatencyStage; 6] = [
+        LatencyStage::Store,
+        LatencyStage::Retrieve,
+        LatencyStage::Encrypt,
+        LatencyStage::Decrypt,
+        LatencyStage::Serialize,
+        LatencyStage::Deserialize,
+    ];
+}
+
+/// The latencies of one stage since the vault was created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageLatency {
+    pub stage: LatencyStage,
+    /// how many times the stage ran
+    pub count: u64,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Where the time of a vault's operations goes, one entry per
+/// `LatencyStage`, from `latency_report`
+///
+/// Percentiles are the upper bounds of the histogram buckets they
+/// fall in, at most a fifth above the exact value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyReport {
+    pub stages: Vec<StageLatency>,
+}
+
+impl LatencyReport {
+    /// the latencies of `stage`
+    pub fn stage(&self, stage: LatencyStage) -> &StageLatency {
+        self.stages.iter()
+            .find(|latency| latency.stage == stage)
+            .expect("a report has every stage")
+    }
+}
+
+// four buckets per power of two nanoseconds, the largest catches
+// everything above about 18 minutes
+const BUCKETS_PER_DOUBLING: f64 = 4.0;
+const BUCKETS: usize = 160;
+
+/// A histogram of durations with lock free recording
+struct Histogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        let bucket = ((nanos.max(1) as f64).log2() * BUCKETS_PER_DOUBLING) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// the upper bound of the bucket holding the `quantile`
+    fn quantile(&self, counts: &[u64], total: u64, quantile: f64) -> Duration {
+        let rank = ((total as f64 * quantile).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = 2f64.powf((bucket + 1) as f64 / BUCKETS_PER_DOUBLING);
+                return Duration::from_nanos(upper as u64).min(self.max())
+            }
+        }
+        self.max()
+    }
+
+    fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed))
+    }
+
+    fn latency(&self, stage: LatencyStage) -> StageLatency {
+        let counts: Vec<u64> = self.buckets.iter().map(|count| count.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return StageLatency {
+                stage,
+                count: 0,
+                mean: Duration::ZERO,
+                p50: Duration::ZERO,
+                p90: Duration::ZERO,
+                p99: Duration::ZERO,
+                max: Duration::ZERO,
+            }
+        }
+
+        StageLatency {
+            stage,
+            count: self.count.load(Ordering::Relaxed),
+            mean: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed) / self.count.load(Ordering::Relaxed).max(1)),
+            p50: self.quantile(&counts, total, 0.5),
+            p90: self.quantile(&counts, total, 0.9),
+            p99: self.quantile(&counts, total, 0.99),
+            max: self.max(),
+        }
+    }
+}
+
+/// The latency histograms of a vault, shared by its namespaces
+#[derive(Clone)]
+pub(crate) struct LatencyRecorder(Arc<[Histogram; 6]>);
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        LatencyRecorder(Arc::new([
+            Histogram::new(), Histogram::new(), Histogram::new(),
+            Histogram::new(), Histogram::new(), Histogram::new(),
+        ]))
+    }
+}
+
+impl LatencyRecorder {
+    fn histogram(&self, stage: LatencyStage) -> &Histogram {
+        &self.0[stage as usize]
+    }
+
+    fn record(&self, stage: LatencyStage, duration: Duration) {
+        self.histogram(stage).record(duration);
+        #[cfg(feature = "log")]
+        Breakdown::add(stage, duration);
+    }
+
+    /// `f` timed as `stage`
+    pub(crate) fn time<R>(&self, stage: LatencyStage, f: impl FnOnce() -> R) -> R {
+        let started = Instant::now();
+        let result = f();
+        self.record(stage, started.elapsed());
+        result
+    }
+
+    /// `future` timed as `stage`
+    pub(crate) async fn time_async<R>(&self, stage: LatencyStage, future: impl Future<Output = R>) -> R {
+        let started = Instant::now();
+        let result = future.await;
+        self.record(stage, started.elapsed());
+        result
+    }
+
+    pub(crate) fn report(&self) -> LatencyReport {
+        LatencyReport {
+            stages: LatencyStage::ALL.iter().map(|stage| self.histogram(*stage).latency(*stage)).collect(),
+        }
+    }
+}
+
+/// `future`, the `operation` of a `backend` vault on `token`,
+/// logged at warn level with the time of each stage if it takes
+/// longer than `threshold`, just awaited without the `log` feature
+#[cfg_attr(not(feature = "log"), allow(unused_variables))]
+pub(crate) async fn log_if_slow<R>(
+    threshold: Option<Duration>,
+    backend: &'static str,
+    operation: &'static str,
+    token: Option<&str>,
+    future: impl Future<Output = R>,
+) -> R {
+    #[cfg(feature = "log")]
+    if let Some(threshold) = threshold {
+        let breakdown = Arc::new(Breakdown::default());
+        let started = Instant::now();
+        let result = Scoped { future: Box::pin(future), breakdown: breakdown.clone() }.await;
+        let elapsed = started.elapsed();
+        if elapsed > threshold {
+            log::warn!(target: "data_vault::slow", "{}", breakdown.describe(backend, operation, token, elapsed));
+        }
+        return result
+    }
+    future.await
+}
+
+#[cfg(feature = "log")]
+thread_local! {
+    // the breakdowns of the operations being polled on this thread,
+    // innermost last
+    static BREAKDOWNS: RefCell<Vec<Arc<Breakdown>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The time one operation spent in each stage
+#[cfg(feature = "log")]
+#[derive(Default)]
+struct Breakdown([AtomicU64; 6]);
+
+#[cfg(feature = "log")]
+impl Breakdown {
+    /// add `duration` to `stage` of the operations being polled
+    fn add(stage: LatencyStage, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        BREAKDOWNS.with(|breakdowns| {
+            for breakdown in breakdowns.borrow().iter() {
+                breakdown.0[stage as usize].fetch_add(nanos, Ordering::Relaxed);
+            }
+        });
+    }
+
+    /// e.g. `slow postgres retrieve_credit_card of d3b0****ff00 took
+    /// 73.1 ms: retrieve 61.0 ms, decrypt 0.1 ms, deserialize 0.0 ms,
+    /// other 12.0 ms`, other is mostly waiting for a connection
+    fn describe(&self, backend: &str, operation: &str, token: Option<&str>, elapsed: Duration) -> String {
+        let mut described = format!("slow {} {}", backend, operation);
+        if let Some(token) = token {
+            described.push_str(&format!(" of {}", redact_token(token)));
+        }
+        described.push_str(&format!(" took {:.1} ms:", elapsed.as_secs_f64() * 1000.0));
+
+        let mut staged = Duration::ZERO;
+        for stage in LatencyStage::ALL.iter() {
+            let nanos = self.0[*stage as usize].load(Ordering::Relaxed);
+            if nanos > 0 {
+                staged += Duration::from_nanos(nanos);
+                described.push_str(&format!(" {} {:.1} ms,", stage.name(), nanos as f64 / 1_000_000.0));
+            }
+        }
+        let other = elapsed.checked_sub(staged).unwrap_or_default();
+        described.push_str(&format!(" other {:.1} ms", other.as_secs_f64() * 1000.0));
+        described
+    }
+}
+
+/// A future with its `breakdown` collecting the stages timed while
+/// it is polled
+#[cfg(feature = "log")]
+struct Scoped<F> {
+    future: Pin<Box<F>>,
+    breakdown: Arc<Breakdown>,
+}
+
+#[cfg(feature = "log")]
+impl<F> Future for Scoped<F>
+    where
+        F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // popped again even if the future panics
+        struct Popped;
+
+        impl Drop for Popped {
+            fn drop(&mut self) {
+                BREAKDOWNS.with(|breakdowns| breakdowns.borrow_mut().pop());
+            }
+        }
+
+        BREAKDOWNS.with(|breakdowns| breakdowns.borrow_mut().push(self.breakdown.clone()));
+        let _popped = Popped;
+        self.future.as_mut().poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::latency::{LatencyRecorder, LatencyStage};
+    use std::time::Duration;
+
+    #[cfg(all(feature = "log", feature = "rt-tokio"))]
+    #[tokio::test]
+    async fn test_breakdown() {
+        use crate::latency::{Breakdown, Scoped};
+        use std::sync::Arc;
+
+        let recorder = LatencyRecorder::default();
+        let breakdown = Arc::new(Breakdown::default());
+        Scoped {
+            future: Box::pin(async {
+                recorder.time(LatencyStage::Encrypt, || std::thread::sleep(Duration::from_millis(2)));
+                recorder.time_async(LatencyStage::Store, tokio::time::sleep(Duration::from_millis(5))).await;
+            }),
+            breakdown: breakdown.clone(),
+        }.await;
+        // outside of the operation
+        recorder.time(LatencyStage::Decrypt, || ());
+
+        let described = breakdown.describe("redis", "store", Some("d3b07384d113edec49eaa6238ad5ff00"), Duration::from_millis(20));
+        assert!(described.starts_with("slow redis store of d3b0****ff00 took 20.0 ms: store "), "{}", described);
+        assert!(described.contains(" encrypt ") && described.contains(" other "), "{}", described);
+        assert!(!described.contains("decrypt"), "{}", described);
+        assert!(breakdown.0[LatencyStage::Store as usize].load(std::sync::atomic::Ordering::Relaxed) >= 5_000_000);
+        assert_eq!(recorder.report().stage(LatencyStage::Decrypt).count, 1);
+    }
+
+    #[test]
+    fn test_latency_report() {
+        let recorder = LatencyRecorder::default();
+        let histogram = recorder.histogram(LatencyStage::Decrypt);
+        for micros in 1..=100 {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(recorder.time(LatencyStage::Encrypt, || 42), 42);
+
+        let report = recorder.clone().report();
+        assert_eq!(report.stages.len(), 6);
+        assert_eq!(report.stage(LatencyStage::Encrypt).count, 1);
+        assert_eq!(report.stage(LatencyStage::Store).count, 0);
+        assert_eq!(report.stage(LatencyStage::Store).p99, Duration::ZERO);
+
+        let decrypt = report.stage(LatencyStage::Decrypt);
+        assert_eq!(decrypt.count, 100);
+        assert_eq!(decrypt.max, Duration::from_micros(100));
+        assert!(decrypt.mean > Duration::from_micros(50) && decrypt.mean < Duration::from_micros(51));
+        for (quantile, exact) in &[(decrypt.p50, 50), (decrypt.p90, 90), (decrypt.p99, 99)] {
+            let exact = Duration::from_micros(*exact);
+            assert!(*quantile >= exact && *quantile <= exact.mul_f64(1.2), "{:?} for {:?}", quantile, exact);
+        }
+    }
+}
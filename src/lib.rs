@@ -12,6 +12,7 @@
 //! ## .env OR environment variables
 //! REDIS_URL=redis://:foobared@127.0.0.1/
 //! ## REDIS_POOL_MAX_SIZE=16
+//! ## REDIS_KEY_PREFIX=dv:prod:
 //! ENCRYPTED_DATA_VAULT_KEY=000102030405060708090a0b0c0d0e0f
 //! ENCRYPTED_DATA_VAULT_IV=f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff
 //! ```
@@ -58,12 +59,188 @@
 //! # Current Features
 //! - Store [Credit Cards](https://github.com/chmoder/credit_card)
 //! - Store `String`
+//! - Store any serializable record, see `VaultRecord`
+//! - Store network tokens (DPANs) with their expiry, cryptogram requirement
+//!   and PAR, linked to the card they stand for, see `NetworkToken`
+//! - Decrypted Apple Pay and Google Pay payloads kept only until the
+//!   payment is authorized, see `WalletPayload`
+//! - Store ACH bank accounts, routing numbers checked against the ABA
+//!   checksum, see `BankAccount`
+//! - Store social security numbers, national ids and driver licenses
+//!   with a sensitivity and retention per field, see `PiiRecord`
+//! - Magstripe track 2 data and EMV payloads staged for at most 5 minutes and
+//!   deleted as they are forwarded, see `Track2Data` and `DataVault::take_record`
 //! - Automatic Encryption and Decryption
-//! - Blake3 tokenization
-//! - Redis Server, URL connection configuration
+//! - Blake3 tokenization, random or deterministic
+//! - Cards already on file found by a fingerprint of their number, see
+//!   `DataVault::store_credit_card_deduplicated`
+//! - Duplicate records of one card merged into one token, the others kept
+//!   resolving as aliases, see `DataVault::merge`
+//! - Cards past their expiry and a grace period deleted, soft deleted or
+//!   flagged by a sweep, see `sweep_expired_cards`
+//! - Account updater responses applied to cards on file in place, with a
+//!   report of updated and closed cards, see `account_updater`
+//! - Non-sensitive metadata such as a customer id kept encrypted with each
+//!   card, see `DataVault::store_credit_card_with_custom_metadata`
+//! - Card nicknames and card art kept for wallets to label cards without
+//!   detokenizing them, see `DataVault::get_display_info`
+//! - Card brand, last 4 digits and expiry kept in a separate summary so display
+//!   endpoints never decrypt the card, see `DataVault::retrieve_summary`
+//! - Security codes kept apart from the card for at most 15 minutes and
+//!   deleted on first read, see `DataVault::store_cvv`
+//! - Redis Server, URL connection configuration, TLS with `redis-tls`
 //! - Configurable from .env file or Environment Variables
+//! - Secrets read from files named by `*_FILE` variables, e.g. `ENCRYPTED_DATA_VAULT_KEY_FILE`
+//! - Several vaults per process, each reading variables with its own prefix, e.g. `VAULT_A_REDIS_URL`
+//! - Configurable in code, see `RedisVaultConfig` and `PostgresVaultConfig`
+//! - Configurable from TOML or YAML files, see `Config`
+//! - Configuration validated at startup, errors name the wrong variable
+//! - Key material and pools reloaded without recreating the vault, see `reload`
+//! - Record count and storage statistics
+//! - Key id and age, records by age and per tenant counts for compliance
+//!   dashboards, see `admin`
+//! - Machine-readable report of algorithms, key age, token kind, security code,
+//!   retention and audit log state for PCI assessments, see `compliance`
+//! - Health checks for readiness and liveness probes, see `HealthReport`
+//! - Lifecycle hooks for metrics, alerting and audit sinks
+//! - Hash chained audit log with verification
+//! - Consistency checks decrypting every record and reporting corrupt or
+//!   orphaned entries, see `verify`
+//! - Index drift reports with repair, e.g. index entries without a record or
+//!   records missing from an index, see `integrity_report`
+//! - Access policies per caller, e.g. existence checks without detokenization
+//! - Detokenization rate limits per caller and tenant
+//! - Retries with exponential backoff after transient backend errors
+//! - Injected latency, timeouts and errors for testing, see `ChaosDataVault`
+//! - Writes failing during an outage kept in an encrypted spool or a secondary
+//!   back end and replayed later, see `DeadLetterVault`
+//! - `tracing` spans with the back end, operation, duration and outcome
+//! - OpenTelemetry spans and duration metrics with the database semantic attributes
+//! - Card numbers, keys and tokens masked in logs and `Debug` output, see `redact`
+//! - Connection checks on checkout and keepalive for idle connections
+//! - Pool size, idle connections, waiting tasks and timeouts, see `pool_status`
+//! - Latency percentiles of the backend, encryption and serialization, see `latency_report`
+//! - Slow store and retrieve operations logged with the time of each stage, see `with_slow_op`
+//! - Lazy connections, vaults are created while their back end is still down
+//! - Unix socket connections to Redis and Postgres
+//! - Postgres read replicas for detokenization, within a lag tolerance
+//! - Batch store and retrieve, one Redis round trip for many cards, see
+//!   `DataVault::store_credit_cards` and `DataVault::retrieve_credit_cards`
+//! - Security codes are stripped, rejected or expire quickly, never kept
+//! - Card numbers of the wrong length or failing the Luhn check and invalid or,
+//!   optionally, past expiry dates rejected before they are stored, expiry dates
+//!   normalized to `MM` and `YYYY`, see `validation`
+//! - Every check on a card reported at once, Luhn, length by brand, expiry and
+//!   test card numbers, for forms to show before storing, see `validate_credit_card`
+//! - Spaces and hyphens dropped from card numbers, digits of other scripts made
+//!   ASCII and cardholder name whitespace collapsed on store, see `normalize_number`
+//! - Card brand filled in from the card number when it is missing, see `detect_brand`
+//! - Cards retrieved with their number masked for display and receipts, see
+//!   `MaskedCreditCard`
+//! - Cards retrieved wrapped so `Debug`, `Display` and serialization redact them
+//!   until `expose`d or exported with `full_export`, see `Sensitive`
+//! - Postgres columns for plaintext or blind indexed card fields
+//! - Streaming over all records
+//! - Encrypted export and import
+//! - Backups with a manifest of record counts and checksums, verified on restore
+//! - Migration between back ends
+//! - Token rotation
+//! - Record versions for optimistic concurrency
+//! - Postgres transactions spanning several operations
+//! - Postgres table created and upgraded by embedded migrations, see `run_migrations`,
+//!   automatically with `DATA_VAULT_AUTO_CREATE_SCHEMA=true`
+//! - Namespaces for multi-tenant vaults
+//! - Soft delete with a retention period and purging of expired records
 //! - Interchangeable Encryption
 //! - Interchangeable Tokenization hasher
+//! - Interchangeable record format, JSON, CBOR, MessagePack or bincode
+//! - Optional record compression before encryption
+//! - Blocking API with the `blocking` feature
+//! - In-memory `MockDataVault` with fixtures and call assertions for unit tests
+//! - Redis and Postgres test containers with ready-to-use vaults, see `containers`
+//! - REST tokenization service with API keys and TLS, the `data-vault-server`
+//!   binary, see `server`
+//! - gRPC Tokenize, Detokenize, Delete and Exists over any vault with mTLS,
+//!   see `grpc` and `proto/data_vault.proto`
+//! - `data-vault` command line tool to store, retrieve, delete, export, migrate,
+//!   import, rekey and verify with the library's configuration, see `cli`
+//! - `DataVaultDyn`, the vault as a trait object, and actix-web app data,
+//!   extractor and tokenization routes, see `actix`
+//! - axum `Router` of tokenization routes and a `FromRef` vault state, see `axum`
+//! - Detokenizing proxy forwarding requests to payment processors with the
+//!   tokens at configured JSON paths replaced by card data, see `proxy`
+//! - `tower::Service` of tokenize and detokenize requests for tower middleware,
+//!   see `service`
+//! - AWS Lambda functions sharing one vault across invocations, with small
+//!   pools and SIGTERM shutdown, see `lambda` and `examples/lambda.rs`
+//! - `RemoteDataVault` calling the tokenization server over HTTP, builds for
+//!   wasm32 edge runtimes such as Cloudflare Workers, see `remote`
+//! - Python bindings storing, retrieving and deleting with the same
+//!   configuration, see `bindings/python`
+//! - A C interface with a stable ABI for switch software written in C or
+//!   C++, see `ffi` and `include/data_vault.h`
+//! - Change events without card data published to Kafka, NATS or HMAC
+//!   signed webhooks, including tokens expiring soon, see `events`
+//! - Resumable bulk import of CSV and NDJSON card files with a mapping of
+//!   rows to tokens, see `import`
+//! - Migration of the PGP encrypted PAN exports of Stripe, Braintree and
+//!   Adyen with a mapping of their tokens to the vault's, see `interop`
+//! - Issuer country, card type and scheme of stored cards looked up by their
+//!   BIN and read without detokenizing, see `bin`
+//! - Parquet and Arrow exports of tokens with brand, last four digits, expiry
+//!   and record metadata for analytics, see `analytics`
+//! - JSON Schemas of the records and an OpenAPI document of the
+//!   tokenization service for generating clients, see `openapi`
+//! - tokio or async-std runtimes
+//!
+//! # Cargo Features
+//! - `redis` (default) - `RedisDataVault` backend
+//! - `postgres` (default) - `PostgresDataVault` backend, requires tokio
+//! - `rt-tokio` (default) - run the connection pools on tokio
+//! - `rt-async-std` - run the connection pools on async-std,
+//!   use with `default-features = false`
+//! - `redis-tls` - `rediss://` urls with CA bundles and client
+//!   certificates, see `RedisTlsConfig`
+//! - `blocking` - synchronous API in `data_vault::blocking`
+//! - `cbor`, `msgpack`, `bincode` - record formats in `data_vault::serializer`
+//! - `lz4`, `deflate` - record compression, see `CompressionAlgo`
+//! - `toml`, `yaml` - configuration files, see `Config::from_file`
+//! - `tracing` - spans around operations and pool checkouts, see `TracedDataVault`
+//! - `otel` - OpenTelemetry spans and metrics of operations, see `OtelDataVault`
+//! - `log` - a logger masking card numbers, keys and tokens, see `RedactingLogger`, and
+//!   slow operation logs, see `with_slow_op`
+//! - `test-util` - `MockDataVault`, an in-memory vault for unit tests
+//! - `test-containers` - redis and postgres in docker containers for
+//!   integration tests, see `data_vault::containers`
+//! - `server` - the `data-vault-server` binary and its routes, see
+//!   `data_vault::server`
+//! - `grpc` - a tonic gRPC service and client, see `data_vault::grpc`
+//! - `cli` - the `data-vault` command line tool, see `data_vault::cli`
+//! - `actix` - actix-web app data and a scope of tokenization routes, see
+//!   `data_vault::actix`
+//! - `axum` - an axum router of tokenization routes and `VaultState`, see
+//!   `data_vault::axum`
+//! - `proxy` - a detokenizing proxy in front of payment processors, see
+//!   `data_vault::proxy`
+//! - `tower` - the vault as a `tower::Service`, see `data_vault::service`
+//! - `lambda` - a vault shared by the invocations of an AWS Lambda function,
+//!   redis or postgres picked from the environment, see `data_vault::lambda`
+//! - `remote` - `RemoteDataVault`, a client of the tokenization server that
+//!   builds for wasm32 without `redis` and `postgres`, see `data_vault::remote`
+//! - `ffi` - `extern "C"` functions to open a vault, store, retrieve into
+//!   a caller's buffer and free it, see `data_vault::ffi`
+//! - `kafka`, `nats` - `PublishingDataVault` publishing change events to a
+//!   Kafka topic or NATS subject, see `data_vault::events`
+//! - `webhooks` - `WebhookSink` posting change events signed with HMAC-SHA256,
+//!   retried on failures, see `data_vault::events`
+//! - `import` - `import_cards`, a bulk import of CSV and NDJSON card files,
+//!   see `data_vault::import`
+//! - `interop` - `import_provider_export`, PGP encrypted processor exports
+//!   decrypted with `gpg` and imported, see `data_vault::interop`
+//! - `bin` - `BinLookupVault`, BIN lookups of stored cards in an embedded
+//!   or a loaded table, see `data_vault::bin`
+//! - `parquet` - `export_analytics`, tokens and non-sensitive card fields as
+//!   Parquet or Arrow IPC files, see `data_vault::analytics`
 //!
 //! # Future Features
 //! - Postgres Database
@@ -78,28 +255,269 @@
 //!
 
 mod traits;
+mod error;
+mod stats;
+mod health;
+mod metadata;
+mod purge;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod sweep;
+mod stream;
+mod export;
+mod backup;
+mod migrate;
+mod hooks;
+mod policy;
+mod rate_limit;
+mod retry;
+mod chaos;
+mod dead_letter;
+#[cfg(feature = "test-util")]
+mod mock;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "otel")]
+mod otel;
+mod redact;
+mod record;
+mod network_token;
+mod wallet;
+mod bank_account;
+mod pii;
+mod card_present;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod cvv;
+mod brand;
+mod masked;
+mod sensitive;
+mod display_info;
+mod summary;
+mod alias;
+mod fingerprint;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod compression;
+mod namespace;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod recycle;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod pool_status;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod latency;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod reload;
+#[cfg(feature = "postgres")]
+mod fields;
+#[cfg(feature = "redis")]
 mod redis_data_vault;
+#[cfg(feature = "redis")]
+mod redis_pool;
+#[cfg(feature = "postgres")]
 mod postgres_data_vault;
+#[cfg(feature = "postgres")]
+mod postgres_replicas;
+#[cfg(feature = "postgres")]
+mod schema;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod verify;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+mod integrity;
 mod config;
+#[cfg(all(any(feature = "redis", feature = "postgres"), any(feature = "toml", feature = "yaml")))]
+mod config_file;
 pub mod utils;
 pub mod encryption;
 pub mod tokenizer;
+pub mod serializer;
+pub mod audit;
+pub mod openapi;
+pub mod validation;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub mod admin;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub mod compliance;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub mod account_updater;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(all(feature = "test-containers", any(feature = "redis", feature = "postgres")))]
+pub mod containers;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "proxy")]
+pub mod proxy;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(all(feature = "lambda", any(feature = "redis", feature = "postgres")))]
+pub mod lambda;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "import")]
+pub mod import;
+#[cfg(feature = "interop")]
+pub mod interop;
+#[cfg(feature = "bin")]
+pub mod bin;
+#[cfg(feature = "parquet")]
+pub mod analytics;
+#[cfg(any(feature = "kafka", feature = "nats", feature = "webhooks"))]
+pub mod events;
 
-pub use traits::DataVault;
+pub use traits::{DataVault, DataVaultDyn};
+pub use record::VaultRecord;
+pub use network_token::{CryptogramRequirement, NetworkToken};
+pub use wallet::{Wallet, WalletPayload, WALLET_PAYLOAD_TTL};
+pub use bank_account::{AccountType, BankAccount};
+pub use pii::{PiiField, PiiRecord, Sensitivity};
+pub use card_present::{EmvPayload, Track2Data, MAX_CARD_PRESENT_TTL};
+pub use error::DataVaultError;
+pub use validation::{validate_credit_card, ValidationError, ValidationReport, MAX_CVV_TTL};
+pub use brand::{detect_brand, CardBrand};
+pub use masked::{mask_number, MaskedCreditCard};
+pub use sensitive::{full_export, Redact, Sensitive};
+pub use display_info::{DisplayInfo, MAX_DISPLAY_INFO_LENGTH};
+pub use summary::CardSummary;
+pub use fingerprint::StoreOutcome;
+pub use config::EncryptionSettings;
+#[cfg(all(any(feature = "redis", feature = "postgres"), any(feature = "toml", feature = "yaml")))]
+pub use config_file::{Backend, Config, TokenizerKind};
+#[cfg(feature = "redis")]
+pub use config::RedisVaultConfig;
+#[cfg(feature = "redis-tls")]
+pub use config::RedisTlsConfig;
+#[cfg(feature = "postgres")]
+pub use config::PostgresVaultConfig;
+#[cfg(feature = "server")]
+pub use config::ServerConfig;
+#[cfg(feature = "lambda")]
+pub use config::LambdaConfig;
+#[cfg(feature = "remote")]
+pub use config::RemoteVaultConfig;
+#[cfg(any(feature = "kafka", feature = "nats", feature = "webhooks"))]
+pub use config::EventsConfig;
+pub use stats::VaultStats;
+pub use health::{CheckResult, HealthReport};
+pub use metadata::{CustomMetadata, RecordMetadata, MAX_METADATA_ENTRIES, MAX_METADATA_KEY_LENGTH, MAX_METADATA_VALUE_LENGTH};
+pub use purge::PurgeReport;
+pub use hooks::{HookedDataVault, Operation, Outcome, VaultEvent, VaultHooks};
+pub use policy::{AccessPolicy, CallerPolicy, PolicyEnforcedVault};
+pub use rate_limit::{RateLimitedVault, RateLimiter};
+pub use retry::{RetryPolicy, RetryingVault};
+pub use chaos::{ChaosDataVault, ChaosPolicy};
+pub use dead_letter::{DeadLetter, DeadLetterRecord, DeadLetterSink, DeadLetterVault, ReplayReport, SpoolSink, VaultSink};
+#[cfg(feature = "test-util")]
+pub use mock::{MockCall, MockDataVault};
+#[cfg(feature = "tracing")]
+pub use trace::TracedDataVault;
+#[cfg(feature = "otel")]
+pub use otel::OtelDataVault;
+pub use redact::{redact, redact_token};
+#[cfg(feature = "log")]
+pub use redact::RedactingLogger;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use cvv::CvvPolicy;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use compression::CompressionAlgo;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use recycle::RecycleMethod;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use pool_status::PoolStatus;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use latency::{LatencyReport, LatencyStage, StageLatency};
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use verify::{RecordIssue, RecordProblem, VerifyReport};
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use integrity::{IntegrityIssue, IntegrityProblem, IntegrityReport};
+pub use stream::{RecordPage, RecordStream};
+pub use backup::{BackupManifest, RestoreReport};
+pub use migrate::{migrate, MigrateOptions, MigrationProgress, MigrationReport, ProgressCallback};
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+pub use purge::purge_expired_every;
+#[cfg(any(feature = "redis", feature = "postgres"))]
+pub use sweep::{is_flagged_expired, sweep_expired_cards, SweepAction, SweepReport};
+#[cfg(any(feature = "redis", feature = "postgres", feature = "test-util", feature = "remote"))]
+pub use namespace::DEFAULT_NAMESPACE;
+#[cfg(feature = "redis")]
 pub use redis_data_vault::RedisDataVault;
-pub use postgres_data_vault::PostgresDataVault;
+#[cfg(feature = "postgres")]
+pub use postgres_data_vault::{PostgresDataVault, PostgresTransaction, TransactionFuture};
+#[cfg(feature = "postgres")]
+pub use schema::{SchemaMigration, SchemaReport};
+#[cfg(feature = "postgres")]
+pub use fields::{CardField, CardFieldLayout, FieldStorage};
 
 
 #[cfg(test)]
 mod tests {
+    #[cfg(any(feature = "redis", all(feature = "postgres", feature = "rt-tokio")))]
     use credit_card::CreditCard;
+    #[cfg(any(feature = "redis", all(feature = "postgres", feature = "rt-tokio")))]
     use crate::traits::DataVault;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use crate::error::DataVaultError;
+    #[cfg(feature = "redis")]
     use crate::redis_data_vault::RedisDataVault;
     use crate::encryption::traits::Encryption;
     use crate::encryption::AesGcmSivEncryption;
+    #[cfg(any(feature = "redis", all(feature = "postgres", feature = "rt-tokio")))]
     use crate::tokenizer::Blake3Tokenizer;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use crate::utils::Salt;
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
     use crate::PostgresDataVault;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use futures::TryStreamExt;
+    #[cfg(all(feature = "redis", feature = "postgres", feature = "rt-tokio"))]
+    use crate::{migrate, MigrateOptions, MigrationProgress};
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    use crate::{Operation, Outcome, VaultEvent, VaultHooks, CallerPolicy, PolicyEnforcedVault};
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    use crate::RateLimiter;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use crate::{CvvPolicy, ValidationError};
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    use crate::MAX_CVV_TTL;
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    use crate::{CardField, CardFieldLayout, FieldStorage};
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    use crate::RedisVaultConfig;
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    use crate::PostgresVaultConfig;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio", feature = "cbor"))]
+    use crate::serializer::CborSerializer;
+    #[cfg(all(feature = "redis", feature = "rt-tokio", feature = "lz4"))]
+    use crate::CompressionAlgo;
+    #[cfg(all(feature = "postgres", feature = "rt-tokio", feature = "bincode"))]
+    use crate::serializer::BincodeSerializer;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use std::time::{Duration, SystemTime};
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use crate::{ChaosPolicy, RecycleMethod, RetryPolicy};
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    use crate::EncryptionSettings;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use crate::{DeadLetterSink, DeadLetterVault};
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    use crate::SpoolSink;
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    use crate::VaultSink;
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    use std::sync::Arc;
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    use std::sync::Mutex;
 
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
     #[tokio::test(flavor = "multi_thread")]
     async fn store_retrieve_redis() {
         let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
@@ -118,6 +536,26 @@ mod tests {
         assert_eq!(credit_card.number, cc.number)
     }
 
+    #[cfg(all(feature = "redis", feature = "rt-async-std"))]
+    #[async_std::test]
+    async fn store_retrieve_redis_async_std() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let credit_card = vault.retrieve_credit_card(&token).await.unwrap();
+        assert_eq!(credit_card.number, cc.number)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
     #[tokio::test(flavor = "multi_thread")]
     async fn store_retrieve_postgres() {
         let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
@@ -136,6 +574,1699 @@ mod tests {
         assert_eq!(credit_card.number, cc.number)
     }
 
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokenize_redis() {
+        // a namespace without a card of this number on file yet
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace(&Salt::generate(16)).unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: Salt::generate(16),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let (token, created) = vault.tokenize(&cc).await.unwrap();
+        let (existing_token, created_again) = vault.tokenize(&cc).await.unwrap();
+        assert!(created);
+        assert!(!created_again);
+        assert_eq!(token, existing_token)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn tokenize_postgres() {
+        // a namespace without a card of this number on file yet
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap()
+            .with_namespace(&Salt::generate(16)).unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: Salt::generate(16),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let (token, created) = vault.tokenize(&cc).await.unwrap();
+        let (existing_token, created_again) = vault.tokenize(&cc).await.unwrap();
+        assert!(created);
+        assert!(!created_again);
+        assert_eq!(token, existing_token)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retrieve_missing_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let token = Salt::generate(64);
+
+        let result = vault.retrieve_credit_card(&token).await;
+        assert!(matches!(result, Err(DataVaultError::NotFound)));
+        assert!(vault.try_retrieve_credit_card(&token).await.unwrap().is_none())
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retrieve_missing_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let token = Salt::generate(64);
+
+        let result = vault.retrieve_credit_card(&token).await;
+        assert!(matches!(result, Err(DataVaultError::NotFound)));
+        assert!(vault.try_retrieve_credit_card(&token).await.unwrap().is_none())
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stats_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        vault.store(&Salt::generate(64), "{}").await.unwrap();
+
+        let count = vault.count().await.unwrap();
+        let stats = vault.stats().await.unwrap();
+        assert!(count >= 1);
+        assert!(stats.count >= 1);
+        assert!(stats.approximate_bytes > 0);
+        assert!(stats.oldest <= stats.newest);
+        assert!(stats.newest.is_some())
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stats_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        vault.store(&Salt::generate(64), "{}").await.unwrap();
+
+        let count = vault.count().await.unwrap();
+        let stats = vault.stats().await.unwrap();
+        assert!(count >= 1);
+        assert!(stats.count >= 1);
+        assert!(stats.approximate_bytes > 0);
+        assert!(stats.oldest <= stats.newest);
+        assert!(stats.newest.is_some())
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pool_status_redis() {
+        let cfg = RedisVaultConfig::from_env().unwrap().with_pool_max_size(2);
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+        let status = vault.pool_status();
+        assert_eq!((status.max_size, status.size, status.waiting, status.timeouts), (2, 0, 0, 0));
+
+        vault.exists("pool-status-missing").await.unwrap();
+        let status = vault.with_namespace("pool-status").unwrap().pool_status();
+        assert_eq!((status.size, status.available, status.waiting), (1, 1, 0));
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pool_status_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap().with_pool_max_size(2);
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone()).unwrap();
+        let status = vault.pool_status();
+        assert_eq!((status.max_size, status.size, status.waiting, status.timeouts), (2, 0, 0, 0));
+
+        vault.exists("pool-status-missing").await.unwrap();
+        let status = vault.with_namespace("pool-status").unwrap().pool_status();
+        assert_eq!((status.size, status.available, status.waiting), (1, 1, 0));
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.with_replica_hosts(&["127.0.0.1"])).unwrap();
+        vault.exists("pool-status-missing").await.unwrap();
+        let replicas = vault.replica_pool_status();
+        assert_eq!(replicas.len(), 1);
+        assert_eq!((replicas[0].max_size, replicas[0].size), (2, 1));
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn latency_report_redis() {
+        use crate::LatencyStage;
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        assert!(vault.latency_report().stages.iter().all(|stage| stage.count == 0));
+
+        let scoped = vault.with_namespace("latency-report").unwrap();
+        let token = scoped.store_credit_card(&cc).await.unwrap();
+        scoped.retrieve_credit_card(&token).await.unwrap();
+        assert!(scoped.retrieve_credit_card("latency-missing").await.is_err());
+
+        let report = vault.latency_report();
+        // the card and its summary are stored, the miss looks for an alias
+        for (stage, count) in &[
+            (LatencyStage::Store, 2),
+            (LatencyStage::Retrieve, 3),
+            (LatencyStage::Encrypt, 2),
+            (LatencyStage::Decrypt, 1),
+            (LatencyStage::Serialize, 1),
+            (LatencyStage::Deserialize, 1),
+        ] {
+            let latency = report.stage(*stage);
+            assert_eq!(latency.count, *count, "{:?}", latency);
+            assert!(latency.p50 <= latency.p99 && latency.p99 <= latency.max);
+        }
+        scoped.delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn latency_report_postgres() {
+        use crate::LatencyStage;
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        assert!(vault.latency_report().stages.iter().all(|stage| stage.count == 0));
+
+        let scoped = vault.with_namespace("latency-report").unwrap();
+        let token = scoped.store_credit_card(&cc).await.unwrap();
+        scoped.retrieve_credit_card(&token).await.unwrap();
+        assert!(scoped.retrieve_credit_card("latency-missing").await.is_err());
+
+        let report = vault.latency_report();
+        // the card and its summary are stored, the miss looks for an alias
+        for (stage, count) in &[
+            (LatencyStage::Store, 2),
+            (LatencyStage::Retrieve, 3),
+            (LatencyStage::Encrypt, 2),
+            (LatencyStage::Decrypt, 1),
+            (LatencyStage::Serialize, 1),
+            (LatencyStage::Deserialize, 1),
+        ] {
+            let latency = report.stage(*stage);
+            assert_eq!(latency.count, *count, "{:?}", latency);
+            assert!(latency.p50 <= latency.p99 && latency.p99 <= latency.max);
+        }
+        scoped.delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn health_check_redis() {
+        let cfg = RedisVaultConfig::from_env().unwrap();
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone()).unwrap();
+        let report = vault.health_check().await;
+        assert!(report.is_healthy(), "{:?}", report);
+        assert_eq!(report.backend, "redis");
+
+        vault.reload_from(RedisVaultConfig::new("redis://127.0.0.1:1/", cfg.encryption)).unwrap();
+        let report = vault.health_check().await;
+        assert!(!report.is_healthy());
+        assert!(!report.connection.healthy);
+        assert!(report.connection.error.is_some());
+        assert!(report.encryption.healthy);
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn health_check_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap();
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone()).unwrap();
+        let report = vault.health_check().await;
+        assert!(report.is_healthy(), "{:?}", report);
+        assert_eq!(report.backend, "postgres");
+
+        let mut unreachable = cfg;
+        unreachable.postgres.port = Some(1);
+        vault.reload_from(unreachable).unwrap();
+        let report = vault.health_check().await;
+        assert!(!report.is_healthy());
+        assert!(!report.connection.healthy);
+        assert!(report.connection.error.is_some());
+        assert!(report.encryption.healthy);
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rotate_token_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let new_token = vault.rotate_token(&token).await.unwrap();
+        assert_ne!(token, new_token);
+        assert!(vault.try_retrieve_credit_card(&token).await.unwrap().is_none());
+        assert_eq!(vault.retrieve_credit_card(&new_token).await.unwrap().number, cc.number);
+        assert!(matches!(vault.rotate_token(&token).await, Err(DataVaultError::NotFound)))
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rotate_token_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let new_token = vault.rotate_token(&token).await.unwrap();
+        assert_ne!(token, new_token);
+        assert!(vault.try_retrieve_credit_card(&token).await.unwrap().is_none());
+        assert_eq!(vault.retrieve_credit_card(&new_token).await.unwrap().number, cc.number);
+        assert!(matches!(vault.rotate_token(&token).await, Err(DataVaultError::NotFound)))
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn namespace_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card_in("tenant-a", &cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card_from("tenant-a", &token).await.unwrap().number, cc.number);
+        assert!(matches!(vault.retrieve_from("tenant-b", &token).await, Err(DataVaultError::NotFound)));
+
+        let tenant_b = vault.with_namespace("tenant-b").unwrap();
+        assert_eq!(tenant_b.namespace(), "tenant-b");
+        assert_eq!(tenant_b.count().await.unwrap(), 0);
+        assert!(matches!(tenant_b.rotate_token(&token).await, Err(DataVaultError::NotFound)));
+        assert!(matches!(vault.with_namespace("tenant:a"), Err(DataVaultError::InvalidNamespace(_))))
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn namespace_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card_in("tenant-a", &cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card_from("tenant-a", &token).await.unwrap().number, cc.number);
+        assert!(matches!(vault.retrieve_from("tenant-b", &token).await, Err(DataVaultError::NotFound)));
+
+        let tenant_b = vault.with_namespace("tenant-b").unwrap();
+        assert_eq!(tenant_b.namespace(), "tenant-b");
+        assert_eq!(tenant_b.count().await.unwrap(), 0);
+        assert!(matches!(tenant_b.rotate_token(&token).await, Err(DataVaultError::NotFound)));
+        assert!(matches!(vault.with_namespace("tenant:a"), Err(DataVaultError::InvalidNamespace(_))))
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn soft_delete_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = String::from("soft-delete-redis");
+        let cc_string = String::from("{number: 123}");
+        vault.store(&token, &cc_string).await.unwrap();
+        assert!(vault.exists(&token).await.unwrap());
+        vault.soft_delete(&token).await.unwrap();
+        assert!(vault.try_retrieve(&token).await.unwrap().is_none());
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(matches!(vault.rotate_token(&token).await, Err(DataVaultError::NotFound)));
+        assert!(matches!(vault.soft_delete(&token).await, Err(DataVaultError::NotFound)));
+
+        vault.store(&token, &cc_string).await.unwrap();
+        assert_eq!(vault.retrieve(&token).await.unwrap(), cc_string)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn soft_delete_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = String::from("soft-delete-postgres");
+        let cc_string = String::from("{number: 123}");
+        vault.store(&token, &cc_string).await.unwrap();
+        assert!(vault.exists(&token).await.unwrap());
+        vault.soft_delete(&token).await.unwrap();
+        assert!(vault.try_retrieve(&token).await.unwrap().is_none());
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(matches!(vault.rotate_token(&token).await, Err(DataVaultError::NotFound)));
+        assert!(matches!(vault.soft_delete(&token).await, Err(DataVaultError::NotFound)));
+
+        vault.store(&token, &cc_string).await.unwrap();
+        assert_eq!(vault.retrieve(&token).await.unwrap(), cc_string)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn update_if_version_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let (_, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        assert_eq!(version, 1);
+
+        cc.expiration_year = "2027".to_string();
+        assert_eq!(vault.update_credit_card_if_version(&token, &cc, version).await.unwrap(), 2);
+        assert!(matches!(vault.update_credit_card_if_version(&token, &cc, version).await, Err(DataVaultError::Conflict)));
+        assert!(matches!(vault.update_credit_card_if_version("missing", &cc, version).await, Err(DataVaultError::NotFound)));
+
+        let (credit_card, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        assert_eq!(credit_card.expiration_year, "2027");
+        assert_eq!(version, 2)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn update_if_version_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let (_, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        assert_eq!(version, 1);
+
+        cc.expiration_year = "2027".to_string();
+        assert_eq!(vault.update_credit_card_if_version(&token, &cc, version).await.unwrap(), 2);
+        assert!(matches!(vault.update_credit_card_if_version(&token, &cc, version).await, Err(DataVaultError::Conflict)));
+        assert!(matches!(vault.update_credit_card_if_version("missing", &cc, version).await, Err(DataVaultError::NotFound)));
+
+        let (credit_card, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        assert_eq!(credit_card.expiration_year, "2027");
+        assert_eq!(version, 2)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn iter_records_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("iter-test").unwrap();
+
+        let tokens = ["abc1", "abc2", "abc3"];
+        for token in tokens.iter() {
+            vault.store(token, "{number: 123}").await.unwrap();
+        }
+
+        let records: Vec<(String, String)> = vault.iter_decrypted_records().try_collect().await.unwrap();
+        assert_eq!(records.len(), tokens.len());
+        assert!(records.iter().all(|(token, string)| tokens.contains(&token.as_str()) && string == "{number: 123}"));
+
+        let ciphertexts: Vec<(String, Vec<u8>)> = vault.iter_records().try_collect().await.unwrap();
+        assert_eq!(ciphertexts.len(), tokens.len())
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn iter_records_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("iter-test").unwrap();
+
+        let tokens = ["abc1", "abc2", "abc3"];
+        for token in tokens.iter() {
+            vault.store(token, "{number: 123}").await.unwrap();
+        }
+
+        let records: Vec<(String, String)> = vault.iter_decrypted_records().try_collect().await.unwrap();
+        assert_eq!(records.len(), tokens.len());
+        assert!(records.iter().all(|(token, string)| tokens.contains(&token.as_str()) && string == "{number: 123}"));
+
+        let ciphertexts: Vec<(String, Vec<u8>)> = vault.iter_records().try_collect().await.unwrap();
+        assert_eq!(ciphertexts.len(), tokens.len())
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn export_import_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let source = vault.with_namespace("export-source").unwrap();
+        let destination = vault.with_namespace("export-destination").unwrap();
+
+        source.store("abc1", "{number: 123}").await.unwrap();
+        source.store("abc2", "{number: 456}").await.unwrap();
+
+        let mut dump = Vec::new();
+        assert_eq!(source.export(&mut dump, b"export key").await.unwrap(), 2);
+        assert!(matches!(destination.import(dump.as_slice(), b"other key").await, Err(DataVaultError::InvalidExport(_))));
+        assert!(matches!(destination.import(&dump[..dump.len() - 1], b"export key").await, Err(DataVaultError::Io(_))));
+
+        assert_eq!(destination.import(dump.as_slice(), b"export key").await.unwrap(), 2);
+        assert_eq!(destination.retrieve("abc2").await.unwrap(), "{number: 456}")
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn export_import_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let source = vault.with_namespace("export-source").unwrap();
+        let destination = vault.with_namespace("export-destination").unwrap();
+
+        source.store("abc1", "{number: 123}").await.unwrap();
+        source.store("abc2", "{number: 456}").await.unwrap();
+
+        let mut dump = Vec::new();
+        assert_eq!(source.export(&mut dump, b"export key").await.unwrap(), 2);
+        assert!(matches!(destination.import(dump.as_slice(), b"other key").await, Err(DataVaultError::InvalidExport(_))));
+        assert!(matches!(destination.import(&dump[..dump.len() - 1], b"export key").await, Err(DataVaultError::Io(_))));
+
+        assert_eq!(destination.import(dump.as_slice(), b"export key").await.unwrap(), 2);
+        assert_eq!(destination.retrieve("abc2").await.unwrap(), "{number: 456}")
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn backup_restore_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let source = vault.with_namespace("backup-source").unwrap();
+        let destination = vault.with_namespace("backup-destination").unwrap();
+        let dir = std::env::temp_dir().join(format!("data_vault_backup_redis_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        source.store("abc1", "{number: 123}").await.unwrap();
+        source.store("abc2", "{number: 456}").await.unwrap();
+
+        let manifest = source.backup(&dir, b"backup key").await.unwrap();
+        assert_eq!((manifest.records, manifest.backend.as_str(), manifest.namespace.as_str()), (2, "redis", "backup-source"));
+        assert!(matches!(source.backup(&dir, b"backup key").await, Err(DataVaultError::Io(_))));
+        assert!(matches!(destination.restore(&dir, b"other key").await, Err(DataVaultError::InvalidExport(_))));
+        assert!(!destination.exists("abc1").await.unwrap());
+
+        let report = destination.restore(&dir, b"backup key").await.unwrap();
+        assert_eq!((report.restored, report.verified, report.mismatched), (2, 2, vec![]));
+        assert_eq!(destination.retrieve("abc2").await.unwrap(), "{number: 456}");
+
+        let records = dir.join("records.dvexport");
+        let mut damaged = std::fs::read(&records).unwrap();
+        damaged.pop();
+        std::fs::write(&records, damaged).unwrap();
+        assert!(matches!(destination.restore(&dir, b"backup key").await, Err(DataVaultError::InvalidExport(_))));
+        destination.delete_many(&["abc1".to_string(), "abc2".to_string()]).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn backup_restore_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let source = vault.with_namespace("backup-source").unwrap();
+        let destination = vault.with_namespace("backup-destination").unwrap();
+        let dir = std::env::temp_dir().join(format!("data_vault_backup_postgres_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        source.store("abc1", "{number: 123}").await.unwrap();
+        source.store("abc2", "{number: 456}").await.unwrap();
+
+        let manifest = source.backup(&dir, b"backup key").await.unwrap();
+        assert_eq!((manifest.records, manifest.backend.as_str(), manifest.namespace.as_str()), (2, "postgres", "backup-source"));
+        assert!(matches!(source.backup(&dir, b"backup key").await, Err(DataVaultError::Io(_))));
+        assert!(matches!(destination.restore(&dir, b"other key").await, Err(DataVaultError::InvalidExport(_))));
+        assert!(!destination.exists("abc1").await.unwrap());
+
+        let report = destination.restore(&dir, b"backup key").await.unwrap();
+        assert_eq!((report.restored, report.verified, report.mismatched), (2, 2, vec![]));
+        assert_eq!(destination.retrieve("abc2").await.unwrap(), "{number: 456}");
+
+        let records = dir.join("records.dvexport");
+        let mut damaged = std::fs::read(&records).unwrap();
+        damaged.pop();
+        std::fs::write(&records, damaged).unwrap();
+        assert!(matches!(destination.restore(&dir, b"backup key").await, Err(DataVaultError::InvalidExport(_))));
+        destination.delete_many(&["abc1".to_string(), "abc2".to_string()]).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn migrate_postgres_to_redis() {
+        let postgres = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("migrate-source").unwrap();
+        let redis = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("migrate-destination").unwrap();
+
+        for token in ["abc1", "abc2", "abc3"].iter() {
+            postgres.store(token, "{number: 123}").await.unwrap();
+        }
+
+        let mut cursors = Vec::new();
+        let options = MigrateOptions {
+            batch_size: 1,
+            on_progress: Some(Box::new(|progress: &MigrationProgress| cursors.push(progress.cursor.clone()))),
+            ..MigrateOptions::default()
+        };
+        let report = migrate(&postgres, &redis, options).await.unwrap();
+        assert_eq!(report.migrated, 3);
+        assert_eq!(report.verified, 3);
+        assert!(report.mismatched.is_empty());
+        assert_eq!(redis.retrieve("abc3").await.unwrap(), "{number: 123}");
+
+        let options = MigrateOptions {
+            batch_size: 1,
+            resume_from: cursors[0].clone(),
+            verify: false,
+            ..MigrateOptions::default()
+        };
+        assert_eq!(migrate(&postgres, &redis, options).await.unwrap().migrated, 2)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[derive(Default)]
+    struct RecordingHooks {
+        events: Mutex<Vec<VaultEvent>>,
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    impl VaultHooks for RecordingHooks {
+        fn on_event(&self, event: &VaultEvent) {
+            self.events.lock().unwrap().push(event.clone())
+        }
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn hooks_redis() {
+        let hooks = Arc::new(RecordingHooks::default());
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_hooks(hooks.clone())
+            .with_namespace("hooks-test").unwrap();
+
+        vault.store("abc", "{number: 123}").await.unwrap();
+        vault.retrieve("abc").await.unwrap();
+        vault.soft_delete("abc").await.unwrap();
+        assert!(matches!(vault.retrieve("abc").await, Err(DataVaultError::NotFound)));
+
+        let events = hooks.events.lock().unwrap();
+        let operations: Vec<_> = events.iter().map(|event| (event.operation, event.outcome)).collect();
+        assert_eq!(operations, [
+            (Operation::Store, Outcome::Success),
+            (Operation::Retrieve, Outcome::Success),
+            (Operation::SoftDelete, Outcome::Success),
+            (Operation::Retrieve, Outcome::NotFound),
+        ]);
+        assert!(events.iter().all(|event| event.token.as_deref() == Some("abc") && event.tenant == "hooks-test"));
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn access_policy_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        vault.store("policy-test", "{number: 123}").await.unwrap();
+
+        let policy = CallerPolicy::default().allow("support", &[Operation::Exists]);
+        let vault = PolicyEnforcedVault::with_policy(vault, policy, "support");
+        assert!(vault.exists("policy-test").await.unwrap());
+        assert!(matches!(vault.retrieve("policy-test").await, Err(DataVaultError::AccessDenied)));
+        assert!(matches!(vault.soft_delete("policy-test").await, Err(DataVaultError::AccessDenied)));
+        assert!(matches!(vault.iter_records().try_next().await, Err(DataVaultError::AccessDenied)));
+        assert_eq!(vault.inner().retrieve("policy-test").await.unwrap(), "{number: 123}")
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rate_limit_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("rate-limit-test").unwrap();
+        vault.store("abc", "{number: 123}").await.unwrap();
+
+        let limiter = Arc::new(RateLimiter::new(2, 0.0));
+        let vault = limiter.limited(vault, "billing");
+        vault.retrieve("abc").await.unwrap();
+        vault.retrieve("abc").await.unwrap();
+        assert!(matches!(vault.retrieve("abc").await, Err(DataVaultError::RateLimited(_))));
+        assert!(vault.exists("abc").await.unwrap());
+
+        let other_caller = limiter.limited(vault.inner().with_namespace("rate-limit-test").unwrap(), "support");
+        assert_eq!(other_caller.retrieve("abc").await.unwrap(), "{number: 123}")
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cvv_policy_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("cvv-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("123".to_string())
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().security_code, None);
+        let count = vault.count().await.unwrap();
+
+        let vault = vault.with_cvv_policy(CvvPolicy::Reject);
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::SecurityCodeNotAllowed)));
+
+        let vault = vault.with_cvv_policy(CvvPolicy::Expire(Duration::from_millis(500)));
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().security_code, cc.security_code);
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(vault.purge_expired().await.unwrap().purged >= 1);
+        assert_eq!(vault.count().await.unwrap(), count)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cvv_policy_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("cvv-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("123".to_string())
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().security_code, None);
+        let count = vault.count().await.unwrap();
+
+        let vault = vault.with_cvv_policy(CvvPolicy::Reject);
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::SecurityCodeNotAllowed)));
+
+        let vault = vault.with_cvv_policy(CvvPolicy::Expire(Duration::from_millis(500)));
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().security_code, cc.security_code);
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(vault.purge_expired().await.unwrap().purged >= 1);
+        assert_eq!(vault.count().await.unwrap(), count)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_cvv_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("store-cvv-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = vault.store_credit_card(&cc).await.unwrap();
+
+        vault.store_cvv(&token, "123", Duration::from_secs(60)).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().security_code, None);
+        assert_eq!(vault.retrieve_and_burn_cvv(&token).await.unwrap(), "123");
+        assert!(matches!(vault.retrieve_and_burn_cvv(&token).await, Err(DataVaultError::NotFound)));
+
+        assert!(matches!(vault.store_cvv(&token, "123", MAX_CVV_TTL * 2).await, Err(DataVaultError::Validation(_))));
+        assert!(matches!(vault.store_cvv("unknown", "123", Duration::from_secs(60)).await, Err(DataVaultError::NotFound)));
+
+        vault.store_cvv(&token, "456", Duration::from_millis(500)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(matches!(vault.retrieve_and_burn_cvv(&token).await, Err(DataVaultError::NotFound)));
+
+        // rotating the token drops the code
+        vault.store_cvv(&token, "789", Duration::from_secs(60)).await.unwrap();
+        let new_token = vault.rotate_token(&token).await.unwrap();
+        assert!(matches!(vault.retrieve_and_burn_cvv(&new_token).await, Err(DataVaultError::NotFound)));
+        assert!(matches!(vault.retrieve_and_burn_cvv(&token).await, Err(DataVaultError::NotFound)));
+
+        vault.delete_many(&[new_token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn card_validation_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("validation-test").unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111121".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::LuhnCheckFailed))));
+        assert!(matches!(vault.tokenize(&cc).await, Err(DataVaultError::Validation(_))));
+        assert!(matches!(vault.store_credit_card_with_token("tok_typo", &cc, true).await, Err(DataVaultError::Validation(_))));
+        cc.number = "4111111111111111".to_string();
+        cc.expiration_month = "13".to_string();
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::InvalidExpirationMonth))));
+        cc.expiration_month = "1".to_string();
+        cc.expiration_year = "23".to_string();
+        assert!(matches!(vault.with_namespace("validation-test").unwrap().with_reject_expired(true).store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::Expired))));
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let stored = vault.retrieve_credit_card(&token).await.unwrap();
+        assert_eq!((stored.expiration_month.as_str(), stored.expiration_year.as_str()), ("01", "2023"));
+        assert_eq!(vault.retrieve_masked_credit_card(&token).await.unwrap().to_string(), "visa 411111******1111 01/2023 Graydon Hoare");
+        vault.delete_many(&[token]).await.unwrap();
+
+        cc.number = "411111111".to_string();
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::InvalidLength(9)))));
+
+        let vault = vault.with_card_validation(false);
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let stored = vault.retrieve_credit_card(&token).await.unwrap();
+        assert_eq!(stored.number, cc.number);
+        // filled in from the number
+        assert_eq!(stored.brand.as_deref(), Some("visa"));
+        vault.delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn card_validation_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("validation-test").unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111121".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::LuhnCheckFailed))));
+        assert!(matches!(vault.tokenize(&cc).await, Err(DataVaultError::Validation(_))));
+        assert!(matches!(vault.store_credit_card_with_token("tok_typo", &cc, true).await, Err(DataVaultError::Validation(_))));
+        cc.number = "4111111111111111".to_string();
+        cc.expiration_month = "13".to_string();
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::InvalidExpirationMonth))));
+        cc.expiration_month = "1".to_string();
+        cc.expiration_year = "23".to_string();
+        assert!(matches!(vault.with_namespace("validation-test").unwrap().with_reject_expired(true).store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::Expired))));
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let stored = vault.retrieve_credit_card(&token).await.unwrap();
+        assert_eq!((stored.expiration_month.as_str(), stored.expiration_year.as_str()), ("01", "2023"));
+        assert_eq!(vault.retrieve_masked_credit_card(&token).await.unwrap().to_string(), "visa 411111******1111 01/2023 Graydon Hoare");
+        let sensitive = vault.retrieve_sensitive_credit_card(&token).await.unwrap();
+        assert_eq!(format!("{:?}", sensitive), "Sensitive(visa 411111******1111 01/2023 Graydon Hoare)");
+        assert_eq!(sensitive.expose().number, "4111111111111111");
+        vault.delete_many(&[token]).await.unwrap();
+
+        cc.number = "411111111".to_string();
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::Validation(ValidationError::InvalidLength(9)))));
+
+        let vault = vault.with_card_validation(false);
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let stored = vault.retrieve_credit_card(&token).await.unwrap();
+        assert_eq!(stored.number, cc.number);
+        // filled in from the number
+        assert_eq!(stored.brand.as_deref(), Some("visa"));
+        vault.delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn card_fields_postgres() {
+        let layout = CardFieldLayout::new().unwrap()
+            .with(CardField::CardholderName, FieldStorage::BlindIndex).unwrap()
+            .with(CardField::ExpirationYear, FieldStorage::Plaintext).unwrap();
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("fields-test").unwrap()
+            .with_card_fields(layout);
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: Salt::generate(16),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.tokens_where(CardField::CardholderName, &cc.cardholder_name).await.unwrap(), vec![token.clone()]);
+        assert!(vault.tokens_where(CardField::ExpirationYear, "2023").await.unwrap().contains(&token));
+        assert!(matches!(vault.tokens_where(CardField::Number, &cc.number).await, Err(DataVaultError::InvalidFieldLayout(_))));
+
+        let (_, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        let old_name = std::mem::replace(&mut cc.cardholder_name, Salt::generate(16));
+        vault.update_credit_card_if_version(&token, &cc, version).await.unwrap();
+        assert!(vault.tokens_where(CardField::CardholderName, &old_name).await.unwrap().is_empty());
+        assert_eq!(vault.tokens_where(CardField::CardholderName, &cc.cardholder_name).await.unwrap(), vec![token.clone()]);
+
+        vault.soft_delete(&token).await.unwrap();
+        assert!(vault.tokens_where(CardField::CardholderName, &cc.cardholder_name).await.unwrap().is_empty())
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio", feature = "cbor"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serializer_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer, CborSerializer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let (credit_card, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        vault.update_credit_card_if_version(&token, &credit_card, version).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().cardholder_name, cc.cardholder_name);
+        // a CBOR record is no string
+        assert!(matches!(vault.retrieve(&token).await, Err(DataVaultError::Encoding(_))));
+
+        // JSON vaults can not read CBOR records
+        let json_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        assert!(matches!(json_vault.retrieve_credit_card(&token).await, Err(DataVaultError::Serialization(_))))
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio", feature = "bincode"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serializer_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer, BincodeSerializer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: Some("visa".to_string()),
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let (credit_card, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        assert_eq!(credit_card.brand, cc.brand);
+        vault.update_credit_card_if_version(&token, &credit_card, version).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio", feature = "cbor"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serializer_retrieve_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer, CborSerializer>::new().unwrap();
+
+        let token = vault.store_credit_card(&CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        }).await.unwrap();
+        // a CBOR record is no string
+        assert!(matches!(vault.retrieve(&token).await, Err(DataVaultError::Encoding(_))));
+        vault.delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio", feature = "lz4"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn compression_redis() {
+        let plain_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("compression-test").unwrap();
+        let vault = plain_vault.with_namespace("compression-test").unwrap()
+            .with_compression(CompressionAlgo::Lz4);
+
+        let document = "{number: 123}".repeat(100);
+        vault.store("compressed", &document).await.unwrap();
+        plain_vault.store("plain", &document).await.unwrap();
+
+        // both vaults read records stored either way
+        assert_eq!(plain_vault.retrieve("compressed").await.unwrap(), document);
+        assert_eq!(vault.retrieve("plain").await.unwrap(), document);
+
+        let mut records = vault.iter_records();
+        while let Some((token, ciphertext)) = records.try_next().await.unwrap() {
+            if token == "compressed" {
+                assert!(ciphertext.len() < document.len() / 2)
+            }
+        }
+
+        let page = plain_vault.decrypted_records_page(None, 100).await.unwrap();
+        assert!(page.records.iter().all(|(_, record)| record == &document))
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Passport {
+        number: String,
+        country: String,
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    impl crate::VaultRecord for Passport {}
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn records_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let passport = Passport {
+            number: "X1234567".to_string(),
+            country: "NZ".to_string(),
+        };
+        let token = vault.store_record(&passport).await.unwrap();
+        assert_eq!(vault.retrieve_record::<Passport>(&token).await.unwrap(), passport);
+        assert!(vault.retrieve_record::<CreditCard>(&token).await.is_err());
+        assert!(vault.try_retrieve_record::<Passport>("not-stored").await.unwrap().is_none());
+
+        // credit cards keep their policies
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("123".to_string())
+        };
+        let token = vault.store_record(&cc).await.unwrap();
+        let credit_card: CreditCard = vault.retrieve_record(&token).await.unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert_eq!(credit_card.security_code, None)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn delete_many_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("delete-many-test").unwrap();
+
+        let tokens = vec![String::from("delete-1"), String::from("delete-2"), String::from("delete-3")];
+        for token in tokens.iter() {
+            vault.store(token, "{number: 123}").await.unwrap();
+        }
+        vault.soft_delete(&tokens[2]).await.unwrap();
+        let count = vault.count().await.unwrap();
+
+        let mut deleting = tokens.clone();
+        deleting.push(String::from("never-stored"));
+        assert_eq!(vault.delete_many(&deleting).await.unwrap(), 2);
+        assert!(!vault.exists(&tokens[0]).await.unwrap());
+        assert_eq!(vault.count().await.unwrap(), count - 2);
+        assert_eq!(vault.delete_many(&tokens).await.unwrap(), 0);
+        assert_eq!(vault.delete_many(&[]).await.unwrap(), 0)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn delete_many_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("delete-many-test").unwrap();
+
+        let tokens = vec![String::from("delete-1"), String::from("delete-2"), String::from("delete-3")];
+        for token in tokens.iter() {
+            vault.store(token, "{number: 123}").await.unwrap();
+        }
+        vault.soft_delete(&tokens[2]).await.unwrap();
+        let count = vault.count().await.unwrap();
+
+        let mut deleting = tokens.clone();
+        deleting.push(String::from("never-stored"));
+        assert_eq!(vault.delete_many(&deleting).await.unwrap(), 2);
+        assert!(!vault.exists(&tokens[0]).await.unwrap());
+        assert_eq!(vault.count().await.unwrap(), count - 2);
+        assert_eq!(vault.delete_many(&tokens).await.unwrap(), 0);
+        assert_eq!(vault.delete_many(&[]).await.unwrap(), 0)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn batch_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("batch-test").unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "1".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let mut credit_cards = vec![cc.clone()];
+        cc.number = "5555555555554444".to_string();
+        credit_cards.push(cc.clone());
+
+        let mut tokens = vault.store_credit_cards(&credit_cards).await.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(vault.retrieve_summary(&tokens[1]).await.unwrap().last_four, "4444");
+        tokens.insert(1, String::from("never-stored"));
+        let retrieved = vault.retrieve_credit_cards(&tokens).await.unwrap();
+        assert_eq!(retrieved[0].as_ref().unwrap().number, "4111111111111111");
+        assert_eq!(retrieved[0].as_ref().unwrap().expiration_month, "01");
+        assert!(retrieved[1].is_none());
+        assert_eq!(retrieved[2].as_ref().unwrap().brand.as_deref(), Some("mastercard"));
+
+        // one invalid card and nothing is stored
+        cc.number = "4111111111111112".to_string();
+        credit_cards.push(cc);
+        let count = vault.count().await.unwrap();
+        assert!(matches!(vault.store_credit_cards(&credit_cards).await, Err(DataVaultError::Validation(ValidationError::LuhnCheckFailed))));
+        assert_eq!(vault.count().await.unwrap(), count);
+        assert!(vault.store_credit_cards(&[]).await.unwrap().is_empty());
+        assert!(vault.retrieve_credit_cards(&[]).await.unwrap().is_empty());
+        vault.delete_many(&tokens).await.unwrap();
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn batch_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("batch-test").unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "1".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let mut credit_cards = vec![cc.clone()];
+        cc.number = "5555555555554444".to_string();
+        credit_cards.push(cc.clone());
+
+        let mut tokens = vault.store_credit_cards(&credit_cards).await.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(vault.retrieve_summary(&tokens[1]).await.unwrap().last_four, "4444");
+        tokens.insert(1, String::from("never-stored"));
+        let retrieved = vault.retrieve_credit_cards(&tokens).await.unwrap();
+        assert_eq!(retrieved[0].as_ref().unwrap().number, "4111111111111111");
+        assert_eq!(retrieved[0].as_ref().unwrap().expiration_month, "01");
+        assert!(retrieved[1].is_none());
+        assert_eq!(retrieved[2].as_ref().unwrap().brand.as_deref(), Some("mastercard"));
+
+        cc.number = "4111111111111112".to_string();
+        assert!(matches!(vault.store_credit_cards(&[cc]).await, Err(DataVaultError::Validation(ValidationError::LuhnCheckFailed))));
+        assert!(vault.store_credit_cards(&[]).await.unwrap().is_empty());
+        assert!(vault.retrieve_credit_cards(&[]).await.unwrap().is_empty());
+        vault.delete_many(&tokens).await.unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_if_absent_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = Salt::generate(64);
+        assert!(vault.store_if_absent(&token, "{number: 123}").await.unwrap());
+        assert!(!vault.store_if_absent(&token, "{number: 456}").await.unwrap());
+        assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        assert_eq!(vault.delete_many(&[token]).await.unwrap(), 1)
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn touch_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = Salt::generate(64);
+        vault.store(&token, "{number: 123}").await.unwrap();
+        vault.touch(&token, Some(Duration::from_secs(60))).await.unwrap();
+        vault.touch(&token, None).await.unwrap();
+        vault.touch(&token, Some(Duration::from_millis(500))).await.unwrap();
+        assert!(vault.exists(&token).await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(matches!(vault.touch(&token, None).await, Err(DataVaultError::NotFound)))
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn touch_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = Salt::generate(64);
+        vault.store(&token, "{number: 123}").await.unwrap();
+        vault.touch(&token, Some(Duration::from_secs(60))).await.unwrap();
+        vault.touch(&token, None).await.unwrap();
+        vault.touch(&token, Some(Duration::from_millis(500))).await.unwrap();
+        assert!(vault.exists(&token).await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(matches!(vault.touch(&token, None).await, Err(DataVaultError::NotFound)))
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn metadata_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("metadata-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let (credit_card, metadata) = vault.retrieve_with_metadata(&token).await.unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert_eq!(metadata.ttl, None);
+        assert_eq!(metadata.tenant, "metadata-test");
+        assert!(metadata.created_at.unwrap() <= SystemTime::now());
+        assert_eq!(metadata.version, vault.retrieve_credit_card_with_version(&token).await.unwrap().1);
+
+        vault.touch(&token, Some(Duration::from_secs(60))).await.unwrap();
+        let (_, metadata) = vault.retrieve_with_metadata(&token).await.unwrap();
+        let ttl = metadata.ttl.unwrap();
+        assert!(ttl > Duration::from_secs(50) && ttl <= Duration::from_secs(60));
+
+        vault.soft_delete(&token).await.unwrap();
+        assert!(matches!(vault.retrieve_with_metadata(&token).await, Err(DataVaultError::NotFound)))
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn metadata_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("metadata-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        let (credit_card, metadata) = vault.retrieve_with_metadata(&token).await.unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert_eq!(metadata.ttl, None);
+        assert_eq!(metadata.tenant, "metadata-test");
+        assert!(metadata.created_at.unwrap() <= SystemTime::now());
+        assert_eq!(metadata.version, vault.retrieve_credit_card_with_version(&token).await.unwrap().1);
+
+        vault.touch(&token, Some(Duration::from_secs(60))).await.unwrap();
+        let (_, metadata) = vault.retrieve_with_metadata(&token).await.unwrap();
+        let ttl = metadata.ttl.unwrap();
+        assert!(ttl > Duration::from_secs(50) && ttl <= Duration::from_secs(60));
+
+        vault.soft_delete(&token).await.unwrap();
+        assert!(matches!(vault.retrieve_with_metadata(&token).await, Err(DataVaultError::NotFound)))
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn from_config_redis() {
+        let cfg = RedisVaultConfig::from_env().unwrap()
+            .with_pool_max_size(2)
+            .with_cvv_policy(CvvPolicy::Reject);
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::from_config(cfg).unwrap();
+        let env_vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let (token, _) = vault.tokenize(&cc).await.unwrap();
+        assert_eq!(env_vault.tokenize(&cc).await.unwrap().0, token);
+        assert_eq!(env_vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        cc.security_code = Some("123".to_string());
+        assert!(matches!(vault.store_credit_card(&cc).await, Err(DataVaultError::SecurityCodeNotAllowed)))
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn key_prefix_redis() {
+        let cfg = RedisVaultConfig::from_env().unwrap().with_key_prefix("dv:test:");
+        let prefixed = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+        let unprefixed = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = Salt::generate(64);
+        prefixed.store(&token, "{number: 123}").await.unwrap();
+        assert!(prefixed.exists(&token).await.unwrap());
+        assert!(!unprefixed.exists(&token).await.unwrap());
+
+        let tenant = prefixed.with_namespace("prefix-test").unwrap();
+        tenant.store(&token, "{number: 456}").await.unwrap();
+        tenant.soft_delete(&token).await.unwrap();
+        assert_eq!(prefixed.retrieve(&token).await.unwrap(), "{number: 123}");
+        assert!(!unprefixed.with_namespace("prefix-test").unwrap().exists(&token).await.unwrap());
+        assert_eq!(prefixed.delete_many(&[token]).await.unwrap(), 1)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn from_config_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap()
+            .with_pool_max_size(2)
+            .with_plaintext_fields(&[CardField::ExpirationYear])
+            .with_blind_index_fields(&[CardField::CardholderName]);
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone()).unwrap()
+            .with_namespace("from-config-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: Salt::generate(16),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.tokens_where(CardField::CardholderName, &cc.cardholder_name).await.unwrap(), vec![token.clone()]);
+        assert_eq!(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("from-config-test").unwrap()
+            .retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        let plaintext_number = cfg.with_plaintext_fields(&[CardField::Number]);
+        assert!(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(plaintext_number).is_err())
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retry_redis() {
+        let policy = RetryPolicy::new(3).with_base_delay(Duration::from_millis(50)).with_jitter(false);
+        let cfg = RedisVaultConfig::new("redis://127.0.0.1:1/", EncryptionSettings::from_env().unwrap())
+            .with_retry(policy);
+        let unreachable = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+
+        // 50ms and 100ms between the three attempts
+        let started = std::time::Instant::now();
+        let error = unreachable.exists("retry").await.unwrap_err();
+        assert!(error.is_transient());
+        assert!(started.elapsed() >= Duration::from_millis(150));
+
+        let vault = policy.retrying(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+        let token = Salt::generate(64);
+        vault.store(&token, "{number: 123}").await.unwrap();
+        assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        assert!(!vault.retrieve("retry-missing").await.unwrap_err().is_transient());
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn chaos_redis() {
+        let failing = ChaosPolicy::new().with_error_rate(1.0)
+            .injecting(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+        let token = Salt::generate(64);
+        assert!(failing.store(&token, "{number: 123}").await.unwrap_err().is_transient());
+        assert!(!failing.inner().exists(&token).await.unwrap());
+
+        let timing_out = ChaosPolicy::new().with_timeout_rate(1.0, Duration::from_millis(50))
+            .injecting(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+        let started = std::time::Instant::now();
+        assert!(timing_out.exists(&token).await.unwrap_err().is_transient());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // half of the attempts fail, the retries get every operation through
+        let chaos = ChaosPolicy::new()
+            .with_latency(Duration::from_millis(1), Duration::from_millis(2))
+            .with_error_rate(0.5)
+            .with_seed(42);
+        let vault = RetryPolicy::new(20).with_base_delay(Duration::from_millis(1))
+            .retrying(chaos.injecting(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()));
+        for _ in 0..10 {
+            vault.store(&token, "{number: 123}").await.unwrap();
+            assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        }
+        vault.inner().inner().delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dead_letter_redis() {
+        let directory = std::env::temp_dir().join(format!("data_vault_dead_letter_redis_{}", std::process::id()));
+        let sink = Arc::new(SpoolSink::new(&directory, b"spool key").unwrap());
+        let failing = DeadLetterVault::with_sink(ChaosPolicy::new().with_error_rate(1.0)
+            .injecting(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()), sink.clone());
+        let (token, card_token) = (Salt::generate(64), Salt::generate(64));
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("123".to_string()),
+        };
+
+        failing.store(&token, "{number: 123}").await.unwrap();
+        failing.store_credit_card_with_token(&card_token, &cc, true).await.unwrap();
+        assert!(failing.store_credit_card_with_token(&card_token, &cc, false).await.unwrap_err().is_transient());
+        assert!(failing.retrieve(&token).await.unwrap_err().is_transient());
+        // an expiring write is never kept past its expiry
+        assert!(failing.store_expiring(&token, "{number: 123}", Duration::from_secs(60)).await.unwrap_err().is_transient());
+        // nor are writes to side namespaces, e.g. security codes
+        assert!(failing.with_side_namespace("cvv").unwrap().store(&token, "123").await.unwrap_err().is_transient());
+        assert_eq!(sink.pending().await.unwrap().len(), 2);
+
+        // still down, the letters wait for the next replay
+        let report = failing.replay().await.unwrap();
+        assert_eq!((report.replayed, report.remaining), (0, 2));
+
+        let vault = DeadLetterVault::with_sink(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap(), sink.clone());
+        let report = vault.replay().await.unwrap();
+        assert_eq!((report.replayed, report.remaining), (2, 0));
+        assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        let credit_card = vault.retrieve_credit_card(&card_token).await.unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert!(credit_card.security_code.is_none());
+        assert!(sink.pending().await.unwrap().is_empty());
+
+        vault.delete_many(&[token, card_token]).await.unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keep_alive_redis() {
+        let cfg = RedisVaultConfig::from_env().unwrap()
+            .with_pool_max_size(2)
+            .with_recycle(RecycleMethod::Fast);
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+
+        assert_eq!(vault.keep_alive().await, 0);
+        let (a, b) = futures::join!(vault.exists("keep-alive-a"), vault.exists("keep-alive-b"));
+        assert!(!a.unwrap() && !b.unwrap());
+        assert!(vault.keep_alive().await >= 1);
+        assert!(!vault.exists("keep-alive-a").await.unwrap());
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keep_alive_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap()
+            .with_pool_max_size(2)
+            .with_recycle(RecycleMethod::Fast);
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+
+        assert_eq!(vault.keep_alive().await, 0);
+        let (a, b) = futures::join!(vault.exists("keep-alive-a"), vault.exists("keep-alive-b"));
+        assert!(!a.unwrap() && !b.unwrap());
+        assert!(vault.keep_alive().await >= 1);
+        assert!(!vault.exists("keep-alive-a").await.unwrap());
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retry_postgres() {
+        let policy = RetryPolicy::new(3).with_base_delay(Duration::from_millis(50)).with_jitter(false);
+        let mut cfg = PostgresVaultConfig::from_env().unwrap().with_retry(policy);
+        cfg.postgres.port = Some(1);
+        let unreachable = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+
+        let started = std::time::Instant::now();
+        let error = unreachable.exists("retry").await.unwrap_err();
+        assert!(error.is_transient());
+        assert!(started.elapsed() >= Duration::from_millis(150));
+
+        let vault = policy.retrying(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+        let token = Salt::generate(64);
+        vault.store(&token, "{number: 123}").await.unwrap();
+        assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        assert!(!vault.retrieve("retry-missing").await.unwrap_err().is_transient());
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn chaos_postgres() {
+        let failing = ChaosPolicy::new().with_error_rate(1.0)
+            .injecting(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+        let token = Salt::generate(64);
+        assert!(failing.store(&token, "{number: 123}").await.unwrap_err().is_transient());
+        assert!(!failing.inner().exists(&token).await.unwrap());
+
+        let chaos = ChaosPolicy::new()
+            .with_timeout_rate(0.25, Duration::from_millis(5))
+            .with_error_rate(0.25)
+            .with_seed(7);
+        let vault = RetryPolicy::new(20).with_base_delay(Duration::from_millis(1))
+            .retrying(chaos.injecting(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()));
+        for _ in 0..10 {
+            vault.store(&token, "{number: 123}").await.unwrap();
+            assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        }
+        vault.inner().inner().delete_many(&[token]).await.unwrap();
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dead_letter_postgres() {
+        let secondary = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let sink = Arc::new(VaultSink::new(secondary.with_namespace(&format!("dead-letters-{}", Salt::generate(16))).unwrap()));
+        let failing = DeadLetterVault::with_sink(ChaosPolicy::new().with_error_rate(1.0)
+            .injecting(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()), sink.clone());
+        let namespace = format!("tenant-{}", Salt::generate(16));
+        let token = Salt::generate(64);
+
+        failing.store(&token, "{number: 123}").await.unwrap();
+        failing.with_namespace(&namespace).unwrap().store(&token, "{number: 456}").await.unwrap();
+        assert!(!failing.inner().inner().exists(&token).await.unwrap());
+        assert_eq!(sink.pending().await.unwrap().len(), 2);
+        // the secondary vault encrypts the letters
+        let (_, ciphertext) = sink.vault().iter_records().try_next().await.unwrap().unwrap();
+        assert!(!String::from_utf8_lossy(&ciphertext).contains("number"));
+
+        // every namespace replays its own letters
+        let vault = DeadLetterVault::with_sink(PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap(), sink.clone());
+        assert_eq!(vault.replay().await.unwrap().replayed, 1);
+        assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        assert_eq!(sink.pending().await.unwrap().len(), 1);
+
+        let tenant = vault.with_namespace(&namespace).unwrap();
+        assert_eq!(tenant.replay().await.unwrap().replayed, 1);
+        assert_eq!(tenant.retrieve(&token).await.unwrap(), "{number: 456}");
+        assert!(sink.pending().await.unwrap().is_empty());
+
+        tenant.delete_many(std::slice::from_ref(&token)).await.unwrap();
+        vault.delete_many(&[token]).await.unwrap();
+    }
+
+    /// a free port forwarding to `target` once `delay` has passed
+    #[cfg(all(any(feature = "redis", feature = "postgres"), feature = "rt-tokio"))]
+    fn delayed_proxy(target: String, delay: Duration) -> u16 {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+            loop {
+                let (mut inbound, _) = listener.accept().await.unwrap();
+                let target = target.clone();
+                tokio::spawn(async move {
+                    let mut outbound = tokio::net::TcpStream::connect(target).await.unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                });
+            }
+        });
+        port
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lazy_connect_redis() {
+        let port = delayed_proxy("127.0.0.1:6379".to_string(), Duration::from_millis(200));
+        let policy = RetryPolicy::new(10).with_base_delay(Duration::from_millis(50)).with_jitter(false);
+        let mut cfg = RedisVaultConfig::from_env().unwrap().with_retry(policy);
+        cfg.redis.url = cfg.redis.url.map(|url| url.replacen("127.0.0.1/", &format!("127.0.0.1:{}/", port), 1));
+
+        // nothing listens yet, the vault is created anyway and the
+        // first operation connects once the back end is up
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+        assert!(!vault.exists("lazy-missing").await.unwrap());
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lazy_connect_postgres() {
+        let target = PostgresVaultConfig::from_env().unwrap().postgres;
+        let port = delayed_proxy(format!("{}:{}", target.host.unwrap(), target.port.unwrap_or(5432)), Duration::from_millis(200));
+        let policy = RetryPolicy::new(10).with_base_delay(Duration::from_millis(50)).with_jitter(false);
+        let mut cfg = PostgresVaultConfig::from_env().unwrap().with_retry(policy);
+        cfg.postgres.host = Some("127.0.0.1".to_string());
+        cfg.postgres.port = Some(port);
+
+        // nothing listens yet, the vault is created anyway and the
+        // first operation connects once the back end is up
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+        assert!(!vault.exists("lazy-missing").await.unwrap());
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reload_redis() {
+        let cfg = RedisVaultConfig::from_env().unwrap();
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone()).unwrap();
+        let tenant = vault.with_namespace("reload").unwrap();
+
+        let mut invalid = cfg.clone();
+        invalid.encryption.key = "short".to_string();
+        assert!(vault.reload_from(invalid).is_err());
+        assert!(!tenant.exists("reload-missing").await.unwrap());
+
+        let unreachable = RedisVaultConfig::new("redis://127.0.0.1:1/", cfg.encryption.clone());
+        vault.reload_from(unreachable).unwrap();
+        assert!(tenant.exists("reload-missing").await.unwrap_err().is_transient());
+
+        let mut rotated = cfg.clone();
+        rotated.encryption.key = "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff".to_string();
+        vault.reload_from(rotated.clone()).unwrap();
+        let token = Salt::generate(64);
+        tenant.store(&token, "{number: 123}").await.unwrap();
+
+        let rotated_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(rotated).unwrap();
+        assert_eq!(rotated_vault.with_namespace("reload").unwrap().retrieve(&token).await.unwrap(), "{number: 123}");
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reload_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap();
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone()).unwrap();
+        let tenant = vault.with_namespace("reload").unwrap();
+
+        let mut invalid = cfg.clone();
+        invalid.encryption.key = "short".to_string();
+        assert!(vault.reload_from(invalid).is_err());
+        assert!(!tenant.exists("reload-missing").await.unwrap());
+
+        let mut unreachable = cfg.clone();
+        unreachable.postgres.port = Some(1);
+        vault.reload_from(unreachable).unwrap();
+        assert!(tenant.exists("reload-missing").await.unwrap_err().is_transient());
+
+        let mut rotated = cfg.clone();
+        rotated.encryption.key = "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff".to_string();
+        vault.reload_from(rotated.clone()).unwrap();
+        let token = Salt::generate(64);
+        tenant.store(&token, "{number: 123}").await.unwrap();
+
+        let rotated_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(rotated).unwrap();
+        assert_eq!(rotated_vault.with_namespace("reload").unwrap().retrieve(&token).await.unwrap(), "{number: 123}");
+    }
+
+    #[cfg(all(feature = "redis", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn caller_token_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = format!("legacy_{}", Salt::generate(16));
+        vault.store_credit_card_with_token(&token, &cc, false).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        cc.number = "5555555555554444".to_string();
+        assert!(matches!(vault.store_credit_card_with_token(&token, &cc, false).await, Err(DataVaultError::AlreadyExists)));
+        vault.store_credit_card_with_token(&token, &cc, true).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        assert!(matches!(vault.store_credit_card_with_token("not a token", &cc, true).await, Err(DataVaultError::InvalidToken)));
+        assert!(matches!(vault.store_credit_card_with_token("", &cc, true).await, Err(DataVaultError::InvalidToken)))
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_if_absent_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let token = Salt::generate(64);
+        assert!(vault.store_if_absent(&token, "{number: 123}").await.unwrap());
+        assert!(!vault.store_if_absent(&token, "{number: 456}").await.unwrap());
+        assert_eq!(vault.retrieve(&token).await.unwrap(), "{number: 123}");
+        assert_eq!(vault.delete_many(&[token]).await.unwrap(), 1)
+    }
+
+    #[cfg(all(feature = "postgres", feature = "rt-tokio"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn caller_token_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let token = format!("legacy_{}", Salt::generate(16));
+        vault.store_credit_card_with_token(&token, &cc, false).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        cc.number = "5555555555554444".to_string();
+        assert!(matches!(vault.store_credit_card_with_token(&token, &cc, false).await, Err(DataVaultError::AlreadyExists)));
+        vault.store_credit_card_with_token(&token, &cc, true).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        assert!(matches!(vault.store_credit_card_with_token("not a token", &cc, true).await, Err(DataVaultError::InvalidToken)));
+        assert!(matches!(vault.store_credit_card_with_token("", &cc, true).await, Err(DataVaultError::InvalidToken)))
+    }
+
     #[test]
     fn test_encrypt_string() {
         let plaintext = "Hello world!".to_string();
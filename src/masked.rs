@@ -0,0 +1,130 @@
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A credit card with all but the first 6 and last 4 digits of its
+/// number masked and without its security code, safe to show,
+/// print on receipts, log and serialize
+///
+/// Returned by `DataVault::retrieve_masked_credit_card`, or made
+/// from a card with `From`.  `Display` is one line for receipts,
+/// e.g. `visa 411111******1111 01/2023 Graydon Hoare`.
+/// # example
+/// ```rust
+/// use credit_card::CreditCard;
+/// use data_vault::MaskedCreditCard;
+///
+/// let cc = CreditCard {
+///     number: "4111111111111111".to_string(),
+///     cardholder_name: "Graydon Hoare".to_string(),
+///     expiration_month: "01".to_string(),
+///     expiration_year: "2023".to_string(),
+///     brand: Some("visa".to_string()),
+///     security_code: Some("123".to_string())
+/// };
+/// let masked = MaskedCreditCard::from(&cc);
+/// assert_eq!(masked.number, "411111******1111");
+/// assert_eq!(masked.to_string(), "visa 411111******1111 01/2023 Graydon Hoare");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaskedCreditCard {
+    pub brand: Option<String>,
+    /// e.g. `411111******1111`, see `mask_number`
+    pub number: String,
+    pub cardholder_name: String,
+    pub expiration_month: String,
+    pub expiration_year: String,
+}
+
+impl MaskedCreditCard {
+    /// The last 4 digits of the card number
+    pub fn last_four(&self) -> &str {
+        &self.number[self.number.len().saturating_sub(4)..]
+    }
+}
+
+impl From<&CreditCard> for MaskedCreditCard {
+    fn from(credit_card: &CreditCard) -> Self {
+        MaskedCreditCard {
+            brand: credit_card.brand.clone(),
+            number: mask_number(&credit_card.number),
+            cardholder_name: credit_card.cardholder_name.clone(),
+            expiration_month: credit_card.expiration_month.clone(),
+            expiration_year: credit_card.expiration_year.clone(),
+        }
+    }
+}
+
+impl From<CreditCard> for MaskedCreditCard {
+    fn from(credit_card: CreditCard) -> Self {
+        MaskedCreditCard::from(&credit_card)
+    }
+}
+
+impl fmt::Display for MaskedCreditCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(brand) = &self.brand {
+            write!(f, "{} ", brand)?;
+        }
+        write!(f, "{} {}/{}", self.number, self.expiration_month, self.expiration_year)?;
+        if !self.cardholder_name.is_empty() {
+            write!(f, " {}", self.cardholder_name)?;
+        }
+        Ok(())
+    }
+}
+
+/// `number` with all but its first 6 and last 4 digits replaced by
+/// `*`, numbers of fewer than 13 characters keep only their last 4
+/// # example
+/// ```rust
+/// use data_vault::mask_number;
+///
+/// assert_eq!(mask_number("378282246310005"), "378282*****0005");
+/// ```
+pub fn mask_number(number: &str) -> String {
+    let chars: Vec<char> = number.chars().collect();
+    let shown_first = match chars.len() {
+        0..=4 => return "*".repeat(chars.len()),
+        5..=12 => 0,
+        _ => 6,
+    };
+    let last = chars.len() - 4;
+    chars.iter().enumerate()
+        .map(|(i, c)| if i < shown_first || i >= last { *c } else { '*' })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::masked::{mask_number, MaskedCreditCard};
+
+    #[test]
+    fn test_masked_credit_card() {
+        assert_eq!(mask_number("4111111111111111"), "411111******1111");
+        assert_eq!(mask_number("4222222222222"), "422222***2222");
+        assert_eq!(mask_number("6759649826438453918"), "675964*********3918");
+        assert_eq!(mask_number("411111111"), "*****1111");
+        assert_eq!(mask_number("4111"), "****");
+        assert_eq!(mask_number(""), "");
+
+        let cc = CreditCard {
+            number: "5555555555554444".to_string(),
+            cardholder_name: String::new(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("123".to_string())
+        };
+        let masked = MaskedCreditCard::from(&cc);
+        assert_eq!(masked.last_four(), "4444");
+        assert_eq!(masked.to_string(), "555555******4444 01/2023");
+
+        let json = serde_json::to_string(&masked).unwrap();
+        assert!(!json.contains("5555555555554444"));
+        assert!(!json.contains("123"));
+        assert!(!format!("{:?}", masked).contains("5555555555554444"));
+        assert_eq!(serde_json::from_str::<MaskedCreditCard>(&json).unwrap(), masked);
+    }
+}
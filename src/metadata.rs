@@ -0,0 +1,115 @@
+use crate::validation::ValidationError;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Most entries a `CustomMetadata` may have
+pub const MAX_METADATA_ENTRIES: usize = 32;
+/// Longest key of a `CustomMetadata` entry, in characters
+pub const MAX_METADATA_KEY_LENGTH: usize = 64;
+/// Longest value of a `CustomMetadata` entry, in characters
+pub const MAX_METADATA_VALUE_LENGTH: usize = 512;
+
+/// Non-sensitive details an integrator keeps with a card, e.g. a
+/// customer id, a nickname or the channel it was added through, see
+/// `DataVault::store_credit_card_with_custom_metadata`
+///
+/// Stored encrypted like the card, but not meant for card data.
+pub type CustomMetadata = HashMap<String, String>;
+
+/// What the vault knows about a stored record besides its content,
+/// see `DataVault::retrieve_with_metadata`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordMetadata {
+    /// when the record was first stored, unknown for records stored
+    /// before the vault kept an index
+    pub created_at: Option<SystemTime>,
+    /// how long until the record expires, `None` if it never does
+    pub ttl: Option<Duration>,
+    /// the version `update_credit_card_if_version` expects
+    pub version: u64,
+    /// the namespace the record is stored in
+    pub tenant: String,
+}
+
+/// Whether `metadata` is within `MAX_METADATA_ENTRIES`,
+/// `MAX_METADATA_KEY_LENGTH` and `MAX_METADATA_VALUE_LENGTH`, keys
+/// can not be empty
+pub(crate) fn validate_custom_metadata(metadata: &CustomMetadata) -> Result<(), ValidationError> {
+    let valid = metadata.len() <= MAX_METADATA_ENTRIES
+        && metadata.iter().all(|(key, value)| {
+            (1..=MAX_METADATA_KEY_LENGTH).contains(&key.chars().count()) && value.chars().count() <= MAX_METADATA_VALUE_LENGTH
+        });
+
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::InvalidMetadata),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metadata::{validate_custom_metadata, CustomMetadata, MAX_METADATA_ENTRIES};
+    use crate::validation::ValidationError;
+
+    #[test]
+    fn test_validate_custom_metadata() {
+        let mut metadata = CustomMetadata::new();
+        assert_eq!(validate_custom_metadata(&metadata), Ok(()));
+        metadata.insert("customer_id".to_string(), "cus_42".to_string());
+        metadata.insert("nickname".to_string(), "Work card \u{1f4bc}".to_string());
+        assert_eq!(validate_custom_metadata(&metadata), Ok(()));
+
+        for (key, value) in [("", "x".to_string()), ("k", "x".repeat(513))] {
+            let mut invalid = metadata.clone();
+            invalid.insert(key.to_string(), value);
+            assert_eq!(validate_custom_metadata(&invalid), Err(ValidationError::InvalidMetadata));
+        }
+        let mut invalid = metadata.clone();
+        invalid.insert("k".repeat(65), String::new());
+        assert_eq!(validate_custom_metadata(&invalid), Err(ValidationError::InvalidMetadata));
+
+        let too_many: CustomMetadata = (0..=MAX_METADATA_ENTRIES).map(|i| (i.to_string(), String::new())).collect();
+        assert_eq!(validate_custom_metadata(&too_many), Err(ValidationError::InvalidMetadata));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_custom_metadata_postgres() {
+        use credit_card::CreditCard;
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::error::DataVaultError;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use crate::traits::DataVault;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("custom-metadata-test").unwrap();
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let mut metadata = CustomMetadata::new();
+        metadata.insert("customer_id".to_string(), "cus_42".to_string());
+        let token = vault.store_credit_card_with_custom_metadata(&cc, &metadata).await.unwrap();
+        let (retrieved, retrieved_metadata) = vault.retrieve_credit_card_with_custom_metadata(&token).await.unwrap();
+        assert_eq!(retrieved.number, cc.number);
+        assert_eq!(retrieved_metadata, metadata);
+
+        // the metadata follows the card to its new token
+        let new_token = vault.rotate_token(&token).await.unwrap();
+        assert_eq!(vault.retrieve_custom_metadata(&new_token).await.unwrap(), metadata);
+        assert!(matches!(vault.retrieve_custom_metadata(&token).await, Err(DataVaultError::NotFound)));
+
+        metadata.insert("nickname".to_string(), "k".repeat(513));
+        let set = vault.set_custom_metadata(&new_token, &metadata).await;
+        assert!(matches!(set, Err(DataVaultError::Validation(ValidationError::InvalidMetadata))));
+
+        vault.delete_many(std::slice::from_ref(&new_token)).await.unwrap();
+        let side = vault.with_side_namespace("metadata").unwrap();
+        assert!(!side.exists(&new_token).await.unwrap());
+    }
+}
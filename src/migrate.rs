@@ -0,0 +1,131 @@
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Called with the progress of a migration after each batch
+pub type ProgressCallback<'a> = Box<dyn FnMut(&MigrationProgress) + Send + 'a>;
+
+/// How `migrate` runs
+pub struct MigrateOptions<'a> {
+    /// records read from the source per batch
+    pub batch_size: usize,
+    /// continue a migration from `MigrationProgress::cursor`
+    pub resume_from: Option<String>,
+    /// read every record back from the destination afterwards
+    pub verify: bool,
+    /// called after each batch is stored
+    pub on_progress: Option<ProgressCallback<'a>>,
+}
+
+impl Default for MigrateOptions<'_> {
+    fn default() -> Self {
+        MigrateOptions {
+            batch_size: DEFAULT_BATCH_SIZE,
+            resume_from: None,
+            verify: true,
+            on_progress: None,
+        }
+    }
+}
+
+/// Handed to `MigrateOptions::on_progress` after each batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationProgress {
+    /// records stored in the destination by this run so far
+    pub migrated: u64,
+    /// pass this as `MigrateOptions::resume_from` to continue after
+    /// this batch, `None` once every record is migrated
+    pub cursor: Option<String>,
+}
+
+/// The outcome of `migrate`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+    /// records stored in the destination by this run
+    pub migrated: u64,
+    /// records found unchanged in the destination by the verification pass
+    pub verified: u64,
+    /// tokens missing or different in the destination
+    pub mismatched: Vec<String>,
+}
+
+/// Copy every record of `from` to `to`, e.g. from redis to postgres
+///
+/// Records are decrypted with the source's encryption and stored with
+/// the destination's, tokens are kept.  Storing is idempotent, after
+/// a failure the migration can be resumed from the cursor of the
+/// last reported progress or simply started again.  The verification
+/// pass compares every source record with the destination.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{DataVault, RedisDataVault, PostgresDataVault, MigrateOptions, migrate};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let redis = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let postgres = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let options = MigrateOptions {
+///     on_progress: Some(Box::new(|progress| println!("{} migrated, resume from {:?}", progress.migrated, progress.cursor))),
+///     ..MigrateOptions::default()
+/// };
+/// let report = migrate(&redis, &postgres, options).await.unwrap();
+/// assert!(report.mismatched.is_empty());
+/// ```
+pub async fn migrate<S, D>(from: &S, to: &D, mut options: MigrateOptions<'_>) -> Result<MigrationReport, DataVaultError>
+    where
+        S: DataVault,
+        D: DataVault,
+{
+    let mut report = MigrationReport::default();
+    let mut cursor = options.resume_from.take();
+
+    loop {
+        let page = from.decrypted_records_page(cursor.as_deref(), options.batch_size).await?;
+        for (token, data) in page.records.iter() {
+            to.store(token, data).await?;
+            report.migrated += 1;
+        }
+
+        cursor = page.next_cursor;
+        if let Some(on_progress) = options.on_progress.as_mut() {
+            on_progress(&MigrationProgress {
+                migrated: report.migrated,
+                cursor: cursor.clone(),
+            });
+        }
+
+        if cursor.is_none() {
+            break
+        }
+    }
+
+    if options.verify {
+        verify(from, to, options.batch_size, &mut report).await?;
+    }
+
+    Ok(report)
+}
+
+async fn verify<S, D>(from: &S, to: &D, batch_size: usize, report: &mut MigrationReport) -> Result<(), DataVaultError>
+    where
+        S: DataVault,
+        D: DataVault,
+{
+    let mut cursor = None;
+
+    loop {
+        let page = from.decrypted_records_page(cursor.as_deref(), batch_size).await?;
+        for (token, data) in page.records {
+            match to.try_retrieve(&token).await? {
+                Some(migrated) if migrated == data => report.verified += 1,
+                _ => report.mismatched.push(token),
+            }
+        }
+
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            return Ok(())
+        }
+    }
+}
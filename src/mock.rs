@@ -0,0 +1,554 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use futures::stream;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::{CheckResult, HealthReport};
+use crate::hooks::Operation;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::redact::redact_token;
+use crate::namespace::{side_namespace, validate_namespace, validate_token, DEFAULT_NAMESPACE};
+use crate::utils::RandomToken;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error;
+use std::fmt;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// An operation a `MockDataVault` was asked to do
+#[derive(Clone, PartialEq)]
+pub struct MockCall {
+    /// the operation, as `VaultEvent` names it
+    pub operation: Operation,
+    /// the token asked for, none for operations on many records
+    pub token: Option<String>,
+    /// the namespace of the vault asked
+    pub tenant: String,
+}
+
+impl fmt::Debug for MockCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockCall")
+            .field("operation", &self.operation)
+            .field("token", &self.token.as_deref().map(redact_token))
+            .field("tenant", &self.tenant)
+            .finish()
+    }
+}
+
+struct MockRecord {
+    data: String,
+    version: u64,
+    created_at: SystemTime,
+    expires_at: Option<SystemTime>,
+    deleted: bool,
+}
+
+impl MockRecord {
+    fn new(data: String) -> Self {
+        MockRecord {
+            data,
+            version: 1,
+            created_at: SystemTime::now(),
+            expires_at: None,
+            deleted: false,
+        }
+    }
+
+    fn is_live(&self, now: SystemTime) -> bool {
+        !self.deleted && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    // by namespace and token, so pages come in token order
+    records: BTreeMap<(String, String), MockRecord>,
+    calls: Vec<MockCall>,
+    failures: HashMap<Operation, VecDeque<DataVaultError>>,
+}
+
+/// A vault keeping its records in memory, for unit tests of code
+/// using a `DataVault` without a redis or postgres to talk to
+///
+/// Fixtures are loaded with `with_credit_card` and `with_record`,
+/// every operation is recorded, see `calls` and `assert_called`, and
+/// `fail_next` scripts the error of an upcoming operation.  Cards
+/// are kept as JSON without encryption, `iter_records` returns that
+/// JSON as the ciphertext.  Security codes are kept as given.
+/// Vaults of other namespaces made by `with_namespace` share the
+/// records and the calls.  Needs the `test-util` feature.
+/// # example
+/// ```rust
+/// use data_vault::{DataVault, MockDataVault, Operation};
+/// use credit_card::CreditCard;
+///
+/// # futures::executor::block_on(async {
+/// let vault = MockDataVault::new().unwrap().with_credit_card("tok_visa", &CreditCard {
+///     number: "4111111111111111".to_string(),
+///     cardholder_name: "Graydon Hoare".to_string(),
+///     expiration_month: "01".to_string(),
+///     expiration_year: "2023".to_string(),
+///     brand: None,
+///     security_code: None
+/// });
+/// assert_eq!(vault.retrieve_credit_card("tok_visa").await.unwrap().number, "4111111111111111");
+/// vault.assert_called(Operation::RetrieveCreditCard);
+/// vault.assert_not_called(Operation::Store);
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct MockDataVault {
+    state: Arc<Mutex<MockState>>,
+    namespace: String,
+}
+
+impl MockDataVault {
+    /// Store `credit_card` under `token` before the test runs, the
+    /// call is not recorded
+    pub fn with_credit_card(self, token: &str, credit_card: &CreditCard) -> Self {
+        let data = serde_json::to_string(credit_card).expect("credit cards serialize to JSON");
+        self.with_record(token, &data)
+    }
+
+    /// Store `string` under `token` before the test runs, the call
+    /// is not recorded
+    pub fn with_record(self, token: &str, string: &str) -> Self {
+        self.state().records.insert(self.key(token), MockRecord::new(string.to_string()));
+        self
+    }
+
+    /// Fail the next call of `operation` with `error`, errors queued
+    /// for the same operation are returned one call after another
+    pub fn fail_next(&self, operation: Operation, error: DataVaultError) {
+        self.state().failures.entry(operation).or_default().push_back(error);
+    }
+
+    /// The operations asked of this vault and those sharing its
+    /// records, in the order they were asked
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state().calls.clone()
+    }
+
+    /// How often `operation` was asked
+    pub fn call_count(&self, operation: Operation) -> usize {
+        self.state().calls.iter().filter(|call| call.operation == operation).count()
+    }
+
+    /// Panics unless `operation` was asked at least once
+    pub fn assert_called(&self, operation: Operation) {
+        assert!(self.call_count(operation) > 0, "{:?} was not called, calls: {:?}", operation, self.calls());
+    }
+
+    /// Panics unless `operation` was asked for `token`
+    pub fn assert_called_with(&self, operation: Operation, token: &str) {
+        let called = self.state().calls.iter()
+            .any(|call| call.operation == operation && call.token.as_deref() == Some(token));
+        assert!(called, "{:?} was not called with {}, calls: {:?}", operation, redact_token(token), self.calls());
+    }
+
+    /// Panics if `operation` was asked
+    pub fn assert_not_called(&self, operation: Operation) {
+        assert_eq!(self.call_count(operation), 0, "{:?} was called, calls: {:?}", operation, self.calls());
+    }
+
+    /// Forget the calls so far, the records stay
+    pub fn clear_calls(&self) {
+        self.state().calls.clear();
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        // a test panicking while holding the lock leaves the state usable
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn key(&self, token: &str) -> (String, String) {
+        (self.namespace.clone(), token.to_string())
+    }
+
+    /// record the call, failing it if an error is scripted
+    fn call(&self, state: &mut MockState, operation: Operation, token: Option<&str>) -> Result<(), DataVaultError> {
+        state.calls.push(MockCall {
+            operation,
+            token: token.map(str::to_string),
+            tenant: self.namespace.clone(),
+        });
+        match state.failures.get_mut(&operation).and_then(VecDeque::pop_front) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// the live record of `token`
+    fn live<'s>(&self, state: &'s mut MockState, token: &str) -> Result<&'s mut MockRecord, DataVaultError> {
+        let now = SystemTime::now();
+        state.records.get_mut(&self.key(token))
+            .filter(|record| record.is_live(now))
+            .ok_or(DataVaultError::NotFound)
+    }
+
+    /// the live records of the namespace after `cursor`
+    fn page(&self, state: &MockState, cursor: Option<&str>, limit: usize) -> Vec<(String, String)> {
+        let now = SystemTime::now();
+        let start = match cursor {
+            Some(cursor) => Bound::Excluded(self.key(cursor)),
+            None => Bound::Included(self.key("")),
+        };
+        state.records.range((start, Bound::Unbounded))
+            .take_while(|((namespace, _), _)| *namespace == self.namespace)
+            .filter(|(_, record)| record.is_live(now))
+            .take(limit)
+            .map(|((_, token), record)| (token.clone(), record.data.clone()))
+            .collect()
+    }
+
+    /// the records of `iter_records`, read at once, or the scripted error
+    fn iter_page(&self) -> Vec<Result<(String, String), DataVaultError>> {
+        let mut state = self.state();
+        match self.call(&mut state, Operation::ReadRecords, None) {
+            Ok(()) => self.page(&state, None, usize::MAX).into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        }
+    }
+
+    /// `store` of `data`, a new version if `token` is stored already
+    fn put(&self, state: &mut MockState, token: &str, data: String) {
+        let now = SystemTime::now();
+        match state.records.get_mut(&self.key(token)) {
+            Some(record) if record.is_live(now) => {
+                record.data = data;
+                record.version += 1;
+            },
+            _ => {
+                state.records.insert(self.key(token), MockRecord::new(data));
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl DataVault for MockDataVault {
+    /// An empty vault in the default namespace
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(MockDataVault::default())
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Store, Some(token))?;
+        validate_token(token)?;
+        self.put(&mut state, token, string.to_string());
+        Ok(())
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Store, Some(token))?;
+        validate_token(token)?;
+        if self.live(&mut state, token).is_ok() {
+            return Ok(false)
+        }
+        self.put(&mut state, token, string.to_string());
+        Ok(true)
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Store, Some(token))?;
+        validate_token(token)?;
+        self.put(&mut state, token, string.to_string());
+        self.live(&mut state, token)?.expires_at = Some(SystemTime::now() + ttl);
+        Ok(())
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        let mut state = self.state();
+        let token = RandomToken::generate();
+        self.call(&mut state, Operation::StoreCreditCard, Some(&token))?;
+        self.put(&mut state, &token, serde_json::to_string(credit_card)?);
+        Ok(token)
+    }
+
+    /// The token of a live card with the same number in the
+    /// namespace, like a deterministic tokenizer, or a new one
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        let mut state = self.state();
+        let existing = self.page(&state, None, usize::MAX).into_iter()
+            .find(|(_, data)| serde_json::from_str::<CreditCard>(data).is_ok_and(|stored| stored.number == credit_card.number))
+            .map(|(token, _)| token);
+        let token = existing.clone().unwrap_or_else(RandomToken::generate);
+        self.call(&mut state, Operation::Tokenize, Some(&token))?;
+        if existing.is_some() {
+            return Ok((token, false))
+        }
+        self.put(&mut state, &token, serde_json::to_string(credit_card)?);
+        Ok((token, true))
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::StoreCreditCard, Some(token))?;
+        validate_token(token)?;
+        if !overwrite && self.live(&mut state, token).is_ok() {
+            return Err(DataVaultError::AlreadyExists)
+        }
+        self.put(&mut state, token, serde_json::to_string(credit_card)?);
+        Ok(())
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Retrieve, Some(token))?;
+        Ok(self.live(&mut state, token)?.data.clone())
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::RetrieveCreditCard, Some(token))?;
+        Ok(serde_json::from_str(&self.live(&mut state, token)?.data)?)
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Exists, Some(token))?;
+        Ok(self.live(&mut state, token).is_ok())
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::RetrieveCreditCard, Some(token))?;
+        let record = self.live(&mut state, token)?;
+        Ok((serde_json::from_str(&record.data)?, record.version))
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::RetrieveCreditCard, Some(token))?;
+        let record = self.live(&mut state, token)?;
+        let metadata = RecordMetadata {
+            created_at: Some(record.created_at),
+            ttl: record.expires_at.and_then(|expires_at| expires_at.duration_since(SystemTime::now()).ok()),
+            version: record.version,
+            tenant: self.namespace.clone(),
+        };
+        Ok((serde_json::from_str(&record.data)?, metadata))
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::UpdateCreditCard, Some(token))?;
+        let record = self.live(&mut state, token)?;
+        if record.version != expected_version {
+            return Err(DataVaultError::Conflict)
+        }
+        record.data = serde_json::to_string(credit_card)?;
+        record.version += 1;
+        Ok(record.version)
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::RotateToken, Some(token))?;
+        self.live(&mut state, token)?;
+        let record = state.records.remove(&self.key(token)).ok_or(DataVaultError::NotFound)?;
+        let new_token = RandomToken::generate();
+        state.records.insert(self.key(&new_token), record);
+        Ok(new_token)
+    }
+
+    /// Hides the record until `purge_expired`, there is no retention
+    /// period
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::SoftDelete, Some(token))?;
+        self.live(&mut state, token)?.deleted = true;
+        Ok(())
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Touch, Some(token))?;
+        self.live(&mut state, token)?.expires_at = ttl.map(|ttl| SystemTime::now() + ttl);
+        Ok(())
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::DeleteMany, None)?;
+        let now = SystemTime::now();
+        Ok(tokens.iter()
+            .filter_map(|token| state.records.remove(&self.key(token)))
+            .filter(|record| record.is_live(now))
+            .count())
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::PurgeExpired, None)?;
+        let now = SystemTime::now();
+        let before = state.records.len();
+        let namespace = &self.namespace;
+        state.records.retain(|(record_namespace, _), record| record_namespace != namespace || record.is_live(now));
+        Ok(PurgeReport {
+            purged: (before - state.records.len()) as u64,
+            ..PurgeReport::default()
+        })
+    }
+
+    /// The records as JSON, they are not encrypted
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        Box::pin(stream::iter(self.iter_page().into_iter().map(|records| {
+            records.map(|(token, data)| (token, data.into_bytes()))
+        })))
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        Box::pin(stream::iter(self.iter_page()))
+    }
+
+    /// The cursor is the last token of the previous page
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::ReadRecords, None)?;
+        let limit = limit.max(1);
+        let records = self.page(&state, cursor, limit);
+        let next_cursor = match records.len() == limit {
+            true => records.last().map(|(token, _)| token.clone()),
+            false => None,
+        };
+        Ok(RecordPage {
+            records,
+            next_cursor,
+        })
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Count, None)?;
+        Ok(self.page(&state, None, usize::MAX).len() as u64)
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        let mut state = self.state();
+        self.call(&mut state, Operation::Stats, None)?;
+        let now = SystemTime::now();
+        let records: Vec<&MockRecord> = state.records.iter()
+            .filter(|((namespace, _), record)| *namespace == self.namespace && record.is_live(now))
+            .map(|(_, record)| record)
+            .collect();
+        Ok(VaultStats {
+            count: records.len() as u64,
+            approximate_bytes: records.iter().map(|record| record.data.len() as u64).sum(),
+            oldest: records.iter().map(|record| record.created_at).min(),
+            newest: records.iter().map(|record| record.created_at).max(),
+        })
+    }
+
+    /// Always healthy
+    async fn health_check(&self) -> HealthReport {
+        let healthy = CheckResult {
+            healthy: true,
+            latency: Duration::from_secs(0),
+            error: None,
+        };
+        HealthReport {
+            backend: self.backend(),
+            connection: healthy.clone(),
+            encryption: healthy,
+            checked_at: SystemTime::now(),
+        }
+    }
+
+    fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn backend(&self) -> &'static str {
+        "mock"
+    }
+
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        validate_namespace(namespace)?;
+        Ok(MockDataVault {
+            state: self.state.clone(),
+            namespace: namespace.to_string(),
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        let mut side = self.with_namespace(DEFAULT_NAMESPACE)?;
+        side.namespace = side_namespace(&self.namespace, kind);
+        Ok(side)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use futures::TryStreamExt;
+    use crate::traits::DataVault;
+    use crate::error::DataVaultError;
+    use crate::hooks::Operation;
+    use crate::mock::MockDataVault;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_data_vault() {
+        futures::executor::block_on(async {
+            let cc = CreditCard {
+                number: "4111111111111111".to_string(),
+                cardholder_name: "Graydon Hoare".to_string(),
+                expiration_month: "01".to_string(),
+                expiration_year: "2023".to_string(),
+                brand: None,
+                security_code: None
+            };
+            let vault = MockDataVault::new().unwrap()
+                .with_credit_card("tok_visa", &cc)
+                .with_record("tok_text", "{number: 123}");
+            assert!(vault.calls().is_empty());
+
+            assert_eq!(vault.retrieve_credit_card("tok_visa").await.unwrap().number, cc.number);
+            assert_eq!(vault.tokenize(&cc).await.unwrap(), ("tok_visa".to_string(), false));
+            assert!(matches!(vault.retrieve("missing").await, Err(DataVaultError::NotFound)));
+            vault.assert_called_with(Operation::RetrieveCreditCard, "tok_visa");
+            vault.assert_not_called(Operation::Store);
+            assert_eq!(vault.calls().len(), 3);
+
+            vault.fail_next(Operation::Retrieve, DataVaultError::AccessDenied);
+            assert!(matches!(vault.retrieve("tok_text").await, Err(DataVaultError::AccessDenied)));
+            assert_eq!(vault.retrieve("tok_text").await.unwrap(), "{number: 123}");
+
+            let (_, version) = vault.retrieve_credit_card_with_version("tok_visa").await.unwrap();
+            assert_eq!(vault.update_credit_card_if_version("tok_visa", &cc, version).await.unwrap(), version + 1);
+            assert!(matches!(vault.update_credit_card_if_version("tok_visa", &cc, version).await, Err(DataVaultError::Conflict)));
+
+            let tenant = vault.with_namespace("tenant").unwrap();
+            assert!(!tenant.exists("tok_visa").await.unwrap());
+            let token = tenant.store_credit_card(&cc).await.unwrap();
+            assert_eq!(tenant.count().await.unwrap(), 1);
+            assert_eq!(vault.count().await.unwrap(), 2);
+
+            let page = vault.decrypted_records_page(None, 1).await.unwrap();
+            assert_eq!(page.records[0].0, "tok_text");
+            let page = vault.decrypted_records_page(page.next_cursor.as_deref(), 1).await.unwrap();
+            assert_eq!(page.records[0].0, "tok_visa");
+            let records: Vec<(String, String)> = vault.iter_decrypted_records().try_collect().await.unwrap();
+            assert_eq!(records.len(), 2);
+
+            tenant.soft_delete(&token).await.unwrap();
+            vault.touch("tok_text", Some(Duration::from_secs(0))).await.unwrap();
+            assert_eq!(vault.purge_expired().await.unwrap().purged, 1);
+            assert_eq!(tenant.purge_expired().await.unwrap().purged, 1);
+            assert_eq!(vault.delete_many(&["tok_visa".to_string()]).await.unwrap(), 1);
+            assert_eq!(vault.count().await.unwrap(), 0);
+
+            vault.clear_calls();
+            assert!(vault.calls().is_empty());
+        })
+    }
+}
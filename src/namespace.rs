@@ -0,0 +1,114 @@
+// only `side_namespace` is used without a back end
+#![cfg_attr(not(any(feature = "redis", feature = "postgres", feature = "test-util", feature = "remote")), allow(dead_code))]
+
+use crate::error::DataVaultError;
+
+/// The namespace of a vault that was not scoped with
+/// `DataVault::with_namespace`, records stored before namespaces
+/// existed live here
+pub const DEFAULT_NAMESPACE: &str = "";
+
+// the postgres tenant column is varchar(64)
+const MAX_NAMESPACE_LENGTH: usize = 64;
+// the postgres token column is varchar(64)
+const MAX_TOKEN_LENGTH: usize = 64;
+// keys of the vault itself live under this prefix in redis
+const RESERVED_NAMESPACE: &str = "data_vault";
+// joins a namespace and the kind of its side namespace, never part
+// of a namespace a caller chooses
+const SIDE_SEPARATOR: char = '~';
+
+/// Namespaces are ASCII letters, digits, `-`, `_` and `.` up to 64
+/// characters.  They end up in redis keys so the `:` separator is
+/// never allowed, which keeps two namespaces from sharing a key, nor
+/// is the `~` of side namespaces, see `side_namespace`.
+pub(crate) fn validate_namespace(namespace: &str) -> Result<(), DataVaultError> {
+    let valid = namespace.len() <= MAX_NAMESPACE_LENGTH
+        && namespace != RESERVED_NAMESPACE
+        && namespace.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    match valid {
+        true => Ok(()),
+        false => Err(DataVaultError::InvalidNamespace(namespace.to_string())),
+    }
+}
+
+/// The namespace keeping data of kind `kind` about the records of
+/// `namespace` apart from them, e.g. `tenant-a~bin`
+///
+/// `validate_namespace` rejects the separator, so no namespace of a
+/// caller shares storage with the side namespace of another.  A
+/// namespace too long to add the kind to is replaced by its hash,
+/// the side namespace starting with the separator then.
+pub(crate) fn side_namespace(namespace: &str, kind: &str) -> String {
+    let side = format!("{}{}{}", namespace, SIDE_SEPARATOR, kind);
+    if side.len() <= MAX_NAMESPACE_LENGTH {
+        return side
+    }
+    let hash = blake3::hash(namespace.as_bytes()).to_hex();
+    let length = MAX_NAMESPACE_LENGTH.saturating_sub(kind.len() + 2).min(hash.len());
+    format!("{}{}{}{}", SIDE_SEPARATOR, &hash[..length], SIDE_SEPARATOR, kind)
+}
+
+/// The side namespaces keeping data about one record under its
+/// token, `rotate_token` moves it to the new token, `delete_many`
+/// deletes it with the record
+#[cfg_attr(not(any(feature = "redis", feature = "postgres")), allow(dead_code))]
+pub(crate) const TOKEN_SIDE_KINDS: [&str; 4] = ["metadata", "expired", "display", "summary"];
+
+/// The side namespaces keeping short lived data about one record
+/// under its token, `rotate_token` and `delete_many` delete it
+#[cfg_attr(not(any(feature = "redis", feature = "postgres")), allow(dead_code))]
+pub(crate) const TRANSIENT_SIDE_KINDS: [&str; 1] = ["cvv"];
+
+/// Tokens chosen by a caller, see `store_credit_card_with_token`, are
+/// 1 to 64 ASCII letters, digits, `-`, `_` and `.`, like namespaces
+pub(crate) fn validate_token(token: &str) -> Result<(), DataVaultError> {
+    let valid = !token.is_empty()
+        && token.len() <= MAX_TOKEN_LENGTH
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    match valid {
+        true => Ok(()),
+        false => Err(DataVaultError::InvalidToken),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::namespace::{side_namespace, validate_namespace, validate_token, DEFAULT_NAMESPACE, MAX_NAMESPACE_LENGTH};
+
+    #[test]
+    fn test_validate_namespace() {
+        assert!(validate_namespace(DEFAULT_NAMESPACE).is_ok());
+        assert!(validate_namespace("merchant-42_eu.1").is_ok());
+        assert!(validate_namespace("merchant:42").is_err());
+        assert!(validate_namespace("data_vault").is_err());
+        assert!(validate_namespace(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_side_namespace() {
+        assert_eq!(side_namespace("acme", "summary"), "acme~summary");
+        assert_eq!(side_namespace(DEFAULT_NAMESPACE, "summary"), "~summary");
+        // no namespace of a caller is the side namespace of another
+        assert!(validate_namespace(&side_namespace("acme", "summary")).is_err());
+        assert_ne!(side_namespace("acme.summary", "summary"), side_namespace("acme", "summary"));
+
+        let long = "a".repeat(MAX_NAMESPACE_LENGTH);
+        for kind in ["metadata", "fingerprint", "summary"].iter() {
+            let side = side_namespace(&long, kind);
+            assert_eq!(side.len(), MAX_NAMESPACE_LENGTH);
+            assert!(side.starts_with('~') && side.ends_with(&format!("~{}", kind)), "{}", side);
+        }
+        assert_ne!(side_namespace(&long, "summary"), side_namespace(&"b".repeat(MAX_NAMESPACE_LENGTH), "summary"));
+    }
+
+    #[test]
+    fn test_validate_token() {
+        assert!(validate_token("tok_4242.legacy-1").is_ok());
+        assert!(validate_token("").is_err());
+        assert!(validate_token("merchant:42").is_err());
+        assert!(validate_token(&"a".repeat(65)).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use crate::record::VaultRecord;
+use crate::redact::{redact, redact_token};
+use std::fmt;
+
+/// When a payment with a network token needs a cryptogram, e.g. a
+/// Visa TAVV or a Mastercard UCAF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CryptogramRequirement {
+    /// every payment, as for tokens of a device wallet
+    #[default]
+    EveryPayment,
+    /// payments the cardholder initiates, merchant initiated
+    /// payments such as subscriptions go without
+    CustomerInitiated,
+    /// no payment needs one
+    NotRequired,
+}
+
+/// A network token, the device or merchant PAN (DPAN) a card
+/// network issues in place of the card number (FPAN)
+///
+/// Stored with `DataVault::store_record`, or with
+/// `DataVault::store_network_token` linked to the credit card it
+/// stands for.  `Debug` masks the token number like a card number.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{CryptogramRequirement, DataVault, NetworkToken, RedisDataVault};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let card_token = data_vault.store_credit_card(&credit_card).await.unwrap();
+/// let network_token = NetworkToken {
+///     token_number: "4895370012345678".to_string(),
+///     expiration_month: "08".to_string(),
+///     expiration_year: "2029".to_string(),
+///     cryptogram: CryptogramRequirement::CustomerInitiated,
+///     par: Some("V0010013019339977890123456789".to_string()),
+///     token_requestor_id: Some("40010030273".to_string()),
+///     card_token: None,
+/// };
+/// let token = data_vault.store_network_token(&card_token, &network_token).await.unwrap();
+/// ```
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkToken {
+    /// the DPAN, 13 to 19 digits like a card number
+    pub token_number: String,
+    /// the expiry of the network token, not of the card
+    pub expiration_month: String,
+    pub expiration_year: String,
+    #[serde(default)]
+    pub cryptogram: CryptogramRequirement,
+    /// the Payment Account Reference, the same for every token and
+    /// card number of one account
+    #[serde(default)]
+    pub par: Option<String>,
+    /// the id of the token requestor the network issued the token to
+    #[serde(default)]
+    pub token_requestor_id: Option<String>,
+    /// the vault token of the credit card (FPAN) the network token
+    /// stands for, see `DataVault::store_network_token`
+    #[serde(default)]
+    pub card_token: Option<String>,
+}
+
+impl VaultRecord for NetworkToken {}
+
+impl fmt::Debug for NetworkToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkToken")
+            .field("token_number", &redact(&self.token_number))
+            .field("expiration_month", &self.expiration_month)
+            .field("expiration_year", &self.expiration_year)
+            .field("cryptogram", &self.cryptogram)
+            .field("par", &self.par)
+            .field("token_requestor_id", &self.token_requestor_id)
+            .field("card_token", &self.card_token.as_deref().map(redact_token))
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use credit_card::CreditCard;
+    use crate::network_token::{CryptogramRequirement, NetworkToken};
+    use crate::traits::DataVault;
+    use crate::error::DataVaultError;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_token() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("network-token-test").unwrap();
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+        let card_token = vault.store_credit_card(&credit_card).await.unwrap();
+        let network_token = NetworkToken {
+            token_number: "4895370012345678".to_string(),
+            expiration_month: "08".to_string(),
+            expiration_year: "2029".to_string(),
+            cryptogram: CryptogramRequirement::CustomerInitiated,
+            par: Some("V0010013019339977890123456789".to_string()),
+            token_requestor_id: None,
+            card_token: None,
+        };
+        assert!(!format!("{:?}", network_token).contains("4895370012345678"));
+
+        let token = vault.store_network_token(&card_token, &network_token).await.unwrap();
+        let stored: NetworkToken = vault.retrieve_record(&token).await.unwrap();
+        assert_eq!(stored.card_token.as_deref(), Some(card_token.as_str()));
+        assert_eq!(NetworkToken { card_token: None, ..stored.clone() }, network_token);
+        assert_eq!(vault.retrieve_credit_card(stored.card_token.as_deref().unwrap()).await.unwrap().number, credit_card.number);
+
+        let unlinked = vault.store_network_token("no-such-card", &network_token).await;
+        assert!(matches!(unlinked, Err(DataVaultError::NotFound)));
+
+        vault.delete_many(&[token, card_token]).await.unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,377 @@
+//! JSON Schemas of the records a vault stores and an OpenAPI document
+//! of the tokenization service, for generating typed clients in other
+//! languages
+//!
+//! The schemas describe the JSON the records are serialized to, e.g.
+//! by the routes of `data_vault::axum` and `data_vault::server`.
+//! They are written by hand rather than derived, so foreign types
+//! such as `CreditCard` and the service's ad hoc JSON bodies are
+//! described as well, the tests check them against the serialized
+//! records.
+//!
+//! # example
+//! ```rust
+//! use data_vault::openapi::{json_schema, openapi_document};
+//!
+//! let credit_card = json_schema("CreditCard").unwrap();
+//! assert_eq!(credit_card["required"][0], "number");
+//! let document = openapi_document().to_string();
+//! ```
+
+use serde_json::{json, Map, Value};
+
+/// The JSON Schema dialect of the schemas, the one OpenAPI 3.1 uses
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The names of the schemas `json_schema` knows, the component
+/// names in `openapi_document`
+pub const SCHEMA_NAMES: &[&str] = &["CreditCard", "MaskedCreditCard", "NetworkToken", "WalletPayload", "BankAccount", "PiiRecord", "Track2Data", "EmvPayload", "TokenResponse", "ErrorResponse"];
+
+/// The JSON Schema of the type `name`, see `SCHEMA_NAMES`, with its
+/// `$schema` dialect
+/// returns:
+///     * `None` for names not in `SCHEMA_NAMES`
+pub fn json_schema(name: &str) -> Option<Value> {
+    let mut schema = schema(name)?;
+    schema.as_object_mut()?.insert("$schema".to_string(), JSON_SCHEMA_DIALECT.into());
+    Some(schema)
+}
+
+/// Every schema of `SCHEMA_NAMES` by name, as OpenAPI components
+pub fn components() -> Map<String, Value> {
+    SCHEMA_NAMES.iter()
+        .filter_map(|name| Some((name.to_string(), schema(name)?)))
+        .collect()
+}
+
+/// An OpenAPI 3.1 document of the routes of `data_vault::server`
+///
+/// The tokenization routes take an API key as a bearer token, the
+/// health check does not.  Errors of every route are an
+/// `ErrorResponse`.
+pub fn openapi_document() -> Value {
+    let error = |description: &str| json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/ErrorResponse" } } },
+    });
+    let token = json!({
+        "name": "token",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" },
+    });
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Data Vault",
+            "description": "Tokenization of credit cards",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "jsonSchemaDialect": JSON_SCHEMA_DIALECT,
+        "security": [{ "apiKey": [] }],
+        "paths": {
+            "/tokens": {
+                "post": {
+                    "operationId": "tokenize",
+                    "summary": "Store a credit card, or find the token it is stored under",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/CreditCard" } } },
+                    },
+                    "responses": {
+                        "201": {
+                            "description": "the card was stored",
+                            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/TokenResponse" } } },
+                        },
+                        "200": {
+                            "description": "the card was stored already",
+                            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/TokenResponse" } } },
+                        },
+                        "400": error("the body is not a credit card"),
+                        "401": error("missing or unknown API key"),
+                        "422": error("the card number is invalid, or the vault does not store security codes"),
+                    },
+                },
+            },
+            "/tokens/{token}": {
+                "get": {
+                    "operationId": "detokenize",
+                    "summary": "The credit card stored under a token",
+                    "parameters": [token],
+                    "responses": {
+                        "200": {
+                            "description": "the credit card",
+                            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/CreditCard" } } },
+                        },
+                        "401": error("missing or unknown API key"),
+                        "404": error("nothing is stored under the token"),
+                    },
+                },
+                "delete": {
+                    "operationId": "delete",
+                    "summary": "Delete the record of a token",
+                    "parameters": [token],
+                    "responses": {
+                        "204": { "description": "the record was deleted" },
+                        "401": error("missing or unknown API key"),
+                        "404": error("nothing is stored under the token"),
+                    },
+                },
+            },
+            "/health": {
+                "get": {
+                    "operationId": "health",
+                    "summary": "Whether the back end answers and the key material works",
+                    "security": [],
+                    "responses": {
+                        "200": { "description": "healthy" },
+                        "503": { "description": "unhealthy" },
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": components(),
+            "securitySchemes": {
+                "apiKey": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+/// `name`'s schema without `$schema`
+fn schema(name: &str) -> Option<Value> {
+    let string = json!({ "type": "string" });
+    let optional_string = json!({ "type": ["string", "null"] });
+    let digits = |description: &str| json!({ "type": "string", "pattern": "^[0-9]{12,19}$", "description": description });
+    let month = json!({ "type": "string", "description": "1 to 12, stored as 2 digits, e.g. `01`" });
+    let year = json!({ "type": "string", "description": "2 or 4 digits, stored as 4 digits" });
+
+    Some(match name {
+        "CreditCard" => json!({
+            "title": "CreditCard",
+            "type": "object",
+            "properties": {
+                "number": digits("the card number (PAN), with a valid Luhn check digit"),
+                "cardholder_name": string,
+                "expiration_month": month,
+                "expiration_year": year,
+                "brand": {
+                    "type": ["string", "null"],
+                    "description": "e.g. `visa` or `amex`, filled in from the number when absent",
+                },
+                "security_code": {
+                    "type": ["string", "null"],
+                    "description": "stripped, rejected or expired by the vault's security code policy",
+                },
+            },
+            "required": ["number", "cardholder_name", "expiration_month", "expiration_year"],
+        }),
+        "MaskedCreditCard" => json!({
+            "title": "MaskedCreditCard",
+            "type": "object",
+            "properties": {
+                "brand": optional_string,
+                "number": {
+                    "type": "string",
+                    "description": "all but the first 6 and last 4 digits masked, e.g. `411111******1111`",
+                },
+                "cardholder_name": string,
+                "expiration_month": month,
+                "expiration_year": year,
+            },
+            "required": ["number", "cardholder_name", "expiration_month", "expiration_year"],
+        }),
+        "NetworkToken" => json!({
+            "title": "NetworkToken",
+            "type": "object",
+            "properties": {
+                "token_number": digits("the network token (DPAN)"),
+                "expiration_month": month,
+                "expiration_year": year,
+                "cryptogram": {
+                    "enum": ["every_payment", "customer_initiated", "not_required"],
+                    "default": "every_payment",
+                },
+                "par": { "type": ["string", "null"], "description": "the Payment Account Reference" },
+                "token_requestor_id": optional_string,
+                "card_token": { "type": ["string", "null"], "description": "the vault token of the card the network token stands for" },
+            },
+            "required": ["token_number", "expiration_month", "expiration_year"],
+        }),
+        "WalletPayload" => json!({
+            "title": "WalletPayload",
+            "type": "object",
+            "properties": {
+                "wallet": { "enum": ["apple_pay", "google_pay"] },
+                "device_pan": digits("the device account number (DPAN)"),
+                "expiration_month": month,
+                "expiration_year": year,
+                "cryptogram": { "type": "string", "description": "the online payment cryptogram, base64 encoded" },
+                "eci": optional_string,
+                "cryptogram_type": optional_string,
+            },
+            "required": ["wallet", "device_pan", "expiration_month", "expiration_year", "cryptogram"],
+        }),
+        "BankAccount" => json!({
+            "title": "BankAccount",
+            "type": "object",
+            "properties": {
+                "routing_number": {
+                    "type": "string",
+                    "pattern": "^[0-9]{9}$",
+                    "description": "the ABA routing transit number, passing its checksum",
+                },
+                "account_number": { "type": "string", "pattern": "^[0-9]{4,17}$" },
+                "account_type": { "enum": ["checking", "savings"], "default": "checking" },
+                "holder_name": string,
+            },
+            "required": ["routing_number", "account_number", "holder_name"],
+        }),
+        "PiiRecord" => json!({
+            "title": "PiiRecord",
+            "type": "object",
+            "properties": {
+                "fields": {
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "object",
+                        "properties": {
+                            "value": string,
+                            "sensitivity": { "enum": ["public", "confidential", "restricted"], "default": "confidential" },
+                            "retention_secs": {
+                                "type": ["integer", "null"],
+                                "description": "seconds after `created_at` the field is kept, absent for as long as the record",
+                            },
+                        },
+                        "required": ["value"],
+                    },
+                },
+                "created_at": { "type": "integer", "description": "seconds since the unix epoch" },
+            },
+            "required": ["fields", "created_at"],
+        }),
+        "Track2Data" => json!({
+            "title": "Track2Data",
+            "type": "object",
+            "properties": {
+                "data": {
+                    "type": "string",
+                    "pattern": "^;?[0-9]{12,19}[=D][0-9]{7}[0-9]*F?\\??$",
+                    "description": "track 2 or EMV tag 57, the card number, expiry and service code",
+                },
+                "ttl_secs": { "type": "integer", "minimum": 1, "maximum": 300, "default": 60 },
+            },
+            "required": ["data"],
+        }),
+        "EmvPayload" => json!({
+            "title": "EmvPayload",
+            "type": "object",
+            "properties": {
+                "tlv": {
+                    "type": "string",
+                    "pattern": "^([0-9A-Fa-f]{2})+$",
+                    "description": "hex encoded BER-TLV",
+                },
+                "ttl_secs": { "type": "integer", "minimum": 1, "maximum": 300, "default": 60 },
+            },
+            "required": ["tlv"],
+        }),
+        "TokenResponse" => json!({
+            "title": "TokenResponse",
+            "type": "object",
+            "properties": {
+                "token": string,
+            },
+            "required": ["token"],
+        }),
+        "ErrorResponse" => json!({
+            "title": "ErrorResponse",
+            "type": "object",
+            "properties": {
+                "error": { "type": "string", "description": "what went wrong, card numbers and tokens masked" },
+            },
+            "required": ["error"],
+        }),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::openapi::{components, json_schema, openapi_document, SCHEMA_NAMES};
+    use crate::bank_account::{AccountType, BankAccount};
+    use crate::masked::MaskedCreditCard;
+    use crate::pii::{PiiField, PiiRecord};
+    use crate::card_present::{EmvPayload, Track2Data};
+    use crate::network_token::{CryptogramRequirement, NetworkToken};
+    use crate::wallet::{Wallet, WalletPayload};
+    use serde_json::Value;
+
+    /// the properties of `value` are those of the schema `name`,
+    /// with the required ones among them
+    fn assert_matches(name: &str, value: Value) {
+        let schema = json_schema(name).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        let fields = value.as_object().unwrap();
+        assert_eq!(properties.keys().collect::<Vec<_>>(), fields.keys().collect::<Vec<_>>(), "{}", name);
+        for required in schema["required"].as_array().unwrap() {
+            assert!(!fields[required.as_str().unwrap()].is_null(), "{} {}", name, required);
+        }
+    }
+
+    #[test]
+    fn test_schemas() {
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+        assert_matches("CreditCard", serde_json::to_value(&credit_card).unwrap());
+        assert_matches("MaskedCreditCard", serde_json::to_value(MaskedCreditCard::from(&credit_card)).unwrap());
+        assert_matches("NetworkToken", serde_json::to_value(NetworkToken {
+            token_number: "4895370012345678".to_string(),
+            expiration_month: "08".to_string(),
+            expiration_year: "2029".to_string(),
+            cryptogram: CryptogramRequirement::NotRequired,
+            par: None,
+            token_requestor_id: None,
+            card_token: None,
+        }).unwrap());
+        assert_matches("WalletPayload", serde_json::to_value(WalletPayload {
+            wallet: Wallet::ApplePay,
+            device_pan: "4895370012345678".to_string(),
+            expiration_month: "08".to_string(),
+            expiration_year: "2029".to_string(),
+            cryptogram: "AgAAAAAABk4DWZ4C28yUQAAAAAA=".to_string(),
+            eci: None,
+            cryptogram_type: None,
+        }).unwrap());
+        assert_matches("BankAccount", serde_json::to_value(BankAccount {
+            routing_number: "011000015".to_string(),
+            account_number: "000123456789".to_string(),
+            account_type: AccountType::Checking,
+            holder_name: "Graydon Hoare".to_string(),
+        }).unwrap());
+        assert_matches("PiiRecord", serde_json::to_value(PiiRecord::new()
+            .with_field("ssn", PiiField::new("078051120"))).unwrap());
+        assert_matches("Track2Data", serde_json::to_value(Track2Data::new(";4111111111111111=25121010000012300000?")).unwrap());
+        assert_matches("EmvPayload", serde_json::to_value(EmvPayload::new("9F2608A1B2C3D4E5F60718")).unwrap());
+        assert!(json_schema("Passport").is_none());
+
+        let document = openapi_document();
+        assert_eq!(document["components"]["schemas"].as_object().unwrap().len(), SCHEMA_NAMES.len());
+        assert_eq!(components().len(), SCHEMA_NAMES.len());
+        // every reference resolves
+        let text = document.to_string();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(SCHEMA_NAMES.contains(&name), "{}", name);
+        }
+    }
+}
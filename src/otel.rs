@@ -0,0 +1,305 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::hooks::Outcome;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::metrics::Histogram;
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+use std::error;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// the instrumentation scope of the spans and metrics
+const SCOPE: &str = "data_vault";
+
+/// A vault reporting each operation of the wrapped vault to
+/// OpenTelemetry, see `DataVault::with_otel`
+///
+/// Each operation is a client span named after the method called,
+/// a child of the current context, with the attributes of the
+/// database semantic conventions, `db.system`, `redis` or
+/// `postgresql`, and `db.operation.name`, and `data_vault.tenant`.
+/// Failed operations have an error status and an `error.type`,
+/// `not_found`, `conflict` or `failure`.  The duration of every
+/// operation is recorded in the `db.client.operation.duration`
+/// histogram, in seconds, with the same attributes.  Card data and
+/// tokens are never recorded.
+///
+/// The spans and metrics go to the global tracer and meter
+/// providers as they are when the vault is wrapped, the
+/// application installs them with an exporter, e.g. OTLP from the
+/// `opentelemetry-otlp` crate, to have the vault show up next to
+/// its other services.
+///
+/// `iter_records` and `iter_decrypted_records` are not reported
+/// because they are lazy, `decrypted_records_page` is.
+pub struct OtelDataVault<V> {
+    inner: V,
+    tracer: Arc<BoxedTracer>,
+    duration: Histogram<f64>,
+}
+
+impl<V> OtelDataVault<V>
+    where
+        V: DataVault,
+{
+    pub(crate) fn new(inner: V) -> Self {
+        let duration = global::meter(SCOPE)
+            .f64_histogram("db.client.operation.duration")
+            .with_unit("s")
+            .with_description("Duration of data vault operations")
+            .build();
+        OtelDataVault {
+            inner,
+            tracer: Arc::new(global::tracer(SCOPE)),
+            duration,
+        }
+    }
+
+    /// The wrapped vault, operations on it are not reported
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// the `db.system` of the wrapped vault
+    fn db_system(&self) -> &'static str {
+        match self.inner.backend() {
+            "postgres" => "postgresql",
+            backend => backend,
+        }
+    }
+
+    async fn reported<F, R>(&self, operation: &'static str, f: F) -> Result<R, DataVaultError>
+        where
+            F: Future<Output = Result<R, DataVaultError>>,
+    {
+        let mut attributes = vec![
+            KeyValue::new("db.system", self.db_system()),
+            KeyValue::new("db.operation.name", operation),
+            KeyValue::new("data_vault.tenant", self.inner.namespace().to_string()),
+        ];
+        let span = self.tracer
+            .span_builder(operation)
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes.clone())
+            .start(&*self.tracer);
+        let cx = Context::current_with_span(span);
+
+        let started = Instant::now();
+        let result = f.with_context(cx.clone()).await;
+        let error_type = match Outcome::from(&result) {
+            Outcome::Success => None,
+            Outcome::NotFound => Some("not_found"),
+            Outcome::Conflict => Some("conflict"),
+            Outcome::Failure => Some("failure"),
+        };
+        if let Some(error_type) = error_type {
+            attributes.push(KeyValue::new("error.type", error_type));
+            cx.span().set_attribute(KeyValue::new("error.type", error_type));
+            cx.span().set_status(Status::error(error_type));
+        }
+        self.duration.record(started.elapsed().as_secs_f64(), &attributes);
+        cx.span().end();
+        result
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for OtelDataVault<V>
+    where
+        V: DataVault,
+{
+    /// A vault from the environment reporting its operations
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(OtelDataVault::new(V::new()?))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.reported("store", self.inner.store(token, string)).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.reported("store_if_absent", self.inner.store_if_absent(token, string)).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.reported("store_expiring", self.inner.store_expiring(token, string, ttl)).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.reported("store_credit_card", self.inner.store_credit_card(credit_card)).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.reported("tokenize", self.inner.tokenize(credit_card)).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        let store = self.inner.store_credit_card_with_token(token, credit_card, overwrite);
+        self.reported("store_credit_card_with_token", store).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.reported("retrieve", self.inner.retrieve(token)).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.reported("retrieve_credit_card", self.inner.retrieve_credit_card(token)).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.reported("exists", self.inner.exists(token)).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.reported("retrieve_credit_card_with_version", self.inner.retrieve_credit_card_with_version(token)).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.reported("retrieve_with_metadata", self.inner.retrieve_with_metadata(token)).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let update = self.inner.update_credit_card_if_version(token, credit_card, expected_version);
+        self.reported("update_credit_card_if_version", update).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.reported("rotate_token", self.inner.rotate_token(token)).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.reported("soft_delete", self.inner.soft_delete(token)).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.reported("touch", self.inner.touch(token, ttl)).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.reported("delete_many", self.inner.delete_many(tokens)).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.reported("purge_expired", self.inner.purge_expired()).await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.reported("decrypted_records_page", self.inner.decrypted_records_page(cursor, limit)).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.reported("count", self.inner.count()).await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.reported("stats", self.inner.stats()).await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault reports its operations the same way
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(OtelDataVault {
+            inner: self.inner.with_namespace(namespace)?,
+            tracer: self.tracer.clone(),
+            duration: self.duration.clone(),
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(OtelDataVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            tracer: self.tracer.clone(),
+            duration: self.duration.clone(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "redis", feature = "rt-tokio"))]
+mod test {
+    use crate::traits::DataVault;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::tokenizer::Blake3Tokenizer;
+    use opentelemetry::trace::{SpanKind, Status};
+    use opentelemetry::{global, KeyValue};
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    #[tokio::test]
+    async fn test_otel() {
+        let spans = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder().with_simple_exporter(spans.clone()).build();
+        global::set_tracer_provider(tracer_provider.clone());
+        let metrics = InMemoryMetricExporter::default();
+        let meter_provider = SdkMeterProvider::builder().with_periodic_exporter(metrics.clone()).build();
+        global::set_meter_provider(meter_provider.clone());
+
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_otel()
+            .with_namespace("otel-test").unwrap();
+        vault.store("otel-token", "{number: 4111111111111111}").await.unwrap();
+        assert!(vault.retrieve("otel-missing").await.is_err());
+        vault.inner().delete_many(&["otel-token".to_string()]).await.unwrap();
+
+        tracer_provider.force_flush().unwrap();
+        let spans = spans.get_finished_spans().unwrap();
+        assert_eq!(spans.iter().map(|span| span.name.as_ref()).collect::<Vec<_>>(), vec!["store", "retrieve"]);
+        assert!(spans.iter().all(|span| span.span_kind == SpanKind::Client));
+        for attribute in &[
+            KeyValue::new("db.system", "redis"),
+            KeyValue::new("db.operation.name", "store"),
+            KeyValue::new("data_vault.tenant", "otel-test"),
+        ] {
+            assert!(spans[0].attributes.contains(attribute), "{:?} not in {:?}", attribute, spans[0].attributes);
+        }
+        assert_eq!(spans[0].status, Status::Unset);
+        assert!(spans[1].attributes.contains(&KeyValue::new("error.type", "not_found")));
+        assert_eq!(spans[1].status, Status::error("not_found"));
+        let recorded = format!("{:?}", spans);
+        assert!(!recorded.contains("otel-token") && !recorded.contains("4111111111111111"));
+
+        meter_provider.force_flush().unwrap();
+        let finished = metrics.get_finished_metrics().unwrap();
+        let duration = finished.iter()
+            .flat_map(|resource| resource.scope_metrics())
+            .flat_map(|scope| scope.metrics())
+            .find(|metric| metric.name() == "db.client.operation.duration")
+            .expect("the duration histogram");
+        assert_eq!(duration.unit(), "s");
+        match duration.data() {
+            AggregatedMetrics::F64(MetricData::Histogram(histogram)) => {
+                assert_eq!(histogram.data_points().map(|point| point.count()).sum::<u64>(), 2);
+            },
+            data => panic!("not a histogram {:?}", data),
+        }
+    }
+}
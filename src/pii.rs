@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use crate::masked::mask_number;
+use crate::record::VaultRecord;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How much of a `PiiField` may be shown outside the vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sensitivity {
+    /// shown as is, e.g. a state of issue
+    Public,
+    /// shown with all but its last 4 characters masked, e.g. a
+    /// driver license number
+    #[default]
+    Confidential,
+    /// never shown, e.g. a social security number
+    Restricted,
+}
+
+/// One named value of a `PiiRecord`
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PiiField {
+    pub value: String,
+    #[serde(default)]
+    pub sensitivity: Sensitivity,
+    /// how many seconds after the record was made the field is
+    /// kept, `None` for as long as the record
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+}
+
+impl PiiField {
+    /// A `Sensitivity::Confidential` field kept as long as its record
+    pub fn new(value: &str) -> Self {
+        PiiField {
+            value: value.to_string(),
+            sensitivity: Sensitivity::default(),
+            retention_secs: None,
+        }
+    }
+
+    pub fn with_sensitivity(self, sensitivity: Sensitivity) -> Self {
+        PiiField { sensitivity, ..self }
+    }
+
+    pub fn with_retention(self, retention: Duration) -> Self {
+        PiiField { retention_secs: Some(retention.as_secs()), ..self }
+    }
+
+    /// The value as its `sensitivity` allows showing it
+    pub fn display_value(&self) -> String {
+        match self.sensitivity {
+            Sensitivity::Public => self.value.clone(),
+            Sensitivity::Confidential => mask_number(&self.value),
+            Sensitivity::Restricted => "****".to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for PiiField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PiiField")
+            .field("value", &self.display_value())
+            .field("sensitivity", &self.sensitivity)
+            .field("retention_secs", &self.retention_secs)
+            .finish()
+    }
+}
+
+/// Personal data other than cards, e.g. a social security number,
+/// national id or driver license, as named fields each with its own
+/// sensitivity and retention
+///
+/// Stored with `DataVault::store_record`, encrypted and tokenized like
+/// any other record.  The record expires with the field kept longest,
+/// fields past their own retention are dropped with `retain_current`
+/// when it is read.  `Debug` shows each field as its `Sensitivity`
+/// allows.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{DataVault, PiiField, PiiRecord, RedisDataVault, Sensitivity};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::time::{Duration, SystemTime};
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let record = PiiRecord::new()
+///     .with_field("ssn", PiiField::new("078-05-1120").with_sensitivity(Sensitivity::Restricted))
+///     .with_field("state", PiiField::new("NY").with_sensitivity(Sensitivity::Public))
+///     .with_field("license", PiiField::new("123456789").with_retention(Duration::from_secs(86400)));
+/// let token = data_vault.store_record(&record).await.unwrap();
+/// let mut record: PiiRecord = data_vault.retrieve_record(&token).await.unwrap();
+/// record.retain_current(SystemTime::now());
+/// ```
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PiiRecord {
+    pub fields: BTreeMap<String, PiiField>,
+    /// when the record was made, in seconds since the unix epoch,
+    /// field retention counts from it
+    pub created_at: u64,
+}
+
+impl PiiRecord {
+    /// A record without fields made now
+    pub fn new() -> Self {
+        PiiRecord {
+            fields: BTreeMap::new(),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_field(mut self, name: &str, field: PiiField) -> Self {
+        self.fields.insert(name.to_string(), field);
+        self
+    }
+
+    /// The value of the field `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|field| field.value.as_str())
+    }
+
+    /// Remove the fields whose retention is over at `now`
+    pub fn retain_current(&mut self, now: SystemTime) {
+        let now = now.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or_default();
+        let created_at = self.created_at;
+        self.fields.retain(|_, field| match field.retention_secs {
+            Some(retention) => created_at.saturating_add(retention) > now,
+            None => true,
+        });
+    }
+}
+
+impl Default for PiiRecord {
+    fn default() -> Self {
+        PiiRecord::new()
+    }
+}
+
+impl VaultRecord for PiiRecord {
+    /// The longest retention of its fields, `None` when any of them
+    /// is kept as long as the record
+    fn ttl(&self) -> Option<Duration> {
+        let mut longest = None;
+        for field in self.fields.values() {
+            let retention = field.retention_secs?;
+            longest = longest.max(Some(retention));
+        }
+        longest.map(Duration::from_secs)
+    }
+}
+
+impl fmt::Debug for PiiRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PiiRecord")
+            .field("fields", &self.fields)
+            .field("created_at", &self.created_at)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pii::{PiiField, PiiRecord, Sensitivity};
+    use crate::record::VaultRecord;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_pii_record() {
+        let mut record = PiiRecord::new()
+            .with_field("ssn", PiiField::new("078051120").with_sensitivity(Sensitivity::Restricted))
+            .with_field("state", PiiField::new("NY").with_sensitivity(Sensitivity::Public))
+            .with_field("license", PiiField::new("123456789").with_retention(Duration::from_secs(60)));
+        record.created_at = 1000;
+        assert_eq!(record.get("state"), Some("NY"));
+        assert_eq!(record.fields["license"].display_value(), "*****6789");
+
+        let debug = format!("{:?}", record);
+        assert!(!debug.contains("078051120"));
+        assert!(!debug.contains("123456789"));
+        assert!(debug.contains("NY"));
+
+        // the ssn is kept as long as the record
+        assert_eq!(record.ttl(), None);
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["fields"]["ssn"]["sensitivity"], "restricted");
+        assert_eq!(serde_json::from_value::<PiiRecord>(json).unwrap(), record);
+
+        record.retain_current(UNIX_EPOCH + Duration::from_secs(1059));
+        assert_eq!(record.get("license"), Some("123456789"));
+        record.retain_current(UNIX_EPOCH + Duration::from_secs(1060));
+        assert_eq!(record.get("license"), None);
+        assert_eq!(record.fields.len(), 2);
+
+        let record = PiiRecord::new()
+            .with_field("passport", PiiField::new("X1234567").with_retention(Duration::from_secs(60)))
+            .with_field("visa", PiiField::new("V7654321").with_retention(Duration::from_secs(3600)));
+        assert_eq!(record.ttl(), Some(Duration::from_secs(3600)));
+    }
+}
@@ -0,0 +1,335 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::hooks::Operation;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use futures::stream;
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Decides which caller may run which operation
+///
+/// Consulted by `PolicyEnforcedVault` before every operation, a
+/// denied operation fails with `DataVaultError::AccessDenied`
+/// without reaching the vault.  Closures taking
+/// `(caller, operation, tenant)` are policies too.
+pub trait AccessPolicy: Send + Sync {
+    fn is_allowed(&self, caller: &str, operation: Operation, tenant: &str) -> bool;
+}
+
+impl<F> AccessPolicy for F
+    where
+        F: Fn(&str, Operation, &str) -> bool + Send + Sync,
+{
+    fn is_allowed(&self, caller: &str, operation: Operation, tenant: &str) -> bool {
+        self(caller, operation, tenant)
+    }
+}
+
+/// Allows each caller a fixed set of operations in every
+/// namespace, callers that were never allowed anything are denied
+/// # example
+/// ```rust
+/// use data_vault::{CallerPolicy, Operation};
+///
+/// let policy = CallerPolicy::default()
+///     .allow("checkout-service", &[Operation::StoreCreditCard, Operation::Tokenize])
+///     .allow("billing-service", &[Operation::RetrieveCreditCard])
+///     .allow("support-portal", &[Operation::Exists]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CallerPolicy {
+    allowed: HashMap<String, HashSet<Operation>>,
+}
+
+impl CallerPolicy {
+    /// Allow `caller` to run `operations` in addition to what it
+    /// was allowed before
+    pub fn allow(mut self, caller: &str, operations: &[Operation]) -> Self {
+        self.allowed.entry(caller.to_string())
+            .or_default()
+            .extend(operations.iter().copied());
+        self
+    }
+}
+
+impl AccessPolicy for CallerPolicy {
+    fn is_allowed(&self, caller: &str, operation: Operation, _tenant: &str) -> bool {
+        self.allowed.get(caller)
+            .map(|operations| operations.contains(&operation))
+            .unwrap_or(false)
+    }
+}
+
+/// A vault running only the operations its `AccessPolicy` allows
+/// for its caller
+///
+/// Combine with `AuditLog::audited` around it to also record
+/// denied attempts.
+/// # example
+/// ```rust
+/// use data_vault::{DataVault, RedisDataVault, CallerPolicy, Operation, PolicyEnforcedVault};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let policy = CallerPolicy::default().allow("support-portal", &[Operation::Exists]);
+/// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let vault = PolicyEnforcedVault::with_policy(vault, policy, "support-portal");
+/// ```
+pub struct PolicyEnforcedVault<V, P> {
+    inner: V,
+    policy: Arc<P>,
+    caller: String,
+    // the namespace the policy is asked about, a side vault's is
+    // that of the vault it was scoped from
+    tenant: String,
+}
+
+impl<V, P> PolicyEnforcedVault<V, P>
+    where
+        V: DataVault,
+        P: AccessPolicy,
+{
+    /// `vault` checking each operation of `caller` with `policy`
+    pub fn with_policy(vault: V, policy: P, caller: &str) -> Self {
+        PolicyEnforcedVault {
+            tenant: vault.namespace().to_string(),
+            inner: vault,
+            policy: Arc::new(policy),
+            caller: caller.to_string(),
+        }
+    }
+
+    /// The wrapped vault, operations on it are not checked
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// The caller the policy is asked about
+    pub fn caller(&self) -> &str {
+        &self.caller
+    }
+
+    fn authorize(&self, operation: Operation) -> Result<(), DataVaultError> {
+        match self.policy.is_allowed(&self.caller, operation, &self.tenant) {
+            true => Ok(()),
+            false => Err(DataVaultError::AccessDenied),
+        }
+    }
+}
+
+#[async_trait]
+impl<V, P> DataVault for PolicyEnforcedVault<V, P>
+    where
+        V: DataVault,
+        P: AccessPolicy,
+{
+    /// Always fails, a policy and caller can not come from the
+    /// environment, use `PolicyEnforcedVault::with_policy`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Err("PolicyEnforcedVault needs a policy and caller, use PolicyEnforcedVault::with_policy".into())
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.authorize(Operation::Store)?;
+        self.inner.store(token, string).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.authorize(Operation::Store)?;
+        self.inner.store_if_absent(token, string).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.authorize(Operation::Store)?;
+        self.inner.store_expiring(token, string, ttl).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.authorize(Operation::StoreCreditCard)?;
+        self.inner.store_credit_card(credit_card).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.authorize(Operation::Tokenize)?;
+        self.inner.tokenize(credit_card).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.authorize(Operation::StoreCreditCard)?;
+        self.inner.store_credit_card_with_token(token, credit_card, overwrite).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.authorize(Operation::Retrieve)?;
+        self.inner.retrieve(token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.authorize(Operation::RetrieveCreditCard)?;
+        self.inner.retrieve_credit_card(token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.authorize(Operation::Exists)?;
+        self.inner.exists(token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.authorize(Operation::RetrieveCreditCard)?;
+        self.inner.retrieve_credit_card_with_version(token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.authorize(Operation::RetrieveCreditCard)?;
+        self.inner.retrieve_with_metadata(token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.authorize(Operation::UpdateCreditCard)?;
+        self.inner.update_credit_card_if_version(token, credit_card, expected_version).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.authorize(Operation::RotateToken)?;
+        self.inner.rotate_token(token).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.authorize(Operation::SoftDelete)?;
+        self.inner.soft_delete(token).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.authorize(Operation::Touch)?;
+        self.inner.touch(token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.authorize(Operation::DeleteMany)?;
+        self.inner.delete_many(tokens).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.authorize(Operation::PurgeExpired)?;
+        self.inner.purge_expired().await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        match self.authorize(Operation::ReadRecords) {
+            Ok(()) => self.inner.iter_records(),
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+        }
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        match self.authorize(Operation::ReadRecords) {
+            Ok(()) => self.inner.iter_decrypted_records(),
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+        }
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.authorize(Operation::ReadRecords)?;
+        self.inner.decrypted_records_page(cursor, limit).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.authorize(Operation::Count)?;
+        self.inner.count().await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.authorize(Operation::Stats)?;
+        self.inner.stats().await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault checks the same policy for the same caller
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        let inner = self.inner.with_namespace(namespace)?;
+        Ok(PolicyEnforcedVault {
+            tenant: inner.namespace().to_string(),
+            inner,
+            policy: self.policy.clone(),
+            caller: self.caller.clone(),
+        })
+    }
+
+    /// The side vault still checks each operation, asking the policy
+    /// about the namespace of this vault, not the side namespace
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(PolicyEnforcedVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            policy: self.policy.clone(),
+            caller: self.caller.clone(),
+            tenant: self.tenant.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::policy::{AccessPolicy, CallerPolicy};
+    use crate::hooks::Operation;
+
+    #[test]
+    fn test_caller_policy() {
+        let policy = CallerPolicy::default()
+            .allow("billing", &[Operation::RetrieveCreditCard])
+            .allow("support", &[Operation::Exists]);
+
+        assert!(policy.is_allowed("billing", Operation::RetrieveCreditCard, ""));
+        assert!(!policy.is_allowed("support", Operation::RetrieveCreditCard, ""));
+        assert!(policy.is_allowed("support", Operation::Exists, "tenant-a"));
+        assert!(!policy.is_allowed("unknown", Operation::Exists, ""));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_side_vaults_ask_about_their_tenant() {
+        use credit_card::CreditCard;
+        use crate::error::DataVaultError;
+        use crate::mock::MockDataVault;
+        use crate::policy::PolicyEnforcedVault;
+        use crate::traits::DataVault;
+        use std::time::Duration;
+
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+        let vault = MockDataVault::new().unwrap().with_namespace("shop").unwrap();
+        futures::executor::block_on(vault.store_credit_card_with_token("tok_visa", &credit_card, false)).unwrap();
+        let policy = |_: &str, operation: Operation, tenant: &str| tenant == "shop" && operation != Operation::DeleteMany;
+        let vault = PolicyEnforcedVault::with_policy(vault, policy, "checkout");
+
+        futures::executor::block_on(async {
+            vault.store_cvv("tok_visa", "123", Duration::from_secs(60)).await.unwrap();
+            // reading the code deletes it, which the caller may not do
+            assert!(matches!(vault.retrieve_and_burn_cvv("tok_visa").await, Err(DataVaultError::AccessDenied)));
+        });
+    }
+}
@@ -0,0 +1,74 @@
+use deadpool::managed::PoolError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// The state of a vault's connection pool, see `pool_status`
+///
+/// A pool with no `available` connections and tasks `waiting` is
+/// saturated, a growing number of `timeouts` means the tasks wait
+/// longer than the pool's wait timeout, `POOL.TIMEOUTS.WAIT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// the most connections the pool opens
+    pub max_size: usize,
+    /// connections open now, in use or idle
+    pub size: usize,
+    /// idle connections
+    pub available: usize,
+    /// tasks waiting for a connection
+    pub waiting: usize,
+    /// checkouts that timed out since the vault was created
+    pub timeouts: u64,
+}
+
+impl PoolStatus {
+    pub(crate) fn new(status: deadpool::Status, timeouts: &TimeoutCounter) -> Self {
+        PoolStatus {
+            max_size: status.max_size,
+            size: status.size,
+            // deadpool counts waiting tasks as negative availability
+            available: status.available.max(0) as usize,
+            waiting: (-status.available).max(0) as usize,
+            timeouts: timeouts.get(),
+        }
+    }
+}
+
+/// Counts the checkouts of a pool that timed out, shared by a vault
+/// and its namespaces and kept when the pool is reloaded
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TimeoutCounter(Arc<AtomicU64>);
+
+impl TimeoutCounter {
+    /// `checkout` as it was, counted if it timed out
+    pub(crate) fn observe<T, E>(&self, checkout: Result<T, PoolError<E>>) -> Result<T, PoolError<E>> {
+        if let Err(PoolError::Timeout(_)) = &checkout {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+        checkout
+    }
+
+    pub(crate) fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pool_status::{PoolStatus, TimeoutCounter};
+    use deadpool::managed::{PoolError, TimeoutType};
+
+    #[test]
+    fn test_pool_status() {
+        let timeouts = TimeoutCounter::default();
+        let _ = timeouts.observe::<(), ()>(Err(PoolError::Timeout(TimeoutType::Wait)));
+        let _ = timeouts.observe::<(), ()>(Err(PoolError::Closed));
+        let _ = timeouts.observe::<(), ()>(Ok(()));
+        assert_eq!(timeouts.clone().get(), 1);
+
+        let status = PoolStatus::new(deadpool::Status { max_size: 4, size: 4, available: -3 }, &timeouts);
+        assert_eq!(status, PoolStatus { max_size: 4, size: 4, available: 0, waiting: 3, timeouts: 1 });
+        let status = PoolStatus::new(deadpool::Status { max_size: 4, size: 2, available: 1 }, &timeouts);
+        assert_eq!((status.available, status.waiting), (1, 0));
+    }
+}
@@ -1,29 +1,76 @@
 use async_trait::async_trait;
 use credit_card::CreditCard;
-use crate::traits::{DataVault, PoolErrors};
-use crate::config::{DeadpoolPostgresConfig};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::config::{pool_runtime, PostgresVaultConfig};
 use crate::encryption::traits::Encryption;
 use crate::tokenizer::{Tokenizer};
+use crate::fingerprint::deduplicate;
+use crate::serializer::{JsonSerializer, Serializer};
+use crate::utils::RandomToken;
+use crate::stats::VaultStats;
+use crate::health::{CheckResult, HealthReport};
+use crate::metadata::RecordMetadata;
+use crate::summary::CardSummary;
+use crate::alias::resolve_alias;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::cvv::CvvPolicy;
+use crate::validation::{normalize_card, validate_card};
+use crate::brand::with_brand;
+use crate::compression::CompressionAlgo;
+use crate::retry::RetryPolicy;
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+use crate::retry::sleep;
+use crate::fields::{CardField, CardFieldLayout};
+use crate::reload::Reloadable;
+use crate::pool_status::{PoolStatus, TimeoutCounter};
+use crate::latency::{log_if_slow, LatencyRecorder, LatencyReport, LatencyStage};
+use crate::postgres_replicas::ReplicaSet;
+use crate::schema::{AutoCreate, SchemaReport, TableName};
+use crate::verify::{check_record, RecordProblem, VerifyReport};
+use crate::admin::{AgeHistogram, KeyInfo, TenantSummary};
+use crate::compliance::ComplianceReport;
+use crate::integrity::{IntegrityProblem, IntegrityReport};
+use futures::stream::{self, TryStreamExt};
+use crate::namespace::{side_namespace, validate_namespace, validate_token, DEFAULT_NAMESPACE, TOKEN_SIDE_KINDS, TRANSIENT_SIDE_KINDS};
 use deadpool_postgres::{tokio_postgres};
+use deadpool_postgres::tokio_postgres::GenericClient;
+use deadpool_postgres::tokio_postgres::types::ToSql;
+use std::future::Future;
+use std::pin::Pin;
+use std::borrow::Cow;
 use std::error;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 /// Use postgres as a data vault back end
 ///
 /// This implementation uses deadpool_postgres
 ///
-/// DDL
+/// The table is created, and upgraded by later versions, with the
+/// migrations in `migrations/postgres`, see `run_migrations`.  Tables
+/// created by hand from the DDL of earlier versions are upgraded
+/// from where they are.  With `DATA_VAULT_AUTO_CREATE_SCHEMA=true`,
+/// see `PostgresVaultConfig::with_auto_create_schema`, the vault
+/// runs them itself before its first operation.
 ///
-/// -- Drop table
+/// The namespace of a vault, see `DataVault::with_namespace`, is
+/// stored in the `tenant` column.  `soft_delete` sets `deleted_at`
+/// and `expires_at`, the end of the retention period after which
+/// the row may be purged.  Records stored with a time to live, see
+/// `CvvPolicy::Expire`, get `expires_at` right away and are no
+/// longer read once it has passed.
 ///
-/// -- DROP TABLE public.data_vault;
-///
-/// CREATE TABLE public.data_vault (
-/// id bigserial NOT NULL DEFAULT nextval('data_vault_id_seq'::regclass),
-/// "token" varchar(64) NOT NULL,
-/// credit_card bytea NOT NULL
-/// );
-/// CREATE UNIQUE INDEX data_vault_token_idx ON public.data_vault USING btree (token);
+/// The whole card is always in the encrypted `credit_card` column.
+/// The card field columns stay NULL unless a `CardFieldLayout`, see
+/// `with_card_fields`, keeps a field there in plaintext or as a
+/// blind index, e.g. for reporting by expiry or brand.  Add an
+/// index on `(tenant, <column>)` for the columns you look up with
+/// `tokens_where`.  Records stored with `store` have no card fields.
 ///
+/// Credit cards are written with the serializer `S`, JSON unless
+/// another one is picked, see `serializer::Serializer`.
 ///
 /// Connection setup is available as environment
 /// variables or a .env file with the following
@@ -35,6 +82,41 @@ use std::error;
 /// PG_POOL_MAX_SIZE=16
 /// PG_POOL_TIMEOUTS_WAIT_SECS=5
 /// PG_POOL_TIMEOUTS_WAIT_NANOS=0
+/// PG_SCHEMA=vault
+/// PG_TABLE_NAME=data_vault
+/// POSTGRES_POOL_RECYCLE=verified
+///
+/// The table is `data_vault` in the schemas of the search path
+/// unless `PG_TABLE_NAME` or `PG_SCHEMA` say otherwise, see
+/// `PostgresVaultConfig::with_table`.  Both are quoted, so they are
+/// case sensitive and may hold any character.
+///
+/// A host starting with `/`, e.g. `PG_HOST=/run/postgresql`, is the
+/// directory of the server's unix socket, which is connected to
+/// instead of TCP, see `PostgresVaultConfig::with_socket_dir`.
+///
+/// Pooled connections are tested with a query on checkout unless
+/// the recycle method is `RecycleMethod::Fast`, `keep_alive_every`
+/// keeps idle connections open behind firewalls that drop them.
+///
+/// `retrieve`, `retrieve_credit_card` and `exists` are answered by
+/// the read replicas, if there are any, taken in turn:
+/// PG_REPLICA_HOSTS=replica-1,replica-2:5433
+/// PG_MAX_REPLICA_LAG_MS=500
+///
+/// Replicas are connected with the user, password, database and
+/// pool settings of the primary, see `PostgresVaultConfig::with_replica`
+/// for others.  A replica further behind the primary than the
+/// maximum lag is skipped, the lag is measured at most once a
+/// second.  The primary answers when no replica can, and when a
+/// replica does not have the record, so records stored a moment
+/// ago are found.  Everything else, including reads of versions and
+/// metadata and transactions, goes to the primary.
+///
+/// Creating the vault does not connect to postgres, connections are
+/// opened by the operations that need them, retried as the vault's
+/// `RetryPolicy` allows, so a vault can be created while postgres
+/// is still starting or briefly unavailable.
 ///
 /// # Examples
 /// ```rust
@@ -43,23 +125,148 @@ use std::error;
 /// use data_vault::tokenizer::Blake3Tokenizer;
 /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
 /// ```
-pub struct PostgresDataVault<E, T> {
-    pool: deadpool_postgres::Pool,
-    encryption: E,
-    tokenizer: T,
+pub struct PostgresDataVault<E, T, S = JsonSerializer> {
+    pool: Reloadable<deadpool_postgres::Pool>,
+    timeouts: TimeoutCounter,
+    latency: LatencyRecorder,
+    replicas: Reloadable<ReplicaSet>,
+    encryption: Reloadable<E>,
+    key: Reloadable<KeyInfo>,
+    tokenizer: Reloadable<T>,
+    serializer: Arc<S>,
+    namespace: String,
+    retention: Duration,
+    slow_op: Option<Duration>,
+    cvv_policy: CvvPolicy,
+    validate_cards: bool,
+    reject_expired: bool,
+    retry: RetryPolicy,
+    compression: CompressionAlgo,
+    card_fields: Reloadable<CardFieldLayout>,
+    table: Arc<TableName>,
+    auto_create: Option<Arc<AutoCreate>>,
+    env_prefix: Option<Arc<str>>,
+}
+
+// `{table}` is replaced with the quoted table name, see `sql`
+// rows are live while `deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())`
+const SELECT_CREDIT_CARD: &str = "SELECT credit_card, version FROM {table} WHERE tenant = $1 AND token = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+const SELECT_CREDIT_CARD_WITH_METADATA: &str = "SELECT credit_card, version, created_at, EXTRACT(EPOCH FROM expires_at - now())::float8 AS ttl_secs FROM {table} WHERE tenant = $1 AND token = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+const SELECT_EXISTS: &str = "SELECT EXISTS (SELECT 1 FROM {table} WHERE tenant = $1 AND token = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now()))";
+// a NULL time to live in $4 stores a record that does not expire,
+// $5 to $9 are the card field columns in the order of `CardField`
+const UPSERT_CREDIT_CARD: &str = "INSERT INTO {table} AS data_vault (tenant, token, credit_card, expires_at, number, cardholder_name, expiration_month, expiration_year, brand) VALUES ($1, $2, $3, now() + make_interval(secs => $4), $5, $6, $7, $8, $9) ON CONFLICT (tenant, token) DO UPDATE SET credit_card = EXCLUDED.credit_card, deleted_at = NULL, expires_at = EXCLUDED.expires_at, version = data_vault.version + 1, number = EXCLUDED.number, cardholder_name = EXCLUDED.cardholder_name, expiration_month = EXCLUDED.expiration_month, expiration_year = EXCLUDED.expiration_year, brand = EXCLUDED.brand";
+// a soft deleted or expired row counts as absent and is replaced
+const INSERT_CREDIT_CARD_IF_ABSENT: &str = "INSERT INTO {table} AS data_vault (tenant, token, credit_card, expires_at, number, cardholder_name, expiration_month, expiration_year, brand) VALUES ($1, $2, $3, now() + make_interval(secs => $4), $5, $6, $7, $8, $9) ON CONFLICT (tenant, token) DO UPDATE SET credit_card = EXCLUDED.credit_card, deleted_at = NULL, expires_at = EXCLUDED.expires_at, version = data_vault.version + 1, number = EXCLUDED.number, cardholder_name = EXCLUDED.cardholder_name, expiration_month = EXCLUDED.expiration_month, expiration_year = EXCLUDED.expiration_year, brand = EXCLUDED.brand WHERE data_vault.deleted_at IS NOT NULL OR data_vault.expires_at <= now()";
+// a NULL time to live in $5 keeps the current expiry
+const UPDATE_CREDIT_CARD_IF_VERSION: &str = "UPDATE {table} SET credit_card = $4, expires_at = COALESCE(now() + make_interval(secs => $5), expires_at), version = version + 1, number = $6, cardholder_name = $7, expiration_month = $8, expiration_year = $9, brand = $10 WHERE tenant = $1 AND token = $2 AND version = $3 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now()) RETURNING version";
+const UPDATE_TOKEN: &str = "UPDATE {table} SET token = $3 WHERE tenant = $1 AND token = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+// a record expiring before the retention period is over keeps its expiry
+const SOFT_DELETE_CREDIT_CARD: &str = "UPDATE {table} SET deleted_at = now(), expires_at = LEAST(expires_at, now() + make_interval(secs => $3)) WHERE tenant = $1 AND token = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+// soft deleted and expired rows are deleted too but not counted
+// a NULL time to live in $3 never expires
+const TOUCH_CREDIT_CARD: &str = "UPDATE {table} SET expires_at = now() + make_interval(secs => $3) WHERE tenant = $1 AND token = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+const DELETE_MANY: &str = "WITH deleted AS (DELETE FROM {table} WHERE tenant = $1 AND token = ANY($2) RETURNING deleted_at, expires_at) SELECT count(*) FROM deleted WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+const SELECT_RECORDS_AFTER: &str = "SELECT id, token, credit_card FROM {table} WHERE tenant = $1 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now()) AND id > $2 ORDER BY id LIMIT $3";
+// deleted and expired rows are checked too, they can be restored or are purged later
+const SELECT_VERIFY_AFTER: &str = "SELECT id, token, credit_card, version, created_at > now() AS created_later, deleted_at IS NOT NULL AND expires_at IS NULL AS deleted_forever, \"number\", cardholder_name, expiration_month, expiration_year, brand FROM {table} WHERE tenant = $1 AND id > $2 ORDER BY id LIMIT $3";
+// rows read per query by iter_records
+const RECORD_BATCH_SIZE: i64 = 1000;
+// the card field columns in the order of `CardField`
+const CARD_FIELD_COLUMNS: [&str; 5] = ["number", "cardholder_name", "expiration_month", "expiration_year", "brand"];
+// deleted and expired rows too, like SELECT_VERIFY_AFTER
+const SELECT_CIPHERTEXT: &str = "SELECT credit_card FROM {table} WHERE tenant = $1 AND token = $2";
+// $8 is the record the columns were computed from, a changed row is left alone
+const UPDATE_CARD_FIELDS: &str = "UPDATE {table} SET \"number\" = $3, cardholder_name = $4, expiration_month = $5, expiration_year = $6, brand = $7 WHERE tenant = $1 AND token = $2 AND credit_card = $8";
+const DELETE_EXPIRED: &str = "DELETE FROM {table} WHERE tenant = $1 AND expires_at <= now()";
+const COUNT_CREDIT_CARDS: &str = "SELECT count(*) FROM {table} WHERE tenant = $1 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())";
+// the relation size is shared by all tenants, each is charged its share of the rows
+const SELECT_STATS: &str = "SELECT count(*) FILTER (WHERE tenant = $1 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())) AS count, count(*) AS total, min(created_at) FILTER (WHERE tenant = $1 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())) AS oldest, max(created_at) FILTER (WHERE tenant = $1 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())) AS newest, pg_total_relation_size($2::text::regclass) AS bytes FROM {table}";
+// $2 are the ascending bucket bounds in seconds, bucket 0 is younger than the first
+const SELECT_AGE_HISTOGRAM: &str = "SELECT width_bucket(EXTRACT(EPOCH FROM now() - created_at)::float8, $2::float8[]) AS bucket, count(*) AS count FROM {table} WHERE tenant = $1 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now()) GROUP BY bucket";
+// ordered bytewise like the namespaces of redis
+const SELECT_TENANT_SUMMARY: &str = "SELECT tenant, count(*) FILTER (WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())) AS count, count(*) FILTER (WHERE deleted_at IS NOT NULL) AS soft_deleted, min(created_at) FILTER (WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())) AS oldest, max(created_at) FILTER (WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now())) AS newest FROM {table} GROUP BY tenant ORDER BY tenant COLLATE \"C\"";
+
+/// `name` quoted as a postgres identifier, quoted names keep
+/// their case
+pub(crate) fn quote_identifier(name: &str) -> Result<String, Box<dyn error::Error>> {
+    // longer names are truncated by postgres, another table could be used
+    if name.is_empty() || name.len() > 63 || name.contains('\0') {
+        return Err(format!("invalid postgres identifier {:?}", name).into())
+    }
+    Ok(format!("\"{}\"", name.replace('"', "\"\"")))
+}
+
+/// The pool of the postgres `postgres` configures
+fn create_pool(postgres: &deadpool_postgres::Config) -> Result<deadpool_postgres::Pool, Box<dyn error::Error>> {
+    let mut postgres_cfg = postgres.clone();
+    #[cfg(not(unix))]
+    if postgres_cfg.host.iter().chain(postgres_cfg.hosts.iter().flatten()).any(|host| host.starts_with('/')) {
+        return Err("unix sockets are not available on this platform".into());
+    }
+    postgres_cfg.pool.get_or_insert_with(Default::default).runtime = pool_runtime();
+    Ok(postgres_cfg.create_pool(tokio_postgres::NoTls)?)
+}
+
+/// The read replicas `cfg.replicas` configures
+fn create_replica_set(cfg: &PostgresVaultConfig) -> Result<ReplicaSet, Box<dyn error::Error>> {
+    let pools = cfg.replicas.iter().map(create_pool).collect::<Result<_, _>>()?;
+    Ok(ReplicaSet::new(pools, cfg.max_replica_lag))
 }
 
-const SELECT_CREDIT_CARD: &str = "SELECT credit_card FROM data_vault WHERE token = $1";
-const INSERT_CREDIT_CARD: &str = "INSERT INTO data_vault VALUES (token, credit_card) ($1, $2)";
-const UPDATE_CREDIT_CARD: &str = "UPDATE data_vault SET credit_card = $2 WHERE token = $1";
-const UPSERT_CREDIT_CARD: &str = "INSERT INTO data_vault (token, credit_card) VALUES ($1, $2) ON CONFLICT (token) DO UPDATE SET credit_card = EXCLUDED.credit_card";
-const DELETE_CREDIT_CARD: &str = "DELETE FROM data_vault WHERE token = $1";
+/// whether a read failing on a replica with `e` is asked of the
+/// primary, the replica may not have the record yet or be down
+fn ask_primary(e: &DataVaultError) -> bool {
+    matches!(e, DataVaultError::NotFound) || e.is_transient()
+}
+
+/// the first contradiction between a row of `SELECT_VERIFY_AFTER` and
+/// the record in it, `credit_card` is `None` for text
+fn metadata_problem(row: &tokio_postgres::Row, credit_card: Option<&CreditCard>, card_fields: &CardFieldLayout) -> Option<RecordProblem> {
+    if row.get::<_, i64>("version") < 1 {
+        return Some(RecordProblem::Metadata("version below 1"))
+    }
+    if row.get("created_later") {
+        return Some(RecordProblem::Metadata("created in the future"))
+    }
+    if row.get("deleted_forever") {
+        return Some(RecordProblem::Metadata("deleted without an expiry"))
+    }
+
+    let expected = credit_card.map(|credit_card| card_fields.columns(credit_card)).unwrap_or_default();
+    for (column, expected) in CARD_FIELD_COLUMNS.iter().zip(expected.iter()) {
+        let stored: Option<String> = row.get(column);
+        if stored.is_some() && stored != *expected {
+            return Some(RecordProblem::Metadata("card field column does not match the record"))
+        }
+    }
+    None
+}
+
+/// the card field columns of a row of `SELECT_VERIFY_AFTER` that
+/// differ from what `card_fields` makes of the record, columns that
+/// are and should be empty are left out, `credit_card` is `None` for
+/// text
+fn card_field_problems(row: &tokio_postgres::Row, credit_card: Option<&CreditCard>, card_fields: &CardFieldLayout) -> Vec<(&'static str, Option<IntegrityProblem>)> {
+    let expected = credit_card.map(|credit_card| card_fields.columns(credit_card)).unwrap_or_default();
+    CARD_FIELD_COLUMNS.iter().zip(expected.iter()).filter_map(|(column, expected)| {
+        let stored: Option<String> = row.get(column);
+        let problem = match (stored, expected) {
+            (None, None) => return None,
+            (None, Some(_)) => Some(IntegrityProblem::Unindexed),
+            (Some(_), None) => Some(IntegrityProblem::Dangling),
+            (Some(stored), Some(expected)) => (stored != *expected).then_some(IntegrityProblem::Stale),
+        };
+        Some((*column, problem))
+    }).collect()
+}
 
 #[async_trait]
-impl<E, T> DataVault for PostgresDataVault<E, T>
+impl<E, T, S> DataVault for PostgresDataVault<E, T, S>
     where
         E: Encryption + std::marker::Sync + std::marker::Send,
         T: Tokenizer + std::marker::Sync + std::marker::Send,
+        S: Serializer + std::marker::Sync + std::marker::Send,
 {
     /// Create new PostgresDataVault backend
     /// # examples
@@ -70,17 +277,7 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
     /// ```
     fn new() -> Result<Self, Box<dyn error::Error>> {
-        let cfg = DeadpoolPostgresConfig::from_env()?;
-
-        let pool = cfg.postgres.create_pool(tokio_postgres::NoTls)?;
-
-        let postgres_data_vault = PostgresDataVault {
-            pool,
-            encryption: E::new(),
-            tokenizer: T::new()
-        };
-
-        Ok(postgres_data_vault)
+        PostgresDataVault::from_config(PostgresVaultConfig::from_env()?)
     }
 
     /// Encrypt and Store a string with the given token as the postgres key
@@ -99,12 +296,49 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
     /// data_vault.store(&token, &credit_card_string);
     /// ```
-    async fn store(&self, token: &String, string: &String) -> Result<(), PoolErrors> {
-        let client = self.pool.get().await?;
-        let encrypted_json = self.encryption.encrypt(string.as_bytes());
-        let stmt = client.prepare(UPSERT_CREDIT_CARD).await.unwrap();
-        let _ = client.query(&stmt, &[&token, &encrypted_json]).await.unwrap();
-        Ok(())
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "store", Some(token), async {
+            let client = self.connection().await?;
+            self.store_on(&**client, token, string).await
+        }).await
+    }
+
+    /// Encrypt and Store a string unless a live record is stored
+    /// under the token, a soft deleted or expired one is replaced
+    /// returns:
+    ///     * whether the string was stored
+    /// # example
+    /// ```rust
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_if_absent("abc123", "{number: 123}");
+    /// ```
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "store_if_absent", Some(token), async {
+            let client = self.connection().await?;
+            self.store_expiring_if_absent_on(&**client, token, string.as_bytes(), None, &Default::default()).await
+        }).await
+    }
+
+    /// Encrypt and Store a string with its expiry in one statement
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use std::time::Duration;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_expiring("abc123", "{number: 123}", Duration::from_secs(300)).await.unwrap();
+    /// ```
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "store_expiring", Some(token), async {
+            let client = self.connection().await?;
+            self.store_expiring_on(&**client, token, string.as_bytes(), Some(ttl), &Default::default()).await
+        }).await
     }
 
     /// Store the credit card in the data vault
@@ -131,11 +365,86 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
     /// let token = data_vault.store_credit_card(&cc);
     /// ```
-    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, PoolErrors> {
-        let token = self.tokenizer.generate(&credit_card);
-        let credit_card_json = serde_json::to_string(&credit_card).unwrap();
-        let _:() = self.store(&token, &credit_card_json).await?;
-        Ok(token)
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "store_credit_card", None, async {
+            let credit_card = self.validate_card(credit_card)?;
+            let fingerprint = self.tokenizer.load().fingerprint(&credit_card);
+            let (token, _) = deduplicate(self, fingerprint, async {
+                let client = self.connection().await?;
+                Ok((self.store_credit_card_on(&**client, &credit_card).await?, true))
+            }).await?;
+            Ok(token)
+        }).await
+    }
+
+    /// Get or create the token for a credit card
+    ///
+    /// With a deterministic tokenizer the card is only written when
+    /// neither its token nor a card with its number is in the vault
+    /// yet, an existing record is left untouched.  Other tokenizers
+    /// always store a new record.
+    /// Arguments:
+    ///     * `CreditCard` - the cc object that you wish to tokenize
+    /// return:
+    ///     the token as String and whether a new record was created
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3DeterministicTokenizer;
+    /// use credit_card::CreditCard;
+    ///
+    /// let cc = CreditCard {
+    ///    number: "4111111111111111".to_string(),
+    ///    cardholder_name: "Graydon Hoare".to_string(),
+    ///    expiration_month: "01".to_string(),
+    ///    expiration_year: "2023".to_string(),
+    ///    brand: None,
+    ///    security_code: None
+    /// };
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+    /// let (token, created) = data_vault.tokenize(&cc).await?;
+    /// let (same_token, created_again) = data_vault.tokenize(&cc).await?;
+    /// assert!(!created_again);
+    /// ```
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "tokenize", None, async {
+            let credit_card = self.validate_card(credit_card)?;
+            let fingerprint = self.tokenizer.load().fingerprint(&credit_card);
+            deduplicate(self, fingerprint, async {
+                let client = self.connection().await?;
+                self.tokenize_on(&**client, &credit_card).await
+            }).await
+        }).await
+    }
+
+    /// Store the credit card under a token chosen by the caller
+    ///
+    /// Without `overwrite` the row is inserted with `ON CONFLICT`, so
+    /// a record stored under the token meanwhile is never replaced.
+    /// Arguments:
+    ///     * `token`: the token to store the card under
+    ///     * `CreditCard` - the cc object that you wish to store
+    ///     * `overwrite`: replace a record stored under `token`
+    /// returns:
+    ///     * `DataVaultError::InvalidToken` for a token that is not
+    ///       1 to 64 characters of `[A-Za-z0-9_.-]`
+    ///     * `DataVaultError::AlreadyExists` when `token` is taken
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_credit_card_with_token("tok_legacy_42", &cc, false).await.unwrap();
+    /// ```
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "store_credit_card_with_token", Some(token), async {
+            let client = self.connection().await?;
+            self.store_credit_card_with_token_on(&**client, token, credit_card, overwrite).await
+        }).await
     }
 
     /// Get decrypted arbitrary data from the vault by token
@@ -143,6 +452,7 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     ///     * `token`: the string form of the ID of the data
     /// returns:
     ///     * the decrypted string of data
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
     /// # example
     /// ```rust,ignore
     /// use data_vault::DataVault;
@@ -156,16 +466,40 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     /// data_vault.store(&token, &cc_string).await;
     /// let credit_card_string = data_vault.retrieve(&token)
     /// ```
-    async fn retrieve(&self, token: &String) -> Result<String, PoolErrors> {
-        let client = self.pool.get().await?;
-        let stmt = client.prepare(SELECT_CREDIT_CARD).await.unwrap();
-        let row_result = client.query_one(&stmt, &[&token]).await;
-        let mut encrypted_credit_card_json: Vec<u8> = Vec::new();
-        if row_result.is_ok() {
-           let row = row_result.unwrap();
-           encrypted_credit_card_json = row.get("credit_card");
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "retrieve", Some(token), async {
+            if let Some(client) = self.replica_connection().await {
+                match self.retrieve_on(&**client, token).await {
+                    Err(e) if ask_primary(&e) => {},
+                    result => return result,
+                }
+            }
+            let client = self.connection().await?;
+            self.retrieve_on(&**client, token).await
+        }).await
+    }
+
+    /// Whether a record is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store("abc123", "{number: 123}").await.unwrap();
+    /// assert!(data_vault.exists("abc123").await.unwrap());
+    /// ```
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        if let Some(client) = self.replica_connection().await {
+            match self.exists_on(&**client, token).await {
+                Ok(false) => {},
+                Err(e) if ask_primary(&e) => {},
+                result => return result,
+            }
         }
-        Ok(self.encryption.decrypt(encrypted_credit_card_json.as_slice()))
+        let client = self.connection().await?;
+        self.exists_on(&**client, token).await
     }
 
     /// Get the credit card from the data vault given a token
@@ -173,6 +507,7 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     ///     * `token`: the string form of the ID of the data
     /// returns:
     ///     * `CreditCard` object
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
     /// # Example
     /// ```rust,ignore
     /// use data_vault::DataVault;
@@ -194,8 +529,1729 @@ impl<E, T> DataVault for PostgresDataVault<E, T>
     /// let token = data_vault.store_credit_card(&cc).await;
     /// let credit_card = data_vault.retrieve_credit_card(&token).await;
     /// ```
-    async fn retrieve_credit_card(&self, token: &String) -> Result<CreditCard, PoolErrors> {
-        let credit_card_json = self.retrieve(token).await?;
-        Ok(serde_json::from_str(&credit_card_json).unwrap_or_default())
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "retrieve_credit_card", Some(token), async {
+            match self.retrieve_credit_card_from_replica(token).await {
+                // the token of a merged card
+                Err(DataVaultError::NotFound) => match resolve_alias(self, token).await? {
+                    Some(survivor) => self.retrieve_credit_card_from_replica(&survivor).await,
+                    None => Err(DataVaultError::NotFound),
+                },
+                result => result,
+            }
+        }).await
+    }
+
+    /// Get the credit card and the version it is at
+    /// Arguments:
+    ///     * `token`: the string form of the ID of the data
+    /// returns:
+    ///     * `CreditCard` object and its version
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let (credit_card, version) = data_vault.retrieve_credit_card_with_version(&token).await.unwrap();
+    /// ```
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "retrieve_credit_card_with_version", Some(token), async {
+            let client = self.connection().await?;
+            self.retrieve_credit_card_with_version_on(&**client, token).await
+        }).await
+    }
+
+    /// Get the credit card with its metadata in one query
+    /// Arguments:
+    ///     * `token`: the string form of the ID of the data
+    /// returns:
+    ///     * `CreditCard` object and its `RecordMetadata`
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let (credit_card, metadata) = data_vault.retrieve_with_metadata(&token).await.unwrap();
+    /// ```
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        log_if_slow(self.slow_op, "postgres", "retrieve_with_metadata", Some(token), async {
+            let client = self.connection().await?;
+            self.retrieve_with_metadata_on(&**client, token).await
+        }).await
+    }
+
+    /// Replace the credit card only if it is still at `expected_version`
+    /// Arguments:
+    ///     * `token`: the token of the card to replace
+    ///     * `CreditCard` - the new card
+    ///     * `expected_version`: the version the change is based on
+    /// returns:
+    ///     * the new version
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    ///     * `DataVaultError::Conflict` when the record is at another version
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let (mut credit_card, version) = data_vault.retrieve_credit_card_with_version(&token).await.unwrap();
+    /// credit_card.expiration_year = "2027".to_string();
+    /// let version = data_vault.update_credit_card_if_version(&token, &credit_card, version).await.unwrap();
+    /// ```
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let client = self.connection().await?;
+        self.update_credit_card_if_version_on(&**client, token, credit_card, expected_version).await
+    }
+
+    /// Move a record to a new random token
+    ///
+    /// The ciphertext is moved as is, after this the old token is
+    /// no longer stored.  Use this when a token has leaked.  A
+    /// deterministic tokenizer will still produce the old token for
+    /// the same card, so `tokenize` would store it again.
+    /// Arguments:
+    ///     * `token`: the token to replace
+    /// returns:
+    ///     * the new token
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let new_token = data_vault.rotate_token(&leaked_token).await.unwrap();
+    /// ```
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        let client = self.connection().await?;
+        self.rotate_token_on(&**client, token).await
+    }
+
+    /// Hide a record until it is purged after the retention period
+    ///
+    /// Storing the token again replaces the deleted record.
+    /// Arguments:
+    ///     * `token`: the token to delete
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.soft_delete(&token).await.unwrap();
+    /// assert!(data_vault.try_retrieve(&token).await.unwrap().is_none());
+    /// ```
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        let client = self.connection().await?;
+        self.soft_delete_on(&**client, token).await
+    }
+
+    /// Set the expiry of a record, or remove it for `None`, the
+    /// record itself and its version are left as they are
+    /// Arguments:
+    ///     * `token`: the token of the record
+    ///     * `ttl`: the new time to live, counted from now
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use std::time::Duration;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// // the customer paid again, keep the card on file for another year
+    /// data_vault.touch(&token, Some(Duration::from_secs(365 * 24 * 60 * 60))).await.unwrap();
+    /// ```
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        let client = self.connection().await?;
+        self.touch_on(&**client, token, ttl).await
+    }
+
+    /// Delete the rows of `tokens` in one statement
+    /// returns:
+    ///     * how many tokens had a row that could be read, soft
+    ///       deleted rows are deleted too but not counted
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let deleted = data_vault.delete_many(&leaked_tokens).await.unwrap();
+    /// ```
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let client = self.connection().await?;
+        self.delete_many_on(&**client, tokens).await
+    }
+
+    /// Delete rows whose `expires_at` has passed, those of the side
+    /// namespaces of the records too, e.g. expired security codes
+    /// returns:
+    ///     * how many rows of the namespace were deleted
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.purge_expired().await.unwrap();
+    /// println!("purged {} cards", report.purged);
+    /// ```
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(DELETE_EXPIRED)).await?;
+        let purged = client.execute(&stmt, &[&self.namespace]).await?;
+        for kind in TOKEN_SIDE_KINDS.iter().chain(TRANSIENT_SIDE_KINDS.iter()) {
+            client.execute(&stmt, &[&side_namespace(&self.namespace, kind)]).await?;
+        }
+        Ok(PurgeReport {
+            purged,
+            expired_by_backend: 0,
+        })
+    }
+
+    /// Every record of the namespace as `(token, ciphertext)`
+    ///
+    /// Rows are read in batches ordered by `id`, rows stored while
+    /// the stream runs may or may not be included.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use futures::TryStreamExt;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let mut records = data_vault.iter_records();
+    /// while let Some((token, ciphertext)) = records.try_next().await.unwrap() {
+    ///     println!("{} {} bytes", token, ciphertext.len());
+    /// }
+    /// ```
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        let batches = stream::try_unfold(Some(0), move |after_id| async move {
+            let after_id = match after_id {
+                Some(after_id) => after_id,
+                None => return Ok::<_, DataVaultError>(None),
+            };
+
+            let (last_id, records) = self.records_after(after_id, RECORD_BATCH_SIZE).await?;
+            let next_id = match records.len() as i64 {
+                RECORD_BATCH_SIZE => Some(last_id),
+                _ => None,
+            };
+            Ok(Some((stream::iter(records.into_iter().map(Ok)), next_id)))
+        });
+        Box::pin(batches.try_flatten())
+    }
+
+    /// Every record of the namespace as `(token, decrypted string)`
+    /// see `iter_records`
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        Box::pin(self.iter_records().and_then(move |(token, ciphertext)| async move {
+            Ok((token, self.open_string(ciphertext.as_slice())?))
+        }))
+    }
+
+    /// Up to `limit` decrypted records starting at `cursor`
+    ///
+    /// The cursor is the id of the last row of the previous page.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let page = data_vault.decrypted_records_page(None, 100).await.unwrap();
+    /// let next_page = data_vault.decrypted_records_page(page.next_cursor.as_deref(), 100).await.unwrap();
+    /// ```
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        let after_id = match cursor {
+            Some(cursor) => cursor.parse().map_err(|_| DataVaultError::InvalidCursor)?,
+            None => 0,
+        };
+
+        let limit = limit.max(1);
+        let (last_id, records) = self.records_after(after_id, limit as i64).await?;
+        let next_cursor = match records.len() {
+            len if len == limit => Some(last_id.to_string()),
+            _ => None,
+        };
+        Ok(RecordPage {
+            records: records.into_iter()
+                .map(|(token, ciphertext)| Ok((token, self.open_string(ciphertext.as_slice())?)))
+                .collect::<Result<_, DataVaultError>>()?,
+            next_cursor,
+        })
+    }
+
+    /// Number of records in the vault
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let count = data_vault.count().await.unwrap();
+    /// ```
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(COUNT_CREDIT_CARDS)).await?;
+        let row = client.query_one(&stmt, &[&self.namespace]).await?;
+        let count: i64 = row.get(0);
+        Ok(count as u64)
+    }
+
+    /// Record count, table size and first/last store times
+    ///
+    /// The size is `pg_total_relation_size` of the table, so it
+    /// includes indexes and TOAST data.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let stats = data_vault.stats().await.unwrap();
+    /// println!("{} cards using ~{} bytes", stats.count, stats.approximate_bytes);
+    /// ```
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(SELECT_STATS)).await?;
+        let row = client.query_one(&stmt, &[&self.namespace, &self.table.qualified()]).await?;
+        let count: i64 = row.get("count");
+        let total: i64 = row.get("total");
+        let bytes: i64 = row.get("bytes");
+        let oldest: Option<SystemTime> = row.get("oldest");
+        let newest: Option<SystemTime> = row.get("newest");
+
+        Ok(VaultStats {
+            count: count as u64,
+            approximate_bytes: match total {
+                0 => 0,
+                total => (bytes as u64) * (count as u64) / (total as u64),
+            },
+            oldest,
+            newest,
+        })
+    }
+
+    /// `SELECT 1` on a connection from the pool of the primary and
+    /// a probe encrypted and decrypted with the vault's keys
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.health_check().await;
+    /// assert!(report.is_healthy(), "{:?}", report);
+    /// ```
+    async fn health_check(&self) -> HealthReport {
+        let checked_at = SystemTime::now();
+        let connection = CheckResult::of(async {
+            let pool = self.pool.load();
+            pool.get().await?.simple_query("SELECT 1").await?;
+            Ok(())
+        }).await;
+
+        HealthReport {
+            backend: self.backend(),
+            connection,
+            encryption: CheckResult::roundtrip(|probe| self.open(&self.seal(probe))),
+            checked_at,
+        }
+    }
+
+    fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+
+    /// Scope the vault to `namespace`, the pool is shared
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let token = data_vault.store_credit_card_in("merchant-42", &cc).await.unwrap();
+    /// let credit_card = data_vault.retrieve_credit_card_from("merchant-42", &token).await.unwrap();
+    /// ```
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        validate_namespace(namespace)?;
+
+        Ok(PostgresDataVault {
+            pool: self.pool.clone(),
+            timeouts: self.timeouts.clone(),
+            latency: self.latency.clone(),
+            replicas: self.replicas.clone(),
+            encryption: self.encryption.clone(),
+            key: self.key.clone(),
+            tokenizer: self.tokenizer.clone(),
+            serializer: self.serializer.clone(),
+            namespace: namespace.to_string(),
+            retention: self.retention,
+            slow_op: self.slow_op,
+            cvv_policy: self.cvv_policy,
+            validate_cards: self.validate_cards,
+            reject_expired: self.reject_expired,
+            retry: self.retry,
+            compression: self.compression,
+            card_fields: self.card_fields.clone(),
+            table: self.table.clone(),
+            auto_create: self.auto_create.clone(),
+            env_prefix: self.env_prefix.clone(),
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        let mut side = self.with_namespace(DEFAULT_NAMESPACE)?;
+        side.namespace = side_namespace(&self.namespace, kind);
+        Ok(side)
+    }
+}
+
+
+/// A boxed future borrowing a `PostgresTransaction`, returned by the
+/// closure given to `PostgresDataVault::transaction`
+pub type TransactionFuture<'t, R> = Pin<Box<dyn Future<Output = Result<R, DataVaultError>> + Send + 't>>;
+
+impl<E, T, S> PostgresDataVault<E, T, S>
+    where
+        E: Encryption + std::marker::Sync + std::marker::Send,
+        T: Tokenizer + std::marker::Sync + std::marker::Send,
+        S: Serializer + std::marker::Sync + std::marker::Send,
+{
+    /// Create a PostgresDataVault from configuration assembled in
+    /// code, `new` is `from_config(PostgresVaultConfig::from_env()?)`,
+    /// fails with a `ConfigError` naming the variable if the
+    /// configuration is invalid, see `PostgresVaultConfig::validate`
+    pub fn from_config(cfg: PostgresVaultConfig) -> Result<Self, Box<dyn error::Error>> {
+        cfg.validate()?;
+        let pool = create_pool(&cfg.postgres)?;
+        let replicas = create_replica_set(&cfg)?;
+        let card_fields = CardFieldLayout::from_config(&cfg.encryption, &cfg.plaintext_fields, &cfg.blind_index_fields)?;
+        let table = TableName::new(cfg.schema.as_deref(), &cfg.table)?;
+        let encryption = E::from_settings(&cfg.encryption);
+
+        Ok(PostgresDataVault {
+            pool: Reloadable::new(pool),
+            timeouts: TimeoutCounter::default(),
+            latency: LatencyRecorder::default(),
+            replicas: Reloadable::new(replicas),
+            key: Reloadable::new(KeyInfo::new(&encryption, &cfg.encryption)),
+            encryption: Reloadable::new(encryption),
+            tokenizer: Reloadable::new(T::from_settings(&cfg.encryption)),
+            serializer: Arc::new(S::new()),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            retention: cfg.retention,
+            slow_op: cfg.slow_op,
+            cvv_policy: cfg.cvv_policy,
+            validate_cards: cfg.validate_cards,
+            reject_expired: cfg.reject_expired,
+            retry: cfg.retry,
+            compression: CompressionAlgo::None,
+            card_fields: Reloadable::new(card_fields),
+            table: Arc::new(table),
+            auto_create: match cfg.auto_create_schema {
+                true => Some(Arc::default()),
+                false => None,
+            },
+            env_prefix: cfg.env_prefix.map(Into::into),
+        })
+    }
+
+    /// Re-read the configuration with `PostgresVaultConfig::from_env`,
+    /// or `from_env_with_prefix` if it was read with a prefix, and
+    /// apply it, see `reload_from`
+    pub fn reload(&self) -> Result<(), Box<dyn error::Error>> {
+        let cfg = match &self.env_prefix {
+            Some(prefix) => PostgresVaultConfig::from_env_with_prefix(prefix)?,
+            None => PostgresVaultConfig::from_env()?,
+        };
+        self.reload_from(cfg)
+    }
+
+    /// Switch to the key material, card field layout and connection
+    /// settings of `cfg` without recreating the vault, e.g. after
+    /// the files of `*_FILE` variables were rotated or to resize
+    /// the pool
+    ///
+    /// The vault, its namespaces and transactions started later use
+    /// a new pool, connections of the old one are closed once they
+    /// are returned.  The table, retention, security code and retry
+    /// policies stay as they were.  Nothing changes if `cfg` is
+    /// invalid.  Records are not re-encrypted, after switching to a
+    /// new key the vault cannot read records sealed with the old
+    /// one, move them over first, see `migrate`.
+    pub fn reload_from(&self, cfg: PostgresVaultConfig) -> Result<(), Box<dyn error::Error>> {
+        cfg.validate()?;
+        let pool = create_pool(&cfg.postgres)?;
+        let replicas = create_replica_set(&cfg)?;
+        let card_fields = CardFieldLayout::from_config(&cfg.encryption, &cfg.plaintext_fields, &cfg.blind_index_fields)?;
+        let encryption = E::from_settings(&cfg.encryption);
+
+        self.key.store(self.key.load().reloaded(KeyInfo::new(&encryption, &cfg.encryption)));
+        self.encryption.store(encryption);
+        self.tokenizer.store(T::from_settings(&cfg.encryption));
+        self.card_fields.store(card_fields);
+        self.pool.store(pool);
+        self.replicas.store(replicas);
+        Ok(())
+    }
+
+    /// This vault applying `policy` to security codes instead of
+    /// the configured `DATA_VAULT_CVV_POLICY`
+    pub fn with_cvv_policy(self, policy: CvvPolicy) -> Self {
+        PostgresDataVault {
+            cvv_policy: policy,
+            ..self
+        }
+    }
+
+    /// This vault checking the card number and expiry of cards
+    /// before storing them, or not, instead of the configured
+    /// `DATA_VAULT_VALIDATE_CARDS`
+    pub fn with_card_validation(self, validate_cards: bool) -> Self {
+        PostgresDataVault {
+            validate_cards,
+            ..self
+        }
+    }
+
+    /// This vault refusing cards that expired before the current
+    /// month, or not, instead of the configured
+    /// `DATA_VAULT_REJECT_EXPIRED`, unless card validation is off
+    pub fn with_reject_expired(self, reject_expired: bool) -> Self {
+        PostgresDataVault {
+            reject_expired,
+            ..self
+        }
+    }
+
+    /// This vault taking connections from its pool as `policy`
+    /// allows instead of the configured `DATA_VAULT_RETRY_*`
+    pub fn with_retry(self, policy: RetryPolicy) -> Self {
+        PostgresDataVault {
+            retry: policy,
+            ..self
+        }
+    }
+
+    /// This vault compressing the records it stores with `algo`,
+    /// records are read whichever way they were stored
+    pub fn with_compression(self, algo: CompressionAlgo) -> Self {
+        PostgresDataVault {
+            compression: algo,
+            ..self
+        }
+    }
+
+    /// a connection from the pool, retried as `self.retry` allows
+    async fn checkout(&self) -> Result<deadpool_postgres::Client, DataVaultError> {
+        let checkout = self.retry.run(DataVaultError::is_transient, || async { Ok(self.timeouts.observe(self.pool.load().get().await)?) });
+        #[cfg(feature = "tracing")]
+        let checkout = crate::trace::checkout("postgres", "primary", checkout);
+        checkout.await
+    }
+
+    /// `checkout` of a connection to a table created first if
+    /// `auto_create_schema` is set
+    async fn connection(&self) -> Result<deadpool_postgres::Client, DataVaultError> {
+        let mut client = self.checkout().await?;
+        if let Some(auto_create) = &self.auto_create {
+            auto_create.ensure(&self.table, &mut client).await?;
+        }
+        Ok(client)
+    }
+
+    /// a connection to a read replica, not retried, the primary is
+    /// asked instead, see `ReplicaSet::connection`
+    async fn replica_connection(&self) -> Option<deadpool_postgres::Client> {
+        self.replicas.load().connection().await
+    }
+
+    /// The connections of the pool of the primary and the tasks
+    /// waiting for one, see `PoolStatus`
+    pub fn pool_status(&self) -> PoolStatus {
+        PoolStatus::new(self.pool.load().status(), &self.timeouts)
+    }
+
+    /// `pool_status` of each read replica, in the order they were
+    /// configured, timeouts are counted since the last `reload`
+    pub fn replica_pool_status(&self) -> Vec<PoolStatus> {
+        self.replicas.load().pool_status()
+    }
+
+    /// Where the time of the vault's operations goes, the round trips
+    /// to postgres, encryption and serialization, see `LatencyReport`
+    pub fn latency_report(&self) -> LatencyReport {
+        self.latency.report()
+    }
+
+    /// Query the idle connections of the pool, so a firewall or
+    /// load balancer does not drop them for being idle, connections
+    /// that do not answer are closed
+    /// returns:
+    ///     * the number of connections that answered
+    pub async fn keep_alive(&self) -> usize {
+        let pool = self.pool.load();
+        let idle = pool.status().available.max(0) as usize;
+        let mut alive = Vec::with_capacity(idle);
+        for _ in 0..idle {
+            let client = match pool.try_get().await {
+                Ok(client) => client,
+                Err(_) => break,
+            };
+            match client.simple_query("SELECT 1").await {
+                Ok(_) => alive.push(client),
+                Err(_) => drop(deadpool::managed::Object::take(client)),
+            }
+        }
+        alive.len()
+    }
+
+    /// `keep_alive` every `interval`, never returns, e.g. spawned
+    /// next to the application
+    #[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+    pub async fn keep_alive_every(&self, interval: Duration) {
+        loop {
+            sleep(interval).await;
+            self.keep_alive().await;
+        }
+    }
+
+    /// `record` compressed and encrypted as it is stored
+    fn seal(&self, record: &[u8]) -> Vec<u8> {
+        self.latency.time(LatencyStage::Encrypt, || self.encryption.load().encrypt(&self.compression.compress(record)))
+    }
+
+    /// the record sealed in `ciphertext`
+    fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, DataVaultError> {
+        self.latency.time(LatencyStage::Decrypt, || {
+            let record = self.encryption.load().decrypt_bytes(ciphertext);
+            Ok(CompressionAlgo::decompress(&record)?.into_owned())
+        })
+    }
+
+    /// `credit_card` with its number, name and expiry normalized, or
+    /// `DataVaultError::Validation` for a card number or expiry that
+    /// is not one, unless validation is turned off
+    fn validate_card<'a>(&self, credit_card: &'a CreditCard) -> Result<Cow<'a, CreditCard>, DataVaultError> {
+        if !self.validate_cards {
+            return Ok(normalize_card(credit_card))
+        }
+        Ok(validate_card(credit_card, self.reject_expired)?)
+    }
+
+    /// `value` as a record, timed
+    fn serialize<V: serde::Serialize>(&self, value: &V) -> Result<Vec<u8>, DataVaultError> {
+        self.latency.time(LatencyStage::Serialize, || self.serializer.serialize(value))
+    }
+
+    /// the value in `record`, timed
+    fn deserialize<V: serde::de::DeserializeOwned>(&self, record: &[u8]) -> Result<V, DataVaultError> {
+        self.latency.time(LatencyStage::Deserialize, || self.serializer.deserialize(record))
+    }
+
+    /// `open` for records handed out as text
+    fn open_string(&self, ciphertext: &[u8]) -> Result<String, DataVaultError> {
+        String::from_utf8(self.open(ciphertext)?).map_err(|e| DataVaultError::Encoding(Box::new(e)))
+    }
+
+    /// `query` on the table of this vault
+    fn sql(&self, query: &str) -> String {
+        query.replace("{table}", self.table.qualified())
+    }
+
+    /// This vault keeping card fields in columns as `layout` says
+    /// instead of the configured `DATA_VAULT_PLAINTEXT_FIELDS` and
+    /// `DATA_VAULT_BLIND_INDEX_FIELDS`
+    ///
+    /// Only cards stored from now on get the new columns.
+    pub fn with_card_fields(self, layout: CardFieldLayout) -> Self {
+        PostgresDataVault {
+            card_fields: Reloadable::new(layout),
+            ..self
+        }
+    }
+
+    /// Tokens of the cards whose `field` is `value`, oldest first
+    ///
+    /// The field has to be kept in plaintext or as a blind index.
+    /// returns:
+    ///     * `DataVaultError::InvalidFieldLayout` when `field` is only encrypted
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault, CardField, CardFieldLayout, FieldStorage};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let layout = CardFieldLayout::new().unwrap()
+    ///     .with(CardField::ExpirationYear, FieldStorage::Plaintext).unwrap();
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+    ///     .with_card_fields(layout);
+    /// let expiring = data_vault.tokens_where(CardField::ExpirationYear, "2023").await.unwrap();
+    /// ```
+    pub async fn tokens_where(&self, field: CardField, value: &str) -> Result<Vec<String>, DataVaultError> {
+        let client = self.connection().await?;
+        self.tokens_where_on(&**client, field, value).await
+    }
+
+    /// Create the table of this vault, or upgrade it, with the
+    /// migrations embedded in the crate it does not have yet
+    ///
+    /// The migrations applied to each table are kept in
+    /// `data_vault_schema_migrations` in its schema.  They run in one
+    /// transaction, vaults migrating at the same time wait for each
+    /// other, so this can run on every start of the application.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.run_migrations().await.unwrap();
+    /// println!("table at version {}, applied {:?}", report.version, report.applied);
+    /// ```
+    pub async fn run_migrations(&self) -> Result<SchemaReport, DataVaultError> {
+        let mut client = self.checkout().await?;
+        self.table.migrate(&mut client).await
+    }
+
+    /// Check that every row of the vault decrypts and reads as a card
+    /// or text, and that its columns agree with it, see `VerifyReport`
+    ///
+    /// Deleted and expired rows not purged yet are checked as well.
+    /// A card field column that is set but holds another value than
+    /// the current `CardFieldLayout` gives for the card is a
+    /// `RecordProblem::Metadata`, e.g. after a field moved from
+    /// plaintext to a blind index.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.verify().await.unwrap();
+    /// assert!(report.is_ok(), "{:?}", report.issues);
+    /// ```
+    pub async fn verify(&self) -> Result<VerifyReport, DataVaultError> {
+        let encryption = self.encryption.load();
+        let card_fields = self.card_fields.load();
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(SELECT_VERIFY_AFTER)).await?;
+
+        let mut report = VerifyReport::default();
+        let mut after_id = 0i64;
+        loop {
+            let rows = client.query(&stmt, &[&self.namespace, &after_id, &RECORD_BATCH_SIZE]).await?;
+            for row in &rows {
+                let token: String = row.get("token");
+                let problem = match check_record(&*encryption, &*self.serializer, &token, row.get("credit_card")) {
+                    Ok(credit_card) => metadata_problem(row, credit_card.as_ref(), &card_fields),
+                    Err(problem) => Some(problem),
+                };
+                report.check(&token, problem);
+            }
+
+            match rows.last() {
+                Some(row) if rows.len() as i64 == RECORD_BATCH_SIZE => after_id = row.get("id"),
+                _ => return Ok(report),
+            }
+        }
+    }
+
+    /// Cross-check the plaintext and blind index card field columns
+    /// of this namespace against the records, see `IntegrityReport`
+    ///
+    /// Reports columns the layout of the vault fills that are empty,
+    /// e.g. of rows stored before a blind index was added, columns
+    /// that do not match the record and columns the layout leaves
+    /// empty that hold a value, e.g. plaintext left behind after a
+    /// field was made encrypted only.  Rows of every state are
+    /// checked, rows that do not decrypt are left to `verify`.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.integrity_report().await.unwrap();
+    /// if !report.is_ok() {
+    ///     println!("repaired {} of {:?}", data_vault.repair_integrity(&report).await.unwrap(), report.issues);
+    /// }
+    /// ```
+    pub async fn integrity_report(&self) -> Result<IntegrityReport, DataVaultError> {
+        let encryption = self.encryption.load();
+        let card_fields = self.card_fields.load();
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(SELECT_VERIFY_AFTER)).await?;
+
+        let mut report = IntegrityReport::default();
+        let mut after_id = 0i64;
+        loop {
+            let rows = client.query(&stmt, &[&self.namespace, &after_id, &RECORD_BATCH_SIZE]).await?;
+            for row in &rows {
+                let token: String = row.get("token");
+                if let Ok(credit_card) = check_record(&*encryption, &*self.serializer, &token, row.get("credit_card")) {
+                    for (column, problem) in card_field_problems(row, credit_card.as_ref(), &card_fields) {
+                        report.check(&token, column, problem);
+                    }
+                }
+            }
+
+            match rows.last() {
+                Some(row) if rows.len() as i64 == RECORD_BATCH_SIZE => after_id = row.get("id"),
+                _ => return Ok(report),
+            }
+        }
+    }
+
+    /// Fix the issues of `report`, made by `integrity_report` of this
+    /// namespace, by writing the card field columns of each row from
+    /// its record again.  Rows changed since they were read are left
+    /// alone.
+    /// returns:
+    ///     * how many issues were fixed
+    pub async fn repair_integrity(&self, report: &IntegrityReport) -> Result<u64, DataVaultError> {
+        let encryption = self.encryption.load();
+        let card_fields = self.card_fields.load();
+        let client = self.connection().await?;
+        let select = client.prepare(&self.sql(SELECT_CIPHERTEXT)).await?;
+        let update = client.prepare(&self.sql(UPDATE_CARD_FIELDS)).await?;
+
+        let issues = || report.issues.iter().filter(|issue| CARD_FIELD_COLUMNS.contains(&issue.index));
+        let mut tokens: Vec<&str> = issues().map(|issue| issue.token.as_str()).collect();
+        tokens.sort_unstable();
+        tokens.dedup();
+
+        let mut repaired = 0;
+        for token in tokens {
+            let ciphertext: Vec<u8> = match client.query_opt(&select, &[&self.namespace, &token]).await? {
+                Some(row) => row.get("credit_card"),
+                None => continue,
+            };
+            let columns = match check_record(&*encryption, &*self.serializer, token, &ciphertext) {
+                Ok(credit_card) => credit_card.map(|credit_card| card_fields.columns(&credit_card)).unwrap_or_default(),
+                Err(_) => continue,
+            };
+            let [number, cardholder_name, expiration_month, expiration_year, brand] = &columns;
+            if client.execute(&update, &[&self.namespace, &token, number, cardholder_name, expiration_month, expiration_year, brand, &ciphertext]).await? == 1 {
+                repaired += issues().filter(|issue| issue.token == token).count() as u64;
+            }
+        }
+        Ok(repaired)
+    }
+
+    /// The id, algorithm and age of the key the vault encrypts with,
+    /// never the key itself, see `KeyInfo`
+    pub fn key_info(&self) -> KeyInfo {
+        KeyInfo::clone(&self.key.load())
+    }
+
+    /// The algorithms, key age, token kind, security code policy and
+    /// retention period of the vault as evidence for a PCI DSS
+    /// assessment, see `ComplianceReport`
+    pub fn compliance_report(&self) -> ComplianceReport
+        where
+            T: Tokenizer,
+    {
+        ComplianceReport::new("postgres", &self.namespace, &self.key.load(), self.tokenizer.load().is_deterministic(), self.cvv_policy, self.compression, self.retention)
+    }
+
+    /// The records of this namespace by the time since they were
+    /// first stored, in a bucket from zero to the first of `bounds`
+    /// and one from each bound to the next, e.g. with
+    /// `AgeHistogram::DEFAULT_BOUNDS`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::admin::AgeHistogram;
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let histogram = data_vault.record_histogram_by_age(&AgeHistogram::DEFAULT_BOUNDS).await.unwrap();
+    /// assert_eq!(histogram.total(), data_vault.count().await.unwrap());
+    /// ```
+    pub async fn record_histogram_by_age(&self, bounds: &[Duration]) -> Result<AgeHistogram, DataVaultError> {
+        let bounds = AgeHistogram::bounds(bounds);
+        let secs: Vec<f64> = bounds.iter().map(Duration::as_secs_f64).collect();
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(SELECT_AGE_HISTOGRAM)).await?;
+
+        let mut counts = vec![0; bounds.len() + 1];
+        for row in client.query(&stmt, &[&self.namespace, &secs]).await? {
+            let bucket: i32 = row.get("bucket");
+            let count: i64 = row.get("count");
+            counts[bucket as usize] = count as u64;
+        }
+        Ok(AgeHistogram::from_counts(&bounds, &counts))
+    }
+
+    /// The live and soft deleted records of every namespace in the
+    /// vault's table, ordered by namespace, side namespaces left out
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// for tenant in data_vault.tenant_summary().await.unwrap() {
+    ///     println!("{:?}: {} records", tenant.namespace, tenant.count);
+    /// }
+    /// ```
+    pub async fn tenant_summary(&self) -> Result<Vec<TenantSummary>, DataVaultError> {
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(SELECT_TENANT_SUMMARY)).await?;
+        let rows = client.query(&stmt, &[]).await?;
+
+        // side namespaces are the only tenants no caller can choose
+        Ok(rows.iter().filter(|row| validate_namespace(row.get("tenant")).is_ok()).map(|row| {
+            let count: i64 = row.get("count");
+            let soft_deleted: i64 = row.get("soft_deleted");
+            TenantSummary {
+                namespace: row.get("tenant"),
+                count: count as u64,
+                soft_deleted: soft_deleted as u64,
+                oldest: row.get("oldest"),
+                newest: row.get("newest"),
+            }
+        }).collect())
+    }
+
+    /// Run several operations in one database transaction
+    ///
+    /// The transaction is committed when `f` returns `Ok` and rolled
+    /// back when it returns `Err`.  Use `PostgresTransaction::client`
+    /// to run statements of your own, e.g. an audit row, in the same
+    /// transaction.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, PostgresDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let token = data_vault.transaction(move |tx| Box::pin(async move {
+    ///     let token = tx.store_credit_card(&cc).await?;
+    ///     tx.client().execute("INSERT INTO audit (token) VALUES ($1)", &[&token]).await?;
+    ///     Ok(token)
+    /// })).await.unwrap();
+    /// ```
+    pub async fn transaction<F, R>(&self, f: F) -> Result<R, DataVaultError>
+        where
+            F: for<'t, 'c> FnOnce(&'t mut PostgresTransaction<'c, E, T, S>) -> TransactionFuture<'t, R>,
+    {
+        let mut client = self.connection().await?;
+        let mut tx = PostgresTransaction {
+            transaction: client.transaction().await?,
+            vault: self,
+        };
+
+        // dropping the transaction without commit rolls it back
+        let result = f(&mut tx).await?;
+        tx.transaction.commit().await?;
+        Ok(result)
+    }
+
+    /// the next batch of records with an id above `after_id`
+    /// returns the id of the last record in the batch
+    async fn records_after(&self, after_id: i64, limit: i64) -> Result<(i64, Vec<(String, Vec<u8>)>), DataVaultError> {
+        let client = self.connection().await?;
+        let stmt = client.prepare(&self.sql(SELECT_RECORDS_AFTER)).await?;
+        let rows = client.query(&stmt, &[&self.namespace, &after_id, &limit]).await?;
+        let last_id = rows.last().map(|row| row.get("id")).unwrap_or(after_id);
+        let records = rows.iter()
+            .map(|row| (row.get("token"), row.get("credit_card")))
+            .collect();
+        Ok((last_id, records))
+    }
+
+    async fn store_on<C>(&self, client: &C, token: &str, string: &str) -> Result<(), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        self.store_expiring_on(client, token, string.as_bytes(), None, &Default::default()).await
+    }
+
+    /// `store_on` with a time to live, `None` never expires, and the
+    /// card field columns
+    async fn store_expiring_on<C>(&self, client: &C, token: &str, record: &[u8], ttl: Option<Duration>, columns: &[Option<String>; 5]) -> Result<(), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let encrypted_json = self.seal(record);
+        let ttl_secs = ttl.map(|ttl| ttl.as_secs_f64());
+        let [number, cardholder_name, expiration_month, expiration_year, brand] = columns;
+        let stmt = client.prepare(&self.sql(UPSERT_CREDIT_CARD)).await?;
+        let params: [&(dyn ToSql + Sync); 9] = [&self.namespace, &token, &encrypted_json, &ttl_secs, number, cardholder_name, expiration_month, expiration_year, brand];
+        self.latency.time_async(LatencyStage::Store, client.execute(&stmt, &params)).await?;
+        Ok(())
+    }
+
+    async fn tokens_where_on<C>(&self, client: &C, field: CardField, value: &str) -> Result<Vec<String>, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let column_value = self.card_fields.load().column_value(field, value)?;
+        // the column name comes from `CardField`, never from the caller
+        let query = format!("SELECT token FROM {} WHERE tenant = $1 AND {} = $2 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > now()) ORDER BY id", self.table.qualified(), field.column());
+        let stmt = client.prepare(&query).await?;
+        let rows = client.query(&stmt, &[&self.namespace, &column_value]).await?;
+        Ok(rows.iter().map(|row| row.get("token")).collect())
+    }
+
+    async fn store_credit_card_on<C>(&self, client: &C, credit_card: &CreditCard) -> Result<String, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let credit_card = self.validate_card(credit_card)?;
+        let credit_card = with_brand(&credit_card);
+        let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+        let token = self.tokenizer.load().generate(&credit_card);
+        let record = self.serialize(&credit_card)?;
+        let columns = self.card_fields.load().columns(&credit_card);
+        self.store_expiring_on(client, &token, &record, ttl, &columns).await?;
+        self.store_summary_on(client, &token, &credit_card, ttl).await?;
+        Ok(token)
+    }
+
+    async fn tokenize_on<C>(&self, client: &C, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let credit_card = self.validate_card(credit_card)?;
+        let credit_card = with_brand(&credit_card);
+        let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+        let token = self.tokenizer.load().generate(&credit_card);
+        let record = self.serialize(&credit_card)?;
+        let columns = self.card_fields.load().columns(&credit_card);
+
+        if !self.tokenizer.load().is_deterministic() {
+            self.store_expiring_on(client, &token, &record, ttl, &columns).await?;
+            self.store_summary_on(client, &token, &credit_card, ttl).await?;
+            return Ok((token, true))
+        }
+
+        let created = self.store_expiring_if_absent_on(client, &token, &record, ttl, &columns).await?;
+        if created {
+            self.store_summary_on(client, &token, &credit_card, ttl).await?;
+        }
+        Ok((token, created))
+    }
+
+    /// `retrieve_credit_card_on` a replica, or the primary when there
+    /// is none or the replica lags
+    async fn retrieve_credit_card_from_replica(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        if let Some(client) = self.replica_connection().await {
+            match self.retrieve_credit_card_on(&**client, token).await {
+                Err(e) if ask_primary(&e) => {},
+                result => return result,
+            }
+        }
+        let client = self.connection().await?;
+        self.retrieve_credit_card_on(&**client, token).await
+    }
+
+    /// Keep the `CardSummary` of `credit_card` in the side namespace
+    /// `<namespace>~summary`, expiring with the card
+    async fn store_summary_on<C>(&self, client: &C, token: &str, credit_card: &CreditCard, ttl: Option<Duration>) -> Result<(), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let summary = serde_json::to_vec(&CardSummary::from(credit_card))?;
+        self.with_side_namespace("summary")?
+            .store_expiring_on(client, token, &summary, ttl, &Default::default()).await
+    }
+
+    /// `store_expiring_on` leaving a live row under `token` as is
+    /// returns:
+    ///     * whether the record was stored
+    async fn store_expiring_if_absent_on<C>(&self, client: &C, token: &str, record: &[u8], ttl: Option<Duration>, columns: &[Option<String>; 5]) -> Result<bool, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let encrypted_json = self.seal(record);
+        let ttl_secs = ttl.map(|ttl| ttl.as_secs_f64());
+        let [number, cardholder_name, expiration_month, expiration_year, brand] = columns;
+        let stmt = client.prepare(&self.sql(INSERT_CREDIT_CARD_IF_ABSENT)).await?;
+        let params: [&(dyn ToSql + Sync); 9] = [&self.namespace, &token, &encrypted_json, &ttl_secs, number, cardholder_name, expiration_month, expiration_year, brand];
+        let inserted = self.latency.time_async(LatencyStage::Store, client.execute(&stmt, &params)).await?;
+        Ok(inserted == 1)
+    }
+
+    async fn store_credit_card_with_token_on<C>(&self, client: &C, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        validate_token(token)?;
+        let credit_card = self.validate_card(credit_card)?;
+        let credit_card = with_brand(&credit_card);
+        let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+        let record = self.serialize(&credit_card)?;
+        let columns = self.card_fields.load().columns(&credit_card);
+
+        if overwrite {
+            self.store_expiring_on(client, token, &record, ttl, &columns).await?;
+            return self.store_summary_on(client, token, &credit_card, ttl).await
+        }
+
+        match self.store_expiring_if_absent_on(client, token, &record, ttl, &columns).await? {
+            true => self.store_summary_on(client, token, &credit_card, ttl).await,
+            false => Err(DataVaultError::AlreadyExists),
+        }
+    }
+
+    async fn retrieve_on<C>(&self, client: &C, token: &str) -> Result<String, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let (record, _) = self.retrieve_with_version_on(client, token).await?;
+        String::from_utf8(record).map_err(|e| DataVaultError::Encoding(Box::new(e)))
+    }
+
+    async fn exists_on<C>(&self, client: &C, token: &str) -> Result<bool, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let stmt = client.prepare(&self.sql(SELECT_EXISTS)).await?;
+        let row = client.query_one(&stmt, &[&self.namespace, &token]).await?;
+        Ok(row.get(0))
+    }
+
+    /// the decrypted record stored under `token` and its version
+    async fn retrieve_with_version_on<C>(&self, client: &C, token: &str) -> Result<(Vec<u8>, u64), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let stmt = client.prepare(&self.sql(SELECT_CREDIT_CARD)).await?;
+        let row = self.latency.time_async(LatencyStage::Retrieve, client.query_opt(&stmt, &[&self.namespace, &token])).await?;
+        match row {
+            Some(row) => {
+                let encrypted_credit_card_json: Vec<u8> = row.get("credit_card");
+                let version: i64 = row.get("version");
+                Ok((self.open(encrypted_credit_card_json.as_slice())?, version as u64))
+            },
+            None => Err(DataVaultError::NotFound),
+        }
+    }
+
+    async fn retrieve_credit_card_on<C>(&self, client: &C, token: &str) -> Result<CreditCard, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let (record, _) = self.retrieve_with_version_on(client, token).await?;
+        self.deserialize(&record)
+    }
+
+    async fn retrieve_credit_card_with_version_on<C>(&self, client: &C, token: &str) -> Result<(CreditCard, u64), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let (record, version) = self.retrieve_with_version_on(client, token).await?;
+        Ok((self.deserialize(&record)?, version))
+    }
+
+    async fn retrieve_with_metadata_on<C>(&self, client: &C, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let stmt = client.prepare(&self.sql(SELECT_CREDIT_CARD_WITH_METADATA)).await?;
+        let row = self.latency.time_async(LatencyStage::Retrieve, client.query_opt(&stmt, &[&self.namespace, &token])).await?
+            .ok_or(DataVaultError::NotFound)?;
+
+        let encrypted_credit_card_json: Vec<u8> = row.get("credit_card");
+        let record = self.open(encrypted_credit_card_json.as_slice())?;
+        let version: i64 = row.get("version");
+        let ttl_secs: Option<f64> = row.get("ttl_secs");
+        let metadata = RecordMetadata {
+            created_at: row.get("created_at"),
+            ttl: ttl_secs.map(|ttl_secs| Duration::from_secs_f64(ttl_secs.max(0.0))),
+            version: version as u64,
+            tenant: self.namespace.clone(),
+        };
+        Ok((self.deserialize(&record)?, metadata))
+    }
+
+    async fn update_credit_card_if_version_on<C>(&self, client: &C, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let credit_card = self.validate_card(credit_card)?;
+        let credit_card = with_brand(&credit_card);
+        let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+        let record = self.serialize(&credit_card)?;
+        let encrypted_json = self.seal(&record);
+        let expected_version = expected_version as i64;
+        let ttl_secs = ttl.map(|ttl| ttl.as_secs_f64());
+        let [number, cardholder_name, expiration_month, expiration_year, brand] = &self.card_fields.load().columns(&credit_card);
+        let stmt = client.prepare(&self.sql(UPDATE_CREDIT_CARD_IF_VERSION)).await?;
+        let row = client.query_opt(&stmt, &[&self.namespace, &token, &expected_version, &encrypted_json, &ttl_secs, number, cardholder_name, expiration_month, expiration_year, brand]).await?;
+
+        if let Some(row) = row {
+            let version: i64 = row.get("version");
+            self.store_summary_on(client, token, &credit_card, ttl).await?;
+            // the card may have a new number, index the one stored
+            if let Some(fingerprint) = self.tokenizer.load().fingerprint(&credit_card) {
+                self.with_side_namespace("fingerprint")?
+                    .store_on(client, &fingerprint, token).await?;
+            }
+            return Ok(version as u64)
+        }
+
+        // nothing updated, tell a missing record from a newer one
+        match self.retrieve_with_version_on(client, token).await {
+            Ok(_) => Err(DataVaultError::Conflict),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn rotate_token_on<C>(&self, client: &C, token: &str) -> Result<String, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let new_token = RandomToken::generate();
+        let stmt = client.prepare(&self.sql(UPDATE_TOKEN)).await?;
+        if client.execute(&stmt, &[&self.namespace, &token, &new_token]).await? == 0 {
+            return Err(DataVaultError::NotFound)
+        }
+        // side data about the record follows it to the new token
+        for kind in TOKEN_SIDE_KINDS.iter() {
+            client.execute(&stmt, &[&side_namespace(&self.namespace, kind), &token, &new_token]).await?;
+        }
+        // and short lived side data is dropped
+        let stmt = client.prepare(&self.sql(DELETE_MANY)).await?;
+        for kind in TRANSIENT_SIDE_KINDS.iter() {
+            client.query_one(&stmt, &[&side_namespace(&self.namespace, kind), &vec![token]]).await?;
+        }
+        Ok(new_token)
+    }
+
+    async fn delete_many_on<C>(&self, client: &C, tokens: &[String]) -> Result<usize, DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let stmt = client.prepare(&self.sql(DELETE_MANY)).await?;
+        let row = client.query_one(&stmt, &[&self.namespace, &tokens]).await?;
+        let deleted: i64 = row.get(0);
+        for kind in TOKEN_SIDE_KINDS.iter().chain(TRANSIENT_SIDE_KINDS.iter()) {
+            client.query_one(&stmt, &[&side_namespace(&self.namespace, kind), &tokens]).await?;
+        }
+        Ok(deleted as usize)
+    }
+
+    async fn soft_delete_on<C>(&self, client: &C, token: &str) -> Result<(), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let stmt = client.prepare(&self.sql(SOFT_DELETE_CREDIT_CARD)).await?;
+        let retention_secs = self.retention.as_secs_f64();
+        match client.execute(&stmt, &[&self.namespace, &token, &retention_secs]).await? {
+            0 => Err(DataVaultError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn touch_on<C>(&self, client: &C, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError>
+        where C: GenericClient + std::marker::Sync
+    {
+        let stmt = client.prepare(&self.sql(TOUCH_CREDIT_CARD)).await?;
+        let ttl_secs = ttl.map(|ttl| ttl.as_secs_f64());
+        match client.execute(&stmt, &[&self.namespace, &token, &ttl_secs]).await? {
+            0 => Err(DataVaultError::NotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A database transaction of a `PostgresDataVault`
+///
+/// Operations behave like the `DataVault` methods of the same name
+/// but only become visible to others once the transaction commits,
+/// see `PostgresDataVault::transaction`.
+pub struct PostgresTransaction<'a, E, T, S = JsonSerializer> {
+    transaction: deadpool_postgres::Transaction<'a>,
+    vault: &'a PostgresDataVault<E, T, S>,
+}
+
+impl<'a, E, T, S> PostgresTransaction<'a, E, T, S>
+    where
+        E: Encryption + std::marker::Sync + std::marker::Send,
+        T: Tokenizer + std::marker::Sync + std::marker::Send,
+        S: Serializer + std::marker::Sync + std::marker::Send,
+{
+    /// see `DataVault::store`
+    pub async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.vault.store_on(&*self.transaction, token, string).await
+    }
+
+    /// see `DataVault::store_if_absent`
+    pub async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.vault.store_expiring_if_absent_on(&*self.transaction, token, string.as_bytes(), None, &Default::default()).await
+    }
+
+    /// see `DataVault::store_credit_card`
+    pub async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.vault.store_credit_card_on(&*self.transaction, credit_card).await
+    }
+
+    /// see `DataVault::tokenize`
+    pub async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.vault.tokenize_on(&*self.transaction, credit_card).await
+    }
+
+    /// see `DataVault::store_credit_card_with_token`
+    pub async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.vault.store_credit_card_with_token_on(&*self.transaction, token, credit_card, overwrite).await
+    }
+
+    /// see `DataVault::retrieve`
+    pub async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.vault.retrieve_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::retrieve_credit_card`
+    pub async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.vault.retrieve_credit_card_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::exists`
+    pub async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.vault.exists_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::retrieve_credit_card_with_version`
+    pub async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.vault.retrieve_credit_card_with_version_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::retrieve_with_metadata`
+    pub async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.vault.retrieve_with_metadata_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::update_credit_card_if_version`
+    pub async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.vault.update_credit_card_if_version_on(&*self.transaction, token, credit_card, expected_version).await
+    }
+
+    /// see `DataVault::rotate_token`
+    pub async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.vault.rotate_token_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::soft_delete`
+    pub async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.vault.soft_delete_on(&*self.transaction, token).await
+    }
+
+    /// see `DataVault::touch`
+    pub async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.vault.touch_on(&*self.transaction, token, ttl).await
+    }
+
+    /// see `DataVault::delete_many`
+    pub async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.vault.delete_many_on(&*self.transaction, tokens).await
+    }
+
+    /// see `PostgresDataVault::tokens_where`
+    pub async fn tokens_where(&self, field: CardField, value: &str) -> Result<Vec<String>, DataVaultError> {
+        self.vault.tokens_where_on(&*self.transaction, field, value).await
+    }
+
+    /// The underlying transaction, for statements of your own
+    pub fn client(&self) -> &deadpool_postgres::Transaction<'a> {
+        &self.transaction
+    }
+}
+
+#[cfg(all(test, feature = "rt-tokio"))]
+mod test {
+    use credit_card::CreditCard;
+    use crate::traits::DataVault;
+    use crate::error::DataVaultError;
+    use crate::postgres_data_vault::{quote_identifier, PostgresDataVault};
+    use crate::config::PostgresVaultConfig;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::tokenizer::Blake3Tokenizer;
+    use crate::fields::{CardField, CardFieldLayout, FieldStorage};
+    use crate::verify::RecordProblem;
+    use crate::admin::AgeHistogram;
+    use crate::integrity::IntegrityProblem;
+    use crate::namespace::side_namespace;
+    use std::time::Duration;
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("data_vault").unwrap(), "\"data_vault\"");
+        assert_eq!(quote_identifier("Cards\"; DROP TABLE x; --").unwrap(), "\"Cards\"\"; DROP TABLE x; --\"");
+        assert!(quote_identifier("").is_err());
+        assert!(quote_identifier(&"x".repeat(64)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_dir() {
+        use deadpool_postgres::tokio_postgres::config::Host;
+
+        let cfg = PostgresVaultConfig::from_env().unwrap().with_socket_dir("/run/postgresql");
+        let pg_config = cfg.postgres.get_pg_config().unwrap();
+        assert_eq!(pg_config.get_hosts(), &[Host::Unix("/run/postgresql".into())]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn custom_table_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap();
+        let pool = cfg.postgres.create_pool(deadpool_postgres::tokio_postgres::NoTls).unwrap();
+        pool.get().await.unwrap().batch_execute(r#"CREATE SCHEMA IF NOT EXISTS "vault test";"#).await.unwrap();
+
+        let cfg = cfg.with_table(Some("vault test"), "Cards\"").with_auto_create_schema(true);
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+        let default_table = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+        assert!(!default_table.exists(&token).await.unwrap());
+        assert!(vault.stats().await.unwrap().count >= 1);
+        assert_eq!(vault.delete_many(&[token]).await.unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn run_migrations_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap();
+        let pool = cfg.postgres.create_pool(deadpool_postgres::tokio_postgres::NoTls).unwrap();
+        let client = pool.get().await.unwrap();
+        // a new table and one from the DDL of the first version
+        client.batch_execute(r#"
+            DROP SCHEMA IF EXISTS migrations_test CASCADE;
+            CREATE SCHEMA migrations_test;
+            CREATE TABLE migrations_test.data_vault (id bigserial NOT NULL PRIMARY KEY, "token" varchar(64) NOT NULL, credit_card bytea NOT NULL);
+            CREATE UNIQUE INDEX data_vault_token_idx ON migrations_test.data_vault USING btree (token);
+        "#).await.unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        for table in &["data_vault", "new_table"] {
+            let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg.clone().with_table(Some("migrations_test"), table)).unwrap();
+            let report = vault.run_migrations().await.unwrap();
+            assert_eq!(report.version, 6);
+            assert_eq!(report.applied.iter().map(|migration| migration.version).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 6]);
+            assert_eq!(vault.run_migrations().await.unwrap(), crate::schema::SchemaReport { version: 6, applied: vec![] });
+
+            let token = vault.store_credit_card(&cc).await.unwrap();
+            // tokens are unique per tenant
+            vault.with_namespace("other").unwrap().store_credit_card_with_token(&token, &cc, false).await.unwrap();
+            assert_eq!(vault.retrieve_credit_card_with_version(&token).await.unwrap().1, 1);
+        }
+        let old_index = client.query_one("SELECT to_regclass('migrations_test.data_vault_token_idx') IS NULL", &[]).await.unwrap();
+        assert!(old_index.get::<_, bool>(0));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn auto_create_schema_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap();
+        let pool = cfg.postgres.create_pool(deadpool_postgres::tokio_postgres::NoTls).unwrap();
+        pool.get().await.unwrap().batch_execute(r#"
+            DROP SCHEMA IF EXISTS auto_create_test CASCADE;
+            CREATE SCHEMA auto_create_test;
+        "#).await.unwrap();
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(
+            cfg.with_table(Some("auto_create_test"), "data_vault").with_auto_create_schema(true)
+        ).unwrap();
+        // the first operations wait for the table to be created
+        let stored = futures::future::try_join_all((0..4).map(|i| {
+            let vault = vault.with_namespace(&format!("tenant-{}", i)).unwrap();
+            async move { vault.store("auto-created", "{number: 123}").await.map(|_| vault) }
+        })).await.unwrap();
+        for vault in &stored {
+            assert_eq!(vault.retrieve("auto-created").await.unwrap(), "{number: 123}");
+        }
+        assert_eq!(vault.run_migrations().await.unwrap().applied, vec![]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replicas_postgres() {
+        let cfg = PostgresVaultConfig::from_env().unwrap();
+        let pool = cfg.postgres.create_pool(deadpool_postgres::tokio_postgres::NoTls).unwrap();
+        pool.get().await.unwrap().batch_execute("CREATE SCHEMA IF NOT EXISTS replica_test").await.unwrap();
+
+        // a "replica" on the same server reading another table, which
+        // holds only what is stored through `behind`
+        let mut replica = cfg.postgres.clone();
+        replica.options = Some("-c search_path=replica_test".to_string());
+        let behind = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(PostgresVaultConfig {
+            postgres: replica.clone(),
+            ..cfg.clone().with_auto_create_schema(true)
+        }).unwrap().with_namespace("replica-test").unwrap();
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(
+            cfg.clone().with_replica(replica).with_max_replica_lag(Duration::from_secs(1))
+        ).unwrap().with_namespace("replica-test").unwrap();
+
+        behind.store("replica-only", "{number: 123}").await.unwrap();
+        assert!(vault.exists("replica-only").await.unwrap());
+        assert_eq!(vault.retrieve("replica-only").await.unwrap(), "{number: 123}");
+        assert!(vault.replicas.load().pools().all(|pool| pool.status().size > 0));
+
+        // not on the replica yet, the primary answers
+        vault.store("primary-only", "{number: 456}").await.unwrap();
+        assert!(vault.exists("primary-only").await.unwrap());
+        assert_eq!(vault.retrieve("primary-only").await.unwrap(), "{number: 456}");
+        assert!(!vault.exists("replica-missing").await.unwrap());
+
+        let mut unreachable = cfg.postgres.clone();
+        unreachable.port = Some(1);
+        let fallback = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(
+            cfg.with_replica(unreachable)
+        ).unwrap().with_namespace("replica-test").unwrap();
+        assert_eq!(fallback.retrieve("primary-only").await.unwrap(), "{number: 456}");
+
+        assert_eq!(behind.delete_many(&["replica-only".to_string()]).await.unwrap(), 1);
+        assert_eq!(vault.delete_many(&["primary-only".to_string()]).await.unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pool_timeouts_postgres() {
+        let mut cfg = PostgresVaultConfig::from_env().unwrap().with_pool_max_size(1);
+        cfg.postgres.pool.as_mut().unwrap().timeouts.wait = Some(Duration::from_millis(50));
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::from_config(cfg).unwrap();
+
+        let held = vault.connection().await.unwrap();
+        assert!(vault.exists("pool-timeout-missing").await.unwrap_err().is_transient());
+        let status = vault.pool_status();
+        assert_eq!((status.size, status.available, status.timeouts), (1, 0, 1));
+        drop(held);
+        assert!(!vault.exists("pool-timeout-missing").await.unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn purge_expired_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let vault = PostgresDataVault {
+            retention: Duration::from_secs(0),
+            ..vault.with_namespace("purge-test").unwrap()
+        };
+
+        let token = String::from("abc123");
+        vault.store(&token, "{number: 123}").await.unwrap();
+        vault.soft_delete(&token).await.unwrap();
+
+        let report = vault.purge_expired().await.unwrap();
+        assert_eq!(report.purged, 1);
+        assert_eq!(vault.purge_expired().await.unwrap().purged, 0)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn purge_expired_cvv_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("purge-cvv-test").unwrap();
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        vault.store_cvv(&token, "123", Duration::from_secs(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        vault.purge_expired().await.unwrap();
+
+        // the expired code is gone from the table, not only unreadable
+        let client = vault.connection().await.unwrap();
+        let row = client.query_one(vault.sql("SELECT count(*) FROM {table} WHERE tenant = $1").as_str(), &[&side_namespace(&vault.namespace, "cvv")]).await.unwrap();
+        assert_eq!(row.get::<_, i64>(0), 0);
+        vault.delete_many(&[token]).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transaction_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let committed = cc.clone();
+        let token = vault.transaction(move |tx| Box::pin(async move {
+            let token = tx.store_credit_card(&committed).await?;
+            assert_eq!(tx.retrieve_credit_card(&token).await?.number, committed.number);
+            Ok(token)
+        })).await.unwrap();
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, cc.number);
+
+        let deleted = token.clone();
+        let result: Result<(), DataVaultError> = vault.transaction(move |tx| Box::pin(async move {
+            tx.soft_delete(&deleted).await?;
+            tx.store("rolled-back", "{number: 123}").await?;
+            Err(DataVaultError::Conflict)
+        })).await;
+        assert!(matches!(result, Err(DataVaultError::Conflict)));
+        assert!(vault.try_retrieve("rolled-back").await.unwrap().is_none());
+        assert!(vault.try_retrieve(&token).await.unwrap().is_some())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn verify_postgres() {
+        let layout = CardFieldLayout::new().unwrap()
+            .with(CardField::ExpirationYear, FieldStorage::Plaintext).unwrap();
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let vault = vault.with_namespace("verify-test").unwrap().with_card_fields(layout);
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        vault.store("verify-text", "{number: 123}").await.unwrap();
+        vault.store("verify-deleted", "{number: 456}").await.unwrap();
+        vault.soft_delete("verify-deleted").await.unwrap();
+        assert!(vault.verify().await.unwrap().is_ok());
+
+        let client = vault.connection().await.unwrap();
+        let sql = |query: &str| vault.sql(query);
+        client.execute(sql("INSERT INTO {table} (tenant, token, credit_card) VALUES ($1, 'verify-corrupt', $2), ($1, 'verify invalid', $2)").as_str(), &[&vault.namespace, &b"not a ciphertext".to_vec()]).await.unwrap();
+        client.execute(sql("UPDATE {table} SET expiration_year = '2099' WHERE tenant = $1 AND token = $2").as_str(), &[&vault.namespace, &token]).await.unwrap();
+        client.execute(sql("UPDATE {table} SET version = 0 WHERE tenant = $1 AND token = 'verify-text'").as_str(), &[&vault.namespace]).await.unwrap();
+        client.execute(sql("UPDATE {table} SET expires_at = NULL WHERE tenant = $1 AND token = 'verify-deleted'").as_str(), &[&vault.namespace]).await.unwrap();
+
+        let report = vault.verify().await.unwrap();
+        assert_eq!(report.checked, 5);
+        let mut issues: Vec<(&str, RecordProblem)> = report.issues.iter().map(|issue| (issue.token.as_str(), issue.problem)).collect();
+        issues.sort_by_key(|(token, _)| *token);
+        let mut expected = vec![
+            (token.as_str(), RecordProblem::Metadata("card field column does not match the record")),
+            ("verify invalid", RecordProblem::InvalidToken),
+            ("verify-corrupt", RecordProblem::Undecryptable),
+            ("verify-deleted", RecordProblem::Metadata("deleted without an expiry")),
+            ("verify-text", RecordProblem::Metadata("version below 1")),
+        ];
+        expected.sort_by_key(|(token, _)| *token);
+        assert_eq!(issues, expected);
+
+        client.execute(sql("DELETE FROM {table} WHERE tenant = $1").as_str(), &[&vault.namespace]).await.unwrap();
+        assert_eq!(vault.verify().await.unwrap().checked, 0)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn admin_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let key = vault.key_info();
+        assert_eq!((key.key_id.len(), key.algorithm), (16, "AES-256-GCM-SIV"));
+        vault.reload().unwrap();
+        assert_eq!(vault.key_info(), key);
+
+        let vault = vault.with_namespace("admin-test").unwrap();
+        let client = vault.connection().await.unwrap();
+        let sql = |query: &str| vault.sql(query);
+        client.execute(sql("DELETE FROM {table} WHERE tenant = $1").as_str(), &[&vault.namespace]).await.unwrap();
+        vault.store("admin-new", "{}").await.unwrap();
+        vault.store("admin-old", "{}").await.unwrap();
+        vault.store("admin-deleted", "{}").await.unwrap();
+        vault.soft_delete("admin-deleted").await.unwrap();
+        client.execute(sql("UPDATE {table} SET created_at = now() - interval '40 days' WHERE tenant = $1 AND token = 'admin-old'").as_str(), &[&vault.namespace]).await.unwrap();
+
+        let histogram = vault.record_histogram_by_age(&AgeHistogram::DEFAULT_BOUNDS).await.unwrap();
+        let counts: Vec<u64> = histogram.buckets.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, vec![1, 0, 1, 0, 0]);
+        assert_eq!(vault.record_histogram_by_age(&[]).await.unwrap().total(), 2);
+
+        let tenants = vault.tenant_summary().await.unwrap();
+        assert!(tenants.windows(2).all(|pair| pair[0].namespace < pair[1].namespace));
+        let tenant = tenants.iter().find(|tenant| tenant.namespace == "admin-test").unwrap();
+        assert_eq!((tenant.count, tenant.soft_deleted), (2, 1));
+        assert!(tenant.oldest < tenant.newest);
+
+        client.execute(sql("DELETE FROM {table} WHERE tenant = $1").as_str(), &[&vault.namespace]).await.unwrap();
+        assert!(vault.tenant_summary().await.unwrap().iter().all(|tenant| tenant.namespace != "admin-test"))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn integrity_postgres() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let before = CardFieldLayout::new().unwrap()
+            .with(CardField::Number, FieldStorage::BlindIndex).unwrap()
+            .with(CardField::CardholderName, FieldStorage::Plaintext).unwrap();
+        let stored = vault.with_namespace("integrity-test").unwrap().with_card_fields(before);
+        let client = stored.connection().await.unwrap();
+        let sql = |query: &str| stored.sql(query);
+        client.execute(sql("DELETE FROM {table} WHERE tenant = $1").as_str(), &[&stored.namespace]).await.unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = stored.store_credit_card(&cc).await.unwrap();
+        stored.store("integrity-text", "{number: 123}").await.unwrap();
+        let report = stored.integrity_report().await.unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.checked, 2);
+
+        // the name is made encrypted only and the year plaintext
+        let after = CardFieldLayout::new().unwrap()
+            .with(CardField::Number, FieldStorage::BlindIndex).unwrap()
+            .with(CardField::ExpirationYear, FieldStorage::Plaintext).unwrap();
+        let vault = vault.with_namespace("integrity-test").unwrap().with_card_fields(after);
+        client.execute(sql("UPDATE {table} SET \"number\" = 'stale' WHERE tenant = $1 AND token = $2").as_str(), &[&vault.namespace, &token]).await.unwrap();
+
+        let report = vault.integrity_report().await.unwrap();
+        assert_eq!(report.checked, 3);
+        let mut issues: Vec<(&str, IntegrityProblem)> = report.issues.iter().map(|issue| (issue.index, issue.problem)).collect();
+        issues.sort_by_key(|(index, _)| *index);
+        assert_eq!(issues, vec![
+            ("cardholder_name", IntegrityProblem::Dangling),
+            ("expiration_year", IntegrityProblem::Unindexed),
+            ("number", IntegrityProblem::Stale),
+        ]);
+        assert!(report.issues.iter().all(|issue| issue.token == token));
+
+        assert_eq!(vault.repair_integrity(&report).await.unwrap(), 3);
+        let repaired = vault.integrity_report().await.unwrap();
+        assert!(repaired.is_ok());
+        assert_eq!(repaired.checked, 2);
+        assert_eq!(vault.tokens_where(CardField::ExpirationYear, "2023").await.unwrap(), vec![token]);
+
+        client.execute(sql("DELETE FROM {table} WHERE tenant = $1").as_str(), &[&vault.namespace]).await.unwrap();
     }
 }
\ No newline at end of file
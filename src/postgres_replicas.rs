@@ -0,0 +1,91 @@
+use deadpool_postgres::{Client, Pool};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use crate::pool_status::{PoolStatus, TimeoutCounter};
+
+/// how long the lag measured on a replica is trusted
+const LAG_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// seconds since the last transaction the replica replayed, 0 when it
+// replayed everything it received or is not in recovery at all, e.g.
+// a primary listed as a replica, NULL when it cannot tell
+const SELECT_REPLICA_LAG: &str = "SELECT CASE WHEN NOT pg_is_in_recovery() OR pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) END::float8";
+
+/// The read replicas of `PostgresDataVault`, taken in turn
+pub(crate) struct ReplicaSet {
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+    max_lag: Option<Duration>,
+}
+
+struct Replica {
+    pool: Pool,
+    timeouts: TimeoutCounter,
+    /// when the lag was last measured and whether it was tolerable
+    checked: Mutex<Option<(Instant, bool)>>,
+}
+
+impl ReplicaSet {
+    pub(crate) fn new(pools: Vec<Pool>, max_lag: Option<Duration>) -> Self {
+        ReplicaSet {
+            replicas: pools.into_iter().map(|pool| Replica { pool, timeouts: TimeoutCounter::default(), checked: Mutex::new(None) }).collect(),
+            next: AtomicUsize::new(0),
+            max_lag,
+        }
+    }
+
+    /// A connection to the next replica that can be reached and is
+    /// no further behind than `max_lag`, `None` when there is none
+    /// and the primary has to answer
+    pub(crate) async fn connection(&self) -> Option<Client> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.replicas.len() {
+            let replica = &self.replicas[(start + i) % self.replicas.len()];
+            let checkout = replica.pool.get();
+            #[cfg(feature = "tracing")]
+            let checkout = crate::trace::checkout("postgres", "replica", checkout);
+            let client = match replica.timeouts.observe(checkout.await) {
+                Ok(client) => client,
+                Err(_) => continue,
+            };
+            if replica.within(&client, self.max_lag).await {
+                return Some(client)
+            }
+        }
+        None
+    }
+
+    pub(crate) fn pool_status(&self) -> Vec<PoolStatus> {
+        self.replicas.iter().map(|replica| PoolStatus::new(replica.pool.status(), &replica.timeouts)).collect()
+    }
+
+    #[cfg(all(test, feature = "rt-tokio"))]
+    pub(crate) fn pools(&self) -> impl Iterator<Item = &Pool> {
+        self.replicas.iter().map(|replica| &replica.pool)
+    }
+}
+
+impl Replica {
+    /// whether the replica is at most `max_lag` behind, measured on
+    /// `client` unless it was within the last `LAG_CHECK_INTERVAL`
+    async fn within(&self, client: &Client, max_lag: Option<Duration>) -> bool {
+        let max_lag = match max_lag {
+            Some(max_lag) => max_lag,
+            None => return true,
+        };
+        if let Some((at, within)) = *self.checked.lock().unwrap_or_else(PoisonError::into_inner) {
+            if at.elapsed() < LAG_CHECK_INTERVAL {
+                return within
+            }
+        }
+
+        let lag = match client.query_one(SELECT_REPLICA_LAG, &[]).await {
+            Ok(row) => row.get::<_, Option<f64>>(0),
+            Err(_) => None,
+        };
+        let within = matches!(lag, Some(lag) if lag <= max_lag.as_secs_f64());
+        *self.checked.lock().unwrap_or_else(PoisonError::into_inner) = Some((Instant::now(), within));
+        within
+    }
+}
@@ -0,0 +1,349 @@
+//! A detokenizing proxy in front of payment processors
+//!
+//! Application code sends its requests to processors through the
+//! proxy with tokens where the processor expects card data.  The
+//! proxy replaces the token at each configured JSON path of the body
+//! with a field of the tokenized card and forwards the request, so
+//! card numbers never pass through the application.
+//!
+//! ```text
+//! ANY /proxy/{route}/{*path}    forwarded to the upstream of the route, followed by path and query
+//! ```
+//!
+//! Paths are dotted names of object members and array indices from
+//! the root of the body, e.g. `payment_method.card.number` or
+//! `cards.0.number`, an optional `$.` prefix is ignored.  A path that
+//! is missing from a body is left alone, a path holding anything but
+//! a string fails the request with 400.  Requests are only forwarded
+//! to the upstreams of configured routes, below their base URL, a
+//! forwarded path with `.` or `..` segments fails with 400.  The
+//! response of the upstream is returned as it is.
+//!
+//! The routes do not authenticate, run the proxy where only your
+//! services can reach it or add the layers guarding the rest of
+//! your service.  Errors are `{"error": "..."}` with card numbers and
+//! tokens masked, see `redact`.
+//!
+//! # Examples
+//! ```rust,no_run
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::proxy::{DetokenizingProxy, ProxyField, ProxyRoute};
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//!
+//! # async fn serve() {
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+//! let acquirer = ProxyRoute::new("https://api.acquirer.example/v1")
+//!     .detokenize("source.number", ProxyField::Number)
+//!     .detokenize("source.name", ProxyField::CardholderName);
+//! let app = DetokenizingProxy::new()
+//!     .route("acquirer", acquirer)
+//!     .router(vault);
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:8081").await.unwrap();
+//! axum::serve(listener, app).await.unwrap();
+//! # }
+//! ```
+
+use ::axum::Router;
+use ::axum::body::{Body, Bytes};
+use ::axum::extract::{Path, State};
+use ::axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use ::axum::response::Response;
+use ::axum::routing::any;
+use credit_card::CreditCard;
+use serde_json::Value;
+use crate::traits::DataVault;
+use crate::axum::{VaultError, VaultState};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// hop-by-hop headers and those reqwest sets itself, never forwarded
+const SKIPPED_HEADERS: [header::HeaderName; 7] = [
+    header::HOST,
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::TE,
+    header::TRAILER,
+    header::UPGRADE,
+];
+
+/// The field of the tokenized card a token is replaced with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyField {
+    Number,
+    CardholderName,
+    ExpirationMonth,
+    ExpirationYear,
+}
+
+impl ProxyField {
+    fn value(self, credit_card: &CreditCard) -> &str {
+        match self {
+            ProxyField::Number => &credit_card.number,
+            ProxyField::CardholderName => &credit_card.cardholder_name,
+            ProxyField::ExpirationMonth => &credit_card.expiration_month,
+            ProxyField::ExpirationYear => &credit_card.expiration_year,
+        }
+    }
+}
+
+/// An upstream and the JSON paths detokenized in the requests to it
+#[derive(Debug, Clone)]
+pub struct ProxyRoute {
+    upstream: String,
+    paths: Vec<(String, Vec<String>, ProxyField)>,
+}
+
+impl ProxyRoute {
+    /// Requests forwarded to `upstream`, a base URL such as
+    /// `https://api.acquirer.example/v1`
+    pub fn new(upstream: &str) -> Self {
+        ProxyRoute {
+            upstream: upstream.trim_end_matches('/').to_string(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Replace the token at `path` with the `field` of its card
+    pub fn detokenize(mut self, path: &str, field: ProxyField) -> Self {
+        let segments = path.strip_prefix("$.").unwrap_or(path)
+            .split('.')
+            .map(str::to_string)
+            .collect();
+        self.paths.push((path.to_string(), segments, field));
+        self
+    }
+}
+
+/// The routes of a detokenizing proxy, see the module documentation
+#[derive(Debug, Clone, Default)]
+pub struct DetokenizingProxy {
+    routes: HashMap<String, ProxyRoute>,
+    client: reqwest::Client,
+}
+
+#[derive(Clone)]
+struct ProxyState {
+    vault: VaultState,
+    routes: Arc<HashMap<String, ProxyRoute>>,
+    client: reqwest::Client,
+}
+
+impl DetokenizingProxy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forward `/proxy/{name}/...` to `route`
+    pub fn route(mut self, name: &str, route: ProxyRoute) -> Self {
+        self.routes.insert(name.to_string(), route);
+        self
+    }
+
+    /// Forward with `client` instead of a default `reqwest::Client`,
+    /// e.g. with timeouts or client certificates of the processor
+    pub fn with_client(self, client: reqwest::Client) -> Self {
+        DetokenizingProxy { client, ..self }
+    }
+
+    /// The proxy routes over `vault`
+    pub fn router<V>(self, vault: V) -> Router
+        where
+            V: DataVault + 'static,
+    {
+        let state = ProxyState {
+            vault: VaultState::new(vault),
+            routes: Arc::new(self.routes),
+            client: self.client,
+        };
+        Router::new()
+            .route("/proxy/{route}", any(forward))
+            .route("/proxy/{route}/{*path}", any(forward))
+            .with_state(state)
+    }
+}
+
+async fn forward(State(state): State<ProxyState>, Path(params): Path<HashMap<String, String>>, method: Method, uri: Uri, headers: HeaderMap, body: Bytes) -> Result<Response, VaultError> {
+    let route = state.routes.get(&params["route"])
+        .ok_or_else(|| VaultError::new(StatusCode::NOT_FOUND, "unknown proxy route"))?;
+    let url = upstream_url(&route.upstream, params.get("path").map(String::as_str), uri.query())?;
+
+    let body = match route.paths.is_empty() || body.is_empty() {
+        true => body,
+        false => detokenize_body(&state.vault, route, &body).await?.into(),
+    };
+
+    let mut request = state.client.request(method, url);
+    for (name, value) in headers.iter().filter(|(name, _)| !SKIPPED_HEADERS.contains(name)) {
+        request = request.header(name, value);
+    }
+    let upstream = request.body(body).send().await
+        .map_err(|e| VaultError::new(StatusCode::BAD_GATEWAY, &format!("upstream request failed: {}", e.without_url())))?;
+
+    let mut response = Response::builder().status(upstream.status());
+    for (name, value) in upstream.headers().iter().filter(|(name, _)| !SKIPPED_HEADERS.contains(name)) {
+        response = response.header(name, value);
+    }
+    let body = upstream.bytes().await
+        .map_err(|e| VaultError::new(StatusCode::BAD_GATEWAY, &format!("upstream response failed: {}", e.without_url())))?;
+    response.body(Body::from(body))
+        .map_err(|e| VaultError::new(StatusCode::BAD_GATEWAY, &e.to_string()))
+}
+
+/// `path` below `upstream`, axum decoded `path` so each segment is
+/// encoded again, and `.` or `..` segments that would leave `upstream`
+/// fail with 400
+fn upstream_url(upstream: &str, path: Option<&str>, query: Option<&str>) -> Result<reqwest::Url, VaultError> {
+    let mut url = reqwest::Url::parse(upstream)
+        .map_err(|e| VaultError::new(StatusCode::BAD_GATEWAY, &format!("invalid upstream: {}", e)))?;
+    if let Some(path) = path {
+        let segments: Vec<&str> = path.split('/').collect();
+        if segments.iter().any(|segment| *segment == "." || *segment == "..") {
+            return Err(VaultError::new(StatusCode::BAD_REQUEST, "path holds a . or .. segment"));
+        }
+        url.path_segments_mut()
+            .map_err(|_| VaultError::new(StatusCode::BAD_GATEWAY, "upstream cannot have a path"))?
+            .pop_if_empty()
+            .extend(segments);
+    }
+    url.set_query(query);
+    Ok(url)
+}
+
+/// `body` with the token at each path of `route` replaced
+async fn detokenize_body(vault: &VaultState, route: &ProxyRoute, body: &[u8]) -> Result<Vec<u8>, VaultError> {
+    // not quoting serde's error, it may hold part of the body
+    let mut json: Value = serde_json::from_slice(body)
+        .map_err(|_| VaultError::new(StatusCode::BAD_REQUEST, "request body is not JSON"))?;
+    // each token is detokenized once, however many paths hold it
+    let mut credit_cards: HashMap<String, CreditCard> = HashMap::new();
+
+    for (path, segments, field) in &route.paths {
+        let value = match lookup(&mut json, segments) {
+            Some(value) => value,
+            None => continue,
+        };
+        let token = value.as_str()
+            .ok_or_else(|| VaultError::new(StatusCode::BAD_REQUEST, &format!("{} is not a token", path)))?
+            .to_string();
+        if !credit_cards.contains_key(&token) {
+            let credit_card = vault.retrieve_credit_card(&token).await?;
+            credit_cards.insert(token.clone(), credit_card);
+        }
+        *value = Value::String(field.value(&credit_cards[&token]).to_string());
+    }
+
+    serde_json::to_vec(&json).map_err(|e| VaultError::new(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
+
+fn lookup<'a>(json: &'a mut Value, segments: &[String]) -> Option<&'a mut Value> {
+    segments.iter().try_fold(json, |value, segment| match value {
+        Value::Object(members) => members.get_mut(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(move |index| items.get_mut(index)),
+        _ => None,
+    })
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use ::axum::Router;
+    use ::axum::body::{self, Body, Bytes};
+    use ::axum::http::{HeaderMap, Method, Request, StatusCode, Uri};
+    use ::axum::routing::any;
+    use credit_card::CreditCard;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+    use crate::proxy::{DetokenizingProxy, ProxyField, ProxyRoute};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+
+    // the upstream answers with what it received
+    async fn echo(method: Method, uri: Uri, headers: HeaderMap, body: Bytes) -> String {
+        let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        let authorization = headers.get("authorization").map(|value| value.to_str().unwrap().to_string());
+        json!({ "method": method.as_str(), "uri": uri.to_string(), "authorization": authorization, "body": body }).to_string()
+    }
+
+    async fn send(app: &Router, uri: &str, body: &str) -> (StatusCode, String) {
+        let request = Request::builder().method(Method::POST).uri(uri)
+            .header("authorization", "Bearer processor-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_detokenizing_proxy() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = format!("http://{}/v1", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, Router::new().fallback(any(echo))).await.unwrap();
+        });
+
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("proxy-test").unwrap();
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+        let token = vault.store_credit_card(&credit_card).await.unwrap();
+
+        let route = ProxyRoute::new(&upstream)
+            .detokenize("$.source.number", ProxyField::Number)
+            .detokenize("source.name", ProxyField::CardholderName)
+            .detokenize("cards.0", ProxyField::ExpirationYear)
+            .detokenize("missing.number", ProxyField::Number);
+        let app = DetokenizingProxy::new().route("acquirer", route).router(vault.with_namespace("proxy-test").unwrap());
+
+        let request = json!({ "amount": 100, "source": { "number": token, "name": token }, "cards": [token] }).to_string();
+        let (status, echoed) = send(&app, "/proxy/acquirer/charges?capture=true", &request).await;
+        assert_eq!(status, StatusCode::OK);
+        let echoed: Value = serde_json::from_str(&echoed).unwrap();
+        assert_eq!(echoed["method"], "POST");
+        assert_eq!(echoed["uri"], "/v1/charges?capture=true");
+        assert_eq!(echoed["authorization"], "Bearer processor-key");
+        assert_eq!(echoed["body"], json!({ "amount": 100, "source": { "number": "4111111111111111", "name": "Graydon Hoare" }, "cards": ["2023"] }));
+
+        let (status, error) = send(&app, "/proxy/acquirer/charges", &json!({ "source": { "number": 4111111111111111u64 } }).to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!error.contains("4111111111111111"));
+        let (status, _) = send(&app, "/proxy/acquirer/charges", &json!({ "source": { "number": "no-such-token" } }).to_string()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(send(&app, "/proxy/unknown/charges", "{}").await.0, StatusCode::NOT_FOUND);
+
+        vault.delete_many(&[token]).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_proxy_path() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = format!("http://{}/v1", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, Router::new().fallback(any(echo))).await.unwrap();
+        });
+
+        // no token in the bodies, the vault is never reached
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let route = ProxyRoute::new(&upstream).detokenize("source.number", ProxyField::Number);
+        let app = DetokenizingProxy::new().route("acquirer", route).router(vault);
+
+        let (status, echoed) = send(&app, "/proxy/acquirer/charges%3Fcapture=false/a%20b?capture=true", "{}").await;
+        assert_eq!(status, StatusCode::OK);
+        let echoed: Value = serde_json::from_str(&echoed).unwrap();
+        assert_eq!(echoed["uri"], "/v1/charges%3Fcapture=false/a%20b?capture=true");
+
+        for path in ["/proxy/acquirer/../admin", "/proxy/acquirer/%2E%2E/admin", "/proxy/acquirer/charges/%2e/x", "/proxy/acquirer/..%2Fadmin"].iter() {
+            assert_eq!(send(&app, path, "{}").await.0, StatusCode::BAD_REQUEST, "{}", path);
+        }
+    }
+}
\ No newline at end of file
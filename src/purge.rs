@@ -0,0 +1,48 @@
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+use crate::traits::DataVault;
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+use crate::error::DataVaultError;
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+use crate::retry::sleep;
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+use std::time::Duration;
+
+/// What a `DataVault::purge_expired` run removed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PurgeReport {
+    /// records past their retention period that are gone now
+    pub purged: u64,
+    /// of those, records the back end had already expired on its
+    /// own, e.g. by a redis key expiration
+    pub expired_by_backend: u64,
+}
+
+/// Call `DataVault::purge_expired` every `interval`, forever
+///
+/// Each result is handed to `on_purge`, a failed run does not stop
+/// the loop.  Spawn it on the runtime selected by the `rt-*` feature.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{DataVault, RedisDataVault, purge_expired_every};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::time::Duration;
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// tokio::spawn(async move {
+///     purge_expired_every(&data_vault, Duration::from_secs(3600), |report| {
+///         println!("{:?}", report);
+///     }).await
+/// });
+/// ```
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+pub async fn purge_expired_every<V, F>(vault: &V, interval: Duration, mut on_purge: F)
+    where
+        V: DataVault,
+        F: FnMut(Result<PurgeReport, DataVaultError>),
+{
+    loop {
+        on_purge(vault.purge_expired().await);
+        sleep(interval).await;
+    }
+}
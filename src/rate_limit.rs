@@ -0,0 +1,295 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    burst: f64,
+    per_second: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets limiting how many records each caller may
+/// detokenize per tenant
+///
+/// Every caller starts with `burst` records and earns `per_second`
+/// more each second up to `burst` again.  Buckets are kept per
+/// caller and namespace, so one tenant's traffic does not use up
+/// another's.
+/// # example
+/// ```rust
+/// use data_vault::{DataVault, RedisDataVault, RateLimiter};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::sync::Arc;
+///
+/// let limiter = Arc::new(RateLimiter::new(100, 10.0).with_caller_limit("batch-job", 10_000, 1_000.0));
+/// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let vault = limiter.limited(vault, "billing-service");
+/// ```
+pub struct RateLimiter {
+    limit: Limit,
+    caller_limits: HashMap<String, Limit>,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    /// Allow bursts of `burst` records refilled at `per_second`
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        RateLimiter {
+            limit: Limit { burst: burst as f64, per_second },
+            caller_limits: HashMap::new(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Another limit for `caller` than everyone else's
+    pub fn with_caller_limit(mut self, caller: &str, burst: u32, per_second: f64) -> Self {
+        self.caller_limits.insert(caller.to_string(), Limit { burst: burst as f64, per_second });
+        self
+    }
+
+    /// `vault` limiting the detokenizations of `caller`
+    pub fn limited<V: DataVault>(self: &Arc<Self>, vault: V, caller: &str) -> RateLimitedVault<V> {
+        RateLimitedVault {
+            inner: vault,
+            limiter: self.clone(),
+            caller: caller.to_string(),
+        }
+    }
+
+    /// Take `records` from the bucket of `caller` in `tenant`
+    /// returns:
+    ///     * `DataVaultError::RateLimited` with the time until
+    ///       enough records are available, nothing is taken then
+    pub fn acquire(&self, caller: &str, tenant: &str, records: u32) -> Result<(), DataVaultError> {
+        let limit = self.caller_limits.get(caller).copied().unwrap_or(self.limit);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry((caller.to_string(), tenant.to_string()))
+            .or_insert(Bucket { tokens: limit.burst, updated: now });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(limit.burst);
+        bucket.updated = now;
+
+        let records = records as f64;
+        if bucket.tokens >= records {
+            bucket.tokens -= records;
+            return Ok(())
+        }
+
+        let retry_after = match limit.per_second > 0.0 && records <= limit.burst {
+            true => Duration::from_secs_f64((records - bucket.tokens) / limit.per_second),
+            false => Duration::MAX,
+        };
+        Err(DataVaultError::RateLimited(retry_after))
+    }
+}
+
+/// A vault limiting how fast its caller can detokenize with a
+/// shared `RateLimiter`
+///
+/// Every decrypted record counts, `retrieve`, `retrieve_credit_card`
+/// and each record of `iter_decrypted_records` and
+/// `decrypted_records_page` alike.  `iter_records` returns
+/// ciphertext and is not limited, other operations neither.
+pub struct RateLimitedVault<V> {
+    inner: V,
+    limiter: Arc<RateLimiter>,
+    caller: String,
+}
+
+impl<V> RateLimitedVault<V>
+    where
+        V: DataVault,
+{
+    /// The wrapped vault, operations on it are not limited
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// The caller whose bucket is used
+    pub fn caller(&self) -> &str {
+        &self.caller
+    }
+
+    fn acquire(&self, records: u32) -> Result<(), DataVaultError> {
+        self.limiter.acquire(&self.caller, self.inner.namespace(), records)
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for RateLimitedVault<V>
+    where
+        V: DataVault,
+{
+    /// Always fails, the limits and caller can not come from the
+    /// environment, use `RateLimiter::limited`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Err("RateLimitedVault needs a rate limiter and caller, use RateLimiter::limited".into())
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.inner.store(token, string).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.inner.store_if_absent(token, string).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.inner.store_expiring(token, string, ttl).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.inner.store_credit_card(credit_card).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.inner.tokenize(credit_card).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        self.inner.store_credit_card_with_token(token, credit_card, overwrite).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.acquire(1)?;
+        self.inner.retrieve(token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.acquire(1)?;
+        self.inner.retrieve_credit_card(token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.inner.exists(token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.acquire(1)?;
+        self.inner.retrieve_credit_card_with_version(token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.acquire(1)?;
+        self.inner.retrieve_with_metadata(token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.inner.update_credit_card_if_version(token, credit_card, expected_version).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inner.rotate_token(token).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.inner.soft_delete(token).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.inner.touch(token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.inner.delete_many(tokens).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.inner.purge_expired().await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    /// Yields `DataVaultError::RateLimited` in place of each record
+    /// over the limit
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        Box::pin(self.inner.iter_decrypted_records().map(move |record| {
+            record.and_then(|record| self.acquire(1).map(|_| record))
+        }))
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        let page = self.inner.decrypted_records_page(cursor, limit).await?;
+        self.acquire(page.records.len() as u32)?;
+        Ok(page)
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.inner.count().await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.inner.stats().await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault uses the same limiter and caller, with the
+    /// buckets of `namespace`
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(RateLimitedVault {
+            inner: self.inner.with_namespace(namespace)?,
+            limiter: self.limiter.clone(),
+            caller: self.caller.clone(),
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(RateLimitedVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            limiter: self.limiter.clone(),
+            caller: self.caller.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::DataVaultError;
+    use crate::rate_limit::RateLimiter;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2, 0.0).with_caller_limit("batch", 10, 0.0);
+
+        assert!(limiter.acquire("billing", "", 1).is_ok());
+        assert!(limiter.acquire("billing", "", 1).is_ok());
+        assert!(matches!(limiter.acquire("billing", "", 1), Err(DataVaultError::RateLimited(_))));
+
+        // buckets are per caller and tenant
+        assert!(limiter.acquire("billing", "tenant-b", 2).is_ok());
+        assert!(limiter.acquire("batch", "", 10).is_ok());
+        assert!(limiter.acquire("support", "", 3).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use credit_card::CreditCard;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::utils::RandomToken;
+use crate::validation::ValidationError;
+use std::any::Any;
+use std::time::Duration;
+
+/// A kind of sensitive record a `DataVault` can store, see
+/// `DataVault::store_record`
+///
+/// `CreditCard` is the canonical record, storing one this way is
+/// the same as `store_credit_card` with the vault's tokenizer,
+/// serializer and security code policy.  Other records are stored
+/// as JSON under a random token, so `retrieve` reads them as well,
+/// and expire after their `ttl`.
+/// # example
+/// ```rust
+/// use data_vault::VaultRecord;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Passport {
+///     number: String,
+///     country: String,
+/// }
+///
+/// impl VaultRecord for Passport {}
+/// ```
+pub trait VaultRecord: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// The token a new record is stored under
+    fn generate_token(&self) -> String {
+        RandomToken::generate()
+    }
+
+    /// How long a new record is kept, `None` keeps it until it is
+    /// deleted
+    fn ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Why the record can not be stored, checked by `store_record`
+    /// before anything is written
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
+
+impl VaultRecord for CreditCard {}
+
+/// `record` if it is a credit card
+pub(crate) fn as_credit_card<R: VaultRecord>(record: &R) -> Option<&CreditCard> {
+    (record as &dyn Any).downcast_ref()
+}
+
+/// `credit_card` as `R` if `R` is `CreditCard`
+pub(crate) fn from_credit_card<R: VaultRecord>(credit_card: CreditCard) -> Option<R> {
+    let credit_card: Box<dyn Any> = Box::new(credit_card);
+    credit_card.downcast().ok().map(|record| *record)
+}
+
+/// Whether `R` is `CreditCard`
+pub(crate) fn is_credit_card<R: VaultRecord>() -> bool {
+    std::any::TypeId::of::<R>() == std::any::TypeId::of::<CreditCard>()
+}
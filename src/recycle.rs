@@ -0,0 +1,31 @@
+use serde::Deserialize;
+
+/// How the pools check a connection before handing it out again
+///
+/// Configured with `REDIS_POOL_RECYCLE` and `POSTGRES_POOL_RECYCLE`,
+/// one of `fast`, `verified` or `clean`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecycleMethod {
+    /// hand connections out as they are, a connection dropped by
+    /// the server or a firewall fails on its first use
+    Fast,
+    /// test every connection on checkout, `PING` on redis and a
+    /// query on postgres
+    #[default]
+    Verified,
+    /// as `Verified` and reset the postgres session, the same as
+    /// `Verified` on redis
+    Clean,
+}
+
+#[cfg(feature = "postgres")]
+impl From<RecycleMethod> for deadpool_postgres::RecyclingMethod {
+    fn from(method: RecycleMethod) -> Self {
+        match method {
+            RecycleMethod::Fast => deadpool_postgres::RecyclingMethod::Fast,
+            RecycleMethod::Verified => deadpool_postgres::RecyclingMethod::Verified,
+            RecycleMethod::Clean => deadpool_postgres::RecyclingMethod::Clean,
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use std::borrow::Cow;
+#[cfg(feature = "log")]
+use crate::config::EncryptionSettings;
+
+// runs of letters and digits at least this long are taken for
+// tokens, the shortest token the tokenizers make has 32 characters
+const MIN_TOKEN_LEN: usize = 24;
+
+/// `token` with all but its first and last 4 characters masked,
+/// enough to tell tokens apart in a log without being able to
+/// detokenize them, tokens of up to 8 characters are masked entirely
+/// # example
+/// ```rust
+/// use data_vault::redact_token;
+///
+/// assert_eq!(redact_token("5f4dcc3b5aa765d61d8327deb882cf99"), "5f4d****cf99");
+/// ```
+pub fn redact_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string()
+    }
+    let first: String = chars[..4].iter().collect();
+    let last: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}****{}", first, last)
+}
+
+/// `text` with the card numbers and tokens in it masked, for
+/// anything that ends up in a log
+///
+/// Card numbers are runs of 13 to 19 digits, possibly grouped with
+/// single spaces or dashes, all but the last 4 digits are masked
+/// whether or not they pass the Luhn check.  Runs of 24 or more
+/// letters and digits are taken for tokens and `redact_token`ed.
+/// # example
+/// ```rust
+/// use data_vault::redact;
+///
+/// assert_eq!(redact("charging 4111 1111 1111 1111"), "charging **** **** **** 1111");
+/// ```
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let bytes = text.as_bytes();
+    let mut redacted = String::new();
+    // text[copied..] is not in `redacted` yet
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_alphanumeric() || (i > 0 && bytes[i - 1].is_ascii_alphanumeric()) {
+            i += 1;
+            continue
+        }
+
+        let pan_end = card_number_end(bytes, i);
+        let word_end = i + bytes[i..].iter().take_while(|b| b.is_ascii_alphanumeric()).count();
+        let (end, masked) = match pan_end {
+            Some(end) => (end, mask_card_number(&text[i..end])),
+            None if word_end - i >= MIN_TOKEN_LEN => (word_end, redact_token(&text[i..word_end])),
+            None => {
+                i = word_end;
+                continue
+            },
+        };
+        redacted.push_str(&text[copied..i]);
+        redacted.push_str(&masked);
+        copied = end;
+        i = end;
+    }
+
+    if copied == 0 {
+        return Cow::Borrowed(text)
+    }
+    redacted.push_str(&text[copied..]);
+    Cow::Owned(redacted)
+}
+
+/// where the card number starting at `start` ends, `None` unless
+/// 13 to 19 digits start there that are not part of a longer word
+///
+/// A grouped run of more than 19 digits, e.g. a card number followed
+/// by its expiry, ends after the last group keeping it within 19
+/// digits, or at its end when no group does.
+fn card_number_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut digits = 0;
+    let mut end = start;
+    // the end of the longest run of whole groups that can be a card number
+    let mut grouped = None;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        digits += 1;
+        end += 1;
+        // a single separator between two digits
+        if end + 1 < bytes.len() && (bytes[end] == b' ' || bytes[end] == b'-') && bytes[end + 1].is_ascii_digit() {
+            if let 13..=19 = digits {
+                grouped = Some(end);
+            }
+            end += 1;
+        }
+    }
+    let in_word = end < bytes.len() && bytes[end].is_ascii_alphanumeric();
+    match digits {
+        13..=19 if !in_word => Some(end),
+        20.. => grouped.or(Some(end)),
+        _ => None,
+    }
+}
+
+/// every digit of `number` but the last 4 masked, separators kept
+fn mask_card_number(number: &str) -> String {
+    let digits = number.bytes().filter(u8::is_ascii_digit).count();
+    let mut seen = 0;
+    number.chars()
+        .map(|c| match c.is_ascii_digit() {
+            true => {
+                seen += 1;
+                if seen > digits - 4 { c } else { '*' }
+            },
+            false => c,
+        })
+        .collect()
+}
+
+/// A `log::Log` passing every record on to another logger with its
+/// message `redact`ed, so a record logged by the vault, or by
+/// anything else, at whatever level can not leak a card number or
+/// token
+///
+/// Key material registered with `with_secrets` or `with_secret` is
+/// replaced with `<redacted>` wherever it appears.  Only the
+/// message is redacted, key value pairs are passed on as they are.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{EncryptionSettings, RedactingLogger};
+///
+/// let logger = RedactingLogger::new(env_logger::Builder::from_default_env().build())
+///     .with_secrets(&EncryptionSettings::from_env().unwrap());
+/// log::set_max_level(log::LevelFilter::Info);
+/// log::set_boxed_logger(Box::new(logger)).unwrap();
+/// ```
+#[cfg(feature = "log")]
+pub struct RedactingLogger<L> {
+    inner: L,
+    secrets: Vec<String>,
+}
+
+#[cfg(feature = "log")]
+impl<L> RedactingLogger<L>
+    where
+        L: log::Log,
+{
+    pub fn new(inner: L) -> Self {
+        RedactingLogger {
+            inner,
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Replace `secret` in every message with `<redacted>`
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        if !secret.is_empty() {
+            self.secrets.push(secret.to_string());
+        }
+        self
+    }
+
+    /// `with_secret` for the key and the iv of `settings`
+    pub fn with_secrets(self, settings: &EncryptionSettings) -> Self {
+        self.with_secret(&settings.key).with_secret(&settings.iv)
+    }
+
+    fn redact_message(&self, message: &str) -> String {
+        let mut message = message.to_string();
+        for secret in &self.secrets {
+            message = message.replace(secret.as_str(), "<redacted>");
+        }
+        redact(&message).into_owned()
+    }
+}
+
+#[cfg(feature = "log")]
+impl<L> log::Log for RedactingLogger<L>
+    where
+        L: log::Log,
+{
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.inner.enabled(record.metadata()) {
+            return
+        }
+        let message = self.redact_message(&record.args().to_string());
+        self.inner.log(&log::Record::builder()
+            .metadata(record.metadata().clone())
+            .args(format_args!("{}", message))
+            .module_path(record.module_path())
+            .file(record.file())
+            .line(record.line())
+            .build());
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::redact::{redact, redact_token};
+    use crate::config::EncryptionConfig;
+    use crate::hooks::{Operation, Outcome, VaultEvent};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact_token("5f4dcc3b5aa765d61d8327deb882cf99"), "5f4d****cf99");
+        assert_eq!(redact_token("12345678"), "****");
+
+        assert_eq!(redact("4111111111111111"), "************1111");
+        assert_eq!(redact("card 4111-1111-1111-1111, ok"), "card ****-****-****-1111, ok");
+        assert_eq!(redact("{\"number\":\"378282246310005\"}"), "{\"number\":\"***********0005\"}");
+        // a card number followed by more digits
+        assert_eq!(redact("charging 4111111111111111 2030"), "charging ************1111 2030");
+        assert_eq!(redact("4111111111111111 12 30"), "**************11 12 30");
+        assert_eq!(redact("4111 1111 1111 1111 2030"), "**** **** **** 1111 2030");
+        // no group ends within 19 digits, all but the last 4 are masked
+        assert_eq!(redact("4111111111111111111111 2030"), "********************** 2030");
+        let token = "d3b07384d113edec49eaa6238ad5ff00d3b07384d113edec49eaa6238ad5ff00";
+        assert_eq!(redact(&format!("retrieve {} failed", token)), "retrieve d3b0****ff00 failed");
+        // too short for a card number, or part of a word
+        for text in &["order 123456789012", "took 1200 ms", "id a4111111111111111", "2023-01-01 12:00:00"] {
+            assert!(matches!(redact(text), std::borrow::Cow::Borrowed(_)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_redacted_debug() {
+        let cfg = EncryptionConfig { key: "000102030405060708090a0b0c0d0e0f".to_string(), iv: "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff".to_string() };
+        assert!(!format!("{:?}", cfg).contains("0001020304"));
+
+        let event = VaultEvent {
+            operation: Operation::Retrieve,
+            outcome: Outcome::Success,
+            token: Some("d3b07384d113edec49eaa6238ad5ff00".to_string()),
+            tenant: "merchant-42".to_string(),
+            timestamp: SystemTime::now(),
+            duration: Duration::from_millis(3),
+        };
+        let debug = format!("{:?}", event);
+        assert!(debug.contains("d3b0****ff00") && debug.contains("merchant-42"), "{}", debug);
+        assert!(!debug.contains("d3b07384d113"));
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn test_redacting_logger() {
+        use crate::redact::RedactingLogger;
+        use crate::config::EncryptionSettings;
+        use log::Log;
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Lines(Mutex<Vec<String>>);
+
+        impl Log for Lines {
+            fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+                metadata.level() <= log::Level::Info
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                self.0.lock().unwrap().push(format!("{} {}", record.target(), record.args()));
+            }
+
+            fn flush(&self) {}
+        }
+
+        let logger = RedactingLogger::new(Lines::default())
+            .with_secrets(&EncryptionSettings::new("000102030405060708090a0b0c0d0e0f", ""));
+        let card = "4111111111111111";
+        let key = "000102030405060708090a0b0c0d0e0f";
+        logger.log(&log::Record::builder()
+            .args(format_args!("stored {} with {}", card, key))
+            .target("data_vault")
+            .level(log::Level::Info)
+            .build());
+        logger.log(&log::Record::builder()
+            .args(format_args!("{}", card))
+            .level(log::Level::Debug)
+            .build());
+        assert_eq!(*logger.inner.0.lock().unwrap(), vec!["data_vault stored ************1111 with <redacted>".to_string()]);
+    }
+}
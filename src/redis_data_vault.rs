@@ -1,11 +1,43 @@
 use async_trait::async_trait;
 use credit_card::CreditCard;
-use deadpool_redis::redis::{AsyncCommands};
-use crate::traits::{DataVault, PoolErrors};
-use crate::config::DeadpoolRedisConfig;
+use deadpool_redis::redis::{AsyncCommands, Pipeline, cmd, pipe};
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::{CheckResult, HealthReport};
+use crate::metadata::RecordMetadata;
+use crate::summary::CardSummary;
+use crate::alias::resolve_alias;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::cvv::CvvPolicy;
+use crate::validation::{normalize_card, validate_card};
+use crate::brand::with_brand;
+use crate::compression::CompressionAlgo;
+use crate::retry::RetryPolicy;
+#[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+use crate::retry::sleep;
+use futures::stream::{self, TryStreamExt};
+use crate::config::RedisVaultConfig;
+use crate::redis_pool::{create_pool, RedisManager, RedisPool};
+use crate::reload::Reloadable;
+use crate::pool_status::{PoolStatus, TimeoutCounter};
+use crate::latency::{log_if_slow, LatencyRecorder, LatencyReport, LatencyStage};
 use crate::encryption::traits::Encryption;
 use crate::tokenizer::{Tokenizer};
+use crate::fingerprint::{self, deduplicate};
+use crate::serializer::{JsonSerializer, Serializer};
+use crate::utils::RandomToken;
+use crate::namespace::{side_namespace, validate_namespace, validate_token, DEFAULT_NAMESPACE, TOKEN_SIDE_KINDS, TRANSIENT_SIDE_KINDS};
+use crate::verify::{check_record, RecordProblem, VerifyReport};
+use crate::admin::{AgeHistogram, KeyInfo, TenantSummary};
+use crate::compliance::ComplianceReport;
+use crate::integrity::{IntegrityIssue, IntegrityProblem, IntegrityReport};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::error;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Use redis as a data vault back end
 ///
@@ -16,6 +48,53 @@ use std::error;
 /// options:
 /// REDIS_URL=redis://127.0.0.1/
 /// REDIS_POOL_MAX_SIZE=16
+/// REDIS_KEY_PREFIX=dv:prod:
+///
+/// REDIS_POOL_RECYCLE=verified
+///
+/// Pooled connections are pinged on checkout unless the recycle
+/// method is `RecycleMethod::Fast`, `keep_alive_every` keeps idle
+/// connections open behind firewalls that drop them.
+///
+/// Creating the vault does not connect to redis, connections are
+/// opened by the operations that need them, retried as the vault's
+/// `RetryPolicy` allows, so a vault can be created while redis is
+/// still starting or briefly unavailable.
+///
+/// `rediss://` urls connect with TLS, with the `redis-tls` feature,
+/// see `RedisTlsConfig` for the CA bundle and client certificates.
+/// `redis+unix://` urls, e.g. `redis+unix:///run/redis.sock?db=1`,
+/// connect over a unix socket instead of TCP, the password is given
+/// as `pass`, e.g. `redis+unix:///run/redis.sock?pass=foobared`.
+///
+/// Every token is also added to the `data_vault:index` sorted
+/// set, scored by the unix time it was first stored.  `count`
+/// and `stats` are answered from this index, records stored by
+/// versions without it are not included.
+///
+/// Namespaced records are stored under `<namespace>:<token>` and
+/// indexed in `data_vault:index:<namespace>`.  Tokens may not
+/// contain `:` so keys of different namespaces can never collide.
+///
+/// `soft_delete` renames a record to `data_vault:tombstone:<key>`
+/// which redis expires after the retention period, the token is
+/// moved from the index to `data_vault:deleted`, scored by the
+/// unix time the tombstone expires.
+///
+/// Record versions are kept in the `data_vault:version` hash, records
+/// stored before versions existed are at version 0.
+///
+/// Credit cards are written with the serializer `S`, JSON unless
+/// another one is picked, see `serializer::Serializer`.
+///
+/// Records stored with a time to live, see `CvvPolicy::Expire`, are
+/// expired by redis and listed in `data_vault:expiring`, scored by
+/// the unix time they expire.  They stay in the index until
+/// `purge_expired` clears them.
+///
+/// With a key prefix, see `RedisVaultConfig::with_key_prefix`, every
+/// key above starts with it, e.g. `dv:prod:data_vault:index`, so
+/// tooling can `SCAN` or delete only the vault's keys.
 ///
 /// # Examples
 /// ```rust
@@ -24,17 +103,1124 @@ use std::error;
 /// use data_vault::tokenizer::Blake3Tokenizer;
 /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
 /// ```
-pub struct RedisDataVault<E, T> {
-    pool: deadpool_redis::Pool,
-    encryption: E,
-    tokenizer: T,
+pub struct RedisDataVault<E, T, S = JsonSerializer> {
+    pool: Reloadable<RedisPool>,
+    timeouts: TimeoutCounter,
+    latency: LatencyRecorder,
+    encryption: Reloadable<E>,
+    key: Reloadable<KeyInfo>,
+    tokenizer: Reloadable<T>,
+    serializer: Arc<S>,
+    namespace: String,
+    key_prefix: Arc<str>,
+    retention: Duration,
+    slow_op: Option<Duration>,
+    cvv_policy: CvvPolicy,
+    validate_cards: bool,
+    reject_expired: bool,
+    retry: RetryPolicy,
+    compression: CompressionAlgo,
+    env_prefix: Option<Arc<str>>,
+}
+
+const INDEX_KEY: &str = "data_vault:index";
+const DELETED_INDEX_KEY: &str = "data_vault:deleted";
+const EXPIRING_INDEX_KEY: &str = "data_vault:expiring";
+const VERSION_KEY: &str = "data_vault:version";
+const TOMBSTONE_PREFIX: &str = "data_vault:tombstone";
+// tombstones removed per transaction by purge_expired
+const PURGE_BATCH_SIZE: isize = 1000;
+// index entries read per ZSCAN by iter_records
+const SCAN_BATCH_SIZE: usize = 1000;
+// records sampled with MEMORY USAGE to estimate the storage size
+const MEMORY_SAMPLE_SIZE: isize = 100;
+
+/// tokens of an index with their scores, as `ZRANGE WITHSCORES` returns them
+type IndexEntries = Vec<(String, f64)>;
+
+/// The keys `scan_tokens` walks
+enum TokenScan {
+    /// the string keys starting with a prefix, the token follows it
+    Keys(String),
+    /// the members of a sorted set
+    SortedSet(String),
+    /// the fields of a hash
+    Hash(String),
+}
+
+/// The cross-checks of `integrity_report`, each walks one kind of
+/// key and looks its tokens up elsewhere
+#[derive(Clone, Copy)]
+enum IndexCheck {
+    /// records missing from the index
+    Records,
+    /// tombstones missing from the deleted index
+    Tombstones,
+    /// index entries without a record that has not expired
+    Index,
+    /// expiring index entries of tokens without a record or index entry
+    Expiring,
+    /// deleted index entries without a tombstone that has not expired
+    Deleted,
+    /// versions of tokens without a record or index entry
+    Versions,
+}
+
+/// seconds since the unix epoch, the score of tokens in the index
+fn unix_timestamp() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+fn from_unix_timestamp(timestamp: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs_f64(timestamp)
+}
+
+/// `key` matching itself in a `SCAN` pattern
+fn escape_glob(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The namespace of an index key, given what follows the index
+/// name, e.g. `:merchant-42`, `None` for keys of something else
+fn namespace_of_index(suffix: &str) -> Option<String> {
+    let namespace = match suffix {
+        DEFAULT_NAMESPACE => DEFAULT_NAMESPACE,
+        suffix => suffix.strip_prefix(':').filter(|namespace| !namespace.is_empty())?,
+    };
+    validate_namespace(namespace).ok()?;
+    Some(namespace.to_string())
+}
+
+impl<E, T, S> RedisDataVault<E, T, S> {
+    /// Create a RedisDataVault from configuration assembled in code,
+    /// `new` is `from_config(RedisVaultConfig::from_env()?)`, fails
+    /// with a `ConfigError` naming the variable if the configuration
+    /// is invalid, see `RedisVaultConfig::validate`
+    pub fn from_config(cfg: RedisVaultConfig) -> Result<Self, Box<dyn error::Error>>
+        where
+            E: Encryption,
+            T: Tokenizer,
+            S: Serializer,
+    {
+        cfg.validate()?;
+        let pool = create_pool(&cfg)?;
+        let encryption = E::from_settings(&cfg.encryption);
+
+        Ok(RedisDataVault {
+            pool: Reloadable::new(pool),
+            timeouts: TimeoutCounter::default(),
+            latency: LatencyRecorder::default(),
+            key: Reloadable::new(KeyInfo::new(&encryption, &cfg.encryption)),
+            encryption: Reloadable::new(encryption),
+            tokenizer: Reloadable::new(T::from_settings(&cfg.encryption)),
+            serializer: Arc::new(S::new()),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            key_prefix: cfg.key_prefix.into(),
+            retention: cfg.retention,
+            slow_op: cfg.slow_op,
+            cvv_policy: cfg.cvv_policy,
+            validate_cards: cfg.validate_cards,
+            reject_expired: cfg.reject_expired,
+            retry: cfg.retry,
+            compression: CompressionAlgo::None,
+            env_prefix: cfg.env_prefix.map(Into::into),
+        })
+    }
+
+    /// Re-read the configuration with `RedisVaultConfig::from_env`,
+    /// or `from_env_with_prefix` if it was read with a prefix, and
+    /// apply it, see `reload_from`
+    pub fn reload(&self) -> Result<(), Box<dyn error::Error>>
+        where
+            E: Encryption,
+            T: Tokenizer,
+    {
+        let cfg = match &self.env_prefix {
+            Some(prefix) => RedisVaultConfig::from_env_with_prefix(prefix)?,
+            None => RedisVaultConfig::from_env()?,
+        };
+        self.reload_from(cfg)
+    }
+
+    /// Switch to the key material and connection settings of `cfg`
+    /// without recreating the vault, e.g. after the files of
+    /// `*_FILE` variables were rotated or to resize the pool
+    ///
+    /// The vault and its namespaces use a new pool, connections of
+    /// the old one are closed once they are returned.  The key
+    /// prefix, retention, security code and retry policies stay as
+    /// they were.  Nothing changes if `cfg` is invalid.  Records are
+    /// not re-encrypted, after switching to a new key the vault
+    /// cannot read records sealed with the old one, move them over
+    /// first, see `migrate`.
+    pub fn reload_from(&self, cfg: RedisVaultConfig) -> Result<(), Box<dyn error::Error>>
+        where
+            E: Encryption,
+            T: Tokenizer,
+    {
+        cfg.validate()?;
+        let pool = create_pool(&cfg)?;
+        let encryption = E::from_settings(&cfg.encryption);
+
+        self.key.store(self.key.load().reloaded(KeyInfo::new(&encryption, &cfg.encryption)));
+        self.encryption.store(encryption);
+        self.tokenizer.store(T::from_settings(&cfg.encryption));
+        self.pool.store(pool);
+        Ok(())
+    }
+
+    /// This vault applying `policy` to security codes instead of
+    /// the configured `DATA_VAULT_CVV_POLICY`
+    pub fn with_cvv_policy(self, policy: CvvPolicy) -> Self {
+        RedisDataVault {
+            cvv_policy: policy,
+            ..self
+        }
+    }
+
+    /// This vault checking the card number and expiry of cards
+    /// before storing them, or not, instead of the configured
+    /// `DATA_VAULT_VALIDATE_CARDS`
+    pub fn with_card_validation(self, validate_cards: bool) -> Self {
+        RedisDataVault {
+            validate_cards,
+            ..self
+        }
+    }
+
+    /// This vault refusing cards that expired before the current
+    /// month, or not, instead of the configured
+    /// `DATA_VAULT_REJECT_EXPIRED`, unless card validation is off
+    pub fn with_reject_expired(self, reject_expired: bool) -> Self {
+        RedisDataVault {
+            reject_expired,
+            ..self
+        }
+    }
+
+    /// This vault taking connections from its pool as `policy`
+    /// allows instead of the configured `DATA_VAULT_RETRY_*`
+    pub fn with_retry(self, policy: RetryPolicy) -> Self {
+        RedisDataVault {
+            retry: policy,
+            ..self
+        }
+    }
+
+    /// This vault compressing the records it stores with `algo`,
+    /// records are read whichever way they were stored
+    pub fn with_compression(self, algo: CompressionAlgo) -> Self {
+        RedisDataVault {
+            compression: algo,
+            ..self
+        }
+    }
+
+    /// a connection from the pool, retried as `self.retry` allows
+    async fn connection(&self) -> Result<deadpool::managed::Object<RedisManager>, DataVaultError> {
+        let checkout = self.retry.run(DataVaultError::is_transient, || async { Ok(self.timeouts.observe(self.pool.load().get().await)?) });
+        #[cfg(feature = "tracing")]
+        let checkout = crate::trace::checkout("redis", "primary", checkout);
+        checkout.await
+    }
+
+    /// The connections of the pool and the tasks waiting for one,
+    /// see `PoolStatus`
+    pub fn pool_status(&self) -> PoolStatus {
+        PoolStatus::new(self.pool.load().status(), &self.timeouts)
+    }
+
+    /// Where the time of the vault's operations goes, the round trips
+    /// to redis, encryption and serialization, see `LatencyReport`
+    pub fn latency_report(&self) -> LatencyReport {
+        self.latency.report()
+    }
+
+    /// `PING` the idle connections of the pool, so a firewall or
+    /// load balancer does not drop them for being idle, connections
+    /// that do not answer are closed
+    /// returns:
+    ///     * the number of connections that answered
+    pub async fn keep_alive(&self) -> usize {
+        let pool = self.pool.load();
+        let idle = pool.status().available.max(0) as usize;
+        let mut alive = Vec::with_capacity(idle);
+        for _ in 0..idle {
+            let mut conn = match pool.try_get().await {
+                Ok(conn) => conn,
+                Err(_) => break,
+            };
+            match cmd("PING").query_async::<_, String>(&mut *conn).await {
+                Ok(_) => alive.push(conn),
+                Err(_) => drop(deadpool::managed::Object::take(conn)),
+            }
+        }
+        alive.len()
+    }
+
+    /// `keep_alive` every `interval`, never returns, e.g. spawned
+    /// next to the application
+    #[cfg(any(feature = "rt-tokio", feature = "rt-async-std"))]
+    pub async fn keep_alive_every(&self, interval: Duration) {
+        loop {
+            sleep(interval).await;
+            self.keep_alive().await;
+        }
+    }
+
+    /// Check that every record of the vault decrypts and reads as a
+    /// card or text, see `VerifyReport`
+    ///
+    /// Records are found through `data_vault:index`.  Tokens listed
+    /// there without a record are `RecordProblem::Orphaned`, unless
+    /// they expired and `purge_expired` has yet to remove them.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.verify().await.unwrap();
+    /// assert!(report.is_ok(), "{:?}", report.issues);
+    /// ```
+    pub async fn verify(&self) -> Result<VerifyReport, DataVaultError>
+        where
+            E: Encryption,
+            S: Serializer,
+    {
+        let encryption = self.encryption.load();
+        let index_key = self.index_key();
+        let mut report = VerifyReport::default();
+        let mut cursor = 0;
+        loop {
+            let mut conn = self.connection().await?;
+            let (next_cursor, members): (u64, Vec<String>) = cmd("ZSCAN")
+                .arg(&index_key).arg(cursor).arg("COUNT").arg(SCAN_BATCH_SIZE)
+                .query_async(&mut *conn)
+                .await?;
+
+            // members and scores are interleaved, tokens no key can be
+            // made of are reported without a lookup
+            let (tokens, invalid): (Vec<String>, Vec<String>) = members.into_iter()
+                .step_by(2)
+                .partition(|token| self.key(token).is_ok());
+            for token in &invalid {
+                report.check(token, Some(RecordProblem::InvalidToken));
+            }
+
+            if !tokens.is_empty() {
+                let keys = tokens.iter().map(|token| self.key(token)).collect::<Result<Vec<String>, DataVaultError>>()?;
+                let records: Vec<Option<Vec<u8>>> = cmd("MGET").arg(keys).query_async(&mut *conn).await?;
+
+                // the index and expiry of tokens without a record, in pairs
+                let missing: Vec<&String> = tokens.iter().zip(&records)
+                    .filter(|(_, record)| record.is_none())
+                    .map(|(token, _)| token)
+                    .collect();
+                let mut scores: Vec<Option<f64>> = Vec::new();
+                if !missing.is_empty() {
+                    let mut lookup = pipe();
+                    for token in missing {
+                        lookup.zscore(&index_key, token).zscore(self.expiring_index_key(), token);
+                    }
+                    scores = lookup.query_async(&mut *conn).await?;
+                }
+                let mut scores = scores.chunks(2);
+                let now = unix_timestamp();
+
+                for (token, record) in tokens.iter().zip(records) {
+                    let problem = match record {
+                        Some(record) => check_record(&*encryption, &*self.serializer, token, &record).err(),
+                        None => match scores.next() {
+                            // deleted since the scan
+                            Some([None, _]) => continue,
+                            Some([_, Some(expires_at)]) if *expires_at <= now => None,
+                            _ => Some(RecordProblem::Orphaned),
+                        },
+                    };
+                    report.check(token, problem);
+                }
+            }
+
+            if next_cursor == 0 {
+                return Ok(report)
+            }
+            cursor = next_cursor;
+        }
+    }
+
+    /// The id, algorithm and age of the key the vault encrypts with,
+    /// never the key itself, see `KeyInfo`
+    pub fn key_info(&self) -> KeyInfo {
+        KeyInfo::clone(&self.key.load())
+    }
+
+    /// The algorithms, key age, token kind, security code policy and
+    /// retention period of the vault as evidence for a PCI DSS
+    /// assessment, see `ComplianceReport`
+    pub fn compliance_report(&self) -> ComplianceReport
+        where
+            T: Tokenizer,
+    {
+        ComplianceReport::new("redis", &self.namespace, &self.key.load(), self.tokenizer.load().is_deterministic(), self.cvv_policy, self.compression, self.retention)
+    }
+
+    /// The records of this namespace by the time since they were
+    /// first stored, in a bucket from zero to the first of `bounds`
+    /// and one from each bound to the next, e.g. with
+    /// `AgeHistogram::DEFAULT_BOUNDS`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::admin::AgeHistogram;
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let histogram = data_vault.record_histogram_by_age(&AgeHistogram::DEFAULT_BOUNDS).await.unwrap();
+    /// assert_eq!(histogram.total(), data_vault.count().await.unwrap());
+    /// ```
+    pub async fn record_histogram_by_age(&self, bounds: &[Duration]) -> Result<AgeHistogram, DataVaultError> {
+        let bounds = AgeHistogram::bounds(bounds);
+        let index_key = self.index_key();
+        let now = unix_timestamp();
+
+        // index scores are the times tokens were first stored, a
+        // bucket from `min_age` to `max_age` holds the scores in
+        // (now - max_age, now - min_age]
+        let mut counts = pipe();
+        let mut newest = "+inf".to_string();
+        for bound in &bounds {
+            let oldest = now - bound.as_secs_f64();
+            counts.zcount(&index_key, format!("({}", oldest), &newest);
+            newest = oldest.to_string();
+        }
+        counts.zcount(&index_key, "-inf", &newest);
+
+        let mut conn = self.connection().await?;
+        let counts: Vec<u64> = counts.query_async(&mut *conn).await?;
+        Ok(AgeHistogram::from_counts(&bounds, &counts))
+    }
+
+    /// The live and soft deleted records of every namespace under
+    /// the vault's key prefix, ordered by namespace.  Namespaces are
+    /// found with `SCAN`, which walks every key of the database.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// for tenant in data_vault.tenant_summary().await.unwrap() {
+    ///     println!("{:?}: {} records", tenant.namespace, tenant.count);
+    /// }
+    /// ```
+    pub async fn tenant_summary(&self) -> Result<Vec<TenantSummary>, DataVaultError> {
+        let mut conn = self.connection().await?;
+        let mut namespaces = BTreeSet::new();
+        for index in &[INDEX_KEY, DELETED_INDEX_KEY] {
+            let prefix = format!("{}{}", self.key_prefix, index);
+            let pattern = format!("{}*", escape_glob(&prefix));
+            let mut cursor = 0;
+            loop {
+                let (next_cursor, keys): (u64, Vec<String>) = cmd("SCAN")
+                    .arg(cursor).arg("MATCH").arg(&pattern).arg("COUNT").arg(SCAN_BATCH_SIZE)
+                    .query_async(&mut *conn)
+                    .await?;
+                namespaces.extend(keys.iter().filter_map(|key| namespace_of_index(&key[prefix.len()..])));
+
+                if next_cursor == 0 {
+                    break
+                }
+                cursor = next_cursor;
+            }
+        }
+
+        let mut summaries = Vec::with_capacity(namespaces.len());
+        for namespace in namespaces {
+            let index_key = self.namespaced_in(INDEX_KEY, &namespace);
+            let (count, oldest, newest, soft_deleted): (u64, IndexEntries, IndexEntries, u64) = pipe()
+                .zcard(&index_key)
+                .zrange_withscores(&index_key, 0, 0)
+                .zrange_withscores(&index_key, -1, -1)
+                .zcard(self.namespaced_in(DELETED_INDEX_KEY, &namespace))
+                .query_async(&mut *conn)
+                .await?;
+
+            summaries.push(TenantSummary {
+                namespace,
+                count,
+                soft_deleted,
+                oldest: oldest.first().map(|(_, score)| from_unix_timestamp(*score)),
+                newest: newest.first().map(|(_, score)| from_unix_timestamp(*score)),
+            });
+        }
+        Ok(summaries)
+    }
+
+    /// Cross-check the indexes of this namespace against the records
+    /// and tombstones, see `IntegrityReport`
+    ///
+    /// Reports records missing from `data_vault:index`, e.g. stored by
+    /// versions without it, tombstones missing from `data_vault:deleted`
+    /// and entries of the indexes and the `data_vault:version` hash
+    /// whose record or tombstone is gone.  Entries redis expired and
+    /// `purge_expired` has yet to clear are no issue.  Records are
+    /// found with `SCAN`, which walks every key of the database, in
+    /// the default namespace without a key prefix any string key
+    /// without `:` is taken for a record.  Entries changed while the
+    /// report runs may be reported, `repair_integrity` checks each
+    /// issue again before fixing it.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.integrity_report().await.unwrap();
+    /// if !report.is_ok() {
+    ///     println!("repaired {} of {:?}", data_vault.repair_integrity(&report).await.unwrap(), report.issues);
+    /// }
+    /// ```
+    pub async fn integrity_report(&self) -> Result<IntegrityReport, DataVaultError> {
+        let mut report = IntegrityReport::default();
+        let checks = [
+            (IndexCheck::Records, TokenScan::Keys(self.key("")?)),
+            (IndexCheck::Tombstones, TokenScan::Keys(self.tombstone_key("")?)),
+            (IndexCheck::Index, TokenScan::SortedSet(self.index_key())),
+            (IndexCheck::Expiring, TokenScan::SortedSet(self.expiring_index_key())),
+            (IndexCheck::Deleted, TokenScan::SortedSet(self.deleted_index_key())),
+            (IndexCheck::Versions, TokenScan::Hash(self.version_key())),
+        ];
+
+        for (check, scan) in checks.iter() {
+            let mut cursor = 0;
+            loop {
+                let (next_cursor, tokens) = self.scan_tokens(scan, cursor).await?;
+                if !tokens.is_empty() {
+                    self.check_integrity(*check, &tokens, &mut report).await?;
+                }
+
+                if next_cursor == 0 {
+                    break
+                }
+                cursor = next_cursor;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Fix the issues of `report`, made by `integrity_report` of this
+    /// namespace: unindexed records and tombstones are indexed, as
+    /// stored now and expiring with their key, dangling entries are
+    /// removed.  Issues that no longer hold or change during the fix
+    /// are left alone.
+    /// returns:
+    ///     * how many issues were fixed
+    pub async fn repair_integrity(&self, report: &IntegrityReport) -> Result<u64, DataVaultError> {
+        let mut repaired = 0;
+        for issue in report.issues.iter() {
+            if self.repair_issue(issue).await? {
+                repaired += 1;
+            }
+        }
+        Ok(repaired)
+    }
+
+    /// one batch of `scan` from `cursor`, tokens no key can be made of
+    /// are left out, `verify` reports them
+    /// returns the next cursor, 0 once the scan is complete
+    async fn scan_tokens(&self, scan: &TokenScan, cursor: u64) -> Result<(u64, Vec<String>), DataVaultError> {
+        let mut command = match scan {
+            // SCAN has no TYPE filter before Redis 6
+            TokenScan::Keys(prefix) => cmd("SCAN").arg(cursor).arg("MATCH").arg(format!("{}*", escape_glob(prefix))).clone(),
+            TokenScan::SortedSet(key) => cmd("ZSCAN").arg(key).arg(cursor).clone(),
+            TokenScan::Hash(key) => cmd("HSCAN").arg(key).arg(cursor).clone(),
+        };
+        let mut conn = self.connection().await?;
+        let (next_cursor, items): (u64, Vec<String>) = command.arg("COUNT").arg(SCAN_BATCH_SIZE)
+            .query_async(&mut *conn)
+            .await?;
+
+        let tokens: Vec<String> = match scan {
+            TokenScan::Keys(_) if items.is_empty() => Vec::new(),
+            TokenScan::Keys(prefix) => {
+                let mut types = pipe();
+                for key in &items {
+                    types.cmd("TYPE").arg(key);
+                }
+                let types: Vec<String> = types.query_async(&mut *conn).await?;
+                items.into_iter().zip(types)
+                    .filter(|(_, key_type)| key_type == "string")
+                    .filter_map(|(key, _)| key.strip_prefix(prefix.as_str()).map(str::to_string))
+                    .collect()
+            },
+            // members and fields are interleaved with scores and values
+            TokenScan::SortedSet(_) | TokenScan::Hash(_) => items.into_iter().step_by(2).collect(),
+        };
+        Ok((next_cursor, tokens.into_iter().filter(|token| self.key(token).is_ok()).collect()))
+    }
+
+    /// the scores of `tokens` in the sorted set `key`
+    async fn scores(&self, key: &str, tokens: &[String]) -> Result<Vec<Option<f64>>, DataVaultError> {
+        let mut lookup = pipe();
+        for token in tokens {
+            lookup.zscore(key, token);
+        }
+        let mut conn = self.connection().await?;
+        Ok(lookup.query_async(&mut *conn).await?)
+    }
+
+    /// whether `keys` exist
+    async fn exist(&self, keys: &[String]) -> Result<Vec<bool>, DataVaultError> {
+        let mut lookup = pipe();
+        for key in keys {
+            lookup.exists(key);
+        }
+        let mut conn = self.connection().await?;
+        Ok(lookup.query_async(&mut *conn).await?)
+    }
+
+    /// `check` the `tokens` of one batch and add them to `report`
+    async fn check_integrity(&self, check: IndexCheck, tokens: &[String], report: &mut IntegrityReport) -> Result<(), DataVaultError> {
+        let records = || tokens.iter().map(|token| self.key(token)).collect::<Result<Vec<String>, DataVaultError>>();
+        let tombstones = || tokens.iter().map(|token| self.tombstone_key(token)).collect::<Result<Vec<String>, DataVaultError>>();
+        let now = unix_timestamp();
+
+        let (index, problems): (&'static str, Vec<Option<IntegrityProblem>>) = match check {
+            IndexCheck::Records | IndexCheck::Tombstones => {
+                let (index, index_key) = match check {
+                    IndexCheck::Records => (INDEX_KEY, self.index_key()),
+                    _ => (DELETED_INDEX_KEY, self.deleted_index_key()),
+                };
+                let scores = self.scores(&index_key, tokens).await?;
+                (index, scores.iter().map(|score| score.is_none().then_some(IntegrityProblem::Unindexed)).collect())
+            },
+            IndexCheck::Index => {
+                let exist = self.exist(&records()?).await?;
+                let expiring = self.scores(&self.expiring_index_key(), tokens).await?;
+                (INDEX_KEY, exist.iter().zip(expiring).map(|(exists, expires_at)| {
+                    let expired = expires_at.is_some_and(|expires_at| expires_at <= now);
+                    (!exists && !expired).then_some(IntegrityProblem::Dangling)
+                }).collect())
+            },
+            IndexCheck::Expiring | IndexCheck::Versions => {
+                let index = match check {
+                    IndexCheck::Expiring => EXPIRING_INDEX_KEY,
+                    _ => VERSION_KEY,
+                };
+                let exist = self.exist(&records()?).await?;
+                let indexed = self.scores(&self.index_key(), tokens).await?;
+                (index, exist.iter().zip(indexed).map(|(exists, created_at)| {
+                    (!exists && created_at.is_none()).then_some(IntegrityProblem::Dangling)
+                }).collect())
+            },
+            IndexCheck::Deleted => {
+                let exist = self.exist(&tombstones()?).await?;
+                let expires_at = self.scores(&self.deleted_index_key(), tokens).await?;
+                (DELETED_INDEX_KEY, exist.iter().zip(expires_at).map(|(exists, expires_at)| {
+                    let expired = expires_at.is_none_or(|expires_at| expires_at <= now);
+                    (!exists && !expired).then_some(IntegrityProblem::Dangling)
+                }).collect())
+            },
+        };
+
+        for (token, problem) in tokens.iter().zip(problems) {
+            report.check(token, index, problem);
+        }
+        Ok(())
+    }
+
+    /// fix `issue` unless it no longer holds
+    /// returns:
+    ///     * whether it was fixed
+    async fn repair_issue(&self, issue: &IntegrityIssue) -> Result<bool, DataVaultError> {
+        let token = issue.token.as_str();
+        let (key, tombstone_key) = (self.key(token)?, self.tombstone_key(token)?);
+        let (index_key, expiring_index_key, deleted_index_key) = (self.index_key(), self.expiring_index_key(), self.deleted_index_key());
+        let mut conn = self.connection().await?;
+        let now = unix_timestamp();
+
+        // WATCH makes EXEC fail if the keys change before the fix
+        let mut fix = pipe();
+        fix.atomic();
+        let holds = match (issue.problem, issue.index) {
+            (IntegrityProblem::Unindexed, INDEX_KEY) | (IntegrityProblem::Unindexed, DELETED_INDEX_KEY) => {
+                let (key, index_key) = match issue.index {
+                    INDEX_KEY => (&key, &index_key),
+                    _ => (&tombstone_key, &deleted_index_key),
+                };
+                let _: () = cmd("WATCH").arg(key).arg(index_key).query_async(&mut *conn).await?;
+                // -2 is a missing key, -1 one without a time to live
+                let (pttl, score): (i64, Option<f64>) = pipe()
+                    .pttl(key)
+                    .zscore(index_key, token)
+                    .query_async(&mut *conn)
+                    .await?;
+                let expires_at = now + pttl.max(0) as f64 / 1000.0;
+                match issue.index {
+                    INDEX_KEY => {
+                        fix.cmd("ZADD").arg(index_key).arg("NX").arg(now).arg(token).ignore();
+                        if pttl >= 0 {
+                            fix.cmd("ZADD").arg(&expiring_index_key).arg("NX").arg(expires_at).arg(token).ignore();
+                        }
+                    },
+                    // a tombstone without an expiry is purged right away
+                    _ => {
+                        fix.cmd("ZADD").arg(index_key).arg("NX").arg(expires_at).arg(token).ignore();
+                    },
+                }
+                pttl != -2 && score.is_none()
+            },
+            (IntegrityProblem::Dangling, INDEX_KEY) | (IntegrityProblem::Dangling, DELETED_INDEX_KEY) => {
+                let (key, index_key) = match issue.index {
+                    INDEX_KEY => (&key, &index_key),
+                    _ => (&tombstone_key, &deleted_index_key),
+                };
+                let _: () = cmd("WATCH").arg(key).arg(index_key).query_async(&mut *conn).await?;
+                let (exists, score): (bool, Option<f64>) = pipe()
+                    .exists(key)
+                    .zscore(index_key, token)
+                    .query_async(&mut *conn)
+                    .await?;
+                fix.zrem(index_key, token).ignore();
+                if issue.index == INDEX_KEY {
+                    fix
+                        .zrem(&expiring_index_key, token).ignore()
+                        .hdel(self.version_key(), token).ignore();
+                }
+                !exists && score.is_some()
+            },
+            (IntegrityProblem::Dangling, EXPIRING_INDEX_KEY) | (IntegrityProblem::Dangling, VERSION_KEY) => {
+                let version_key = self.version_key();
+                let _: () = cmd("WATCH").arg(&key).arg(&index_key).arg(&expiring_index_key).arg(&version_key).query_async(&mut *conn).await?;
+                let (exists, created_at, expires_at, version): (bool, Option<f64>, Option<f64>, Option<u64>) = pipe()
+                    .exists(&key)
+                    .zscore(&index_key, token)
+                    .zscore(&expiring_index_key, token)
+                    .hget(&version_key, token)
+                    .query_async(&mut *conn)
+                    .await?;
+                let listed = match issue.index {
+                    EXPIRING_INDEX_KEY => {
+                        fix.zrem(&expiring_index_key, token).ignore();
+                        expires_at.is_some()
+                    },
+                    _ => {
+                        fix.hdel(&version_key, token).ignore();
+                        version.is_some()
+                    },
+                };
+                !exists && created_at.is_none() && listed
+            },
+            // an issue of another back end
+            _ => return Ok(false),
+        };
+
+        if !holds {
+            let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+            return Ok(false)
+        }
+        let fixed: Option<()> = fix.query_async(&mut *conn).await?;
+        Ok(fixed.is_some())
+    }
+
+    /// `record` compressed and encrypted as it is stored
+    fn seal(&self, record: &[u8]) -> Vec<u8>
+        where E: Encryption
+    {
+        self.latency.time(LatencyStage::Encrypt, || self.encryption.load().encrypt(&self.compression.compress(record)))
+    }
+
+    /// the record sealed in `ciphertext`
+    fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>, DataVaultError>
+        where E: Encryption
+    {
+        self.latency.time(LatencyStage::Decrypt, || {
+            let record = self.encryption.load().decrypt_bytes(ciphertext);
+            Ok(CompressionAlgo::decompress(&record)?.into_owned())
+        })
+    }
+
+    /// `credit_card` with its number, name and expiry normalized, or
+    /// `DataVaultError::Validation` for a card number or expiry that
+    /// is not one, unless validation is turned off
+    fn validate_card<'a>(&self, credit_card: &'a CreditCard) -> Result<Cow<'a, CreditCard>, DataVaultError> {
+        if !self.validate_cards {
+            return Ok(normalize_card(credit_card))
+        }
+        Ok(validate_card(credit_card, self.reject_expired)?)
+    }
+
+    /// `value` as a record, timed
+    fn serialize<V: serde::Serialize>(&self, value: &V) -> Result<Vec<u8>, DataVaultError>
+        where S: Serializer
+    {
+        self.latency.time(LatencyStage::Serialize, || self.serializer.serialize(value))
+    }
+
+    /// the value in `record`, timed
+    fn deserialize<V: serde::de::DeserializeOwned>(&self, record: &[u8]) -> Result<V, DataVaultError>
+        where S: Serializer
+    {
+        self.latency.time(LatencyStage::Deserialize, || self.serializer.deserialize(record))
+    }
+
+    /// `open` for records handed out as text
+    fn open_string(&self, ciphertext: &[u8]) -> Result<String, DataVaultError>
+        where E: Encryption
+    {
+        String::from_utf8(self.open(ciphertext)?).map_err(|e| DataVaultError::Encoding(Box::new(e)))
+    }
+
+    /// the redis key of `token` in this vault's namespace
+    fn key(&self, token: &str) -> Result<String, DataVaultError> {
+        Ok(format!("{}{}", self.key_prefix, self.record_key(token)?))
+    }
+
+    /// `key` without the key prefix
+    fn record_key(&self, token: &str) -> Result<String, DataVaultError> {
+        if token.contains(':') {
+            return Err(DataVaultError::InvalidToken)
+        }
+
+        match self.namespace.as_str() {
+            DEFAULT_NAMESPACE => Ok(token.to_string()),
+            namespace => Ok(format!("{}:{}", namespace, token)),
+        }
+    }
+
+    /// `key` for this vault's namespace, `key` itself in the default
+    /// one, after the key prefix
+    fn namespaced(&self, key: &str) -> String {
+        self.namespaced_in(key, &self.namespace)
+    }
+
+    /// `namespaced` for the vault's namespace `namespace`
+    fn namespaced_in(&self, key: &str, namespace: &str) -> String {
+        match namespace {
+            DEFAULT_NAMESPACE => format!("{}{}", self.key_prefix, key),
+            namespace => format!("{}{}:{}", self.key_prefix, key, namespace),
+        }
+    }
+
+    /// the sorted set indexing this vault's namespace
+    fn index_key(&self) -> String {
+        self.namespaced(INDEX_KEY)
+    }
+
+    /// the sorted set of soft deleted tokens in this vault's namespace
+    fn deleted_index_key(&self) -> String {
+        self.namespaced(DELETED_INDEX_KEY)
+    }
+
+    /// the sorted set of records with a time to live in this vault's namespace
+    fn expiring_index_key(&self) -> String {
+        self.namespaced(EXPIRING_INDEX_KEY)
+    }
+
+    /// the hash of record versions in this vault's namespace
+    fn version_key(&self) -> String {
+        self.namespaced(VERSION_KEY)
+    }
+
+    /// the key a soft deleted `token` is kept under
+    fn tombstone_key(&self, token: &str) -> Result<String, DataVaultError> {
+        Ok(format!("{}{}:{}", self.key_prefix, TOMBSTONE_PREFIX, self.record_key(token)?))
+    }
+
+    /// `store` with a time to live, `None` never expires
+    async fn store_bytes(&self, token: &str, record: &[u8], ttl: Option<Duration>) -> Result<(), DataVaultError>
+        where E: Encryption
+    {
+        let mut store = pipe();
+        store.atomic();
+        self.queue_store(&mut store, token, record, ttl)?;
+        let mut conn = self.connection().await?;
+        let _: () = self.latency.time_async(LatencyStage::Store, store.query_async(&mut *conn)).await?;
+        Ok(())
+    }
+
+    /// Add the commands of `store_bytes` to `store`, replies ignored
+    fn queue_store(&self, store: &mut Pipeline, token: &str, record: &[u8], ttl: Option<Duration>) -> Result<(), DataVaultError>
+        where E: Encryption
+    {
+        let key = self.key(token)?;
+        let encrypted_json = self.seal(record);
+        match ttl {
+            // SET rejects PX 0, a record expiring right away lives 1ms
+            Some(ttl) => store
+                .cmd("SET").arg(&key).arg(encrypted_json).arg("PX").arg(ttl.as_millis().max(1) as u64).ignore()
+                .cmd("ZADD").arg(self.expiring_index_key()).arg(unix_timestamp() + ttl.as_secs_f64()).arg(token).ignore(),
+            None => store
+                .set(&key, encrypted_json).ignore()
+                .zrem(self.expiring_index_key(), token).ignore(),
+        };
+        store
+            .cmd("ZADD").arg(self.index_key()).arg("NX").arg(unix_timestamp()).arg(token).ignore()
+            .hincr(self.version_key(), token, 1).ignore();
+        Ok(())
+    }
+
+    /// `store_bytes` with `SET NX`, an existing record is left as is
+    /// returns:
+    ///     * whether the record was stored
+    async fn store_expiring_if_absent(&self, token: &str, record: &[u8], ttl: Option<Duration>) -> Result<bool, DataVaultError>
+        where E: Encryption
+    {
+        let key = self.key(token)?;
+        let mut conn = self.connection().await?;
+        let encrypted_json = self.seal(record);
+        let mut store = pipe();
+        store.atomic();
+        match ttl {
+            // an existing record keeps its expiry, purge_expired
+            // ignores the extra entry in the expiring index
+            Some(ttl) => store
+                .cmd("SET").arg(&key).arg(encrypted_json).arg("NX").arg("PX").arg(ttl.as_millis().max(1) as u64)
+                .cmd("ZADD").arg(self.expiring_index_key()).arg("NX").arg(unix_timestamp() + ttl.as_secs_f64()).arg(token).ignore(),
+            None => store
+                .cmd("SET").arg(&key).arg(encrypted_json).arg("NX"),
+        };
+        let store = store
+            .cmd("ZADD").arg(self.index_key()).arg("NX").arg(unix_timestamp()).arg(token).ignore()
+            .hset_nx(self.version_key(), token, 1).ignore()
+            .query_async(&mut *conn);
+        let (created,): (Option<String>,) = self.latency.time_async(LatencyStage::Store, store).await?;
+        Ok(created.is_some())
+    }
+
+    /// `delete_many` of this namespace only, side data is left alone
+    async fn delete_records(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        if tokens.is_empty() {
+            return Ok(0)
+        }
+
+        let keys = tokens.iter().map(|token| self.key(token)).collect::<Result<Vec<String>, DataVaultError>>()?;
+        let tombstone_keys = tokens.iter().map(|token| self.tombstone_key(token)).collect::<Result<Vec<String>, DataVaultError>>()?;
+        let mut conn = self.connection().await?;
+        let (deleted,): (usize,) = pipe()
+            .atomic()
+            .del(keys.as_slice())
+            .del(tombstone_keys.as_slice()).ignore()
+            .zrem(self.index_key(), tokens).ignore()
+            .zrem(self.expiring_index_key(), tokens).ignore()
+            .zrem(self.deleted_index_key(), tokens).ignore()
+            .hdel(self.version_key(), tokens).ignore()
+            .query_async(&mut *conn)
+            .await?;
+        Ok(deleted)
+    }
+
+    /// Keep the `CardSummary` of `credit_card` in the side namespace
+    /// `<namespace>~summary`, expiring with the card
+    async fn store_summary(&self, token: &str, credit_card: &CreditCard, ttl: Option<Duration>) -> Result<(), DataVaultError>
+        where
+            E: Encryption + std::marker::Sync + std::marker::Send,
+            T: Tokenizer + std::marker::Sync + std::marker::Send,
+            S: Serializer + std::marker::Sync + std::marker::Send,
+    {
+        let summary = serde_json::to_vec(&CardSummary::from(credit_card))?;
+        self.with_side_namespace("summary")?
+            .store_bytes(token, &summary, ttl).await
+    }
+
+    /// Store a validated card and its summary under a new token, or
+    /// return the token a deduplicating tokenizer already stored it under
+    async fn store_prepared(&self, credit_card: &CreditCard, ttl: Option<Duration>) -> Result<String, DataVaultError>
+        where
+            E: Encryption + std::marker::Sync + std::marker::Send,
+            T: Tokenizer + std::marker::Sync + std::marker::Send,
+            S: Serializer + std::marker::Sync + std::marker::Send,
+    {
+        let fingerprint = self.tokenizer.load().fingerprint(credit_card);
+        let (token, _) = deduplicate(self, fingerprint, async {
+            let token = self.tokenizer.load().generate(credit_card);
+            let record = self.serialize(credit_card)?;
+            let _:() = self.store_bytes(&token, &record, ttl).await?;
+            self.store_summary(&token, credit_card, ttl).await?;
+            Ok((token, true))
+        }).await?;
+        Ok(token)
+    }
+
+    /// Move the side data of `token` to `new_token` after
+    /// `rotate_token`, short lived side data is deleted
+    async fn rotate_side_records(&self, token: &str, new_token: &str) -> Result<(), DataVaultError>
+        where
+            E: Encryption + std::marker::Sync + std::marker::Send,
+            T: Tokenizer + std::marker::Sync + std::marker::Send,
+            S: Serializer + std::marker::Sync + std::marker::Send,
+    {
+        for kind in TOKEN_SIDE_KINDS.iter() {
+            let side = self.with_side_namespace(kind)?;
+            match side.retrieve_bytes(token).await {
+                Ok(record) => side.store_bytes(new_token, &record, None).await?,
+                Err(DataVaultError::NotFound) => continue,
+                Err(e) => return Err(e),
+            }
+            side.delete_records(&[token.to_string()]).await?;
+        }
+        for kind in TRANSIENT_SIDE_KINDS.iter() {
+            self.with_side_namespace(kind)?.delete_records(&[token.to_string()]).await?;
+        }
+        Ok(())
+    }
+
+    /// the decrypted record stored under `token`
+    async fn retrieve_bytes(&self, token: &str) -> Result<Vec<u8>, DataVaultError>
+        where E: Encryption
+    {
+        let key = self.key(token)?;
+        let mut conn = self.connection().await?;
+        let encrypted_credit_card_json: Option<Vec<u8>> = self.latency.time_async(LatencyStage::Retrieve, conn.get(&key)).await?;
+        match encrypted_credit_card_json {
+            Some(encrypted) => self.open(encrypted.as_slice()),
+            None => Err(DataVaultError::NotFound),
+        }
+    }
+
+    /// the tombstone half of `purge_expired`
+    async fn purge_tombstones(&self, now: f64) -> Result<PurgeReport, DataVaultError> {
+        let deleted_index_key = self.deleted_index_key();
+        let mut conn = self.connection().await?;
+        let mut report = PurgeReport::default();
+
+        loop {
+            // WATCH makes EXEC fail if a due token is deleted again meanwhile
+            let _: () = cmd("WATCH").arg(&deleted_index_key).query_async(&mut *conn).await?;
+            let due: Vec<String> = conn.zrangebyscore_limit(&deleted_index_key, "-inf", now, 0, PURGE_BATCH_SIZE).await?;
+
+            if due.is_empty() {
+                let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+                return Ok(report)
+            }
+
+            let mut purge = pipe();
+            purge.atomic();
+            for token in due.iter() {
+                purge.del(self.tombstone_key(token)?);
+            }
+            purge.zrem(&deleted_index_key, due.as_slice()).ignore();
+            let removed: Option<Vec<u64>> = purge.query_async(&mut *conn).await?;
+
+            match removed {
+                Some(removed) => {
+                    let left_behind: u64 = removed.iter().sum();
+                    report.purged += due.len() as u64;
+                    report.expired_by_backend += due.len() as u64 - left_behind;
+                },
+                None => return Err(DataVaultError::Conflict),
+            }
+
+            if (due.len() as isize) < PURGE_BATCH_SIZE {
+                return Ok(report)
+            }
+        }
+    }
+
+    /// the expiring record half of `purge_expired`
+    async fn purge_expiring(&self, now: f64, report: &mut PurgeReport) -> Result<(), DataVaultError> {
+        let expiring_index_key = self.expiring_index_key();
+        let mut conn = self.connection().await?;
+
+        loop {
+            // WATCH makes EXEC fail if a due record is stored again meanwhile
+            let _: () = cmd("WATCH").arg(&expiring_index_key).query_async(&mut *conn).await?;
+            let due: Vec<String> = conn.zrangebyscore_limit(&expiring_index_key, "-inf", now, 0, PURGE_BATCH_SIZE).await?;
+
+            if due.is_empty() {
+                let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+                return Ok(())
+            }
+
+            let keys = due.iter().map(|token| self.key(token)).collect::<Result<Vec<String>, DataVaultError>>()?;
+            let _: () = cmd("WATCH").arg(keys.as_slice()).query_async(&mut *conn).await?;
+            let mut pttls = pipe();
+            for key in keys.iter() {
+                pttls.pttl(key);
+            }
+            let pttls: Vec<i64> = pttls.query_async(&mut *conn).await?;
+
+            let mut purge = pipe();
+            purge.atomic();
+            purge.zrem(&expiring_index_key, due.as_slice()).ignore();
+            let mut expired = Vec::new();
+            for (token, pttl) in due.iter().zip(pttls) {
+                match pttl {
+                    // gone, -1 is a record stored again without a time to live
+                    -2 => expired.push(token.as_str()),
+                    -1 => (),
+                    // not expired yet, check again when it is due
+                    pttl => {
+                        purge.cmd("ZADD").arg(&expiring_index_key).arg(now + pttl as f64 / 1000.0).arg(token).ignore();
+                    },
+                }
+            }
+            if !expired.is_empty() {
+                purge
+                    .zrem(self.index_key(), expired.as_slice()).ignore()
+                    .hdel(self.version_key(), expired.as_slice()).ignore();
+            }
+            let purged: Option<()> = purge.query_async(&mut *conn).await?;
+
+            match purged {
+                Some(()) => {
+                    report.purged += expired.len() as u64;
+                    report.expired_by_backend += expired.len() as u64;
+                },
+                None => return Err(DataVaultError::Conflict),
+            }
+
+            if (due.len() as isize) < PURGE_BATCH_SIZE {
+                return Ok(())
+            }
+        }
+    }
+
+    /// one ZSCAN batch of the index with the ciphertext of its tokens
+    /// returns the next cursor, 0 once the scan is complete
+    async fn scan_records(&self, cursor: u64, count: usize) -> Result<(u64, Vec<(String, Vec<u8>)>), DataVaultError> {
+        let mut conn = self.connection().await?;
+        let (next_cursor, members): (u64, Vec<String>) = cmd("ZSCAN")
+            .arg(self.index_key()).arg(cursor).arg("COUNT").arg(count)
+            .query_async(&mut *conn)
+            .await?;
+
+        // members and scores are interleaved
+        let tokens: Vec<String> = members.into_iter().step_by(2).collect();
+        if tokens.is_empty() {
+            return Ok((next_cursor, Vec::new()))
+        }
+
+        let keys = tokens.iter().map(|token| self.key(token)).collect::<Result<Vec<String>, DataVaultError>>()?;
+        let records: Vec<Option<Vec<u8>>> = cmd("MGET").arg(keys).query_async(&mut *conn).await?;
+
+        // records deleted since the scan are skipped
+        let records = tokens.into_iter()
+            .zip(records)
+            .filter_map(|(token, record)| record.map(|record| (token, record)))
+            .collect();
+        Ok((next_cursor, records))
+    }
 }
 
 #[async_trait]
-impl<E, T> DataVault for RedisDataVault<E, T>
+impl<E, T, S> DataVault for RedisDataVault<E, T, S>
     where
         E: Encryption + std::marker::Sync + std::marker::Send,
         T: Tokenizer + std::marker::Sync + std::marker::Send,
+        S: Serializer + std::marker::Sync + std::marker::Send,
 {
     /// Create new RedisDataVault backend
     /// # examples
@@ -45,17 +1231,7 @@ impl<E, T> DataVault for RedisDataVault<E, T>
     /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
     /// ```
     fn new() -> Result<Self, Box<dyn error::Error>> {
-        let cfg = DeadpoolRedisConfig::from_env()?;
-
-        let pool = cfg.redis.create_pool()?;
-
-        let redis_data_vault = RedisDataVault {
-            pool,
-            encryption: E::new(),
-            tokenizer: T::new()
-        };
-
-        Ok(redis_data_vault)
+        RedisDataVault::from_config(RedisVaultConfig::from_env()?)
     }
 
     /// Encrypt and Store a string with the given token as the redis key
@@ -74,11 +1250,47 @@ impl<E, T> DataVault for RedisDataVault<E, T>
     /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
     /// data_vault.store(&token, &credit_card_string);
     /// ```
-    async fn store(&self, token: &String, string: &String) -> Result<(), PoolErrors> {
-        let mut conn = self.pool.get().await?;
-        let encrypted_json = self.encryption.encrypt(string.as_bytes());
-        let _:() = conn.set(token, encrypted_json).await.unwrap();
-        Ok(())
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "store", Some(token), async {
+            self.store_bytes(token, string.as_bytes(), None).await
+        }).await
+    }
+
+    /// Encrypt and Store a string with `SET NX`, unless a record is
+    /// stored under the token already
+    /// returns:
+    ///     * whether the string was stored
+    /// # example
+    /// ```rust
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_if_absent("abc123", "{number: 123}");
+    /// ```
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "store_if_absent", Some(token), async {
+            self.store_expiring_if_absent(token, string.as_bytes(), None).await
+        }).await
+    }
+
+    /// Encrypt and Store a string with `SET PX`, the record and its
+    /// expiry are written together
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use std::time::Duration;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_expiring("abc123", "{number: 123}", Duration::from_secs(300)).await.unwrap();
+    /// ```
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "store_expiring", Some(token), async {
+            self.store_bytes(token, string.as_bytes(), Some(ttl)).await
+        }).await
     }
 
     /// Store the credit card in the data vault
@@ -105,35 +1317,256 @@ impl<E, T> DataVault for RedisDataVault<E, T>
     /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
     /// let token = data_vault.store_credit_card(&cc);
     /// ```
-    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, PoolErrors> {
-        let token = self.tokenizer.generate(&credit_card);
-        let credit_card_json = serde_json::to_string(&credit_card).unwrap();
-        let _:() = self.store(&token, &credit_card_json).await?;
-        Ok(token)
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "store_credit_card", None, async {
+            let credit_card = self.validate_card(credit_card)?;
+            let credit_card = with_brand(&credit_card);
+            let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+            self.store_prepared(&credit_card, ttl).await
+        }).await
     }
 
-    /// Get decrypted arbitrary data from the vault by token
-    /// Arguments:
-    ///     * `token`: the string form of the ID of the data
+    /// Store many credit cards and their summaries in one pipeline,
+    /// one round trip for all of them
+    ///
+    /// Every card is validated before anything is written, and the
+    /// pipeline runs as a transaction, so either all cards are stored
+    /// or none.  Tokenizers that deduplicate cards by fingerprint look
+    /// each card up first and store them one after the other, then
+    /// a failure can leave the cards before it stored.
     /// returns:
-    ///     * the decrypted string of data
+    ///     * the tokens in the order of `credit_cards`
     /// # example
     /// ```rust,ignore
-    /// use data_vault::DataVault;
-    /// use data_vault::RedisDataVault;
+    /// use data_vault::{DataVault, RedisDataVault};
     /// use data_vault::encryption::AesGcmSivEncryption;
     /// use data_vault::tokenizer::Blake3Tokenizer;
     ///
-    /// let token = String::from("abc123");
-    /// let cc_string = String::from("{number: 123}");
     /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
-    /// data_vault.store(&token, &cc_string).await;
-    /// let credit_card_string = data_vault.retrieve(&token)
+    /// let tokens = data_vault.store_credit_cards(&credit_cards).await.unwrap();
     /// ```
-    async fn retrieve(&self, token: &String) -> Result<String, PoolErrors> {
-        let mut conn = self.pool.get().await?;
-        let encrypted_credit_card_json: Vec<u8> = conn.get(token).await.unwrap();
-        Ok(self.encryption.decrypt(encrypted_credit_card_json.as_slice()))
+    async fn store_credit_cards(&self, credit_cards: &[CreditCard]) -> Result<Vec<String>, DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "store_credit_cards", None, async {
+            let mut prepared = Vec::with_capacity(credit_cards.len());
+            for credit_card in credit_cards {
+                let credit_card = self.validate_card(credit_card)?;
+                let credit_card = with_brand(&credit_card);
+                let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+                prepared.push((credit_card.into_owned(), ttl));
+            }
+            if prepared.iter().any(|(credit_card, _)| self.tokenizer.load().fingerprint(credit_card).is_some()) {
+                let mut tokens = Vec::with_capacity(prepared.len());
+                for (credit_card, ttl) in &prepared {
+                    tokens.push(self.store_prepared(credit_card, *ttl).await?);
+                }
+                return Ok(tokens)
+            }
+            if prepared.is_empty() {
+                return Ok(Vec::new())
+            }
+
+            let summaries = self.with_side_namespace("summary")?;
+            let mut store = pipe();
+            store.atomic();
+            let mut tokens = Vec::with_capacity(prepared.len());
+            for (credit_card, ttl) in &prepared {
+                let token = self.tokenizer.load().generate(credit_card);
+                self.queue_store(&mut store, &token, &self.serialize(credit_card)?, *ttl)?;
+                let summary = serde_json::to_vec(&CardSummary::from(credit_card))?;
+                summaries.queue_store(&mut store, &token, &summary, *ttl)?;
+                tokens.push(token);
+            }
+            let mut conn = self.connection().await?;
+            let _: () = self.latency.time_async(LatencyStage::Store, store.query_async(&mut *conn)).await?;
+            Ok(tokens)
+        }).await
+    }
+
+    /// Get decrypted credit cards with one `MGET`, one round trip for
+    /// all of them
+    ///
+    /// Tokens that are not stored are looked up as aliases of merged
+    /// cards, see `merge`, one after the other.
+    /// returns:
+    ///     * the cards in the order of `tokens`, `None` for tokens
+    ///       with nothing stored under them
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let credit_cards = data_vault.retrieve_credit_cards(&tokens).await.unwrap();
+    /// ```
+    async fn retrieve_credit_cards(&self, tokens: &[String]) -> Result<Vec<Option<CreditCard>>, DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "retrieve_credit_cards", None, async {
+            if tokens.is_empty() {
+                return Ok(Vec::new())
+            }
+
+            let keys = tokens.iter().map(|token| self.key(token)).collect::<Result<Vec<String>, DataVaultError>>()?;
+            let mut conn = self.connection().await?;
+            let records: Vec<Option<Vec<u8>>> = self.latency.time_async(LatencyStage::Retrieve, cmd("MGET").arg(keys).query_async(&mut *conn)).await?;
+            drop(conn);
+
+            let mut credit_cards = Vec::with_capacity(tokens.len());
+            for (token, record) in tokens.iter().zip(records) {
+                let record = match record {
+                    Some(encrypted) => self.open(&encrypted)?,
+                    // the token of a merged card
+                    None => match resolve_alias(self, token).await? {
+                        Some(survivor) => self.retrieve_bytes(&survivor).await?,
+                        None => {
+                            credit_cards.push(None);
+                            continue
+                        },
+                    },
+                };
+                credit_cards.push(Some(self.deserialize(&record)?));
+            }
+            Ok(credit_cards)
+        }).await
+    }
+
+    /// Get or create the token for a credit card
+    ///
+    /// With a deterministic tokenizer the card is only written when
+    /// neither its token nor a card with its number is in the vault
+    /// yet, an existing record is left untouched.  Other tokenizers
+    /// always store a new record.
+    /// Arguments:
+    ///     * `CreditCard` - the cc object that you wish to tokenize
+    /// return:
+    ///     the token as String and whether a new record was created
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3DeterministicTokenizer;
+    /// use credit_card::CreditCard;
+    ///
+    /// let cc = CreditCard {
+    ///    number: "4111111111111111".to_string(),
+    ///    cardholder_name: "Graydon Hoare".to_string(),
+    ///    expiration_month: "01".to_string(),
+    ///    expiration_year: "2023".to_string(),
+    ///    brand: None,
+    ///    security_code: None
+    /// };
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+    /// let (token, created) = data_vault.tokenize(&cc).await.unwrap();
+    /// let (same_token, created_again) = data_vault.tokenize(&cc).await.unwrap();
+    /// assert!(!created_again);
+    /// ```
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "tokenize", None, async {
+            let credit_card = self.validate_card(credit_card)?;
+            let credit_card = with_brand(&credit_card);
+            let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+            let fingerprint = self.tokenizer.load().fingerprint(&credit_card);
+            deduplicate(self, fingerprint, async {
+                let token = self.tokenizer.load().generate(&credit_card);
+                let record = self.serialize(&credit_card)?;
+
+                if !self.tokenizer.load().is_deterministic() {
+                    self.store_bytes(&token, &record, ttl).await?;
+                    self.store_summary(&token, &credit_card, ttl).await?;
+                    return Ok((token, true))
+                }
+
+                let created = self.store_expiring_if_absent(&token, &record, ttl).await?;
+                if created {
+                    self.store_summary(&token, &credit_card, ttl).await?;
+                }
+                Ok((token, created))
+            }).await
+        }).await
+    }
+
+    /// Store the credit card under a token chosen by the caller
+    ///
+    /// Without `overwrite` the card is written with `SET NX`, so a
+    /// record stored under the token meanwhile is never replaced.
+    /// Arguments:
+    ///     * `token`: the token to store the card under
+    ///     * `CreditCard` - the cc object that you wish to store
+    ///     * `overwrite`: replace a record stored under `token`
+    /// returns:
+    ///     * `DataVaultError::InvalidToken` for a token that is not
+    ///       1 to 64 characters of `[A-Za-z0-9_.-]`
+    ///     * `DataVaultError::AlreadyExists` when `token` is taken
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_credit_card_with_token("tok_legacy_42", &cc, false).await.unwrap();
+    /// ```
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "store_credit_card_with_token", Some(token), async {
+            validate_token(token)?;
+            let credit_card = self.validate_card(credit_card)?;
+            let credit_card = with_brand(&credit_card);
+            let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+            let record = self.serialize(&credit_card)?;
+
+            if overwrite {
+                self.store_bytes(token, &record, ttl).await?;
+                return self.store_summary(token, &credit_card, ttl).await
+            }
+
+            match self.store_expiring_if_absent(token, &record, ttl).await? {
+                true => self.store_summary(token, &credit_card, ttl).await,
+                false => Err(DataVaultError::AlreadyExists),
+            }
+        }).await
+    }
+
+    /// Get decrypted arbitrary data from the vault by token
+    /// Arguments:
+    ///     * `token`: the string form of the ID of the data
+    /// returns:
+    ///     * the decrypted string of data
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::DataVault;
+    /// use data_vault::RedisDataVault;
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let token = String::from("abc123");
+    /// let cc_string = String::from("{number: 123}");
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store(&token, &cc_string).await;
+    /// let credit_card_string = data_vault.retrieve(&token)
+    /// ```
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "retrieve", Some(token), async {
+            let record = self.retrieve_bytes(token).await?;
+            String::from_utf8(record).map_err(|e| DataVaultError::Encoding(Box::new(e)))
+        }).await
+    }
+
+    /// Whether a record is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store("abc123", "{number: 123}").await.unwrap();
+    /// assert!(data_vault.exists("abc123").await.unwrap());
+    /// ```
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        let key = self.key(token)?;
+        let mut conn = self.connection().await?;
+        Ok(conn.exists(&key).await?)
     }
 
     /// Get the credit card from the data vault given a token
@@ -141,6 +1574,7 @@ impl<E, T> DataVault for RedisDataVault<E, T>
     ///     * `token`: the string form of the ID of the data
     /// returns:
     ///     * `CreditCard` object
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
     /// # Example
     /// ```rust,ignore
     /// use data_vault::DataVault;
@@ -162,8 +1596,822 @@ impl<E, T> DataVault for RedisDataVault<E, T>
     /// let token = data_vault.store_credit_card(&cc).await;
     /// let credit_card = data_vault.retrieve_credit_card(&token).await;
     /// ```
-    async fn retrieve_credit_card(&self, token: &String) -> Result<CreditCard, PoolErrors> {
-        let credit_card_json = self.retrieve(token).await?;
-        Ok(serde_json::from_str(&credit_card_json).unwrap_or_default())
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "retrieve_credit_card", Some(token), async {
+            let record = match self.retrieve_bytes(token).await {
+                // the token of a merged card
+                Err(DataVaultError::NotFound) => match resolve_alias(self, token).await? {
+                    Some(survivor) => self.retrieve_bytes(&survivor).await?,
+                    None => return Err(DataVaultError::NotFound),
+                },
+                record => record?,
+            };
+            self.deserialize(&record)
+        }).await
+    }
+
+    /// Get the credit card and the version it is at
+    /// Arguments:
+    ///     * `token`: the string form of the ID of the data
+    /// returns:
+    ///     * `CreditCard` object and its version
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let (credit_card, version) = data_vault.retrieve_credit_card_with_version(&token).await.unwrap();
+    /// ```
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "retrieve_credit_card_with_version", Some(token), async {
+            let key = self.key(token)?;
+            let mut conn = self.connection().await?;
+            let mut retrieve = pipe();
+            retrieve.atomic()
+                .get(&key)
+                .hget(self.version_key(), token);
+            let (encrypted_credit_card_json, version): (Option<Vec<u8>>, Option<u64>) = self.latency.time_async(LatencyStage::Retrieve, retrieve.query_async(&mut *conn)).await?;
+
+            match encrypted_credit_card_json {
+                Some(encrypted) => {
+                    let record = self.open(encrypted.as_slice())?;
+                    Ok((self.deserialize(&record)?, version.unwrap_or_default()))
+                },
+                None => Err(DataVaultError::NotFound),
+            }
+        }).await
+    }
+
+    /// Get the credit card with its metadata in one pipeline
+    /// Arguments:
+    ///     * `token`: the string form of the ID of the data
+    /// returns:
+    ///     * `CreditCard` object and its `RecordMetadata`
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let (credit_card, metadata) = data_vault.retrieve_with_metadata(&token).await.unwrap();
+    /// ```
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        log_if_slow(self.slow_op, "redis", "retrieve_with_metadata", Some(token), async {
+            let key = self.key(token)?;
+            let mut conn = self.connection().await?;
+            let mut retrieve = pipe();
+            retrieve.atomic()
+                .get(&key)
+                .pttl(&key)
+                .zscore(self.index_key(), token)
+                .hget(self.version_key(), token);
+            let (encrypted_credit_card_json, pttl, created_at, version): (Option<Vec<u8>>, i64, Option<f64>, Option<u64>) = self.latency.time_async(LatencyStage::Retrieve, retrieve.query_async(&mut *conn)).await?;
+
+            let encrypted = encrypted_credit_card_json.ok_or(DataVaultError::NotFound)?;
+            let record = self.open(encrypted.as_slice())?;
+            let metadata = RecordMetadata {
+                created_at: created_at.map(from_unix_timestamp),
+                // -1 is a key without a time to live
+                ttl: match pttl {
+                    pttl if pttl >= 0 => Some(Duration::from_millis(pttl as u64)),
+                    _ => None,
+                },
+                version: version.unwrap_or_default(),
+                tenant: self.namespace.clone(),
+            };
+            Ok((self.deserialize(&record)?, metadata))
+        }).await
+    }
+
+    /// Replace the credit card only if it is still at `expected_version`
+    /// Arguments:
+    ///     * `token`: the token of the card to replace
+    ///     * `CreditCard` - the new card
+    ///     * `expected_version`: the version the change is based on
+    /// returns:
+    ///     * the new version
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    ///     * `DataVaultError::Conflict` when the record is at another version
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let (mut credit_card, version) = data_vault.retrieve_credit_card_with_version(&token).await.unwrap();
+    /// credit_card.expiration_year = "2027".to_string();
+    /// let version = data_vault.update_credit_card_if_version(&token, &credit_card, version).await.unwrap();
+    /// ```
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let key = self.key(token)?;
+        let version_key = self.version_key();
+        let credit_card = self.validate_card(credit_card)?;
+        let credit_card = with_brand(&credit_card);
+        let (credit_card, ttl) = self.cvv_policy.apply(&credit_card)?;
+        let record = self.serialize(&credit_card)?;
+        let mut conn = self.connection().await?;
+
+        // every version change writes the record, WATCH makes EXEC fail
+        // if that happens before the update
+        let _: () = cmd("WATCH").arg(&key).query_async(&mut *conn).await?;
+        let (pttl, version): (i64, Option<u64>) = pipe()
+            .pttl(&key)
+            .hget(&version_key, token)
+            .query_async(&mut *conn)
+            .await?;
+        // -2 is a missing key, -1 one without a time to live
+        let exists = pttl != -2;
+
+        if !exists || version.unwrap_or_default() != expected_version {
+            let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+            return match exists {
+                false => Err(DataVaultError::NotFound),
+                true => Err(DataVaultError::Conflict),
+            }
+        }
+
+        let encrypted_json = self.seal(&record);
+        let mut update = pipe();
+        update.atomic();
+        match ttl {
+            Some(ttl) => update
+                .cmd("SET").arg(&key).arg(encrypted_json).arg("PX").arg(ttl.as_millis() as u64).ignore()
+                .cmd("ZADD").arg(self.expiring_index_key()).arg(unix_timestamp() + ttl.as_secs_f64()).arg(token).ignore(),
+            // KEEPTTL needs redis 6, the remaining time is set again instead
+            None if pttl > 0 => update
+                .cmd("SET").arg(&key).arg(encrypted_json).arg("PX").arg(pttl).ignore(),
+            None => update
+                .set(&key, encrypted_json).ignore(),
+        };
+        let updated: Option<()> = update
+            .hset(&version_key, token, expected_version + 1).ignore()
+            .query_async(&mut *conn)
+            .await?;
+
+        match updated {
+            Some(()) => {
+                let ttl = ttl.or_else(|| Some(Duration::from_millis(pttl as u64)).filter(|_| pttl > 0));
+                self.store_summary(token, &credit_card, ttl).await?;
+                fingerprint::index(self, self.tokenizer.load().fingerprint(&credit_card), token).await?;
+                Ok(expected_version + 1)
+            },
+            None => Err(DataVaultError::Conflict),
+        }
+    }
+
+    /// Move a record to a new random token
+    ///
+    /// The ciphertext is moved as is, after this the old token is
+    /// no longer stored.  Use this when a token has leaked.  A
+    /// deterministic tokenizer will still produce the old token for
+    /// the same card, so `tokenize` would store it again.
+    /// Arguments:
+    ///     * `token`: the token to replace
+    /// returns:
+    ///     * the new token
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let new_token = data_vault.rotate_token(&leaked_token).await.unwrap();
+    /// ```
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        let key = self.key(token)?;
+        let index_key = self.index_key();
+        let mut conn = self.connection().await?;
+        let new_token = RandomToken::generate();
+        let new_key = self.key(&new_token)?;
+
+        // WATCH makes EXEC fail if the record changes before the rename
+        let _: () = cmd("WATCH").arg(&key).query_async(&mut *conn).await?;
+        let (exists, created_at, version, expires_at): (bool, Option<f64>, Option<u64>, Option<f64>) = pipe()
+            .exists(&key)
+            .zscore(&index_key, token)
+            .hget(self.version_key(), token)
+            .zscore(self.expiring_index_key(), token)
+            .query_async(&mut *conn)
+            .await?;
+
+        if !exists {
+            let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+            return Err(DataVaultError::NotFound)
+        }
+
+        let mut rotate = pipe();
+        rotate.atomic();
+        if let Some(expires_at) = expires_at {
+            rotate
+                .cmd("ZADD").arg(self.expiring_index_key()).arg(expires_at).arg(&new_token).ignore()
+                .zrem(self.expiring_index_key(), token).ignore();
+        }
+        let renamed: Option<()> = rotate
+            .rename(&key, &new_key).ignore()
+            .cmd("ZADD").arg(&index_key).arg(created_at.unwrap_or_else(unix_timestamp)).arg(&new_token).ignore()
+            .zrem(&index_key, token).ignore()
+            .hset(self.version_key(), &new_token, version.unwrap_or_default()).ignore()
+            .hdel(self.version_key(), token).ignore()
+            .query_async(&mut *conn)
+            .await?;
+
+        match renamed {
+            Some(()) => {
+                self.rotate_side_records(token, &new_token).await?;
+                Ok(new_token)
+            },
+            None => Err(DataVaultError::Conflict),
+        }
+    }
+
+    /// Hide a record until redis expires it after the retention period
+    ///
+    /// The token can be stored again, that creates a new record and
+    /// leaves the tombstone alone.
+    /// Arguments:
+    ///     * `token`: the token to delete
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.soft_delete(&token).await.unwrap();
+    /// assert!(data_vault.try_retrieve(&token).await.unwrap().is_none());
+    /// ```
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        let key = self.key(token)?;
+        let tombstone_key = self.tombstone_key(token)?;
+        let mut conn = self.connection().await?;
+
+        // WATCH makes EXEC fail if the record changes before the rename
+        let _: () = cmd("WATCH").arg(&key).query_async(&mut *conn).await?;
+        let pttl: i64 = conn.pttl(&key).await?;
+
+        // -2 is a missing key, -1 one without a time to live
+        if pttl == -2 {
+            let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+            return Err(DataVaultError::NotFound)
+        }
+
+        // a record expiring before the retention period is over keeps its expiry
+        let retention = match pttl {
+            -1 => self.retention,
+            pttl => self.retention.min(Duration::from_millis(pttl as u64)),
+        };
+        let deleted: Option<()> = pipe()
+            .atomic()
+            .rename(&key, &tombstone_key).ignore()
+            .pexpire(&tombstone_key, retention.as_millis() as usize).ignore()
+            .zrem(self.index_key(), token).ignore()
+            .zrem(self.expiring_index_key(), token).ignore()
+            .cmd("ZADD").arg(self.deleted_index_key()).arg(unix_timestamp() + retention.as_secs_f64()).arg(token).ignore()
+            .hdel(self.version_key(), token).ignore()
+            .query_async(&mut *conn)
+            .await?;
+
+        match deleted {
+            Some(()) => Ok(()),
+            None => Err(DataVaultError::Conflict),
+        }
+    }
+
+    /// Set the expiry of a record with `PEXPIRE`, or remove it with
+    /// `PERSIST` for `None`, the record itself is not rewritten
+    /// Arguments:
+    ///     * `token`: the token of the record
+    ///     * `ttl`: the new time to live, counted from now
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use std::time::Duration;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// // the customer paid again, keep the card on file for another year
+    /// data_vault.touch(&token, Some(Duration::from_secs(365 * 24 * 60 * 60))).await.unwrap();
+    /// ```
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        let key = self.key(token)?;
+        let mut conn = self.connection().await?;
+
+        // WATCH makes EXEC fail if the record is deleted meanwhile
+        let _: () = cmd("WATCH").arg(&key).query_async(&mut *conn).await?;
+        let exists: bool = conn.exists(&key).await?;
+
+        if !exists {
+            let _: () = cmd("UNWATCH").query_async(&mut *conn).await?;
+            return Err(DataVaultError::NotFound)
+        }
+
+        let mut touch = pipe();
+        touch.atomic();
+        match ttl {
+            Some(ttl) => touch
+                .pexpire(&key, ttl.as_millis() as usize).ignore()
+                .cmd("ZADD").arg(self.expiring_index_key()).arg(unix_timestamp() + ttl.as_secs_f64()).arg(token).ignore(),
+            None => touch
+                .persist(&key).ignore()
+                .zrem(self.expiring_index_key(), token).ignore(),
+        };
+        let touched: Option<()> = touch.query_async(&mut *conn).await?;
+
+        match touched {
+            Some(()) => Ok(()),
+            None => Err(DataVaultError::Conflict),
+        }
+    }
+
+    /// Delete the records and tombstones of `tokens` with `DEL`
+    /// and clear them from every index
+    /// returns:
+    ///     * how many tokens had a record, tombstones are not counted
+    ///     * `DataVaultError::InvalidToken` when a token contains `:`,
+    ///       nothing is deleted then
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let deleted = data_vault.delete_many(&leaked_tokens).await.unwrap();
+    /// ```
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let deleted = self.delete_records(tokens).await?;
+        for kind in TOKEN_SIDE_KINDS.iter().chain(TRANSIENT_SIDE_KINDS.iter()) {
+            self.with_side_namespace(kind)?.delete_records(tokens).await?;
+        }
+        Ok(deleted)
+    }
+
+    /// Remove tombstones whose retention period is over and clear
+    /// records whose time to live is over from the index
+    ///
+    /// Redis expires tombstones and records by itself, this verifies
+    /// that it did, deletes any tombstones that are left and clears
+    /// them from the `data_vault:deleted` and `data_vault:expiring`
+    /// indexes.
+    /// returns:
+    ///     * how many tombstones and records were due and how many
+    ///       redis had already expired
+    ///     * `DataVaultError::Conflict` when a due token was deleted
+    ///       again during the purge, run it again
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.purge_expired().await.unwrap();
+    /// println!("purged {} cards", report.purged);
+    /// ```
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        let now = unix_timestamp();
+        let mut report = self.purge_tombstones(now).await?;
+        self.purge_expiring(now, &mut report).await?;
+        Ok(report)
+    }
+
+    /// Every record of the namespace as `(token, ciphertext)`
+    ///
+    /// The index is walked with `ZSCAN`, a record that is stored
+    /// while the stream runs may or may not be included and may be
+    /// returned twice.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use futures::TryStreamExt;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let mut records = data_vault.iter_records();
+    /// while let Some((token, ciphertext)) = records.try_next().await.unwrap() {
+    ///     println!("{} {} bytes", token, ciphertext.len());
+    /// }
+    /// ```
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        let batches = stream::try_unfold(Some(0), move |cursor| async move {
+            let cursor = match cursor {
+                Some(cursor) => cursor,
+                None => return Ok::<_, DataVaultError>(None),
+            };
+
+            let (next_cursor, records) = self.scan_records(cursor, SCAN_BATCH_SIZE).await?;
+            let next_cursor = match next_cursor {
+                0 => None,
+                next_cursor => Some(next_cursor),
+            };
+            Ok(Some((stream::iter(records.into_iter().map(Ok)), next_cursor)))
+        });
+        Box::pin(batches.try_flatten())
+    }
+
+    /// Every record of the namespace as `(token, decrypted string)`
+    /// see `iter_records`
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        Box::pin(self.iter_records().and_then(move |(token, ciphertext)| async move {
+            Ok((token, self.open_string(ciphertext.as_slice())?))
+        }))
+    }
+
+    /// About `limit` decrypted records starting at `cursor`
+    ///
+    /// The cursor is the `ZSCAN` cursor of the index, `limit` is
+    /// passed as its `COUNT` hint.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let mut cursor = None;
+    /// loop {
+    ///     let page = data_vault.decrypted_records_page(cursor.as_deref(), 100).await.unwrap();
+    ///     println!("{} records", page.records.len());
+    ///     cursor = match page.next_cursor {
+    ///         Some(next_cursor) => Some(next_cursor),
+    ///         None => break,
+    ///     };
+    /// }
+    /// ```
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        let cursor = match cursor {
+            Some(cursor) => cursor.parse().map_err(|_| DataVaultError::InvalidCursor)?,
+            None => 0,
+        };
+
+        let limit = limit.max(1);
+        let (next_cursor, records) = self.scan_records(cursor, limit).await?;
+        Ok(RecordPage {
+            records: records.into_iter()
+                .map(|(token, ciphertext)| Ok((token, self.open_string(ciphertext.as_slice())?)))
+                .collect::<Result<_, DataVaultError>>()?,
+            next_cursor: match next_cursor {
+                0 => None,
+                next_cursor => Some(next_cursor.to_string()),
+            },
+        })
+    }
+
+    /// Number of records in the vault
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let count = data_vault.count().await.unwrap();
+    /// ```
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        let mut conn = self.connection().await?;
+        Ok(conn.zcard(self.index_key()).await?)
+    }
+
+    /// Record count, approximate size and first/last store times
+    ///
+    /// The size is estimated from `MEMORY USAGE` of a sample of
+    /// records plus the index itself.
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let stats = data_vault.stats().await.unwrap();
+    /// println!("{} cards using ~{} bytes", stats.count, stats.approximate_bytes);
+    /// ```
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        let index_key = self.index_key();
+        let mut conn = self.connection().await?;
+        let (count, oldest, newest, sample): (u64, Vec<(String, f64)>, Vec<(String, f64)>, Vec<String>) = pipe()
+            .zcard(&index_key)
+            .zrange_withscores(&index_key, 0, 0)
+            .zrange_withscores(&index_key, -1, -1)
+            .zrange(&index_key, 0, MEMORY_SAMPLE_SIZE - 1)
+            .query_async(&mut *conn)
+            .await?;
+
+        let mut memory_usage = pipe();
+        memory_usage.cmd("MEMORY").arg("USAGE").arg(&index_key);
+        for token in sample.iter() {
+            memory_usage.cmd("MEMORY").arg("USAGE").arg(self.key(token)?);
+        }
+        let usage: Vec<Option<u64>> = memory_usage.query_async(&mut *conn).await?;
+
+        let index_bytes = usage.first().copied().flatten().unwrap_or_default();
+        let sampled: Vec<u64> = usage.iter().skip(1).filter_map(|bytes| *bytes).collect();
+        let record_bytes = match sampled.len() {
+            0 => 0,
+            n => sampled.iter().sum::<u64>() * count / n as u64,
+        };
+
+        Ok(VaultStats {
+            count,
+            approximate_bytes: index_bytes + record_bytes,
+            oldest: oldest.first().map(|(_, score)| from_unix_timestamp(*score)),
+            newest: newest.first().map(|(_, score)| from_unix_timestamp(*score)),
+        })
+    }
+
+    /// `PING` on a connection from the pool and a probe encrypted
+    /// and decrypted with the vault's keys
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let report = data_vault.health_check().await;
+    /// assert!(report.is_healthy(), "{:?}", report);
+    /// ```
+    async fn health_check(&self) -> HealthReport {
+        let checked_at = SystemTime::now();
+        let connection = CheckResult::of(async {
+            let pool = self.pool.load();
+            let mut conn = pool.get().await?;
+            cmd("PING").query_async::<_, String>(&mut *conn).await?;
+            Ok(())
+        }).await;
+
+        HealthReport {
+            backend: self.backend(),
+            connection,
+            encryption: CheckResult::roundtrip(|probe| self.open(&self.seal(probe))),
+            checked_at,
+        }
+    }
+
+    fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    fn backend(&self) -> &'static str {
+        "redis"
+    }
+
+    /// Scope the vault to `namespace`, the pool is shared
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let merchant_vault = data_vault.with_namespace("merchant-42").unwrap();
+    /// let token = merchant_vault.store_credit_card(&cc).await.unwrap();
+    /// assert!(data_vault.try_retrieve(&token).await.unwrap().is_none());
+    /// ```
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        validate_namespace(namespace)?;
+
+        Ok(RedisDataVault {
+            pool: self.pool.clone(),
+            timeouts: self.timeouts.clone(),
+            latency: self.latency.clone(),
+            encryption: self.encryption.clone(),
+            key: self.key.clone(),
+            tokenizer: self.tokenizer.clone(),
+            serializer: self.serializer.clone(),
+            namespace: namespace.to_string(),
+            key_prefix: self.key_prefix.clone(),
+            retention: self.retention,
+            slow_op: self.slow_op,
+            cvv_policy: self.cvv_policy,
+            validate_cards: self.validate_cards,
+            reject_expired: self.reject_expired,
+            retry: self.retry,
+            compression: self.compression,
+            env_prefix: self.env_prefix.clone(),
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        let mut side = self.with_namespace(DEFAULT_NAMESPACE)?;
+        side.namespace = side_namespace(&self.namespace, kind);
+        Ok(side)
+    }
+}
+
+#[cfg(all(test, feature = "rt-tokio"))]
+mod test {
+    use credit_card::CreditCard;
+    use deadpool_redis::redis::{AsyncCommands, pipe};
+    use crate::traits::DataVault;
+    use crate::redis_data_vault::{unix_timestamp, RedisDataVault, TokenScan};
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::tokenizer::Blake3Tokenizer;
+    use crate::verify::RecordProblem;
+    use crate::admin::AgeHistogram;
+    use crate::compliance::TokenKind;
+    use crate::integrity::IntegrityProblem;
+    use crate::utils::Salt;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn purge_expired_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let vault = RedisDataVault {
+            retention: Duration::from_secs(0),
+            ..vault.with_namespace("purge-test").unwrap()
+        };
+
+        let token = String::from("abc123");
+        vault.store(&token, "{number: 123}").await.unwrap();
+        vault.soft_delete(&token).await.unwrap();
+
+        let report = vault.purge_expired().await.unwrap();
+        assert_eq!(report.purged, 1);
+        assert_eq!(report.expired_by_backend, 1);
+        assert_eq!(vault.purge_expired().await.unwrap().purged, 0)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn verify_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let vault = vault.with_namespace("verify-test").unwrap();
+
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let token = vault.store_credit_card(&cc).await.unwrap();
+        vault.store("verify-text", "{number: 123}").await.unwrap();
+        assert!(vault.verify().await.unwrap().is_ok());
+
+        let now = unix_timestamp();
+        let mut conn = vault.connection().await.unwrap();
+        let _: () = pipe()
+            .set(vault.key("verify-corrupt").unwrap(), &b"not a ciphertext"[..]).ignore()
+            .zadd(vault.index_key(), "verify-corrupt", now).ignore()
+            .zadd(vault.index_key(), "verify-orphan", now).ignore()
+            .zadd(vault.index_key(), "verify-expired", now).ignore()
+            .zadd(vault.expiring_index_key(), "verify-expired", now - 1.0).ignore()
+            .zadd(vault.index_key(), "verify:invalid", now).ignore()
+            .query_async(&mut *conn)
+            .await
+            .unwrap();
+
+        let report = vault.verify().await.unwrap();
+        assert_eq!(report.checked, 6);
+        let mut issues: Vec<(&str, RecordProblem)> = report.issues.iter().map(|issue| (issue.token.as_str(), issue.problem)).collect();
+        issues.sort_by_key(|(token, _)| *token);
+        assert_eq!(issues, vec![
+            ("verify-corrupt", RecordProblem::Undecryptable),
+            ("verify-orphan", RecordProblem::Orphaned),
+            ("verify:invalid", RecordProblem::InvalidToken),
+        ]);
+
+        let _: () = pipe()
+            .zrem(vault.index_key(), &["verify-orphan", "verify-expired", "verify:invalid"]).ignore()
+            .zrem(vault.expiring_index_key(), "verify-expired").ignore()
+            .query_async(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        vault.delete_many(&[token, "verify-text".to_string(), "verify-corrupt".to_string()]).await.unwrap();
+        assert!(vault.verify().await.unwrap().is_ok())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn admin_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let key = vault.key_info();
+        assert_eq!((key.key_id.len(), key.algorithm), (16, "AES-256-GCM-SIV"));
+        vault.reload().unwrap();
+        assert_eq!(vault.key_info(), key);
+        let report = vault.compliance_report();
+        assert_eq!((report.backend, report.encryption.key_id), ("redis", key.key_id));
+        assert_eq!(report.tokens, TokenKind::Salted);
+
+        let namespace = format!("admin-{}", Salt::generate(16));
+        let vault = vault.with_namespace(&namespace).unwrap();
+        vault.store("admin-new", "{}").await.unwrap();
+        vault.store("admin-old", "{}").await.unwrap();
+        vault.store("admin-deleted", "{}").await.unwrap();
+        vault.soft_delete("admin-deleted").await.unwrap();
+
+        let forty_days = 40.0 * 86400.0;
+        let mut conn = vault.connection().await.unwrap();
+        let _: () = pipe()
+            .zadd(vault.index_key(), "admin-old", unix_timestamp() - forty_days)
+            .query_async(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let histogram = vault.record_histogram_by_age(&AgeHistogram::DEFAULT_BOUNDS).await.unwrap();
+        let counts: Vec<u64> = histogram.buckets.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, vec![1, 0, 1, 0, 0]);
+        assert_eq!(vault.record_histogram_by_age(&[]).await.unwrap().total(), 2);
+
+        let tenants = vault.tenant_summary().await.unwrap();
+        assert!(tenants.windows(2).all(|pair| pair[0].namespace < pair[1].namespace));
+        let tenant = tenants.iter().find(|tenant| tenant.namespace == namespace).unwrap();
+        assert_eq!((tenant.count, tenant.soft_deleted), (2, 1));
+        assert!(tenant.oldest < tenant.newest);
+
+        let tokens = ["admin-new", "admin-old", "admin-deleted"].iter().map(|token| token.to_string()).collect::<Vec<String>>();
+        vault.delete_many(&tokens).await.unwrap();
+        assert!(vault.tenant_summary().await.unwrap().iter().all(|tenant| tenant.namespace != namespace))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn integrity_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let vault = vault.with_namespace(&format!("integrity-{}", Salt::generate(16))).unwrap();
+        vault.store("integrity-ok", "{number: 123}").await.unwrap();
+        assert!(vault.integrity_report().await.unwrap().is_ok());
+
+        let now = unix_timestamp();
+        let mut conn = vault.connection().await.unwrap();
+        let _: () = pipe()
+            .set(vault.key("integrity-legacy").unwrap(), &b"not a ciphertext"[..]).ignore()
+            .zadd(vault.index_key(), "integrity-gone", now).ignore()
+            .zadd(vault.expiring_index_key(), "integrity-stray", now + 100.0).ignore()
+            .zadd(vault.deleted_index_key(), "integrity-purged", now + 100.0).ignore()
+            .cmd("SET").arg(vault.tombstone_key("integrity-buried").unwrap()).arg("not a ciphertext").arg("PX").arg(100_000).ignore()
+            .hset(vault.version_key(), "integrity-old", 3).ignore()
+            .query_async(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let report = vault.integrity_report().await.unwrap();
+        assert_eq!(report.checked, 9);
+        let mut issues: Vec<(&str, &str, IntegrityProblem)> = report.issues.iter().map(|issue| (issue.token.as_str(), issue.index, issue.problem)).collect();
+        issues.sort_by_key(|(token, _, _)| *token);
+        assert_eq!(issues, vec![
+            ("integrity-buried", "data_vault:deleted", IntegrityProblem::Unindexed),
+            ("integrity-gone", "data_vault:index", IntegrityProblem::Dangling),
+            ("integrity-legacy", "data_vault:index", IntegrityProblem::Unindexed),
+            ("integrity-old", "data_vault:version", IntegrityProblem::Dangling),
+            ("integrity-purged", "data_vault:deleted", IntegrityProblem::Dangling),
+            ("integrity-stray", "data_vault:expiring", IntegrityProblem::Dangling),
+        ]);
+
+        assert_eq!(vault.repair_integrity(&report).await.unwrap(), 6);
+        assert!(vault.integrity_report().await.unwrap().is_ok());
+        assert_eq!(vault.count().await.unwrap(), 2);
+        assert_eq!(vault.repair_integrity(&report).await.unwrap(), 0);
+
+        let tokens = ["integrity-ok", "integrity-legacy", "integrity-buried"].iter().map(|token| token.to_string()).collect::<Vec<String>>();
+        vault.delete_many(&tokens).await.unwrap();
+        assert_eq!(vault.integrity_report().await.unwrap().checked, 0)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scan_tokens_redis() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let vault = vault.with_namespace(&format!("scan-{}", Salt::generate(16))).unwrap();
+        vault.store("scan-record", "{number: 123}").await.unwrap();
+        // a key of another type under the record key prefix
+        let mut conn = vault.connection().await.unwrap();
+        let _: () = conn.hset(vault.key("scan-hash").unwrap(), "field", "value").await.unwrap();
+        drop(conn);
+
+        // without SCAN TYPE, which Redis 5 does not know
+        let scan = TokenScan::Keys(vault.key("").unwrap());
+        let mut tokens = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next_cursor, batch) = vault.scan_tokens(&scan, cursor).await.unwrap();
+            tokens.extend(batch);
+            cursor = next_cursor;
+            if cursor == 0 {
+                break
+            }
+        }
+        assert_eq!(tokens, vec!["scan-record".to_string()]);
+        assert!(vault.integrity_report().await.unwrap().is_ok());
+
+        let mut conn = vault.connection().await.unwrap();
+        let _: () = conn.del(vault.key("scan-hash").unwrap()).await.unwrap();
+        drop(conn);
+        vault.delete_many(&["scan-record".to_string()]).await.unwrap();
     }
 }
\ No newline at end of file
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use deadpool::managed::{self, RecycleError, RecycleResult};
+use deadpool_redis::redis::{self, aio::Connection, Client, ConnectionInfo, IntoConnectionInfo, RedisError};
+use crate::config::{pool_runtime, RedisVaultConfig};
+use crate::recycle::RecycleMethod;
+use std::error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "redis-tls")]
+use crate::config::RedisTlsConfig;
+#[cfg(feature = "redis-tls")]
+use deadpool_redis::redis::ConnectionAddr;
+
+pub(crate) type RedisPool = managed::Pool<RedisManager>;
+
+/// Creates the connections of `RedisDataVault`, the same as
+/// `deadpool_redis::Manager` but for `rediss://` addresses, which
+/// are connected with the vault's `RedisTlsConfig`
+pub(crate) struct RedisManager {
+    client: Client,
+    ping_number: AtomicUsize,
+    recycle: RecycleMethod,
+    #[cfg(feature = "redis-tls")]
+    tls: Option<tokio_native_tls::TlsConnector>,
+}
+
+impl RedisManager {
+    fn new(info: ConnectionInfo, cfg: &RedisVaultConfig) -> Result<Self, Box<dyn error::Error>> {
+        #[cfg(feature = "redis-tls")]
+        let tls = match &*info.addr {
+            ConnectionAddr::TcpTls { insecure, .. } => Some(tls_connector(&cfg.tls, *insecure)?.into()),
+            _ => None,
+        };
+
+        Ok(RedisManager {
+            client: Client::open(info)?,
+            ping_number: AtomicUsize::new(0),
+            recycle: cfg.recycle,
+            #[cfg(feature = "redis-tls")]
+            tls,
+        })
+    }
+}
+
+#[async_trait]
+impl managed::Manager for RedisManager {
+    type Type = Connection;
+    type Error = RedisError;
+
+    async fn create(&self) -> Result<Connection, RedisError> {
+        #[cfg(feature = "redis-tls")]
+        if let Some(connector) = &self.tls {
+            return connect_tls(connector, self.client.get_connection_info()).await
+        }
+        self.client.get_async_connection().await
+    }
+
+    async fn recycle(&self, conn: &mut Connection) -> RecycleResult<RedisError> {
+        if self.recycle == RecycleMethod::Fast {
+            return Ok(())
+        }
+
+        let ping_number = self.ping_number.fetch_add(1, Ordering::Relaxed).to_string();
+        let pong: String = redis::cmd("PING").arg(&ping_number).query_async(conn).await?;
+        match pong == ping_number {
+            true => Ok(()),
+            false => Err(RecycleError::Message("invalid PING response".to_string())),
+        }
+    }
+}
+
+/// The pool of the redis `cfg.redis` configures
+pub(crate) fn create_pool(cfg: &RedisVaultConfig) -> Result<RedisPool, Box<dyn error::Error>> {
+    let info = match (&cfg.redis.url, &cfg.redis.connection) {
+        (Some(url), None) => url.as_str().into_connection_info()?,
+        (None, Some(connection)) => connection.clone().into_connection_info()?,
+        (None, None) => "redis://127.0.0.1/".into_connection_info()?,
+        (Some(_), Some(_)) => return Err("url and connection must not be specified at the same time".into()),
+    };
+
+    let mut pool_cfg = cfg.redis.get_pool_config();
+    pool_cfg.runtime = pool_runtime();
+    Ok(RedisPool::from_config(RedisManager::new(info, cfg)?, pool_cfg))
+}
+
+/// A connector trusting the CA bundle and presenting the client
+/// certificate of `tls`, `insecure` is the `#insecure` of the url
+#[cfg(feature = "redis-tls")]
+fn tls_connector(tls: &RedisTlsConfig, insecure: bool) -> Result<native_tls::TlsConnector, Box<dyn error::Error>> {
+    let mut builder = native_tls::TlsConnector::builder();
+    if let Some(ca_file) = &tls.ca_file {
+        for certificate in native_tls::Certificate::stack_from_pem(&std::fs::read(ca_file)?)? {
+            builder.add_root_certificate(certificate);
+        }
+    }
+    match (&tls.cert_file, &tls.key_file) {
+        (Some(cert_file), Some(key_file)) => {
+            builder.identity(native_tls::Identity::from_pkcs8(&std::fs::read(cert_file)?, &std::fs::read(key_file)?)?);
+        },
+        (None, None) => {},
+        _ => return Err("a client certificate needs both a certificate and a key file".into()),
+    }
+    builder.danger_accept_invalid_hostnames(insecure || !tls.verify_hostname);
+    builder.danger_accept_invalid_certs(insecure);
+    Ok(builder.build()?)
+}
+
+#[cfg(feature = "redis-tls")]
+async fn connect_tls(connector: &tokio_native_tls::TlsConnector, info: &ConnectionInfo) -> Result<Connection, RedisError> {
+    let (host, port) = match &*info.addr {
+        ConnectionAddr::TcpTls { host, port, .. } => (host.as_str(), *port),
+        _ => unreachable!("only rediss:// addresses have a connector"),
+    };
+
+    let tcp = tokio::net::TcpStream::connect((host, port)).await?;
+    let stream = connector.connect(host, tcp).await?;
+    let stream: std::pin::Pin<Box<dyn redis::aio::AsyncStream + Send + Sync>> = Box::pin(stream);
+    Connection::new(info, stream).await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{EncryptionSettings, RedisVaultConfig};
+    use crate::redis_pool::create_pool;
+    #[cfg(feature = "redis-tls")]
+    use crate::config::RedisTlsConfig;
+    #[cfg(feature = "redis-tls")]
+    use crate::redis_pool::tls_connector;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_url() {
+        use deadpool_redis::redis::ConnectionAddr;
+
+        for url in &["redis+unix:///run/redis.sock?db=1", "unix:///run/redis.sock"] {
+            let cfg = RedisVaultConfig::new(url, EncryptionSettings::default());
+            let pool = create_pool(&cfg).unwrap();
+            let info = pool.manager().client.get_connection_info();
+            assert_eq!(*info.addr, ConnectionAddr::Unix("/run/redis.sock".into()));
+        }
+    }
+
+    #[cfg(feature = "redis-tls")]
+    #[test]
+    fn test_tls_connector() {
+        assert!(tls_connector(&RedisTlsConfig::default(), false).is_ok());
+
+        let missing = RedisTlsConfig {
+            ca_file: Some("/nonexistent/ca.pem".into()),
+            ..RedisTlsConfig::default()
+        };
+        assert!(tls_connector(&missing, false).is_err());
+
+        let key_only = RedisTlsConfig {
+            key_file: Some("/nonexistent/client.key".into()),
+            ..RedisTlsConfig::default()
+        };
+        assert!(tls_connector(&key_only, false).is_err());
+
+        // TLS settings only apply to rediss:// urls
+        let cfg = RedisVaultConfig::new("redis://127.0.0.1/", EncryptionSettings::default())
+            .with_tls(missing.clone());
+        assert!(create_pool(&cfg).is_ok());
+        let cfg = RedisVaultConfig::new("rediss://127.0.0.1/", EncryptionSettings::default())
+            .with_tls(missing);
+        assert!(create_pool(&cfg).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// A value a vault replaces on `reload`, shared by the vault and
+/// every namespace made from it, so all of them see the new value
+///
+/// `load` hands out the current value, operations holding it keep
+/// it until they are done even if it is replaced meanwhile.
+pub(crate) struct Reloadable<T>(Arc<RwLock<Arc<T>>>);
+
+impl<T> Reloadable<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Reloadable(Arc::new(RwLock::new(Arc::new(value))))
+    }
+
+    /// the current value
+    pub(crate) fn load(&self) -> Arc<T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// replace the value for every holder of this `Reloadable`
+    pub(crate) fn store(&self, value: T) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(value);
+    }
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Reloadable(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reload::Reloadable;
+
+    #[test]
+    fn test_reloadable() {
+        let value = Reloadable::new(1);
+        let shared = value.clone();
+        let before = value.load();
+        shared.store(2);
+        assert_eq!(*value.load(), 2);
+        assert_eq!(*before, 1);
+    }
+}
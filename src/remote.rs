@@ -0,0 +1,413 @@
+//! A vault calling the REST service of `server` over HTTP, for
+//! runtimes that can not reach redis or postgres, such as Cloudflare
+//! Workers or Fastly Compute
+//!
+//! `RemoteDataVault` builds for `wasm32-unknown-unknown` without the
+//! `redis` and `postgres` features, requests go out through the
+//! runtime's `fetch`.  On other targets it uses rustls.
+//!
+//! The service tokenizes, detokenizes, deletes and checks tokens,
+//! the other operations of `DataVault` fail with
+//! `DataVaultError::Unsupported`.  Errors of the service come back as
+//! the `DataVaultError` it answered with where the status tells it,
+//! `DataVaultError::RemoteStatus` otherwise.
+//!
+//! # Examples
+//! ```rust,ignore
+//! use data_vault::{DataVault, RemoteVaultConfig};
+//! use data_vault::remote::RemoteDataVault;
+//!
+//! // e.g. from the bindings of a worker
+//! let vault = RemoteDataVault::from_config(RemoteVaultConfig {
+//!     url: "https://vault.internal:8443".to_string(),
+//!     api_key: env.secret("VAULT_API_KEY")?.to_string(),
+//!     ..RemoteVaultConfig::default()
+//! })?;
+//! let token = vault.store_credit_card(&credit_card).await?;
+//! ```
+
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use futures::stream;
+use reqwest::{header, Client, Method, StatusCode};
+use serde::Deserialize;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::validation::ValidationError;
+use crate::stats::VaultStats;
+use crate::health::{CheckResult, HealthReport};
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::config::RemoteVaultConfig;
+use crate::namespace::{validate_namespace, validate_token, DEFAULT_NAMESPACE};
+use std::error;
+use std::time::{Duration, SystemTime};
+
+#[derive(Deserialize)]
+struct Tokenized {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// A vault whose records are kept by a vault service, see `server`
+#[derive(Clone)]
+pub struct RemoteDataVault {
+    client: Client,
+    url: String,
+    api_key: String,
+    timeout: Duration,
+}
+
+impl RemoteDataVault {
+    /// A vault calling the service at `cfg.url`
+    pub fn from_config(cfg: RemoteVaultConfig) -> Result<Self, Box<dyn error::Error>> {
+        if !cfg.url.starts_with("https://") && !cfg.url.starts_with("http://") {
+            return Err("the URL of the vault service must start with https:// or http://".into())
+        }
+        if cfg.api_key.is_empty() {
+            return Err("an API key of the vault service is required".into())
+        }
+        Ok(RemoteDataVault {
+            client: Client::new(),
+            url: cfg.url.trim_end_matches('/').to_string(),
+            api_key: cfg.api_key,
+            timeout: Duration::from_millis(cfg.timeout_ms),
+        })
+    }
+
+    /// One request to the service, its status and body
+    async fn send(&self, method: Method, path: &str, body: Option<Vec<u8>>) -> Result<(StatusCode, Vec<u8>), DataVaultError> {
+        let url = format!("{}{}", self.url, path);
+        let exchange = async move {
+            let mut request = self.client.request(method, url)
+                .bearer_auth(&self.api_key)
+                .timeout(self.timeout);
+            if let Some(body) = body {
+                request = request.header(header::CONTENT_TYPE, "application/json").body(body);
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            Ok::<_, reqwest::Error>((status, response.bytes().await?.to_vec()))
+        };
+        // `fetch` futures are not `Send`, wasm32 runs them on its only thread
+        #[cfg(target_arch = "wasm32")]
+        let exchange = send_wrapper::SendWrapper::new(exchange);
+        Ok(exchange.await?)
+    }
+
+    /// The path of a token, which must be one the service could have
+    /// handed out
+    fn token_path(token: &str) -> Result<String, DataVaultError> {
+        validate_token(token)?;
+        Ok(format!("/tokens/{}", token))
+    }
+}
+
+/// The `DataVaultError` of an error answer of the service, whose
+/// message is redacted already
+fn status_error(status: StatusCode, body: &[u8]) -> DataVaultError {
+    let message = serde_json::from_slice::<ErrorBody>(body)
+        .map(|body| body.error)
+        .unwrap_or_default();
+    match status {
+        StatusCode::NOT_FOUND => DataVaultError::NotFound,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => DataVaultError::AccessDenied,
+        StatusCode::CONFLICT => DataVaultError::Conflict,
+        StatusCode::UNPROCESSABLE_ENTITY => match message.strip_prefix("invalid card: ") {
+            Some(reason) => DataVaultError::Validation(validation_error(reason)),
+            None => DataVaultError::SecurityCodeNotAllowed,
+        },
+        StatusCode::TOO_MANY_REQUESTS => {
+            // "rate limited, retry after 3 seconds"
+            let seconds = message.split_whitespace().find_map(|word| word.parse().ok()).unwrap_or(1);
+            DataVaultError::RateLimited(Duration::from_secs(seconds))
+        },
+        status => DataVaultError::RemoteStatus(status.as_u16(), message),
+    }
+}
+
+/// The `ValidationError` displayed as `reason`
+fn validation_error(reason: &str) -> ValidationError {
+    let digits = |reason: &str| reason.split_whitespace().nth(2).and_then(|length| length.parse().ok()).unwrap_or_default();
+    match reason {
+        "number is not numeric" => ValidationError::NotNumeric,
+        "number fails the Luhn check" => ValidationError::LuhnCheckFailed,
+        "expiration_month is not 1 to 12" => ValidationError::InvalidExpirationMonth,
+        "expiration_year is not 2 or 4 digits" => ValidationError::InvalidExpirationYear,
+        "card is expired" => ValidationError::Expired,
+        "routing_number fails the ABA checksum" => ValidationError::InvalidRoutingNumber,
+        "account_number is not 4 to 17 digits" => ValidationError::InvalidAccountNumber,
+        "metadata is over 32 entries, 64 character keys or 512 character values" => ValidationError::InvalidMetadata,
+        "security_code is not 3 or 4 digits" => ValidationError::InvalidSecurityCode,
+        "security code ttl is zero or over 15 minutes" => ValidationError::InvalidCvvTtl,
+        "data is not track 2" => ValidationError::InvalidTrackData,
+        "tlv is not hex encoded BER-TLV" => ValidationError::InvalidEmvPayload,
+        "card present data ttl is zero or over 5 minutes" => ValidationError::InvalidCardPresentTtl,
+        "nickname or art_id is over 64 characters" => ValidationError::InvalidDisplayInfo,
+        "tokens are not of the same card number" => ValidationError::NotSameCard,
+        // "number has 11 digits, not a length of its brand"
+        reason if reason.ends_with("its brand") => ValidationError::InvalidLengthForBrand(digits(reason)),
+        // "number has 11 digits, not 12 to 19"
+        reason => ValidationError::InvalidLength(digits(reason)),
+    }
+}
+
+// `SystemTime::now` panics on wasm32-unknown-unknown
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
+
+#[async_trait]
+impl DataVault for RemoteDataVault {
+    /// A vault calling the service named by the environment, see
+    /// `RemoteVaultConfig::from_env`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        RemoteDataVault::from_config(RemoteVaultConfig::from_env()?)
+    }
+
+    async fn store(&self, _token: &str, _string: &str) -> Result<(), DataVaultError> {
+        Err(DataVaultError::Unsupported("store"))
+    }
+
+    async fn store_if_absent(&self, _token: &str, _string: &str) -> Result<bool, DataVaultError> {
+        Err(DataVaultError::Unsupported("store_if_absent"))
+    }
+
+    async fn store_expiring(&self, _token: &str, _string: &str, _ttl: Duration) -> Result<(), DataVaultError> {
+        Err(DataVaultError::Unsupported("store_expiring"))
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        Ok(self.tokenize(credit_card).await?.0)
+    }
+
+    /// The service tokenizes the card with its own tokenizer
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        let body = serde_json::to_vec(credit_card)?;
+        match self.send(Method::POST, "/tokens", Some(body)).await? {
+            (status, body) if status.is_success() => {
+                let tokenized: Tokenized = serde_json::from_slice(&body)?;
+                Ok((tokenized.token, status == StatusCode::CREATED))
+            },
+            (status, body) => Err(status_error(status, &body)),
+        }
+    }
+
+    async fn store_credit_card_with_token(&self, _token: &str, _credit_card: &CreditCard, _overwrite: bool) -> Result<(), DataVaultError> {
+        Err(DataVaultError::Unsupported("store_credit_card_with_token"))
+    }
+
+    async fn retrieve(&self, _token: &str) -> Result<String, DataVaultError> {
+        Err(DataVaultError::Unsupported("retrieve"))
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        match self.send(Method::GET, &Self::token_path(token)?, None).await? {
+            (StatusCode::OK, body) => Ok(serde_json::from_slice(&body)?),
+            (status, body) => Err(status_error(status, &body)),
+        }
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        match self.send(Method::HEAD, &Self::token_path(token)?, None).await? {
+            (StatusCode::OK, _) => Ok(true),
+            (StatusCode::NOT_FOUND, _) => Ok(false),
+            (status, body) => Err(status_error(status, &body)),
+        }
+    }
+
+    async fn retrieve_credit_card_with_version(&self, _token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        Err(DataVaultError::Unsupported("retrieve_credit_card_with_version"))
+    }
+
+    async fn retrieve_with_metadata(&self, _token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        Err(DataVaultError::Unsupported("retrieve_with_metadata"))
+    }
+
+    async fn update_credit_card_if_version(&self, _token: &str, _credit_card: &CreditCard, _expected_version: u64) -> Result<u64, DataVaultError> {
+        Err(DataVaultError::Unsupported("update_credit_card_if_version"))
+    }
+
+    async fn rotate_token(&self, _token: &str) -> Result<String, DataVaultError> {
+        Err(DataVaultError::Unsupported("rotate_token"))
+    }
+
+    async fn soft_delete(&self, _token: &str) -> Result<(), DataVaultError> {
+        Err(DataVaultError::Unsupported("soft_delete"))
+    }
+
+    async fn touch(&self, _token: &str, _ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        Err(DataVaultError::Unsupported("touch"))
+    }
+
+    /// One request per token
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        let mut deleted = 0;
+        for token in tokens {
+            match self.send(Method::DELETE, &Self::token_path(token)?, None).await? {
+                (StatusCode::NO_CONTENT, _) => deleted += 1,
+                (StatusCode::NOT_FOUND, _) => {},
+                (status, body) => return Err(status_error(status, &body)),
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        Err(DataVaultError::Unsupported("purge_expired"))
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        Box::pin(stream::once(async { Err(DataVaultError::Unsupported("iter_records")) }))
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        Box::pin(stream::once(async { Err(DataVaultError::Unsupported("iter_decrypted_records")) }))
+    }
+
+    async fn decrypted_records_page(&self, _cursor: Option<&str>, _limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        Err(DataVaultError::Unsupported("decrypted_records_page"))
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        Err(DataVaultError::Unsupported("count"))
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        Err(DataVaultError::Unsupported("stats"))
+    }
+
+    /// The connection is healthy when the service answers, the
+    /// encryption when the service reports its own checks passed
+    async fn health_check(&self) -> HealthReport {
+        let checked_at = now();
+        let answer = self.send(Method::GET, "/health", None).await;
+        let latency = now().duration_since(checked_at).unwrap_or_default();
+        let (connection, encryption) = match answer {
+            Ok((status, _)) => (None, match status {
+                StatusCode::OK => None,
+                status => Some(format!("vault service answered {}", status.as_u16())),
+            }),
+            Err(e) => (Some(e.to_string()), Some("vault service did not answer".to_string())),
+        };
+        HealthReport {
+            backend: self.backend(),
+            connection: CheckResult { healthy: connection.is_none(), latency, error: connection },
+            encryption: CheckResult { healthy: encryption.is_none(), latency, error: encryption },
+            checked_at,
+        }
+    }
+
+    /// The service's vault is scoped to its own namespace, which
+    /// this vault does not know
+    fn namespace(&self) -> &str {
+        DEFAULT_NAMESPACE
+    }
+
+    fn backend(&self) -> &'static str {
+        "remote"
+    }
+
+    /// The service serves one namespace, only the default one can
+    /// be asked for
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        validate_namespace(namespace)?;
+        match namespace {
+            DEFAULT_NAMESPACE => Ok(self.clone()),
+            _ => Err(DataVaultError::Unsupported("with_namespace")),
+        }
+    }
+
+    /// Data kept apart from the records, e.g. custom metadata, is not
+    /// served
+    fn with_side_namespace(&self, _kind: &str) -> Result<Self, DataVaultError> {
+        Err(DataVaultError::Unsupported("with_side_namespace"))
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod test {
+    use credit_card::CreditCard;
+    use crate::traits::DataVault;
+    use crate::error::DataVaultError;
+    use crate::config::RemoteVaultConfig;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::remote::RemoteDataVault;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+
+    async fn serve() -> String {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+        let app = crate::server::router(vault, &["remote-key"]);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    fn remote(url: &str, api_key: &str) -> RemoteDataVault {
+        RemoteDataVault::from_config(RemoteVaultConfig {
+            url: url.to_string(),
+            api_key: api_key.to_string(),
+            ..RemoteVaultConfig::default()
+        }).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remote_vault() {
+        let url = serve().await;
+        let vault = remote(&url, "remote-key");
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+
+        let token = vault.store_credit_card(&credit_card).await.unwrap();
+        assert_eq!(vault.tokenize(&credit_card).await.unwrap(), (token.clone(), false));
+        assert_eq!(vault.retrieve_credit_card(&token).await.unwrap().number, credit_card.number);
+        assert!(vault.exists(&token).await.unwrap());
+        assert!(vault.health_check().await.is_healthy());
+        assert!(matches!(vault.count().await, Err(DataVaultError::Unsupported("count"))));
+        assert!(matches!(vault.retrieve_credit_card("../health").await, Err(DataVaultError::InvalidToken)));
+        assert!(matches!(remote(&url, "other-key").retrieve_credit_card(&token).await, Err(DataVaultError::AccessDenied)));
+
+        assert_eq!(vault.delete_many(&[token.clone(), token.clone()]).await.unwrap(), 1);
+        assert!(!vault.exists(&token).await.unwrap());
+        assert!(matches!(vault.retrieve_credit_card(&token).await, Err(DataVaultError::NotFound)));
+    }
+
+    #[test]
+    fn test_validation_status() {
+        use crate::remote::status_error;
+        use crate::validation::ValidationError;
+        use reqwest::StatusCode;
+
+        for e in [ValidationError::NotNumeric, ValidationError::InvalidLength(11), ValidationError::InvalidLengthForBrand(16),
+                  ValidationError::LuhnCheckFailed,
+                  ValidationError::InvalidExpirationMonth, ValidationError::InvalidExpirationYear, ValidationError::Expired,
+                  ValidationError::InvalidRoutingNumber, ValidationError::InvalidAccountNumber, ValidationError::InvalidMetadata,
+                  ValidationError::InvalidSecurityCode, ValidationError::InvalidCvvTtl, ValidationError::InvalidTrackData,
+                  ValidationError::InvalidEmvPayload, ValidationError::InvalidCardPresentTtl, ValidationError::InvalidDisplayInfo,
+                  ValidationError::NotSameCard] {
+            let body = serde_json::json!({ "error": DataVaultError::Validation(e).to_string() }).to_string();
+            assert!(matches!(status_error(StatusCode::UNPROCESSABLE_ENTITY, body.as_bytes()), DataVaultError::Validation(found) if found == e));
+        }
+        let body = serde_json::json!({ "error": DataVaultError::SecurityCodeNotAllowed.to_string() }).to_string();
+        assert!(matches!(status_error(StatusCode::UNPROCESSABLE_ENTITY, body.as_bytes()), DataVaultError::SecurityCodeNotAllowed));
+    }
+}
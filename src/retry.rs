@@ -0,0 +1,339 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use rand::Rng;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use std::error;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often and how patiently an operation failing with a
+/// transient error is attempted, see `DataVaultError::is_transient`
+///
+/// The n-th retry waits `base_delay * 2^(n-1)`, at most `max_delay`.
+/// With `jitter` every wait is a random time up to that, so clients
+/// do not all retry at once after a failover.  The back ends use it
+/// to take connections from their pools, `retrying` also retries
+/// the operations themselves.  Without `rt-tokio` or `rt-async-std`
+/// retries are not delayed.
+///
+/// Configured with `DATA_VAULT_RETRY_MAX_ATTEMPTS`,
+/// `DATA_VAULT_RETRY_BASE_DELAY_MS`, `DATA_VAULT_RETRY_MAX_DELAY_MS`
+/// and `DATA_VAULT_RETRY_JITTER`, by default nothing is retried.
+/// # example
+/// ```rust
+/// use data_vault::{DataVault, RedisDataVault, RetryPolicy};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(4).with_base_delay(Duration::from_millis(100));
+/// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+///     .with_retry(policy);
+/// let vault = policy.retrying(vault);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// attempts in total, 1 does not retry
+    pub max_attempts: u32,
+    /// the wait before the first retry
+    pub base_delay: Duration,
+    /// the longest wait between two attempts
+    pub max_delay: Duration,
+    /// wait a random time up to the delay
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Make up to `max_attempts` attempts with the default delays
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// see `RetryConfig::from_env`
+    pub fn from_env() -> Result<Self, ::config::ConfigError> {
+        Ok(crate::config::RetryConfig::from_env(None)?.into())
+    }
+
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// `vault` retrying its operations, see `RetryingVault`
+    pub fn retrying<V: DataVault>(self, vault: V) -> RetryingVault<V> {
+        RetryingVault {
+            inner: vault,
+            policy: self,
+        }
+    }
+
+    /// The longest wait before retry number `retry`, counted from 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.checked_mul(factor).unwrap_or(Duration::MAX).min(self.max_delay)
+    }
+
+    /// `operation` attempted until it succeeds, fails with an error
+    /// `is_transient` rejects or `max_attempts` are made
+    pub(crate) async fn run<R, E, F, Fut>(&self, is_transient: fn(&E) -> bool, mut operation: F) -> Result<R, E>
+        where
+            F: FnMut() -> Fut,
+            Fut: Future<Output = Result<R, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = match self.jitter {
+                        true => self.delay(attempt).mul_f64(rand::thread_rng().gen::<f64>()),
+                        false => self.delay(attempt),
+                    };
+                    sleep(delay).await;
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A vault attempting its operations again after transient errors,
+/// as its `RetryPolicy` allows
+///
+/// Only operations that do the same when they run twice are
+/// retried: reads, `store`, `store_credit_card_with_token` with
+/// `overwrite`, `touch`, `purge_expired`, `count` and `stats`.  The
+/// others may have taken effect before the error, e.g. a token was
+/// rotated but the reply lost, and are attempted once.  Streams are
+/// not retried either.
+pub struct RetryingVault<V> {
+    inner: V,
+    policy: RetryPolicy,
+}
+
+impl<V> RetryingVault<V>
+    where
+        V: DataVault,
+{
+    /// The wrapped vault, operations on it are not retried
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for RetryingVault<V>
+    where
+        V: DataVault,
+{
+    /// A vault from the environment retrying with
+    /// `RetryPolicy::from_env`
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(RetryPolicy::from_env()?.retrying(V::new()?))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.store(token, string)).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.inner.store_if_absent(token, string).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.store_expiring(token, string, ttl)).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.inner.store_credit_card(credit_card).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.inner.tokenize(credit_card).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        match overwrite {
+            true => self.policy.run(DataVaultError::is_transient, || self.inner.store_credit_card_with_token(token, credit_card, true)).await,
+            false => self.inner.store_credit_card_with_token(token, credit_card, false).await,
+        }
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.retrieve(token)).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.retrieve_credit_card(token)).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.exists(token)).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.retrieve_credit_card_with_version(token)).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.retrieve_with_metadata(token)).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        self.inner.update_credit_card_if_version(token, credit_card, expected_version).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.inner.rotate_token(token).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.inner.soft_delete(token).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.touch(token, ttl)).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.inner.delete_many(tokens).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.purge_expired()).await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.decrypted_records_page(cursor, limit)).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.count()).await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.policy.run(DataVaultError::is_transient, || self.inner.stats()).await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(RetryingVault {
+            inner: self.inner.with_namespace(namespace)?,
+            policy: self.policy,
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(RetryingVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            policy: self.policy,
+        })
+    }
+}
+
+#[cfg(feature = "rt-tokio")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+#[cfg(all(feature = "rt-async-std", not(feature = "rt-tokio")))]
+pub(crate) async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await
+}
+
+#[cfg(not(any(feature = "rt-tokio", feature = "rt-async-std")))]
+pub(crate) async fn sleep(_duration: Duration) {}
+
+#[cfg(test)]
+mod test {
+    use crate::retry::RetryPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::new(5)
+            .with_base_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(500));
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+        assert_eq!(policy.delay(4), Duration::from_millis(500));
+        assert_eq!(policy.delay(100), Duration::from_millis(500));
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[tokio::test]
+    async fn test_retry_run() {
+        use std::cell::Cell;
+
+        let policy = RetryPolicy::new(3).with_base_delay(Duration::from_millis(1));
+        let attempts = Cell::new(0);
+        let result: Result<(), bool> = policy.run(|transient| *transient, || {
+            attempts.set(attempts.get() + 1);
+            async { Err(true) }
+        }).await;
+        assert_eq!(result, Err(true));
+        assert_eq!(attempts.get(), 3);
+
+        attempts.set(0);
+        let result: Result<(), bool> = policy.run(|transient| *transient, || {
+            attempts.set(attempts.get() + 1);
+            async { Err(false) }
+        }).await;
+        assert_eq!(result, Err(false));
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::DataVaultError;
+use crate::postgres_data_vault::quote_identifier;
+use std::error;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A versioned change to the table of a `PostgresDataVault`, see
+/// `PostgresDataVault::run_migrations`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SchemaMigration {
+    /// migrations are applied in the order of their versions
+    pub version: u32,
+    pub name: &'static str,
+    sql: &'static str,
+}
+
+/// The outcome of `PostgresDataVault::run_migrations`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaReport {
+    /// the version of the table afterwards
+    pub version: u32,
+    /// the migrations applied by this run, none if the table was
+    /// up to date
+    pub applied: Vec<SchemaMigration>,
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal) => {
+        SchemaMigration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!("../migrations/postgres/V", $version, "__", $name, ".sql")),
+        }
+    };
+}
+
+// `{table}` is replaced with the quoted table name, `{schema}` with
+// the quoted schema and a dot, if there is one, and `{name}` with the
+// table name escaped for use in a quoted identifier, see `TableName`
+//
+// every statement is idempotent, so tables created from the DDL of
+// earlier versions are picked up at whatever version they are
+const MIGRATIONS: &[SchemaMigration] = &[
+    migration!(1, "create_table"),
+    migration!(2, "add_created_at"),
+    migration!(3, "add_tenant"),
+    migration!(4, "add_soft_delete"),
+    migration!(5, "add_version"),
+    migration!(6, "add_card_field_columns"),
+];
+
+// one history for all vault tables of a schema
+const CREATE_HISTORY: &str = "CREATE TABLE IF NOT EXISTS {schema}data_vault_schema_migrations (table_name text NOT NULL, version integer NOT NULL, name text NOT NULL, applied_at timestamptz NOT NULL DEFAULT now(), PRIMARY KEY (table_name, version))";
+const SELECT_VERSION: &str = "SELECT COALESCE(max(version), 0) FROM {schema}data_vault_schema_migrations WHERE table_name = $1";
+const INSERT_VERSION: &str = "INSERT INTO {schema}data_vault_schema_migrations (table_name, version, name) VALUES ($1, $2, $3)";
+// held while migrating, so vaults started together migrate one by one
+const MIGRATION_LOCK: i64 = 0x6461_7461_7661_756c;
+
+/// The table of a vault, `PG_TABLE_NAME` in the schema `PG_SCHEMA`
+pub(crate) struct TableName {
+    qualified: String,
+    schema: String,
+    name: String,
+}
+
+impl TableName {
+    pub(crate) fn new(schema: Option<&str>, name: &str) -> Result<Self, Box<dyn error::Error>> {
+        let schema = match schema {
+            Some(schema) => format!("{}.", quote_identifier(schema)?),
+            None => String::new(),
+        };
+        Ok(TableName {
+            qualified: format!("{}{}", schema, quote_identifier(name)?),
+            schema,
+            name: name.replace('"', "\"\""),
+        })
+    }
+
+    /// the quoted name, with the schema if there is one
+    pub(crate) fn qualified(&self) -> &str {
+        &self.qualified
+    }
+
+    /// `sql` of a migration for this table
+    fn expand(&self, sql: &str) -> String {
+        sql.replace("{schema}", &self.schema)
+            .replace("{name}", &self.name)
+            .replace("{table}", &self.qualified)
+    }
+
+    /// Apply the migrations the table does not have yet, in one
+    /// transaction
+    pub(crate) async fn migrate(&self, client: &mut deadpool_postgres::Client) -> Result<SchemaReport, DataVaultError> {
+        let tx = client.transaction().await?;
+        tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK]).await?;
+        tx.batch_execute(&self.expand(CREATE_HISTORY)).await?;
+        let current: i32 = tx.query_one(self.expand(SELECT_VERSION).as_str(), &[&self.qualified]).await?.get(0);
+
+        let mut applied = Vec::new();
+        for migration in MIGRATIONS.iter().filter(|migration| migration.version as i32 > current) {
+            tx.batch_execute(&self.expand(migration.sql)).await?;
+            tx.execute(self.expand(INSERT_VERSION).as_str(), &[&self.qualified, &(migration.version as i32), &migration.name]).await?;
+            applied.push(*migration);
+        }
+        tx.commit().await?;
+
+        // a newer version of the crate may have migrated further
+        let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+        Ok(SchemaReport {
+            version: latest.max(current as u32),
+            applied,
+        })
+    }
+}
+
+/// Runs the migrations of a vault's table once, on the first
+/// connection the vault takes, see
+/// `PostgresVaultConfig::with_auto_create_schema`
+#[derive(Default)]
+pub(crate) struct AutoCreate {
+    created: AtomicBool,
+    // connections taken while the first one migrates wait for it
+    migrating: futures::lock::Mutex<()>,
+}
+
+impl AutoCreate {
+    pub(crate) async fn ensure(&self, table: &TableName, client: &mut deadpool_postgres::Client) -> Result<(), DataVaultError> {
+        if self.created.load(Ordering::Acquire) {
+            return Ok(())
+        }
+        let _migrating = self.migrating.lock().await;
+        // a failed run is tried again by the next connection
+        if !self.created.load(Ordering::Acquire) {
+            table.migrate(client).await?;
+            self.created.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema::{TableName, MIGRATIONS};
+
+    #[test]
+    fn test_migrations() {
+        assert!(MIGRATIONS.windows(2).all(|pair| pair[0].version + 1 == pair[1].version));
+        assert!(MIGRATIONS.iter().all(|migration| !migration.sql.trim().is_empty()));
+
+        let table = TableName::new(Some("vault test"), "Cards\"").unwrap();
+        assert_eq!(table.qualified(), "\"vault test\".\"Cards\"\"\"");
+        assert_eq!(
+            table.expand(MIGRATIONS[2].sql).lines().last().unwrap(),
+            "DROP INDEX IF EXISTS \"vault test\".\"Cards\"\"_token_idx\";"
+        );
+        assert_eq!(TableName::new(None, "data_vault").unwrap().expand("ALTER TABLE {table}"), "ALTER TABLE \"data_vault\"");
+    }
+}
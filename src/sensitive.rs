@@ -0,0 +1,198 @@
+use credit_card::CreditCard;
+use serde::{Serialize, Serializer};
+use crate::masked::MaskedCreditCard;
+use std::cell::Cell;
+use std::fmt;
+
+thread_local! {
+    // set while `full_export` runs on this thread
+    static FULL_EXPORT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// How a value wrapped in `Sensitive` is shown by `Debug` and
+/// `Display` and serialized outside `full_export`
+pub trait Redact {
+    /// what is serialized instead of the value
+    type Redacted: Serialize;
+
+    /// Write the value without the parts that must not be logged
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// The value without the parts that must not leave the service
+    fn redacted(&self) -> Self::Redacted;
+}
+
+/// The number masked and the security code left out, like
+/// `MaskedCreditCard`
+impl Redact for CreditCard {
+    type Redacted = MaskedCreditCard;
+
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.redacted())
+    }
+
+    fn redacted(&self) -> MaskedCreditCard {
+        MaskedCreditCard::from(self)
+    }
+}
+
+/// Nothing of the string, e.g. a security code
+impl Redact for String {
+    type Redacted = &'static str;
+
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.redacted())
+    }
+
+    fn redacted(&self) -> &'static str {
+        "****"
+    }
+}
+
+/// Run `f` with every `Sensitive` it serializes on this thread
+/// serialized in full, e.g. for an export to another vault
+///
+/// Serialize inside `f`, not in a future it returns, the mode only
+/// holds while `f` runs.
+/// # example
+/// ```rust,ignore
+/// use data_vault::full_export;
+///
+/// let json = full_export(|| serde_json::to_string(&sensitive_card))?;
+/// ```
+pub fn full_export<R>(f: impl FnOnce() -> R) -> R {
+    struct Reset(bool);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            FULL_EXPORT.with(|full_export| full_export.set(self.0));
+        }
+    }
+
+    let _reset = Reset(FULL_EXPORT.with(|full_export| full_export.replace(true)));
+    f()
+}
+
+/// A value read from the vault that `Debug` and `Display` show
+/// redacted, so a stray `{:?}` in a log can not dump card data
+///
+/// Returned by `DataVault::retrieve_sensitive_credit_card`.  The raw
+/// value is only reached with an explicit `expose` or `into_inner`,
+/// which is easy to find in review, `Sensitive` does not deref to
+/// it.  Serializing it, e.g. into an API response, writes the
+/// redacted value, a `MaskedCreditCard` for a card, unless it runs in
+/// `full_export`.
+/// # example
+/// ```rust
+/// use credit_card::CreditCard;
+/// use data_vault::Sensitive;
+///
+/// let cc = Sensitive::new(CreditCard {
+///     number: "4111111111111111".to_string(),
+///     cardholder_name: "Graydon Hoare".to_string(),
+///     expiration_month: "01".to_string(),
+///     expiration_year: "2023".to_string(),
+///     brand: Some("visa".to_string()),
+///     security_code: Some("123".to_string())
+/// });
+/// assert_eq!(format!("{:?}", cc), "Sensitive(visa 411111******1111 01/2023 Graydon Hoare)");
+/// assert_eq!(cc.expose().number, "4111111111111111");
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Sensitive<T>(T);
+
+impl<T> Sensitive<T> {
+    pub fn new(value: T) -> Self {
+        Sensitive(value)
+    }
+
+    /// The raw value, e.g. to send the card number to a processor
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// The raw value, taken out of the wrapper
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Sensitive<T> {
+    fn from(value: T) -> Self {
+        Sensitive(value)
+    }
+}
+
+impl<T: Redact> fmt::Debug for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Sensitive(")?;
+        self.0.fmt_redacted(f)?;
+        write!(f, ")")
+    }
+}
+
+impl<T: Redact> fmt::Display for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_redacted(f)
+    }
+}
+
+impl<T: Serialize + Redact> Serialize for Sensitive<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match FULL_EXPORT.with(Cell::get) {
+            true => self.0.serialize(serializer),
+            false => self.0.redacted().serialize(serializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::sensitive::{full_export, Sensitive};
+
+    #[test]
+    fn test_sensitive() {
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("987".to_string())
+        };
+        let sensitive = Sensitive::from(cc.clone());
+        for shown in [format!("{:?}", sensitive), format!("{:#?}", sensitive), sensitive.to_string()].iter() {
+            assert!(!shown.contains("4111111111111111"), "{}", shown);
+            assert!(!shown.contains("987"), "{}", shown);
+            assert!(shown.contains("1111"), "{}", shown);
+        }
+        assert_eq!(sensitive.expose().number, cc.number);
+        assert_eq!(sensitive.into_inner().security_code, cc.security_code);
+
+        let cvv = Sensitive::new("987".to_string());
+        assert_eq!(format!("{:?}", cvv), "Sensitive(****)");
+    }
+
+    #[test]
+    fn test_sensitive_serialize() {
+        let cc = Sensitive::new(CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: Some("987".to_string())
+        });
+        let json = serde_json::to_value(&cc).unwrap();
+        assert_eq!(json["number"], "411111******1111");
+        assert!(json.get("security_code").is_none());
+        assert_eq!(serde_json::to_string(&Sensitive::new("987".to_string())).unwrap(), "\"****\"");
+
+        let json = full_export(|| serde_json::to_value(&cc)).unwrap();
+        assert_eq!(json["number"], "4111111111111111");
+        assert_eq!(json["security_code"], "987");
+        // the mode ends with the closure
+        assert_eq!(serde_json::to_value(&cc).unwrap()["number"], "411111******1111");
+    }
+}
@@ -0,0 +1,46 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::error::DataVaultError;
+use crate::serializer::Serializer;
+
+/// bincode, the smallest and fastest format but not self describing,
+/// records can not be read once `CreditCard` changes its fields
+pub struct BincodeSerializer;
+
+impl Serializer for BincodeSerializer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>, DataVaultError> {
+        bincode::serialize(value).map_err(|e| DataVaultError::Encoding(e))
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V, DataVaultError> {
+        bincode::deserialize(bytes).map_err(|e| DataVaultError::Encoding(e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::serializer::{Serializer, BincodeSerializer};
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let serializer = BincodeSerializer::new();
+        let bytes = serializer.serialize(&cc).unwrap();
+        let credit_card: CreditCard = serializer.deserialize(&bytes).unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert!(serializer.deserialize::<CreditCard>(&bytes[..bytes.len() / 2]).is_err())
+    }
+}
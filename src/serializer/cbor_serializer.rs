@@ -0,0 +1,45 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::error::DataVaultError;
+use crate::serializer::Serializer;
+
+/// CBOR, self describing like JSON but smaller
+pub struct CborSerializer;
+
+impl Serializer for CborSerializer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>, DataVaultError> {
+        serde_cbor::to_vec(value).map_err(|e| DataVaultError::Encoding(Box::new(e)))
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V, DataVaultError> {
+        serde_cbor::from_slice(bytes).map_err(|e| DataVaultError::Encoding(Box::new(e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::serializer::{Serializer, CborSerializer};
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let serializer = CborSerializer::new();
+        let bytes = serializer.serialize(&cc).unwrap();
+        let credit_card: CreditCard = serializer.deserialize(&bytes).unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert!(serializer.deserialize::<CreditCard>(&bytes[..bytes.len() / 2]).is_err())
+    }
+}
@@ -0,0 +1,46 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::error::DataVaultError;
+use crate::serializer::Serializer;
+
+/// JSON, the format of every record stored before serializers could
+/// be chosen and the default of every vault
+pub struct JsonSerializer;
+
+impl Serializer for JsonSerializer {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>, DataVaultError> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V, DataVaultError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::serializer::{Serializer, JsonSerializer};
+
+    #[test]
+    fn test_json_round_trip() {
+        let cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        let serializer = JsonSerializer::new();
+        let bytes = serializer.serialize(&cc).unwrap();
+        let credit_card: CreditCard = serializer.deserialize(&bytes).unwrap();
+        assert_eq!(credit_card.number, cc.number);
+        assert!(serializer.deserialize::<CreditCard>(&bytes[..bytes.len() / 2]).is_err())
+    }
+}
@@ -0,0 +1,17 @@
+mod traits;
+mod json_serializer;
+#[cfg(feature = "cbor")]
+mod cbor_serializer;
+#[cfg(feature = "msgpack")]
+mod message_pack_serializer;
+#[cfg(feature = "bincode")]
+mod bincode_serializer;
+
+pub use traits::Serializer;
+pub use json_serializer::JsonSerializer;
+#[cfg(feature = "cbor")]
+pub use cbor_serializer::CborSerializer;
+#[cfg(feature = "msgpack")]
+pub use message_pack_serializer::MessagePackSerializer;
+#[cfg(feature = "bincode")]
+pub use bincode_serializer::BincodeSerializer;
@@ -0,0 +1,15 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use crate::error::DataVaultError;
+
+/// The format credit cards are written in before they are encrypted
+///
+/// A vault can only read the records written in its own format,
+/// pick one before storing the first card.  `retrieve`, the
+/// decrypted record streams and `migrate` hand out records as text,
+/// they need a text format such as JSON.
+pub trait Serializer {
+    fn new() -> Self;
+    fn serialize<V: Serialize>(&self, value: &V) -> Result<Vec<u8>, DataVaultError>;
+    fn deserialize<V: DeserializeOwned>(&self, bytes: &[u8]) -> Result<V, DataVaultError>;
+}
@@ -0,0 +1,203 @@
+//! A REST tokenization service over any `DataVault`, run by the
+//! `data-vault-server` binary
+//!
+//! ```text
+//! POST   /tokens           a credit card as JSON   201 {"token": "..."}, 200 if stored already
+//! GET    /tokens/{token}                           200 the credit card as JSON
+//! DELETE /tokens/{token}                           204
+//! GET    /health                                   200 or 503, without an API key
+//! ```
+//!
+//! The tokenization routes are those of `axum::router`, every request
+//! to them sends one of the API keys as `Authorization: Bearer <key>`.
+//! Errors are `{"error": "..."}` with card numbers and tokens masked,
+//! see `redact`.
+//!
+//! # Examples
+//! ```sh
+//! DATA_VAULT_SERVER_API_KEYS=key-1 \
+//! DATA_VAULT_SERVER_TLS_CERT_FILE=server.pem DATA_VAULT_SERVER_TLS_KEY_FILE=server.key \
+//! cargo run --features server --bin data-vault-server
+//! ```
+
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum_server::Handle;
+use axum_server::tls_rustls::RustlsConfig;
+use crate::traits::DataVault;
+use crate::axum::{VaultError, VaultState};
+use crate::config::ServerConfig;
+use crate::config_file::{postgres_vault, redis_vault, Backend, Config, TokenizerKind};
+use crate::tokenizer::{Blake3DeterministicTokenizer, Blake3Tokenizer};
+use std::error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// requests in flight get this long to finish on shutdown
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+// blake3 of the API keys, `blake3::Hash` compares in constant time
+type ApiKeys = Arc<Vec<blake3::Hash>>;
+
+async fn authorize(State(api_keys): State<ApiKeys>, request: Request, next: Next) -> Response {
+    let key = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|key| blake3::hash(key.trim().as_bytes()));
+    match key {
+        Some(key) if api_keys.contains(&key) => next.run(request).await,
+        _ => {
+            let mut response = VaultError::new(StatusCode::UNAUTHORIZED, "missing or unknown API key").into_response();
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        },
+    }
+}
+
+async fn health(State(vault): State<VaultState>) -> StatusCode {
+    match vault.health_check().await.is_healthy() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// The routes of the service over `vault`, accepting `api_keys`
+///
+/// Nest or merge it into a service of your own, or let `run` serve it.
+pub fn router<V>(vault: V, api_keys: &[&str]) -> Router
+    where
+        V: DataVault + 'static,
+{
+    let api_keys: ApiKeys = Arc::new(api_keys.iter().map(|key| blake3::hash(key.as_bytes())).collect());
+    crate::axum::router()
+        .route_layer(middleware::from_fn_with_state(api_keys, authorize))
+        .route("/health", get(health))
+        .with_state(VaultState::new(vault))
+}
+
+/// Serve the vault `cfg` selects until SIGTERM or ctrl-c, letting
+/// requests in flight finish
+pub async fn run(cfg: ServerConfig) -> Result<(), Box<dyn error::Error>> {
+    cfg.validate()?;
+    let vault_config = match &cfg.vault_config {
+        Some(path) => Some(Config::from_file(path)?),
+        None => None,
+    };
+    let backend = vault_config.as_ref().map_or(cfg.backend, |vault_config| vault_config.backend);
+    let tokenizer = vault_config.as_ref().map_or(cfg.tokenizer, |vault_config| vault_config.tokenizer);
+
+    match (backend, tokenizer) {
+        (Backend::Redis, TokenizerKind::Random) => serve(&cfg, redis_vault::<Blake3Tokenizer>(vault_config)?).await,
+        (Backend::Redis, TokenizerKind::Deterministic) => serve(&cfg, redis_vault::<Blake3DeterministicTokenizer>(vault_config)?).await,
+        (Backend::Postgres, TokenizerKind::Random) => serve(&cfg, postgres_vault::<Blake3Tokenizer>(vault_config)?).await,
+        (Backend::Postgres, TokenizerKind::Deterministic) => serve(&cfg, postgres_vault::<Blake3DeterministicTokenizer>(vault_config)?).await,
+    }
+}
+
+async fn serve<V>(cfg: &ServerConfig, vault: V) -> Result<(), Box<dyn error::Error>>
+    where
+        V: DataVault + 'static,
+{
+    let addr: SocketAddr = cfg.addr.parse()
+        .map_err(|e| format!("DATA_VAULT_SERVER_ADDR {:?}: {}", cfg.addr, e))?;
+    let app = router(vault, &cfg.api_keys()).into_make_service();
+    let handle = Handle::new();
+    tokio::spawn(shutdown_on_signal(handle.clone()));
+
+    match (&cfg.tls_cert_file, &cfg.tls_key_file) {
+        (Some(cert_file), Some(key_file)) => {
+            // fails if the process installed a provider already, which is fine
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let tls = RustlsConfig::from_pem_file(cert_file, key_file).await?;
+            axum_server::bind_rustls(addr, tls).handle(handle).serve(app).await?
+        },
+        _ => axum_server::bind(addr).handle(handle).serve(app).await?,
+    }
+    Ok(())
+}
+
+async fn shutdown_on_signal(handle: Handle) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+    handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+}
+
+#[cfg(test)]
+mod test {
+    use axum::body::{self, Body};
+    use axum::http::{header, Method, Request, StatusCode};
+    use tower::ServiceExt;
+    use crate::traits::DataVault;
+    use crate::config::ServerConfig;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+
+    async fn send(app: &axum::Router, method: Method, uri: &str, key: Option<&str>, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+        }
+        let response = app.clone().oneshot(request.body(Body::from(body.to_string())).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_router() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let app = crate::server::router(vault, &["old-key", "new-key"]);
+        let card = r#"{"number": "4111111111111111", "cardholder_name": "Graydon Hoare", "expiration_month": "01", "expiration_year": "2023", "brand": null, "security_code": null}"#;
+
+        assert_eq!(send(&app, Method::POST, "/tokens", None, card).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&app, Method::POST, "/tokens", Some("wrong-key"), card).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&app, Method::GET, "/health", None, "").await.0, StatusCode::OK);
+
+        let (status, created) = send(&app, Method::POST, "/tokens", Some("old-key"), card).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let uri = format!("/tokens/{}", created["token"].as_str().unwrap());
+        let (status, credit_card) = send(&app, Method::GET, &uri, Some("new-key"), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(credit_card["number"], "4111111111111111");
+
+        assert_eq!(send(&app, Method::DELETE, &uri, Some("new-key"), "").await.0, StatusCode::NO_CONTENT);
+        assert_eq!(send(&app, Method::GET, &uri, Some("new-key"), "").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send(&app, Method::DELETE, &uri, Some("new-key"), "").await.0, StatusCode::NOT_FOUND);
+
+        // the parse error quotes the card number, masked
+        let (status, error) = send(&app, Method::POST, "/tokens", Some("old-key"), r#"{"number": 4111111111111111}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!error["error"].as_str().unwrap().contains("4111111111111111"));
+    }
+
+    #[test]
+    fn test_server_config() {
+        let mut cfg = ServerConfig { api_keys: " key-1, ,key-2".to_string(), ..ServerConfig::default() };
+        assert_eq!(cfg.api_keys(), vec!["key-1", "key-2"]);
+        assert!(!format!("{:?}", cfg).contains("key-1"));
+        assert!(cfg.validate().unwrap_err().to_string().contains("DATA_VAULT_SERVER_ALLOW_PLAINTEXT"));
+
+        cfg.allow_plaintext = true;
+        assert!(cfg.validate().is_ok());
+        cfg.tls_cert_file = Some("server.pem".into());
+        assert!(cfg.validate().is_err());
+        cfg.api_keys = String::new();
+        assert!(cfg.validate().unwrap_err().to_string().contains("DATA_VAULT_SERVER_API_KEYS"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,192 @@
+//! The vault as a `tower::Service` of `TokenizeRequest` and
+//! `DetokenizeRequest`, so timeouts, rate limits, load shedding and
+//! retries are layered around vault calls like around any other service
+//!
+//! Calls never wait for capacity, `poll_ready` is always ready, the
+//! pool of the vault bounds concurrency.  Requests are `Clone` for
+//! retry layers, whose policies can retry the errors
+//! `DataVaultError::is_transient` names.
+//!
+//! # Examples
+//! ```rust,ignore
+//! use data_vault::{DataVault, RedisDataVault};
+//! use data_vault::service::{DetokenizeRequest, TokenizeRequest, VaultService};
+//! use data_vault::encryption::AesGcmSivEncryption;
+//! use data_vault::tokenizer::Blake3Tokenizer;
+//! use tower::{ServiceBuilder, ServiceExt};
+//! use std::time::Duration;
+//!
+//! let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+//! let service = ServiceBuilder::new()
+//!     .timeout(Duration::from_millis(250))
+//!     .concurrency_limit(64)
+//!     .service(VaultService::new(vault));
+//! let tokenized = service.clone().oneshot(TokenizeRequest::new(credit_card)).await.unwrap();
+//! let credit_card = service.oneshot(DetokenizeRequest::new(&tokenized.token)).await.unwrap();
+//! ```
+
+use credit_card::CreditCard;
+use futures::future::BoxFuture;
+use tower_service::Service;
+use crate::traits::{DataVault, DataVaultDyn};
+use crate::error::DataVaultError;
+use crate::redact::{redact, redact_token};
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Store a credit card, see `DataVault::tokenize`
+#[derive(Clone)]
+pub struct TokenizeRequest {
+    pub credit_card: CreditCard,
+}
+
+impl TokenizeRequest {
+    pub fn new(credit_card: CreditCard) -> Self {
+        TokenizeRequest { credit_card }
+    }
+}
+
+impl fmt::Debug for TokenizeRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenizeRequest")
+            .field("number", &redact(&self.credit_card.number))
+            .finish_non_exhaustive()
+    }
+}
+
+/// The answer to a `TokenizeRequest`
+#[derive(Clone, PartialEq, Eq)]
+pub struct TokenizeResponse {
+    pub token: String,
+    /// false when the card was stored under this token already
+    pub created: bool,
+}
+
+impl fmt::Debug for TokenizeResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenizeResponse")
+            .field("token", &redact_token(&self.token))
+            .field("created", &self.created)
+            .finish()
+    }
+}
+
+/// Read the credit card stored under a token, see
+/// `DataVault::retrieve_credit_card`
+#[derive(Clone)]
+pub struct DetokenizeRequest {
+    pub token: String,
+}
+
+impl DetokenizeRequest {
+    pub fn new(token: &str) -> Self {
+        DetokenizeRequest { token: token.to_string() }
+    }
+}
+
+impl fmt::Debug for DetokenizeRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetokenizeRequest")
+            .field("token", &redact_token(&self.token))
+            .finish()
+    }
+}
+
+/// A vault as a `Service`, clones share the vault
+#[derive(Clone)]
+pub struct VaultService {
+    vault: Arc<dyn DataVaultDyn>,
+}
+
+impl VaultService {
+    pub fn new<V>(vault: V) -> Self
+        where
+            V: DataVault + 'static,
+    {
+        VaultService { vault: Arc::new(vault) }
+    }
+
+    /// The service of a vault shared with other owners
+    pub fn from_arc(vault: Arc<dyn DataVaultDyn>) -> Self {
+        VaultService { vault }
+    }
+
+    pub fn vault(&self) -> &dyn DataVaultDyn {
+        &*self.vault
+    }
+}
+
+impl Service<TokenizeRequest> for VaultService {
+    type Response = TokenizeResponse;
+    type Error = DataVaultError;
+    type Future = BoxFuture<'static, Result<TokenizeResponse, DataVaultError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), DataVaultError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: TokenizeRequest) -> Self::Future {
+        let vault = self.vault.clone();
+        Box::pin(async move {
+            let (token, created) = vault.tokenize(&request.credit_card).await?;
+            Ok(TokenizeResponse { token, created })
+        })
+    }
+}
+
+impl Service<DetokenizeRequest> for VaultService {
+    type Response = CreditCard;
+    type Error = DataVaultError;
+    type Future = BoxFuture<'static, Result<CreditCard, DataVaultError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), DataVaultError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: DetokenizeRequest) -> Self::Future {
+        let vault = self.vault.clone();
+        Box::pin(async move { vault.retrieve_credit_card(&request.token).await })
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod test {
+    use credit_card::CreditCard;
+    use tower::{BoxError, ServiceBuilder, ServiceExt};
+    use crate::service::{DetokenizeRequest, TokenizeRequest, VaultService};
+    use crate::traits::DataVault;
+    use crate::error::DataVaultError;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_vault_service() {
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3DeterministicTokenizer>::new().unwrap();
+        let service = ServiceBuilder::new()
+            .timeout(Duration::from_secs(5))
+            .service(VaultService::new(vault));
+        let credit_card = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None,
+        };
+
+        let request = TokenizeRequest::new(credit_card.clone());
+        assert!(!format!("{:?}", request).contains("4111111111111111"));
+        let tokenized = service.clone().oneshot(request.clone()).await.unwrap();
+        assert_eq!(service.clone().oneshot(request).await.unwrap().token, tokenized.token);
+
+        let detokenized = service.clone().oneshot(DetokenizeRequest::new(&tokenized.token)).await.unwrap();
+        assert_eq!(detokenized.number, credit_card.number);
+
+        service.get_ref().vault().delete_many(std::slice::from_ref(&tokenized.token)).await.unwrap();
+        let e: BoxError = service.oneshot(DetokenizeRequest::new(&tokenized.token)).await.unwrap_err();
+        assert!(matches!(e.downcast_ref::<DataVaultError>(), Some(DataVaultError::NotFound)));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+use std::time::SystemTime;
+
+/// Storage statistics for capacity planning
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VaultStats {
+    /// number of stored records
+    pub count: u64,
+    /// approximate bytes used by the records, including indexes
+    pub approximate_bytes: u64,
+    /// when the oldest record was first stored
+    pub oldest: Option<SystemTime>,
+    /// when the newest record was first stored
+    pub newest: Option<SystemTime>,
+}
@@ -0,0 +1,28 @@
+use futures::stream::BoxStream;
+use crate::error::DataVaultError;
+use crate::redact::redact_token;
+use std::fmt;
+
+/// A stream of `(token, record)` pairs, see `DataVault::iter_records`
+pub type RecordStream<'a, T> = BoxStream<'a, Result<(String, T), DataVaultError>>;
+
+/// One batch of records, see `DataVault::decrypted_records_page`,
+/// `Debug` only prints the ends of the tokens and no records
+#[derive(Clone, Default, PartialEq)]
+pub struct RecordPage<T> {
+    /// `(token, record)` pairs of this batch, may be empty even if
+    /// more batches follow
+    pub records: Vec<(String, T)>,
+    /// where the next batch starts, `None` after the last batch
+    pub next_cursor: Option<String>,
+}
+
+impl<T> fmt::Debug for RecordPage<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens: Vec<String> = self.records.iter().map(|(token, _)| redact_token(token)).collect();
+        f.debug_struct("RecordPage")
+            .field("tokens", &tokens)
+            .field("next_cursor", &self.next_cursor)
+            .finish()
+    }
+}
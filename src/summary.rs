@@ -0,0 +1,137 @@
+use credit_card::CreditCard;
+use serde::{Deserialize, Serialize};
+use crate::metadata::CustomMetadata;
+
+/// The parts of a stored card that are safe to show, kept apart from
+/// it so display endpoints never decrypt the card, see
+/// `DataVault::retrieve_summary`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CardSummary {
+    pub brand: Option<String>,
+    pub last_four: String,
+    pub expiration_month: String,
+    pub expiration_year: String,
+    /// the custom metadata of the card, see `CustomMetadata`
+    #[serde(default, skip_serializing_if = "CustomMetadata::is_empty")]
+    pub metadata: CustomMetadata,
+}
+
+impl From<&CreditCard> for CardSummary {
+    fn from(credit_card: &CreditCard) -> Self {
+        let number = &credit_card.number;
+        CardSummary {
+            brand: credit_card.brand.clone(),
+            last_four: number.chars().skip(number.chars().count().saturating_sub(4)).collect(),
+            expiration_month: credit_card.expiration_month.clone(),
+            expiration_year: credit_card.expiration_year.clone(),
+            metadata: CustomMetadata::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::summary::CardSummary;
+
+    #[test]
+    fn test_card_summary() {
+        let summary = CardSummary::from(&CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: Some("visa".to_string()),
+            security_code: Some("123".to_string())
+        });
+        assert_eq!(summary.last_four, "1111");
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(json, r#"{"brand":"visa","last_four":"1111","expiration_month":"01","expiration_year":"2023"}"#);
+        assert_eq!(serde_json::from_str::<CardSummary>(&json).unwrap(), summary);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retrieve_summary_postgres() {
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::error::DataVaultError;
+        use crate::metadata::CustomMetadata;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use crate::traits::DataVault;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("summary-test").unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "1".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let mut metadata = CustomMetadata::new();
+        metadata.insert("customer_id".to_string(), "cus_42".to_string());
+        let token = vault.store_credit_card_with_custom_metadata(&cc, &metadata).await.unwrap();
+        let summary = vault.retrieve_summary(&token).await.unwrap();
+        assert_eq!((summary.brand.as_deref(), summary.last_four.as_str()), (Some("visa"), "1111"));
+        assert_eq!(summary.expiration_month, "01");
+        assert_eq!(summary.metadata, metadata);
+
+        // the summary is kept apart from the card
+        let side = vault.with_side_namespace("summary").unwrap();
+        assert!(!side.retrieve(&token).await.unwrap().contains("4111111111111111"));
+
+        cc.number = "5555555555554444".to_string();
+        let (_, version) = vault.retrieve_credit_card_with_version(&token).await.unwrap();
+        vault.update_credit_card_if_version(&token, &cc, version).await.unwrap();
+        let token = vault.rotate_token(&token).await.unwrap();
+        let summary = vault.retrieve_summary(&token).await.unwrap();
+        assert_eq!((summary.brand.as_deref(), summary.last_four.as_str()), (Some("mastercard"), "4444"));
+
+        vault.soft_delete(&token).await.unwrap();
+        assert!(matches!(vault.retrieve_summary(&token).await, Err(DataVaultError::NotFound)));
+        vault.delete_many(std::slice::from_ref(&token)).await.unwrap();
+        assert!(!side.exists(&token).await.unwrap());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_side_namespaces_postgres() {
+        use crate::encryption::AesGcmSivEncryption;
+        use crate::postgres_data_vault::PostgresDataVault;
+        use crate::tokenizer::Blake3Tokenizer;
+        use crate::traits::DataVault;
+
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+
+        // the longest namespace has room for its side namespaces
+        let long = vault.with_namespace(&"side-namespace-test-".repeat(4)[..64]).unwrap();
+        let token = long.store_credit_card(&cc).await.unwrap();
+        let (tokenized, _) = long.tokenize(&cc).await.unwrap();
+        cc.number = "5555555555554444".to_string();
+        let (_, version) = long.retrieve_credit_card_with_version(&token).await.unwrap();
+        long.update_credit_card_if_version(&token, &cc, version).await.unwrap();
+        assert_eq!(long.retrieve_summary(&token).await.unwrap().last_four, "4444");
+        long.delete_many(&[token, tokenized]).await.unwrap();
+
+        // a namespace named like a side namespace of another is its own
+        let acme = vault.with_namespace("side-namespace-acme").unwrap();
+        let acme_summary = vault.with_namespace("side-namespace-acme.summary").unwrap();
+        let token = acme.store_credit_card(&cc).await.unwrap();
+        assert!(!acme_summary.exists(&token).await.unwrap());
+        acme_summary.store(&token, "{}").await.unwrap();
+        assert_eq!(acme.retrieve_summary(&token).await.unwrap().last_four, "4444");
+        acme_summary.delete_many(std::slice::from_ref(&token)).await.unwrap();
+        assert_eq!(acme.retrieve_summary(&token).await.unwrap().last_four, "4444");
+        acme.delete_many(&[token]).await.unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use futures::TryStreamExt;
+use crate::error::DataVaultError;
+use crate::traits::DataVault;
+use crate::validation::{is_expired, normalize_expiry};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What `sweep_expired_cards` does with a card past its expiry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepAction {
+    /// remove the record right away, see `DataVault::delete_many`
+    Delete,
+    /// hide the record until it is purged after the retention
+    /// period, see `DataVault::soft_delete`
+    SoftDelete,
+    /// keep the record and flag its token, see `is_flagged_expired`
+    Flag,
+}
+
+/// What a `sweep_expired_cards` run found
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    /// credit cards looked at, other records are skipped
+    pub scanned: u64,
+    /// tokens of the cards past their expiry and grace period, the
+    /// action was applied to all of them
+    pub expired: Vec<String>,
+}
+
+/// Delete, soft delete or flag every card of `vault`'s namespace that
+/// expired more than `grace` ago, so dead cards do not pile up
+///
+/// A card expires at the end of its expiration month, with a `grace`
+/// of 30 days a card of `01/2024` is swept from March 2nd 2024 on.
+/// Records that are not credit cards, and cards whose expiry can not
+/// be read, are left alone.  The whole namespace is read before
+/// anything is changed.  Run it from a scheduled job, e.g. next to
+/// `purge_expired_every`.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{sweep_expired_cards, DataVault, RedisDataVault, SweepAction};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::time::Duration;
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let report = sweep_expired_cards(&data_vault, Duration::from_secs(90 * 86400), SweepAction::SoftDelete).await?;
+/// println!("{} of {} cards swept", report.expired.len(), report.scanned);
+/// ```
+pub async fn sweep_expired_cards<V: DataVault>(vault: &V, grace: Duration, action: SweepAction) -> Result<SweepReport, DataVaultError> {
+    sweep_expired_cards_at(vault, grace, action, SystemTime::now()).await
+}
+
+async fn sweep_expired_cards_at<V: DataVault>(vault: &V, grace: Duration, action: SweepAction, now: SystemTime) -> Result<SweepReport, DataVaultError> {
+    let cutoff = now.checked_sub(grace).unwrap_or(UNIX_EPOCH);
+    let mut report = SweepReport::default();
+
+    // tokens only, cards are read with the vault's serializer
+    let mut records = vault.iter_records();
+    while let Some((token, _)) = records.try_next().await? {
+        let credit_card = match vault.try_retrieve_credit_card(&token).await {
+            Ok(Some(credit_card)) => credit_card,
+            Ok(None) | Err(DataVaultError::Serialization(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        report.scanned += 1;
+        if let Ok((month, year)) = normalize_expiry(&credit_card.expiration_month, &credit_card.expiration_year) {
+            if is_expired(&month, &year, cutoff) {
+                report.expired.push(token);
+            }
+        }
+    }
+
+    match action {
+        SweepAction::Delete => {
+            vault.delete_many(&report.expired).await?;
+        },
+        SweepAction::SoftDelete => for token in &report.expired {
+            match vault.soft_delete(token).await {
+                Ok(()) | Err(DataVaultError::NotFound) => (),
+                Err(e) => return Err(e),
+            }
+        },
+        SweepAction::Flag => {
+            let flags = vault.with_side_namespace("expired")?;
+            let flagged_at = now.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or_default();
+            for token in &report.expired {
+                flags.store(token, &flagged_at.to_string()).await?;
+            }
+        },
+    }
+    Ok(report)
+}
+
+/// Whether `sweep_expired_cards` flagged the card of `token` as
+/// expired with `SweepAction::Flag`
+pub async fn is_flagged_expired<V: DataVault>(vault: &V, token: &str) -> Result<bool, DataVaultError> {
+    vault.with_side_namespace("expired")?.exists(token).await
+}
+
+#[cfg(all(test, feature = "postgres"))]
+mod test {
+    use credit_card::CreditCard;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::error::DataVaultError;
+    use crate::postgres_data_vault::PostgresDataVault;
+    use crate::sweep::{is_flagged_expired, sweep_expired_cards_at, SweepAction};
+    use crate::tokenizer::Blake3Tokenizer;
+    use crate::traits::DataVault;
+    use crate::utils::Salt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sweep_expired_cards() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace(&Salt::generate(16)).unwrap();
+        let card = |month: &str, year: &str| CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: month.to_string(),
+            expiration_year: year.to_string(),
+            brand: None,
+            security_code: None
+        };
+        let expired = vault.store_credit_card(&card("01", "2024")).await.unwrap();
+        let in_grace = vault.store_credit_card(&card("02", "2024")).await.unwrap();
+        let current = vault.store_credit_card(&card("12", "2024")).await.unwrap();
+        let string = Salt::generate(64);
+        vault.store(&string, "not a card").await.unwrap();
+
+        // 2024-03-15 with 30 days of grace
+        let now = UNIX_EPOCH + Duration::from_secs(1710460800);
+        let grace = Duration::from_secs(30 * 86400);
+        let report = sweep_expired_cards_at(&vault, grace, SweepAction::Flag, now).await.unwrap();
+        assert_eq!(report.scanned, 3);
+        assert_eq!(report.expired, vec![expired.clone()]);
+        assert!(is_flagged_expired(&vault, &expired).await.unwrap());
+        assert!(!is_flagged_expired(&vault, &in_grace).await.unwrap());
+        assert!(vault.exists(&expired).await.unwrap());
+
+        let report = sweep_expired_cards_at(&vault, grace, SweepAction::SoftDelete, now).await.unwrap();
+        assert_eq!(report.expired, vec![expired.clone()]);
+        assert!(matches!(vault.retrieve_credit_card(&expired).await, Err(DataVaultError::NotFound)));
+
+        let report = sweep_expired_cards_at(&vault, Duration::ZERO, SweepAction::Delete, now).await.unwrap();
+        assert_eq!(report.expired, vec![in_grace.clone()]);
+        assert!(!vault.exists(&in_grace).await.unwrap());
+        assert!(vault.exists(&current).await.unwrap());
+
+        vault.delete_many(&[expired.clone(), current, string]).await.unwrap();
+        vault.with_namespace(&format!("{}.expired", vault.namespace())).unwrap().delete_many(&[expired]).await.unwrap();
+    }
+}
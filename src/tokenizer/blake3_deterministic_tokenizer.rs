@@ -0,0 +1,119 @@
+use credit_card::CreditCard;
+use crate::config::EncryptionSettings;
+use crate::tokenizer::{Tokenizer};
+
+const KEY_CONTEXT: &str = "data_vault 2021-05-01 deterministic token";
+const FINGERPRINT_CONTEXT: &str = "data_vault 2026-10-17 card fingerprint";
+
+/// Generates the same token every time a card is tokenized
+///
+/// The token is a keyed blake3 hash of the card number, cardholder
+/// name and expiration date.  The hash key is derived from
+/// `ENCRYPTED_DATA_VAULT_KEY` so tokens can not be brute forced
+/// from the card number space without it.  The security code is
+/// never part of the token.
+///
+/// Cards are fingerprinted by their number alone with a second key,
+/// so a vault finds a card stored before under another name or
+/// expiry, see `DataVault::store_credit_card_deduplicated`.
+pub struct Blake3DeterministicTokenizer {
+    key: [u8; 32],
+    fingerprint_key: [u8; 32],
+}
+
+impl Tokenizer for Blake3DeterministicTokenizer {
+    fn new() -> Self {
+        Self::from_settings(&EncryptionSettings::from_env().unwrap())
+    }
+
+    fn from_settings(settings: &EncryptionSettings) -> Self {
+        let mut key = [0u8; 32];
+        blake3::derive_key(KEY_CONTEXT, settings.key.as_bytes(), &mut key);
+        let mut fingerprint_key = [0u8; 32];
+        blake3::derive_key(FINGERPRINT_CONTEXT, settings.key.as_bytes(), &mut fingerprint_key);
+
+        Self {
+            key,
+            fingerprint_key,
+        }
+    }
+
+    /// creates a token for a given credit card
+    /// # Arguments
+    /// * `CreditCard` - a credit card object to hash
+    /// # Examples
+    /// ```rust
+    /// use data_vault::tokenizer::Tokenizer;
+    /// use data_vault::tokenizer::Blake3DeterministicTokenizer;
+    /// use credit_card::CreditCard;
+    ///
+    /// let cc = CreditCard {
+    ///    number: "4111111111111111".to_string(),
+    ///    cardholder_name: "Graydon Hoare".to_string(),
+    ///    expiration_month: "01".to_string(),
+    ///    expiration_year: "2023".to_string(),
+    ///    brand: None,
+    ///    security_code: None
+    /// };
+    ///
+    /// let tokenizer = Blake3DeterministicTokenizer::new();
+    /// assert_eq!(tokenizer.generate(&cc), tokenizer.generate(&cc));
+    /// ```
+    fn generate(&self, credit_card: &CreditCard) -> String {
+        // fields are NUL separated so shifting characters between
+        // adjacent fields can not produce the same token
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        hasher.update(credit_card.number.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(credit_card.cardholder_name.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(credit_card.expiration_month.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(credit_card.expiration_year.as_bytes());
+
+        let digest = hasher.finalize();
+        let hex_digest = digest.to_hex();
+        hex_digest.to_string()
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+
+    fn fingerprint(&self, credit_card: &CreditCard) -> Option<String> {
+        Some(blake3::keyed_hash(&self.fingerprint_key, credit_card.number.as_bytes()).to_hex().to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tokenizer::Tokenizer;
+    use crate::tokenizer::Blake3DeterministicTokenizer;
+    use credit_card::CreditCard;
+
+    #[test]
+    fn test_blake3_deterministic_tokenization() {
+        let mut cc = CreditCard {
+           number: "4111111111111111".to_string(),
+           cardholder_name: "Graydon Hoare".to_string(),
+           expiration_month: "01".to_string(),
+           expiration_year: "2023".to_string(),
+           brand: None,
+           security_code: None
+        };
+
+        let tokenizer = Blake3DeterministicTokenizer::new();
+        let token = tokenizer.generate(&cc);
+        assert_eq!(token.len(), 64);
+
+        cc.security_code = Some("123".to_string());
+        assert_eq!(tokenizer.generate(&cc), token);
+
+        let fingerprint = tokenizer.fingerprint(&cc).unwrap();
+        cc.expiration_year = "2024".to_string();
+        assert_ne!(tokenizer.generate(&cc), token);
+        // the same number under another expiry
+        assert_eq!(tokenizer.fingerprint(&cc).unwrap(), fingerprint);
+        assert_ne!(fingerprint, token);
+    }
+}
@@ -1,5 +1,7 @@
 mod traits;
 mod blake3_tokenizer;
+mod blake3_deterministic_tokenizer;
 
 pub use traits::Tokenizer;
-pub use blake3_tokenizer::Blake3Tokenizer;
\ No newline at end of file
+pub use blake3_tokenizer::Blake3Tokenizer;
+pub use blake3_deterministic_tokenizer::Blake3DeterministicTokenizer;
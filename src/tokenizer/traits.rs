@@ -1,6 +1,23 @@
 use credit_card::CreditCard;
+use crate::config::EncryptionSettings;
 
 pub trait Tokenizer {
     fn new() -> Self;
+    /// for tokenizers keyed with the vault's key, others ignore `settings`
+    fn from_settings(_settings: &EncryptionSettings) -> Self
+        where Self: Sized
+    {
+        Self::new()
+    }
     fn generate(&self, credit_card: &CreditCard) -> String;
-}
\ No newline at end of file
+    /// true when the same card always produces the same token
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+    /// the same string for every card with the number of
+    /// `credit_card`, whatever its name and expiry, `None` for
+    /// tokenizers that do not fingerprint cards
+    fn fingerprint(&self, _credit_card: &CreditCard) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,324 @@
+use async_trait::async_trait;
+use credit_card::CreditCard;
+use crate::traits::DataVault;
+use crate::error::DataVaultError;
+use crate::hooks::Outcome;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::RecordMetadata;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::redact::redact_token;
+use std::error;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::Instrument;
+
+/// `checkout`, taking a connection from a pool, in a
+/// `data_vault.pool` span with the `backend`, the `pool`, `primary`
+/// or `replica`, the `duration_ms` and the `outcome`, `success` or
+/// `failure`
+pub(crate) async fn checkout<R, E, F>(backend: &'static str, pool: &'static str, checkout: F) -> Result<R, E>
+    where
+        F: Future<Output = Result<R, E>>,
+{
+    let span = tracing::debug_span!("data_vault.pool", backend, pool, duration_ms = Empty, outcome = Empty);
+    let started = Instant::now();
+    let result = checkout.instrument(span.clone()).await;
+    span.record("duration_ms", started.elapsed().as_secs_f64() * 1000.0);
+    span.record("outcome", if result.is_ok() { "success" } else { "failure" });
+    result
+}
+
+/// A vault running each operation of the wrapped vault in a
+/// `data_vault` span, see `DataVault::traced`
+///
+/// The span has the `backend`, the `operation`, the method called,
+/// the `tenant`, the `duration_ms` and the `outcome`, `success`,
+/// `not_found`, `conflict` or `failure`.  Errors are reduced to
+/// their kind and card data is never recorded.  Tokens are only
+/// recorded, in a `token` field, after `with_token_field`, as a
+/// deterministic token identifies a card as well as its number,
+/// and then only their first and last 4 characters, see
+/// `redact_token`.
+/// Connections taken from the pools are traced in `data_vault.pool`
+/// spans within.
+///
+/// `iter_records` and `iter_decrypted_records` are not traced
+/// because they are lazy, `decrypted_records_page` is.
+pub struct TracedDataVault<V> {
+    inner: V,
+    token_field: bool,
+}
+
+impl<V> TracedDataVault<V>
+    where
+        V: DataVault,
+{
+    pub(crate) fn new(inner: V) -> Self {
+        TracedDataVault {
+            inner,
+            token_field: false,
+        }
+    }
+
+    /// The wrapped vault, operations on it are not traced
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Record the ends of the token operated on in the spans, or not
+    pub fn with_token_field(self, enabled: bool) -> Self {
+        TracedDataVault {
+            token_field: enabled,
+            ..self
+        }
+    }
+
+    async fn traced<F, R>(&self, operation: &'static str, token: Option<&str>, f: F) -> Result<R, DataVaultError>
+        where
+            F: Future<Output = Result<R, DataVaultError>>,
+    {
+        let span = tracing::info_span!(
+            "data_vault",
+            backend = self.inner.backend(),
+            operation,
+            tenant = self.inner.namespace(),
+            token = Empty,
+            duration_ms = Empty,
+            outcome = Empty,
+        );
+        if let (true, Some(token)) = (self.token_field, token) {
+            span.record("token", redact_token(token).as_str());
+        }
+
+        let started = Instant::now();
+        let result = f.instrument(span.clone()).await;
+        span.record("duration_ms", started.elapsed().as_secs_f64() * 1000.0);
+        span.record("outcome", match Outcome::from(&result) {
+            Outcome::Success => "success",
+            Outcome::NotFound => "not_found",
+            Outcome::Conflict => "conflict",
+            Outcome::Failure => "failure",
+        });
+        result
+    }
+}
+
+#[async_trait]
+impl<V> DataVault for TracedDataVault<V>
+    where
+        V: DataVault,
+{
+    /// A vault from the environment tracing its operations
+    fn new() -> Result<Self, Box<dyn error::Error>> {
+        Ok(TracedDataVault::new(V::new()?))
+    }
+
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        self.traced("store", Some(token), self.inner.store(token, string)).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        self.traced("store_if_absent", Some(token), self.inner.store_if_absent(token, string)).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        self.traced("store_expiring", Some(token), self.inner.store_expiring(token, string, ttl)).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        self.traced("store_credit_card", None, self.inner.store_credit_card(credit_card)).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        self.traced("tokenize", None, self.inner.tokenize(credit_card)).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        let store = self.inner.store_credit_card_with_token(token, credit_card, overwrite);
+        self.traced("store_credit_card_with_token", Some(token), store).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        self.traced("retrieve", Some(token), self.inner.retrieve(token)).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        self.traced("retrieve_credit_card", Some(token), self.inner.retrieve_credit_card(token)).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        self.traced("exists", Some(token), self.inner.exists(token)).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        self.traced("retrieve_credit_card_with_version", Some(token), self.inner.retrieve_credit_card_with_version(token)).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        self.traced("retrieve_with_metadata", Some(token), self.inner.retrieve_with_metadata(token)).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        let update = self.inner.update_credit_card_if_version(token, credit_card, expected_version);
+        self.traced("update_credit_card_if_version", Some(token), update).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        self.traced("rotate_token", Some(token), self.inner.rotate_token(token)).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        self.traced("soft_delete", Some(token), self.inner.soft_delete(token)).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        self.traced("touch", Some(token), self.inner.touch(token, ttl)).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        self.traced("delete_many", None, self.inner.delete_many(tokens)).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        self.traced("purge_expired", None, self.inner.purge_expired()).await
+    }
+
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>> {
+        self.inner.iter_records()
+    }
+
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String> {
+        self.inner.iter_decrypted_records()
+    }
+
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError> {
+        self.traced("decrypted_records_page", None, self.inner.decrypted_records_page(cursor, limit)).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        self.traced("count", None, self.inner.count()).await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        self.traced("stats", None, self.inner.stats()).await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        self.inner.health_check().await
+    }
+
+    fn namespace(&self) -> &str {
+        self.inner.namespace()
+    }
+
+    fn backend(&self) -> &'static str {
+        self.inner.backend()
+    }
+
+    /// The scoped vault traces its operations the same way
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError> {
+        Ok(TracedDataVault {
+            inner: self.inner.with_namespace(namespace)?,
+            token_field: self.token_field,
+        })
+    }
+
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError> {
+        Ok(TracedDataVault {
+            inner: self.inner.with_side_namespace(kind)?,
+            token_field: self.token_field,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "redis", feature = "rt-tokio"))]
+mod test {
+    use crate::traits::DataVault;
+    use crate::redis_data_vault::RedisDataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::tokenizer::Blake3Tokenizer;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// keeps `span name.field = value` of every span
+    #[derive(Default)]
+    struct Fields {
+        names: Mutex<Vec<&'static str>>,
+        fields: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Visitor<'a>(&'static str, &'a Mutex<Vec<String>>);
+
+    impl Visit for Visitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.1.lock().unwrap().push(format!("{}.{} = {:?}", self.0, field.name(), value));
+        }
+    }
+
+    impl Subscriber for Fields {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut names = self.names.lock().unwrap();
+            names.push(span.metadata().name());
+            span.record(&mut Visitor(span.metadata().name(), &self.fields));
+            Id::from_u64(names.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let name = self.names.lock().unwrap()[span.into_u64() as usize - 1];
+            values.record(&mut Visitor(name, &self.fields));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_traced() {
+        let subscriber = Fields::default();
+        let fields = subscriber.fields.clone();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .traced()
+            .with_namespace("trace-test").unwrap();
+        vault.store("trace-token", "{number: 4111111111111111}").await.unwrap();
+        assert!(vault.retrieve("trace-missing").await.is_err());
+
+        let recorded = fields.lock().unwrap().join("\n");
+        for field in &[
+            "data_vault.backend = \"redis\"",
+            "data_vault.operation = \"store\"",
+            "data_vault.tenant = \"trace-test\"",
+            "data_vault.outcome = \"success\"",
+            "data_vault.operation = \"retrieve\"",
+            "data_vault.outcome = \"not_found\"",
+            "data_vault.duration_ms",
+            "data_vault.pool.pool = \"primary\"",
+            "data_vault.pool.outcome = \"success\"",
+        ] {
+            assert!(recorded.contains(field), "{} not in\n{}", field, recorded);
+        }
+        assert!(!recorded.contains("trace-token"));
+        assert!(!recorded.contains("4111111111111111"));
+
+        let vault = vault.with_token_field(true);
+        vault.exists("trace-token").await.unwrap();
+        assert!(fields.lock().unwrap().iter().any(|field| field == "data_vault.token = \"trac****oken\""));
+        vault.inner().delete_many(&["trace-token".to_string()]).await.unwrap();
+    }
+}
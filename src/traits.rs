@@ -1,33 +1,949 @@
 use async_trait::async_trait;
 use credit_card::CreditCard;
-use deadpool_redis::PoolError as RedisPoolError;
-use deadpool_postgres::PoolError as PostgresPoolError;
+use crate::error::DataVaultError;
+use crate::stats::VaultStats;
+use crate::health::HealthReport;
+use crate::metadata::{self, CustomMetadata, RecordMetadata};
+use crate::validation::{self, ValidationError};
+use crate::alias;
+use crate::purge::PurgeReport;
+use crate::stream::{RecordPage, RecordStream};
+use crate::export;
+use crate::backup::{self, BackupManifest, RestoreReport};
+use crate::hooks::{HookedDataVault, VaultHooks};
+#[cfg(feature = "tracing")]
+use crate::trace::TracedDataVault;
+#[cfg(feature = "otel")]
+use crate::otel::OtelDataVault;
+#[cfg(feature = "bin")]
+use crate::bin::{BinLookupVault, BinSource};
+use crate::record::{self, VaultRecord};
+use crate::network_token::NetworkToken;
+use crate::masked::MaskedCreditCard;
+use crate::display_info::{DisplayInfo, StoredDisplayInfo};
+use crate::summary::CardSummary;
+use crate::sensitive::Sensitive;
+use crate::fingerprint::StoreOutcome;
 use std::error;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
+/// This is what a Data Vault can do
+/// It's fundamental purpose is to store and retrieve
+/// data in a secure encrypted manner
+#[async_trait]
+pub trait DataVault: Send + Sync {
+    fn new() -> Result<Self, Box<dyn error::Error>>
+        where Self: std::marker::Sized;
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError>;
+    /// `store` unless a record is stored under `token` already, so a
+    /// retried write never replaces a newer one
+    /// returns:
+    ///     * whether `string` was stored
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError>;
+    /// `store` with an expiry `ttl` from now, written together with
+    /// the string so it is never kept without one
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError>;
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError>;
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError>;
+    /// Store the credit card under a token chosen by the caller,
+    /// e.g. one minted by a system being migrated from
+    /// returns:
+    ///     * `DataVaultError::InvalidToken` unless `token` is 1 to 64
+    ///       characters of `[A-Za-z0-9_.-]`
+    ///     * `DataVaultError::AlreadyExists` when a record is stored
+    ///       under `token` and `overwrite` is false
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError>;
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError>;
+    async fn retrieve_credit_card(&self, token: &str)  -> Result<CreditCard, DataVaultError>;
+    /// Whether a record is stored under `token`, without reading
+    /// or decrypting it
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError>;
+    /// Every store of a token increases its version, new records
+    /// start at version 1
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError>;
+    /// The credit card with its `RecordMetadata` in one round trip
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError>;
+    /// Fails with `DataVaultError::Conflict` instead of overwriting a
+    /// record that changed since `expected_version` was read
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError>;
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError>;
+    /// Hide a record from retrieval and physically remove it once
+    /// the retention period (`DATA_VAULT_RETENTION_SECS`) is over
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError>;
+    /// Let the record of `token` expire `ttl` from now, or never for
+    /// `None`, without rewriting it
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError>;
+    /// Physically remove the records of `tokens` right away, soft
+    /// deleted ones included
+    /// returns:
+    ///     * how many of the tokens had a record that could be read,
+    ///       tokens that are not stored are skipped
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError>;
+    /// Physically remove the records of this namespace whose
+    /// retention period is over
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError>;
+    /// Every record of this namespace as `(token, ciphertext)`, read
+    /// in batches so vaults larger than memory can be processed
+    fn iter_records(&self) -> RecordStream<'_, Vec<u8>>;
+    /// Like `iter_records` but decrypted
+    fn iter_decrypted_records(&self) -> RecordStream<'_, String>;
+    /// About `limit` decrypted records starting at `cursor`, `None`
+    /// starts at the beginning.  Cursors are opaque strings that stay
+    /// valid across vault instances, so a job can be resumed later.
+    async fn decrypted_records_page(&self, cursor: Option<&str>, limit: usize) -> Result<RecordPage<String>, DataVaultError>;
+    async fn count(&self) -> Result<u64, DataVaultError>;
+    async fn stats(&self) -> Result<VaultStats, DataVaultError>;
+    /// Check that the back end answers and the key material works,
+    /// for readiness and liveness probes, see `HealthReport`.  The
+    /// connection is not retried, so an outage shows right away.
+    async fn health_check(&self) -> HealthReport;
 
-#[derive(Debug)]
-pub enum PoolErrors {
-    RedisPoolError,
-    PostgresPoolError
-}
+    /// The namespace every operation of this vault is scoped to
+    fn namespace(&self) -> &str;
+
+    /// The back end keeping the records, `redis` or `postgres`,
+    /// vaults wrapping another report the wrapped one's
+    fn backend(&self) -> &'static str;
+
+    /// A vault sharing this one's pool, encryption and tokenizer
+    /// but scoped to `namespace`.  Records in one namespace can not
+    /// be read, rotated or counted from another, the same token may
+    /// be stored in several namespaces.
+    /// returns:
+    ///     * `DataVaultError::InvalidNamespace` unless `namespace` is
+    ///       `[A-Za-z0-9_.-]`, at most 64 characters and not `data_vault`
+    fn with_namespace(&self, namespace: &str) -> Result<Self, DataVaultError>
+        where Self: std::marker::Sized;
+
+    /// A vault scoped to the side namespace of `kind` of this one,
+    /// keeping data about its records apart from them, e.g. their
+    /// custom metadata
+    ///
+    /// Side namespaces are no namespaces a caller can choose, vaults
+    /// wrapping another scope the wrapped one.
+    #[doc(hidden)]
+    fn with_side_namespace(&self, kind: &str) -> Result<Self, DataVaultError>
+        where Self: std::marker::Sized;
+
+    /// This vault calling `hooks` after each operation
+    /// # example
+    /// ```rust
+    /// use data_vault::{DataVault, RedisDataVault, VaultEvent, VaultHooks};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use std::sync::Arc;
+    ///
+    /// struct PrintHooks;
+    ///
+    /// impl VaultHooks for PrintHooks {
+    ///     fn on_event(&self, event: &VaultEvent) {
+    ///         println!("{:?} {:?} {:?}", event.operation, event.token, event.outcome)
+    ///     }
+    /// }
+    ///
+    /// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+    ///     .with_hooks(Arc::new(PrintHooks));
+    /// ```
+    fn with_hooks(self, hooks: Arc<dyn VaultHooks>) -> HookedDataVault<Self>
+        where Self: std::marker::Sized
+    {
+        HookedDataVault::new(self, hooks)
+    }
+
+    /// This vault running each operation in a `tracing` span, see
+    /// `TracedDataVault`
+    /// # example
+    /// ```rust
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+    ///     .traced();
+    /// ```
+    #[cfg(feature = "tracing")]
+    fn traced(self) -> TracedDataVault<Self>
+        where Self: std::marker::Sized
+    {
+        TracedDataVault::new(self)
+    }
+
+    /// This vault reporting each operation to OpenTelemetry, see
+    /// `OtelDataVault`
+    /// # example
+    /// ```rust
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+    ///     .with_otel();
+    /// ```
+    #[cfg(feature = "otel")]
+    fn with_otel(self) -> OtelDataVault<Self>
+        where Self: std::marker::Sized
+    {
+        OtelDataVault::new(self)
+    }
+
+    /// This vault keeping the `BinInfo` of the cards it stores, looked
+    /// up in `source`, see `BinLookupVault`
+    #[cfg(feature = "bin")]
+    fn with_bin_lookup(self, source: Arc<dyn BinSource>) -> BinLookupVault<Self>
+        where Self: std::marker::Sized
+    {
+        BinLookupVault::new(self, source)
+    }
+
+    /// Like `retrieve` but a missing token is `Ok(None)`
+    /// instead of `Err(DataVaultError::NotFound)`
+    async fn try_retrieve(&self, token: &str) -> Result<Option<String>, DataVaultError> {
+        match self.retrieve(token).await {
+            Ok(string) => Ok(Some(string)),
+            Err(DataVaultError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like `retrieve_credit_card` but a missing token is `Ok(None)`
+    /// instead of `Err(DataVaultError::NotFound)`
+    async fn try_retrieve_credit_card(&self, token: &str) -> Result<Option<CreditCard>, DataVaultError> {
+        match self.retrieve_credit_card(token).await {
+            Ok(credit_card) => Ok(Some(credit_card)),
+            Err(DataVaultError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store many credit cards, see `store_credit_card`
+    ///
+    /// Back ends that can write them in one round trip override this,
+    /// the default stores one card after the other.
+    /// returns:
+    ///     * the tokens in the order of `credit_cards`
+    ///     * the first error, cards before it may be stored
+    async fn store_credit_cards(&self, credit_cards: &[CreditCard]) -> Result<Vec<String>, DataVaultError> {
+        let mut tokens = Vec::with_capacity(credit_cards.len());
+        for credit_card in credit_cards {
+            tokens.push(self.store_credit_card(credit_card).await?);
+        }
+        Ok(tokens)
+    }
+
+    /// Retrieve many credit cards, see `retrieve_credit_card`
+    ///
+    /// Back ends that can read them in one round trip override this,
+    /// the default retrieves one card after the other.
+    /// returns:
+    ///     * the cards in the order of `tokens`, `None` for tokens
+    ///       with nothing stored under them
+    async fn retrieve_credit_cards(&self, tokens: &[String]) -> Result<Vec<Option<CreditCard>>, DataVaultError> {
+        let mut credit_cards = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            credit_cards.push(self.try_retrieve_credit_card(token).await?);
+        }
+        Ok(credit_cards)
+    }
+
+    /// The credit card stored under `token` with its number masked
+    /// and without its security code, for showing it to the
+    /// cardholder or printing it on a receipt, see `MaskedCreditCard`
+    async fn retrieve_masked_credit_card(&self, token: &str) -> Result<MaskedCreditCard, DataVaultError> {
+        Ok(MaskedCreditCard::from(self.retrieve_credit_card(token).await?))
+    }
+
+    /// The credit card stored under `token` wrapped so `Debug` and
+    /// `Display` mask its number and leave out its security code,
+    /// `Sensitive::expose` reaches the card
+    async fn retrieve_sensitive_credit_card(&self, token: &str) -> Result<Sensitive<CreditCard>, DataVaultError> {
+        Ok(Sensitive::new(self.retrieve_credit_card(token).await?))
+    }
+
+    /// Store the credit card with `metadata`, kept encrypted in the
+    /// side namespace `<namespace>~metadata` under the card's token
+    ///
+    /// The metadata moves with the card on `rotate_token` and is
+    /// deleted with it by `delete_many`.
+    /// returns:
+    ///     * the token of the card
+    ///     * `DataVaultError::Validation` for metadata over the limits
+    ///       of `CustomMetadata`, nothing is stored then
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{CustomMetadata, DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let mut metadata = CustomMetadata::new();
+    /// metadata.insert("customer_id".to_string(), "cus_42".to_string());
+    /// let token = data_vault.store_credit_card_with_custom_metadata(&credit_card, &metadata).await.unwrap();
+    /// let (credit_card, metadata) = data_vault.retrieve_credit_card_with_custom_metadata(&token).await.unwrap();
+    /// ```
+    async fn store_credit_card_with_custom_metadata(&self, credit_card: &CreditCard, metadata: &CustomMetadata) -> Result<String, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        metadata::validate_custom_metadata(metadata)?;
+        let token = self.store_credit_card(credit_card).await?;
+        self.set_custom_metadata(&token, metadata).await?;
+        Ok(token)
+    }
+
+    /// Replace the metadata of the record stored under `token`, an
+    /// empty `metadata` removes it
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    ///     * `DataVaultError::Validation` for metadata over the limits
+    ///       of `CustomMetadata`
+    async fn set_custom_metadata(&self, token: &str, metadata: &CustomMetadata) -> Result<(), DataVaultError>
+        where Self: std::marker::Sized
+    {
+        metadata::validate_custom_metadata(metadata)?;
+        if !self.exists(token).await? {
+            return Err(DataVaultError::NotFound)
+        }
+        let side = self.with_side_namespace("metadata")?;
+        match metadata.is_empty() {
+            true => side.delete_many(&[token.to_string()]).await.map(|_| ()),
+            false => side.store(token, &serde_json::to_string(metadata)?).await,
+        }
+    }
+
+    /// The metadata of the record stored under `token`, empty for
+    /// records stored without
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    async fn retrieve_custom_metadata(&self, token: &str) -> Result<CustomMetadata, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        if !self.exists(token).await? {
+            return Err(DataVaultError::NotFound)
+        }
+        let side = self.with_side_namespace("metadata")?;
+        match side.try_retrieve(token).await? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(CustomMetadata::new()),
+        }
+    }
+
+    /// The credit card stored under `token` with its metadata
+    async fn retrieve_credit_card_with_custom_metadata(&self, token: &str) -> Result<(CreditCard, CustomMetadata), DataVaultError>
+        where Self: std::marker::Sized
+    {
+        let credit_card = self.retrieve_credit_card(token).await?;
+        Ok((credit_card, self.retrieve_custom_metadata(token).await?))
+    }
+
+    /// The brand, last 4 digits, expiry and custom metadata of the
+    /// card stored under `token`, without decrypting the card
+    ///
+    /// Cards are stored with a `CardSummary` in the side namespace
+    /// `<namespace>~summary`, only that and the metadata are read.
+    /// Cards stored before summaries were kept are decrypted in full.
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let summary = data_vault.retrieve_summary(&token).await.unwrap();
+    /// println!("{} ending in {}", summary.brand.unwrap_or_default(), summary.last_four);
+    /// ```
+    async fn retrieve_summary(&self, token: &str) -> Result<CardSummary, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        // checks the card is live, its summary may outlive it
+        let metadata = self.retrieve_custom_metadata(token).await?;
+        let side = self.with_side_namespace("summary")?;
+        let mut summary: CardSummary = match side.try_retrieve(token).await? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => CardSummary::from(&self.retrieve_credit_card(token).await?),
+        };
+        summary.metadata = metadata;
+        Ok(summary)
+    }
+
+    /// Set the nickname and card art id a wallet shows for the card
+    /// stored under `token`, kept in the side namespace
+    /// `<namespace>~display`, `None` for both removes them
+    ///
+    /// Like custom metadata they move with the card on `rotate_token`
+    /// and are deleted with it by `delete_many`.
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    ///     * `DataVaultError::Validation` for a nickname or art id over
+    ///       `MAX_DISPLAY_INFO_LENGTH` characters
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.set_display_info(&token, Some("Work Visa"), Some("art-42")).await.unwrap();
+    /// // Work Visa •••• 1111
+    /// println!("{}", data_vault.get_display_info(&token).await.unwrap());
+    /// ```
+    async fn set_display_info(&self, token: &str, nickname: Option<&str>, art_id: Option<&str>) -> Result<(), DataVaultError>
+        where Self: std::marker::Sized
+    {
+        let info = StoredDisplayInfo::new(nickname, art_id)?;
+        if !self.exists(token).await? {
+            return Err(DataVaultError::NotFound)
+        }
+        let side = self.with_side_namespace("display")?;
+        match info == StoredDisplayInfo::default() {
+            true => side.delete_many(&[token.to_string()]).await.map(|_| ()),
+            false => side.store(token, &serde_json::to_string(&info)?).await,
+        }
+    }
+
+    /// The nickname and card art id of the card stored under `token`
+    /// with its brand and last 4 digits, the card number never leaves
+    /// the vault
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    async fn get_display_info(&self, token: &str) -> Result<DisplayInfo, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        let summary = self.retrieve_summary(token).await?;
+        let side = self.with_side_namespace("display")?;
+        let info: StoredDisplayInfo = match side.try_retrieve(token).await? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => StoredDisplayInfo::default(),
+        };
+        Ok(DisplayInfo {
+            nickname: info.nickname,
+            art_id: info.art_id,
+            brand: summary.brand,
+            last_four: summary.last_four,
+        })
+    }
+
+    /// Keep the security code of the card stored under `token` for
+    /// `ttl`, e.g. until authorization is done
+    ///
+    /// The code is kept apart from the card, encrypted in the side
+    /// namespace `<namespace>~cvv`, and read once with
+    /// `retrieve_and_burn_cvv`.  `rotate_token` and `delete_many`
+    /// delete it with the card.
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    ///     * `DataVaultError::Validation` unless the code is 3 or 4
+    ///       digits and `ttl` at most `MAX_CVV_TTL`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    /// use std::time::Duration;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// data_vault.store_cvv(&token, "123", Duration::from_secs(300)).await.unwrap();
+    /// let cvv = data_vault.retrieve_and_burn_cvv(&token).await.unwrap();
+    /// ```
+    async fn store_cvv(&self, token: &str, cvv: &str, ttl: Duration) -> Result<(), DataVaultError>
+        where Self: std::marker::Sized
+    {
+        validation::validate_cvv(cvv, ttl)?;
+        if !self.exists(token).await? {
+            return Err(DataVaultError::NotFound)
+        }
+        // never keep a security code without an expiry
+        self.with_side_namespace("cvv")?.store_expiring(token, cvv, ttl).await
+    }
+
+    /// The security code kept for `token` by `store_cvv`, deleted as
+    /// it is read so only one caller ever gets it
+    /// returns:
+    ///     * `DataVaultError::NotFound` when no code is kept, it
+    ///       expired or was read before
+    async fn retrieve_and_burn_cvv(&self, token: &str) -> Result<String, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        let side = self.with_side_namespace("cvv")?;
+        let cvv = side.try_retrieve(token).await?;
+        // only the caller that deletes the code may use it
+        match (cvv, side.delete_many(&[token.to_string()]).await?) {
+            (Some(cvv), 1) => Ok(cvv),
+            _ => Err(DataVaultError::NotFound),
+        }
+    }
+
+    /// Store the credit card unless a card with its number is stored
+    /// in this namespace already, e.g. on file for the same customer
+    ///
+    /// Cards are found by the fingerprint of their number, which only
+    /// deterministic tokenizers make, see `Tokenizer::fingerprint`.
+    /// Vaults with those look up the fingerprint index in
+    /// `store_credit_card` and `tokenize` as well, and index the new
+    /// number of a card in `update_credit_card_if_version`, other
+    /// vaults always store a new card.
+    /// returns:
+    ///     * `StoreOutcome::Duplicate` with the token of the stored card
+    ///     * `StoreOutcome::Created` with the token of the new card
+    async fn store_credit_card_deduplicated(&self, credit_card: &CreditCard) -> Result<StoreOutcome, DataVaultError> {
+        match self.tokenize(credit_card).await? {
+            (token, true) => Ok(StoreOutcome::Created(token)),
+            (token, false) => Ok(StoreOutcome::Duplicate(token)),
+        }
+    }
+
+    /// Consolidate duplicate records of one card into the first of
+    /// `tokens`, e.g. after importing a portfolio from several sources
+    ///
+    /// The other tokens become aliases of the surviving one, kept in
+    /// the side namespace `<namespace>~alias`, so `retrieve_credit_card`
+    /// and `resolve_token` still find the card under them.  Their
+    /// custom metadata is merged into the survivor's, which wins on
+    /// keys both have, then their records are deleted.  Tokens that
+    /// are aliases already are merged as the card they resolve to.
+    /// returns:
+    ///     * the surviving token
+    ///     * `DataVaultError::NotFound` when `tokens` is empty or
+    ///       nothing is stored under one of them
+    ///     * `DataVaultError::Validation` when the cards do not have
+    ///       the same number, nothing is changed then
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let token = data_vault.merge(&[crm_token, billing_token]).await.unwrap();
+    /// assert_eq!(data_vault.resolve_token(&billing_token).await.unwrap(), token);
+    /// ```
+    async fn merge(&self, tokens: &[String]) -> Result<String, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        let (survivor, duplicates) = tokens.split_first().ok_or(DataVaultError::NotFound)?;
+        let survivor = self.resolve_token(survivor).await?;
+        let number = self.retrieve_credit_card(&survivor).await?.number;
+
+        let mut merged: Vec<String> = Vec::new();
+        let mut metadata = CustomMetadata::new();
+        for token in duplicates {
+            let token = self.resolve_token(token).await?;
+            if token == survivor || merged.contains(&token) {
+                continue
+            }
+            if self.retrieve_credit_card(&token).await?.number != number {
+                return Err(DataVaultError::Validation(ValidationError::NotSameCard))
+            }
+            for (key, value) in self.retrieve_custom_metadata(&token).await? {
+                metadata.entry(key).or_insert(value);
+            }
+            merged.push(token);
+        }
+        if merged.is_empty() {
+            return Ok(survivor)
+        }
+
+        if !metadata.is_empty() {
+            metadata.extend(self.retrieve_custom_metadata(&survivor).await?);
+            self.set_custom_metadata(&survivor, &metadata).await?;
+        }
+        // aliases first, so a merged token never resolves to nothing
+        let aliases = alias::aliases(self)?;
+        for token in &merged {
+            aliases.store(token, &survivor).await?;
+        }
+        self.delete_many(&merged).await?;
+        Ok(survivor)
+    }
+
+    /// The token the card of `token` is stored under, `token` itself
+    /// unless it was merged into another card with `merge`
+    /// returns:
+    ///     * `DataVaultError::NotFound` when no card is stored under
+    ///       `token` or the card it was merged into
+    async fn resolve_token(&self, token: &str) -> Result<String, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        if self.exists(token).await? {
+            return Ok(token.to_string())
+        }
+        alias::resolve_alias(self, token).await?.ok_or(DataVaultError::NotFound)
+    }
+
+    /// Store any `VaultRecord`, expiring after its `ttl`
+    /// returns:
+    ///     * the token of the new record
+    ///     * `DataVaultError::Validation` when the record fails its
+    ///       `validate`
+    /// # example
+    /// ```rust,ignore
+    /// use data_vault::{DataVault, RedisDataVault, VaultRecord};
+    /// use data_vault::encryption::AesGcmSivEncryption;
+    /// use data_vault::tokenizer::Blake3Tokenizer;
+    ///
+    /// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+    /// let token = data_vault.store_record(&passport).await.unwrap();
+    /// let passport: Passport = data_vault.retrieve_record(&token).await.unwrap();
+    /// ```
+    async fn store_record<R: VaultRecord>(&self, record: &R) -> Result<String, DataVaultError> {
+        if let Some(credit_card) = record::as_credit_card(record) {
+            return self.store_credit_card(credit_card).await
+        }
+
+        record.validate()?;
+        let token = record.generate_token();
+        let string = serde_json::to_string(record)?;
+        match record.ttl() {
+            // never keep a record meant to expire without its expiry
+            Some(ttl) => self.store_expiring(&token, &string, ttl).await?,
+            None => self.store(&token, &string).await?,
+        }
+        Ok(token)
+    }
+
+    /// Get a record stored with `store_record`
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under `token`
+    ///     * `DataVaultError::Serialization` when the record is of another kind
+    async fn retrieve_record<R: VaultRecord>(&self, token: &str) -> Result<R, DataVaultError> {
+        if record::is_credit_card::<R>() {
+            let credit_card = self.retrieve_credit_card(token).await?;
+            return Ok(record::from_credit_card(credit_card).expect("R is CreditCard"))
+        }
+
+        let string = self.retrieve(token).await?;
+        Ok(serde_json::from_str(&string)?)
+    }
+
+    /// Like `retrieve_record` but a missing token is `Ok(None)`
+    /// instead of `Err(DataVaultError::NotFound)`
+    async fn try_retrieve_record<R: VaultRecord>(&self, token: &str) -> Result<Option<R>, DataVaultError> {
+        match self.retrieve_record(token).await {
+            Ok(record) => Ok(Some(record)),
+            Err(DataVaultError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 
-impl From<RedisPoolError> for PoolErrors {
-    fn from(_: RedisPoolError) -> Self {PoolErrors::RedisPoolError}
+    /// Get a record stored with `store_record` and delete it, so only
+    /// one caller ever gets it, e.g. `Track2Data` forwarded to the
+    /// switch
+    /// returns:
+    ///     * `DataVaultError::NotFound` when nothing is stored under
+    ///       `token` or another caller took it first
+    ///     * `DataVaultError::Serialization` when the record is of
+    ///       another kind, it is not deleted then
+    async fn take_record<R: VaultRecord>(&self, token: &str) -> Result<R, DataVaultError> {
+        let record = self.retrieve_record(token).await?;
+        match self.delete_many(&[token.to_string()]).await? {
+            1 => Ok(record),
+            _ => Err(DataVaultError::NotFound),
+        }
+    }
+
+    /// Store `network_token` linked to the credit card stored under
+    /// `card_token`, its `card_token` is set to it
+    /// returns:
+    ///     * the token of the network token
+    ///     * `DataVaultError::NotFound` when no credit card is stored
+    ///       under `card_token`
+    async fn store_network_token(&self, card_token: &str, network_token: &NetworkToken) -> Result<String, DataVaultError> {
+        if !self.exists(card_token).await? {
+            return Err(DataVaultError::NotFound)
+        }
+        let network_token = NetworkToken {
+            card_token: Some(card_token.to_string()),
+            ..network_token.clone()
+        };
+        self.store_record(&network_token).await
+    }
+
+    /// `store` in `namespace`
+    async fn store_in(&self, namespace: &str, token: &str, string: &str) -> Result<(), DataVaultError>
+        where Self: std::marker::Sized
+    {
+        self.with_namespace(namespace)?.store(token, string).await
+    }
+
+    /// `store_credit_card` in `namespace`
+    async fn store_credit_card_in(&self, namespace: &str, credit_card: &CreditCard) -> Result<String, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        self.with_namespace(namespace)?.store_credit_card(credit_card).await
+    }
+
+    /// `retrieve` from `namespace`
+    async fn retrieve_from(&self, namespace: &str, token: &str) -> Result<String, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        self.with_namespace(namespace)?.retrieve(token).await
+    }
+
+    /// `retrieve_credit_card` from `namespace`
+    async fn retrieve_credit_card_from(&self, namespace: &str, token: &str) -> Result<CreditCard, DataVaultError>
+        where Self: std::marker::Sized
+    {
+        self.with_namespace(namespace)?.retrieve_credit_card(token).await
+    }
+
+    /// Write every record of this namespace to `writer` as a
+    /// versioned, encrypted and integrity protected export
+    ///
+    /// `export_key` is a high entropy secret, not a password, the
+    /// same key is needed to `import` the export.  Records are
+    /// decrypted with this vault's encryption and re-encrypted for
+    /// the export, so it can be imported into a vault with other keys.
+    /// returns:
+    ///     * the number of exported records
+    async fn export<W>(&self, writer: W, export_key: &[u8]) -> Result<u64, DataVaultError>
+        where
+            Self: std::marker::Sized,
+            W: Write + Send,
+    {
+        export::export(self, writer, export_key).await
+    }
+
+    /// Store every record of an export made by `export` in this
+    /// namespace, existing tokens are overwritten
+    /// returns:
+    ///     * the number of imported records
+    ///     * `DataVaultError::InvalidExport` when the export is damaged,
+    ///       truncated or was made with another key, records read
+    ///       before the damage are already stored
+    async fn import<R>(&self, reader: R, export_key: &[u8]) -> Result<u64, DataVaultError>
+        where
+            Self: std::marker::Sized,
+            R: Read + Send,
+    {
+        export::import(self, reader, export_key).await
+    }
+
+    /// Back up every record of this namespace to the directory
+    /// `destination`, created if need be, as an `export` made with
+    /// `backup_key` and a `BackupManifest` of the records
+    ///
+    /// Records are read as `iter_decrypted_records` hands them out,
+    /// each one as it was when it was read, records stored while
+    /// the backup runs may or may not be in it.  The manifest is
+    /// written last, a directory without one holds no backup.
+    /// returns:
+    ///     * the manifest
+    ///     * `DataVaultError::Io` when `destination` holds a backup already
+    async fn backup<P>(&self, destination: P, backup_key: &[u8]) -> Result<BackupManifest, DataVaultError>
+        where
+            Self: std::marker::Sized,
+            P: AsRef<Path> + Send,
+    {
+        backup::backup(self, destination.as_ref(), backup_key).await
+    }
+
+    /// Store every record of the backup in the directory `source`
+    /// in this namespace, existing tokens are overwritten
+    ///
+    /// The records file is checked against the manifest before
+    /// anything is stored, the records against it once they are
+    /// stored, and every record is read back from the vault.
+    /// returns:
+    ///     * the records restored and read back unchanged
+    ///     * `DataVaultError::InvalidExport` when the backup is damaged,
+    ///       does not match its manifest or was made with another key
+    async fn restore<P>(&self, source: P, backup_key: &[u8]) -> Result<RestoreReport, DataVaultError>
+        where
+            Self: std::marker::Sized,
+            P: AsRef<Path> + Send,
+    {
+        backup::restore(self, source.as_ref(), backup_key).await
+    }
 }
 
-impl From<PostgresPoolError> for PoolErrors {
-    fn from(_: PostgresPoolError) -> Self {PoolErrors::PostgresPoolError}
+/// The operations of `DataVault` on one record and the vault's
+/// state, usable as `dyn DataVaultDyn` where the vault type should
+/// not show, e.g. in web handlers
+///
+/// `DataVault` itself has generic methods and constructors, which a
+/// trait object can not have.  Every `DataVault` is a `DataVaultDyn`.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{DataVault, DataVaultDyn, RedisDataVault};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+/// use std::sync::Arc;
+///
+/// let vault: Arc<dyn DataVaultDyn> = Arc::new(RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap());
+/// let token = vault.store_credit_card(&credit_card).await.unwrap();
+/// ```
+#[async_trait]
+pub trait DataVaultDyn: Send + Sync {
+    /// see `DataVault::store`
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError>;
+    /// see `DataVault::store_if_absent`
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError>;
+    /// see `DataVault::store_expiring`
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError>;
+    /// see `DataVault::store_credit_card`
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError>;
+    /// see `DataVault::tokenize`
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError>;
+    /// see `DataVault::store_credit_card_deduplicated`
+    async fn store_credit_card_deduplicated(&self, credit_card: &CreditCard) -> Result<StoreOutcome, DataVaultError>;
+    /// see `DataVault::store_credit_card_with_token`
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError>;
+    /// see `DataVault::retrieve`
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError>;
+    /// see `DataVault::retrieve_credit_card`
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError>;
+    /// see `DataVault::store_credit_cards`
+    async fn store_credit_cards(&self, credit_cards: &[CreditCard]) -> Result<Vec<String>, DataVaultError>;
+    /// see `DataVault::retrieve_credit_cards`
+    async fn retrieve_credit_cards(&self, tokens: &[String]) -> Result<Vec<Option<CreditCard>>, DataVaultError>;
+    /// see `DataVault::retrieve_masked_credit_card`
+    async fn retrieve_masked_credit_card(&self, token: &str) -> Result<MaskedCreditCard, DataVaultError>;
+    /// see `DataVault::retrieve_sensitive_credit_card`
+    async fn retrieve_sensitive_credit_card(&self, token: &str) -> Result<Sensitive<CreditCard>, DataVaultError>;
+    /// see `DataVault::exists`
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError>;
+    /// see `DataVault::retrieve_credit_card_with_version`
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError>;
+    /// see `DataVault::retrieve_with_metadata`
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError>;
+    /// see `DataVault::update_credit_card_if_version`
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError>;
+    /// see `DataVault::rotate_token`
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError>;
+    /// see `DataVault::soft_delete`
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError>;
+    /// see `DataVault::touch`
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError>;
+    /// see `DataVault::delete_many`
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError>;
+    /// see `DataVault::purge_expired`
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError>;
+    /// see `DataVault::count`
+    async fn count(&self) -> Result<u64, DataVaultError>;
+    /// see `DataVault::stats`
+    async fn stats(&self) -> Result<VaultStats, DataVaultError>;
+    /// see `DataVault::health_check`
+    async fn health_check(&self) -> HealthReport;
+    /// see `DataVault::namespace`
+    fn namespace(&self) -> &str;
+    /// see `DataVault::backend`
+    fn backend(&self) -> &'static str;
 }
 
-/// This is what a Data Vault can do
-/// It's fundamental purpose is to store and retrieve
-/// data in a secure encrypted manner
 #[async_trait]
-pub trait DataVault {
-    fn new() -> Result<Self, Box<dyn error::Error>>
-        where Self: std::marker::Sized;
-    async fn store(&self, token: &String, string: &String) -> Result<(), PoolErrors>;
-    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, PoolErrors>;
-    async fn retrieve(&self, token: &String) -> Result<String, PoolErrors>;
-    async fn retrieve_credit_card(&self, token: &String)  -> Result<CreditCard, PoolErrors>;
+impl<V: DataVault> DataVaultDyn for V {
+    async fn store(&self, token: &str, string: &str) -> Result<(), DataVaultError> {
+        DataVault::store(self, token, string).await
+    }
+
+    async fn store_if_absent(&self, token: &str, string: &str) -> Result<bool, DataVaultError> {
+        DataVault::store_if_absent(self, token, string).await
+    }
+
+    async fn store_expiring(&self, token: &str, string: &str, ttl: Duration) -> Result<(), DataVaultError> {
+        DataVault::store_expiring(self, token, string, ttl).await
+    }
+
+    async fn store_credit_card(&self, credit_card: &CreditCard) -> Result<String, DataVaultError> {
+        DataVault::store_credit_card(self, credit_card).await
+    }
+
+    async fn tokenize(&self, credit_card: &CreditCard) -> Result<(String, bool), DataVaultError> {
+        DataVault::tokenize(self, credit_card).await
+    }
+
+    async fn store_credit_card_deduplicated(&self, credit_card: &CreditCard) -> Result<StoreOutcome, DataVaultError> {
+        DataVault::store_credit_card_deduplicated(self, credit_card).await
+    }
+
+    async fn store_credit_card_with_token(&self, token: &str, credit_card: &CreditCard, overwrite: bool) -> Result<(), DataVaultError> {
+        DataVault::store_credit_card_with_token(self, token, credit_card, overwrite).await
+    }
+
+    async fn retrieve(&self, token: &str) -> Result<String, DataVaultError> {
+        DataVault::retrieve(self, token).await
+    }
+
+    async fn retrieve_credit_card(&self, token: &str) -> Result<CreditCard, DataVaultError> {
+        DataVault::retrieve_credit_card(self, token).await
+    }
+
+    async fn store_credit_cards(&self, credit_cards: &[CreditCard]) -> Result<Vec<String>, DataVaultError> {
+        DataVault::store_credit_cards(self, credit_cards).await
+    }
+
+    async fn retrieve_credit_cards(&self, tokens: &[String]) -> Result<Vec<Option<CreditCard>>, DataVaultError> {
+        DataVault::retrieve_credit_cards(self, tokens).await
+    }
+
+    async fn retrieve_masked_credit_card(&self, token: &str) -> Result<MaskedCreditCard, DataVaultError> {
+        DataVault::retrieve_masked_credit_card(self, token).await
+    }
+
+    async fn retrieve_sensitive_credit_card(&self, token: &str) -> Result<Sensitive<CreditCard>, DataVaultError> {
+        DataVault::retrieve_sensitive_credit_card(self, token).await
+    }
+
+    async fn exists(&self, token: &str) -> Result<bool, DataVaultError> {
+        DataVault::exists(self, token).await
+    }
+
+    async fn retrieve_credit_card_with_version(&self, token: &str) -> Result<(CreditCard, u64), DataVaultError> {
+        DataVault::retrieve_credit_card_with_version(self, token).await
+    }
+
+    async fn retrieve_with_metadata(&self, token: &str) -> Result<(CreditCard, RecordMetadata), DataVaultError> {
+        DataVault::retrieve_with_metadata(self, token).await
+    }
+
+    async fn update_credit_card_if_version(&self, token: &str, credit_card: &CreditCard, expected_version: u64) -> Result<u64, DataVaultError> {
+        DataVault::update_credit_card_if_version(self, token, credit_card, expected_version).await
+    }
+
+    async fn rotate_token(&self, token: &str) -> Result<String, DataVaultError> {
+        DataVault::rotate_token(self, token).await
+    }
+
+    async fn soft_delete(&self, token: &str) -> Result<(), DataVaultError> {
+        DataVault::soft_delete(self, token).await
+    }
+
+    async fn touch(&self, token: &str, ttl: Option<Duration>) -> Result<(), DataVaultError> {
+        DataVault::touch(self, token, ttl).await
+    }
+
+    async fn delete_many(&self, tokens: &[String]) -> Result<usize, DataVaultError> {
+        DataVault::delete_many(self, tokens).await
+    }
+
+    async fn purge_expired(&self) -> Result<PurgeReport, DataVaultError> {
+        DataVault::purge_expired(self).await
+    }
+
+    async fn count(&self) -> Result<u64, DataVaultError> {
+        DataVault::count(self).await
+    }
+
+    async fn stats(&self) -> Result<VaultStats, DataVaultError> {
+        DataVault::stats(self).await
+    }
+
+    async fn health_check(&self) -> HealthReport {
+        DataVault::health_check(self).await
+    }
+
+    fn namespace(&self) -> &str {
+        DataVault::namespace(self)
+    }
+
+    fn backend(&self) -> &'static str {
+        DataVault::backend(self)
+    }
 }
\ No newline at end of file
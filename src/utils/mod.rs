@@ -1,3 +1,3 @@
 mod random;
 
-pub use random::Salt;
\ No newline at end of file
+pub use random::{Salt, RandomToken};
\ No newline at end of file
@@ -20,13 +20,35 @@ impl Salt {
     }
 }
 
+pub struct RandomToken;
+impl RandomToken {
+    /// creates a random token in the same 64 character hex
+    /// format the blake3 tokenizers produce
+    /// ```rust
+    /// use data_vault::utils::RandomToken;
+    ///
+    /// let token = RandomToken::generate();
+    /// ```
+    pub fn generate() -> String {
+        let bytes: [u8; 32] = thread_rng().gen();
+        hex::encode(bytes)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::utils::Salt;
+    use crate::utils::{Salt, RandomToken};
 
     #[test]
     fn test_salt_generate() {
         let salt = Salt::generate(12);
         assert_eq!(salt.len(), 12)
     }
+
+    #[test]
+    fn test_random_token_generate() {
+        let token = RandomToken::generate();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, RandomToken::generate())
+    }
 }
\ No newline at end of file
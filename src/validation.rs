@@ -0,0 +1,609 @@
+use credit_card::CreditCard;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use crate::brand::{detect_brand, CardBrand};
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Why a card or bank account was not stored, see `validate_number`,
+/// `normalize_expiry` and `validate_routing_number`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// the number contains characters other than digits
+    NotNumeric,
+    /// the number has fewer than 12 or more than 19 digits
+    InvalidLength(usize),
+    /// the brand of the number issues no numbers of this many digits,
+    /// see `validate_credit_card`
+    InvalidLengthForBrand(usize),
+    /// the check digit is wrong, e.g. two digits were swapped
+    LuhnCheckFailed,
+    /// the expiration month is not 1 to 12
+    InvalidExpirationMonth,
+    /// the expiration year is not 2 or 4 digits
+    InvalidExpirationYear,
+    /// the card expired before the current month, only with
+    /// `with_reject_expired`
+    Expired,
+    /// the routing number is not 9 digits or fails the ABA checksum
+    InvalidRoutingNumber,
+    /// the bank account number is not 4 to 17 digits
+    InvalidAccountNumber,
+    /// custom metadata has too many entries, an empty key or a key or
+    /// value that is too long, see `CustomMetadata`
+    InvalidMetadata,
+    /// the security code is not 3 or 4 digits
+    InvalidSecurityCode,
+    /// the time to live of a security code is zero or over
+    /// `MAX_CVV_TTL`
+    InvalidCvvTtl,
+    /// the data is not track 2, a card number, separator, expiry and
+    /// service code
+    InvalidTrackData,
+    /// the EMV payload is not hex encoded BER-TLV
+    InvalidEmvPayload,
+    /// the time to live of track data or an EMV payload is zero or
+    /// over `MAX_CARD_PRESENT_TTL`
+    InvalidCardPresentTtl,
+    /// a card nickname or art id is over `MAX_DISPLAY_INFO_LENGTH`
+    /// characters
+    InvalidDisplayInfo,
+    /// the cards given to `DataVault::merge` have different numbers
+    NotSameCard,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NotNumeric => write!(f, "number is not numeric"),
+            ValidationError::InvalidLength(length) => write!(f, "number has {} digits, not 12 to 19", length),
+            ValidationError::InvalidLengthForBrand(length) => write!(f, "number has {} digits, not a length of its brand", length),
+            ValidationError::LuhnCheckFailed => write!(f, "number fails the Luhn check"),
+            ValidationError::InvalidExpirationMonth => write!(f, "expiration_month is not 1 to 12"),
+            ValidationError::InvalidExpirationYear => write!(f, "expiration_year is not 2 or 4 digits"),
+            ValidationError::Expired => write!(f, "card is expired"),
+            ValidationError::InvalidRoutingNumber => write!(f, "routing_number fails the ABA checksum"),
+            ValidationError::InvalidAccountNumber => write!(f, "account_number is not 4 to 17 digits"),
+            ValidationError::InvalidMetadata => write!(f, "metadata is over 32 entries, 64 character keys or 512 character values"),
+            ValidationError::InvalidSecurityCode => write!(f, "security_code is not 3 or 4 digits"),
+            ValidationError::InvalidCvvTtl => write!(f, "security code ttl is zero or over 15 minutes"),
+            ValidationError::InvalidTrackData => write!(f, "data is not track 2"),
+            ValidationError::InvalidEmvPayload => write!(f, "tlv is not hex encoded BER-TLV"),
+            ValidationError::InvalidCardPresentTtl => write!(f, "card present data ttl is zero or over 5 minutes"),
+            ValidationError::InvalidDisplayInfo => write!(f, "nickname or art_id is over 64 characters"),
+            ValidationError::NotSameCard => write!(f, "tokens are not of the same card number"),
+        }
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// Whether the last digit of `number` is its Luhn (mod 10) check
+/// digit, `false` for anything but digits
+pub fn luhn_check(number: &str) -> bool {
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return false
+    }
+
+    let sum: u32 = number.bytes().rev()
+        .map(|b| u32::from(b - b'0'))
+        .enumerate()
+        .map(|(i, digit)| match i % 2 {
+            0 => digit,
+            _ if digit > 4 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// The reason `number` is not a card number: not 12 to 19 digits or
+/// failing the Luhn check
+///
+/// Applied by `store_credit_card`, `tokenize`,
+/// `store_credit_card_with_token` and `update_credit_card_if_version`
+/// unless disabled with `DATA_VAULT_VALIDATE_CARDS=false` or
+/// `with_card_validation(false)`.
+/// # example
+/// ```rust
+/// use data_vault::validation::{validate_number, ValidationError};
+///
+/// assert_eq!(validate_number("4111111111111111"), Ok(()));
+/// assert_eq!(validate_number("4111111111111112"), Err(ValidationError::LuhnCheckFailed));
+/// ```
+pub fn validate_number(number: &str) -> Result<(), ValidationError> {
+    if !number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ValidationError::NotNumeric)
+    }
+    if !(12..=19).contains(&number.len()) {
+        return Err(ValidationError::InvalidLength(number.len()))
+    }
+    if !luhn_check(number) {
+        return Err(ValidationError::LuhnCheckFailed)
+    }
+    Ok(())
+}
+
+/// `month` and `year` as the vault stores them, a 2 digit month and
+/// a 4 digit year, e.g. `01` and `2027` for `1` and `27`
+///
+/// 2 digit years are taken to be of the current century.
+/// # example
+/// ```rust
+/// use data_vault::validation::{normalize_expiry, ValidationError};
+///
+/// assert_eq!(normalize_expiry("1", "27"), Ok(("01".to_string(), "2027".to_string())));
+/// assert_eq!(normalize_expiry("13", "2027"), Err(ValidationError::InvalidExpirationMonth));
+/// ```
+pub fn normalize_expiry(month: &str, year: &str) -> Result<(String, String), ValidationError> {
+    let month = month.trim();
+    let month: u32 = match month.len() {
+        1 | 2 if month.bytes().all(|b| b.is_ascii_digit()) => month.parse().unwrap_or_default(),
+        _ => 0,
+    };
+    if !(1..=12).contains(&month) {
+        return Err(ValidationError::InvalidExpirationMonth)
+    }
+
+    let year = year.trim();
+    if !year.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ValidationError::InvalidExpirationYear)
+    }
+    let year = match year.len() {
+        2 => current_month(SystemTime::now()).0 / 100 * 100 + year.parse::<u32>().unwrap_or_default(),
+        4 => year.parse().unwrap_or_default(),
+        _ => return Err(ValidationError::InvalidExpirationYear),
+    };
+    Ok((format!("{:02}", month), year.to_string()))
+}
+
+/// Whether a card expiring in `month` of `year`, as `normalize_expiry`
+/// returns them, can no longer be used at `now`, cards are good until
+/// the end of their expiration month
+pub fn is_expired(month: &str, year: &str, now: SystemTime) -> bool {
+    match (month.parse::<u32>(), year.parse::<u32>()) {
+        (Ok(month), Ok(year)) => (year, month) < current_month(now),
+        _ => false,
+    }
+}
+
+/// The reason `routing_number` is not an ABA routing transit number:
+/// not 9 digits or failing its checksum, the digits weighted 3, 7
+/// and 1 in turn must add up to a multiple of 10
+/// # example
+/// ```rust
+/// use data_vault::validation::{validate_routing_number, ValidationError};
+///
+/// assert_eq!(validate_routing_number("011000015"), Ok(()));
+/// assert_eq!(validate_routing_number("011000016"), Err(ValidationError::InvalidRoutingNumber));
+/// ```
+pub fn validate_routing_number(routing_number: &str) -> Result<(), ValidationError> {
+    if routing_number.len() != 9 || !routing_number.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ValidationError::InvalidRoutingNumber)
+    }
+
+    let sum: u32 = routing_number.bytes()
+        .map(|b| u32::from(b - b'0'))
+        .zip([3, 7, 1].iter().cycle())
+        .map(|(digit, weight)| digit * weight)
+        .sum();
+    match sum.is_multiple_of(10) {
+        true => Ok(()),
+        false => Err(ValidationError::InvalidRoutingNumber),
+    }
+}
+
+/// The reason `account_number` is not a bank account number, 4 to
+/// 17 digits as an ACH entry holds them
+pub fn validate_account_number(account_number: &str) -> Result<(), ValidationError> {
+    match (4..=17).contains(&account_number.len()) && account_number.bytes().all(|b| b.is_ascii_digit()) {
+        true => Ok(()),
+        false => Err(ValidationError::InvalidAccountNumber),
+    }
+}
+
+// well known numbers of the test cards of the major schemes and
+// processors, accepted by sandboxes and declined in production
+const TEST_CARD_NUMBERS: [&str; 16] = [
+    "4111111111111111", "4242424242424242", "4012888888881881", "4000056655665556",
+    "4222222222222", "5555555555554444", "5105105105105100", "2223003122003222",
+    "378282246310005", "371449635398431", "378734493671000", "6011111111111117",
+    "6011000990139424", "3056930009020004", "36227206271667", "3566002020360505",
+];
+
+/// Every check `validate_credit_card` runs on a card, each failing
+/// one with the `ValidationError` it failed with, so a form can point
+/// at the field to fix before the card is sent to the vault
+///
+/// Serialized as `{"valid": false, "brand": "amex", "test_card":
+/// false, "luhn": {"passed": true}, "length": {"passed": false,
+/// "error": "number has 16 digits, not a length of its brand"},
+/// "expiry": {"passed": true}}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// the brand of the number, see `detect_brand`
+    pub brand: Option<CardBrand>,
+    /// digits only and a correct check digit
+    pub luhn: Result<(), ValidationError>,
+    /// a length the brand issues, 12 to 19 digits for an unknown brand
+    pub length: Result<(), ValidationError>,
+    /// a valid month and year that has not passed
+    pub expiry: Result<(), ValidationError>,
+    /// the number is a well known test card number, e.g.
+    /// `4242424242424242`, not a failure by itself
+    pub test_card: bool,
+}
+
+impl ValidationReport {
+    /// Whether the card passed every check, test cards included
+    pub fn is_valid(&self) -> bool {
+        self.errors().is_empty()
+    }
+
+    /// The errors of the failed checks, in the order of the fields
+    pub fn errors(&self) -> Vec<ValidationError> {
+        [self.luhn, self.length, self.expiry].iter()
+            .filter_map(|check| check.err())
+            .collect()
+    }
+}
+
+impl Serialize for ValidationReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Check {
+            passed: bool,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            error: Option<String>,
+        }
+        let check = |result: Result<(), ValidationError>| Check {
+            passed: result.is_ok(),
+            error: result.err().map(|e| e.to_string()),
+        };
+
+        let mut report = serializer.serialize_struct("ValidationReport", 6)?;
+        report.serialize_field("valid", &self.is_valid())?;
+        report.serialize_field("brand", &self.brand)?;
+        report.serialize_field("test_card", &self.test_card)?;
+        report.serialize_field("luhn", &check(self.luhn))?;
+        report.serialize_field("length", &check(self.length))?;
+        report.serialize_field("expiry", &check(self.expiry))?;
+        report.end()
+    }
+}
+
+/// Every check on `credit_card` at once, unlike `validate_number`
+/// that stops at the first failing one
+///
+/// The number is normalized first, see `normalize_number`.  The
+/// length is checked against the lengths of the brand of the number,
+/// which is stricter than the 12 to 19 digits the vault requires
+/// when storing, and the expiry against the current month.
+/// # example
+/// ```rust
+/// use credit_card::CreditCard;
+/// use data_vault::{validate_credit_card, CardBrand, ValidationError};
+///
+/// let report = validate_credit_card(&CreditCard {
+///     number: "3782 8224 6310 0051".to_string(),
+///     cardholder_name: "Graydon Hoare".to_string(),
+///     expiration_month: "13".to_string(),
+///     expiration_year: "2099".to_string(),
+///     brand: None,
+///     security_code: None
+/// });
+/// assert_eq!(report.brand, Some(CardBrand::Amex));
+/// assert_eq!(report.errors(), vec![
+///     ValidationError::LuhnCheckFailed,
+///     ValidationError::InvalidLengthForBrand(16),
+///     ValidationError::InvalidExpirationMonth,
+/// ]);
+/// ```
+pub fn validate_credit_card(credit_card: &CreditCard) -> ValidationReport {
+    let number = normalize_number(&credit_card.number);
+    let numeric = !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit());
+    let brand = detect_brand(&number);
+
+    let luhn = match (numeric, luhn_check(&number)) {
+        (false, _) => Err(ValidationError::NotNumeric),
+        (true, false) => Err(ValidationError::LuhnCheckFailed),
+        (true, true) => Ok(()),
+    };
+    let length = match brand {
+        _ if !numeric => Err(ValidationError::NotNumeric),
+        Some(brand) if !brand.is_valid_length(number.len()) => Err(ValidationError::InvalidLengthForBrand(number.len())),
+        None if !(12..=19).contains(&number.len()) => Err(ValidationError::InvalidLength(number.len())),
+        _ => Ok(()),
+    };
+    let expiry = normalize_expiry(&credit_card.expiration_month, &credit_card.expiration_year)
+        .and_then(|(month, year)| match is_expired(&month, &year, SystemTime::now()) {
+            true => Err(ValidationError::Expired),
+            false => Ok(()),
+        });
+
+    ValidationReport {
+        brand,
+        luhn,
+        length,
+        expiry,
+        test_card: TEST_CARD_NUMBERS.contains(&&*number),
+    }
+}
+
+/// The longest a security code is kept by `DataVault::store_cvv`,
+/// long enough for an authorization
+pub const MAX_CVV_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// A security code of 3 or 4 digits kept for a time to live of at
+/// most `MAX_CVV_TTL`
+pub(crate) fn validate_cvv(cvv: &str, ttl: Duration) -> Result<(), ValidationError> {
+    if !(3..=4).contains(&cvv.len()) || !cvv.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ValidationError::InvalidSecurityCode)
+    }
+    if ttl.is_zero() || ttl > MAX_CVV_TTL {
+        return Err(ValidationError::InvalidCvvTtl)
+    }
+    Ok(())
+}
+
+/// The card `credit_card` is stored as, its number, name and expiry
+/// normalized
+/// returns:
+///     * the first `ValidationError` of its number and expiry, and
+///       `ValidationError::Expired` with `reject_expired`
+#[cfg_attr(not(any(feature = "redis", feature = "postgres")), allow(dead_code))]
+pub(crate) fn validate_card(credit_card: &CreditCard, reject_expired: bool) -> Result<Cow<'_, CreditCard>, ValidationError> {
+    let credit_card = normalize_card(credit_card);
+    validate_number(&credit_card.number)?;
+    let (month, year) = normalize_expiry(&credit_card.expiration_month, &credit_card.expiration_year)?;
+    if reject_expired && is_expired(&month, &year, SystemTime::now()) {
+        return Err(ValidationError::Expired)
+    }
+
+    if month == credit_card.expiration_month && year == credit_card.expiration_year {
+        return Ok(credit_card)
+    }
+    let mut credit_card = credit_card.into_owned();
+    credit_card.expiration_month = month;
+    credit_card.expiration_year = year;
+    Ok(Cow::Owned(credit_card))
+}
+
+/// `credit_card` with its number and cardholder name normalized, see
+/// `normalize_number` and `normalize_name`, also when card validation
+/// is turned off
+#[cfg_attr(not(any(feature = "redis", feature = "postgres")), allow(dead_code))]
+pub(crate) fn normalize_card(credit_card: &CreditCard) -> Cow<'_, CreditCard> {
+    let number = normalize_number(&credit_card.number);
+    let cardholder_name = normalize_name(&credit_card.cardholder_name);
+    if let (Cow::Borrowed(_), Cow::Borrowed(_)) = (&number, &cardholder_name) {
+        return Cow::Borrowed(credit_card)
+    }
+    Cow::Owned(CreditCard {
+        number: number.into_owned(),
+        cardholder_name: cardholder_name.into_owned(),
+        ..credit_card.clone()
+    })
+}
+
+/// `number` as the vault stores it, without the spaces and hyphens
+/// it is often typed with and with digits of other scripts, e.g. the
+/// fullwidth `４`, as ASCII digits
+///
+/// The same card typed either way gets the same token from a
+/// deterministic tokenizer.
+/// # example
+/// ```rust
+/// use data_vault::validation::normalize_number;
+///
+/// assert_eq!(normalize_number("4111 1111-1111 1111"), "4111111111111111");
+/// assert_eq!(normalize_number("４１１１１１１１１１１１１１１１"), "4111111111111111");
+/// ```
+pub fn normalize_number(number: &str) -> Cow<'_, str> {
+    if number.bytes().all(|b| b.is_ascii_digit()) {
+        return Cow::Borrowed(number)
+    }
+    number.chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '\u{2010}'..='\u{2015}'))
+        .map(|c| ascii_digit(c).unwrap_or(c))
+        .collect()
+}
+
+/// `name` trimmed, with every run of whitespace inside as one space
+pub fn normalize_name(name: &str) -> Cow<'_, str> {
+    let normalized = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+    match normalized == name {
+        true => Cow::Borrowed(name),
+        false => Cow::Owned(normalized),
+    }
+}
+
+// the zero of each block of ten unicode decimal digits in scripts
+// card numbers are written in
+const UNICODE_ZEROS: [u32; 21] = [
+    0x0660, 0x06F0, 0x07C0, 0x0966, 0x09E6, 0x0A66, 0x0AE6, 0x0B66, 0x0BE6, 0x0C66, 0x0CE6,
+    0x0D66, 0x0DE6, 0x0E50, 0x0ED0, 0x0F20, 0x1040, 0x1090, 0x17E0, 0x1810, 0xFF10,
+];
+
+/// The ASCII digit of the unicode decimal digit `c`
+fn ascii_digit(c: char) -> Option<char> {
+    let c = u32::from(c);
+    UNICODE_ZEROS.iter()
+        .find(|zero| (**zero..**zero + 10).contains(&c))
+        .and_then(|zero| char::from_digit(c - zero, 10))
+}
+
+/// The UTC year and month of `now`
+fn current_month(now: SystemTime) -> (u32, u32) {
+    let days = now.duration_since(UNIX_EPOCH).map(|since| since.as_secs() / 86400).unwrap_or_default() as i64;
+    // the civil calendar from days since 1970-01-01, by Howard Hinnant
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year as u32, month as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use credit_card::CreditCard;
+    use crate::validation::{current_month, is_expired, luhn_check, normalize_expiry, validate_account_number, normalize_name, normalize_number, validate_card, validate_credit_card, validate_cvv, validate_number, validate_routing_number, ValidationError, MAX_CVV_TTL};
+    use crate::brand::CardBrand;
+    use std::borrow::Cow;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_validate_number() {
+        for number in ["4111111111111111", "5555555555554444", "378282246310005", "6011111111111117", "4222222222222"] {
+            assert!(luhn_check(number), "{}", number);
+            assert_eq!(validate_number(number), Ok(()));
+        }
+
+        assert!(!luhn_check(""));
+        assert!(!luhn_check("4111 1111 1111 1111"));
+        // swapped digits
+        assert_eq!(validate_number("4111111111111121"), Err(ValidationError::LuhnCheckFailed));
+        assert_eq!(validate_number("4111-1111-1111-1111"), Err(ValidationError::NotNumeric));
+        assert_eq!(validate_number(""), Err(ValidationError::InvalidLength(0)));
+        assert_eq!(validate_number("42424242424"), Err(ValidationError::InvalidLength(11)));
+        assert_eq!(validate_number("42424242424242424242"), Err(ValidationError::InvalidLength(20)));
+    }
+
+    #[test]
+    fn test_validate_bank_account() {
+        for routing_number in ["011000015", "021000021", "111000025", "122105155"] {
+            assert_eq!(validate_routing_number(routing_number), Ok(()), "{}", routing_number);
+        }
+        // swapped digits
+        assert_eq!(validate_routing_number("012000015"), Err(ValidationError::InvalidRoutingNumber));
+        assert_eq!(validate_routing_number("01100001"), Err(ValidationError::InvalidRoutingNumber));
+        assert_eq!(validate_routing_number("01100001a"), Err(ValidationError::InvalidRoutingNumber));
+
+        assert_eq!(validate_account_number("1234"), Ok(()));
+        assert_eq!(validate_account_number("12345678901234567"), Ok(()));
+        assert_eq!(validate_account_number("123"), Err(ValidationError::InvalidAccountNumber));
+        assert_eq!(validate_account_number("123456789012345678"), Err(ValidationError::InvalidAccountNumber));
+        assert_eq!(validate_account_number("1234-5678"), Err(ValidationError::InvalidAccountNumber));
+    }
+
+    #[test]
+    fn test_expiry() {
+        // 2024-02-29 and 2000-01-01
+        assert_eq!(current_month(UNIX_EPOCH + Duration::from_secs(1709164800)), (2024, 2));
+        assert_eq!(current_month(UNIX_EPOCH + Duration::from_secs(946684800)), (2000, 1));
+        assert_eq!(current_month(UNIX_EPOCH), (1970, 1));
+
+        let century = current_month(SystemTime::now()).0 / 100 * 100;
+        assert_eq!(normalize_expiry("1", "27"), Ok(("01".to_string(), (century + 27).to_string())));
+        assert_eq!(normalize_expiry(" 12", "2031 "), Ok(("12".to_string(), "2031".to_string())));
+        assert_eq!(normalize_expiry("13", "2023"), Err(ValidationError::InvalidExpirationMonth));
+        assert_eq!(normalize_expiry("0", "2023"), Err(ValidationError::InvalidExpirationMonth));
+        assert_eq!(normalize_expiry("+1", "2023"), Err(ValidationError::InvalidExpirationMonth));
+        assert_eq!(normalize_expiry("jan", "2023"), Err(ValidationError::InvalidExpirationMonth));
+        assert_eq!(normalize_expiry("01", "\u{201d}23\u{201d}"), Err(ValidationError::InvalidExpirationYear));
+        assert_eq!(normalize_expiry("01", "202"), Err(ValidationError::InvalidExpirationYear));
+
+        let now = UNIX_EPOCH + Duration::from_secs(1709164800);
+        assert!(is_expired("01", "2024", now));
+        assert!(is_expired("12", "2023", now));
+        // good until the end of the month
+        assert!(!is_expired("02", "2024", now));
+        assert!(!is_expired("01", "2025", now));
+
+        let mut cc = CreditCard {
+            number: "4111111111111111".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "01".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        assert!(matches!(validate_card(&cc, false), Ok(Cow::Borrowed(_))));
+        assert_eq!(validate_card(&cc, true).unwrap_err(), ValidationError::Expired);
+        cc.expiration_month = "1".to_string();
+        assert_eq!(validate_card(&cc, false).unwrap().expiration_month, "01");
+    }
+
+    #[test]
+    fn test_validate_cvv() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(validate_cvv("123", minute), Ok(()));
+        assert_eq!(validate_cvv("1234", MAX_CVV_TTL), Ok(()));
+        for cvv in ["12", "12345", "12a", ""].iter() {
+            assert_eq!(validate_cvv(cvv, minute), Err(ValidationError::InvalidSecurityCode));
+        }
+        assert_eq!(validate_cvv("123", Duration::ZERO), Err(ValidationError::InvalidCvvTtl));
+        assert_eq!(validate_cvv("123", MAX_CVV_TTL + Duration::from_secs(1)), Err(ValidationError::InvalidCvvTtl));
+    }
+
+    #[test]
+    fn test_normalize_card() {
+        assert!(matches!(normalize_number("4111111111111111"), Cow::Borrowed(_)));
+        for number in ["4111 1111 1111 1111", "4111-1111-1111-1111", " 4111\t1111\u{a0}1111\u{2013}1111 ",
+                       "\u{0664}\u{0661}\u{0661}\u{0661}111111111111", "\u{FF14}111111111111111"].iter() {
+            assert_eq!(normalize_number(number), "4111111111111111", "{}", number);
+        }
+        assert_eq!(normalize_number("4111 1111 x"), "41111111x");
+        assert_eq!(normalize_name("  Graydon \t Hoare\n"), "Graydon Hoare");
+        assert!(matches!(normalize_name("Graydon Hoare"), Cow::Borrowed(_)));
+
+        let cc = CreditCard {
+            number: "4111 1111 1111 1111".to_string(),
+            cardholder_name: "Graydon  Hoare".to_string(),
+            expiration_month: "1".to_string(),
+            expiration_year: "2023".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let normalized = validate_card(&cc, false).unwrap();
+        assert_eq!(normalized.number, "4111111111111111");
+        assert_eq!(normalized.cardholder_name, "Graydon Hoare");
+        assert_eq!(normalized.expiration_month, "01");
+        let invalid = CreditCard { number: "4111 1111 1111 111x".to_string(), ..cc };
+        assert_eq!(validate_card(&invalid, false).unwrap_err(), ValidationError::NotNumeric);
+    }
+
+    #[test]
+    fn test_validate_credit_card() {
+        let mut cc = CreditCard {
+            number: "4242 4242 4242 4242".to_string(),
+            cardholder_name: "Graydon Hoare".to_string(),
+            expiration_month: "1".to_string(),
+            expiration_year: "2099".to_string(),
+            brand: None,
+            security_code: None
+        };
+        let report = validate_credit_card(&cc);
+        assert!(report.is_valid());
+        assert!(report.test_card);
+        assert_eq!(report.brand, Some(CardBrand::Visa));
+
+        // valid for any brand but no Mastercard has 19 digits
+        cc.number = "5555555555555555556".to_string();
+        cc.expiration_year = "2020".to_string();
+        let report = validate_credit_card(&cc);
+        assert!(!report.test_card);
+        assert_eq!(report.luhn, Ok(()));
+        assert_eq!(report.errors(), vec![ValidationError::InvalidLengthForBrand(19), ValidationError::Expired]);
+        let json = serde_json::to_value(report).unwrap();
+        assert_eq!(json["valid"], false);
+        assert_eq!(json["brand"], "mastercard");
+        assert_eq!(json["luhn"], serde_json::json!({"passed": true}));
+        assert_eq!(json["length"]["error"], "number has 19 digits, not a length of its brand");
+        assert_eq!(json["expiry"]["error"], "card is expired");
+
+        cc.number = "9999 99x".to_string();
+        cc.expiration_month = "0".to_string();
+        let report = validate_credit_card(&cc);
+        assert_eq!(report.brand, None);
+        assert_eq!(report.errors(), vec![ValidationError::NotNumeric, ValidationError::NotNumeric, ValidationError::InvalidExpirationMonth]);
+        cc.number = "99999999995".to_string();
+        assert_eq!(validate_credit_card(&cc).length, Err(ValidationError::InvalidLength(11)));
+    }
+}
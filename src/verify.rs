@@ -0,0 +1,115 @@
+use credit_card::CreditCard;
+use crate::encryption::traits::Encryption;
+use crate::serializer::Serializer;
+use crate::compression::CompressionAlgo;
+use crate::namespace::validate_token;
+use crate::redact::redact_token;
+use std::fmt;
+
+/// What `verify` found wrong with a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordProblem {
+    /// the ciphertext is damaged or was not sealed with the vault's key
+    Undecryptable,
+    /// the record decrypts but is neither a card in the vault's
+    /// record format nor text, or does not decompress
+    Unreadable,
+    /// the token is not 1 to 64 ASCII letters, digits, `-`, `_` and `.`
+    InvalidToken,
+    /// the index lists the token but the record is gone, and not
+    /// because it expired, redis only
+    Orphaned,
+    /// the columns next to the record contradict it or each other,
+    /// postgres only
+    Metadata(&'static str),
+}
+
+/// A record `verify` found a problem with
+#[derive(Clone, PartialEq)]
+pub struct RecordIssue {
+    pub token: String,
+    pub problem: RecordProblem,
+}
+
+impl fmt::Debug for RecordIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordIssue")
+            .field("token", &redact_token(&self.token))
+            .field("problem", &self.problem)
+            .finish()
+    }
+}
+
+/// The outcome of `verify`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    /// records and index entries checked
+    pub checked: u64,
+    /// the records with a problem, in the order they were checked
+    pub issues: Vec<RecordIssue>,
+}
+
+impl VerifyReport {
+    /// Whether every record checked out
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub(crate) fn check(&mut self, token: &str, problem: Option<RecordProblem>) {
+        self.checked += 1;
+        if let Some(problem) = problem {
+            self.issues.push(RecordIssue {
+                token: token.to_string(),
+                problem,
+            });
+        }
+    }
+}
+
+/// The problem with the record sealed in `ciphertext` under `token`,
+/// if any, the decrypted card is returned for further checks
+pub(crate) fn check_record<E, S>(encryption: &E, serializer: &S, token: &str, ciphertext: &[u8]) -> Result<Option<CreditCard>, RecordProblem>
+    where
+        E: Encryption,
+        S: Serializer,
+{
+    validate_token(token).map_err(|_| RecordProblem::InvalidToken)?;
+    let record = encryption.try_decrypt_bytes(ciphertext).ok_or(RecordProblem::Undecryptable)?;
+    let record = CompressionAlgo::decompress(&record).map_err(|_| RecordProblem::Unreadable)?;
+    // records of `store` are text
+    match serializer.deserialize::<CreditCard>(&record) {
+        Ok(credit_card) => Ok(Some(credit_card)),
+        Err(_) if std::str::from_utf8(&record).is_ok() => Ok(None),
+        Err(_) => Err(RecordProblem::Unreadable),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::verify::{check_record, RecordProblem, VerifyReport};
+    use crate::encryption::traits::Encryption;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::serializer::{JsonSerializer, Serializer};
+
+    #[test]
+    fn test_check_record() {
+        let encryption = AesGcmSivEncryption::new();
+        let serializer = JsonSerializer::new();
+        let card = encryption.encrypt_string(r#"{"number":"4111111111111111","cardholder_name":"Graydon Hoare","expiration_month":"01","expiration_year":"2023","brand":null,"security_code":null}"#);
+        assert_eq!(check_record(&encryption, &serializer, "abc1", &card).unwrap().unwrap().number, "4111111111111111");
+        assert!(check_record(&encryption, &serializer, "abc1", &encryption.encrypt_string("{number: 123}")).unwrap().is_none());
+
+        let problem = |token: &str, ciphertext: &[u8]| check_record(&encryption, &serializer, token, ciphertext).err();
+        assert_eq!(problem("abc1", &encryption.encrypt(&[0xff, 0xfe])), Some(RecordProblem::Unreadable));
+        assert_eq!(problem("abc1", &card[1..]), Some(RecordProblem::Undecryptable));
+        assert_eq!(problem("abc 1", &card), Some(RecordProblem::InvalidToken));
+
+        let mut report = VerifyReport::default();
+        report.check("abc1", None);
+        assert!(report.is_ok());
+        report.check("5f4dcc3b5aa765d61d8327deb882cf99", Some(RecordProblem::Orphaned));
+        assert_eq!(report.checked, 2);
+        assert!(!report.is_ok());
+        assert!(format!("{:?}", report).contains("5f4d****cf99"));
+    }
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use crate::record::VaultRecord;
+use crate::redact::redact;
+use std::fmt;
+use std::time::Duration;
+
+/// How long a `WalletPayload` is kept, its cryptogram is good for
+/// one authorization shortly after the cardholder paid
+pub const WALLET_PAYLOAD_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// The wallet a payload was decrypted from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wallet {
+    ApplePay,
+    GooglePay,
+}
+
+/// The decrypted payment data of an Apple Pay or Google Pay token,
+/// kept until the payment it was made for is authorized
+///
+/// Stored with `DataVault::store_record`, which lets the record
+/// expire after `WALLET_PAYLOAD_TTL`.  `Debug` masks the device
+/// account number and leaves out the cryptogram.
+/// # example
+/// ```rust,ignore
+/// use data_vault::{DataVault, RedisDataVault, Wallet, WalletPayload};
+/// use data_vault::encryption::AesGcmSivEncryption;
+/// use data_vault::tokenizer::Blake3Tokenizer;
+///
+/// let data_vault = RedisDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap();
+/// let payload = WalletPayload {
+///     wallet: Wallet::ApplePay,
+///     device_pan: "4895370012345678".to_string(),
+///     expiration_month: "08".to_string(),
+///     expiration_year: "2029".to_string(),
+///     cryptogram: "AgAAAAAABk4DWZ4C28yUQAAAAAA=".to_string(),
+///     eci: Some("07".to_string()),
+///     cryptogram_type: Some("3DSecure".to_string()),
+/// };
+/// let token = data_vault.store_record(&payload).await.unwrap();
+/// let payload: WalletPayload = data_vault.retrieve_record(&token).await.unwrap();
+/// ```
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletPayload {
+    pub wallet: Wallet,
+    /// the device account number, a network token (DPAN)
+    pub device_pan: String,
+    pub expiration_month: String,
+    pub expiration_year: String,
+    /// the online payment cryptogram, e.g. a TAVV, base64 encoded
+    pub cryptogram: String,
+    /// the electronic commerce indicator, absent from some EMV payloads
+    #[serde(default)]
+    pub eci: Option<String>,
+    /// the kind of cryptogram, e.g. `3DSecure` or `EMV` for Apple Pay
+    /// or `CRYPTOGRAM_3DS` for Google Pay
+    #[serde(default)]
+    pub cryptogram_type: Option<String>,
+}
+
+impl VaultRecord for WalletPayload {
+    fn ttl(&self) -> Option<Duration> {
+        Some(WALLET_PAYLOAD_TTL)
+    }
+}
+
+impl fmt::Debug for WalletPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalletPayload")
+            .field("wallet", &self.wallet)
+            .field("device_pan", &redact(&self.device_pan))
+            .field("expiration_month", &self.expiration_month)
+            .field("expiration_year", &self.expiration_year)
+            .field("eci", &self.eci)
+            .field("cryptogram_type", &self.cryptogram_type)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "postgres", feature = "rt-tokio"))]
+mod test {
+    use crate::wallet::{Wallet, WalletPayload, WALLET_PAYLOAD_TTL};
+    use crate::traits::DataVault;
+    use crate::encryption::AesGcmSivEncryption;
+    use crate::postgres_data_vault::PostgresDataVault;
+    use crate::tokenizer::Blake3Tokenizer;
+    use crate::record::VaultRecord;
+    use serde::{Deserialize, Serialize};
+    use std::time::Duration;
+
+    #[derive(Serialize, Deserialize)]
+    struct Expired(WalletPayload);
+
+    impl VaultRecord for Expired {
+        fn ttl(&self) -> Option<Duration> {
+            Some(Duration::ZERO)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wallet_payload() {
+        let vault = PostgresDataVault::<AesGcmSivEncryption, Blake3Tokenizer>::new().unwrap()
+            .with_namespace("wallet-test").unwrap();
+        let payload = WalletPayload {
+            wallet: Wallet::GooglePay,
+            device_pan: "4895370012345678".to_string(),
+            expiration_month: "08".to_string(),
+            expiration_year: "2029".to_string(),
+            cryptogram: "AgAAAAAABk4DWZ4C28yUQAAAAAA=".to_string(),
+            eci: Some("05".to_string()),
+            cryptogram_type: Some("CRYPTOGRAM_3DS".to_string()),
+        };
+        let debug = format!("{:?}", payload);
+        assert!(!debug.contains("4895370012345678"));
+        assert!(!debug.contains("AgAAAAAABk4DWZ4C28yUQAAAAAA="));
+
+        let token = vault.store_record(&payload).await.unwrap();
+        let stored: WalletPayload = vault.retrieve_record(&token).await.unwrap();
+        assert_eq!(stored, payload);
+
+        assert_eq!(payload.ttl(), Some(WALLET_PAYLOAD_TTL));
+        vault.delete_many(&[token]).await.unwrap();
+
+        let token = vault.store_record(&Expired(payload)).await.unwrap();
+        assert!(!vault.exists(&token).await.unwrap());
+    }
+}